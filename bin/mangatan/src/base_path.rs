@@ -0,0 +1,49 @@
+use std::str::FromStr;
+
+use axum::Router;
+
+/// Where a reverse proxy mounts the web UI (e.g. `/manga` behind nginx or Traefik), without a
+/// trailing slash; empty when it's served at the root.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BasePath(String);
+
+impl FromStr for BasePath {
+    type Err = String;
+
+    /// Accepts `manga`, `/manga` or `/manga/`; empty or `/` is the root.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let trimmed = value.trim().trim_matches('/');
+        if trimmed.is_empty() {
+            return Ok(Self::default());
+        }
+        let valid = trimmed.split('/').all(|segment| {
+            !matches!(segment, "" | "." | "..")
+                && !segment.contains(|c: char| {
+                    c.is_whitespace() || matches!(c, '?' | '#' | '"' | '<' | '>' | '\\')
+                })
+        });
+        match valid {
+            true => Ok(Self(format!("/{trimmed}"))),
+            false => Err(format!("{value:?} is not a usable URL path")),
+        }
+    }
+}
+
+impl BasePath {
+    /// The `<base href>` injected into `index.html`, which the web UI resolves its assets and
+    /// API calls against.
+    pub fn href(&self) -> String {
+        format!("{}/", self.0)
+    }
+
+    /// Serves `app` under the base path, and still at the root for proxies that strip the
+    /// prefix before forwarding.
+    pub fn mount(&self, app: Router) -> Router {
+        match self.0.is_empty() {
+            true => app,
+            false => Router::new()
+                .nest_service(&self.0, app.clone())
+                .fallback_service(app),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::{fs, path::Path, process::Command};
+
+use mangatan_core::data_dir;
+
+use crate::{FrontendAssets, JAR_BYTES, extract_assets, io::resolve_java};
+
+/// Result of one `mangatan check` step: a short detail on success, the reason on failure.
+struct CheckResult {
+    name: &'static str,
+    outcome: Result<String, String>,
+}
+
+/// Runs the launcher's dependency resolution (assets, Java, web UI) without starting anything,
+/// prints one line per step and returns the process exit code: 0 when Mangatan can start.
+pub fn run(data_dir: &Path) -> i32 {
+    let results = [
+        CheckResult {
+            name: "data dir",
+            outcome: data_dir::check_writable(data_dir)
+                .map(|()| "writable".to_string())
+                .map_err(|err| format!("{} is not writable: {err}", data_dir.display())),
+        },
+        CheckResult {
+            name: "assets",
+            outcome: check_assets(data_dir),
+        },
+        CheckResult {
+            name: "java",
+            outcome: check_java(data_dir),
+        },
+        CheckResult {
+            name: "web ui",
+            outcome: match FrontendAssets::get("index.html") {
+                Some(_) => Ok(format!("{} embedded files", FrontendAssets::iter().count())),
+                None => Err("index.html is missing from this build".to_string()),
+            },
+        },
+    ];
+
+    println!("Mangatan {} ({})", crate::APP_VERSION, data_dir.display());
+    for result in &results {
+        match &result.outcome {
+            Ok(detail) => println!("  ok    {:<8} {detail}", result.name),
+            Err(reason) => println!("  FAIL  {:<8} {reason}", result.name),
+        }
+    }
+
+    let failed = results.iter().filter(|r| r.outcome.is_err()).count();
+    match failed {
+        0 => {
+            println!("Ready to start.");
+            0
+        }
+        n => {
+            println!("{n} check(s) failed.");
+            1
+        }
+    }
+}
+
+/// Extracts the bundled assets like a normal start would, then verifies the jar on disk matches
+/// the one in this binary.
+fn check_assets(data_dir: &Path) -> Result<String, String> {
+    let jar_rel_path = extract_assets(data_dir).map_err(|err| err.to_string())?;
+    let jar_path = data_dir.join(&jar_rel_path);
+    let on_disk =
+        fs::read(&jar_path).map_err(|err| format!("Cannot read {}: {err}", jar_path.display()))?;
+    if on_disk != JAR_BYTES {
+        return Err(format!(
+            "{} does not match the bundled jar ({} of {} bytes)",
+            jar_path.display(),
+            on_disk.len(),
+            JAR_BYTES.len()
+        ));
+    }
+    Ok(format!(
+        "{} ({:.1} MB)",
+        jar_rel_path.display(),
+        JAR_BYTES.len() as f64 / 1_000_000.0
+    ))
+}
+
+/// Resolves Java the way startup does and makes sure it actually runs.
+fn check_java(data_dir: &Path) -> Result<String, String> {
+    let java_exec =
+        resolve_java(data_dir).map_err(|err| format!("Failed to resolve java install: {err}"))?;
+    let java_home = java_exec
+        .parent()
+        .and_then(|p| p.parent())
+        .unwrap_or(data_dir);
+
+    let output = Command::new(&java_exec)
+        .env("JAVA_HOME", java_home)
+        .arg("-version")
+        .output()
+        .map_err(|err| format!("Cannot run {}: {err}", java_exec.display()))?;
+    // `java -version` prints to stderr
+    let version = String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    match output.status.success() {
+        true => Ok(format!("{} ({version})", java_exec.display())),
+        false => Err(format!(
+            "{} -version exited with {}: {version}",
+            java_exec.display(),
+            output.status
+        )),
+    }
+}
@@ -0,0 +1,314 @@
+//! What the data dir and Suwayomi's dir take up on disk, by category, for the GUI's disk usage
+//! breakdown. The walk runs on its own thread and can be cancelled.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::runtime::Handle;
+use tracing::{error, info};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    Downloads,
+    Database,
+    OcrCache,
+    Dictionaries,
+    Runtime,
+    Logs,
+    Other,
+}
+
+impl Category {
+    pub const ALL: [Category; 7] = [
+        Category::Downloads,
+        Category::Database,
+        Category::OcrCache,
+        Category::Dictionaries,
+        Category::Runtime,
+        Category::Logs,
+        Category::Other,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Category::Downloads => "Downloads",
+            Category::Database => "Library database",
+            Category::OcrCache => "OCR cache",
+            Category::Dictionaries => "Dictionaries",
+            Category::Runtime => "Java & Suwayomi",
+            Category::Logs => "Logs",
+            Category::Other => "Other",
+        }
+    }
+
+    /// Purge endpoints that free this category's space without losing anything that can't be
+    /// rebuilt. Empty when there's no safe way.
+    pub fn cleanup_endpoints(self) -> &'static [&'static str] {
+        match self {
+            // Pinned pages are kept; everything else is OCR'd again on demand
+            Category::OcrCache => &[
+                "/api/ocr/purge-cache?keep_pinned=true",
+                "/api/ocr/purge-image-cache",
+            ],
+            _ => &[],
+        }
+    }
+
+    /// Category of a top-level entry of the Mangatan data dir.
+    fn of_data_entry(name: &str) -> Self {
+        match name {
+            "ocr-cache.json"
+            | "ocr-cache"
+            | "ocr-image-cache"
+            | "ocr-pinned.json"
+            | "ocr-job-history.json"
+            | "ocr-quarantine.json" => Category::OcrCache,
+            "jre" | "bin" | "natives" => Category::Runtime,
+            "http-recording.ndjson" | "startup-timings.json" => Category::Logs,
+            // Includes the WAL and shared-memory files next to it
+            _ if name.starts_with("yomitan.db") => Category::Dictionaries,
+            _ if name.ends_with(".log") => Category::Logs,
+            _ => Category::Other,
+        }
+    }
+
+    /// Category of a top-level entry of Suwayomi's data dir.
+    fn of_suwayomi_entry(name: &str) -> Self {
+        match name {
+            "downloads" | "local" => Category::Downloads,
+            "logs" => Category::Logs,
+            "bin" => Category::Runtime,
+            _ if name.starts_with("database") => Category::Database,
+            _ => Category::Other,
+        }
+    }
+}
+
+/// Bytes in one category, and the folder "Open folder" shows for it.
+#[derive(Clone, Debug)]
+pub struct CategoryUsage {
+    pub category: Category,
+    pub bytes: u64,
+    pub folder: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Breakdown {
+    /// Every category, largest first.
+    pub categories: Vec<CategoryUsage>,
+    pub total_bytes: u64,
+    pub took: Duration,
+}
+
+/// Walks `data_dir` and `suwayomi_dir`, stopping early (with `None`) once `cancel` is set.
+pub fn analyze(
+    data_dir: &Path,
+    suwayomi_dir: Option<&Path>,
+    cancel: &AtomicBool,
+) -> Option<Breakdown> {
+    let started = Instant::now();
+    let mut categories: Vec<CategoryUsage> = Category::ALL
+        .iter()
+        .map(|&category| CategoryUsage {
+            category,
+            bytes: 0,
+            folder: None,
+        })
+        .collect();
+
+    let roots = [
+        Some((data_dir, Category::of_data_entry as fn(&str) -> Category)),
+        suwayomi_dir.map(|dir| (dir, Category::of_suwayomi_entry as fn(&str) -> Category)),
+    ];
+    for (root, categorize) in roots.into_iter().flatten() {
+        let Ok(entries) = fs::read_dir(root) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let category = categorize(&name.to_string_lossy());
+            let path = entry.path();
+            let bytes = size_of(&path, cancel)?;
+            let usage = categories
+                .iter_mut()
+                .find(|usage| usage.category == category)
+                .expect("every category is listed");
+            usage.bytes += bytes;
+            // A category's own folder when it has one, else the dir its files are in
+            if path.is_dir() && usage.folder.as_deref().is_none_or(|folder| folder == root) {
+                usage.folder = Some(path);
+            } else if usage.folder.is_none() {
+                usage.folder = Some(root.to_path_buf());
+            }
+        }
+    }
+
+    categories.sort_by_key(|usage| std::cmp::Reverse(usage.bytes));
+    Some(Breakdown {
+        total_bytes: categories.iter().map(|usage| usage.bytes).sum(),
+        categories,
+        took: started.elapsed(),
+    })
+}
+
+/// Size of a file, or of everything under a dir. Symlinks count as themselves and aren't
+/// followed.
+fn size_of(path: &Path, cancel: &AtomicBool) -> Option<u64> {
+    if cancel.load(Ordering::Relaxed) {
+        return None;
+    }
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return Some(0);
+    };
+    if !metadata.is_dir() {
+        return Some(metadata.len());
+    }
+    let Ok(entries) = fs::read_dir(path) else {
+        return Some(0);
+    };
+    let mut total = 0;
+    for entry in entries.flatten() {
+        total += size_of(&entry.path(), cancel)?;
+    }
+    Some(total)
+}
+
+#[derive(Clone, Default)]
+pub enum ScanState {
+    #[default]
+    Idle,
+    Running,
+    CleaningUp(Category),
+    Done(Breakdown),
+    Cancelled,
+}
+
+/// The GUI's handle on the background walk. The last breakdown stays until the next one.
+#[derive(Clone)]
+pub struct DiskUsageScan {
+    runtime: Handle,
+    data_dir: PathBuf,
+    suwayomi_dir: Option<PathBuf>,
+    state: Arc<Mutex<ScanState>>,
+    cancel: Arc<Mutex<Arc<AtomicBool>>>,
+}
+
+impl DiskUsageScan {
+    pub fn new(runtime: Handle, data_dir: PathBuf, suwayomi_dir: Option<PathBuf>) -> Self {
+        Self {
+            runtime,
+            data_dir,
+            suwayomi_dir,
+            state: Arc::new(Mutex::new(ScanState::Idle)),
+            cancel: Arc::new(Mutex::new(Arc::new(AtomicBool::new(false)))),
+        }
+    }
+
+    pub fn state(&self) -> ScanState {
+        self.state.lock().expect("lock shouldn't panic").clone()
+    }
+
+    /// Starts a walk unless one is already running; `ctx` is repainted when it's done.
+    pub fn start(&self, ctx: &eframe::egui::Context) {
+        {
+            let mut state = self.state.lock().expect("lock shouldn't panic");
+            if matches!(*state, ScanState::Running | ScanState::CleaningUp(_)) {
+                return;
+            }
+            *state = ScanState::Running;
+        }
+        let cancel = Arc::new(AtomicBool::new(false));
+        *self.cancel.lock().expect("lock shouldn't panic") = cancel.clone();
+
+        let scan = self.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            scan.run(&cancel);
+            ctx.request_repaint();
+        });
+    }
+
+    pub fn cancel(&self) {
+        self.cancel
+            .lock()
+            .expect("lock shouldn't panic")
+            .store(true, Ordering::Relaxed);
+    }
+
+    /// Frees `category` through its purge endpoints, then analyzes again.
+    pub fn clean_up(&self, category: Category, ctx: &eframe::egui::Context) {
+        {
+            let mut state = self.state.lock().expect("lock shouldn't panic");
+            if matches!(*state, ScanState::Running | ScanState::CleaningUp(_)) {
+                return;
+            }
+            *state = ScanState::CleaningUp(category);
+        }
+        let cancel = Arc::new(AtomicBool::new(false));
+        *self.cancel.lock().expect("lock shouldn't panic") = cancel.clone();
+
+        let scan = self.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            if let Err(err) = call_endpoints(&scan.runtime, category.cleanup_endpoints()) {
+                error!("❌ Failed to clean up {}: {err}", category.label());
+            }
+            scan.run(&cancel);
+            ctx.request_repaint();
+        });
+    }
+
+    fn run(&self, cancel: &AtomicBool) {
+        let result = analyze(&self.data_dir, self.suwayomi_dir.as_deref(), cancel);
+        let state = match result {
+            Some(breakdown) => {
+                info!(
+                    "💽 Disk usage: {} in {:.1}s",
+                    format_size(breakdown.total_bytes),
+                    breakdown.took.as_secs_f32()
+                );
+                ScanState::Done(breakdown)
+            }
+            None => ScanState::Cancelled,
+        };
+        *self.state.lock().expect("lock shouldn't panic") = state;
+    }
+}
+
+/// POSTs to the local server's purge endpoints, from a thread outside `runtime`.
+fn call_endpoints(runtime: &Handle, endpoints: &[&str]) -> anyhow::Result<()> {
+    runtime.block_on(async {
+        let client = reqwest::Client::new();
+        for endpoint in endpoints {
+            client
+                .post(format!("http://127.0.0.1:4568{endpoint}"))
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Ok(())
+    })
+}
+
+/// `1.2 GB`, `340 MB`, ...
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{bytes} B"),
+        _ if size < 10.0 => format!("{size:.1} {}", UNITS[unit]),
+        _ => format!("{size:.0} {}", UNITS[unit]),
+    }
+}
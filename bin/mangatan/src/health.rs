@@ -0,0 +1,166 @@
+use std::{
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use axum::{
+    Json,
+    extract::{Request, State},
+    http::{StatusCode, header::RETRY_AFTER},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+/// How often the Suwayomi probe runs once Suwayomi is up.
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(5);
+/// A successful probe older than this no longer counts as ready (a few missed probes).
+const PROBE_STALE_AFTER: Duration = Duration::from_secs(20);
+
+/// Answers whether a background worker has stalled.
+type StallCheck = Box<dyn Fn() -> bool + Send + Sync>;
+
+/// Readiness signals for `/readyz`, written by the Suwayomi probe and `run_server`.
+#[derive(Clone, Default)]
+pub struct Health {
+    suwayomi_ok_at: Arc<Mutex<Option<Instant>>>,
+    services_ready: Arc<AtomicBool>,
+    /// Why Suwayomi couldn't be started, or stopped on its own.
+    suwayomi_error: Arc<Mutex<Option<String>>>,
+    stall_checks: Arc<RwLock<Vec<(&'static str, StallCheck)>>>,
+}
+
+#[derive(Serialize)]
+struct Readiness {
+    ready: bool,
+    suwayomi: bool,
+    services: bool,
+    /// Seconds since Suwayomi last answered the probe.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suwayomi_last_ok_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suwayomi_error: Option<String>,
+    /// Background workers that have stalled, e.g. the OCR cache saver. They don't affect
+    /// `ready`, since requests are still served.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    degraded: Vec<&'static str>,
+}
+
+impl Health {
+    pub fn record_suwayomi_ok(&self) {
+        *self.suwayomi_ok_at.lock().expect("lock shouldn't panic") = Some(Instant::now());
+    }
+
+    /// Suwayomi has answered the probe at least once since launch.
+    pub fn suwayomi_started(&self) -> bool {
+        self.suwayomi_ok_at
+            .lock()
+            .expect("lock shouldn't panic")
+            .is_some()
+    }
+
+    /// Records why Suwayomi isn't running, for `/readyz` and the GUI. The web server keeps
+    /// serving everything else.
+    pub fn record_suwayomi_failed(&self, error: String) {
+        *self.suwayomi_error.lock().expect("lock shouldn't panic") = Some(error);
+    }
+
+    pub fn suwayomi_error(&self) -> Option<String> {
+        self.suwayomi_error
+            .lock()
+            .expect("lock shouldn't panic")
+            .clone()
+    }
+
+    /// Marks the OCR and Yomitan states as initialized.
+    pub fn set_services_ready(&self) {
+        self.services_ready.store(true, Ordering::Relaxed);
+    }
+
+    /// Reports `component` as degraded whenever `stalled` says so.
+    pub fn watch_stalls(
+        &self,
+        component: &'static str,
+        stalled: impl Fn() -> bool + Send + Sync + 'static,
+    ) {
+        self.stall_checks
+            .write()
+            .expect("lock shouldn't panic")
+            .push((component, Box::new(stalled)));
+    }
+
+    fn readiness(&self) -> Readiness {
+        let last_ok = self
+            .suwayomi_ok_at
+            .lock()
+            .expect("lock shouldn't panic")
+            .map(|at| at.elapsed());
+        let suwayomi = last_ok.is_some_and(|age| age <= PROBE_STALE_AFTER);
+        let services = self.services_ready.load(Ordering::Relaxed);
+        let degraded = self
+            .stall_checks
+            .read()
+            .expect("lock shouldn't panic")
+            .iter()
+            .filter(|(_, stalled)| stalled())
+            .map(|(component, _)| *component)
+            .collect();
+
+        Readiness {
+            ready: suwayomi && services,
+            suwayomi,
+            services,
+            suwayomi_last_ok_secs: last_ok.map(|age| age.as_secs()),
+            suwayomi_error: self.suwayomi_error(),
+            degraded,
+        }
+    }
+}
+
+/// Succeeds as soon as the web server is serving requests.
+pub async fn livez_handler() -> &'static str {
+    "ok"
+}
+
+/// Succeeds only while Suwayomi answers the probe and the OCR/Yomitan states are up.
+pub async fn readyz_handler(State(health): State<Health>) -> impl IntoResponse {
+    let readiness = health.readiness();
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness))
+}
+
+/// Answers with a 503 until Suwayomi first answers the probe, so clients of the proxied routes
+/// get a clear "starting up" instead of the 502s of a backend that isn't listening yet, or the
+/// reason it failed to start.
+pub async fn hold_until_suwayomi_started(
+    State(health): State<Health>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if health.suwayomi_started() {
+        return next.run(request).await;
+    }
+    if let Some(error) = health.suwayomi_error() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "status": "failed", "message": error })),
+        )
+            .into_response();
+    }
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(RETRY_AFTER, "2")],
+        Json(serde_json::json!({
+            "status": "starting",
+            "message": "Suwayomi is still starting up",
+        })),
+    )
+        .into_response()
+}
@@ -0,0 +1,254 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, anyhow};
+use directories::BaseDirs;
+use reqwest::Client;
+use tokio::process::Command;
+use tracing::{error, info, warn};
+
+const KIOSK_URL: &str = "http://localhost:4568";
+const READY_URL: &str = "http://127.0.0.1:4568/readyz";
+const PROFILE_DIR: &str = "kiosk-profile";
+/// A browser that lived at least this long before exiting is restarted right away.
+const STABLE_RUN: Duration = Duration::from_secs(30);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BrowserKind {
+    Chromium,
+    Firefox,
+}
+
+#[derive(Clone, Debug)]
+pub struct KioskBrowser {
+    path: PathBuf,
+    kind: BrowserKind,
+}
+
+impl KioskBrowser {
+    /// The configured browser, or the first Chromium-based browser or Firefox found.
+    pub fn resolve(configured: Option<&Path>) -> Option<Self> {
+        if let Some(path) = configured {
+            return Some(Self::from_path(path.to_path_buf()));
+        }
+        browser_candidates()
+            .into_iter()
+            .find(|path| path.is_file())
+            .map(Self::from_path)
+    }
+
+    fn from_path(path: PathBuf) -> Self {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        let kind = match name.contains("firefox") || name.contains("librewolf") {
+            true => BrowserKind::Firefox,
+            false => BrowserKind::Chromium,
+        };
+        Self { path, kind }
+    }
+
+    /// Fullscreen app window on a dedicated profile. The profile keeps the window in its own
+    /// process, so it doesn't just hand the URL to an already running browser and exit.
+    fn command(&self, profile_dir: &Path) -> Command {
+        let mut cmd = Command::new(&self.path);
+        match self.kind {
+            BrowserKind::Chromium => {
+                cmd.arg(format!("--app={KIOSK_URL}"))
+                    .arg("--start-fullscreen")
+                    .arg("--no-first-run")
+                    .arg(format!("--user-data-dir={}", profile_dir.display()));
+            }
+            BrowserKind::Firefox => {
+                cmd.arg("-kiosk")
+                    .arg("-no-remote")
+                    .arg("-profile")
+                    .arg(profile_dir)
+                    .arg(KIOSK_URL);
+            }
+        }
+        cmd.kill_on_drop(true);
+        cmd
+    }
+}
+
+/// Waits for the server to be ready, then keeps a fullscreen browser on the WebUI, restarting it
+/// whenever it exits. Runs until the task is dropped, which also closes the browser.
+pub async fn run(browser: Option<KioskBrowser>, data_dir: PathBuf) {
+    let client = Client::new();
+    wait_until_ready(&client).await;
+
+    let Some(browser) = browser else {
+        warn!("⚠️ [Kiosk] No supported browser found, opening the default one instead.");
+        if let Err(e) = open::that(KIOSK_URL) {
+            error!("❌ [Kiosk] Failed to open browser: {e}");
+        }
+        return;
+    };
+
+    let profile_dir = data_dir.join(PROFILE_DIR);
+    if let Err(e) = fs::create_dir_all(&profile_dir) {
+        error!("❌ [Kiosk] Failed to create browser profile dir: {e}");
+        return;
+    }
+
+    let mut restart_delay = Duration::from_secs(1);
+    loop {
+        info!("🖥️ [Kiosk] Starting {}", browser.path.display());
+        let started = Instant::now();
+        match browser.command(&profile_dir).spawn() {
+            Ok(mut child) => match child.wait().await {
+                Ok(status) => warn!("⚠️ [Kiosk] Browser exited ({status}), restarting..."),
+                Err(e) => error!("❌ [Kiosk] Lost track of the browser: {e}"),
+            },
+            Err(e) => error!("❌ [Kiosk] Failed to start {}: {e}", browser.path.display()),
+        }
+
+        // Back off when the browser keeps dying straight away
+        restart_delay = match started.elapsed() >= STABLE_RUN {
+            true => Duration::from_secs(1),
+            false => (restart_delay * 2).min(MAX_RESTART_DELAY),
+        };
+        tokio::time::sleep(restart_delay).await;
+        wait_until_ready(&client).await;
+    }
+}
+
+/// Polls `/readyz` until the server reports ready. No timeout: a cold boot on a small box can
+/// take a while to bring up the JVM.
+async fn wait_until_ready(client: &Client) {
+    let mut logged = false;
+    loop {
+        match client.get(READY_URL).send().await {
+            Ok(resp) if resp.status().is_success() => return,
+            _ if !logged => {
+                info!("⏳ [Kiosk] Waiting for the server to be ready...");
+                logged = true;
+            }
+            _ => {}
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+fn browser_candidates() -> Vec<PathBuf> {
+    let names: &[&str] = if cfg!(target_os = "windows") {
+        &["chrome.exe", "msedge.exe", "brave.exe", "firefox.exe"]
+    } else {
+        &[
+            "chromium",
+            "chromium-browser",
+            "google-chrome",
+            "google-chrome-stable",
+            "microsoft-edge",
+            "brave-browser",
+            "firefox",
+        ]
+    };
+    let mut candidates: Vec<PathBuf> = env::var_os("PATH")
+        .map(|path| {
+            env::split_paths(&path)
+                .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if cfg!(target_os = "windows") {
+        for base in ["ProgramFiles", "ProgramFiles(x86)", "LocalAppData"] {
+            if let Some(base) = env::var_os(base).map(PathBuf::from) {
+                candidates.push(base.join(r"Google\Chrome\Application\chrome.exe"));
+                candidates.push(base.join(r"Microsoft\Edge\Application\msedge.exe"));
+                candidates.push(base.join(r"Mozilla Firefox\firefox.exe"));
+            }
+        }
+    } else if cfg!(target_os = "macos") {
+        for app in [
+            "Google Chrome.app/Contents/MacOS/Google Chrome",
+            "Chromium.app/Contents/MacOS/Chromium",
+            "Microsoft Edge.app/Contents/MacOS/Microsoft Edge",
+            "Firefox.app/Contents/MacOS/firefox",
+        ] {
+            candidates.push(Path::new("/Applications").join(app));
+        }
+    }
+    candidates
+}
+
+/// Registers (or removes) a login item that starts `mangatan --kiosk`, so a TV box boots
+/// straight into the reader. Returns the file written or removed.
+pub fn set_autostart(enabled: bool, browser: Option<&Path>) -> anyhow::Result<PathBuf> {
+    let entry = autostart_path()?;
+    if !enabled {
+        if entry.exists() {
+            fs::remove_file(&entry).context("Failed to remove autostart entry")?;
+        }
+        return Ok(entry);
+    }
+
+    let exe = env::current_exe().context("Failed to locate the mangatan executable")?;
+    let mut args = vec!["--kiosk".to_string()];
+    if let Some(browser) = browser {
+        args.push("--kiosk-browser".to_string());
+        args.push(browser.display().to_string());
+    }
+
+    if let Some(dir) = entry.parent() {
+        fs::create_dir_all(dir).context("Failed to create autostart dir")?;
+    }
+    fs::write(&entry, autostart_entry(&exe, &args)).context("Failed to write autostart entry")?;
+    Ok(entry)
+}
+
+fn autostart_path() -> anyhow::Result<PathBuf> {
+    let dirs = BaseDirs::new().ok_or_else(|| anyhow!("Could not determine home directory"))?;
+    Ok(if cfg!(target_os = "windows") {
+        dirs.config_dir()
+            .join(r"Microsoft\Windows\Start Menu\Programs\Startup\Mangatan Kiosk.cmd")
+    } else if cfg!(target_os = "macos") {
+        dirs.home_dir()
+            .join("Library/LaunchAgents/app.mangatan.kiosk.plist")
+    } else {
+        dirs.config_dir().join("autostart/mangatan-kiosk.desktop")
+    })
+}
+
+fn autostart_entry(exe: &Path, args: &[String]) -> String {
+    let quoted = |arg: &str| format!("\"{arg}\"");
+    let exe = exe.display().to_string();
+    if cfg!(target_os = "windows") {
+        let args: Vec<String> = args.iter().map(|arg| quoted(arg)).collect();
+        format!(
+            "@echo off\r\nstart \"\" {} {}\r\n",
+            quoted(&exe),
+            args.join(" ")
+        )
+    } else if cfg!(target_os = "macos") {
+        let program: String = std::iter::once(&exe)
+            .chain(args)
+            .map(|arg| format!("        <string>{arg}</string>\n"))
+            .collect();
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \
+             \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+             <plist version=\"1.0\">\n<dict>\n\
+             \x20   <key>Label</key>\n    <string>app.mangatan.kiosk</string>\n\
+             \x20   <key>ProgramArguments</key>\n    <array>\n{program}    </array>\n\
+             \x20   <key>RunAtLoad</key>\n    <true/>\n\
+             </dict>\n</plist>\n"
+        )
+    } else {
+        let args: Vec<String> = args.iter().map(|arg| quoted(arg)).collect();
+        format!(
+            "[Desktop Entry]\nType=Application\nName=Mangatan Kiosk\nExec={} {}\n\
+             X-GNOME-Autostart-enabled=true\n",
+            quoted(&exe),
+            args.join(" ")
+        )
+    }
+}
@@ -0,0 +1,162 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{info, level_filters::LevelFilter};
+use tracing_subscriber::{
+    EnvFilter, Registry, fmt, layer::SubscriberExt, reload, util::SubscriberInitExt,
+};
+
+/// Target of the Suwayomi reverse proxy's logs.
+pub const PROXY_TARGET: &str = "mangatan::proxy";
+/// Target of the logs about the Suwayomi child process (spawn, readiness probe, exit).
+pub const SUWAYOMI_TARGET: &str = "mangatan::suwayomi";
+
+/// Components whose level can be set on their own, and the tracing target each one logs under.
+pub const COMPONENTS: &[(&str, &str)] = &[
+    ("proxy", PROXY_TARGET),
+    ("ocr", "mangatan_ocr_server"),
+    ("yomitan", "mangatan_yomitan_server"),
+    ("anki", "mangatan_yomitan_server::anki"),
+    ("suwayomi-child", SUWAYOMI_TARGET),
+];
+/// Pseudo-component for the directive without a target, i.e. everything not listed otherwise.
+pub const DEFAULT_COMPONENT: &str = "default";
+pub const LEVELS: &[&str] = &["off", "error", "warn", "info", "debug", "trace"];
+
+/// Handle on the tracing filter, so the log level can change without a restart (and without
+/// losing whatever state the bug needs to reproduce).
+#[derive(Clone)]
+pub struct LogLevel {
+    handle: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogLevel {
+    /// Installs the global subscriber with `filter` as its initial, reloadable filter.
+    pub fn init(filter: EnvFilter) -> Self {
+        let (filter, handle) = reload::Layer::new(filter);
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(fmt::layer())
+            .init();
+        Self { handle }
+    }
+
+    /// The effective filter, in `RUST_LOG` syntax.
+    pub fn current(&self) -> String {
+        self.handle
+            .with_current(|filter| filter.to_string())
+            .unwrap_or_default()
+    }
+
+    /// Replaces the whole filter. An invalid one is rejected and the active filter kept.
+    pub fn set_filter(&self, filter: &str) -> Result<String, String> {
+        let parsed = EnvFilter::builder()
+            .parse(filter)
+            .map_err(|e| format!("Invalid filter {filter:?}: {e}"))?;
+        self.handle.reload(parsed).map_err(|e| e.to_string())?;
+        let current = self.current();
+        info!("🔧 Log filter set to {current}");
+        Ok(current)
+    }
+
+    /// Sets the level of single components (see [`COMPONENTS`]), keeping the rest of the filter.
+    pub fn set_levels(&self, levels: &BTreeMap<String, String>) -> Result<String, String> {
+        let mut filter = self.current();
+        for (component, level) in levels {
+            let level = LevelFilter::from_str(level)
+                .map_err(|_| format!("Invalid level {level:?} for {component}"))?;
+            let target = component_target(component)
+                .ok_or_else(|| format!("Unknown component {component:?}"))?;
+            filter = with_directive(&filter, target, &level.to_string());
+        }
+        self.set_filter(&filter)
+    }
+
+    /// The level `component` currently logs at: its own directive, or else the default one.
+    pub fn level_of(&self, component: &str) -> String {
+        let filter = self.current();
+        let target = component_target(component).flatten();
+        let directive_level = |wanted: Option<&str>| {
+            filter
+                .split(',')
+                .find(|directive| directive_target(directive) == wanted)
+                .map(|directive| match directive.rsplit_once('=') {
+                    Some((_, level)) => level.to_string(),
+                    None => directive.to_string(),
+                })
+        };
+        target
+            .and_then(|target| directive_level(Some(target)))
+            .or_else(|| directive_level(None))
+            .unwrap_or_else(|| "error".to_string())
+    }
+}
+
+/// The target `component` logs under: `Some(None)` for the default directive, `None` when the
+/// name is unknown.
+fn component_target(component: &str) -> Option<Option<&'static str>> {
+    if component == DEFAULT_COMPONENT {
+        return Some(None);
+    }
+    COMPONENTS
+        .iter()
+        .find(|(name, _)| *name == component)
+        .map(|(_, target)| Some(*target))
+}
+
+/// The target a directive applies to; `None` for a bare level.
+fn directive_target(directive: &str) -> Option<&str> {
+    let (target, _) = directive.rsplit_once('=')?;
+    Some(target.split('[').next().unwrap_or(target))
+}
+
+/// `filter` with the directive for `target` replaced by (or extended with) `level`.
+fn with_directive(filter: &str, target: Option<&str>, level: &str) -> String {
+    let mut directives: Vec<String> = filter
+        .split(',')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty() && directive_target(directive) != target)
+        .map(str::to_string)
+        .collect();
+    directives.push(match target {
+        Some(target) => format!("{target}={level}"),
+        None => level.to_string(),
+    });
+    directives.join(",")
+}
+
+#[derive(Deserialize)]
+pub struct LogLevelRequest {
+    /// A full filter in `RUST_LOG` syntax, replacing the current one.
+    filter: Option<String>,
+    /// Component name (or `default`) to level, applied on top of the current filter.
+    #[serde(default)]
+    levels: BTreeMap<String, String>,
+}
+
+/// `GET /api/log-level`
+pub async fn get_handler(State(log_level): State<LogLevel>) -> impl IntoResponse {
+    Json(json!({
+        "filter": log_level.current(),
+        "components": COMPONENTS.iter().copied().collect::<BTreeMap<_, _>>(),
+    }))
+}
+
+/// `PUT /api/log-level` with either `{"filter": "..."}` or `{"levels": {"proxy": "debug"}}`.
+pub async fn put_handler(
+    State(log_level): State<LogLevel>,
+    Json(req): Json<LogLevelRequest>,
+) -> impl IntoResponse {
+    let result = match (req.filter, req.levels.is_empty()) {
+        (Some(filter), true) => log_level.set_filter(&filter),
+        (None, false) => log_level.set_levels(&req.levels),
+        (Some(_), false) => Err("Send either `filter` or `levels`, not both".to_string()),
+        (None, true) => Err("Missing `filter` or `levels`".to_string()),
+    };
+    match result {
+        Ok(filter) => (StatusCode::OK, Json(json!({ "filter": filter }))),
+        Err(error) => (StatusCode::BAD_REQUEST, Json(json!({ "error": error }))),
+    }
+}
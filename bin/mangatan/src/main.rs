@@ -1,21 +1,42 @@
+mod base_path;
+mod check;
+mod disk_usage;
+mod health;
 mod io;
+mod kiosk;
+mod log_level;
+mod network;
+mod recorder;
+mod selftest;
+mod startup;
+mod tls;
 
 use std::{
     env,
     fs::{self},
-    path::PathBuf,
+    net::SocketAddr,
+    path::{Path, PathBuf},
     process::Stdio,
     sync::{
         Arc, Mutex,
         mpsc::{Receiver, Sender},
     },
-    thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 #[cfg(feature = "embed-jre")]
 use crate::io::extract_zip;
-use crate::io::{extract_file, resolve_java};
+use crate::{
+    base_path::BasePath,
+    disk_usage::{DiskUsageScan, ScanState},
+    health::Health,
+    io::{extract_file, resolve_java},
+    log_level::{LogLevel, PROXY_TARGET, SUWAYOMI_TARGET},
+    network::{NetworkDiagnostics, Service},
+    recorder::Recorder,
+    startup::{SUWAYOMI_READY_PHASE, StartupTracker},
+    tls::TlsSetup,
+};
 use anyhow::anyhow;
 use axum::{
     Router,
@@ -25,26 +46,32 @@ use axum::{
         ws::{Message, WebSocket, WebSocketUpgrade},
     },
     http::{HeaderMap, StatusCode, Uri},
+    middleware,
     response::{IntoResponse, Response},
-    routing::any,
+    routing::{any, get, post},
 };
-use clap::Parser;
-use directories::{BaseDirs, ProjectDirs};
+use axum_server::tls_rustls::RustlsConfig;
+use clap::{Parser, Subcommand};
+use directories::{BaseDirs, ProjectDirs, UserDirs};
 use eframe::{
     egui::{self},
     icon_data,
 };
 use futures::{SinkExt, StreamExt, TryStreamExt};
+use mangatan_core::{
+    build_info::{self, BuildInfo},
+    data_dir,
+};
 use reqwest::{
     Client, Method,
     header::{
         ACCEPT, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_ORIGIN,
-        ACCESS_CONTROL_REQUEST_METHOD, AUTHORIZATION, CONTENT_TYPE, ORIGIN,
+        ACCESS_CONTROL_REQUEST_METHOD, AUTHORIZATION, CONTENT_DISPOSITION, CONTENT_TYPE, ORIGIN,
     },
 };
 use rust_embed::RustEmbed;
 use serde::Serialize;
-use tokio::process::Command;
+use tokio::{process::Command, runtime::Handle};
 use tokio_tungstenite::{
     connect_async,
     tungstenite::{
@@ -52,11 +79,20 @@ use tokio_tungstenite::{
         protocol::{Message as TungsteniteMessage, frame::coding::CloseCode},
     },
 };
+use tokio_util::sync::CancellationToken;
 use tower_http::cors::{AllowOrigin, CorsLayer};
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 use tracing_subscriber::EnvFilter;
 
 const APP_VERSION: &str = env!("MANGATAN_VERSION");
+/// Cargo features reported in the build info.
+const APP_FEATURES: &[&str] = &[
+    #[cfg(feature = "embed-jre")]
+    "embed-jre",
+];
+/// How long blocking work (a save, an update download) gets to finish once everything has been
+/// told to stop.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
 
 static ICON_BYTES: &[u8] = include_bytes!("../resources/faviconlogo.png");
 static JAR_BYTES: &[u8] = include_bytes!("../resources/Suwayomi-Server.jar");
@@ -72,6 +108,9 @@ struct FrontendAssets;
 struct VersionResponse {
     version: String,
     variant: String,
+    target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    commit: Option<String>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -86,7 +125,16 @@ enum UpdateStatus {
 }
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    after_help = "Health endpoints (no auth, on the web port):\n  \
+                  GET /livez   200 once the web server is serving\n  \
+                  GET /readyz  200 while Suwayomi answers its probe and OCR/Yomitan are \
+                  initialized, 503 otherwise"
+)]
 struct Cli {
     /// Runs the server without the GUI (Fixes Docker/Server deployments)
     #[arg(long, env = "MANGATAN_HEADLESS")]
@@ -95,42 +143,200 @@ struct Cli {
     /// Opens the web interface in the default browser after server start (Requires --headless)
     #[arg(long, requires = "headless")]
     open_page: bool,
+
+    /// Runs without the GUI and keeps the web interface open in a fullscreen browser window,
+    /// restarting it if it closes (for TV boxes and kiosks)
+    #[arg(long, env = "MANGATAN_KIOSK", conflicts_with = "open_page")]
+    kiosk: bool,
+
+    /// Browser for --kiosk, Chromium-based or Firefox (detected when not set)
+    #[arg(long, env = "MANGATAN_KIOSK_BROWSER")]
+    kiosk_browser: Option<PathBuf>,
+
+    /// Overrides where Suwayomi keeps its library and database (defaults to its own data dir)
+    #[arg(long, env = "MANGATAN_SUWAYOMI_DATA")]
+    suwayomi_data: Option<PathBuf>,
+
+    /// Also serves HTTPS using this PEM certificate (needs --tls-key)
+    #[arg(
+        long,
+        env = "MANGATAN_TLS_CERT",
+        requires = "tls_key",
+        conflicts_with = "tls_self_signed"
+    )]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key for --tls-cert
+    #[arg(long, env = "MANGATAN_TLS_KEY", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Also serves HTTPS using a generated self-signed certificate, kept in the data dir
+    #[arg(long, env = "MANGATAN_TLS_SELF_SIGNED")]
+    tls_self_signed: bool,
+
+    /// Extra hostnames or IPs for the self-signed certificate (localhost and the LAN IP are
+    /// always included)
+    #[arg(
+        long = "tls-hostname",
+        env = "MANGATAN_TLS_HOSTNAMES",
+        value_delimiter = ','
+    )]
+    tls_hostnames: Vec<String>,
+
+    /// Port for the HTTPS listener
+    #[arg(long, env = "MANGATAN_TLS_PORT", default_value_t = tls::DEFAULT_TLS_PORT)]
+    tls_port: u16,
+
+    /// Records sanitized API traffic to http-recording.ndjson in the data dir for this many
+    /// minutes (30 if no value is given), for attaching to frontend bug reports
+    #[arg(
+        long,
+        env = "MANGATAN_RECORD_REQUESTS",
+        value_name = "MINUTES",
+        num_args = 0..=1,
+        default_missing_value = "30"
+    )]
+    record_requests: Option<u64>,
+
+    /// Answers Suwayomi API requests with 503 "starting up" until Suwayomi first responds,
+    /// instead of proxying them to a backend that isn't listening yet (502)
+    #[arg(long, env = "MANGATAN_HOLD_PROXY_UNTIL_READY")]
+    hold_proxy_until_ready: bool,
+
+    /// Serves the web UI under this path (e.g. /manga) for a reverse proxy that mounts
+    /// Mangatan on a subpath; it stays reachable at the root too
+    #[arg(long, env = "MANGATAN_BASE_PATH", default_value = "")]
+    base_path: BasePath,
+
+    /// Keeps data in a temporary directory when the data dir isn't writable, instead of
+    /// refusing to start. Nothing is saved past a reboot
+    #[arg(long, env = "MANGATAN_TEMP_DATA_IF_READ_ONLY")]
+    temp_data_if_read_only: bool,
+
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+#[derive(Subcommand, Debug)]
+enum CliCommand {
+    /// Resolves Java and extracts/verifies the bundled assets without starting the servers.
+    /// Exits 0 when everything needed to start is present, 1 otherwise
+    Check,
+    /// Starts `mangatan --kiosk` at login (with --kiosk-browser, if given)
+    Autostart {
+        /// Removes the login entry instead
+        #[arg(long)]
+        disable: bool,
+    },
 }
 
 fn main() -> eframe::Result<()> {
     let args = Cli::parse();
+    build_info::init(
+        BuildInfo::compiled("desktop")
+            .with_version(APP_VERSION)
+            .with_features(APP_FEATURES),
+    );
+
+    // self_update builds its own reqwest client, which only reads the standard proxy variables
+    if let Ok(proxy) = env::var("MANGATAN_HTTP_PROXY")
+        && !proxy.trim().is_empty()
+        && env::var_os("HTTPS_PROXY").is_none()
+        && env::var_os("https_proxy").is_none()
+    {
+        // SAFETY: no other threads exist yet, so nothing can be reading the environment
+        unsafe { env::set_var("HTTPS_PROXY", proxy.trim()) };
+    }
 
+    // `check` prints its own summary, so keep the extraction logs out of it
+    let default_level = match args.command {
+        Some(CliCommand::Check) => "warn",
+        Some(CliCommand::Autostart { .. }) | None => "info",
+    };
     let rust_log = env::var(EnvFilter::DEFAULT_ENV).unwrap_or_default();
     let env_filter = match rust_log.is_empty() {
-        true => EnvFilter::builder().parse_lossy("info"),
+        true => EnvFilter::builder().parse_lossy(default_level),
         false => EnvFilter::builder().parse_lossy(rust_log),
     };
-    tracing_subscriber::fmt().with_env_filter(env_filter).init();
+    let log_level = LogLevel::init(env_filter);
+    let network = NetworkDiagnostics::default();
 
     let proj_dirs =
         ProjectDirs::from("", "", "mangatan").expect("Could not determine home directory");
     let data_dir = proj_dirs.data_dir().to_path_buf();
+    match args.command {
+        Some(CliCommand::Check) => std::process::exit(check::run(&data_dir)),
+        Some(CliCommand::Autostart { disable }) => {
+            match kiosk::set_autostart(!disable, args.kiosk_browser.as_deref()) {
+                Ok(entry) if disable => info!("🗑️ Removed autostart entry {}", entry.display()),
+                Ok(entry) => info!("✅ Kiosk mode will start at login ({})", entry.display()),
+                Err(err) => {
+                    error!("❌ {err:#}");
+                    std::process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+    // Found out now rather than as SQLite or cache save errors once the servers are up
+    let data_dir = match data_dir::resolve(&data_dir, args.temp_data_if_read_only) {
+        Ok(resolved) if resolved.temporary => {
+            warn!("⚠️ ============================================================");
+            warn!(
+                "⚠️ {} is not writable. Using {} instead: NOTHING WILL BE SAVED past a reboot.",
+                data_dir.display(),
+                resolved.path.display()
+            );
+            warn!("⚠️ ============================================================");
+            resolved.path
+        }
+        Ok(resolved) => resolved.path,
+        Err(err) => {
+            error!("❌ {err}");
+            error!("   (--temp-data-if-read-only or MANGATAN_TEMP_DATA_IF_READ_ONLY=1)");
+            std::process::exit(1);
+        }
+    };
+    let startup = StartupTracker::new(&data_dir);
+    let tls = resolve_tls(&args, &data_dir);
 
     let server_data_dir = data_dir.clone();
     let gui_data_dir = data_dir.clone();
+    let suwayomi_data_dir = args.suwayomi_data.clone();
+    let record_for = args
+        .record_requests
+        .map(|minutes| Duration::from_secs(minutes * 60));
+    let hold_proxy = args.hold_proxy_until_ready;
+    let base_path = args.base_path.clone();
+    let health = Health::default();
+
+    // The one runtime for the server and everything the GUI starts in the background.
+    // Cancelling `shutdown` stops all of it.
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+    let shutdown = CancellationToken::new();
+
+    if args.headless || args.kiosk {
+        match args.kiosk {
+            true => info!("🖥️ Starting in Kiosk Mode..."),
+            false => info!("👻 Starting in Headless Mode (No GUI)..."),
+        }
 
-    if args.headless {
-        info!("👻 Starting in Headless Mode (No GUI)...");
-
-        let rt = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
-
-        rt.block_on(async {
+        runtime.block_on(async {
             if args.open_page {
-                tokio::spawn(async { open_webpage_when_ready().await });
+                tokio::spawn(open_webpage_when_ready(shutdown.clone()));
             }
+            let kiosk_task = args.kiosk.then(|| {
+                let browser = kiosk::KioskBrowser::resolve(args.kiosk_browser.as_deref());
+                tokio::spawn(kiosk::run(browser, data_dir.clone()))
+            });
 
-            let (shutdown_tx, shutdown_rx) = tokio::sync::mpsc::channel::<()>(1);
+            let ctrl_c_shutdown = shutdown.clone();
             tokio::spawn(async move {
                 match tokio::signal::ctrl_c().await {
                     Ok(()) => {
                         info!("🛑 Received Ctrl+C, shutting down server...");
-
-                        let _ = shutdown_tx.send(()).await;
+                        ctrl_c_shutdown.cancel();
                     }
 
                     Err(err) => {
@@ -139,35 +345,76 @@ fn main() -> eframe::Result<()> {
                 }
             });
 
-            if let Err(err) = run_server(shutdown_rx, &server_data_dir).await {
+            if let Err(err) = run_server(
+                shutdown.clone(),
+                &server_data_dir,
+                suwayomi_data_dir,
+                startup,
+                tls,
+                record_for,
+                log_level,
+                network,
+                hold_proxy,
+                base_path,
+                health,
+            )
+            .await
+            {
                 error!("Server crashed: {err}");
             }
+
+            // Dropping the task closes the browser with the server
+            if let Some(kiosk_task) = kiosk_task {
+                kiosk_task.abort();
+                let _ = kiosk_task.await;
+            }
         });
+        shutdown.cancel();
+        runtime.shutdown_timeout(SHUTDOWN_GRACE);
 
         return Ok(());
     }
 
-    let (shutdown_tx, shutdown_rx) = tokio::sync::mpsc::channel::<()>(1);
     let (server_stopped_tx, server_stopped_rx) = std::sync::mpsc::channel::<()>();
 
-    thread::spawn(move || {
-        let rt = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
-        rt.block_on(async {
-            tokio::spawn(async { open_webpage_when_ready().await });
-            let _guard = ServerGuard {
-                tx: server_stopped_tx,
-            };
-
-            if let Err(err) = run_server(shutdown_rx, &server_data_dir).await {
-                error!("Server crashed: {err}");
-            }
-        });
+    let gui_suwayomi_data_dir = suwayomi_data_dir.clone();
+    let gui_startup = startup.clone();
+    let gui_tls = tls.clone();
+    let gui_log_level = log_level.clone();
+    let gui_network = network.clone();
+    let gui_health = health.clone();
+    let server_shutdown = shutdown.clone();
+    runtime.spawn(async move {
+        tokio::spawn(open_webpage_when_ready(server_shutdown.clone()));
+        let _guard = ServerGuard {
+            tx: server_stopped_tx,
+        };
+
+        if let Err(err) = run_server(
+            server_shutdown,
+            &server_data_dir,
+            suwayomi_data_dir,
+            startup,
+            tls,
+            record_for,
+            log_level,
+            network,
+            hold_proxy,
+            base_path,
+            health,
+        )
+        .await
+        {
+            error!("Server crashed: {err}");
+        }
     });
 
     let icon = icon_data::from_png_bytes(ICON_BYTES).expect("The icon data must be valid");
+    // Room for Suwayomi's startup status, and the HTTPS address and certificate export
+    let window_height = if gui_tls.is_some() { 455.0 } else { 405.0 };
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size([320.0, 320.0])
+            .with_inner_size([320.0, window_height])
             .with_icon(icon)
             .with_title("Mangatan")
             .with_resizable(false)
@@ -176,14 +423,23 @@ fn main() -> eframe::Result<()> {
     };
 
     info!("🎨 Attempting to open GUI window...");
+    let gui_runtime = runtime.handle().clone();
+    let gui_shutdown = shutdown.clone();
     let result = eframe::run_native(
         "Mangatan",
         options,
         Box::new(|_cc| {
             Ok(Box::new(MyApp::new(
-                shutdown_tx,
+                gui_runtime,
+                gui_shutdown,
                 server_stopped_rx,
                 gui_data_dir,
+                gui_suwayomi_data_dir,
+                gui_startup,
+                gui_tls,
+                gui_log_level,
+                gui_network,
+                gui_health,
             )))
         }),
     );
@@ -194,6 +450,9 @@ fn main() -> eframe::Result<()> {
     } else {
         info!("👋 GUI exited normally.");
     }
+    // Also covers the window failing to open, which leaves the server running
+    shutdown.cancel();
+    runtime.shutdown_timeout(SHUTDOWN_GRACE);
 
     result
 }
@@ -208,64 +467,159 @@ impl Drop for ServerGuard {
 }
 
 struct MyApp {
-    shutdown_tx: tokio::sync::mpsc::Sender<()>,
+    runtime: Handle,
+    shutdown: CancellationToken,
     server_stopped_rx: Receiver<()>,
     is_shutting_down: bool,
+    /// The server has stopped, so the next close request goes through.
+    can_close: bool,
     data_dir: PathBuf,
+    suwayomi_data_dir: Option<PathBuf>,
+    startup: StartupTracker,
+    tls: Option<TlsSetup>,
     update_status: Arc<Mutex<UpdateStatus>>,
+    log_level: LogLevel,
+    network: NetworkDiagnostics,
+    health: Health,
+    /// Component the log level dropdown currently shows
+    log_component: &'static str,
+    disk_usage: DiskUsageScan,
+    disk_usage_open: bool,
 }
 
 impl MyApp {
+    #[allow(clippy::too_many_arguments)]
     fn new(
-        shutdown_tx: tokio::sync::mpsc::Sender<()>,
+        runtime: Handle,
+        shutdown: CancellationToken,
         server_stopped_rx: Receiver<()>,
         data_dir: PathBuf,
+        suwayomi_data_dir: Option<PathBuf>,
+        startup: StartupTracker,
+        tls: Option<TlsSetup>,
+        log_level: LogLevel,
+        network: NetworkDiagnostics,
+        health: Health,
     ) -> Self {
         // Initialize status
         let update_status = Arc::new(Mutex::new(UpdateStatus::Idle));
 
         // Optional: Trigger a check immediately on startup
-        let status_clone = update_status.clone();
-        std::thread::spawn(move || {
-            if !is_flatpak() {
-                check_for_updates(status_clone);
-            }
-        });
+        if !is_flatpak() {
+            runtime.spawn(check_for_updates(update_status.clone(), shutdown.clone()));
+        }
+
+        let disk_usage = DiskUsageScan::new(
+            runtime.clone(),
+            data_dir.clone(),
+            resolve_suwayomi_data_dir(suwayomi_data_dir.as_ref()),
+        );
 
         Self {
-            shutdown_tx,
+            runtime,
+            shutdown,
             server_stopped_rx,
             is_shutting_down: false,
+            can_close: false,
             data_dir,
+            suwayomi_data_dir,
+            startup,
+            tls,
             update_status,
+            log_level,
+            network,
+            health,
+            log_component: log_level::DEFAULT_COMPONENT,
+            disk_usage,
+            disk_usage_open: false,
         }
     }
 
     fn trigger_update(&self) {
-        let status_clone = self.update_status.clone();
-
-        *status_clone.lock().expect("lock shouldn't panic") = UpdateStatus::Downloading;
+        *self.update_status.lock().expect("lock shouldn't panic") = UpdateStatus::Downloading;
+        self.runtime.spawn(perform_update(
+            self.update_status.clone(),
+            self.shutdown.clone(),
+        ));
+    }
 
-        std::thread::spawn(move || match perform_update() {
-            Ok(_) => {
-                *status_clone.lock().expect("lock shouldn't panic") = UpdateStatus::RestartRequired
-            }
-            Err(e) => {
-                *status_clone.lock().expect("lock shouldn't panic") =
-                    UpdateStatus::Error(e.to_string())
-            }
-        });
+    /// The disk usage breakdown, in a window over the main panel while it's open. Closing it
+    /// cancels a running analysis; the last result is kept for next time.
+    fn show_disk_usage(&mut self, ctx: &egui::Context) {
+        let mut open = self.disk_usage_open;
+        egui::Window::new("Disk usage")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .vscroll(true)
+            .show(ctx, |ui| match self.disk_usage.state() {
+                ScanState::Idle => {}
+                state @ (ScanState::Running | ScanState::CleaningUp(_)) => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label(match state {
+                            ScanState::CleaningUp(category) => {
+                                format!("Cleaning up {}...", category.label())
+                            }
+                            _ => "Analyzing...".to_string(),
+                        });
+                        if ui.small_button("Cancel").clicked() {
+                            self.disk_usage.cancel();
+                        }
+                    });
+                }
+                ScanState::Cancelled => {
+                    ui.label("Analysis cancelled.");
+                    if ui.button("🔄 Analyze").clicked() {
+                        self.disk_usage.start(ctx);
+                    }
+                }
+                ScanState::Done(breakdown) => {
+                    ui.strong(format!(
+                        "Total: {}",
+                        disk_usage::format_size(breakdown.total_bytes)
+                    ));
+                    for usage in breakdown.categories.iter().filter(|usage| usage.bytes > 0) {
+                        let fraction = usage.bytes as f32 / breakdown.total_bytes as f32;
+                        ui.add(egui::ProgressBar::new(fraction).text(format!(
+                            "{}: {}",
+                            usage.category.label(),
+                            disk_usage::format_size(usage.bytes)
+                        )));
+                        ui.horizontal(|ui| {
+                            if let Some(folder) = &usage.folder
+                                && ui.small_button("📂 Open folder").clicked()
+                            {
+                                let _ = open::that(folder);
+                            }
+                            if !usage.category.cleanup_endpoints().is_empty()
+                                && ui.small_button("🧹 Clean up").clicked()
+                            {
+                                self.disk_usage.clean_up(usage.category, ctx);
+                            }
+                        });
+                    }
+                    ui.add_space(5.0);
+                    if ui.button("🔄 Analyze again").clicked() {
+                        self.disk_usage.start(ctx);
+                    }
+                }
+            });
+        if self.disk_usage_open && !open {
+            self.disk_usage.cancel();
+        }
+        self.disk_usage_open = open;
     }
 }
 
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Handle window close requests
-        if ctx.input(|i| i.viewport().close_requested()) {
+        if ctx.input(|i| i.viewport().close_requested()) && !self.can_close {
             if !self.is_shutting_down {
                 self.is_shutting_down = true;
                 tracing::info!("❌ Close requested. Signaling server to stop...");
-                let _ = self.shutdown_tx.try_send(());
+                self.shutdown.cancel();
             }
             ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
         }
@@ -281,8 +635,9 @@ impl eframe::App for MyApp {
                 });
             });
 
-            if self.server_stopped_rx.try_recv().is_ok() {
-                std::process::exit(0);
+            if !self.can_close && self.server_stopped_rx.try_recv().is_ok() {
+                self.can_close = true;
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
             }
             ctx.request_repaint();
             return;
@@ -295,7 +650,16 @@ impl eframe::App for MyApp {
             .anchor(egui::Align2::LEFT_BOTTOM, [8.0, -8.0])
             .order(egui::Order::Foreground)
             .show(ctx, |ui| {
-                ui.weak(APP_VERSION);
+                ui.weak(build_info::current().to_string());
+                // Fall back to the previous launch while this one is still booting
+                let summary = self
+                    .startup
+                    .current()
+                    .summary()
+                    .or_else(|| self.startup.previous().and_then(|p| p.summary()));
+                if let Some(summary) = summary {
+                    ui.weak(format!("Startup: {summary}"));
+                }
             });
 
         egui::CentralPanel::default().show(ctx, |ui| {
@@ -314,8 +678,10 @@ impl eframe::App for MyApp {
                         match status {
                             UpdateStatus::Idle | UpdateStatus::UpToDate => {
                                 if ui.small_button("🔄 Check Updates").clicked() {
-                                    let status_clone = self.update_status.clone();
-                                    std::thread::spawn(move || check_for_updates(status_clone));
+                                    self.runtime.spawn(check_for_updates(
+                                        self.update_status.clone(),
+                                        self.shutdown.clone(),
+                                    ));
                                 }
                             }
                             UpdateStatus::Checking => {
@@ -384,6 +750,15 @@ impl eframe::App for MyApp {
                 UpdateStatus::Error(e) => {
                     ui.colored_label(egui::Color32::RED, "Update Failed");
                     ui.small(e.chars().take(40).collect::<String>());
+                    if let Some(hint) = self.network.explain(Service::Github, &e) {
+                        ui.small(hint);
+                        if ui.small_button("🌐 Network details").clicked() {
+                            let _ = open::that(format!(
+                                "http://localhost:4568{}",
+                                network::REPORT_PATH
+                            ));
+                        }
+                    }
                     if ui.button("Retry").clicked() {
                         *self.update_status.lock().expect("lock shouldn't panic") =
                             UpdateStatus::Idle;
@@ -406,6 +781,35 @@ impl eframe::App for MyApp {
                 }
             });
 
+            // The web UI is up before Suwayomi, which boots (or fails to) in the background
+            if let Some(error) = self.health.suwayomi_error() {
+                ui.add_space(5.0);
+                ui.vertical_centered(|ui| {
+                    ui.colored_label(egui::Color32::RED, "⚠ Suwayomi failed to start");
+                    ui.small(error.chars().take(120).collect::<String>());
+                });
+            } else if !self.health.suwayomi_started() {
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Starting Suwayomi...");
+                });
+                ctx.request_repaint_after(Duration::from_millis(500));
+            }
+
+            if let Some(tls) = &self.tls {
+                ui.add_space(5.0);
+                ui.vertical_centered(|ui| {
+                    let url = tls.https_url();
+                    if ui.link(format!("🔐 {url}")).clicked() {
+                        let _ = open::that(&url);
+                    }
+                    if tls.self_signed && ui.small_button("📜 Export Certificate").clicked() {
+                        export_certificate(&tls.cert_path);
+                    }
+                });
+            }
+
             ui.add_space(15.0);
 
             // --- SECONDARY ACTIONS (Community) ---
@@ -441,84 +845,198 @@ impl eframe::App for MyApp {
                 if ui
                     .add_sized([width, 30.0], egui::Button::new("📂 Suwayomi Data"))
                     .clicked()
-                    && let Some(base_dirs) = BaseDirs::new()
+                    && let Some(dir) = resolve_suwayomi_data_dir(self.suwayomi_data_dir.as_ref())
                 {
-                    let dir = base_dirs.data_local_dir().join("Tachidesk");
                     if !dir.exists() {
                         let _ = std::fs::create_dir_all(&dir);
                     }
                     let _ = open::that(&dir);
                 }
             });
+
+            if ui
+                .add_sized(
+                    [ui.available_width(), 24.0],
+                    egui::Button::new("💽 Analyze disk usage"),
+                )
+                .clicked()
+            {
+                self.disk_usage_open = true;
+                if matches!(
+                    self.disk_usage.state(),
+                    ScanState::Idle | ScanState::Cancelled
+                ) {
+                    self.disk_usage.start(ctx);
+                }
+            }
+
+            // Log level per component, applied right away (same as PUT /api/log-level)
+            ui.horizontal(|ui| {
+                ui.label("Log level:");
+                egui::ComboBox::from_id_salt("log_component")
+                    .selected_text(self.log_component)
+                    .show_ui(ui, |ui| {
+                        let components = std::iter::once(log_level::DEFAULT_COMPONENT)
+                            .chain(log_level::COMPONENTS.iter().map(|(name, _)| *name));
+                        for component in components {
+                            ui.selectable_value(&mut self.log_component, component, component);
+                        }
+                    });
+                let current = self.log_level.level_of(self.log_component);
+                egui::ComboBox::from_id_salt("log_level")
+                    .selected_text(&current)
+                    .show_ui(ui, |ui| {
+                        for level in log_level::LEVELS {
+                            if ui.selectable_label(current == *level, *level).clicked() {
+                                let levels = [(self.log_component.to_string(), level.to_string())];
+                                if let Err(err) = self.log_level.set_levels(&levels.into()) {
+                                    error!("❌ {err}");
+                                }
+                            }
+                        }
+                    });
+            });
         });
+
+        self.show_disk_usage(ctx);
     }
 }
 
-async fn run_server(
-    mut shutdown_signal: tokio::sync::mpsc::Receiver<()>,
-    data_dir: &PathBuf,
-) -> Result<(), Box<anyhow::Error>> {
-    info!("🚀 Initializing Mangatan Launcher...");
-    info!("📂 Data Directory: {}", data_dir.display());
-
-    if !data_dir.exists() {
-        fs::create_dir_all(data_dir).map_err(|err| anyhow!("Failed to create data dir {err:?}"))?;
-    }
-    let bin_dir = data_dir.join("bin");
-    if !bin_dir.exists() {
-        fs::create_dir_all(&bin_dir).map_err(|err| anyhow!("Failed to create bin dir {err:?}"))?;
-    }
+/// Sets up HTTPS from the CLI flags. A broken TLS setup is logged and skipped so it can't
+/// keep the plain HTTP server from starting.
+fn resolve_tls(args: &Cli, data_dir: &Path) -> Option<TlsSetup> {
+    let setup = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => TlsSetup::provided(cert.clone(), key.clone(), args.tls_port),
+        _ if args.tls_self_signed => {
+            TlsSetup::self_signed(data_dir, &args.tls_hostnames, args.tls_port)
+        }
+        _ => return None,
+    };
 
-    info!("📦 Extracting assets...");
-    let jar_name = "Suwayomi-Server.jar";
-    let _ = extract_file(&bin_dir, jar_name, JAR_BYTES)
-        .map_err(|err| anyhow!("Failed to extract {jar_name} {err:?}"))?;
-    let jar_rel_path = PathBuf::from("bin").join(jar_name);
+    setup
+        .inspect_err(|err| error!("❌ HTTPS disabled: {err:#}"))
+        .ok()
+}
 
-    #[cfg(feature = "embed-jre")]
-    {
-        let natives_dir = data_dir.join("natives");
-        if !natives_dir.exists() {
-            info!("📦 Extracting Native Libraries (JogAmp)...");
-            fs::create_dir_all(&natives_dir)
-                .map_err(|e| anyhow!("Failed to create natives dir: {e}"))?;
+/// Copies the self-signed certificate to Downloads (or opens its folder when there's none)
+/// so it can be sent to a phone and installed there.
+fn export_certificate(cert_path: &Path) {
+    let exported = UserDirs::new()
+        .and_then(|dirs| dirs.download_dir().map(Path::to_path_buf))
+        .and_then(|dir| {
+            let target = dir.join("mangatan.crt");
+            fs::copy(cert_path, &target).ok().map(|_| dir)
+        });
 
-            extract_zip(NATIVES_BYTES, &natives_dir)
-                .map_err(|e| anyhow!("Failed to extract natives: {e}"))?;
+    match exported {
+        Some(dir) => {
+            info!("📜 Certificate exported to {}", dir.display());
+            let _ = open::that(dir);
+        }
+        None => {
+            if let Some(dir) = cert_path.parent() {
+                let _ = open::that(dir);
+            }
         }
     }
+}
 
-    info!("🔍 Resolving Java...");
-    let java_exec =
-        resolve_java(data_dir).map_err(|err| anyhow!("Failed to resolve java install {err:?}"))?;
-    let java_home = java_exec
-        .parent()
-        .and_then(|p| p.parent())
-        .unwrap_or(data_dir);
+/// Suwayomi's data dir: the configured override, or its default `Tachidesk` dir.
+fn resolve_suwayomi_data_dir(configured: Option<&PathBuf>) -> Option<PathBuf> {
+    match configured {
+        Some(dir) => Some(dir.clone()),
+        None => BaseDirs::new().map(|base_dirs| base_dirs.data_local_dir().join("Tachidesk")),
+    }
+}
 
-    info!("☕ Spawning Suwayomi...");
-    let mut suwayomi_proc = Command::new(&java_exec)
-        .current_dir(data_dir)
-        .env("JAVA_HOME", java_home)
-        .arg("-Dsuwayomi.tachidesk.config.server.initialOpenInBrowserEnabled=false")
-        .arg("-Dsuwayomi.tachidesk.config.server.webUIChannel=BUNDLED")
-        .arg("-XX:+ExitOnOutOfMemoryError")
-        .arg("--enable-native-access=ALL-UNNAMED")
-        .arg("--add-opens=java.desktop/sun.awt=ALL-UNNAMED")
-        .arg("--add-opens=java.desktop/javax.swing=ALL-UNNAMED")
-        .arg("-jar")
-        .arg(&jar_rel_path)
-        .kill_on_drop(true)
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .spawn()
-        .map_err(|err| anyhow!("Failed to launch suwayomi {err:?}"))?;
+#[allow(clippy::too_many_arguments)]
+async fn run_server(
+    shutdown: CancellationToken,
+    data_dir: &Path,
+    suwayomi_data_dir: Option<PathBuf>,
+    startup: StartupTracker,
+    tls: Option<TlsSetup>,
+    record_for: Option<Duration>,
+    log_level: LogLevel,
+    network: NetworkDiagnostics,
+    hold_proxy: bool,
+    base_path: BasePath,
+    health: Health,
+) -> Result<(), Box<anyhow::Error>> {
+    info!("🚀 Initializing Mangatan Launcher...");
+    info!("📂 Data Directory: {}", data_dir.display());
 
-    info!("🌍 Starting Web Interface at http://localhost:4568");
+    // Started first and left to run alongside the web server, so the status page and health
+    // endpoints are up while the JVM boots (or report why it couldn't)
+    let suwayomi_shutdown = shutdown.child_token();
+    let _stop_suwayomi = suwayomi_shutdown.clone().drop_guard();
+    let mut suwayomi = tokio::spawn(run_suwayomi(
+        data_dir.to_path_buf(),
+        suwayomi_data_dir,
+        startup.clone(),
+        health.clone(),
+        suwayomi_shutdown,
+    ));
+
+    info!(
+        "🌍 Starting Web Interface at http://localhost:4568{}",
+        base_path.href()
+    );
+
+    let mut ocr_state = startup.time("ocr_init", || {
+        mangatan_ocr_server::state::AppState::new(data_dir.to_path_buf())
+    });
+    // The final flush happens below, once the web server is down
+    ocr_state.shutdown = shutdown.child_token();
+    let ocr_router = mangatan_ocr_server::create_router_with_state(ocr_state.clone()).layer(
+        middleware::from_fn_with_state(network.clone(), network::annotate_ocr_errors),
+    );
+    let yomitan_state = startup
+        .time("yomitan_init", || {
+            mangatan_yomitan_server::ServerState::new(data_dir.to_path_buf())
+        })
+        .map_err(anyhow::Error::from)?;
+    let yomitan_router =
+        mangatan_yomitan_server::create_router_with_state(yomitan_state.clone(), true);
+    let saver_state = ocr_state.clone();
+    health.watch_stalls("ocr_cache_saver", move || {
+        saver_state.saver_health().stalled
+    });
+    let writer_state = yomitan_state.app.clone();
+    health.watch_stalls("yomitan_writer", move || {
+        writer_state.writer_health().stalled
+    });
+    health.set_services_ready();
+    let proxy_gate = health.clone();
+    let health_router = Router::new()
+        .route("/livez", get(health::livez_handler))
+        .route("/readyz", get(health::readyz_handler))
+        .with_state(health);
+    let mut system_router = Router::new()
+        .route("/version", any(current_version_handler))
+        .route("/startup", get(startup_timings_handler));
+    if let Some(cert_path) = tls
+        .as_ref()
+        .filter(|tls| tls.self_signed)
+        .map(|tls| tls.cert_path.clone())
+    {
+        system_router = system_router.route(
+            "/tls-cert",
+            get(move || tls_cert_handler(cert_path.clone())),
+        );
+    }
+    let system_router = system_router.with_state(startup.clone());
 
-    let ocr_router = mangatan_ocr_server::create_router(data_dir.clone());
-    let yomitan_router = mangatan_yomitan_server::create_router(data_dir.clone(), true);
-    let system_router = Router::new().route("/version", any(current_version_handler));
+    let recorder = Recorder::new(data_dir);
+    if let Some(duration) = record_for
+        && let Err(err) = recorder.start(duration)
+    {
+        error!("❌ Failed to start request recording: {err}");
+    }
+    let debug_router = Router::new()
+        .route("/recording", get(recorder::download_handler))
+        .route("/recording/stop", post(recorder::stop_handler))
+        .with_state(recorder.clone());
 
     let client = Client::new();
     let cors = CorsLayer::new()
@@ -541,43 +1059,281 @@ async fn run_server(
         ])
         .allow_credentials(true);
 
-    let proxy_router = Router::new()
+    let log_level_router = Router::new()
+        .route(
+            "/api/log-level",
+            get(log_level::get_handler).put(log_level::put_handler),
+        )
+        .with_state(log_level);
+
+    tokio::spawn(network.clone().run_and_log());
+    let network_router = Router::new()
+        .route(network::REPORT_PATH, get(network::report_handler))
+        .with_state(network);
+
+    let selftest_router = Router::new()
+        .route("/api/mining-selftest", post(selftest::selftest_handler))
+        .with_state(yomitan_state.clone());
+
+    let mut proxy_router = Router::new()
         .route("/api/{*path}", any(proxy_suwayomi_handler))
         .with_state(client);
+    if hold_proxy {
+        proxy_router = proxy_router.route_layer(middleware::from_fn_with_state(
+            proxy_gate,
+            health::hold_until_suwayomi_started,
+        ));
+    }
 
     let app = Router::new()
         .nest("/api/ocr", ocr_router)
         .nest("/api/yomitan", yomitan_router)
         .nest("/api/system", system_router)
+        .nest("/api/debug", debug_router)
+        .route("/version", get(current_version_handler))
+        .route("/api/version", get(build_info_handler))
+        .merge(health_router)
+        .merge(log_level_router)
+        .merge(network_router)
+        .merge(selftest_router)
         .merge(proxy_router)
-        .fallback(serve_react_app)
+        .fallback({
+            let base_href = base_path.href();
+            move |uri| serve_react_app(uri, base_href.clone())
+        })
+        .layer(middleware::from_fn_with_state(recorder, recorder::record))
         .layer(cors);
+    let app = base_path.mount(app);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:4568")
         .await
         .map_err(|err| anyhow!("Failed create main server socket: {err:?}"))?;
 
+    let tls_handle = axum_server::Handle::new();
+    if let Some(tls) = &tls {
+        spawn_tls_server(tls, app.clone(), tls_handle.clone()).await;
+    }
+
     let server_future = axum::serve(listener, app).with_graceful_shutdown(async move {
-        let _ = shutdown_signal.recv().await;
+        shutdown.cancelled().await;
         info!("🛑 Shutdown signal received.");
+        tls_handle.graceful_shutdown(Some(Duration::from_secs(5)));
     });
 
     info!("✅ Unified Server Running.");
 
+    let suwayomi_exited = tokio::select! {
+        _ = &mut suwayomi => true,
+        _ = server_future => {
+            info!("✅ Web server shutdown complete.");
+            false
+        }
+    };
+    if !suwayomi_exited {
+        let _ = suwayomi.await;
+    }
+
+    ocr_state.flush_cache();
+    ocr_state.log_session_summary();
+    yomitan_state.log_session_summary();
+
+    Ok(())
+}
+
+/// Prepares and runs Suwayomi until `shutdown`, then stops it; returns earlier only if Suwayomi
+/// exits on its own. Failing to start is logged and recorded in `health` rather than stopping
+/// the web server, so the error shows on `/readyz` and in the GUI.
+async fn run_suwayomi(
+    data_dir: PathBuf,
+    suwayomi_data_dir: Option<PathBuf>,
+    startup: StartupTracker,
+    health: Health,
+    shutdown: CancellationToken,
+) {
+    let start = async {
+        let (jar_rel_path, java_exec) = prepare_suwayomi(&data_dir, &startup).await?;
+        spawn_suwayomi(
+            &data_dir,
+            suwayomi_data_dir.as_deref(),
+            &jar_rel_path,
+            &java_exec,
+        )
+    };
+    let mut suwayomi_proc = tokio::select! {
+        result = start => match result {
+            Ok(proc) => proc,
+            Err(err) => {
+                error!(target: SUWAYOMI_TARGET, "❌ Suwayomi failed to start: {err}");
+                health.record_suwayomi_failed(err.to_string());
+                shutdown.cancelled().await;
+                return;
+            }
+        },
+        _ = shutdown.cancelled() => return,
+    };
+    tokio::spawn(probe_suwayomi(
+        startup,
+        health.clone(),
+        Instant::now(),
+        shutdown.clone(),
+    ));
+
     tokio::select! {
-        _ = suwayomi_proc.wait() => { error!("❌ Suwayomi exited unexpectedly"); }
-        _ = server_future => { info!("✅ Web server shutdown complete."); }
+        status = suwayomi_proc.wait() => {
+            error!(target: SUWAYOMI_TARGET, "❌ Suwayomi exited unexpectedly");
+            health.record_suwayomi_failed(match status {
+                Ok(status) => format!("Suwayomi exited unexpectedly ({status})"),
+                Err(err) => format!("Suwayomi exited unexpectedly ({err})"),
+            });
+        }
+        _ = shutdown.cancelled() => {}
     }
 
     info!("🛑 terminating child processes...");
 
     if let Err(err) = suwayomi_proc.kill().await {
-        error!("Error killing Suwayomi: {err}");
+        error!(target: SUWAYOMI_TARGET, "Error killing Suwayomi: {err}");
     }
     let _ = suwayomi_proc.wait().await;
-    info!("   Suwayomi terminated.");
+    info!(target: SUWAYOMI_TARGET, "   Suwayomi terminated.");
+}
 
-    Ok(())
+/// Extracts the bundled assets and resolves Java side by side, as neither needs the other.
+/// Returns the jar's path relative to `data_dir` and the `java` executable.
+async fn prepare_suwayomi(
+    data_dir: &Path,
+    startup: &StartupTracker,
+) -> anyhow::Result<(PathBuf, PathBuf)> {
+    info!("📦 Extracting assets and resolving Java...");
+    let extract = tokio::task::spawn_blocking({
+        let (data_dir, startup) = (data_dir.to_path_buf(), startup.clone());
+        move || startup.time("extract_assets", || extract_assets(&data_dir))
+    });
+    let java = tokio::task::spawn_blocking({
+        let (data_dir, startup) = (data_dir.to_path_buf(), startup.clone());
+        move || startup.time("resolve_java", || resolve_java(&data_dir))
+    });
+    let (jar_rel_path, java_exec) = tokio::join!(extract, java);
+    let jar_rel_path = jar_rel_path??;
+    let java_exec = java_exec?.map_err(|err| anyhow!("Failed to resolve java install {err:?}"))?;
+    Ok((jar_rel_path, java_exec))
+}
+
+fn spawn_suwayomi(
+    data_dir: &Path,
+    suwayomi_data_dir: Option<&Path>,
+    jar_rel_path: &Path,
+    java_exec: &Path,
+) -> anyhow::Result<tokio::process::Child> {
+    let java_home = java_exec
+        .parent()
+        .and_then(|p| p.parent())
+        .unwrap_or(data_dir);
+
+    info!(target: SUWAYOMI_TARGET, "☕ Spawning Suwayomi...");
+    let mut suwayomi_cmd = Command::new(java_exec);
+    suwayomi_cmd
+        .current_dir(data_dir)
+        .env("JAVA_HOME", java_home)
+        .arg("-Dsuwayomi.tachidesk.config.server.initialOpenInBrowserEnabled=false")
+        .arg("-Dsuwayomi.tachidesk.config.server.webUIChannel=BUNDLED");
+    if let Some(root_dir) = suwayomi_data_dir {
+        info!(
+            target: SUWAYOMI_TARGET,
+            "📂 Suwayomi Data Directory: {}",
+            root_dir.display()
+        );
+        suwayomi_cmd.arg(format!(
+            "-Dsuwayomi.tachidesk.config.server.rootDir={}",
+            root_dir.display()
+        ));
+    }
+    suwayomi_cmd
+        .arg("-XX:+ExitOnOutOfMemoryError")
+        .arg("--enable-native-access=ALL-UNNAMED")
+        .arg("--add-opens=java.desktop/sun.awt=ALL-UNNAMED")
+        .arg("--add-opens=java.desktop/javax.swing=ALL-UNNAMED")
+        .arg("-jar")
+        .arg(jar_rel_path)
+        .kill_on_drop(true)
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|err| anyhow!("Failed to launch suwayomi {err:?}"))
+}
+
+/// Creates the data dir and writes the bundled Suwayomi jar (and natives) into it. Returns the
+/// jar's path relative to `data_dir`.
+fn extract_assets(data_dir: &Path) -> anyhow::Result<PathBuf> {
+    if !data_dir.exists() {
+        fs::create_dir_all(data_dir).map_err(|err| anyhow!("Failed to create data dir {err:?}"))?;
+    }
+    let bin_dir = data_dir.join("bin");
+    if !bin_dir.exists() {
+        fs::create_dir_all(&bin_dir).map_err(|err| anyhow!("Failed to create bin dir {err:?}"))?;
+    }
+
+    let jar_name = "Suwayomi-Server.jar";
+    extract_file(&bin_dir, jar_name, JAR_BYTES)
+        .map_err(|err| anyhow!("Failed to extract {jar_name} {err:?}"))?;
+    let jar_rel_path = PathBuf::from("bin").join(jar_name);
+
+    #[cfg(feature = "embed-jre")]
+    {
+        let natives_dir = data_dir.join("natives");
+        if !natives_dir.exists() {
+            info!("📦 Extracting Native Libraries (JogAmp)...");
+            fs::create_dir_all(&natives_dir)
+                .map_err(|e| anyhow!("Failed to create natives dir: {e}"))?;
+
+            extract_zip(NATIVES_BYTES, &natives_dir)
+                .map_err(|e| anyhow!("Failed to extract natives: {e}"))?;
+        }
+    }
+
+    Ok(jar_rel_path)
+}
+
+/// Serves `app` over HTTPS on the TLS port, next to the plain HTTP listener. Failures are
+/// logged; plain HTTP keeps working either way.
+async fn spawn_tls_server(tls: &TlsSetup, app: Router, handle: axum_server::Handle) {
+    // reqwest and tungstenite already pull in ring; pin it so rustls doesn't have to choose
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let config = match RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path).await {
+        Ok(config) => config,
+        Err(err) => {
+            error!("❌ HTTPS disabled, failed to load certificate: {err}");
+            return;
+        }
+    };
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], tls.port));
+    info!("🔐 Serving HTTPS at {}", tls.https_url());
+    tokio::spawn(async move {
+        let result = axum_server::bind_rustls(addr, config)
+            .handle(handle)
+            .serve(app.into_make_service())
+            .await;
+        if let Err(err) = result {
+            error!("❌ HTTPS server stopped: {err}");
+        }
+    });
+}
+
+/// Hands out the self-signed certificate so phones can download and trust it.
+async fn tls_cert_handler(cert_path: PathBuf) -> Response {
+    match tokio::fs::read(&cert_path).await {
+        Ok(bytes) => (
+            [
+                (CONTENT_TYPE, "application/x-x509-ca-cert"),
+                (CONTENT_DISPOSITION, "attachment; filename=\"mangatan.crt\""),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(err) => (StatusCode::NOT_FOUND, err.to_string()).into_response(),
+    }
 }
 
 async fn proxy_suwayomi_handler(State(client): State<Client>, req: Request) -> Response {
@@ -760,6 +1516,9 @@ fn tungstenite_to_axum(msg: TungsteniteMessage) -> Message {
     }
 }
 
+/// Forwards `req` to `base_url`. Each call logs method, path, upstream status and time to the
+/// upstream's response headers at debug level, so `RUST_LOG=mangatan=debug` shows which proxied
+/// calls fail or are slow.
 async fn proxy_request(
     client: Client,
     req: Request,
@@ -781,10 +1540,16 @@ async fn proxy_request(
     let target_url = format!("{base_url}{target_path}");
 
     let method = req.method().clone();
+    // The query can carry credentials, so only the path is logged
+    let path = target_path
+        .split_once('?')
+        .map_or(target_path, |(path, _)| path)
+        .to_string();
     let headers = req.headers().clone();
     let body = reqwest::Body::wrap_stream(req.into_body().into_data_stream());
 
-    let mut builder = client.request(method, &target_url).body(body);
+    let started = Instant::now();
+    let mut builder = client.request(method.clone(), &target_url).body(body);
 
     for (key, value) in headers.iter() {
         if key.as_str() != "host" {
@@ -795,6 +1560,14 @@ async fn proxy_request(
     match builder.send().await {
         Ok(resp) => {
             let status = resp.status();
+            debug!(
+                target: PROXY_TARGET,
+                %method,
+                path,
+                status = status.as_u16(),
+                elapsed_ms = started.elapsed().as_millis() as u64,
+                "proxied"
+            );
             let mut response_builder = Response::builder().status(status);
             for (key, value) in resp.headers() {
                 response_builder = response_builder.header(key, value);
@@ -805,7 +1578,14 @@ async fn proxy_request(
                 .expect("Failed to build proxied response")
         }
         Err(err) => {
-            info!("Proxy Error to {target_url}: {err}");
+            debug!(
+                target: PROXY_TARGET,
+                %method,
+                path,
+                elapsed_ms = started.elapsed().as_millis() as u64,
+                "proxy failed"
+            );
+            info!(target: PROXY_TARGET, "Proxy Error to {target_url}: {err}");
             Response::builder()
                 .status(StatusCode::BAD_GATEWAY)
                 .body(Body::empty())
@@ -814,7 +1594,8 @@ async fn proxy_request(
     }
 }
 
-async fn serve_react_app(uri: Uri) -> impl IntoResponse {
+/// Embedded web UI files, with `index.html` (with its `<base href>` set) for every other path.
+async fn serve_react_app(uri: Uri, base_href: String) -> impl IntoResponse {
     let path = uri.path().trim_start_matches('/');
 
     if !path.is_empty()
@@ -831,7 +1612,8 @@ async fn serve_react_app(uri: Uri) -> impl IntoResponse {
     if let Some(index) = FrontendAssets::get("index.html")
         && let Ok(html_string) = std::str::from_utf8(index.data.as_ref())
     {
-        let fixed_html = html_string.replace("<head>", "<head><base href=\"/\" />");
+        let fixed_html =
+            html_string.replace("<head>", &format!("<head><base href=\"{base_href}\" />"));
 
         return (
             [(axum::http::header::CONTENT_TYPE, "text/html")],
@@ -865,51 +1647,60 @@ fn get_asset_target_string() -> &'static str {
     }
 }
 
-fn check_for_updates(status: Arc<Mutex<UpdateStatus>>) {
+async fn check_for_updates(status: Arc<Mutex<UpdateStatus>>, shutdown: CancellationToken) {
     *status.lock().expect("lock shouldn't panic") = UpdateStatus::Checking;
 
+    // self_update blocks on its own HTTP client
+    let result = tokio::select! {
+        result = tokio::task::spawn_blocking(latest_release) => result,
+        _ = shutdown.cancelled() => return,
+    };
+    *status.lock().expect("lock shouldn't panic") = match result {
+        Ok(Ok(Some(version))) => UpdateStatus::UpdateAvailable(version),
+        Ok(Ok(None)) => UpdateStatus::UpToDate,
+        Ok(Err(e)) => UpdateStatus::Error(e),
+        Err(e) => UpdateStatus::Error(e.to_string()),
+    };
+}
+
+/// The latest release's version, if it's newer than this build.
+fn latest_release() -> Result<Option<String>, String> {
     // We use the same configuration for checking as we do for updating
     // This ensures we only "find" releases that actually match our custom asset naming
     let target_str = get_asset_target_string();
     let clean_version = APP_VERSION.trim_start_matches('v');
 
-    let updater_result = self_update::backends::github::Update::configure()
+    let release = self_update::backends::github::Update::configure()
         .repo_owner("KolbyML")
         .repo_name("Mangatan")
         .bin_name("mangatan") // This must match the binary name inside the zip/tar
         .target(target_str) // CRITICAL: Forces it to look for "Windows-x64" etc.
         .current_version(clean_version)
-        .build();
-
-    match updater_result {
-        Ok(updater) => {
-            match updater.get_latest_release() {
-                Ok(release) => {
-                    // Check if remote version > local version
-                    let is_newer =
-                        self_update::version::bump_is_greater(clean_version, &release.version)
-                            .unwrap_or(false);
-
-                    if is_newer {
-                        *status.lock().expect("lock shouldn't panic") =
-                            UpdateStatus::UpdateAvailable(release.version);
-                    } else {
-                        *status.lock().expect("lock shouldn't panic") = UpdateStatus::UpToDate;
-                    }
-                }
-                Err(e) => {
-                    *status.lock().expect("lock shouldn't panic") =
-                        UpdateStatus::Error(e.to_string())
-                }
-            }
-        }
-        Err(e) => {
-            *status.lock().expect("lock shouldn't panic") = UpdateStatus::Error(e.to_string())
-        }
-    }
+        .build()
+        .and_then(|updater| updater.get_latest_release())
+        .map_err(|e| e.to_string())?;
+
+    // Check if remote version > local version
+    let is_newer =
+        self_update::version::bump_is_greater(clean_version, &release.version).unwrap_or(false);
+    Ok(is_newer.then_some(release.version))
+}
+
+async fn perform_update(status: Arc<Mutex<UpdateStatus>>, shutdown: CancellationToken) {
+    // The download itself can't be interrupted; shutting down just stops waiting for it
+    let install = tokio::task::spawn_blocking(|| install_update().map_err(|e| e.to_string()));
+    let result = tokio::select! {
+        result = install => result,
+        _ = shutdown.cancelled() => return,
+    };
+    *status.lock().expect("lock shouldn't panic") = match result {
+        Ok(Ok(())) => UpdateStatus::RestartRequired,
+        Ok(Err(e)) => UpdateStatus::Error(e),
+        Err(e) => UpdateStatus::Error(e.to_string()),
+    };
 }
 
-fn perform_update() -> Result<(), Box<dyn std::error::Error>> {
+fn install_update() -> Result<(), Box<dyn std::error::Error>> {
     let target_str = get_asset_target_string();
 
     self_update::backends::github::Update::configure()
@@ -926,7 +1717,7 @@ fn perform_update() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-async fn open_webpage_when_ready() {
+async fn open_webpage_when_ready(shutdown: CancellationToken) {
     let client = Client::new();
     let query_payload = r#"{"query": "query AllCategories { categories { nodes { mangas { nodes { title } } } } }"}"#;
 
@@ -958,21 +1749,84 @@ async fn open_webpage_when_ready() {
         }
     };
 
-    if tokio::time::timeout(Duration::from_secs(10), polling_task)
-        .await
-        .is_err()
-    {
-        error!("❌ Timed out waiting for server readiness (10s). Browser open cancelled.");
+    tokio::select! {
+        result = tokio::time::timeout(Duration::from_secs(10), polling_task) => {
+            if result.is_err() {
+                error!("❌ Timed out waiting for server readiness (10s). Browser open cancelled.");
+            }
+        }
+        _ = shutdown.cancelled() => {}
     }
 }
 
+/// Polls Suwayomi directly until it answers GraphQL, then closes out the startup timings.
+/// Keeps probing at a slower pace afterwards so `/readyz` reflects whether it's still up, until
+/// `shutdown` is cancelled.
+async fn probe_suwayomi(
+    startup: StartupTracker,
+    health: Health,
+    spawned_at: Instant,
+    shutdown: CancellationToken,
+) {
+    let client = Client::new();
+    let query_payload = r#"{"query": "query { aboutServer { name } }"}"#;
+    let mut started = false;
+
+    loop {
+        let response = client
+            .post("http://127.0.0.1:4567/api/graphql")
+            .header("Content-Type", "application/json")
+            .body(query_payload)
+            .send()
+            .await;
+
+        match response {
+            Ok(resp) if resp.status().is_success() || resp.status() == StatusCode::UNAUTHORIZED => {
+                health.record_suwayomi_ok();
+                if !started {
+                    started = true;
+                    startup.record(SUWAYOMI_READY_PHASE, spawned_at.elapsed());
+                    startup.finish();
+                }
+            }
+            Ok(resp) => debug!(target: SUWAYOMI_TARGET, status = resp.status().as_u16(), "probe"),
+            Err(err) => debug!(target: SUWAYOMI_TARGET, "probe failed: {err}"),
+        }
+
+        let interval = match started {
+            true => health::PROBE_INTERVAL,
+            false => Duration::from_millis(250),
+        };
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = shutdown.cancelled() => return,
+        }
+    }
+}
+
+async fn startup_timings_handler(State(startup): State<StartupTracker>) -> impl IntoResponse {
+    axum::Json(serde_json::json!({
+        "current": startup.current(),
+        "previous": startup.previous(),
+    }))
+}
+
 async fn current_version_handler() -> impl IntoResponse {
+    // Same source as /api/version, so both report the same build
+    let build = build_info::current();
     axum::Json(VersionResponse {
-        version: APP_VERSION.to_string(),
+        version: build.version.clone(),
         variant: "desktop".to_string(), // Frontend will see 'desktop' and HIDE the button
+        target: build.target.clone(),
+        commit: build.git_hash.clone(),
     })
 }
 
+/// The same build info the OCR and yomitan status endpoints embed.
+async fn build_info_handler() -> impl IntoResponse {
+    axum::Json(build_info::current())
+}
+
 fn is_flatpak() -> bool {
     std::env::var("FLATPAK_ID").is_ok()
 }
@@ -0,0 +1,437 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    Json,
+    body::{Body, to_bytes},
+    extract::{Query, Request, State},
+    http::{
+        HeaderValue, StatusCode,
+        header::{CONTENT_LENGTH, CONTENT_TYPE},
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use reqwest::{Client, redirect::Policy, tls::TlsInfo};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tracing::{info, warn};
+
+/// Where the latest report is served, for the hints that point at it.
+pub const REPORT_PATH: &str = "/api/diagnostics/network";
+/// Plain HTTP hosts whose `Date` header the system clock is compared against. HTTP, because a
+/// skewed clock is exactly what breaks HTTPS.
+const TIME_HOSTS: &[&str] = &["http://www.google.com/", "http://www.cloudflare.com/"];
+/// Offsets below this are ordinary drift.
+const SKEW_THRESHOLD: Duration = Duration::from_secs(5 * 60);
+const TIMEOUT: Duration = Duration::from_secs(10);
+// Response bodies larger than this aren't OCR errors
+const MAX_ERROR_BODY: usize = 64 * 1024;
+
+/// An HTTPS service Mangatan depends on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Service {
+    /// Google Lens, used for OCR.
+    Lens,
+    /// GitHub, used by the update check.
+    Github,
+}
+
+impl Service {
+    const ALL: [Self; 2] = [Self::Lens, Self::Github];
+
+    fn host(self) -> &'static str {
+        match self {
+            Self::Lens => "lensfrontend-pa.googleapis.com",
+            Self::Github => "api.github.com",
+        }
+    }
+}
+
+/// Something specific that's wrong with the network, as opposed to the service being down.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Finding {
+    /// The system clock is off; positive when it runs ahead.
+    ClockSkew {
+        minutes: i64,
+    },
+    DnsFailure {
+        service: Service,
+        error: String,
+    },
+    /// The certificate wasn't issued by a public CA, so something on the way re-signs traffic
+    /// (antivirus, a corporate proxy).
+    ProxyInterception {
+        service: Service,
+        issuer: String,
+    },
+    TlsFailure {
+        service: Service,
+        error: String,
+    },
+}
+
+impl Finding {
+    pub fn message(&self) -> String {
+        match self {
+            Self::ClockSkew { minutes } => {
+                let direction = if *minutes > 0 { "ahead" } else { "behind" };
+                format!(
+                    "The system clock is {} minutes {direction}; fix the date and time",
+                    minutes.abs()
+                )
+            }
+            Self::DnsFailure { service, .. } => {
+                format!(
+                    "{} can't be resolved; check the DNS settings",
+                    service.host()
+                )
+            }
+            Self::ProxyInterception { service, issuer } => format!(
+                "The connection to {} is intercepted (certificate issued by {issuer}), probably \
+                 by antivirus or a proxy",
+                service.host()
+            ),
+            Self::TlsFailure { service, .. } => {
+                format!("The secure connection to {} fails", service.host())
+            }
+        }
+    }
+
+    fn affects(&self, service: Service) -> bool {
+        match self {
+            Self::ClockSkew { .. } => true,
+            Self::DnsFailure { service: s, .. }
+            | Self::ProxyInterception { service: s, .. }
+            | Self::TlsFailure { service: s, .. } => *s == service,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct HostCheck {
+    pub service: Service,
+    pub host: &'static str,
+    pub dns_ok: bool,
+    pub tls_ok: bool,
+    /// Issuer of the certificate the host presented, verified or not.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct NetworkReport {
+    /// Unix time of the check.
+    pub checked_at: u64,
+    /// Seconds the system clock is ahead of the time hosts; `None` when none answered.
+    pub clock_skew_secs: Option<i64>,
+    pub hosts: Vec<HostCheck>,
+    pub findings: Vec<Finding>,
+}
+
+/// Checks for the network problems that otherwise only show up as opaque TLS errors in the
+/// update check and OCR. Runs at startup; the latest report backs the GUI and OCR error hints.
+#[derive(Clone, Default)]
+pub struct NetworkDiagnostics {
+    latest: Arc<Mutex<Option<NetworkReport>>>,
+}
+
+impl NetworkDiagnostics {
+    pub fn latest(&self) -> Option<NetworkReport> {
+        self.latest.lock().expect("lock shouldn't panic").clone()
+    }
+
+    /// Runs every check and keeps the result as the latest report.
+    pub async fn run(&self) -> NetworkReport {
+        let clock_skew_secs = clock_skew().await;
+        let mut hosts = Vec::new();
+        for service in Service::ALL {
+            hosts.push(check_host(service).await);
+        }
+
+        let mut findings = Vec::new();
+        let clock_skewed =
+            clock_skew_secs.is_some_and(|skew| skew.unsigned_abs() >= SKEW_THRESHOLD.as_secs());
+        if let Some(skew) = clock_skew_secs.filter(|_| clock_skewed) {
+            findings.push(Finding::ClockSkew { minutes: skew / 60 });
+        }
+        for check in &hosts {
+            let service = check.service;
+            let error = check.error.clone().unwrap_or_default();
+            if !check.dns_ok {
+                findings.push(Finding::DnsFailure { service, error });
+            } else if !check.tls_ok {
+                // The issuer only tells something when the clock isn't to blame
+                let unknown_issuer = error.to_lowercase().contains("unknownissuer");
+                match &check.issuer {
+                    Some(issuer) if unknown_issuer && !clock_skewed => {
+                        findings.push(Finding::ProxyInterception {
+                            service,
+                            issuer: issuer.clone(),
+                        });
+                    }
+                    _ => findings.push(Finding::TlsFailure { service, error }),
+                }
+            }
+        }
+
+        let report = NetworkReport {
+            checked_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs()),
+            clock_skew_secs,
+            hosts,
+            findings,
+        };
+        *self.latest.lock().expect("lock shouldn't panic") = Some(report.clone());
+        report
+    }
+
+    /// Runs the checks and logs what they found.
+    pub async fn run_and_log(self) {
+        let report = self.run().await;
+        if report.findings.is_empty() {
+            info!("🌐 Network check found no problems");
+        }
+        for finding in &report.findings {
+            warn!("🌐 Network problem: {}", finding.message());
+        }
+    }
+
+    /// A finding that explains `error` from talking to `service`, if the latest report has one
+    /// and the error is a connection failure at all.
+    pub fn explain(&self, service: Service, error: &str) -> Option<String> {
+        if !is_connection_error(error) {
+            return None;
+        }
+        let latest = self.latest.lock().expect("lock shouldn't panic");
+        latest
+            .as_ref()?
+            .findings
+            .iter()
+            .find(|finding| finding.affects(service))
+            .map(Finding::message)
+    }
+}
+
+fn is_connection_error(error: &str) -> bool {
+    let error = error.to_lowercase();
+    [
+        "error sending request",
+        "connect",
+        "certificate",
+        "tls",
+        "handshake",
+        "dns",
+        "resolve",
+        "timed out",
+    ]
+    .iter()
+    .any(|needle| error.contains(needle))
+}
+
+/// Median offset of the system clock against the time hosts.
+async fn clock_skew() -> Option<i64> {
+    let client = Client::builder()
+        .timeout(TIMEOUT)
+        .redirect(Policy::none())
+        .build()
+        .ok()?;
+    let mut skews = Vec::new();
+    for url in TIME_HOSTS {
+        let Ok(response) = client.head(*url).send().await else {
+            continue;
+        };
+        let Some(date) = response
+            .headers()
+            .get(reqwest::header::DATE)
+            .and_then(|date| date.to_str().ok())
+            .and_then(|date| httpdate::parse_http_date(date).ok())
+        else {
+            continue;
+        };
+        let skew = match SystemTime::now().duration_since(date) {
+            Ok(ahead) => ahead.as_secs() as i64,
+            Err(behind) => -(behind.duration().as_secs() as i64),
+        };
+        skews.push(skew);
+    }
+    skews.sort_unstable();
+    skews.get(skews.len() / 2).copied()
+}
+
+async fn check_host(service: Service) -> HostCheck {
+    let host = service.host();
+    let mut check = HostCheck {
+        service,
+        host,
+        dns_ok: false,
+        tls_ok: false,
+        issuer: None,
+        error: None,
+    };
+
+    // Through a proxy the proxy resolves the name, so a local failure means nothing
+    let proxied = ["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]
+        .iter()
+        .any(|var| std::env::var_os(var).is_some());
+    match tokio::net::lookup_host((host, 443)).await {
+        Ok(_) => check.dns_ok = true,
+        Err(err) if !proxied => {
+            check.error = Some(err.to_string());
+            return check;
+        }
+        Err(_) => check.dns_ok = true,
+    }
+
+    let url = format!("https://{host}/");
+    match head_with_issuer(&url, false).await {
+        Ok(issuer) => {
+            check.tls_ok = true;
+            check.issuer = issuer;
+        }
+        Err(err) => {
+            check.error = Some(err);
+            // Skipping verification is fine here: nothing is sent, only the issuer is read
+            check.issuer = head_with_issuer(&url, true).await.ok().flatten();
+        }
+    }
+    check
+}
+
+/// HEADs `url` and returns the issuer of the certificate the server presented. Any HTTP status
+/// counts as success; only the connection matters.
+async fn head_with_issuer(url: &str, accept_invalid: bool) -> Result<Option<String>, String> {
+    let client = Client::builder()
+        .timeout(TIMEOUT)
+        .redirect(Policy::none())
+        .tls_info(true)
+        .danger_accept_invalid_certs(accept_invalid)
+        .build()
+        .map_err(|err| err.to_string())?;
+    let response = client.head(url).send().await.map_err(|err| {
+        // reqwest's own message leaves out the rustls cause
+        let mut message = err.to_string();
+        let mut source = std::error::Error::source(&err);
+        while let Some(cause) = source {
+            message.push_str(&format!(": {cause}"));
+            source = cause.source();
+        }
+        message
+    })?;
+    Ok(response
+        .extensions()
+        .get::<TlsInfo>()
+        .and_then(TlsInfo::peer_certificate)
+        .and_then(certificate_issuer))
+}
+
+/// Splits one DER element off `input`: its tag, its contents and what follows it.
+fn der_next(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = match first & 0x80 {
+        0 => (first as usize, rest),
+        _ => {
+            let octets = (first & 0x7f) as usize;
+            if octets == 0 || octets > 4 || rest.len() < octets {
+                return None;
+            }
+            let (len, rest) = rest.split_at(octets);
+            (len.iter().fold(0, |acc, b| acc << 8 | *b as usize), rest)
+        }
+    };
+    (rest.len() >= len).then(|| (tag, &rest[..len], &rest[len..]))
+}
+
+/// Common name (or else organization) of the issuer of a DER certificate.
+fn certificate_issuer(der: &[u8]) -> Option<String> {
+    const COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+    const ORGANIZATION: &[u8] = &[0x55, 0x04, 0x0a];
+
+    let (_, certificate, _) = der_next(der)?;
+    let (_, tbs, _) = der_next(certificate)?;
+    // Optional explicit version, then serial number and signature algorithm
+    let (tag, _, mut rest) = der_next(tbs)?;
+    if tag == 0xa0 {
+        (_, _, rest) = der_next(rest)?;
+    }
+    let (_, _, rest) = der_next(rest)?;
+    let (_, mut names, _) = der_next(rest)?;
+
+    let mut attributes = Vec::new();
+    while let Some((_, set, next)) = der_next(names) {
+        if let Some((_, attribute, _)) = der_next(set)
+            && let Some((_, oid, value)) = der_next(attribute)
+            && let Some((_, value, _)) = der_next(value)
+        {
+            attributes.push((oid, String::from_utf8_lossy(value).into_owned()));
+        }
+        names = next;
+    }
+    [COMMON_NAME, ORGANIZATION].iter().find_map(|wanted| {
+        attributes
+            .iter()
+            .find(|(oid, _)| oid == wanted)
+            .map(|(_, value)| value.clone())
+    })
+}
+
+#[derive(Deserialize)]
+pub struct ReportParams {
+    /// Runs the checks again instead of returning the startup report.
+    #[serde(default)]
+    refresh: bool,
+}
+
+/// `GET /api/diagnostics/network[?refresh=true]`
+pub async fn report_handler(
+    State(diagnostics): State<NetworkDiagnostics>,
+    Query(params): Query<ReportParams>,
+) -> impl IntoResponse {
+    let report = match diagnostics.latest() {
+        Some(report) if !params.refresh => report,
+        _ => diagnostics.run().await,
+    };
+    Json(report)
+}
+
+/// Adds the matching finding to Lens failures from the OCR server, so the client can show why
+/// OCR fails instead of a bare TLS error.
+pub async fn annotate_ocr_errors(
+    State(diagnostics): State<NetworkDiagnostics>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(req).await;
+    if response.status() != StatusCode::BAD_GATEWAY {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_ERROR_BODY).await else {
+        return (StatusCode::BAD_GATEWAY, "Bad Gateway").into_response();
+    };
+    let Ok(mut error) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    let hint = error["message"]
+        .as_str()
+        .and_then(|message| diagnostics.explain(Service::Lens, message));
+    let Some(hint) = hint else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+
+    error["network_finding"] = json!({ "message": hint, "details": REPORT_PATH });
+    let mut response = Response::from_parts(parts, Body::from(error.to_string()));
+    let headers = response.headers_mut();
+    headers.remove(CONTENT_LENGTH);
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
@@ -0,0 +1,294 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    Json,
+    body::{Body, HttpBody},
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, Uri, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::{Value, json};
+use tracing::{error, info, warn};
+
+pub const RECORDING_FILE: &str = "http-recording.ndjson";
+/// Recorded JSON bodies are cut off after this many bytes.
+const MAX_RECORDED_BODY: usize = 4 * 1024;
+/// JSON bodies larger than this (or of unknown length) pass through without being recorded.
+const MAX_BUFFERED_BODY: u64 = 1024 * 1024;
+const REDACTED: &str = "[redacted]";
+const SENSITIVE_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+];
+/// Query params and JSON keys whose lowercased name contains one of these are redacted.
+const SENSITIVE_KEYS: &[&str] = &[
+    "password",
+    "passwd",
+    "token",
+    "secret",
+    "auth",
+    "cookie",
+    "apikey",
+    "api_key",
+    "session",
+    "credential",
+];
+
+/// Opt-in log of API traffic for reproducing frontend bugs: one NDJSON line per request with
+/// method, path, status, timing and (for JSON only) truncated bodies. Credentials are redacted
+/// before anything touches the disk.
+#[derive(Clone)]
+pub struct Recorder {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    active: AtomicBool,
+    file: Mutex<Option<File>>,
+    path: PathBuf,
+}
+
+#[derive(Serialize)]
+struct Exchange {
+    ts_ms: u128,
+    method: String,
+    path: String,
+    status: u16,
+    duration_ms: f64,
+    request_headers: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_body: Option<String>,
+}
+
+impl Recorder {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                active: AtomicBool::new(false),
+                file: Mutex::new(None),
+                path: data_dir.join(RECORDING_FILE),
+            }),
+        }
+    }
+
+    /// Starts a fresh recording that stops by itself after `duration`.
+    pub fn start(&self, duration: Duration) -> io::Result<()> {
+        let file = File::create(&self.inner.path)?;
+        *self.inner.file.lock().expect("lock shouldn't panic") = Some(file);
+        self.inner.active.store(true, Ordering::Relaxed);
+        warn!(
+            "⏺️ Recording API requests to {} for {} min",
+            self.inner.path.display(),
+            duration.as_secs() / 60
+        );
+
+        let recorder = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(duration).await;
+            recorder.stop();
+        });
+        Ok(())
+    }
+
+    /// Ends the recording; the file stays for download. Returns whether one was running.
+    pub fn stop(&self) -> bool {
+        if !self.inner.active.swap(false, Ordering::Relaxed) {
+            return false;
+        }
+        self.inner.file.lock().expect("lock shouldn't panic").take();
+        info!("⏹️ Stopped recording API requests");
+        true
+    }
+
+    fn is_active(&self) -> bool {
+        self.inner.active.load(Ordering::Relaxed)
+    }
+
+    fn append(&self, exchange: &Exchange) {
+        let Ok(mut line) = serde_json::to_vec(exchange) else {
+            return;
+        };
+        line.push(b'\n');
+        if let Some(file) = self
+            .inner
+            .file
+            .lock()
+            .expect("lock shouldn't panic")
+            .as_mut()
+            && let Err(e) = file.write_all(&line)
+        {
+            error!("❌ Failed to write request recording: {e}");
+        }
+    }
+}
+
+/// Middleware that records each exchange while a recording runs. When it doesn't, the only cost
+/// is one atomic load.
+pub async fn record(State(recorder): State<Recorder>, req: Request, next: Next) -> Response {
+    if !recorder.is_active() || req.uri().path().starts_with("/api/debug/") {
+        return next.run(req).await;
+    }
+
+    let started = Instant::now();
+    let method = req.method().to_string();
+    let path = redact_query(req.uri());
+    let request_headers = redact_headers(req.headers());
+    let (parts, body) = req.into_parts();
+    let (body, request_body) = capture_json(&parts.headers, body).await;
+
+    let response = next.run(Request::from_parts(parts, body)).await;
+    let (parts, body) = response.into_parts();
+    let (body, response_body) = capture_json(&parts.headers, body).await;
+
+    recorder.append(&Exchange {
+        ts_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis()),
+        method,
+        path,
+        status: parts.status.as_u16(),
+        duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+        request_headers,
+        request_body,
+        response_body,
+    });
+    Response::from_parts(parts, body)
+}
+
+/// Buffers a JSON body of known, modest size and returns it along with its sanitized text.
+/// Images and other binary bodies are never read.
+async fn capture_json(headers: &HeaderMap, body: Body) -> (Body, Option<String>) {
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("json"));
+    let fits = body
+        .size_hint()
+        .upper()
+        .is_some_and(|len| len <= MAX_BUFFERED_BODY);
+    if !is_json || !fits {
+        return (body, None);
+    }
+
+    match axum::body::to_bytes(body, MAX_BUFFERED_BODY as usize).await {
+        Ok(bytes) => {
+            let text = sanitize_json(&bytes);
+            (Body::from(bytes), Some(text))
+        }
+        Err(e) => (Body::empty(), Some(format!("[unreadable body: {e}]"))),
+    }
+}
+
+fn is_sensitive(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SENSITIVE_KEYS.iter().any(|needle| key.contains(needle))
+}
+
+fn redact_headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = match SENSITIVE_HEADERS.contains(&name.as_str()) {
+                true => REDACTED.to_string(),
+                false => String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+fn redact_query(uri: &Uri) -> String {
+    let Some(query) = uri.query() else {
+        return uri.path().to_string();
+    };
+    let query: Vec<String> = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((key, _)) if is_sensitive(key) => format!("{key}={REDACTED}"),
+            _ => pair.to_string(),
+        })
+        .collect();
+    format!("{}?{}", uri.path(), query.join("&"))
+}
+
+fn sanitize_json(bytes: &[u8]) -> String {
+    let text = match serde_json::from_slice::<Value>(bytes) {
+        Ok(mut value) => {
+            redact_value(&mut value);
+            value.to_string()
+        }
+        Err(_) => String::from_utf8_lossy(bytes).into_owned(),
+    };
+    truncate(text, MAX_RECORDED_BODY)
+}
+
+fn redact_value(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match is_sensitive(key) {
+                    true => *value = json!(REDACTED),
+                    false => redact_value(value),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_value),
+        _ => {}
+    }
+}
+
+fn truncate(mut text: String, max_bytes: usize) -> String {
+    if text.len() > max_bytes {
+        let mut end = max_bytes;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("…[truncated]");
+    }
+    text
+}
+
+/// `GET /api/debug/recording`: the current (or last) recording as an NDJSON download.
+pub async fn download_handler(State(recorder): State<Recorder>) -> Response {
+    match tokio::fs::read(&recorder.inner.path).await {
+        Ok(bytes) => (
+            [
+                (header::CONTENT_TYPE, "application/x-ndjson"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"http-recording.ndjson\"",
+                ),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(_) => (
+            StatusCode::NOT_FOUND,
+            "No recording; start Mangatan with --record-requests",
+        )
+            .into_response(),
+    }
+}
+
+/// `POST /api/debug/recording/stop`
+pub async fn stop_handler(State(recorder): State<Recorder>) -> impl IntoResponse {
+    Json(json!({ "stopped": recorder.stop() }))
+}
@@ -0,0 +1,131 @@
+use std::time::Instant;
+
+use axum::{Json, extract::State};
+use mangatan_ocr_server::{error::ApiError, logic, merge::MergeConfig};
+use mangatan_yomitan_server::ServerState;
+use serde::Serialize;
+use tracing::{info, warn};
+
+/// Sample page for the OCR step; it reads 日本.
+static SAMPLE_IMAGE: &[u8] = include_bytes!("../resources/selftest.png");
+/// What the sample says, looked up when OCR fails so the later steps still run.
+const SAMPLE_TEXT: &str = "日本";
+
+#[derive(Serialize)]
+pub struct StepReport {
+    pub step: &'static str,
+    pub ok: bool,
+    pub duration_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl StepReport {
+    fn new(step: &'static str, started: Instant, result: Result<String, String>) -> Self {
+        let (detail, error) = match result {
+            Ok(detail) => (Some(detail), None),
+            Err(error) => (None, Some(error)),
+        };
+        Self {
+            step,
+            ok: error.is_none(),
+            duration_ms: started.elapsed().as_millis(),
+            detail,
+            error,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SelfTestReport {
+    /// Whether every step passed.
+    pub ok: bool,
+    pub steps: Vec<StepReport>,
+}
+
+/// OCRs the sample page, bypassing the cache so Lens is really asked.
+async fn ocr_sample() -> Result<String, String> {
+    let raw_chunks = logic::get_raw_ocr_data(SAMPLE_IMAGE, None, None)
+        .await
+        .map_err(|e| ApiError::from(e).to_string())?;
+    let results = logic::merge_raw_chunks(raw_chunks, &MergeConfig::from_env());
+    let text = logic::results_to_plain_text(&results);
+    match text.trim() {
+        "" => Err("Lens answered but found no text in the sample".to_string()),
+        text => Ok(text.to_string()),
+    }
+}
+
+/// Segments `text` and looks its first word up in the enabled dictionaries.
+async fn look_up(state: ServerState, text: String) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let lookup = state
+            .lookup
+            .get()
+            .ok_or("The dictionary engine is still loading")?;
+        let token = lookup
+            .token_at(&text, 0)
+            .map(|range| text[range].to_string())
+            .ok_or("Nothing to look up")?;
+        let entries = lookup.search(&state.app, &text, 0);
+        match entries.len() {
+            0 => Err(format!(
+                "No dictionary entries for \"{token}\"; is a dictionary installed and enabled?"
+            )),
+            count => Ok(format!("\"{token}\": {count} entries")),
+        }
+    })
+    .await
+    .map_err(|e| format!("Lookup task failed: {e}"))?
+}
+
+async fn ping_anki(state: &ServerState) -> Result<String, String> {
+    if !state.anki.is_enabled() {
+        return Err("Anki integration is disabled; set MANGATAN_YOMITAN_ANKI_CHECK=1".to_string());
+    }
+    let version = state.anki.version().await.map_err(|e| e.to_string())?;
+    Ok(format!("AnkiConnect version {version}"))
+}
+
+/// `POST /api/mining-selftest`: runs OCR, lookup and the AnkiConnect ping in turn and reports
+/// each, so a new setup can be checked end to end in one go.
+pub async fn selftest_handler(State(state): State<ServerState>) -> Json<SelfTestReport> {
+    let mut steps = Vec::new();
+
+    let started = Instant::now();
+    let ocr = ocr_sample().await;
+    let text = match &ocr {
+        Ok(text) => text.clone(),
+        Err(_) => SAMPLE_TEXT.to_string(),
+    };
+    steps.push(StepReport::new(
+        "ocr",
+        started,
+        ocr.map(|text| format!("Recognized \"{text}\"")),
+    ));
+
+    let started = Instant::now();
+    steps.push(StepReport::new(
+        "lookup",
+        started,
+        look_up(state.clone(), text).await,
+    ));
+
+    let started = Instant::now();
+    steps.push(StepReport::new("anki", started, ping_anki(&state).await));
+
+    let ok = steps.iter().all(|step| step.ok);
+    for step in steps.iter().filter(|step| !step.ok) {
+        warn!(
+            "🧪 Mining self-test: {} failed: {}",
+            step.step,
+            step.error.as_deref().unwrap_or_default()
+        );
+    }
+    if ok {
+        info!("🧪 Mining self-test passed");
+    }
+    Json(SelfTestReport { ok, steps })
+}
@@ -0,0 +1,123 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+const TIMINGS_FILE: &str = "startup-timings.json";
+
+/// Phase name used for the span between spawning the JVM and Suwayomi answering GraphQL.
+pub const SUWAYOMI_READY_PHASE: &str = "suwayomi_ready";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PhaseTiming {
+    pub name: String,
+    pub ms: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StartupTimings {
+    pub phases: Vec<PhaseTiming>,
+    /// Launch-to-ready time. Unset until Suwayomi has answered its first request.
+    pub total_ms: Option<u64>,
+}
+
+impl StartupTimings {
+    pub fn phase_ms(&self, name: &str) -> Option<u64> {
+        self.phases.iter().find(|p| p.name == name).map(|p| p.ms)
+    }
+
+    /// Short human readable form for the GUI, e.g. `12.4s (JVM 9.1s)`.
+    pub fn summary(&self) -> Option<String> {
+        let total = self.total_ms?;
+        let secs = |ms: u64| ms as f64 / 1000.0;
+        Some(match self.phase_ms(SUWAYOMI_READY_PHASE) {
+            Some(jvm) => format!("{:.1}s (JVM {:.1}s)", secs(total), secs(jvm)),
+            None => format!("{:.1}s", secs(total)),
+        })
+    }
+}
+
+/// Collects per-phase startup durations and persists them so the next launch can compare.
+#[derive(Clone)]
+pub struct StartupTracker {
+    started: Instant,
+    path: PathBuf,
+    current: Arc<Mutex<StartupTimings>>,
+    previous: Option<StartupTimings>,
+}
+
+impl StartupTracker {
+    pub fn new(data_dir: &Path) -> Self {
+        let path = data_dir.join(TIMINGS_FILE);
+        let previous = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<StartupTimings>(&bytes).ok());
+
+        Self {
+            started: Instant::now(),
+            path,
+            current: Arc::new(Mutex::new(StartupTimings::default())),
+            previous,
+        }
+    }
+
+    pub fn record(&self, name: &str, elapsed: Duration) {
+        let ms = elapsed.as_millis() as u64;
+        info!("⏱️ Startup phase '{name}' took {ms}ms");
+        self.current
+            .lock()
+            .expect("lock shouldn't panic")
+            .phases
+            .push(PhaseTiming {
+                name: name.to_string(),
+                ms,
+            });
+    }
+
+    pub fn time<T>(&self, name: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(name, start.elapsed());
+        result
+    }
+
+    /// Marks startup as complete, logs a comparison with the last run and saves the timings.
+    pub fn finish(&self) {
+        let total_ms = self.started.elapsed().as_millis() as u64;
+        let snapshot = {
+            let mut current = self.current.lock().expect("lock shouldn't panic");
+            current.total_ms = Some(total_ms);
+            current.clone()
+        };
+
+        match self.previous.as_ref().and_then(|p| p.total_ms) {
+            Some(prev) if total_ms > prev.saturating_mul(3) / 2 => {
+                warn!("🐢 Startup took {total_ms}ms (previous run: {prev}ms)");
+            }
+            Some(prev) => info!("⏱️ Startup took {total_ms}ms (previous run: {prev}ms)"),
+            None => info!("⏱️ Startup took {total_ms}ms"),
+        }
+
+        match serde_json::to_vec_pretty(&snapshot) {
+            Ok(bytes) => {
+                if let Err(err) = fs::write(&self.path, bytes) {
+                    warn!("Failed to save startup timings: {err}");
+                }
+            }
+            Err(err) => warn!("Failed to serialize startup timings: {err}"),
+        }
+    }
+
+    pub fn current(&self) -> StartupTimings {
+        self.current.lock().expect("lock shouldn't panic").clone()
+    }
+
+    pub fn previous(&self) -> Option<&StartupTimings> {
+        self.previous.as_ref()
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, anyhow};
+use mangatan_core::lan_access::lan_ip;
+use tracing::info;
+
+const TLS_DIR: &str = "tls";
+const CERT_FILE: &str = "cert.pem";
+const KEY_FILE: &str = "key.pem";
+// Names the persisted certificate was issued for, so it's only regenerated when they change
+const HOSTS_FILE: &str = "hosts.txt";
+
+pub const DEFAULT_TLS_PORT: u16 = 4569;
+
+/// Where the HTTPS listener gets its certificate from.
+#[derive(Clone, Debug)]
+pub struct TlsSetup {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    pub port: u16,
+    /// Set when we generated the certificate, so it's worth offering for export.
+    pub self_signed: bool,
+}
+
+impl TlsSetup {
+    /// User-provided certificate and key, used as-is.
+    pub fn provided(cert_path: PathBuf, key_path: PathBuf, port: u16) -> anyhow::Result<Self> {
+        for path in [&cert_path, &key_path] {
+            if !path.is_file() {
+                return Err(anyhow!("TLS file not found: {}", path.display()));
+            }
+        }
+        Ok(Self {
+            cert_path,
+            key_path,
+            port,
+            self_signed: false,
+        })
+    }
+
+    /// Loads the self-signed certificate under `data_dir/tls`, generating a new one when it's
+    /// missing or was issued for a different set of hostnames.
+    pub fn self_signed(data_dir: &Path, extra_hosts: &[String], port: u16) -> anyhow::Result<Self> {
+        let dir = data_dir.join(TLS_DIR);
+        let cert_path = dir.join(CERT_FILE);
+        let key_path = dir.join(KEY_FILE);
+        let hosts_path = dir.join(HOSTS_FILE);
+
+        let hosts = certificate_hosts(extra_hosts);
+        let hosts_line = hosts.join("\n");
+        let up_to_date = cert_path.is_file()
+            && key_path.is_file()
+            && fs::read_to_string(&hosts_path).is_ok_and(|saved| saved == hosts_line);
+
+        if !up_to_date {
+            info!(
+                "🔐 Generating self-signed certificate for {}",
+                hosts.join(", ")
+            );
+            let rcgen::CertifiedKey { cert, key_pair } =
+                rcgen::generate_simple_self_signed(hosts.clone())
+                    .context("Failed to generate self-signed certificate")?;
+
+            fs::create_dir_all(&dir).context("Failed to create TLS dir")?;
+            fs::write(&cert_path, cert.pem()).context("Failed to write certificate")?;
+            write_private(&key_path, key_pair.serialize_pem().as_bytes())
+                .context("Failed to write private key")?;
+            fs::write(&hosts_path, &hosts_line).context("Failed to write TLS hosts")?;
+        }
+
+        Ok(Self {
+            cert_path,
+            key_path,
+            port,
+            self_signed: true,
+        })
+    }
+
+    /// URL other devices on the LAN should use; falls back to localhost without a LAN address.
+    pub fn https_url(&self) -> String {
+        let host = lan_ip().map_or_else(|| "localhost".to_string(), |ip| ip.to_string());
+        format!("https://{host}:{}", self.port)
+    }
+}
+
+/// localhost, the LAN address and any configured names, deduplicated in a stable order.
+fn certificate_hosts(extra_hosts: &[String]) -> Vec<String> {
+    let mut hosts = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+    if let Some(ip) = lan_ip() {
+        hosts.push(ip.to_string());
+    }
+    hosts.extend(
+        extra_hosts
+            .iter()
+            .map(|h| h.trim().to_string())
+            .filter(|h| !h.is_empty()),
+    );
+
+    let mut seen = std::collections::HashSet::new();
+    hosts.retain(|h| seen.insert(h.clone()));
+    hosts
+}
+
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    fs::write(path, bytes)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+
+    Ok(())
+}
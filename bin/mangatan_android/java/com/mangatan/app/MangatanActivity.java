@@ -1,21 +1,219 @@
-package com.mangatan.app;
-
-import android.app.NativeActivity;
-import android.content.Intent;
-import android.util.Log;
-
-public class MangatanActivity extends NativeActivity {
-    @Override
-    public void onDestroy() {
-        Log.d("Mangatan", "MangatanActivity onDestroy - Force killing process to prevent ANR");
-        
-        // 1. Stop the service explicitly
-        Intent serviceIntent = new Intent(this, MangatanService.class);
-        stopService(serviceIntent);
-        
-        // 2. Kill the process immediately. 
-        // We do not call super.onDestroy() because it waits for the native thread, which causes the hang.
-        android.os.Process.killProcess(android.os.Process.myPid());
-        System.exit(0);
-    }
+package com.mangatan.app;
+
+import android.app.NativeActivity;
+import android.app.UiModeManager;
+import android.content.ActivityNotFoundException;
+import android.content.Intent;
+import android.content.res.Configuration;
+import android.graphics.Bitmap;
+import android.graphics.BitmapFactory;
+import android.net.Uri;
+import android.os.Build;
+import android.util.Log;
+
+import java.io.ByteArrayOutputStream;
+import java.io.IOException;
+import java.io.InputStream;
+
+public class MangatanActivity extends NativeActivity {
+    // --- Photo Picker (OCR from image) ---
+    // Results are handed to the native side through these fields, which it polls from the UI loop.
+    public static final int PICK_IDLE = 0;
+    public static final int PICK_PENDING = 1;
+    public static final int PICK_DONE = 2;
+    public static final int PICK_CANCELLED = 3;
+    public static final int PICK_ERROR = 4;
+
+    private static final int PICK_IMAGE_REQUEST = 200;
+    // Long edge cap for uploads; camera photos are far larger than OCR needs
+    private static final int MAX_IMAGE_EDGE = 2048;
+
+    private static volatile int pickState = PICK_IDLE;
+    private static volatile byte[] pickedImage = null;
+    private static volatile String pickError = null;
+
+    public void pickImageForOcr() {
+        pickState = PICK_PENDING;
+        pickedImage = null;
+        pickError = null;
+
+        runOnUiThread(new Runnable() {
+            @Override
+            public void run() {
+                launchPicker();
+            }
+        });
+    }
+
+    private void launchPicker() {
+        Intent intent;
+        if (Build.VERSION.SDK_INT >= 33) {
+            // System photo picker, no storage permission required
+            intent = new Intent("android.provider.action.PICK_IMAGES");
+        } else {
+            intent = new Intent(Intent.ACTION_OPEN_DOCUMENT);
+            intent.addCategory(Intent.CATEGORY_OPENABLE);
+        }
+        intent.setType("image/*");
+
+        try {
+            startActivityForResult(intent, PICK_IMAGE_REQUEST);
+        } catch (ActivityNotFoundException e) {
+            failPick("No photo picker available");
+        }
+    }
+
+    public static int pollPickState() {
+        return pickState;
+    }
+
+    public static byte[] takePickedImage() {
+        byte[] image = pickedImage;
+        pickedImage = null;
+        pickState = PICK_IDLE;
+        return image;
+    }
+
+    public static String takePickError() {
+        String error = pickError;
+        pickError = null;
+        pickState = PICK_IDLE;
+        return error;
+    }
+
+    @Override
+    protected void onActivityResult(int requestCode, int resultCode, Intent data) {
+        if (requestCode != PICK_IMAGE_REQUEST) {
+            super.onActivityResult(requestCode, resultCode, data);
+            return;
+        }
+
+        final Uri uri = (resultCode == RESULT_OK && data != null) ? data.getData() : null;
+        if (uri == null) {
+            pickState = PICK_CANCELLED;
+            return;
+        }
+
+        // Decoding a full-size camera image can take a moment, keep it off the UI thread
+        new Thread(new Runnable() {
+            @Override
+            public void run() {
+                try {
+                    pickedImage = readScaledJpeg(uri);
+                    pickState = PICK_DONE;
+                } catch (SecurityException e) {
+                    failPick("Permission to read the image was revoked");
+                } catch (IOException e) {
+                    failPick("Could not read the image: " + e.getMessage());
+                }
+            }
+        }).start();
+    }
+
+    private static void failPick(String message) {
+        Log.w("Mangatan", "Photo picker: " + message);
+        pickError = message;
+        pickState = PICK_ERROR;
+    }
+
+    private byte[] readScaledJpeg(Uri uri) throws IOException {
+        BitmapFactory.Options bounds = new BitmapFactory.Options();
+        bounds.inJustDecodeBounds = true;
+        try (InputStream in = getContentResolver().openInputStream(uri)) {
+            if (in == null) throw new IOException("Empty stream");
+            BitmapFactory.decodeStream(in, null, bounds);
+        }
+        if (bounds.outWidth <= 0 || bounds.outHeight <= 0) {
+            throw new IOException("Not an image");
+        }
+
+        int sampleSize = 1;
+        while (Math.max(bounds.outWidth, bounds.outHeight) / (sampleSize * 2) >= MAX_IMAGE_EDGE) {
+            sampleSize *= 2;
+        }
+
+        BitmapFactory.Options options = new BitmapFactory.Options();
+        options.inSampleSize = sampleSize;
+        Bitmap bitmap;
+        try (InputStream in = getContentResolver().openInputStream(uri)) {
+            if (in == null) throw new IOException("Empty stream");
+            bitmap = BitmapFactory.decodeStream(in, null, options);
+        }
+        if (bitmap == null) {
+            throw new IOException("Failed to decode image");
+        }
+
+        int longEdge = Math.max(bitmap.getWidth(), bitmap.getHeight());
+        if (longEdge > MAX_IMAGE_EDGE) {
+            float scale = (float) MAX_IMAGE_EDGE / longEdge;
+            Bitmap scaled = Bitmap.createScaledBitmap(
+                    bitmap,
+                    Math.round(bitmap.getWidth() * scale),
+                    Math.round(bitmap.getHeight() * scale),
+                    true);
+            bitmap.recycle();
+            bitmap = scaled;
+        }
+
+        ByteArrayOutputStream out = new ByteArrayOutputStream();
+        bitmap.compress(Bitmap.CompressFormat.JPEG, 90, out);
+        bitmap.recycle();
+        return out.toByteArray();
+    }
+
+    // --- TV / D-pad ---
+    // Asked by the native UI, which scales itself up for a screen across the room
+    public boolean isTelevision() {
+        UiModeManager uiModeManager = (UiModeManager) getSystemService(UI_MODE_SERVICE);
+        return uiModeManager != null
+                && uiModeManager.getCurrentModeType() == Configuration.UI_MODE_TYPE_TELEVISION;
+    }
+
+    // Finishing the activity kills the process, server included (see onDestroy), so Back only
+    // leaves for the home screen
+    public void moveToBackground() {
+        runOnUiThread(new Runnable() {
+            @Override
+            public void run() {
+                moveTaskToBack(true);
+            }
+        });
+    }
+
+    @Override
+    public void onBackPressed() {
+        moveTaskToBack(true);
+    }
+
+    // Implemented in the native library; logs the session's OCR/dictionary stats
+    private static native void logSessionSummary();
+
+    // Implemented in the native library; lets a running dictionary write commit first
+    private static native void flushForShutdown();
+
+    @Override
+    public void onDestroy() {
+        Log.d("Mangatan", "MangatanActivity onDestroy - Force killing process to prevent ANR");
+
+        try {
+            logSessionSummary();
+        } catch (UnsatisfiedLinkError e) {
+            Log.w("Mangatan", "Session summary unavailable: " + e.getMessage());
+        }
+
+        try {
+            flushForShutdown();
+        } catch (UnsatisfiedLinkError e) {
+            Log.w("Mangatan", "Database flush unavailable: " + e.getMessage());
+        }
+        
+        // 1. Stop the service explicitly
+        Intent serviceIntent = new Intent(this, MangatanService.class);
+        stopService(serviceIntent);
+        
+        // 2. Kill the process immediately. 
+        // We do not call super.onDestroy() because it waits for the native thread, which causes the hang.
+        android.os.Process.killProcess(android.os.Process.myPid());
+        System.exit(0);
+    }
 }
\ No newline at end of file
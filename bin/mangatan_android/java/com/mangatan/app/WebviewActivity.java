@@ -18,6 +18,8 @@ import android.widget.Toast;
 public class WebviewActivity extends Activity {
     private WebView myWebView;
     private static final String TARGET_URL = "http://127.0.0.1:4568";
+    // Optional WebUI route to open instead of the root (e.g. the OCR view for a picked photo)
+    public static final String EXTRA_PATH = "path";
     
     // --- File Chooser Variables ---
     private ValueCallback<Uri[]> uploadMessage;
@@ -81,7 +83,12 @@ public class WebviewActivity extends Activity {
             }
         });
 
-        myWebView.loadUrl(TARGET_URL);
+        String path = getIntent().getStringExtra(EXTRA_PATH);
+        if (path != null && path.startsWith("/")) {
+            myWebView.loadUrl(TARGET_URL + path);
+        } else {
+            myWebView.loadUrl(TARGET_URL);
+        }
     }
 
     // --- ADD THIS: Handle the result from the System File Picker ---
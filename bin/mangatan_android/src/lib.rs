@@ -3,10 +3,11 @@ use axum::{
     Json, Router,
     body::{Body, Bytes},
     extract::{
-        FromRequestParts, Request, State,
+        ConnectInfo, FromRequestParts, Request, State,
         ws::{Message, WebSocket, WebSocketUpgrade},
     },
-    http::{HeaderMap, Method, StatusCode, Uri},
+    http::{HeaderMap, Method, StatusCode, Uri, header},
+    middleware::{self, Next},
     response::{IntoResponse, Response},
     routing::any,
 };
@@ -14,12 +15,16 @@ use eframe::egui;
 use flate2::read::GzDecoder;
 use futures::{SinkExt, StreamExt};
 use jni::{
-    JavaVM,
-    objects::{JObject, JString, JValue},
+    JNIEnv, JavaVM,
+    objects::{JByteArray, JClass, JObject, JString, JValue},
     signature::{Primitive, ReturnType},
     sys::{JNI_VERSION_1_6, jint, jobject},
 };
 use lazy_static::lazy_static;
+use mangatan_core::{
+    build_info::{self, BuildInfo},
+    lan_access::{self, LanAccess},
+};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::AtomicI64;
@@ -28,14 +33,15 @@ use std::{
     ffi::{CString, c_void},
     fs::{self, File},
     io::{self, BufReader},
+    net::{IpAddr, SocketAddr},
     os::unix::io::FromRawFd,
     path::{Path, PathBuf},
     sync::{
-        Arc, Mutex,
+        Arc, Mutex, OnceLock, RwLock,
         atomic::{AtomicBool, Ordering},
     },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tar::Archive;
 use tokio::{fs as tokio_fs, net::TcpListener};
@@ -46,8 +52,9 @@ use tokio_tungstenite::{
         protocol::{Message as TungsteniteMessage, frame::coding::CloseCode},
     },
 };
+use tokio_util::io::ReaderStream;
 use tower_http::cors::{AllowOrigin, CorsLayer};
-use tracing::{error, info, trace};
+use tracing::{error, info, trace, warn};
 use tracing_log::LogTracer;
 use tracing_subscriber::{EnvFilter, fmt::MakeWriter};
 use winit::platform::android::{EventLoopBuilderExtAndroid, activity::AndroidApp};
@@ -56,6 +63,26 @@ lazy_static! {
     static ref LOG_BUFFER: Mutex<VecDeque<String>> = Mutex::new(VecDeque::with_capacity(500));
 }
 
+/// App settings in the files dir; for now the LAN login.
+const SETTINGS_FILE: &str = "settings.json";
+/// How often the launcher re-reads the Wi-Fi address for the LAN link.
+const LAN_IP_REFRESH: Duration = Duration::from_secs(10);
+/// Side of the LAN link's QR code, in points.
+const QR_SIZE: f32 = 200.0;
+
+/// Cargo features reported in the build info.
+const APP_FEATURES: &[&str] = &[
+    #[cfg(feature = "native_webview")]
+    "native_webview",
+];
+
+// Server states kept for the shutdown summary; the activity kills the process on destroy, so
+// there's no graceful shutdown to hook into.
+static SESSION_STATE: OnceLock<(
+    mangatan_ocr_server::state::AppState,
+    mangatan_yomitan_server::ServerState,
+)> = OnceLock::new();
+
 struct GuiWriter;
 impl io::Write for GuiWriter {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
@@ -190,8 +217,54 @@ type JniCreateJavaVM = unsafe extern "system" fn(
     args: *mut c_void,
 ) -> jint;
 
+// WebUI route that shows the OCR overlay for an uploaded image
+const PHOTO_OCR_VIEW_PATH: &str = "/ocr-view";
+
+// Mirrors the PICK_* constants in MangatanActivity.java
+const PICK_PENDING: i32 = 1;
+const PICK_DONE: i32 = 2;
+const PICK_ERROR: i32 = 4;
+
+// Launcher scale on a TV, where it's read from across the room with a D-pad
+const TV_ZOOM_FACTOR: f32 = 1.75;
+const DPAD_KEYS: [egui::Key; 5] = [
+    egui::Key::ArrowUp,
+    egui::Key::ArrowDown,
+    egui::Key::ArrowLeft,
+    egui::Key::ArrowRight,
+    egui::Key::Enter,
+];
+
+#[derive(Clone, Debug)]
+enum PhotoOcrStatus {
+    Idle,
+    Picking,
+    Uploading,
+    Ready(String),
+    Failed(String),
+}
+
+enum PickOutcome {
+    Pending,
+    Image(Vec<u8>),
+    Cancelled,
+    Error(String),
+}
+
+#[derive(Deserialize)]
+struct OcrUploadResponse {
+    cache_key: String,
+}
+
 struct MangatanApp {
     server_ready: Arc<AtomicBool>,
+    android_app: AndroidApp,
+    photo_ocr: Arc<Mutex<PhotoOcrStatus>>,
+    lan_access: Arc<RwLock<LanAccess>>,
+    settings_path: PathBuf,
+    // The Wi-Fi address, and when it was last looked up
+    lan_ip: Option<IpAddr>,
+    lan_ip_checked: Instant,
     #[cfg(feature = "native_webview")]
     webview_launcher: Box<dyn Fn() + Send + Sync>,
     #[cfg(feature = "native_webview")]
@@ -200,18 +273,229 @@ struct MangatanApp {
 
 impl MangatanApp {
     fn new(
-        _cc: &eframe::CreationContext<'_>,
+        cc: &eframe::CreationContext<'_>,
         server_ready: Arc<AtomicBool>,
+        android_app: AndroidApp,
+        lan_access: Arc<RwLock<LanAccess>>,
+        settings_path: PathBuf,
         #[cfg(feature = "native_webview")] webview_launcher: Box<dyn Fn() + Send + Sync>,
     ) -> Self {
+        let television = is_television(&android_app).unwrap_or_else(|e| {
+            warn!("Failed to read the UI mode: {:?}", e);
+            false
+        });
+        if television {
+            info!("📺 TV UI mode, scaling the launcher for D-pad use");
+            cc.egui_ctx.set_zoom_factor(TV_ZOOM_FACTOR);
+            cc.egui_ctx.style_mut(|style| {
+                style.spacing.button_padding = egui::vec2(16.0, 10.0);
+                style.spacing.item_spacing.y += 8.0;
+            });
+        }
+
         Self {
             server_ready,
+            android_app,
+            photo_ocr: Arc::new(Mutex::new(PhotoOcrStatus::Idle)),
+            lan_access,
+            settings_path,
+            lan_ip: lan_access::lan_ip(),
+            lan_ip_checked: Instant::now(),
             #[cfg(feature = "native_webview")]
             webview_launcher,
             #[cfg(feature = "native_webview")]
             webview_launched: false,
         }
     }
+
+    fn photo_ocr_ui(&mut self, ui: &mut egui::Ui) {
+        let status = self.photo_ocr.lock().expect("lock").clone();
+        match status {
+            PhotoOcrStatus::Idle | PhotoOcrStatus::Ready(_) => {
+                if focus_ring(
+                    ui.add(
+                        egui::Button::new("📷 OCR from Image").min_size(egui::vec2(200.0, 50.0)),
+                    ),
+                )
+                .clicked()
+                {
+                    info!("User clicked OCR from Image");
+                    match launch_photo_picker(&self.android_app) {
+                        Ok(()) => *self.photo_ocr.lock().expect("lock") = PhotoOcrStatus::Picking,
+                        Err(e) => {
+                            error!("Failed to launch photo picker: {:?}", e);
+                            *self.photo_ocr.lock().expect("lock") =
+                                PhotoOcrStatus::Failed("Could not open the photo picker".into());
+                        }
+                    }
+                }
+            }
+            PhotoOcrStatus::Picking => {
+                ui.spinner();
+                ui.label("Waiting for a photo...");
+            }
+            PhotoOcrStatus::Uploading => {
+                ui.spinner();
+                ui.label("Running OCR...");
+            }
+            PhotoOcrStatus::Failed(message) => {
+                ui.colored_label(egui::Color32::RED, message);
+                if focus_ring(ui.button("OK")).clicked() {
+                    *self.photo_ocr.lock().expect("lock") = PhotoOcrStatus::Idle;
+                }
+            }
+        }
+    }
+
+    /// The link other devices on the Wi-Fi open Mangatan with, as text and a QR code, and the
+    /// switch for requiring a login. With the login on, the link carries the token, so scanning
+    /// it logs the device in.
+    fn lan_access_ui(&mut self, ui: &mut egui::Ui) {
+        if self.lan_ip_checked.elapsed() > LAN_IP_REFRESH {
+            self.lan_ip = lan_access::lan_ip();
+            self.lan_ip_checked = Instant::now();
+        }
+        let Some(ip) = self.lan_ip else {
+            ui.weak("Connect to Wi-Fi to open Mangatan on other devices");
+            return;
+        };
+
+        let mut access = self.lan_access.read().expect("lock").clone();
+        let mut required = access.required;
+        let checkbox = egui::Checkbox::new(&mut required, "Require a login for other devices");
+        if focus_ring(ui.add_enabled(!access.token.is_empty(), checkbox)).changed() {
+            access.required = required;
+            if let Err(e) = access.save(&self.settings_path) {
+                error!("Failed to save {SETTINGS_FILE}: {e}");
+            }
+            info!("LAN login required: {required}");
+            *self.lan_access.write().expect("lock") = access.clone();
+        }
+
+        let base = format!("http://{ip}:4568");
+        let url = match access.required {
+            true => access.login_url(&base),
+            false => base,
+        };
+        ui.label("Open on another device:");
+        ui.monospace(&url);
+        qr_code(ui, &url);
+    }
+
+    /// D-pad and remote keys. egui moves focus with the arrows and clicks the focused button on
+    /// Enter, but only once something has focus, so the first key press focuses the first button.
+    fn handle_remote_input(&self, ctx: &egui::Context) {
+        let (dpad, back) = ctx.input(|i| {
+            (
+                DPAD_KEYS.iter().any(|key| i.key_pressed(*key)),
+                i.key_pressed(egui::Key::BrowserBack),
+            )
+        });
+        if back {
+            info!("Back pressed, moving to the background");
+            if let Err(e) = move_to_background(&self.android_app) {
+                error!("Failed to move to the background: {:?}", e);
+            }
+        }
+        if dpad && ctx.memory(|m| m.focused().is_none()) {
+            ctx.memory_mut(|m| m.move_focus(egui::FocusDirection::Next));
+        }
+    }
+
+    fn poll_photo_ocr(&mut self, ctx: &egui::Context) {
+        let status = self.photo_ocr.lock().expect("lock").clone();
+        match status {
+            PhotoOcrStatus::Picking => {
+                ctx.request_repaint_after(Duration::from_millis(200));
+                let next = match poll_photo_picker(&self.android_app) {
+                    Ok(PickOutcome::Pending) => return,
+                    Ok(PickOutcome::Cancelled) => {
+                        info!("Photo picker cancelled");
+                        PhotoOcrStatus::Idle
+                    }
+                    Ok(PickOutcome::Error(message)) => PhotoOcrStatus::Failed(message),
+                    Ok(PickOutcome::Image(bytes)) => {
+                        info!("Photo picked ({} bytes), uploading for OCR...", bytes.len());
+                        let photo_ocr = self.photo_ocr.clone();
+                        thread::spawn(move || {
+                            let next = match upload_photo_for_ocr(bytes) {
+                                Ok(cache_key) => PhotoOcrStatus::Ready(cache_key),
+                                Err(e) => {
+                                    error!("Photo OCR failed: {}", e);
+                                    PhotoOcrStatus::Failed(e)
+                                }
+                            };
+                            *photo_ocr.lock().expect("lock") = next;
+                        });
+                        PhotoOcrStatus::Uploading
+                    }
+                    Err(e) => {
+                        error!("Failed to poll photo picker: {:?}", e);
+                        PhotoOcrStatus::Failed("Lost track of the photo picker".into())
+                    }
+                };
+                *self.photo_ocr.lock().expect("lock") = next;
+            }
+            PhotoOcrStatus::Uploading => {
+                ctx.request_repaint_after(Duration::from_millis(200));
+            }
+            PhotoOcrStatus::Ready(cache_key) => {
+                *self.photo_ocr.lock().expect("lock") = PhotoOcrStatus::Idle;
+                let path = format!(
+                    "{}?key={}",
+                    PHOTO_OCR_VIEW_PATH,
+                    cache_key.replace('/', "%2F")
+                );
+                if cfg!(feature = "native_webview") {
+                    launch_webview_activity(&self.android_app, Some(&path));
+                } else {
+                    ctx.open_url(egui::OpenUrl::new_tab(format!(
+                        "http://127.0.0.1:4568{}",
+                        path
+                    )));
+                }
+            }
+            PhotoOcrStatus::Idle | PhotoOcrStatus::Failed(_) => {}
+        }
+    }
+}
+
+/// Draws `text` as a QR code, black on white with the quiet zone scanners expect.
+fn qr_code(ui: &mut egui::Ui, text: &str) {
+    let Ok(code) = qrcode::QrCode::new(text) else {
+        return;
+    };
+    const QUIET_ZONE: usize = 4;
+    let width = code.width();
+    let module = QR_SIZE / (width + 2 * QUIET_ZONE) as f32;
+    let (response, painter) =
+        ui.allocate_painter(egui::vec2(QR_SIZE, QR_SIZE), egui::Sense::hover());
+    painter.rect_filled(response.rect, 0.0, egui::Color32::WHITE);
+    for (i, color) in code.to_colors().into_iter().enumerate() {
+        if color != qrcode::Color::Dark {
+            continue;
+        }
+        let (x, y) = (i % width + QUIET_ZONE, i / width + QUIET_ZONE);
+        let min = response.rect.min + egui::vec2(x as f32 * module, y as f32 * module);
+        painter.rect_filled(
+            egui::Rect::from_min_size(min, egui::vec2(module, module)),
+            0.0,
+            egui::Color32::BLACK,
+        );
+    }
+}
+
+/// Outlines `response`'s widget while it has keyboard focus, which egui barely shows otherwise.
+fn focus_ring(response: egui::Response) -> egui::Response {
+    if response.has_focus() {
+        let color = response.ctx.style().visuals.selection.stroke.color;
+        response.ctx.layer_painter(response.layer_id).rect_stroke(
+            response.rect.expand(4.0),
+            6.0,
+            egui::Stroke::new(3.0, color),
+        );
+    }
+    response
 }
 
 impl eframe::App for MangatanApp {
@@ -220,6 +504,8 @@ impl eframe::App for MangatanApp {
         if !is_ready {
             ctx.request_repaint_after(Duration::from_millis(100));
         }
+        self.handle_remote_input(ctx);
+        self.poll_photo_ocr(ctx);
 
         // --- NATIVE WEBVIEW MODE ---
         #[cfg(feature = "native_webview")]
@@ -244,9 +530,13 @@ impl eframe::App for MangatanApp {
                         // Minimal UI in case user backs out of WebView
                         ui.heading("Mangatan is Running");
                         ui.add_space(20.0);
-                        if ui.button("Return to App").clicked() {
+                        if focus_ring(ui.button("Return to App")).clicked() {
                             (self.webview_launcher)();
                         }
+                        ui.add_space(20.0);
+                        self.photo_ocr_ui(ui);
+                        ui.add_space(20.0);
+                        self.lan_access_ui(ui);
                     }
                 });
             });
@@ -258,6 +548,7 @@ impl eframe::App for MangatanApp {
             ui.vertical_centered(|ui| {
                 ui.add_space(20.0);
                 ui.heading(egui::RichText::new("Mangatan").size(32.0).strong());
+                ui.weak(build_info::current().to_string());
                 ui.add_space(20.0);
 
                 if is_ready {
@@ -274,22 +565,31 @@ impl eframe::App for MangatanApp {
                 }
                 ui.add_space(20.0);
 
-                if ui
-                    .add(egui::Button::new("Open WebUI").min_size(egui::vec2(200.0, 50.0)))
-                    .clicked()
+                if focus_ring(
+                    ui.add(egui::Button::new("Open WebUI").min_size(egui::vec2(200.0, 50.0))),
+                )
+                .clicked()
                 {
                     ctx.open_url(egui::OpenUrl::new_tab("http://127.0.0.1:4568"));
                     info!("User clicked Open WebUI");
                 }
 
                 ui.add_space(10.0);
-                if ui
-                    .add(egui::Button::new("Join our Discord").min_size(egui::vec2(200.0, 50.0)))
-                    .clicked()
+                if focus_ring(
+                    ui.add(egui::Button::new("Join our Discord").min_size(egui::vec2(200.0, 50.0))),
+                )
+                .clicked()
                 {
                     ctx.open_url(egui::OpenUrl::new_tab("https://discord.gg/tDAtpPN8KK"));
                     info!("User clicked Discord");
                 }
+
+                if is_ready {
+                    ui.add_space(10.0);
+                    self.photo_ocr_ui(ui);
+                    ui.add_space(20.0);
+                    self.lan_access_ui(ui);
+                }
             });
 
             ui.add_space(20.0);
@@ -316,12 +616,39 @@ impl eframe::App for MangatanApp {
     }
 }
 
+/// Called from `MangatanActivity.onDestroy` right before the process is killed.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_mangatan_app_MangatanActivity_logSessionSummary(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    if let Some((ocr_state, yomitan_state)) = SESSION_STATE.get() {
+        ocr_state.log_session_summary();
+        yomitan_state.log_session_summary();
+    }
+}
+
+/// Called from `MangatanActivity.onDestroy` before the process is killed. Saves unsaved OCR
+/// results and gives a running dictionary write a moment to commit, short enough to stay clear
+/// of an ANR.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_mangatan_app_MangatanActivity_flushForShutdown(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    if let Some((ocr_state, yomitan_state)) = SESSION_STATE.get() {
+        ocr_state.flush_cache();
+        yomitan_state.app.flush_for_shutdown(Duration::from_secs(2));
+    }
+}
+
 #[unsafe(no_mangle)]
 fn android_main(app: AndroidApp) {
     init_tracing();
     redirect_stdout_to_gui();
+    build_info::init(BuildInfo::compiled("android").with_features(APP_FEATURES));
 
-    info!("Starting Mangatan...");
+    info!("Starting Mangatan {}...", build_info::current());
 
     check_and_request_permissions(&app);
 
@@ -343,6 +670,18 @@ fn android_main(app: AndroidApp) {
     let files_dir = app.internal_data_path().expect("Failed to get data path");
     let files_dir_clone = files_dir.clone();
 
+    let settings_path = files_dir.join(SETTINGS_FILE);
+    let lan_access = LanAccess::load_or_create(&settings_path).unwrap_or_else(|e| {
+        // Without a token the login can't be turned on, so the server stays as open as before
+        error!("Failed to load {SETTINGS_FILE}: {e}");
+        LanAccess {
+            required: false,
+            token: String::new(),
+        }
+    });
+    let lan_access = Arc::new(RwLock::new(lan_access));
+    let lan_access_server = lan_access.clone();
+
     let server_ready = Arc::new(AtomicBool::new(false));
     let server_ready_bg = server_ready.clone();
     let server_ready_gui = server_ready.clone();
@@ -383,8 +722,8 @@ fn android_main(app: AndroidApp) {
         });
 
         rt.block_on(async move {
-            if let Err(e) = start_web_server(files_dir_clone).await {
-                error!("Web Server Crashed: {:?}", e);
+            if let Err(e) = start_web_server(files_dir_clone, lan_access_server).await {
+                error!("Web Server Crashed: {e}");
             }
         });
     });
@@ -414,6 +753,7 @@ fn android_main(app: AndroidApp) {
     }));
 
     let app_for_launcher = app.clone();
+    let app_for_ui = app.clone();
 
     eframe::run_native(
         "Mangatan",
@@ -422,12 +762,15 @@ fn android_main(app: AndroidApp) {
             // Setup the launcher closure
             #[cfg(feature = "native_webview")]
             let launcher = Box::new(move || {
-                launch_webview_activity(&app_for_launcher);
+                launch_webview_activity(&app_for_launcher, None);
             });
 
             Ok(Box::new(MangatanApp::new(
                 cc,
                 server_ready_gui,
+                app_for_ui,
+                lan_access,
+                settings_path,
                 #[cfg(feature = "native_webview")]
                 launcher,
             )))
@@ -438,7 +781,7 @@ fn android_main(app: AndroidApp) {
     });
 }
 
-fn launch_webview_activity(app: &AndroidApp) {
+fn launch_webview_activity(app: &AndroidApp, path: Option<&str>) {
     use jni::objects::{JObject, JValue};
 
     info!("🚀 Launching Native Webview Activity...");
@@ -477,6 +820,17 @@ fn launch_webview_activity(app: &AndroidApp) {
         )
         .expect("Failed to set class name");
 
+    if let Some(path) = path {
+        let key = env.new_string("path").unwrap();
+        let value = env.new_string(path).unwrap();
+        let _ = env.call_method(
+            &intent,
+            "putExtra",
+            "(Ljava/lang/String;Ljava/lang/String;)Landroid/content/Intent;",
+            &[JValue::Object(&key), JValue::Object(&value)],
+        );
+    }
+
     let _ = env
         .call_method(
             &context,
@@ -487,9 +841,122 @@ fn launch_webview_activity(app: &AndroidApp) {
         .expect("Failed to start Webview Activity");
 }
 
-async fn start_web_server(data_dir: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+fn launch_photo_picker(app: &AndroidApp) -> jni::errors::Result<()> {
+    let vm_ptr = app.vm_as_ptr() as *mut jni::sys::JavaVM;
+    let vm = unsafe { JavaVM::from_raw(vm_ptr)? };
+    let mut env = vm.attach_current_thread()?;
+    let activity = unsafe { JObject::from_raw(app.activity_as_ptr() as jobject) };
+
+    env.call_method(&activity, "pickImageForOcr", "()V", &[])?;
+    Ok(())
+}
+
+fn is_television(app: &AndroidApp) -> jni::errors::Result<bool> {
+    let vm_ptr = app.vm_as_ptr() as *mut jni::sys::JavaVM;
+    let vm = unsafe { JavaVM::from_raw(vm_ptr)? };
+    let mut env = vm.attach_current_thread()?;
+    let activity = unsafe { JObject::from_raw(app.activity_as_ptr() as jobject) };
+
+    env.call_method(&activity, "isTelevision", "()Z", &[])?.z()
+}
+
+fn move_to_background(app: &AndroidApp) -> jni::errors::Result<()> {
+    let vm_ptr = app.vm_as_ptr() as *mut jni::sys::JavaVM;
+    let vm = unsafe { JavaVM::from_raw(vm_ptr)? };
+    let mut env = vm.attach_current_thread()?;
+    let activity = unsafe { JObject::from_raw(app.activity_as_ptr() as jobject) };
+
+    env.call_method(&activity, "moveToBackground", "()V", &[])?;
+    Ok(())
+}
+
+fn poll_photo_picker(app: &AndroidApp) -> jni::errors::Result<PickOutcome> {
+    let vm_ptr = app.vm_as_ptr() as *mut jni::sys::JavaVM;
+    let vm = unsafe { JavaVM::from_raw(vm_ptr)? };
+    let mut env = vm.attach_current_thread()?;
+    let activity = unsafe { JObject::from_raw(app.activity_as_ptr() as jobject) };
+
+    // find_class can't see app classes from a native thread, so go through the instance
+    let activity_cls = env.get_object_class(&activity)?;
+    let state = env
+        .call_static_method(&activity_cls, "pollPickState", "()I", &[])?
+        .i()?;
+
+    match state {
+        PICK_PENDING => Ok(PickOutcome::Pending),
+        PICK_DONE => {
+            let image = env
+                .call_static_method(&activity_cls, "takePickedImage", "()[B", &[])?
+                .l()?;
+            if image.is_null() {
+                return Ok(PickOutcome::Error(
+                    "The photo picker returned no data".into(),
+                ));
+            }
+            let bytes = env.convert_byte_array(JByteArray::from(image))?;
+            Ok(PickOutcome::Image(bytes))
+        }
+        PICK_ERROR => {
+            let message = env
+                .call_static_method(&activity_cls, "takePickError", "()Ljava/lang/String;", &[])?
+                .l()?;
+            if message.is_null() {
+                return Ok(PickOutcome::Error("Failed to read the photo".into()));
+            }
+            let message: String = env.get_string(&JString::from(message))?.into();
+            Ok(PickOutcome::Error(message))
+        }
+        // PICK_CANCELLED, or idle because the result was lost (e.g. the activity was recreated)
+        _ => {
+            env.call_static_method(&activity_cls, "takePickError", "()Ljava/lang/String;", &[])?;
+            Ok(PickOutcome::Cancelled)
+        }
+    }
+}
+
+fn upload_photo_for_ocr(image: Vec<u8>) -> Result<String, String> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    rt.block_on(async move {
+        let part = reqwest::multipart::Part::bytes(image)
+            .file_name("photo.jpg")
+            .mime_str("image/jpeg")
+            .map_err(|e| e.to_string())?;
+        let form = reqwest::multipart::Form::new()
+            .part("file", part)
+            .text("context", "Photos");
+
+        let resp = Client::new()
+            .post("http://127.0.0.1:4568/api/ocr/ocr")
+            .multipart(form)
+            .send()
+            .await
+            .map_err(|e| format!("Upload failed: {}", e))?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("OCR failed ({}): {}", status, body));
+        }
+
+        let body: OcrUploadResponse = resp
+            .json()
+            .await
+            .map_err(|e| format!("Invalid OCR response: {}", e))?;
+        Ok(body.cache_key)
+    })
+}
+
+async fn start_web_server(
+    data_dir: PathBuf,
+    lan_access: Arc<RwLock<LanAccess>>,
+) -> Result<(), Box<dyn std::error::Error>> {
     info!("🚀 Initializing Axum Proxy Server on port 4568...");
-    let ocr_router = mangatan_ocr_server::create_router(data_dir.clone());
+    let ocr_state = mangatan_ocr_server::state::AppState::new(data_dir.clone());
+    let ocr_router = mangatan_ocr_server::create_router_with_state(ocr_state.clone());
 
     #[cfg(feature = "native_webview")]
     let auto_install_yomitan = true;
@@ -501,13 +968,21 @@ async fn start_web_server(data_dir: PathBuf) -> Result<(), Box<dyn std::error::E
         "📚 Initializing Yomitan Server (Auto-Install: {})...",
         auto_install_yomitan
     );
-    let yomitan_router =
-        mangatan_yomitan_server::create_router(data_dir.clone(), auto_install_yomitan);
+    let yomitan_state = mangatan_yomitan_server::ServerState::new(data_dir.clone())?;
+    let yomitan_router = mangatan_yomitan_server::create_router_with_state(
+        yomitan_state.clone(),
+        auto_install_yomitan,
+    );
+    let _ = SESSION_STATE.set((ocr_state, yomitan_state));
 
     let webui_dir = data_dir.join("webui");
     let client = Client::new();
 
-    let state = AppState { client, webui_dir };
+    let state = AppState {
+        client,
+        webui_dir,
+        lan_access,
+    };
 
     let cors = CorsLayer::new()
         .allow_origin(AllowOrigin::mirror_request())
@@ -543,8 +1018,13 @@ async fn start_web_server(data_dir: PathBuf) -> Result<(), Box<dyn std::error::E
                 .route("/download-update", any(download_update_handler))
                 .route("/install-update", any(install_update_handler)),
         )
+        .route("/api/version", any(build_info_handler))
         .merge(proxy_router)
         .fallback(serve_react_app)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_lan_token,
+        ))
         .layer(cors)
         .into();
 
@@ -552,32 +1032,128 @@ async fn start_web_server(data_dir: PathBuf) -> Result<(), Box<dyn std::error::E
 
     let listener = TcpListener::bind("0.0.0.0:4568").await?;
     info!("✅ Web Server listening on 0.0.0.0:4568");
-    axum::serve(listener, app_with_state).await?;
+    axum::serve(
+        listener,
+        app_with_state.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
     Ok(())
 }
 
+/// Turns other devices away while the LAN login is on and they don't have the token (see
+/// [`LanAccess`]). Opening the login link sets the token as a cookie and redirects to the page
+/// without it, so it doesn't linger in the address bar.
+async fn require_lan_token(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let access = state.lan_access.read().expect("lock").clone();
+    let header_value = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok());
+    let presented = lan_access::presented_token(
+        header_value(lan_access::TOKEN_HEADER),
+        header_value(header::COOKIE.as_str()),
+        req.uri().query(),
+    );
+    if !access.allows(peer.ip(), presented) {
+        info!(
+            "🔒 Refused {} {} from {}: no LAN login",
+            req.method(),
+            req.uri().path(),
+            peer.ip()
+        );
+        return (
+            StatusCode::UNAUTHORIZED,
+            "This Mangatan server requires a login. Scan the QR code in the Mangatan app.",
+        )
+            .into_response();
+    }
+
+    let from_link = lan_access::presented_token(None, None, req.uri().query())
+        .is_some_and(|token| access.matches(token));
+    if access.required && from_link && req.method() == Method::GET {
+        let prefix = format!("{}=", lan_access::TOKEN_PARAM);
+        let rest: Vec<&str> = req
+            .uri()
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|pair| !pair.is_empty() && !pair.starts_with(&prefix))
+            .collect();
+        let location = match rest.is_empty() {
+            true => req.uri().path().to_string(),
+            false => format!("{}?{}", req.uri().path(), rest.join("&")),
+        };
+        return (
+            StatusCode::SEE_OTHER,
+            [
+                (header::LOCATION, location),
+                (header::SET_COOKIE, access.cookie()),
+            ],
+        )
+            .into_response();
+    }
+    next.run(req).await
+}
+
+/// Largest file served from the WebUI dir; real assets are a few MB at most.
+const MAX_WEBUI_FILE_BYTES: u64 = 64 * 1024 * 1024;
+/// Opening a file on slow or failing storage shouldn't hold the request forever.
+const WEBUI_OPEN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Streams a file from the WebUI dir rather than reading it into memory first. `None` when
+/// there's no such file, so the caller can fall back to index.html.
+async fn webui_file_response(webui_dir: &Path, rel_path: &str) -> Option<Response> {
+    let open = async {
+        // Canonical paths keep `..` and symlinks from reaching outside the WebUI dir
+        let root = tokio_fs::canonicalize(webui_dir).await.ok()?;
+        let file_path = tokio_fs::canonicalize(root.join(rel_path)).await.ok()?;
+        if !file_path.starts_with(&root) {
+            return None;
+        }
+        let file = tokio_fs::File::open(&file_path).await.ok()?;
+        let metadata = file.metadata().await.ok()?;
+        metadata
+            .is_file()
+            .then(|| (file_path, file, metadata.len()))
+    };
+    let (file_path, file, len) = match tokio::time::timeout(WEBUI_OPEN_TIMEOUT, open).await {
+        Ok(found) => found?,
+        Err(_) => {
+            warn!("⚠️ Timed out opening WebUI file {rel_path}");
+            return Some(StatusCode::GATEWAY_TIMEOUT.into_response());
+        }
+    };
+    if len > MAX_WEBUI_FILE_BYTES {
+        warn!("⚠️ Refusing to serve WebUI file {rel_path} ({len} bytes)");
+        return Some(StatusCode::PAYLOAD_TOO_LARGE.into_response());
+    }
+
+    let mime = mime_guess::from_path(&file_path).first_or_octet_stream();
+    Some(
+        (
+            [
+                (axum::http::header::CONTENT_TYPE, mime.to_string()),
+                (axum::http::header::CONTENT_LENGTH, len.to_string()),
+                (
+                    axum::http::header::CACHE_CONTROL,
+                    "no-cache, no-store, must-revalidate".to_string(),
+                ),
+            ],
+            Body::from_stream(ReaderStream::new(file)),
+        )
+            .into_response(),
+    )
+}
+
 async fn serve_react_app(State(state): State<AppState>, uri: Uri) -> impl IntoResponse {
     let path_str = uri.path().trim_start_matches('/');
 
-    if !path_str.is_empty() {
-        let file_path = state.webui_dir.join(path_str);
-
-        if file_path.starts_with(&state.webui_dir) && file_path.exists() {
-            if let Ok(content) = tokio_fs::read(&file_path).await {
-                let mime = mime_guess::from_path(&file_path).first_or_octet_stream();
-                return (
-                    [
-                        (axum::http::header::CONTENT_TYPE, mime.as_ref()),
-                        (
-                            axum::http::header::CACHE_CONTROL,
-                            "no-cache, no-store, must-revalidate",
-                        ),
-                    ],
-                    content,
-                )
-                    .into_response();
-            }
-        }
+    if !path_str.is_empty()
+        && let Some(response) = webui_file_response(&state.webui_dir, path_str).await
+    {
+        return response;
     }
 
     let index_path = state.webui_dir.join("index.html");
@@ -768,6 +1344,8 @@ fn tungstenite_to_axum(msg: TungsteniteMessage) -> Message {
 }
 
 fn start_background_services(app: AndroidApp, files_dir: PathBuf) {
+    let startup_start = Instant::now();
+    let phase_start = Instant::now();
     let apk_time = get_apk_update_time(&app).unwrap_or(i64::MAX);
     let marker = files_dir.join(".extracted_apk_time");
 
@@ -805,6 +1383,7 @@ fn start_background_services(app: AndroidApp, files_dir: PathBuf) {
     } else {
         info!("Assets up-to-date, skipping extraction");
     }
+    log_startup_phase("extract_assets", phase_start);
 
     // Create 'bin' directory to satisfy Suwayomi's directory scanner
     let bin_dir = files_dir.join("bin");
@@ -840,7 +1419,9 @@ fn start_background_services(app: AndroidApp, files_dir: PathBuf) {
         error!("Failed to create temp dir: {:?}", e);
         return;
     }
+    let phase_start = Instant::now();
     let _ = copy_single_asset(&app, "Suwayomi-Server.jar", &jar_path);
+    log_startup_phase("copy_jar", phase_start);
 
     let lib_jli_path = find_file_in_dir(&jre_root, "libjli.so");
     if lib_jli_path.is_none() {
@@ -858,6 +1439,7 @@ fn start_background_services(app: AndroidApp, files_dir: PathBuf) {
 
     unsafe {
         info!("Loading JRE libraries...");
+        let phase_start = Instant::now();
 
         let _lib_jli = libloading::os::unix::Library::open(
             Some(&lib_jli_path),
@@ -898,6 +1480,7 @@ fn start_background_services(app: AndroidApp, files_dir: PathBuf) {
                 trace!("Library not found, skipping preload: {}", name);
             }
         }
+        log_startup_phase("load_jre_libraries", phase_start);
 
         let jar_path_abs = jar_path.canonicalize().unwrap_or(jar_path.clone());
         trace!("Classpath: {:?}", jar_path_abs);
@@ -950,6 +1533,7 @@ fn start_background_services(app: AndroidApp, files_dir: PathBuf) {
         };
 
         info!("Calling JNI_CreateJavaVM...");
+        let phase_start = Instant::now();
         let mut jvm: *mut jni::sys::JavaVM = std::ptr::null_mut();
         let mut env: *mut c_void = std::ptr::null_mut();
         let result = create_vm_fn(&mut jvm, &mut env, &mut vm_args as *mut _ as *mut c_void);
@@ -959,6 +1543,7 @@ fn start_background_services(app: AndroidApp, files_dir: PathBuf) {
             return;
         }
         trace!("JVM Created Successfully");
+        log_startup_phase("create_jvm", phase_start);
 
         let jvm_wrapper = JavaVM::from_raw(jvm).unwrap();
         let mut env = jvm_wrapper.attach_current_thread().unwrap();
@@ -1071,6 +1656,11 @@ fn start_background_services(app: AndroidApp, files_dir: PathBuf) {
             }
         };
 
+        // Main blocks for the lifetime of the server, so report the launch total up front
+        info!(
+            "⏱️ Startup reached Suwayomi main after {}ms",
+            startup_start.elapsed().as_millis()
+        );
         info!("Invoking Main...");
         if let Err(e) = env.call_static_method_unchecked(
             &main_class,
@@ -1084,6 +1674,13 @@ fn start_background_services(app: AndroidApp, files_dir: PathBuf) {
     }
 }
 
+fn log_startup_phase(name: &str, started: Instant) {
+    info!(
+        "⏱️ Startup phase '{name}' took {}ms",
+        started.elapsed().as_millis()
+    );
+}
+
 fn install_webui(app: &AndroidApp, target_dir: &Path) -> std::io::Result<()> {
     let filename = CString::new("mangatan-webui.tar").unwrap();
 
@@ -1159,6 +1756,7 @@ fn find_file_in_dir(dir: &Path, filename: &str) -> Option<PathBuf> {
 struct AppState {
     client: Client,
     webui_dir: PathBuf,
+    lan_access: Arc<RwLock<LanAccess>>,
 }
 
 fn ensure_battery_unrestricted(app: &AndroidApp) {
@@ -1655,6 +2253,11 @@ async fn current_version_handler() -> impl IntoResponse {
     })
 }
 
+/// The same build info the OCR and yomitan status endpoints embed.
+async fn build_info_handler() -> impl IntoResponse {
+    Json(build_info::current())
+}
+
 async fn download_update_handler(Json(payload): Json<UpdateRequest>) -> impl IntoResponse {
     match native_download_manager(&payload.url, &payload.filename) {
         Ok(_) => (StatusCode::OK, "Download started".to_string()),
@@ -7,6 +7,7 @@
 
 #import "AppDelegate.h"
 #import "ViewController.h" // Import to access forceReload
+#import "Mangatan-Bridging-Header.h"
 
 @interface AppDelegate ()
 @property (nonatomic, assign) UIBackgroundTaskIdentifier backgroundTask;
@@ -38,6 +39,11 @@
         [application endBackgroundTask:self.backgroundTask];
         self.backgroundTask = UIBackgroundTaskInvalid;
     }];
+
+    // Let a running dictionary import commit before iOS gets a chance to kill us mid-write.
+    dispatch_async(dispatch_get_global_queue(QOS_CLASS_UTILITY, 0), ^{
+        flush_for_background();
+    });
 }
 
 // 2. SAFE RESUME: Prevent the "Stale Connection crash"
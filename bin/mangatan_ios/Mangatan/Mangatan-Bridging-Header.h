@@ -8,4 +8,9 @@ void start_rust_server(const char* bundle_path, const char* docs_path, const cha
 
 bool is_server_ready(void);
 
+void flush_for_background(void);
+
+// Owned by Rust and valid until the process exits; don't free it.
+const char* build_info_summary(void);
+
 #endif
@@ -38,9 +38,16 @@
     UILabel *label = [[UILabel alloc] initWithFrame:CGRectMake(0, spinner.frame.origin.y + 50, self.view.bounds.size.width, 30)];
     label.text = @"Mangatan is starting...";
     label.textAlignment = NSTextAlignmentCenter;
+
+    UILabel *buildLabel = [[UILabel alloc] initWithFrame:CGRectMake(0, label.frame.origin.y + 36, self.view.bounds.size.width, 20)];
+    buildLabel.text = [NSString stringWithUTF8String:build_info_summary()];
+    buildLabel.textAlignment = NSTextAlignmentCenter;
+    buildLabel.font = [UIFont systemFontOfSize:12];
+    buildLabel.textColor = [UIColor secondaryLabelColor];
     
     [self.loadingView addSubview:spinner];
     [self.loadingView addSubview:label];
+    [self.loadingView addSubview:buildLabel];
     [self.view addSubview:self.loadingView];
 
     // 3. Start Polling Timer (Checks Rust every 1 second)
@@ -1,10 +1,13 @@
 // #![cfg(target_os = "ios")]
 use std::{
-    ffi::CStr,
+    ffi::{CStr, CString},
     net::SocketAddr,
     os::raw::c_char,
-    path::PathBuf,
-    sync::atomic::{AtomicBool, Ordering},
+    path::{Path, PathBuf},
+    sync::{
+        OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
     thread,
     time::Duration,
 };
@@ -21,6 +24,7 @@ use axum::{
     routing::any,
 };
 use futures::{SinkExt, StreamExt};
+use mangatan_core::build_info::{self, BuildInfo};
 use reqwest::Client;
 use serde::Serialize;
 use socket2::{Domain, Protocol, Socket, Type};
@@ -32,6 +36,7 @@ use tokio_tungstenite::{
         protocol::{Message as TungsteniteMessage, frame::coding::CloseCode},
     },
 };
+use tokio_util::io::ReaderStream;
 use tower_http::cors::{AllowOrigin, CorsLayer};
 use tracing::{error, info, warn};
 
@@ -51,11 +56,38 @@ struct VersionResponse {
 // Global state used by Objective-C to determine if it should show the WebView
 static SERVER_READY: AtomicBool = AtomicBool::new(false);
 
+// Kept so a trip to the background can flush the OCR cache and the dictionary database
+static OCR_STATE: OnceLock<mangatan_ocr_server::state::AppState> = OnceLock::new();
+static YOMITAN_STATE: OnceLock<mangatan_yomitan_server::ServerState> = OnceLock::new();
+
 #[unsafe(no_mangle)]
 pub extern "C" fn is_server_ready() -> bool {
     SERVER_READY.load(Ordering::Relaxed)
 }
 
+/// Called when the app moves to the background, where iOS may kill it without warning. Saves
+/// unsaved OCR results and blocks until a running dictionary write has committed (or a few
+/// seconds have passed), so call it off the main thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn flush_for_background() {
+    if let Some(ocr_state) = OCR_STATE.get() {
+        ocr_state.flush_cache();
+    }
+    if let Some(yomitan_state) = YOMITAN_STATE.get() {
+        yomitan_state.app.flush_for_shutdown(Duration::from_secs(5));
+    }
+}
+
+/// One line describing the build, for the loading screen. The string lives for the rest of the
+/// process; don't free it.
+#[unsafe(no_mangle)]
+pub extern "C" fn build_info_summary() -> *const c_char {
+    static SUMMARY: OnceLock<CString> = OnceLock::new();
+    SUMMARY
+        .get_or_init(|| CString::new(build_info::current().to_string()).unwrap_or_default())
+        .as_ptr()
+}
+
 #[allow(clippy::missing_safety_doc)]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn start_rust_server(
@@ -82,6 +114,8 @@ pub unsafe extern "C" fn start_rust_server(
     };
     let version_str = unsafe { CStr::from_ptr(version).to_str().unwrap().to_string() };
     let bundle = PathBuf::from(bundle_str);
+    build_info::init(BuildInfo::compiled("ios").with_version(version_str.clone()));
+    info!("📦 [RUST] Build {}", build_info::current());
 
     thread::spawn(move || {
         let rt = Runtime::new().expect("Should be able to get tokio runtime");
@@ -137,8 +171,13 @@ async fn start_web_server(
     app_version: String,
 ) -> Result<(), Box<dyn std::error::Error>> {
     info!("🚀 Initializing Axum Proxy Server on port 4568...");
-    let ocr_router = mangatan_ocr_server::create_router(data_dir.clone());
-    let yomitan_router = mangatan_yomitan_server::create_router(data_dir.clone(), true);
+    let ocr_state = mangatan_ocr_server::state::AppState::new(data_dir.clone());
+    let ocr_router = mangatan_ocr_server::create_router_with_state(ocr_state.clone());
+    let _ = OCR_STATE.set(ocr_state);
+    let yomitan_state = mangatan_yomitan_server::ServerState::new(data_dir.clone())?;
+    let yomitan_router =
+        mangatan_yomitan_server::create_router_with_state(yomitan_state.clone(), true);
+    let _ = YOMITAN_STATE.set(yomitan_state);
     let system_router = Router::new().route("/version", any(current_version_handler));
     let state = AppState {
         client: Client::new(),
@@ -173,6 +212,7 @@ async fn start_web_server(
         .nest_service("/api/ocr", ocr_router)
         .nest_service("/api/yomitan", yomitan_router)
         .nest("/api/system", system_router)
+        .route("/api/version", any(build_info_handler))
         .merge(proxy_router)
         .fallback(serve_react_app)
         .layer(cors);
@@ -197,19 +237,59 @@ async fn start_web_server(
     Ok(())
 }
 
+/// Largest file served from the WebUI dir; real assets are a few MB at most.
+const MAX_WEBUI_FILE_BYTES: u64 = 64 * 1024 * 1024;
+/// Opening a file on slow or failing storage shouldn't hold the request forever.
+const WEBUI_OPEN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Streams a file from the WebUI dir rather than reading it into memory first. `None` when
+/// there's no such file, so the caller can fall back to index.html.
+async fn webui_file_response(webui_dir: &Path, rel_path: &str) -> Option<Response> {
+    let open = async {
+        // Canonical paths keep `..` and symlinks from reaching outside the WebUI dir
+        let root = tokio_fs::canonicalize(webui_dir).await.ok()?;
+        let file_path = tokio_fs::canonicalize(root.join(rel_path)).await.ok()?;
+        if !file_path.starts_with(&root) {
+            return None;
+        }
+        let file = tokio_fs::File::open(&file_path).await.ok()?;
+        let metadata = file.metadata().await.ok()?;
+        metadata
+            .is_file()
+            .then(|| (file_path, file, metadata.len()))
+    };
+    let (file_path, file, len) = match tokio::time::timeout(WEBUI_OPEN_TIMEOUT, open).await {
+        Ok(found) => found?,
+        Err(_) => {
+            warn!("⚠️ Timed out opening WebUI file {rel_path}");
+            return Some(StatusCode::GATEWAY_TIMEOUT.into_response());
+        }
+    };
+    if len > MAX_WEBUI_FILE_BYTES {
+        warn!("⚠️ Refusing to serve WebUI file {rel_path} ({len} bytes)");
+        return Some(StatusCode::PAYLOAD_TOO_LARGE.into_response());
+    }
+
+    let mime = mime_guess::from_path(&file_path).first_or_octet_stream();
+    Some(
+        (
+            [
+                (axum::http::header::CONTENT_TYPE, mime.to_string()),
+                (axum::http::header::CONTENT_LENGTH, len.to_string()),
+            ],
+            Body::from_stream(ReaderStream::new(file)),
+        )
+            .into_response(),
+    )
+}
+
 async fn serve_react_app(State(state): State<AppState>, uri: Uri) -> impl IntoResponse {
     let path_str = uri.path().trim_start_matches('/');
 
-    if !path_str.is_empty() {
-        let file_path = state.webui_dir.join(path_str);
-
-        if file_path.starts_with(&state.webui_dir)
-            && file_path.exists()
-            && let Ok(content) = tokio_fs::read(&file_path).await
-        {
-            let mime = mime_guess::from_path(&file_path).first_or_octet_stream();
-            return ([(axum::http::header::CONTENT_TYPE, mime.as_ref())], content).into_response();
-        }
+    if !path_str.is_empty()
+        && let Some(response) = webui_file_response(&state.webui_dir, path_str).await
+    {
+        return response;
     }
 
     let index_path = state.webui_dir.join("index.html");
@@ -392,6 +472,11 @@ fn tungstenite_to_axum(msg: TungsteniteMessage) -> Message {
     }
 }
 
+/// The same build info the OCR and yomitan status endpoints embed.
+async fn build_info_handler() -> impl IntoResponse {
+    axum::Json(build_info::current())
+}
+
 async fn current_version_handler(State(state): State<AppState>) -> impl IntoResponse {
     let version = env!("CARGO_PKG_VERSION");
     axum::Json(VersionResponse {
@@ -0,0 +1,65 @@
+use std::{
+    env,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Plain `git` rather than vergen, so the mobile cross builds don't need libgit2.
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    let out = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (output.status.success() && !out.is_empty()).then_some(out)
+}
+
+/// `YYYY-MM-DD` for a Unix time, UTC.
+fn date(secs: u64) -> String {
+    // Howard Hinnant's civil_from_days
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=MANGATAN_VERSION");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    if let Some(git_dir) = git(&["rev-parse", "--absolute-git-dir"]) {
+        println!("cargo:rerun-if-changed={git_dir}/HEAD");
+        println!("cargo:rerun-if-changed={git_dir}/refs");
+    }
+
+    // Same tag the desktop build reports, so every binary agrees on the version
+    let version = env::var("MANGATAN_VERSION")
+        .ok()
+        .or_else(|| git(&["describe", "--tags", "--abbrev=0"]))
+        .unwrap_or_else(|| format!("v{}", env!("CARGO_PKG_VERSION")));
+    println!("cargo:rustc-env=MANGATAN_BUILD_VERSION={version}");
+
+    if let Some(hash) = git(&["rev-parse", "--short=10", "HEAD"]) {
+        println!("cargo:rustc-env=MANGATAN_BUILD_GIT_HASH={hash}");
+    }
+
+    // Reproducible builds pin the date through SOURCE_DATE_EPOCH
+    let secs = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|since| since.as_secs())
+        });
+    if let Some(secs) = secs {
+        println!("cargo:rustc-env=MANGATAN_BUILD_DATE={}", date(secs));
+    }
+
+    let target = env::var("TARGET").unwrap_or_default();
+    println!("cargo:rustc-env=MANGATAN_BUILD_TARGET={target}");
+}
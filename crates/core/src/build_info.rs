@@ -0,0 +1,85 @@
+//! Which build is running. Every binary registers its [`BuildInfo`] at startup with [`init`];
+//! `/api/version` and the OCR and yomitan status endpoints all report [`current`], so any one
+//! response from a bug report identifies the build.
+
+use std::{fmt, sync::OnceLock};
+
+use serde::Serialize;
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// Release tag, e.g. `v0.9.1`; `MANGATAN_VERSION` at build time overrides `git describe`.
+    pub version: String,
+    /// `desktop`, `android` or `ios`; `unknown` until a binary calls [`init`].
+    pub binary: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_hash: Option<String>,
+    /// UTC date of the build, `YYYY-MM-DD`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_date: Option<String>,
+    pub target: String,
+    /// Cargo features the binary was built with.
+    pub features: Vec<String>,
+}
+
+impl BuildInfo {
+    /// What the build script recorded, for `binary`.
+    pub fn compiled(binary: &str) -> Self {
+        Self {
+            version: env!("MANGATAN_BUILD_VERSION").to_string(),
+            binary: binary.to_string(),
+            git_hash: option_env!("MANGATAN_BUILD_GIT_HASH").map(str::to_string),
+            build_date: option_env!("MANGATAN_BUILD_DATE").map(str::to_string),
+            target: env!("MANGATAN_BUILD_TARGET").to_string(),
+            features: Vec::new(),
+        }
+    }
+
+    /// Replaces the recorded version, for binaries that get theirs from elsewhere (the iOS
+    /// bundle version, say).
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    pub fn with_features(mut self, features: &[&str]) -> Self {
+        self.features = features.iter().map(|f| f.to_string()).collect();
+        self
+    }
+}
+
+/// One line for GUIs and logs, e.g. `v0.9.1 (3f2a9c1e0b, 2026-10-16, aarch64-linux-android)`.
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let details: Vec<&str> = [
+            self.git_hash.as_deref(),
+            self.build_date.as_deref(),
+            Some(self.target.as_str()).filter(|t| !t.is_empty()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        write!(f, "{}", self.version)?;
+        if !details.is_empty() {
+            write!(f, " ({})", details.join(", "))?;
+        }
+        if !self.features.is_empty() {
+            write!(f, " [{}]", self.features.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+static CURRENT: OnceLock<BuildInfo> = OnceLock::new();
+
+/// Registers the running binary's build info. Only the first call counts; returns whether
+/// this one did.
+pub fn init(info: BuildInfo) -> bool {
+    CURRENT.set(info).is_ok()
+}
+
+/// The registered build info, or the compiled-in one for an `unknown` binary when nothing
+/// registered it (tests, mostly).
+pub fn current() -> &'static BuildInfo {
+    CURRENT.get_or_init(|| BuildInfo::compiled("unknown"))
+}
@@ -0,0 +1,78 @@
+//! Making sure the data directory can be written before anything opens files in it. Without
+//! this check, a read-only data dir shows up much later as SQLite or cache save errors that
+//! don't mention permissions.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Written and removed again by [`check_writable`].
+const PROBE_FILE: &str = ".mangatan-write-test";
+
+/// The directory to keep data in, as picked by [`resolve`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DataDir {
+    pub path: PathBuf,
+    /// The configured dir wasn't writable and this is a temp dir instead. Nothing in it is
+    /// kept past a reboot.
+    pub temporary: bool,
+}
+
+/// The data dir can't be written, and there was no usable fallback.
+#[derive(Debug)]
+pub struct ReadOnlyDataDir {
+    pub path: PathBuf,
+    pub source: io::Error,
+}
+
+impl fmt::Display for ReadOnlyDataDir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The data directory {} is not writable ({}). Fix its permissions, or start with a \
+             temporary data directory instead (nothing will be saved).",
+            self.path.display(),
+            self.source
+        )
+    }
+}
+
+impl std::error::Error for ReadOnlyDataDir {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Creates `dir` if needed, then writes and removes a small file in it.
+pub fn check_writable(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(PROBE_FILE);
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)
+}
+
+/// `dir` if it's writable. Otherwise, with `fall_back_to_temp`, a `mangatan` dir under the
+/// system temp dir, and an error without it (or if that isn't writable either).
+pub fn resolve(dir: &Path, fall_back_to_temp: bool) -> Result<DataDir, ReadOnlyDataDir> {
+    let source = match check_writable(dir) {
+        Ok(()) => {
+            return Ok(DataDir {
+                path: dir.to_path_buf(),
+                temporary: false,
+            });
+        }
+        Err(source) => source,
+    };
+    let temp = std::env::temp_dir().join("mangatan");
+    match fall_back_to_temp && check_writable(&temp).is_ok() {
+        true => Ok(DataDir {
+            path: temp,
+            temporary: true,
+        }),
+        false => Err(ReadOnlyDataDir {
+            path: dir.to_path_buf(),
+            source,
+        }),
+    }
+}
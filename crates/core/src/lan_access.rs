@@ -0,0 +1,119 @@
+//! Optional token check for other devices on the network. Requests from the device itself are
+//! always let through, so the local WebView or browser never needs the token; everyone else
+//! presents it once (from the login link or its QR code) and gets it back as a cookie.
+
+use std::{
+    fs, io,
+    net::{IpAddr, UdpSocket},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Query parameter carrying the token in a login link.
+pub const TOKEN_PARAM: &str = "mangatan_token";
+/// Cookie the token is kept in after logging in.
+pub const TOKEN_COOKIE: &str = "mangatan_token";
+/// Header scripts can send the token in instead.
+pub const TOKEN_HEADER: &str = "x-mangatan-token";
+/// How long the login cookie lasts, in seconds (a year).
+const COOKIE_MAX_AGE: u64 = 365 * 24 * 60 * 60;
+
+/// The LAN settings, kept in the app's settings file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LanAccess {
+    /// Whether other devices need the token. Off, anyone on the network gets in.
+    #[serde(default)]
+    pub required: bool,
+    pub token: String,
+}
+
+impl LanAccess {
+    /// Reads the settings at `path`, writing fresh ones (a new token, not required) when
+    /// there are none or they can't be read.
+    pub fn load_or_create(path: &Path) -> io::Result<Self> {
+        if let Some(access) = fs::read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Self>(&bytes).ok())
+            .filter(|access| !access.token.is_empty())
+        {
+            return Ok(access);
+        }
+        let access = Self {
+            required: false,
+            token: generate_token()?,
+        };
+        access.save(path)?;
+        Ok(access)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+
+    /// Whether a request from `peer` that came with `presented` may go through.
+    pub fn allows(&self, peer: IpAddr, presented: Option<&str>) -> bool {
+        !self.required
+            || peer.to_canonical().is_loopback()
+            || presented.is_some_and(|token| self.matches(token))
+    }
+
+    /// Compares in constant time, so response timings don't give the token away.
+    pub fn matches(&self, token: &str) -> bool {
+        let (a, b) = (self.token.as_bytes(), token.as_bytes());
+        a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+    }
+
+    /// `base` (e.g. `http://192.168.1.20:4568`) with the token in it, for the login link and
+    /// its QR code.
+    pub fn login_url(&self, base: &str) -> String {
+        format!(
+            "{}/?{TOKEN_PARAM}={}",
+            base.trim_end_matches('/'),
+            self.token
+        )
+    }
+
+    /// `Set-Cookie` value that keeps a device logged in.
+    pub fn cookie(&self) -> String {
+        format!(
+            "{TOKEN_COOKIE}={}; Path=/; Max-Age={COOKIE_MAX_AGE}; HttpOnly; SameSite=Lax",
+            self.token
+        )
+    }
+}
+
+/// Where a request put the token: the [`TOKEN_HEADER`], the [`TOKEN_COOKIE`] in its `Cookie`
+/// header, or the [`TOKEN_PARAM`] of its query string, in that order.
+pub fn presented_token<'a>(
+    header: Option<&'a str>,
+    cookies: Option<&'a str>,
+    query: Option<&'a str>,
+) -> Option<&'a str> {
+    let pair = |list: &'a str, separator: char, name: &str| {
+        list.split(separator)
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    };
+    header
+        .or_else(|| cookies.and_then(|c| pair(c, ';', TOKEN_COOKIE)))
+        .or_else(|| query.and_then(|q| pair(q, '&', TOKEN_PARAM)))
+        .filter(|token| !token.is_empty())
+}
+
+/// 32 hex characters from the OS random number generator.
+pub fn generate_token() -> io::Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(bytes.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+/// The address of the interface that routes to the internet. Connecting a UDP socket sends
+/// nothing, it only picks the route.
+pub fn lan_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("8.8.8.8:80").ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    (!ip.is_loopback() && !ip.is_unspecified()).then_some(ip)
+}
@@ -0,0 +1,5 @@
+//! Pieces shared by the desktop, Android and iOS binaries and the servers they embed.
+
+pub mod build_info;
+pub mod data_dir;
+pub mod lan_access;
@@ -0,0 +1,67 @@
+use mangatan_core::build_info::{self, BuildInfo};
+use serde_json::json;
+
+fn sample() -> BuildInfo {
+    BuildInfo {
+        version: "v0.9.1".to_string(),
+        binary: "android".to_string(),
+        git_hash: Some("3f2a9c1e0b".to_string()),
+        build_date: Some("2026-10-16".to_string()),
+        target: "aarch64-linux-android".to_string(),
+        features: vec!["native_webview".to_string()],
+    }
+}
+
+#[test]
+fn compiled_info_is_filled_in() {
+    let info = BuildInfo::compiled("desktop");
+    assert_eq!(info.binary, "desktop");
+    assert!(!info.version.is_empty());
+    assert!(!info.target.is_empty());
+    let date = info.build_date.expect("build date");
+    assert_eq!(date.len(), 10, "{date}");
+    assert_eq!(&date[4..5], "-");
+}
+
+#[test]
+fn display_is_one_line() {
+    assert_eq!(
+        sample().to_string(),
+        "v0.9.1 (3f2a9c1e0b, 2026-10-16, aarch64-linux-android) [native_webview]"
+    );
+
+    let bare = BuildInfo {
+        git_hash: None,
+        build_date: None,
+        features: Vec::new(),
+        ..sample()
+    };
+    assert_eq!(bare.to_string(), "v0.9.1 (aarch64-linux-android)");
+}
+
+#[test]
+fn serializes_without_missing_fields() {
+    let info = BuildInfo {
+        git_hash: None,
+        ..sample()
+    }
+    .with_version("1.2.0")
+    .with_features(&["embed-jre"]);
+    assert_eq!(
+        serde_json::to_value(info).expect("json"),
+        json!({
+            "version": "1.2.0",
+            "binary": "android",
+            "build_date": "2026-10-16",
+            "target": "aarch64-linux-android",
+            "features": ["embed-jre"],
+        })
+    );
+}
+
+#[test]
+fn first_registration_wins() {
+    assert!(build_info::init(sample()));
+    assert!(!build_info::init(BuildInfo::compiled("ios")));
+    assert_eq!(build_info::current(), &sample());
+}
@@ -0,0 +1,50 @@
+use std::{fs, path::PathBuf};
+
+use mangatan_core::data_dir::{self, DataDir};
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mangatan-data-dir-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("create scratch dir");
+    dir
+}
+
+/// A dir that can't be created, even by root: its parent is a file.
+fn unwritable(name: &str) -> PathBuf {
+    let file = scratch(name).join("not-a-dir");
+    fs::write(&file, b"").expect("write file");
+    file.join("data")
+}
+
+#[test]
+fn a_writable_dir_is_used_as_is() {
+    let dir = scratch("writable").join("nested");
+    let resolved = data_dir::resolve(&dir, false).expect("writable");
+    assert_eq!(
+        resolved,
+        DataDir {
+            path: dir.clone(),
+            temporary: false,
+        }
+    );
+    // Created, and the probe cleaned up
+    assert_eq!(fs::read_dir(&dir).expect("read dir").count(), 0);
+}
+
+#[test]
+fn an_unwritable_dir_is_refused_with_its_path() {
+    let dir = unwritable("refused");
+    let err = data_dir::resolve(&dir, false).expect_err("unwritable");
+    assert_eq!(err.path, dir);
+    let message = err.to_string();
+    assert!(message.contains(&dir.display().to_string()), "{message}");
+    assert!(message.contains("not writable"), "{message}");
+}
+
+#[test]
+fn an_unwritable_dir_can_fall_back_to_temp() {
+    let resolved = data_dir::resolve(&unwritable("fallback"), true).expect("fallback");
+    assert!(resolved.temporary);
+    assert!(resolved.path.starts_with(std::env::temp_dir()));
+    data_dir::check_writable(&resolved.path).expect("temp dir is writable");
+}
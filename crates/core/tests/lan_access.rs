@@ -0,0 +1,90 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use mangatan_core::lan_access::{self, LanAccess};
+
+const PHONE: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 31));
+
+fn required() -> LanAccess {
+    LanAccess {
+        required: true,
+        token: "0123456789abcdef".to_string(),
+    }
+}
+
+#[test]
+fn other_devices_need_the_token_once_required() {
+    let access = required();
+    assert!(!access.allows(PHONE, None));
+    assert!(!access.allows(PHONE, Some("wrong")));
+    assert!(!access.allows(PHONE, Some("0123456789abcde")));
+    assert!(access.allows(PHONE, Some("0123456789abcdef")));
+
+    let open = LanAccess {
+        required: false,
+        ..required()
+    };
+    assert!(open.allows(PHONE, None));
+}
+
+#[test]
+fn the_device_itself_never_does() {
+    let access = required();
+    assert!(access.allows(IpAddr::V4(Ipv4Addr::LOCALHOST), None));
+    assert!(access.allows(IpAddr::V6(Ipv6Addr::LOCALHOST), None));
+    assert!(access.allows(IpAddr::V6(Ipv4Addr::LOCALHOST.to_ipv6_mapped()), None));
+}
+
+#[test]
+fn tokens_are_found_wherever_they_were_sent() {
+    let presented = lan_access::presented_token;
+    assert_eq!(
+        presented(Some("from-header"), None, None),
+        Some("from-header")
+    );
+    assert_eq!(
+        presented(None, Some("theme=dark; mangatan_token=abc"), None),
+        Some("abc")
+    );
+    assert_eq!(
+        presented(None, None, Some("page=2&mangatan_token=abc")),
+        Some("abc")
+    );
+    // Similar names and empty values don't count
+    assert_eq!(presented(None, Some("x_mangatan_token=abc"), None), None);
+    assert_eq!(presented(None, None, Some("mangatan_token=")), None);
+}
+
+#[test]
+fn settings_are_created_once_and_kept() {
+    let dir = std::env::temp_dir().join(format!("mangatan-lan-access-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("create dir");
+    let path = dir.join("settings.json");
+
+    let created = LanAccess::load_or_create(&path).expect("create");
+    assert!(!created.required, "off until turned on");
+    assert_eq!(created.token.len(), 32);
+    assert!(created.token.chars().all(|c| c.is_ascii_hexdigit()));
+
+    let turned_on = LanAccess {
+        required: true,
+        ..created.clone()
+    };
+    turned_on.save(&path).expect("save");
+    assert_eq!(LanAccess::load_or_create(&path).expect("load"), turned_on);
+    assert_ne!(lan_access::generate_token().expect("token"), created.token);
+}
+
+#[test]
+fn login_links_carry_the_token() {
+    let access = required();
+    assert_eq!(
+        access.login_url("http://192.168.1.20:4568/"),
+        "http://192.168.1.20:4568/?mangatan_token=0123456789abcdef"
+    );
+    assert!(
+        access
+            .cookie()
+            .starts_with("mangatan_token=0123456789abcdef;")
+    );
+}
@@ -0,0 +1,367 @@
+//! OCR for local CBZ/ZIP archives that never went through Suwayomi. The pages are cached under
+//! `/local/<archive-hash>/<page-index>`, so `/ocr?url=` and `/is-chapter-preprocessed` (with
+//! `base_url=/local/<archive-hash>`) work on them like on chapter pages.
+
+use std::{
+    cmp::Ordering,
+    fs,
+    io::{self, Cursor, Read},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering as AtomicOrdering},
+    },
+    time::Duration,
+};
+
+use futures::StreamExt;
+use tokio::sync::Mutex;
+use zip::ZipArchive;
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    job_history::{self, JobSummary, PageFailure},
+    jobs,
+    logic::{self, count_lens_calls},
+    merge::MergeConfig,
+    state::{AppState, CacheEntry, JobProgress},
+};
+
+/// Upload limit for `/preprocess-archive` when `MANGATAN_OCR_ARCHIVE_MAX_MB` isn't set.
+pub const DEFAULT_MAX_ARCHIVE_MB: usize = 512;
+/// Largest page an archive may unpack to, so a zip bomb can't fill the disk.
+pub const MAX_PAGE_BYTES: u64 = 64 * 1024 * 1024;
+
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif", "bmp", "avif"];
+
+/// Body limit for `/preprocess-archive`, from `MANGATAN_OCR_ARCHIVE_MAX_MB`.
+pub fn max_archive_bytes() -> usize {
+    let mb = std::env::var("MANGATAN_OCR_ARCHIVE_MAX_MB")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(DEFAULT_MAX_ARCHIVE_MB);
+    mb * 1024 * 1024
+}
+
+/// Identifies an archive by its content, so uploading it again finds the pages already done.
+pub fn archive_hash(bytes: &[u8]) -> String {
+    format!("{:016x}", crate::state::fingerprint(bytes))
+}
+
+/// The job (and `base_url`) of an archive's pages.
+pub fn job_id(hash: &str) -> String {
+    format!("/local/{hash}")
+}
+
+/// Cache key of the page at `index` (from 0, in [`natural_cmp`] order).
+pub fn page_cache_key(hash: &str, index: usize) -> String {
+    format!("/local/{hash}/{index}")
+}
+
+/// Compares names the way people number pages: runs of digits by value, so `page2` comes
+/// before `page10`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a_rest, mut b_rest) = (a, b);
+    loop {
+        let (Some(a_char), Some(b_char)) = (a_rest.chars().next(), b_rest.chars().next()) else {
+            return a_rest.len().cmp(&b_rest.len()).then_with(|| a.cmp(b));
+        };
+        let ordering = match (a_char.is_ascii_digit(), b_char.is_ascii_digit()) {
+            (true, true) => {
+                let (a_digits, a_tail) = split_digits(a_rest);
+                let (b_digits, b_tail) = split_digits(b_rest);
+                (a_rest, b_rest) = (a_tail, b_tail);
+                let (a_value, b_value) = (
+                    a_digits.trim_start_matches('0'),
+                    b_digits.trim_start_matches('0'),
+                );
+                a_value
+                    .len()
+                    .cmp(&b_value.len())
+                    .then_with(|| a_value.cmp(b_value))
+            }
+            _ => {
+                (a_rest, b_rest) = (&a_rest[a_char.len_utf8()..], &b_rest[b_char.len_utf8()..]);
+                a_char.to_lowercase().cmp(b_char.to_lowercase())
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn split_digits(text: &str) -> (&str, &str) {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    text.split_at(end)
+}
+
+/// Whether a zip entry is a page: an image, and not macOS metadata or a hidden file.
+fn is_page(name: &str) -> bool {
+    let file_name = name.rsplit('/').next().unwrap_or(name);
+    let extension = Path::new(file_name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    !name.starts_with("__MACOSX/")
+        && !file_name.starts_with('.')
+        && extension.is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// An archive's pages unpacked into a temp dir, which is removed again when this is dropped,
+/// whether the job finished or not.
+#[derive(Debug)]
+pub struct ExtractedArchive {
+    pub hash: String,
+    pub dir: PathBuf,
+    /// In page order. Named by index, so entry names never become paths.
+    pub pages: Vec<PathBuf>,
+}
+
+impl ExtractedArchive {
+    /// Unpacks the image entries of the zip in `bytes` into a new dir under `parent`. A bad or
+    /// empty archive is the client's fault; failing to write the pages is ours.
+    pub fn extract(bytes: &[u8], parent: &Path) -> Result<Self, ApiError> {
+        let hash = archive_hash(bytes);
+        let mut zip = ZipArchive::new(Cursor::new(bytes))
+            .map_err(|e| ApiError::bad_request(format!("Not a ZIP/CBZ archive: {e}")))?;
+        let mut names: Vec<String> = zip
+            .file_names()
+            .filter(|name| is_page(name))
+            .map(str::to_string)
+            .collect();
+        if names.is_empty() {
+            return Err(ApiError::bad_request("The archive has no images"));
+        }
+        names.sort_by(|a, b| natural_cmp(a, b));
+
+        // Unique per upload, so the same archive sent twice doesn't share a dir
+        static UPLOADS: AtomicUsize = AtomicUsize::new(0);
+        let dir = parent.join(format!(
+            "mangatan-archive-{hash}-{}-{}",
+            std::process::id(),
+            UPLOADS.fetch_add(1, AtomicOrdering::Relaxed)
+        ));
+        let write_failed = |path: &Path, e: io::Error| {
+            internal(format!("Failed to write {}: {e}", path.display()))
+        };
+        fs::create_dir_all(&dir).map_err(|e| write_failed(&dir, e))?;
+        // From here on, dropping `archive` cleans up
+        let mut archive = Self {
+            hash,
+            dir,
+            pages: Vec::with_capacity(names.len()),
+        };
+
+        for (index, name) in names.iter().enumerate() {
+            let unpack_failed = |e: &dyn std::fmt::Display| {
+                ApiError::bad_request(format!("Failed to unpack {name}: {e}"))
+            };
+            let mut entry = zip.by_name(name).map_err(|e| unpack_failed(&e))?;
+            let mut image = Vec::new();
+            (&mut entry)
+                .take(MAX_PAGE_BYTES + 1)
+                .read_to_end(&mut image)
+                .map_err(|e| unpack_failed(&e))?;
+            if image.len() as u64 > MAX_PAGE_BYTES {
+                return Err(unpack_failed(&format_args!(
+                    "larger than {MAX_PAGE_BYTES} bytes"
+                )));
+            }
+            let extension = Path::new(name)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or("img");
+            let path = archive.dir.join(format!("{index:05}.{extension}"));
+            fs::write(&path, image).map_err(|e| write_failed(&path, e))?;
+            archive.pages.push(path);
+        }
+        Ok(archive)
+    }
+}
+
+impl Drop for ExtractedArchive {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            tracing::warn!("Failed to remove {}: {e}", self.dir.display());
+        }
+    }
+}
+
+/// OCRs an extracted archive's pages and caches them, reporting progress like a chapter job
+/// (under [`job_id`]). The temp dir goes away with `archive` once the job is done.
+pub async fn run_archive_job(state: AppState, archive: ExtractedArchive, context: String) {
+    let job_id = job_id(&archive.hash);
+    let total = archive.pages.len();
+    let started_at = job_history::unix_millis();
+    let trips_before = state.backend_watch.trips();
+    let pages: Vec<(String, &Path)> = archive
+        .pages
+        .iter()
+        .enumerate()
+        .map(|(index, path)| (page_cache_key(&archive.hash, index), path.as_path()))
+        .collect();
+
+    // Pages cached by an earlier upload of the same archive count as done from the start
+    let (cached, pages): (Vec<_>, Vec<_>) = {
+        let cache = state.cache.read().expect("lock");
+        pages
+            .into_iter()
+            .partition(|(key, _)| cache.contains_key(key))
+    };
+    let already_cached = cached.len();
+    // `/is-chapter-preprocessed` compares against this instead of asking Suwayomi
+    state
+        .chapter_pages_map
+        .write()
+        .expect("lock")
+        .insert(job_id.clone(), total);
+    state.active_chapter_jobs.write().expect("lock").insert(
+        job_id.clone(),
+        JobProgress {
+            current: already_cached,
+            total,
+            resumed_from: already_cached,
+            key_collisions: 0,
+            failed: 0,
+            still_failing: None,
+            last_error: None,
+            backend_suspect: state.backend_watch.is_suspect(),
+        },
+    );
+    state.active_jobs.fetch_add(1, AtomicOrdering::Relaxed);
+    tracing::info!("[Archive {job_id}] Started for {context} ({total} pages)");
+
+    let completed = AtomicUsize::new(already_cached);
+    let processed = AtomicUsize::new(0);
+    let lens_calls = Arc::new(AtomicUsize::new(0));
+    let save_lock = Mutex::new(());
+    let failed = std::sync::Mutex::new(Vec::new());
+
+    futures::stream::iter(pages)
+        .for_each_concurrent(jobs::PAGE_CONCURRENCY, |(key, path)| {
+            let (state, job_id, context) = (&state, &job_id, &context);
+            let lens_calls = lens_calls.clone();
+            let (completed, processed, save_lock, failed) =
+                (&completed, &processed, &save_lock, &failed);
+            async move {
+                while state.is_paused() {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+                let failure =
+                    match count_lens_calls(lens_calls, process_page(state, &key, path, context))
+                        .await
+                    {
+                        Ok(()) => {
+                            processed.fetch_add(1, AtomicOrdering::Relaxed);
+                            None
+                        }
+                        Err(err) => {
+                            tracing::warn!("[Archive {key}] Failed: {err}");
+                            failed.lock().expect("lock").push((key, path));
+                            Some(err)
+                        }
+                    };
+
+                let current = completed.fetch_add(1, AtomicOrdering::Relaxed) + 1;
+                if let Some(progress) = state
+                    .active_chapter_jobs
+                    .write()
+                    .expect("lock")
+                    .get_mut(job_id)
+                {
+                    progress.current = current;
+                    progress.backend_suspect = state.backend_watch.is_suspect();
+                    if let Some(err) = failure {
+                        progress.failed += 1;
+                        progress.last_error = Some(err);
+                    }
+                }
+                if current.is_multiple_of(5)
+                    && let Ok(_guard) = save_lock.try_lock()
+                {
+                    state.save_cache();
+                }
+            }
+        })
+        .await;
+
+    // One more go at the failed pages, as chapter jobs do
+    let retries = std::mem::take(&mut *failed.lock().expect("lock"));
+    let mut failures = Vec::new();
+    for (key, path) in retries {
+        while state.is_paused() {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+        let page = process_page(&state, &key, path, &context);
+        match count_lens_calls(lens_calls.clone(), page).await {
+            Ok(()) => {
+                processed.fetch_add(1, AtomicOrdering::Relaxed);
+            }
+            Err(err) => {
+                tracing::warn!("[Archive {key}] Failed again: {err}");
+                failures.push(PageFailure {
+                    url: key,
+                    error: err.to_string(),
+                });
+            }
+        }
+    }
+
+    state.save_cache();
+    state.active_jobs.fetch_sub(1, AtomicOrdering::Relaxed);
+    state.jobs_completed.fetch_add(1, AtomicOrdering::Relaxed);
+    state.job_history.record(JobSummary {
+        base_url: job_id.clone(),
+        context: context.clone(),
+        started_at,
+        finished_at: job_history::unix_millis(),
+        total,
+        skipped: already_cached,
+        processed: processed.load(AtomicOrdering::Relaxed),
+        failed: failures,
+        lens_calls: lens_calls.load(AtomicOrdering::Relaxed),
+        backend_suspect: state.backend_watch.is_suspect()
+            || state.backend_watch.trips() != trips_before,
+    });
+    state
+        .active_chapter_jobs
+        .write()
+        .expect("lock")
+        .remove(&job_id);
+    tracing::info!("[Archive {job_id}] Finished for {context}");
+}
+
+/// OCRs one unpacked page and caches the result under `key`.
+async fn process_page(
+    state: &AppState,
+    key: &str,
+    path: &Path,
+    context: &str,
+) -> Result<(), ApiError> {
+    let image_bytes = tokio::fs::read(path)
+        .await
+        .map_err(|e| internal(format!("Failed to read {}: {e}", path.display())))?;
+    let raw_chunks = logic::get_raw_ocr_data(&image_bytes, None, None).await?;
+    let merge_config = MergeConfig::from_env();
+    let (mut data, merge_quality) = logic::merge_raw_chunks_measured(raw_chunks, &merge_config);
+    let truncated_from = merge_config
+        .max_results
+        .and_then(|max| logic::cap_results(&mut data, max));
+    state.cache.write().expect("lock").insert(
+        key.to_string(),
+        CacheEntry {
+            context: context.to_string(),
+            data,
+            truncated_from,
+            merge_quality,
+        },
+    );
+    Ok(())
+}
+
+fn internal(message: String) -> ApiError {
+    ApiError::new(ErrorCode::Internal, message)
+}
@@ -0,0 +1,228 @@
+//! Notices when Lens starts answering pages that clearly have text with no lines at all. That
+//! looks like success, so without this every page read during such an outage gets cached empty
+//! and stays blank after Lens recovers.
+
+use std::{
+    collections::HashSet,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use reqwest::header::HeaderMap;
+use tracing::{debug, info, warn};
+
+use crate::{logic::PageResults, state::AppState};
+
+/// Empty pages in a row, not counting blank ones, before Lens counts as suspect when
+/// `MANGATAN_OCR_EMPTY_STREAK` isn't set.
+pub const DEFAULT_EMPTY_STREAK: usize = 5;
+/// How often the canary page is retried while Lens is suspect.
+const CANARY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Reads `MANGATAN_OCR_EMPTY_STREAK`; `0` turns detection off.
+pub fn empty_streak_from_env() -> Option<usize> {
+    match std::env::var("MANGATAN_OCR_EMPTY_STREAK") {
+        Ok(value) => match value.trim().parse::<usize>() {
+            Ok(0) => None,
+            Ok(pages) => Some(pages),
+            Err(_) => {
+                warn!("Ignoring invalid MANGATAN_OCR_EMPTY_STREAK={value:?}");
+                Some(DEFAULT_EMPTY_STREAK)
+            }
+        },
+        Err(_) => Some(DEFAULT_EMPTY_STREAK),
+    }
+}
+
+/// A page that came back empty when Lens became suspect, retried until it has lines again.
+#[derive(Clone, Debug)]
+pub struct CanaryPage {
+    pub url: String,
+    pub user: Option<String>,
+    pub pass: Option<String>,
+    pub fetch_headers: HeaderMap,
+}
+
+/// What to do with a freshly OCR'd page.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// Cache it as usual.
+    Cache,
+    /// An empty result while Lens is suspect; don't cache it.
+    Skip,
+    /// The page that made Lens suspect; don't cache it either.
+    Tripped,
+}
+
+#[derive(Default)]
+struct Streak {
+    /// Empty, non-blank pages in a row.
+    pages: usize,
+    /// Cache keys of the empty results cached so far in this streak.
+    keys: Vec<String>,
+    suspect: bool,
+    canary: Option<CanaryPage>,
+}
+
+/// The empty-result streak, shared by interactive requests and chapter jobs.
+#[derive(Clone)]
+pub struct BackendWatch {
+    threshold: Option<usize>,
+    streak: Arc<Mutex<Streak>>,
+    /// Empty results cached during streaks that ended up tripping, until they're purged.
+    suspect_keys: Arc<Mutex<HashSet<String>>>,
+    trips: Arc<AtomicUsize>,
+}
+
+impl BackendWatch {
+    /// Trips after `threshold` empty pages in a row; `None` never does.
+    pub fn new(threshold: Option<usize>) -> Self {
+        Self {
+            threshold,
+            streak: Arc::new(Mutex::new(Streak::default())),
+            suspect_keys: Arc::new(Mutex::new(HashSet::new())),
+            trips: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(empty_streak_from_env())
+    }
+
+    /// Lens has been returning nothing for pages that look like they have text.
+    pub fn is_suspect(&self) -> bool {
+        self.streak.lock().expect("streak lock poisoned").suspect
+    }
+
+    /// How many times Lens became suspect since the server started.
+    pub fn trips(&self) -> usize {
+        self.trips.load(Ordering::Relaxed)
+    }
+
+    /// Feeds an OCR'd page into the streak. Pages with placed lines end it, pages the text
+    /// prefilter considers blank don't count either way. `canary` is only called when this page
+    /// trips.
+    pub fn record(
+        &self,
+        cache_key: &str,
+        page: &PageResults,
+        canary: impl FnOnce() -> CanaryPage,
+    ) -> Verdict {
+        let Some(threshold) = self.threshold else {
+            return Verdict::Cache;
+        };
+        if page.looks_blank {
+            return Verdict::Cache;
+        }
+        let mut streak = self.streak.lock().expect("streak lock poisoned");
+        if crate::logic::placed_len(&page.results) > 0 {
+            if streak.suspect {
+                info!("✅ Lens is returning text again; caching empty results again");
+            }
+            *streak = Streak::default();
+            return Verdict::Cache;
+        }
+        if streak.suspect {
+            return Verdict::Skip;
+        }
+
+        streak.pages += 1;
+        if streak.pages < threshold {
+            streak.keys.push(cache_key.to_string());
+            return Verdict::Cache;
+        }
+        streak.suspect = true;
+        streak.canary = Some(canary());
+        self.suspect_keys
+            .lock()
+            .expect("suspect keys lock poisoned")
+            .extend(streak.keys.drain(..));
+        self.trips.fetch_add(1, Ordering::Relaxed);
+        warn!(
+            "🚨 Lens returned no text for {threshold} pages in a row that don't look blank; \
+             not caching empty results until it does again (MANGATAN_OCR_EMPTY_STREAK). \
+             POST /maintenance/purge-suspect-empty drops the ones cached before this."
+        );
+        Verdict::Tripped
+    }
+
+    /// The page to retry, while Lens is suspect.
+    pub fn canary(&self) -> Option<CanaryPage> {
+        let streak = self.streak.lock().expect("streak lock poisoned");
+        streak.canary.clone().filter(|_| streak.suspect)
+    }
+
+    /// Empty results cached during streaks that tripped, forgetting them.
+    pub fn take_suspect_keys(&self) -> HashSet<String> {
+        std::mem::take(
+            &mut *self
+                .suspect_keys
+                .lock()
+                .expect("suspect keys lock poisoned"),
+        )
+    }
+}
+
+/// Records `page` with the state's [`BackendWatch`], starting the canary when it trips.
+/// Returns whether the page should be cached.
+pub fn should_cache(
+    state: &AppState,
+    cache_key: &str,
+    page: &PageResults,
+    canary: impl FnOnce() -> CanaryPage,
+) -> bool {
+    match state.backend_watch.record(cache_key, page, canary) {
+        Verdict::Cache => true,
+        Verdict::Skip => {
+            warn!("Not caching the empty result for {cache_key}: Lens is suspect");
+            false
+        }
+        Verdict::Tripped => {
+            tokio::spawn(retry_canary(state.clone(), state.backend_watch.trips()));
+            false
+        }
+    }
+}
+
+/// OCRs the canary page every [`CANARY_INTERVAL`] until it has lines, or until the streak
+/// `trip` started is over some other way.
+async fn retry_canary(state: AppState, trip: usize) {
+    loop {
+        tokio::time::sleep(CANARY_INTERVAL).await;
+        if state.backend_watch.trips() != trip {
+            return;
+        }
+        let Some(canary) = state.backend_watch.canary() else {
+            return;
+        };
+        if state.is_paused() {
+            continue;
+        }
+        let result = crate::logic::fetch_and_process(
+            &canary.url,
+            canary.user.clone(),
+            canary.pass.clone(),
+            &canary.fetch_headers,
+            None,
+            None,
+            state.image_cache.as_ref(),
+            &state.rate_limiter,
+        )
+        .await;
+        match result {
+            Ok(page) if !page.incomplete => {
+                let cache_key = crate::logic::get_cache_key(&canary.url);
+                state.backend_watch.record(&cache_key, &page, || canary);
+                if !state.backend_watch.is_suspect() {
+                    return;
+                }
+                debug!("Canary page {cache_key} is still empty");
+            }
+            Ok(_) => debug!("Canary page ran out of time"),
+            Err(e) => debug!("Canary page failed: {e}"),
+        }
+    }
+}
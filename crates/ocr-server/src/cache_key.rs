@@ -0,0 +1,102 @@
+use std::collections::{HashMap, HashSet};
+
+use lazy_static::lazy_static;
+use tracing::warn;
+
+use crate::rate_limit::host_matches;
+
+lazy_static! {
+    static ref RULES: CacheKeyRules = CacheKeyRules::from_env();
+}
+
+/// Query parameters kept in the cache key for hosts that page by query string (`?page=3`).
+/// Every other URL is keyed by its path alone, which is also what older caches used.
+#[derive(Clone, Debug, Default)]
+pub struct CacheKeyRules {
+    /// `(host pattern, parameter names)`, checked in order.
+    rules: Vec<(String, Vec<String>)>,
+}
+
+impl CacheKeyRules {
+    /// Parses `host=param,param;host=param`, e.g. `*.example.org=page;reader.net=chapter,p`.
+    /// Host patterns work like in `MANGATAN_OCR_RATE_LIMITS` (`name`, `*.suffix`, `prefix*`).
+    pub fn parse(spec: &str) -> Self {
+        let rules = spec
+            .split(';')
+            .filter(|rule| !rule.trim().is_empty())
+            .filter_map(|rule| {
+                let Some((host, params)) = rule.split_once('=') else {
+                    warn!("Ignoring invalid MANGATAN_OCR_CACHE_KEY_QUERY rule: {rule}");
+                    return None;
+                };
+                let params: Vec<String> = params
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|param| !param.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                Some((host.trim().to_ascii_lowercase(), params))
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Rules from `MANGATAN_OCR_CACHE_KEY_QUERY`; none by default.
+    pub fn from_env() -> Self {
+        Self::parse(&std::env::var("MANGATAN_OCR_CACHE_KEY_QUERY").unwrap_or_default())
+    }
+
+    /// The URL path, plus the kept query parameters (sorted by name) when a rule matches the
+    /// host and the URL has any of them.
+    pub fn key_for(&self, url: &str) -> String {
+        let Ok(parsed) = reqwest::Url::parse(url) else {
+            return url.split('?').next().unwrap_or(url).to_string();
+        };
+        let path = parsed.path();
+
+        let host = parsed.host_str().unwrap_or_default().to_ascii_lowercase();
+        let Some((_, keep)) = self
+            .rules
+            .iter()
+            .find(|(pattern, _)| host_matches(pattern, &host))
+        else {
+            return path.to_string();
+        };
+
+        let mut pairs: Vec<(String, String)> = parsed
+            .query_pairs()
+            .filter(|(name, _)| keep.iter().any(|param| param == name))
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect();
+        if pairs.is_empty() {
+            return path.to_string();
+        }
+        pairs.sort();
+
+        let query = pairs
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("&");
+        format!("{path}?{query}")
+    }
+
+    /// How many of `urls` share their cache key with a different URL. Non-zero means those
+    /// pages would overwrite each other's OCR, typically because the host pages by query string.
+    pub fn count_collisions(&self, urls: &[String]) -> usize {
+        let mut by_key: HashMap<String, HashSet<&str>> = HashMap::new();
+        for url in urls {
+            by_key.entry(self.key_for(url)).or_default().insert(url);
+        }
+        by_key
+            .values()
+            .filter(|urls| urls.len() > 1)
+            .map(HashSet::len)
+            .sum()
+    }
+}
+
+/// The rules from the environment, read once.
+pub fn rules() -> &'static CacheKeyRules {
+    &RULES
+}
@@ -0,0 +1,78 @@
+use std::io::Cursor;
+
+use anyhow::anyhow;
+use image::{
+    DynamicImage, ImageFormat,
+    codecs::{jpeg::JpegEncoder, webp::WebPEncoder},
+};
+use serde::Deserialize;
+
+use crate::logic;
+
+pub const DEFAULT_QUALITY: u8 = 85;
+
+/// Formats `/convert-image` can re-encode a page to, both of which every WebView displays.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConvertFormat {
+    #[default]
+    Webp,
+    Jpeg,
+}
+
+impl ConvertFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Webp => "webp",
+            Self::Jpeg => "jpeg",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Webp => "image/webp",
+            Self::Jpeg => "image/jpeg",
+        }
+    }
+}
+
+/// Image cache key of a converted page. Kept apart from the source image's own key, which the
+/// OCR pipeline reuses.
+pub fn cache_key(page_key: &str, format: ConvertFormat, quality: u8) -> String {
+    format!("convert:{}:{quality}:{page_key}", format.as_str())
+}
+
+/// Decodes `image_bytes` with the same decoders as OCR (so AVIF works) and re-encodes them.
+/// `quality` (1-100) applies to JPEG; WebP output is lossless.
+pub fn convert_image(
+    image_bytes: &[u8],
+    format: ConvertFormat,
+    quality: u8,
+) -> anyhow::Result<Vec<u8>> {
+    let image = logic::decode_image(image_bytes)?;
+    let mut out = Cursor::new(Vec::new());
+    match format {
+        ConvertFormat::Webp => {
+            // The WebP encoder takes 8-bit RGB(A) only
+            let image = match image.color().has_alpha() {
+                true => DynamicImage::ImageRgba8(image.to_rgba8()),
+                false => DynamicImage::ImageRgb8(image.to_rgb8()),
+            };
+            image.write_with_encoder(WebPEncoder::new_lossless(&mut out))
+        }
+        // JPEG has no alpha channel
+        ConvertFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut out, quality)),
+    }
+    .map_err(|err| anyhow!("Failed to encode {}: {err}", format.as_str()))?;
+    Ok(out.into_inner())
+}
+
+/// Whether `bytes` already are in `format`, so the conversion can be skipped.
+pub fn is_already(bytes: &[u8], format: ConvertFormat) -> bool {
+    let wanted = match format {
+        ConvertFormat::Webp => ImageFormat::WebP,
+        ConvertFormat::Jpeg => ImageFormat::Jpeg,
+    };
+    image::guess_format(bytes).is_ok_and(|guessed| guessed == wanted)
+}
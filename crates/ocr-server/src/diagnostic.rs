@@ -0,0 +1,103 @@
+use std::io::{Cursor, Write};
+
+use anyhow::anyhow;
+use image::ImageReader;
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+use crate::{
+    logic,
+    merge::{MergeConfig, ReadingDirection},
+};
+
+/// Images above this size are left out of the bundle to keep it shareable.
+const MAX_BUNDLED_IMAGE_BYTES: usize = 10 * 1024 * 1024;
+
+#[derive(Serialize)]
+struct BundleInfo {
+    server_version: &'static str,
+    source_url: String,
+    image_included: bool,
+    image_bytes: usize,
+}
+
+/// Diagnostics are hidden unless `MANGATAN_OCR_DIAGNOSTICS` is set, since the bundle exposes
+/// page content to whoever can reach the server.
+pub fn diagnostics_enabled() -> bool {
+    std::env::var("MANGATAN_OCR_DIAGNOSTICS").is_ok_and(|v| v == "1" || v == "true")
+}
+
+/// Runs the OCR pipeline for a single page and packs every intermediate artifact into a zip.
+///
+/// The file names follow the `ocr-test-data` layout used by `tests/merge_regression.rs`, so the
+/// bundle can be unpacked straight into a fixture directory.
+pub async fn build_bundle(
+    url: &str,
+    user: Option<String>,
+    pass: Option<String>,
+    add_space_on_merge: Option<bool>,
+    reading_direction: Option<ReadingDirection>,
+    include_image: bool,
+) -> anyhow::Result<Vec<u8>> {
+    let image_bytes =
+        logic::fetch_image_bytes(url, user.clone(), pass.clone(), &HeaderMap::new()).await?;
+    let raw_chunks = logic::get_raw_ocr_data(&image_bytes, user, pass).await?;
+
+    let merge_config = MergeConfig {
+        add_space_on_merge,
+        reading_direction,
+        ..MergeConfig::from_env()
+    };
+    let merged = logic::merge_raw_chunks(raw_chunks.clone(), &merge_config);
+
+    let image_included = include_image && image_bytes.len() <= MAX_BUNDLED_IMAGE_BYTES;
+    let info = BundleInfo {
+        server_version: env!("CARGO_PKG_VERSION"),
+        source_url: redact_url(url),
+        image_included,
+        image_bytes: image_bytes.len(),
+    };
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+
+    let mut add_file = |name: &str, bytes: &[u8]| -> anyhow::Result<()> {
+        zip.start_file(name, options)?;
+        zip.write_all(bytes)?;
+        Ok(())
+    };
+
+    if image_included {
+        let extension = ImageReader::new(Cursor::new(&image_bytes))
+            .with_guessed_format()
+            .ok()
+            .and_then(|reader| reader.format())
+            .and_then(|format| format.extensions_str().first().copied())
+            .unwrap_or("png");
+        add_file(&format!("page.{extension}"), &image_bytes)?;
+    }
+    add_file("page.raw.json", &serde_json::to_vec_pretty(&raw_chunks)?)?;
+    add_file("page.expected.json", &serde_json::to_vec_pretty(&merged)?)?;
+    add_file(
+        "merge-config.json",
+        &serde_json::to_vec_pretty(&merge_config)?,
+    )?;
+    add_file("info.json", &serde_json::to_vec_pretty(&info)?)?;
+
+    let cursor = zip
+        .finish()
+        .map_err(|err| anyhow!("Failed to finish diagnostic zip: {err}"))?;
+    Ok(cursor.into_inner())
+}
+
+/// Strips credentials from a URL before it is written into a bundle.
+fn redact_url(url: &str) -> String {
+    let Ok(mut parsed) = reqwest::Url::parse(url) else {
+        return logic::get_cache_key(url);
+    };
+    let _ = parsed.set_username("");
+    let _ = parsed.set_password(None);
+    parsed.set_query(None);
+    parsed.to_string()
+}
@@ -0,0 +1,127 @@
+//! Page images that carry their own OCR, for archives a viewer can read without the server.
+
+use std::io::{Cursor, Write};
+
+use anyhow::{Context, anyhow};
+use image::ImageFormat;
+use serde::Deserialize;
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+use crate::logic::{self, OcrResult};
+
+/// Keyword of the PNG `iTXt` chunk holding the results, as JSON.
+pub const OCR_KEYWORD: &str = "mangatan:ocr";
+/// Name of the results next to the image in a [`EmbedFormat::Zip`].
+pub const SIDECAR_NAME: &str = "page.ocr.json";
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// How `/ocr-image` packs a page with its results.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbedFormat {
+    /// The page as PNG, with the results in an `iTXt` chunk. Other formats are re-encoded.
+    #[default]
+    Png,
+    /// The page as it was served, with the results in [`SIDECAR_NAME`].
+    Zip,
+}
+
+impl EmbedFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Zip => "application/zip",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Zip => "zip",
+        }
+    }
+}
+
+/// `image_bytes` packed with `results` as `format` says.
+pub fn embed(
+    image_bytes: &[u8],
+    results: &[OcrResult],
+    format: EmbedFormat,
+) -> anyhow::Result<Vec<u8>> {
+    let json = serde_json::to_string(results)?;
+    match format {
+        EmbedFormat::Png => png_with_text(image_bytes, OCR_KEYWORD, &json),
+        EmbedFormat::Zip => zip_with_sidecar(image_bytes, &json),
+    }
+}
+
+/// The results [`embed`] put in a PNG, if it has any.
+pub fn read_png(png: &[u8]) -> Option<Vec<OcrResult>> {
+    let mut rest = png.strip_prefix(PNG_SIGNATURE)?;
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes(rest[..4].try_into().ok()?) as usize;
+        let kind = &rest[4..8];
+        let data = rest.get(8..8 + length)?;
+        // Null separator, uncompressed, no language tag or translated keyword
+        if kind == b"iTXt"
+            && let Some(text) = data.strip_prefix(OCR_KEYWORD.as_bytes())
+            && let Some(json) = text.strip_prefix(b"\0\0\0\0\0")
+        {
+            return serde_json::from_slice(json).ok();
+        }
+        rest = rest.get(12 + length..)?;
+    }
+    None
+}
+
+/// `image_bytes` as PNG (re-encoded unless it already is one) with an uncompressed `iTXt`
+/// chunk of `text` under `keyword`, just before the end.
+fn png_with_text(image_bytes: &[u8], keyword: &str, text: &str) -> anyhow::Result<Vec<u8>> {
+    let mut png = match image::guess_format(image_bytes) {
+        Ok(ImageFormat::Png) => image_bytes.to_vec(),
+        _ => {
+            let mut out = Cursor::new(Vec::new());
+            logic::decode_image(image_bytes)?
+                .write_to(&mut out, ImageFormat::Png)
+                .map_err(|err| anyhow!("Failed to encode png: {err}"))?;
+            out.into_inner()
+        }
+    };
+
+    // IEND is the last chunk, and always 12 bytes
+    let end = png
+        .len()
+        .checked_sub(12)
+        .filter(|&at| png[at + 4..at + 8] == *b"IEND")
+        .context("PNG doesn't end with IEND")?;
+    let mut data = Vec::with_capacity(keyword.len() + 5 + text.len());
+    data.extend_from_slice(keyword.as_bytes());
+    data.extend_from_slice(b"\0\0\0\0\0");
+    data.extend_from_slice(text.as_bytes());
+
+    let mut chunk = Vec::with_capacity(data.len() + 12);
+    chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    chunk.extend_from_slice(b"iTXt");
+    chunk.extend_from_slice(&data);
+    chunk.extend_from_slice(&crc32fast::hash(&chunk[4..]).to_be_bytes());
+    png.splice(end..end, chunk);
+    Ok(png)
+}
+
+fn zip_with_sidecar(image_bytes: &[u8], json: &str) -> anyhow::Result<Vec<u8>> {
+    let extension = image::guess_format(image_bytes)
+        .ok()
+        .and_then(|format| format.extensions_str().first().copied())
+        .unwrap_or("img");
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+    zip.start_file(format!("page.{extension}"), options)?;
+    zip.write_all(image_bytes)?;
+    zip.start_file(SIDECAR_NAME, options)?;
+    zip.write_all(json.as_bytes())?;
+    let cursor = zip
+        .finish()
+        .map_err(|err| anyhow!("Failed to finish zip: {err}"))?;
+    Ok(cursor.into_inner())
+}
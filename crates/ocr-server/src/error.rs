@@ -0,0 +1,184 @@
+use std::fmt;
+
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+/// Machine-readable reason attached to every error response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The page (or chapter) doesn't exist on Suwayomi or the source.
+    UpstreamNotFound,
+    /// Suwayomi rejected the supplied credentials.
+    UpstreamUnauthorized,
+    /// The fetched bytes aren't an image we can decode.
+    DecodeFailed,
+    /// Lens failed for a reason other than rate limiting.
+    OcrBackendFailed,
+    RateLimited,
+    /// The request itself is malformed (missing fields, empty upload, ...).
+    BadRequest,
+    /// The endpoint exists but is switched off.
+    NotFound,
+    /// Temporarily refusing work, e.g. while OCR is paused.
+    Unavailable,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> StatusCode {
+        match self {
+            Self::UpstreamNotFound | Self::NotFound => StatusCode::NOT_FOUND,
+            Self::UpstreamUnauthorized => StatusCode::UNAUTHORIZED,
+            Self::DecodeFailed => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::OcrBackendFailed => StatusCode::BAD_GATEWAY,
+            Self::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            Self::BadRequest => StatusCode::BAD_REQUEST,
+            Self::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Whether the same request may succeed if the client tries again later.
+    pub fn retryable(self) -> bool {
+        matches!(
+            self,
+            Self::OcrBackendFailed | Self::RateLimited | Self::Unavailable
+        )
+    }
+}
+
+/// Error envelope returned by every OCR endpoint:
+/// `{ "code": "...", "message": "...", "retryable": bool }`.
+#[derive(Clone, Debug, Serialize)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub retryable: bool,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            retryable: code.retryable(),
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::BadRequest, message)
+    }
+
+    /// Classifies an error from the OCR pipeline. Upstream HTTP failures keep their
+    /// `reqwest::Error` in the chain and Lens failures a [`LensFailure`]; those are the only
+    /// places a rate limit is read from. Decode failures are recognised by the messages `logic`
+    /// attaches to them. Messages carry the page URL, so they're never searched for status codes.
+    pub fn classify(err: &anyhow::Error) -> ErrorCode {
+        let lens_failure = err
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<LensFailure>());
+        if let Some(lens) = lens_failure {
+            return if lens.is_rate_limited() {
+                ErrorCode::RateLimited
+            } else {
+                ErrorCode::OcrBackendFailed
+            };
+        }
+        let upstream_status = err
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<reqwest::Error>())
+            .and_then(reqwest::Error::status);
+        if let Some(status) = upstream_status {
+            return match status {
+                StatusCode::NOT_FOUND | StatusCode::GONE => ErrorCode::UpstreamNotFound,
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ErrorCode::UpstreamUnauthorized,
+                StatusCode::TOO_MANY_REQUESTS => ErrorCode::RateLimited,
+                _ => ErrorCode::Internal,
+            };
+        }
+
+        let message = format!("{err:#}");
+        if message.contains("Failed process_image_bytes") || message.contains("LensClient") {
+            ErrorCode::OcrBackendFailed
+        } else if message.contains("MANGATAN_OCR_TOTAL_TIMEOUT") {
+            ErrorCode::Unavailable
+        } else if message.contains("Failed decode")
+            || message.contains("with_guessed_format")
+            || message.contains("avif-decode")
+        {
+            ErrorCode::DecodeFailed
+        } else {
+            ErrorCode::Internal
+        }
+    }
+}
+
+/// A failed Lens request, with the Lens error kept as its source rather than flattened into the
+/// message, so [`ApiError::classify`] can tell a quota error from any other failure.
+#[derive(Debug)]
+pub struct LensFailure {
+    source: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl LensFailure {
+    pub fn new(source: impl std::error::Error + Send + Sync + 'static) -> Self {
+        Self {
+            source: Box::new(source),
+        }
+    }
+
+    /// Lens answered 429, or gRPC's `RESOURCE_EXHAUSTED`. Only the Lens error's own chain is
+    /// read; it never contains a page URL.
+    pub fn is_rate_limited(&self) -> bool {
+        let mut cause: Option<&(dyn std::error::Error + 'static)> = Some(self.source.as_ref());
+        while let Some(err) = cause {
+            if let Some(status) = err
+                .downcast_ref::<reqwest::Error>()
+                .and_then(reqwest::Error::status)
+            {
+                return status == StatusCode::TOO_MANY_REQUESTS;
+            }
+            let text = err.to_string();
+            if text.contains("RESOURCE_EXHAUSTED") || text.contains("Too Many Requests") {
+                return true;
+            }
+            cause = err.source();
+        }
+        false
+    }
+}
+
+impl fmt::Display for LensFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Failed process_image_bytes")
+    }
+}
+
+impl std::error::Error for LensFailure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        Self::new(Self::classify(&err), format!("{err:#}"))
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.code.status(), Json(self)).into_response()
+    }
+}
@@ -0,0 +1,184 @@
+use std::{collections::HashMap, fmt::Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    logic::{BoundingBox, OcrResult, reading_order},
+    state::CacheEntry,
+};
+
+/// How long each block is shown in the subtitle formats; cues only need to be sequential.
+const CUE_SECONDS: u64 = 3;
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// One page per paragraph, blocks joined by newlines.
+    #[default]
+    Text,
+    /// One JSON object per merged block.
+    Jsonl,
+    Srt,
+    Ass,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Text | Self::Srt | Self::Ass => "text/plain; charset=utf-8",
+            Self::Jsonl => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Text => "txt",
+            Self::Jsonl => "jsonl",
+            Self::Srt => "srt",
+            Self::Ass => "ass",
+        }
+    }
+}
+
+/// A merged block (usually one speech bubble) with where it sits in the chapter.
+#[derive(Serialize, Clone, Debug)]
+pub struct ExportCue {
+    pub page_index: usize,
+    /// Position within the page, in reading order.
+    pub reading_index: usize,
+    pub text: String,
+    #[serde(rename = "box")]
+    pub bounding_box: BoundingBox,
+    pub orientation: &'static str,
+}
+
+/// Collects the cached pages of the chapter at `chapter_path` (the cache key of the chapter's
+/// base URL) as cues, ordered by page and then by reading order within each page. Page URLs are
+/// `{base_url}{index}`, so the index is the number following the chapter path. Lines Lens
+/// couldn't place have no box to give a cue, so they're left out.
+pub fn chapter_cues(cache: &HashMap<String, CacheEntry>, chapter_path: &str) -> Vec<ExportCue> {
+    let mut pages: Vec<(usize, &[OcrResult])> = cache
+        .iter()
+        .filter_map(|(key, entry)| {
+            let page_index = key.strip_prefix(chapter_path)?.parse().ok()?;
+            Some((page_index, entry.data.as_slice()))
+        })
+        .collect();
+    pages.sort_by_key(|(page_index, _)| *page_index);
+
+    pages
+        .into_iter()
+        .flat_map(|(page_index, results)| {
+            reading_order(results)
+                .into_iter()
+                .filter(|r| r.no_geometry != Some(true) && !r.text.trim().is_empty())
+                .enumerate()
+                .map(move |(reading_index, r)| ExportCue {
+                    page_index,
+                    reading_index,
+                    text: r.text.clone(),
+                    bounding_box: r.tight_bounding_box.clone(),
+                    orientation: orientation(r),
+                })
+        })
+        .collect()
+}
+
+/// Lens' orientation when recorded, otherwise guessed from the box shape.
+fn orientation(result: &OcrResult) -> &'static str {
+    match result.forced_orientation.as_deref() {
+        Some("vertical") => "vertical",
+        Some("horizontal") => "horizontal",
+        _ if result.tight_bounding_box.height > result.tight_bounding_box.width => "vertical",
+        _ => "horizontal",
+    }
+}
+
+pub fn render(cues: &[ExportCue], format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Text => render_text(cues),
+        ExportFormat::Jsonl => render_jsonl(cues),
+        ExportFormat::Srt => render_srt(cues),
+        ExportFormat::Ass => render_ass(cues),
+    }
+}
+
+fn render_text(cues: &[ExportCue]) -> String {
+    let mut out = String::new();
+    for (i, cue) in cues.iter().enumerate() {
+        if i > 0 {
+            let new_page = cues[i - 1].page_index != cue.page_index;
+            out.push_str(if new_page { "\n\n" } else { "\n" });
+        }
+        out.push_str(&cue.text);
+    }
+    out
+}
+
+fn render_jsonl(cues: &[ExportCue]) -> String {
+    cues.iter()
+        .filter_map(|cue| serde_json::to_string(cue).ok())
+        .map(|line| line + "\n")
+        .collect()
+}
+
+fn render_srt(cues: &[ExportCue]) -> String {
+    let mut out = String::new();
+    for (i, cue) in cues.iter().enumerate() {
+        let (start, end) = cue_span(i);
+        let _ = write!(
+            out,
+            "{}\n{} --> {}\n{}\n\n",
+            i + 1,
+            srt_time(start),
+            srt_time(end),
+            cue.text
+        );
+    }
+    out
+}
+
+fn render_ass(cues: &[ExportCue]) -> String {
+    let mut out = String::from(
+        "[Script Info]\nScriptType: v4.00+\n\n[V4+ Styles]\n\
+         Format: Name, Fontname, Fontsize, Alignment\nStyle: Default,Noto Sans CJK JP,48,2\n\n\
+         [Events]\nFormat: Layer, Start, End, Style, Name, Text\n",
+    );
+    for (i, cue) in cues.iter().enumerate() {
+        let (start, end) = cue_span(i);
+        // The name column carries the source position so cues can be traced back to the page
+        let _ = writeln!(
+            out,
+            "Dialogue: 0,{},{},Default,p{}#{},{}",
+            ass_time(start),
+            ass_time(end),
+            cue.page_index,
+            cue.reading_index,
+            cue.text.replace('\n', "\\N")
+        );
+    }
+    out
+}
+
+fn cue_span(index: usize) -> (u64, u64) {
+    let start = index as u64 * CUE_SECONDS;
+    (start, start + CUE_SECONDS)
+}
+
+fn srt_time(seconds: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02},000",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn ass_time(seconds: u64) -> String {
+    format!(
+        "{}:{:02}:{:02}.00",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
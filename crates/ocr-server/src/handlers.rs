@@ -1,16 +1,35 @@
-use std::{collections::hash_map::Entry, sync::atomic::Ordering};
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    sync::atomic::Ordering,
+    time::Duration,
+};
 
 use axum::{
     Json,
-    extract::{Query, State},
-    http::StatusCode,
+    extract::{Multipart, Query, State},
+    http::{HeaderMap, HeaderValue, header},
+    response::{IntoResponse, Response},
 };
-use serde::Deserialize;
-use tracing::{info, warn};
+use mangatan_tokenize::{Token, Tokenizer};
+use serde::{Deserialize, Deserializer, Serialize};
+use tracing::{error, info, warn};
 
 use crate::{
-    jobs, logic,
-    state::{AppState, CacheEntry},
+    archive::{self, ExtractedArchive},
+    backend_watch::{self, CanaryPage},
+    convert::{self, ConvertFormat},
+    diagnostic,
+    embed::{self, EmbedFormat},
+    error::{ApiError, ErrorCode},
+    export::{self, ExportFormat},
+    job_history::JobSummary,
+    jobs::{self, ReadingOrder},
+    logic,
+    merge::{MergeConfig, ReadingDirection},
+    quality::{MergeQuality, QualityThresholds},
+    sanitize,
+    state::{AppState, CacheEntry, StorageStats},
+    watchdog,
 };
 
 #[derive(Deserialize)]
@@ -21,29 +40,447 @@ pub struct OcrRequest {
     #[serde(default = "default_context")]
     pub context: String,
     pub add_space_on_merge: Option<bool>,
+    /// `rtl` or `ltr` forces the column order of vertical text; unset, it's detected.
+    pub reading_direction: Option<ReadingDirection>,
+    /// Keep lines Lens couldn't locate (placeholder box, `noGeometry: true`) in the response.
+    #[serde(default)]
+    pub include_no_geometry: bool,
+    /// Overrides `MANGATAN_OCR_NO_ZWSP` for this request.
+    pub strip_zero_width: Option<bool>,
+    /// Extra headers for the page download (`Referer`, `Cookie`, ...) for hotlink-protected
+    /// sources. A query string can't carry a map, so they come JSON-encoded:
+    /// `headers={"Referer":"https://example.org/"}`. Never forwarded to Lens.
+    #[serde(default, deserialize_with = "json_header_map")]
+    pub headers: HashMap<String, String>,
+    /// Adds each block's words (Lindera/UniDic) as `tokens`, saving a tokenize call per bubble.
+    #[serde(default)]
+    pub tokenize: bool,
+    /// Bearer token for a Suwayomi that requires auth. Without one (or `user`/`pass`), the
+    /// request's own `Authorization` header is sent along with the page download.
+    pub token: Option<String>,
+}
+
+/// An OCR block with its text segmented into words.
+#[derive(Serialize)]
+pub struct TokenizedResult {
+    #[serde(flatten)]
+    pub result: crate::logic::OcrResult,
+    pub tokens: Vec<Token>,
 }
 
 fn default_context() -> String {
     "No Context".to_string()
 }
 
+/// Headers for a page download: the request's extra `headers` plus Suwayomi credentials (see
+/// [`logic::with_suwayomi_auth`]).
+fn page_fetch_headers(
+    extra: &HashMap<String, String>,
+    token: Option<&str>,
+    incoming: &HeaderMap,
+) -> Result<HeaderMap, ApiError> {
+    logic::image_fetch_headers(extra)
+        .and_then(|headers| logic::with_suwayomi_auth(headers, token, incoming))
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))
+}
+
+fn json_header_map<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, String>, D::Error> {
+    let raw = String::deserialize(deserializer)?;
+    serde_json::from_str(&raw).map_err(serde::de::Error::custom)
+}
+
 // --- Handlers ---
 
 pub async fn status_handler(State(state): State<AppState>) -> Json<serde_json::Value> {
     let cache_size = state.cache.read().expect("cache lock poisoned").len();
+    #[cfg(feature = "text-prefilter")]
+    let prefilter_skipped = Some(crate::prefilter::skipped_chunks());
+    #[cfg(not(feature = "text-prefilter"))]
+    let prefilter_skipped: Option<usize> = None;
     Json(serde_json::json!({
         "status": "running",
         "backend": "Rust (mangatan-ocr-server)",
         "requests_processed": state.requests_processed.load(Ordering::Relaxed),
         "items_in_cache": cache_size,
         "active_jobs": state.active_jobs.load(Ordering::Relaxed),
+        "paused": state.is_paused(),
+        "image_cache": state.image_cache.as_ref().map(|cache| cache.stats()),
+        "pinned_entries": state.pinned.read().expect("pinned lock poisoned").len(),
+        "unsaved_changes": state.has_unsaved_changes(),
+        "saver": state.saver_health(),
+        "backend_suspect": state.backend_watch.is_suspect(),
+        "prefilter_skipped_chunks": prefilter_skipped,
+        "build": mangatan_core::build_info::current(),
+    }))
+}
+
+/// How long `/maintenance/force-save` waits on the save before answering.
+const FORCE_SAVE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Escape hatch for a stalled saver: saves the whole cache from a fresh task, whether or not
+/// anything changed.
+pub async fn force_save_handler(
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let before = state.saver_health();
+    warn!(
+        "Forced OCR cache save requested (saver last activity: {}, {}s ago)",
+        before.last_activity, before.last_heartbeat_secs
+    );
+    let took = watchdog::force_save(state.clone(), FORCE_SAVE_TIMEOUT)
+        .await
+        .map_err(|e| {
+            error!("❌ Forced OCR cache save failed: {e}");
+            ApiError::new(ErrorCode::Unavailable, e)
+        })?;
+    Ok(Json(serde_json::json!({
+        "status": "ok",
+        "duration_ms": took.as_millis(),
+        "saver": state.saver_health(),
+    })))
+}
+
+/// Drops the empty results cached during a streak that made Lens suspect, so those pages get
+/// OCR'd again. Entries that have been re-OCR'd since are kept.
+pub async fn purge_suspect_empty_handler(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let keys = state.backend_watch.take_suspect_keys();
+    let removed = {
+        let mut cache = state.cache.write().expect("lock");
+        let before = cache.len();
+        cache.retain(|key, entry| !(entry.placed_len() == 0 && keys.contains(key)));
+        before - cache.len()
+    };
+    if removed > 0 {
+        state.cache_changed();
+    }
+    info!("Purged {removed} empty OCR results cached while Lens was suspect");
+    Json(serde_json::json!({
+        "status": "ok",
+        "removed": removed,
+        "backend_suspect": state.backend_watch.is_suspect(),
+    }))
+}
+
+/// Writes unsaved cache changes to disk now, e.g. before a shutdown or backup.
+pub async fn flush_cache_handler(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let flushed = {
+        let state = state.clone();
+        tokio::task::spawn_blocking(move || state.flush_cache())
+            .await
+            .unwrap_or(false)
+    };
+    info!("OCR cache flush requested (wrote: {flushed})");
+    Json(serde_json::json!({
+        "status": "ok",
+        "written": flushed,
+        "items_in_cache": state.cache.read().expect("cache lock poisoned").len(),
+    }))
+}
+
+#[derive(Deserialize, Default)]
+pub struct QualityReportRequest {
+    /// Only pages cached under this context (the series title, for the WebUI).
+    pub context: Option<String>,
+    // Overrides for the `QualityThresholds` defaults
+    pub single_line_share: Option<f64>,
+    pub font_size_variation: Option<f64>,
+    pub overlap_fraction: Option<f64>,
+    pub mixed_orientation_groups: Option<usize>,
+}
+
+/// A cached page whose merge looks wrong.
+#[derive(Serialize)]
+pub struct SuspectPage {
+    pub cache_key: String,
+    pub context: String,
+    /// How far past their thresholds the exceeded metrics are, summed.
+    pub severity: f64,
+    pub exceeded: Vec<&'static str>,
+    pub quality: MergeQuality,
+}
+
+/// Cached pages whose merge metrics exceed the thresholds, worst first. Pages cached before the
+/// metrics were measured are only counted, as `unmeasured`.
+pub async fn quality_report_handler(
+    State(state): State<AppState>,
+    Query(params): Query<QualityReportRequest>,
+) -> Json<serde_json::Value> {
+    let defaults = QualityThresholds::default();
+    let thresholds = QualityThresholds {
+        single_line_share: params
+            .single_line_share
+            .unwrap_or(defaults.single_line_share),
+        font_size_variation: params
+            .font_size_variation
+            .unwrap_or(defaults.font_size_variation),
+        overlap_fraction: params.overlap_fraction.unwrap_or(defaults.overlap_fraction),
+        mixed_orientation_groups: params
+            .mixed_orientation_groups
+            .unwrap_or(defaults.mixed_orientation_groups),
+    };
+
+    let (mut checked, mut unmeasured) = (0, 0);
+    let mut pages = Vec::new();
+    {
+        let cache = state.cache.read().expect("lock");
+        let entries = cache.iter().filter(|(_, entry)| {
+            params
+                .context
+                .as_ref()
+                .is_none_or(|context| &entry.context == context)
+        });
+        for (cache_key, entry) in entries {
+            let Some(quality) = &entry.merge_quality else {
+                unmeasured += 1;
+                continue;
+            };
+            checked += 1;
+            let exceeded = thresholds.exceeded(quality);
+            if exceeded.is_empty() {
+                continue;
+            }
+            pages.push(SuspectPage {
+                cache_key: cache_key.clone(),
+                context: entry.context.clone(),
+                severity: exceeded.iter().map(|(_, ratio)| ratio).sum(),
+                exceeded: exceeded.into_iter().map(|(metric, _)| metric).collect(),
+                quality: quality.clone(),
+            });
+        }
+    }
+    pages.sort_by(|a, b| {
+        b.severity
+            .total_cmp(&a.severity)
+            .then_with(|| a.cache_key.cmp(&b.cache_key))
+    });
+
+    Json(serde_json::json!({
+        "thresholds": thresholds,
+        "pages_checked": checked,
+        "unmeasured": unmeasured,
+        "pages": pages,
     }))
 }
 
+/// Disk space taken by the OCR cache and the image cache.
+pub async fn storage_stats_handler(
+    State(state): State<AppState>,
+) -> Result<Json<StorageStats>, ApiError> {
+    tokio::task::spawn_blocking(move || state.storage_stats())
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::new(ErrorCode::Internal, e.to_string()))
+}
+
+pub async fn pause_handler(State(state): State<AppState>) -> Json<serde_json::Value> {
+    state.set_paused(true);
+    info!("OCR background jobs paused");
+    Json(serde_json::json!({ "status": "paused" }))
+}
+
+pub async fn resume_handler(State(state): State<AppState>) -> Json<serde_json::Value> {
+    state.set_paused(false);
+    info!("OCR background jobs resumed");
+    Json(serde_json::json!({ "status": "resumed" }))
+}
+
 pub async fn ocr_handler(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Query(params): Query<OcrRequest>,
-) -> Result<Json<Vec<crate::logic::OcrResult>>, (StatusCode, String)> {
+) -> Result<Response, ApiError> {
+    let include_no_geometry = params.include_no_geometry;
+    let strip = params.strip_zero_width.unwrap_or(state.strip_zero_width);
+    let tokenize = params.tokenize;
+    let page = get_or_process_page(&state, params, &headers).await?;
+    let truncated = truncation_header(&page);
+    let data = strip_zero_width(filter_no_geometry(page.results, include_no_geometry), strip);
+    if !tokenize {
+        return Ok((truncated, Json(data)).into_response());
+    }
+
+    // Loading UniDic the first time takes a moment, so keep it off the async workers
+    let tokenized = tokio::task::spawn_blocking(move || {
+        let tokenizer = Tokenizer::shared();
+        data.into_iter()
+            .map(|result| TokenizedResult {
+                tokens: tokenizer.tokenize(&result.text),
+                result,
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .map_err(|e| ApiError::new(ErrorCode::Internal, e.to_string()))?;
+    Ok((truncated, Json(tokenized)).into_response())
+}
+
+/// Response header telling how many blocks a page had when the per-page cap dropped some.
+pub const TRUNCATED_HEADER: &str = "x-mangatan-truncated-from";
+/// Response header set when `MANGATAN_OCR_TOTAL_TIMEOUT` cut the page short. Such results
+/// aren't cached, so asking again OCRs the page from scratch.
+pub const INCOMPLETE_HEADER: &str = "x-mangatan-incomplete";
+
+fn truncation_header(page: &logic::PageResults) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(total) = page.truncated_from {
+        headers.insert(TRUNCATED_HEADER, total.into());
+    }
+    if page.incomplete {
+        headers.insert(INCOMPLETE_HEADER, HeaderValue::from_static("true"));
+    }
+    headers
+}
+
+/// Same pipeline as `/ocr`, but returns only the text in reading order as `text/plain`.
+pub async fn ocr_text_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<OcrRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let include_no_geometry = params.include_no_geometry;
+    let strip = params.strip_zero_width.unwrap_or(state.strip_zero_width);
+    let page = get_or_process_page(&state, params, &headers).await?;
+    let truncated = truncation_header(&page);
+    let data = strip_zero_width(filter_no_geometry(page.results, include_no_geometry), strip);
+    Ok((
+        truncated,
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        logic::results_to_plain_text(&data),
+    ))
+}
+
+/// The cache always keeps lines without geometry; drop them unless the client asked for them.
+fn filter_no_geometry(
+    mut data: Vec<crate::logic::OcrResult>,
+    include: bool,
+) -> Vec<crate::logic::OcrResult> {
+    if !include {
+        data.retain(|r| r.no_geometry != Some(true));
+    }
+    data
+}
+
+/// Zero-width characters stay in the cache; they're only removed from what gets returned.
+fn strip_zero_width(
+    mut data: Vec<crate::logic::OcrResult>,
+    strip: bool,
+) -> Vec<crate::logic::OcrResult> {
+    if strip {
+        for result in &mut data {
+            result.text = logic::strip_zero_width(&result.text);
+        }
+    }
+    data
+}
+
+/// Runs OCR on an uploaded image (multipart field `file`, optional `context`) rather than a page
+/// fetched from Suwayomi. The result is cached under a synthetic `/upload/<hash>` key, which
+/// `/ocr?url=` accepts as well.
+pub async fn ocr_upload_handler(
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let mut image_bytes = None;
+    let mut context = "Uploaded Images".to_string();
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| ApiError::bad_request(e.to_string()))?
+    {
+        match field.name() {
+            Some("file") => {
+                let bytes = field
+                    .bytes()
+                    .await
+                    .map_err(|e| ApiError::bad_request(e.to_string()))?;
+                image_bytes = Some(bytes);
+            }
+            Some("context") => {
+                if let Ok(text) = field.text().await
+                    && !text.trim().is_empty()
+                {
+                    context = text;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let image_bytes = image_bytes
+        .filter(|bytes| !bytes.is_empty())
+        .ok_or_else(|| ApiError::bad_request("No file field found"))?;
+    let cache_key = logic::upload_cache_key(&image_bytes);
+    info!(
+        "OCR Upload: Received {} bytes as cache_key={}",
+        image_bytes.len(),
+        cache_key
+    );
+
+    let cached = state
+        .cache
+        .read()
+        .expect("lock")
+        .get(&cache_key)
+        .map(|entry| (entry.data.clone(), entry.truncated_from));
+    let (data, truncated_from) = match cached {
+        Some(cached) => {
+            state.cache_hits.fetch_add(1, Ordering::Relaxed);
+            cached
+        }
+        None => {
+            state.cache_misses.fetch_add(1, Ordering::Relaxed);
+            let raw_chunks = logic::get_raw_ocr_data(&image_bytes, None, None)
+                .await
+                .map_err(|e| {
+                    let err = ApiError::from(e);
+                    warn!("OCR Upload: Processing FAILED for cache_key={cache_key}: {err}");
+                    err
+                })?;
+            let merge_config = MergeConfig::from_env();
+            let (mut data, merge_quality) =
+                logic::merge_raw_chunks_measured(raw_chunks, &merge_config);
+            let truncated_from = merge_config
+                .max_results
+                .and_then(|max| logic::cap_results(&mut data, max));
+
+            if let Some(image_cache) = &state.image_cache {
+                image_cache.put(&cache_key, &image_bytes);
+            }
+            state.cache.write().expect("lock").insert(
+                cache_key.clone(),
+                CacheEntry {
+                    context,
+                    data: data.clone(),
+                    truncated_from,
+                    merge_quality,
+                },
+            );
+            state.cache_changed();
+            (data, truncated_from)
+        }
+    };
+    state.requests_processed.fetch_add(1, Ordering::Relaxed);
+
+    let mut response = serde_json::json!({
+        "cache_key": cache_key,
+        "data": strip_zero_width(filter_no_geometry(data, false), state.strip_zero_width),
+    });
+    if let Some(total) = truncated_from {
+        response["truncated_from"] = total.into();
+    }
+    Ok(Json(response))
+}
+
+/// Serves a page from the cache, or runs OCR and caches it on a miss.
+async fn get_or_process_page(
+    state: &AppState,
+    params: OcrRequest,
+    incoming: &HeaderMap,
+) -> Result<logic::PageResults, ApiError> {
+    let fetch_headers = page_fetch_headers(&params.headers, params.token.as_deref(), incoming)?;
     let cache_key = logic::get_cache_key(&params.url);
     info!("OCR Handler: Incoming request for cache_key={}", cache_key);
 
@@ -51,8 +488,19 @@ pub async fn ocr_handler(
     if let Some(entry) = state.cache.read().expect("lock").get(&cache_key) {
         info!("OCR Handler: Cache HIT for cache_key={}", cache_key);
         state.requests_processed.fetch_add(1, Ordering::Relaxed);
-        return Ok(Json(entry.data.clone()));
+        state.cache_hits.fetch_add(1, Ordering::Relaxed);
+        return Ok(logic::PageResults {
+            results: entry.data.clone(),
+            truncated_from: entry.truncated_from,
+            incomplete: false,
+            looks_blank: false,
+            merge_quality: entry.merge_quality.clone(),
+        });
+    }
+    if state.pause_interactive && state.is_paused() {
+        return Err(ApiError::new(ErrorCode::Unavailable, "OCR is paused"));
     }
+    state.cache_misses.fetch_add(1, Ordering::Relaxed);
     info!(
         "OCR Handler: Cache MISS for cache_key={}. Starting processing.",
         cache_key
@@ -62,17 +510,37 @@ pub async fn ocr_handler(
         &params.url,
         params.user.clone(),
         params.pass.clone(),
+        &fetch_headers,
         params.add_space_on_merge,
+        params.reading_direction,
+        state.image_cache.as_ref(),
+        &state.rate_limiter,
     )
     .await;
 
     match result {
-        Ok(data) => {
+        Ok(page) if page.incomplete => {
+            state.requests_processed.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                "OCR Handler: Deadline hit for cache_key={cache_key}; not caching the partial result"
+            );
+            Ok(page)
+        }
+        Ok(page) => {
             state.requests_processed.fetch_add(1, Ordering::Relaxed);
             info!(
                 "OCR Handler: Processing successful for cache_key={}",
                 cache_key
             );
+            let canary = || CanaryPage {
+                url: params.url.clone(),
+                user: params.user.clone(),
+                pass: params.pass.clone(),
+                fetch_headers: fetch_headers.clone(),
+            };
+            if !backend_watch::should_cache(state, &cache_key, &page, canary) {
+                return Ok(page);
+            }
 
             info!("OCR Handler: Attempting to acquire cache write lock for insertion...");
             {
@@ -82,36 +550,133 @@ pub async fn ocr_handler(
                     cache_key.clone(),
                     CacheEntry {
                         context: params.context,
-                        data: data.clone(),
+                        data: page.results.clone(),
+                        truncated_from: page.truncated_from,
+                        merge_quality: page.merge_quality.clone(),
                     },
                 );
                 info!("OCR Handler: Cache data inserted. Releasing write lock.");
             }
 
-            info!("OCR Handler: Triggering cache save to disk...");
-            state.save_cache();
-            info!("OCR Handler: Cache save complete.");
+            state.cache_changed();
 
-            Ok(Json(data))
+            Ok(page)
         }
         Err(e) => {
+            let err = ApiError::from(e);
             warn!(
                 "OCR Handler: Processing FAILED for cache_key={}: {}",
-                cache_key, e
+                cache_key, err
             );
-            Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+            Err(err)
         }
     }
 }
 
+#[derive(Deserialize)]
+pub struct BackendHealthRequest {
+    pub user: Option<String>,
+    pub pass: Option<String>,
+}
+
+pub async fn backend_health_handler(
+    Query(params): Query<BackendHealthRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    backend_health_response(logic::check_lens_health(params.user, params.pass).await)
+}
+
+/// The `/ocr-backend-health` answer for a finished probe: its latency, `rate_limited` while Lens
+/// is refusing us, and `ocr_backend_failed` for anything else.
+pub fn backend_health_response(
+    probe: anyhow::Result<Duration>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    match probe {
+        Ok(latency) => Ok(Json(serde_json::json!({
+            "status": "ok",
+            "latency_ms": latency.as_millis(),
+        }))),
+        Err(e) => {
+            // Lens is all the probe talks to
+            let code = match ApiError::classify(&e) {
+                ErrorCode::RateLimited => ErrorCode::RateLimited,
+                _ => ErrorCode::OcrBackendFailed,
+            };
+            let err = ApiError::new(code, format!("{e:#}"));
+            warn!("Lens health check failed: {err}");
+            Err(err)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DiagnosticRequest {
+    pub url: String,
+    pub user: Option<String>,
+    pub pass: Option<String>,
+    pub add_space_on_merge: Option<bool>,
+    pub reading_direction: Option<ReadingDirection>,
+    #[serde(default = "default_include_image")]
+    pub include_image: bool,
+}
+
+fn default_include_image() -> bool {
+    true
+}
+
+pub async fn diagnostic_handler(
+    Query(params): Query<DiagnosticRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if !diagnostic::diagnostics_enabled() {
+        return Err(ApiError::new(ErrorCode::NotFound, "Not Found"));
+    }
+
+    info!(
+        "Diagnostic Handler: Building bundle for {}",
+        logic::get_cache_key(&params.url)
+    );
+
+    let bundle = diagnostic::build_bundle(
+        &params.url,
+        params.user,
+        params.pass,
+        params.add_space_on_merge,
+        params.reading_direction,
+        params.include_image,
+    )
+    .await
+    .map_err(|e| {
+        let err = ApiError::from(e);
+        warn!("Diagnostic Handler: Failed to build bundle: {err}");
+        err
+    })?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"ocr-diagnostic.zip\"",
+            ),
+        ],
+        bundle,
+    ))
+}
+
 #[derive(Deserialize)]
 pub struct JobRequest {
     pub base_url: String,
     pub user: Option<String>,
     pub pass: Option<String>,
+    /// Same as on `/ocr`: a bearer token for Suwayomi, else the request's `Authorization`.
+    pub token: Option<String>,
     pub context: String,
     pub pages: Option<Vec<String>>,
     pub add_space_on_merge: Option<bool>,
+    pub reading_direction: Option<ReadingDirection>,
+    /// Order `/preprocess-chapter` works through `pages` in; forward when not given.
+    pub reading_order: Option<ReadingOrder>,
+    /// Index into `pages` of the page on screen, OCR'd first along with the ones after it.
+    pub start_page: Option<usize>,
 }
 
 pub async fn is_chapter_preprocessed_handler(
@@ -127,11 +692,20 @@ pub async fn is_chapter_preprocessed_handler(
             .cloned()
     };
 
+    let last_job = state.job_history.latest_for(&req.base_url);
+
     if let Some(p) = progress {
         return Json(serde_json::json!({
             "status": "processing",
             "progress": p.current,
-            "total": p.total
+            "total": p.total,
+            "resumed_from": p.resumed_from,
+            "key_collisions": p.key_collisions,
+            "failed": p.failed,
+            "rate_limit_delay_ms": state.rate_limiter.current_delay(&req.base_url).as_millis(),
+            "last_error": p.last_error,
+            "paused": state.is_paused(),
+            "last_job": last_job,
         }));
     }
 
@@ -154,7 +728,7 @@ pub async fn is_chapter_preprocessed_handler(
                         .write()
                         .expect("lock")
                         .insert(chapter_base_path.clone(), total);
-                    state.save_cache();
+                    state.cache_changed();
                     total
                 }
                 Err(e) => {
@@ -162,7 +736,7 @@ pub async fn is_chapter_preprocessed_handler(
                         "is_chapter_preprocessed_handler: Failed GraphQL fallback: {}",
                         e
                     );
-                    return Json(serde_json::json!({ "status": "idle" }));
+                    return Json(serde_json::json!({ "status": "idle", "last_job": last_job }));
                 }
             }
         }
@@ -175,23 +749,36 @@ pub async fn is_chapter_preprocessed_handler(
         }
     }
     if cached_count >= total {
-        return Json(
-            serde_json::json!({ "status": "processed", "cached_count": cached_count, "total_expected": total }),
-        );
+        return Json(serde_json::json!({
+            "status": "processed",
+            "cached_count": cached_count,
+            "total_expected": total,
+            "last_job": last_job,
+        }));
     }
-    Json(
-        serde_json::json!({ "status": "idle", "cached_count": cached_count, "total_expected": total }),
-    )
+    Json(serde_json::json!({
+        "status": "idle",
+        "cached_count": cached_count,
+        "total_expected": total,
+        "last_job": last_job,
+    }))
+}
+
+/// Summaries of the most recent finished chapter jobs, newest first.
+pub async fn job_history_handler(State(state): State<AppState>) -> Json<Vec<JobSummary>> {
+    Json(state.job_history.recent())
 }
 
 pub async fn preprocess_handler(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(req): Json<JobRequest>,
-) -> Json<serde_json::Value> {
-    let pages = match req.pages {
-        Some(p) => p,
-        None => return Json(serde_json::json!({ "error": "No pages provided" })),
-    };
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let pages = req
+        .pages
+        .ok_or_else(|| ApiError::bad_request("No pages provided"))?;
+    let pages = jobs::order_pages(pages, req.reading_order.unwrap_or_default(), req.start_page);
+    let fetch_headers = page_fetch_headers(&HashMap::new(), req.token.as_deref(), &headers)?;
 
     let is_processing = {
         state
@@ -202,7 +789,7 @@ pub async fn preprocess_handler(
     };
 
     if is_processing {
-        return Json(serde_json::json!({ "status": "already_processing" }));
+        return Ok(Json(serde_json::json!({ "status": "already_processing" })));
     }
 
     let state_clone = state.clone();
@@ -213,23 +800,270 @@ pub async fn preprocess_handler(
             pages,
             req.user,
             req.pass,
+            fetch_headers,
             req.context,
             req.add_space_on_merge,
+            req.reading_direction,
         )
         .await;
     });
 
-    Json(serde_json::json!({ "status": "started" }))
+    Ok(Json(serde_json::json!({ "status": "started" })))
+}
+
+/// Runs a job over just the pages of `pages` (a whole series' worth, say) that have no OCR
+/// results yet or only empty ones, to finish a series after a partial or failed run. Progress
+/// shows up on `/is-chapter-preprocessed` under `base_url`, as for `/preprocess-chapter`.
+pub async fn fill_missing_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<JobRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let pages = req
+        .pages
+        .ok_or_else(|| ApiError::bad_request("No pages provided"))?;
+    let pages = jobs::order_pages(pages, req.reading_order.unwrap_or_default(), req.start_page);
+    let fetch_headers = page_fetch_headers(&HashMap::new(), req.token.as_deref(), &headers)?;
+
+    let is_processing = state
+        .active_chapter_jobs
+        .read()
+        .expect("lock poisoned")
+        .contains_key(&req.base_url);
+    if is_processing {
+        return Ok(Json(serde_json::json!({ "status": "already_processing" })));
+    }
+
+    let total = pages.len();
+    let missing = jobs::take_missing_pages(&state, pages);
+    if missing.pages.is_empty() {
+        return Ok(Json(
+            serde_json::json!({ "status": "complete", "total": total }),
+        ));
+    }
+    info!(
+        "Fill missing: {} of {total} pages of {} need OCR ({} uncached, {} empty)",
+        missing.pages.len(),
+        req.context,
+        missing.uncached,
+        missing.empty
+    );
+
+    let response = serde_json::json!({
+        "status": "started",
+        "total": total,
+        "missing": missing.pages.len(),
+        "uncached": missing.uncached,
+        "empty": missing.empty,
+    });
+    tokio::spawn(jobs::run_chapter_job(
+        state,
+        req.base_url,
+        missing.pages,
+        req.user,
+        req.pass,
+        fetch_headers,
+        req.context,
+        req.add_space_on_merge,
+        req.reading_direction,
+    ));
+
+    Ok(Json(response))
+}
+
+/// Starts OCR of an uploaded CBZ/ZIP (multipart field `file`, optional `context`). The pages
+/// are unpacked in name order and cached as `/local/<archive_hash>/<index>`; progress shows up
+/// on `/is-chapter-preprocessed` with `base_url` set to the returned `base_url`.
+pub async fn preprocess_archive_handler(
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let mut archive_bytes = None;
+    let mut context = None;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| ApiError::bad_request(e.to_string()))?
+    {
+        match field.name() {
+            Some("file") => {
+                // The file name stands in for a title when no context is given
+                let file_stem = field
+                    .file_name()
+                    .and_then(|name| name.rsplit_once('.').map(|(stem, _)| stem.to_string()));
+                let bytes = field
+                    .bytes()
+                    .await
+                    .map_err(|e| ApiError::bad_request(e.to_string()))?;
+                archive_bytes = Some(bytes);
+                context = context.or(file_stem);
+            }
+            Some("context") => {
+                if let Ok(text) = field.text().await
+                    && !text.trim().is_empty()
+                {
+                    context = Some(text);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let archive_bytes = archive_bytes
+        .filter(|bytes| !bytes.is_empty())
+        .ok_or_else(|| ApiError::bad_request("No file field found"))?;
+    let context = context.unwrap_or_else(|| "Local Archives".to_string());
+    let archive_hash = archive::archive_hash(&archive_bytes);
+    let base_url = archive::job_id(&archive_hash);
+    let is_processing = state
+        .active_chapter_jobs
+        .read()
+        .expect("lock poisoned")
+        .contains_key(&base_url);
+    if is_processing {
+        return Ok(Json(serde_json::json!({
+            "status": "already_processing",
+            "archive_hash": archive_hash,
+            "base_url": base_url,
+        })));
+    }
+
+    info!(
+        "OCR Archive: Received {} bytes as {base_url} ({context})",
+        archive_bytes.len()
+    );
+    let archive = tokio::task::spawn_blocking(move || {
+        ExtractedArchive::extract(&archive_bytes, &std::env::temp_dir())
+    })
+    .await
+    .map_err(|e| ApiError::new(ErrorCode::Internal, e.to_string()))??;
+    let pages = archive.pages.len();
+
+    tokio::spawn(archive::run_archive_job(state, archive, context));
+
+    Ok(Json(serde_json::json!({
+        "status": "started",
+        "archive_hash": archive_hash,
+        "base_url": base_url,
+        "pages": pages,
+    })))
+}
+
+/// Most URLs a single `/cached-status` call may ask about.
+pub const MAX_CACHED_STATUS_URLS: usize = 10_000;
+
+#[derive(Serialize)]
+pub struct CachedStatus {
+    pub url: String,
+    pub cached: bool,
+    /// Number of placed text blocks in the cached result; lines without geometry don't count.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_count: Option<usize>,
+    pub pinned: bool,
+}
+
+/// Reports which of many page URLs already have OCR results, so the reader can learn it for a
+/// whole screen of pages in one request. Answers from the cache alone and never starts OCR.
+pub async fn cached_status_handler(
+    State(state): State<AppState>,
+    Json(urls): Json<Vec<String>>,
+) -> Result<Json<Vec<CachedStatus>>, ApiError> {
+    if urls.len() > MAX_CACHED_STATUS_URLS {
+        return Err(ApiError::bad_request(format!(
+            "At most {MAX_CACHED_STATUS_URLS} URLs per request"
+        )));
+    }
+
+    // Keys are computed before taking the locks, which are then held for one pass each
+    let keys: Vec<String> = urls.iter().map(|url| logic::get_cache_key(url)).collect();
+    let block_counts: Vec<Option<usize>> = {
+        let cache = state.cache.read().expect("cache lock poisoned");
+        keys.iter()
+            .map(|key| cache.get(key).map(|entry| entry.placed_len()))
+            .collect()
+    };
+    let pinned: Vec<bool> = {
+        let pinned = state.pinned.read().expect("pinned lock poisoned");
+        keys.iter().map(|key| pinned.contains(key)).collect()
+    };
+
+    Ok(Json(
+        urls.into_iter()
+            .zip(block_counts)
+            .zip(pinned)
+            .map(|((url, block_count), pinned)| CachedStatus {
+                url,
+                cached: block_count.is_some(),
+                block_count,
+                pinned,
+            })
+            .collect(),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct PinRequest {
+    pub url: String,
+}
+
+pub async fn pin_entry_handler(
+    State(state): State<AppState>,
+    Json(req): Json<PinRequest>,
+) -> Json<serde_json::Value> {
+    let cache_key = logic::get_cache_key(&req.url);
+    state.set_pinned(&cache_key, true);
+    info!("Pinned cache_key={cache_key}");
+    let cached = state.cache.read().expect("lock").contains_key(&cache_key);
+    Json(serde_json::json!({ "status": "pinned", "cache_key": cache_key, "cached": cached }))
+}
+
+pub async fn unpin_entry_handler(
+    State(state): State<AppState>,
+    Json(req): Json<PinRequest>,
+) -> Json<serde_json::Value> {
+    let cache_key = logic::get_cache_key(&req.url);
+    let was_pinned = state.set_pinned(&cache_key, false);
+    Json(serde_json::json!({
+        "status": "unpinned",
+        "cache_key": cache_key,
+        "was_pinned": was_pinned,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct PurgeCacheRequest {
+    /// Keep pinned entries (and their pins) instead of wiping everything.
+    #[serde(default)]
+    pub keep_pinned: bool,
 }
 
-pub async fn purge_cache_handler(State(state): State<AppState>) -> Json<serde_json::Value> {
+pub async fn purge_cache_handler(
+    State(state): State<AppState>,
+    Query(params): Query<PurgeCacheRequest>,
+) -> Json<serde_json::Value> {
     let mut cache = state.cache.write().expect("lock");
-    cache.clear();
+    if params.keep_pinned {
+        let pinned = state.pinned.read().expect("pinned lock poisoned");
+        cache.retain(|key, _| pinned.contains(key));
+    } else {
+        cache.clear();
+    }
+    let kept = cache.len();
 
     drop(cache);
 
+    if !params.keep_pinned {
+        state.clear_pinned();
+    }
     state.save_cache();
-    Json(serde_json::json!({ "status": "cleared" }))
+    Json(serde_json::json!({ "status": "cleared", "kept_pinned": kept }))
+}
+
+pub async fn purge_image_cache_handler(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let files_removed = state.image_cache.as_ref().map_or(0, |cache| cache.purge());
+    info!("Image cache purged ({files_removed} files)");
+    Json(serde_json::json!({ "status": "cleared", "files_removed": files_removed }))
 }
 
 pub async fn export_cache_handler(
@@ -239,15 +1073,240 @@ pub async fn export_cache_handler(
     Json(cache.clone())
 }
 
+#[derive(Deserialize)]
+pub struct ChapterExportRequest {
+    pub base_url: String,
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// Overrides `MANGATAN_OCR_NO_ZWSP` for this export.
+    pub strip_zero_width: Option<bool>,
+}
+
+/// Exports the cached OCR text of a chapter, one entry per merged block.
+pub async fn export_chapter_handler(
+    State(state): State<AppState>,
+    Query(params): Query<ChapterExportRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let chapter_path = logic::get_cache_key(&params.base_url);
+    let mut cues = {
+        let cache = state.cache.read().expect("lock");
+        export::chapter_cues(&cache, &chapter_path)
+    };
+    if params.strip_zero_width.unwrap_or(state.strip_zero_width) {
+        for cue in &mut cues {
+            cue.text = logic::strip_zero_width(&cue.text);
+        }
+    }
+    if cues.is_empty() {
+        return Err(ApiError::new(
+            ErrorCode::NotFound,
+            format!("No cached pages for {chapter_path}"),
+        ));
+    }
+
+    let format = params.format;
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"chapter.{}\"", format.extension()),
+            ),
+        ],
+        export::render(&cues, format),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct ConvertImageRequest {
+    pub url: String,
+    pub user: Option<String>,
+    pub pass: Option<String>,
+    pub token: Option<String>,
+    /// Same as on `/ocr`: extra headers for the page download, JSON-encoded.
+    #[serde(default, deserialize_with = "json_header_map")]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub format: ConvertFormat,
+    /// JPEG quality, 1-100. WebP output is lossless.
+    pub quality: Option<u8>,
+}
+
+/// Re-encodes a page (AVIF, or anything else the OCR decoders read) as WebP or JPEG, for
+/// WebViews that can't display the source format. Fetched like `/ocr` fetches pages; results go
+/// through the image cache when it's enabled.
+pub async fn convert_image_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<ConvertImageRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let quality = params.quality.unwrap_or(convert::DEFAULT_QUALITY);
+    if !(1..=100).contains(&quality) {
+        return Err(ApiError::bad_request("quality must be between 1 and 100"));
+    }
+    let fetch_headers = page_fetch_headers(&params.headers, params.token.as_deref(), &headers)?;
+
+    let page_key = logic::get_cache_key(&params.url);
+    let converted_key = convert::cache_key(&page_key, params.format, quality);
+    let image_cache = state.image_cache.as_ref();
+    let converted = match image_cache.and_then(|cache| cache.get(&converted_key)) {
+        Some(bytes) => bytes,
+        None => {
+            let source = page_image(
+                &state,
+                &params.url,
+                params.user,
+                params.pass,
+                &fetch_headers,
+            )
+            .await?;
+            let format = params.format;
+            let converted = match convert::is_already(&source, format) {
+                true => source,
+                false => tokio::task::spawn_blocking(move || {
+                    convert::convert_image(&source, format, quality)
+                })
+                .await
+                .map_err(|e| ApiError::new(ErrorCode::Internal, e.to_string()))??,
+            };
+            if let Some(cache) = image_cache {
+                cache.put(&converted_key, &converted);
+            }
+            converted
+        }
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, params.format.content_type()),
+            (header::CACHE_CONTROL, "public, max-age=86400"),
+        ],
+        converted,
+    ))
+}
+
+/// A page's image: inline in a data URL, from the image cache, or downloaded.
+async fn page_image(
+    state: &AppState,
+    url: &str,
+    user: Option<String>,
+    pass: Option<String>,
+    fetch_headers: &HeaderMap,
+) -> Result<Vec<u8>, ApiError> {
+    if let Some(inline) = logic::data_url_bytes(url) {
+        return inline.map_err(|e| ApiError::bad_request(format!("{e:#}")));
+    }
+    let page_key = logic::get_cache_key(url);
+    if let Some(bytes) = state
+        .image_cache
+        .as_ref()
+        .and_then(|cache| cache.get(&page_key))
+    {
+        return Ok(bytes);
+    }
+    state.rate_limiter.acquire(url).await;
+    Ok(logic::fetch_image_bytes(url, user, pass, fetch_headers).await?)
+}
+
+#[derive(Deserialize)]
+pub struct EmbedRequest {
+    #[serde(default)]
+    pub embed: EmbedFormat,
+}
+
+/// A page with its OCR packed in (see [`embed`]), so archived pages don't need the server to
+/// be read. Takes the same parameters as `/ocr`, plus `embed=png` (the default) or `embed=zip`.
+/// With the image cache off, a page OCR'd for this request is downloaded twice.
+pub async fn ocr_image_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<OcrRequest>,
+    Query(request): Query<EmbedRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let fetch_headers = page_fetch_headers(&params.headers, params.token.as_deref(), &headers)?;
+    let (url, user, pass) = (params.url.clone(), params.user.clone(), params.pass.clone());
+    let include_no_geometry = params.include_no_geometry;
+    let strip = params.strip_zero_width.unwrap_or(state.strip_zero_width);
+
+    let page = get_or_process_page(&state, params, &headers).await?;
+    let data = strip_zero_width(filter_no_geometry(page.results, include_no_geometry), strip);
+    let image = page_image(&state, &url, user, pass, &fetch_headers).await?;
+    let format = request.embed;
+    let packed = tokio::task::spawn_blocking(move || embed::embed(&image, &data, format))
+        .await
+        .map_err(|e| ApiError::new(ErrorCode::Internal, e.to_string()))??;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"page.{}\"", format.extension()),
+            ),
+        ],
+        packed,
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct RawOcrRequest {
+    pub url: String,
+    pub user: Option<String>,
+    pub pass: Option<String>,
+    pub token: Option<String>,
+    /// Same as on `/ocr`: extra headers for the page download, JSON-encoded.
+    #[serde(default, deserialize_with = "json_header_map")]
+    pub headers: HashMap<String, String>,
+}
+
+/// Lens' lines for a page before merging, in the `.raw.json` fixture format, so a merge bug can
+/// be reported with the data that reproduces it. Always asks Lens; nothing is cached.
+pub async fn raw_ocr_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<RawOcrRequest>,
+) -> Result<Json<Vec<logic::RawChunk>>, ApiError> {
+    let fetch_headers = page_fetch_headers(&params.headers, params.token.as_deref(), &headers)?;
+    info!(
+        "Raw OCR Handler: Request for cache_key={}",
+        logic::get_cache_key(&params.url)
+    );
+
+    let chunks = logic::fetch_raw_chunks(
+        &params.url,
+        params.user,
+        params.pass,
+        &fetch_headers,
+        state.image_cache.as_ref(),
+        &state.rate_limiter,
+    )
+    .await?;
+    Ok(Json(chunks))
+}
+
+#[derive(Deserialize)]
+pub struct ImportCacheRequest {
+    /// Set entries that can't be used aside in `ocr-quarantine.json` instead of just counting
+    /// them.
+    #[serde(default)]
+    pub quarantine: bool,
+}
+
+/// Adds exported entries the cache doesn't have yet. Each one is checked first: what can be
+/// repaired is, the rest is skipped.
 pub async fn import_cache_handler(
     State(state): State<AppState>,
-    Json(data): Json<std::collections::HashMap<String, CacheEntry>>,
+    Query(params): Query<ImportCacheRequest>,
+    Json(data): Json<HashMap<String, serde_json::Value>>,
 ) -> Json<serde_json::Value> {
+    let mut rejected = Vec::new();
+    let mut entries = sanitize::parse_entries(data, &mut rejected);
+    let repaired = sanitize::check_entries(&mut entries, &mut rejected);
     let mut added = 0;
 
     {
         let mut cache = state.cache.write().expect("lock");
-        for (k, v) in data {
+        for (k, v) in entries {
             if let Entry::Vacant(e) = cache.entry(k) {
                 e.insert(v);
                 added += 1;
@@ -258,5 +1317,18 @@ pub async fn import_cache_handler(
     if added > 0 {
         state.save_cache();
     }
-    Json(serde_json::json!({ "message": "Import successful", "added": added }))
+    if !rejected.is_empty() {
+        warn!(
+            "Cache import skipped {} entries that can't be used",
+            rejected.len()
+        );
+    }
+    let quarantined = params.quarantine && !rejected.is_empty() && state.quarantine(&rejected);
+    Json(serde_json::json!({
+        "message": "Import successful",
+        "added": added,
+        "repaired": repaired,
+        "rejected": rejected.len(),
+        "quarantined": quarantined,
+    }))
 }
@@ -0,0 +1,128 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::state::{fingerprint, write_atomic};
+
+const IMAGE_CACHE_DIR: &str = "ocr-image-cache";
+
+/// Bounded on-disk copy of fetched page images, so a retry or re-OCR doesn't hit the source
+/// again. Eviction is least-recently-used by file mtime, which is refreshed on every hit.
+#[derive(Clone, Debug)]
+pub struct ImageCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct ImageCacheStats {
+    pub files: usize,
+    pub bytes: u64,
+    pub max_bytes: u64,
+}
+
+impl ImageCache {
+    /// Enabled by setting `MANGATAN_OCR_IMAGE_CACHE_MB` to a size above zero; off otherwise.
+    pub fn from_env(cache_dir: &Path) -> Option<Self> {
+        let max_mb = std::env::var("MANGATAN_OCR_IMAGE_CACHE_MB")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .filter(|mb| *mb > 0)?;
+
+        let dir = cache_dir.join(IMAGE_CACHE_DIR);
+        if let Err(e) = fs::create_dir_all(&dir) {
+            warn!(
+                "Image cache disabled, failed to create {}: {e}",
+                dir.display()
+            );
+            return None;
+        }
+        info!("Image cache enabled ({max_mb} MB) at {}", dir.display());
+
+        Some(Self {
+            dir,
+            max_bytes: max_mb * 1024 * 1024,
+        })
+    }
+
+    fn path_for(&self, cache_key: &str) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}.img", fingerprint(cache_key.as_bytes())))
+    }
+
+    pub fn get(&self, cache_key: &str) -> Option<Vec<u8>> {
+        let path = self.path_for(cache_key);
+        let bytes = fs::read(&path).ok()?;
+
+        // Touch so the entry counts as recently used
+        if let Ok(file) = fs::File::options().append(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(bytes)
+    }
+
+    pub fn put(&self, cache_key: &str, bytes: &[u8]) {
+        if bytes.len() as u64 > self.max_bytes {
+            return;
+        }
+        if write_atomic(&self.path_for(cache_key), bytes) {
+            self.evict();
+        }
+    }
+
+    /// Deletes every cached image and returns how many files were removed.
+    pub fn purge(&self) -> usize {
+        self.entries()
+            .into_iter()
+            .filter(|(path, _, _)| fs::remove_file(path).is_ok())
+            .count()
+    }
+
+    pub fn stats(&self) -> ImageCacheStats {
+        let entries = self.entries();
+        ImageCacheStats {
+            files: entries.len(),
+            bytes: entries.iter().map(|(_, len, _)| len).sum(),
+            max_bytes: self.max_bytes,
+        }
+    }
+
+    fn evict(&self) {
+        let mut entries = self.entries();
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        if total <= self.max_bytes {
+            return;
+        }
+
+        entries.sort_by_key(|(_, _, modified)| *modified);
+        for (path, len, _) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total = total.saturating_sub(len);
+            }
+        }
+    }
+
+    fn entries(&self) -> Vec<(PathBuf, u64, SystemTime)> {
+        let Ok(read_dir) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+
+        read_dir
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "img"))
+            .filter_map(|entry| {
+                let meta = entry.metadata().ok()?;
+                let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                Some((entry.path(), meta.len(), modified))
+            })
+            .collect()
+    }
+}
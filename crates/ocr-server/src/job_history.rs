@@ -0,0 +1,120 @@
+//! Summaries of finished chapter jobs. The live progress in `active_chapter_jobs` is dropped
+//! when a job ends, so this is what's left to tell a chapter with failed pages from a complete
+//! one.
+
+use std::{
+    collections::VecDeque,
+    fs,
+    path::{Path, PathBuf},
+    sync::RwLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::state::write_atomic;
+
+/// How many finished jobs are kept.
+pub const HISTORY_LIMIT: usize = 50;
+const HISTORY_FILE: &str = "ocr-job-history.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PageFailure {
+    pub url: String,
+    pub error: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct JobSummary {
+    pub base_url: String,
+    /// The `context` the job was started with, e.g. the manga and chapter title.
+    pub context: String,
+    /// Unix times in milliseconds.
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub total: usize,
+    /// Pages that were already cached, so never sent to Lens.
+    pub skipped: usize,
+    /// Pages OCR'd by this job, including those that only succeeded on the retry sweep.
+    pub processed: usize,
+    /// Pages still failing after the retry sweep.
+    pub failed: Vec<PageFailure>,
+    pub lens_calls: usize,
+    /// Lens was suspect at some point during the job, so empty pages may have been left uncached.
+    #[serde(default)]
+    pub backend_suspect: bool,
+}
+
+/// The last [`HISTORY_LIMIT`] finished jobs, oldest first. Kept on disk only when
+/// `MANGATAN_OCR_JOB_HISTORY_PERSIST` is set.
+pub struct JobHistory {
+    entries: RwLock<VecDeque<JobSummary>>,
+    path: Option<PathBuf>,
+}
+
+impl JobHistory {
+    /// A history saved to (and loaded from) `path`, or kept in memory only when `None`.
+    pub fn new(path: Option<PathBuf>) -> Self {
+        let entries = path.as_deref().map(load).unwrap_or_default();
+        Self {
+            entries: RwLock::new(entries),
+            path,
+        }
+    }
+
+    pub fn from_env(cache_dir: &Path) -> Self {
+        let persist = std::env::var("MANGATAN_OCR_JOB_HISTORY_PERSIST")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        Self::new(persist.then(|| cache_dir.join(HISTORY_FILE)))
+    }
+
+    pub fn record(&self, summary: JobSummary) {
+        let mut entries = self.entries.write().expect("history lock poisoned");
+        if entries.len() >= HISTORY_LIMIT {
+            entries.pop_front();
+        }
+        entries.push_back(summary);
+        if let Some(path) = &self.path {
+            write_atomic(path, &serde_json::to_vec(&*entries).unwrap_or_default());
+        }
+    }
+
+    /// Finished jobs, most recent first.
+    pub fn recent(&self) -> Vec<JobSummary> {
+        let entries = self.entries.read().expect("history lock poisoned");
+        entries.iter().rev().cloned().collect()
+    }
+
+    /// The most recent finished job for a chapter.
+    pub fn latest_for(&self, base_url: &str) -> Option<JobSummary> {
+        let entries = self.entries.read().expect("history lock poisoned");
+        entries
+            .iter()
+            .rev()
+            .find(|summary| summary.base_url == base_url)
+            .cloned()
+    }
+}
+
+fn load(path: &Path) -> VecDeque<JobSummary> {
+    let Ok(bytes) = fs::read(path) else {
+        return VecDeque::new();
+    };
+    let mut entries: VecDeque<JobSummary> = serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+        warn!("Failed to deserialize job history: {e}. Starting without it.");
+        VecDeque::new()
+    });
+    while entries.len() > HISTORY_LIMIT {
+        entries.pop_front();
+    }
+    entries
+}
+
+/// Now as Unix milliseconds, for [`JobSummary`] times.
+pub fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
@@ -1,78 +1,225 @@
-use std::sync::{
-    Arc,
-    atomic::{AtomicUsize, Ordering},
+use std::{
+    collections::HashSet,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
 };
 
 use futures::StreamExt;
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
 use tokio::sync::Mutex;
 
-use crate::state::{AppState, JobProgress};
+use crate::{
+    backend_watch::{self, CanaryPage},
+    error::{ApiError, ErrorCode},
+    job_history::{self, JobSummary, PageFailure},
+    logic::count_lens_calls,
+    merge::ReadingDirection,
+    state::{AppState, JobProgress},
+};
+
+/// Pages of one job OCR'd at a time; lower on Android for stability.
+pub const PAGE_CONCURRENCY: usize = if cfg!(target_os = "android") { 2 } else { 6 };
+
+/// Which way the reader moves through a chapter's page list. Cache keys come from the page
+/// URLs, so this only decides which pages are ready first.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadingOrder {
+    /// Up the list, from index 0.
+    #[default]
+    Forward,
+    /// Down the list, for readers that index right-to-left chapters from the far end.
+    Reverse,
+}
+
+/// Puts `pages` in the order a chapter job should OCR them: from `start` (the page on screen,
+/// as an index into `pages`) onwards in reading order, then the pages already behind the
+/// reader. Without `start`, from the first page in reading order.
+pub fn order_pages(
+    mut pages: Vec<String>,
+    order: ReadingOrder,
+    start: Option<usize>,
+) -> Vec<String> {
+    if order == ReadingOrder::Reverse {
+        pages.reverse();
+    }
+    let Some(start) = start.filter(|&start| start < pages.len()) else {
+        return pages;
+    };
+    // Positions flip along with the list
+    let start = match order {
+        ReadingOrder::Forward => start,
+        ReadingOrder::Reverse => pages.len() - 1 - start,
+    };
+    pages.rotate_left(start);
+    pages
+}
+
+/// The pages of a series still without usable OCR results, as left by [`take_missing_pages`].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MissingPages {
+    /// In the order given, one per cache key.
+    pub pages: Vec<String>,
+    /// How many of `pages` were never cached.
+    pub uncached: usize,
+    /// How many of `pages` had an empty result cached, which is now dropped.
+    pub empty: usize,
+}
 
+/// Picks out the pages of `pages` a job should OCR to complete a series: those not cached, and
+/// those cached with no placed text blocks, whose empty results are removed so the job doesn't skip
+/// them. Pinned results are kept as they are, even empty ones.
+pub fn take_missing_pages(state: &AppState, pages: Vec<String>) -> MissingPages {
+    let mut missing = MissingPages::default();
+    let mut seen = HashSet::new();
+    {
+        let mut cache = state.cache.write().expect("lock");
+        let pinned = state.pinned.read().expect("pinned lock poisoned");
+        for url in pages {
+            let key = crate::logic::get_cache_key(&url);
+            if !seen.insert(key.clone()) {
+                continue;
+            }
+            match cache.get(&key) {
+                None => missing.uncached += 1,
+                Some(entry) if entry.placed_len() == 0 && !pinned.contains(&key) => {
+                    cache.remove(&key);
+                    missing.empty += 1;
+                }
+                Some(_) => continue,
+            }
+            missing.pages.push(url);
+        }
+    }
+    if missing.empty > 0 {
+        state.cache_changed();
+    }
+    missing
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn run_chapter_job(
     state: AppState,
     base_url: String,
     pages: Vec<String>,
     user: Option<String>,
     pass: Option<String>,
+    fetch_headers: HeaderMap,
     context: String,
     add_space_on_merge: Option<bool>,
+    reading_direction: Option<ReadingDirection>,
 ) {
     let total = pages.len();
     let job_id = base_url.clone();
+    let started_at = job_history::unix_millis();
+    let trips_before = state.backend_watch.trips();
+
+    let key_collisions = crate::cache_key::rules().count_collisions(&pages);
+    if key_collisions > 0 {
+        tracing::warn!(
+            "[Job] {key_collisions}/{total} pages of {context} share a cache key with another page; \
+             their OCR results will overwrite each other. If the source pages by query string, \
+             list the parameter in MANGATAN_OCR_CACHE_KEY_QUERY."
+        );
+    }
+
+    // Pages cached by an earlier, interrupted run count as done from the start
+    let (cached, pages): (Vec<String>, Vec<String>) = {
+        let cache = state.cache.read().expect("lock");
+        pages
+            .into_iter()
+            .partition(|url| cache.contains_key(&crate::logic::get_cache_key(url)))
+    };
+    let already_cached = cached.len();
 
     {
         state
             .active_chapter_jobs
             .write()
             .expect("lock poisoned")
-            .insert(base_url.clone(), JobProgress { current: 0, total });
+            .insert(
+                base_url.clone(),
+                JobProgress {
+                    current: already_cached,
+                    total,
+                    resumed_from: already_cached,
+                    key_collisions,
+                    failed: 0,
+                    still_failing: None,
+                    last_error: None,
+                    backend_suspect: state.backend_watch.is_suspect(),
+                },
+            );
     }
 
     state.active_jobs.fetch_add(1, Ordering::Relaxed);
     tracing::info!("[Job] Started for {} ({} pages)", context, total);
+    if already_cached > 0 {
+        tracing::info!("[Job] Resuming: {already_cached}/{total} already cached");
+    }
 
-    let completed_counter = Arc::new(AtomicUsize::new(0));
+    let completed_counter = Arc::new(AtomicUsize::new(already_cached));
     let save_lock = Arc::new(Mutex::new(()));
+    let failed_urls = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let skipped = Arc::new(AtomicUsize::new(already_cached));
+    let processed = Arc::new(AtomicUsize::new(0));
+    let lens_calls = Arc::new(AtomicUsize::new(0));
     let stream = futures::stream::iter(pages.into_iter());
 
-    // Change from 6 to 2 or 3 for Android stability
-    let concurrency_limit = if cfg!(target_os = "android") { 2 } else { 6 };
-
     stream
-        .for_each_concurrent(concurrency_limit, |url| {
+        .for_each_concurrent(PAGE_CONCURRENCY, |url| {
             let state = state.clone();
             let base_url = base_url.clone();
             let user = user.clone();
             let pass = pass.clone();
+            let fetch_headers = fetch_headers.clone();
             let context = context.clone();
             let completed_counter = completed_counter.clone();
             let save_lock = save_lock.clone();
+            let failed_urls = failed_urls.clone();
+            let skipped = skipped.clone();
+            let processed = processed.clone();
+            let lens_calls = lens_calls.clone();
 
             let page_id = url.split('/').next_back().unwrap_or("unknown").to_string();
 
             async move {
+                // Sit idle while paused; progress is kept so the job picks up where it stopped
+                while state.is_paused() {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+
+                let mut failure = None;
                 let cache_key = crate::logic::get_cache_key(&url);
                 let exists = { state.cache.read().expect("lock").contains_key(&cache_key) };
                 if exists {
                     tracing::info!("[Page {page_id}] Skip (Cached)");
+                    skipped.fetch_add(1, Ordering::Relaxed);
                 } else {
                     tracing::info!("[Page {page_id}] Starting fetch_and_process (Async)...");
 
-                    // None defaults to Smart Detection for space merging
-                    match crate::logic::fetch_and_process(&url, user, pass, add_space_on_merge)
-                        .await
-                    {
-                        Ok(res) => {
-                            state.cache.write().expect("lock").insert(
-                                cache_key,
-                                crate::state::CacheEntry {
-                                    context: context.clone(),
-                                    data: res,
-                                },
-                            );
+                    let page = process_page(
+                        &state,
+                        &url,
+                        user,
+                        pass,
+                        &fetch_headers,
+                        &context,
+                        add_space_on_merge,
+                        reading_direction,
+                    );
+                    match count_lens_calls(lens_calls, page).await {
+                        Ok(()) => {
+                            processed.fetch_add(1, Ordering::Relaxed);
                         }
                         Err(err) => {
-                            tracing::warn!("[Page {page_id}] Failed: {err:?}");
+                            tracing::warn!("[Page {page_id}] Failed: {err}");
+                            failure = Some(err);
+                            failed_urls.lock().expect("lock").push(url.clone());
                         }
                     }
                 }
@@ -87,6 +234,11 @@ pub async fn run_chapter_job(
                         .get_mut(&base_url)
                     {
                         prog.current = current;
+                        prog.backend_suspect = state.backend_watch.is_suspect();
+                        if let Some(err) = failure {
+                            prog.failed += 1;
+                            prog.last_error = Some(err);
+                        }
                     }
                 }
 
@@ -99,12 +251,72 @@ pub async fn run_chapter_job(
         })
         .await;
 
+    // One more go at the failed pages, for transient failures (rate limits, timeouts, ...)
+    let failed_urls = std::mem::take(&mut *failed_urls.lock().expect("lock"));
+    let mut still_failing = failed_urls.len();
+    let mut failures = Vec::new();
+    if !failed_urls.is_empty() {
+        tracing::info!(
+            "[Job {job_id}] Retrying {} failed pages...",
+            failed_urls.len()
+        );
+        set_still_failing(&state, &base_url, still_failing, None);
+
+        for url in failed_urls {
+            while state.is_paused() {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            let page_id = url.split('/').next_back().unwrap_or("unknown");
+            let page = process_page(
+                &state,
+                &url,
+                user.clone(),
+                pass.clone(),
+                &fetch_headers,
+                &context,
+                add_space_on_merge,
+                reading_direction,
+            );
+            match count_lens_calls(lens_calls.clone(), page).await {
+                Ok(()) => {
+                    tracing::info!("[Page {page_id}] Succeeded on retry");
+                    processed.fetch_add(1, Ordering::Relaxed);
+                    still_failing -= 1;
+                    set_still_failing(&state, &base_url, still_failing, None);
+                }
+                Err(err) => {
+                    tracing::warn!("[Page {page_id}] Failed again: {err}");
+                    failures.push(PageFailure {
+                        url: url.clone(),
+                        error: err.to_string(),
+                    });
+                    set_still_failing(&state, &base_url, still_failing, Some(err));
+                }
+            }
+        }
+    }
+
     // Final Save
     tracing::info!("[Job {job_id}] Final save...");
     state.save_cache();
     tracing::info!("[Job {job_id}] Final save complete.");
 
     state.active_jobs.fetch_sub(1, Ordering::Relaxed);
+    state.jobs_completed.fetch_add(1, Ordering::Relaxed);
+
+    state.job_history.record(JobSummary {
+        base_url: base_url.clone(),
+        context: context.clone(),
+        started_at,
+        finished_at: job_history::unix_millis(),
+        total,
+        skipped: skipped.load(Ordering::Relaxed),
+        processed: processed.load(Ordering::Relaxed),
+        failed: failures,
+        lens_calls: lens_calls.load(Ordering::Relaxed),
+        backend_suspect: state.backend_watch.is_suspect()
+            || state.backend_watch.trips() != trips_before,
+    });
 
     {
         state
@@ -114,5 +326,78 @@ pub async fn run_chapter_job(
             .remove(&base_url);
     }
 
-    tracing::info!("[Job {job_id}] Finished for {}", context);
+    if still_failing > 0 {
+        tracing::warn!(
+            "[Job {job_id}] Finished for {context}; {still_failing}/{total} pages still failing after the retry"
+        );
+    } else {
+        tracing::info!("[Job {job_id}] Finished for {}", context);
+    }
+}
+
+/// OCRs one page and caches the result.
+#[allow(clippy::too_many_arguments)]
+async fn process_page(
+    state: &AppState,
+    url: &str,
+    user: Option<String>,
+    pass: Option<String>,
+    fetch_headers: &HeaderMap,
+    context: &str,
+    add_space_on_merge: Option<bool>,
+    reading_direction: Option<ReadingDirection>,
+) -> Result<(), ApiError> {
+    // None defaults to Smart Detection for space merging
+    let res = crate::logic::fetch_and_process(
+        url,
+        user.clone(),
+        pass.clone(),
+        fetch_headers,
+        add_space_on_merge,
+        reading_direction,
+        state.image_cache.as_ref(),
+        &state.rate_limiter,
+    )
+    .await?;
+    // A partial page would stay cached; leave it to the retry sweep instead
+    if res.incomplete {
+        return Err(ApiError::new(
+            ErrorCode::Unavailable,
+            "OCR timed out before the whole page was done (MANGATAN_OCR_TOTAL_TIMEOUT)",
+        ));
+    }
+    let cache_key = crate::logic::get_cache_key(url);
+    let canary = || CanaryPage {
+        url: url.to_string(),
+        user: user.clone(),
+        pass: pass.clone(),
+        fetch_headers: fetch_headers.clone(),
+    };
+    if !backend_watch::should_cache(state, &cache_key, &res, canary) {
+        return Ok(());
+    }
+    state.cache.write().expect("lock").insert(
+        cache_key,
+        crate::state::CacheEntry {
+            context: context.to_string(),
+            data: res.results,
+            truncated_from: res.truncated_from,
+            merge_quality: res.merge_quality,
+        },
+    );
+    Ok(())
+}
+
+fn set_still_failing(state: &AppState, base_url: &str, count: usize, error: Option<ApiError>) {
+    if let Some(prog) = state
+        .active_chapter_jobs
+        .write()
+        .expect("lock")
+        .get_mut(base_url)
+    {
+        prog.still_failing = Some(count);
+        if let Some(err) = error {
+            prog.last_error = Some(err);
+        }
+    }
 }
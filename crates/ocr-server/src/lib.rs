@@ -1,8 +1,24 @@
+pub mod archive;
+pub mod backend_watch;
+pub mod cache_key;
+pub mod convert;
+pub mod diagnostic;
+pub mod embed;
+pub mod error;
+pub mod export;
 pub mod handlers;
+pub mod image_cache;
+pub mod job_history;
 pub mod jobs;
 pub mod logic;
 pub mod merge;
+#[cfg(feature = "text-prefilter")]
+pub mod prefilter;
+pub mod quality;
+pub mod rate_limit;
+pub mod sanitize;
 pub mod state;
+pub mod watchdog;
 
 use std::path::PathBuf;
 
@@ -15,21 +31,66 @@ use state::AppState;
 
 /// Creates the OCR Router.
 pub fn create_router(cache_dir: PathBuf) -> Router {
-    let state = AppState::new(cache_dir);
+    create_router_with_state(AppState::new(cache_dir))
+}
 
+/// Creates the OCR Router around an existing state, so the caller can keep a handle to it
+/// (e.g. to log the session summary on shutdown).
+pub fn create_router_with_state(state: AppState) -> Router {
     // Spawn the job worker if you want strict concurrency,
     // or we just spawn tasks per request (handled in handlers).
+    if let Some(interval) = state.flush_interval {
+        tokio::spawn(state::flush_periodically(state.clone(), interval));
+    }
+    tokio::spawn(watchdog::watch_saver(state.clone()));
 
     Router::new()
         .route("/", get(handlers::status_handler))
-        .route("/ocr", get(handlers::ocr_handler))
+        .route(
+            "/ocr",
+            get(handlers::ocr_handler).post(handlers::ocr_upload_handler),
+        )
+        .route("/ocr-text", get(handlers::ocr_text_handler))
+        .route("/raw-ocr", get(handlers::raw_ocr_handler))
+        .route("/convert-image", get(handlers::convert_image_handler))
+        .route("/ocr-image", get(handlers::ocr_image_handler))
+        .route("/ocr-backend-health", get(handlers::backend_health_handler))
+        .route("/diagnostic", get(handlers::diagnostic_handler))
         .route(
             "/is-chapter-preprocessed",
             post(handlers::is_chapter_preprocessed_handler),
         )
         .route("/preprocess-chapter", post(handlers::preprocess_handler))
+        .route("/fill-missing", post(handlers::fill_missing_handler))
+        .route(
+            "/preprocess-archive",
+            post(handlers::preprocess_archive_handler)
+                .layer(DefaultBodyLimit::max(archive::max_archive_bytes())),
+        )
+        .route("/jobs/history", get(handlers::job_history_handler))
+        .route("/pause", post(handlers::pause_handler))
+        .route("/resume", post(handlers::resume_handler))
+        .route("/flush-cache", post(handlers::flush_cache_handler))
+        .route("/storage-stats", get(handlers::storage_stats_handler))
+        .route("/quality-report", get(handlers::quality_report_handler))
+        .route(
+            "/maintenance/force-save",
+            post(handlers::force_save_handler),
+        )
+        .route(
+            "/maintenance/purge-suspect-empty",
+            post(handlers::purge_suspect_empty_handler),
+        )
+        .route("/cached-status", post(handlers::cached_status_handler))
+        .route("/pin-entry", post(handlers::pin_entry_handler))
+        .route("/unpin-entry", post(handlers::unpin_entry_handler))
         .route("/purge-cache", post(handlers::purge_cache_handler))
+        .route(
+            "/purge-image-cache",
+            post(handlers::purge_image_cache_handler),
+        )
         .route("/export-cache", get(handlers::export_cache_handler))
+        .route("/export-chapter", get(handlers::export_chapter_handler))
         .route("/import-cache", post(handlers::import_cache_handler))
         .layer(DefaultBodyLimit::max(50 * 1024 * 1024)) // 50MB limit for imports
         .with_state(state)
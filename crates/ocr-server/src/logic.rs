@@ -1,14 +1,33 @@
-use std::{io::Cursor, time::Duration};
-
-use anyhow::anyhow;
+use std::{
+    collections::{HashMap, hash_map::RandomState},
+    hash::{BuildHasher, Hasher},
+    io::Cursor,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, anyhow};
+use base64::{Engine, prelude::BASE64_STANDARD};
 use chrome_lens_ocr::LensClient;
-use image::{DynamicImage, GenericImageView, ImageBuffer, ImageFormat, ImageReader};
+use image::{
+    DynamicImage, GenericImageView, ImageBuffer, ImageFormat, ImageReader, RgbaImage,
+    imageops::FilterType,
+};
 use lazy_static::lazy_static;
 use regex::Regex;
-use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue};
 use serde::{Deserialize, Serialize};
 
-use crate::merge::{self, MergeConfig};
+use crate::{
+    error::LensFailure,
+    image_cache::ImageCache,
+    merge::{self, MergeConfig, ReadingDirection},
+    quality::MergeQuality,
+    rate_limit::RateLimiter,
+};
 
 // --- GraphQL Query Definitions ---
 
@@ -269,8 +288,49 @@ pub struct OcrResult {
     #[serde(rename = "isMerged", skip_serializing_if = "Option::is_none")]
     pub is_merged: Option<bool>,
 
+    /// `vertical` or `horizontal`; kept for clients that predate [`Orientation`].
     #[serde(rename = "forcedOrientation", skip_serializing_if = "Option::is_none")]
     pub forced_orientation: Option<String>,
+
+    /// Set on merged results; unlike `forced_orientation` it tells which way columns run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orientation: Option<Orientation>,
+
+    /// Set on text Lens recognized but could not locate; the box is a zero-sized placeholder.
+    #[serde(rename = "noGeometry", default, skip_serializing_if = "Option::is_none")]
+    pub no_geometry: Option<bool>,
+}
+
+/// How many of `results` have a box on the page, leaving out `no_geometry` placeholders.
+pub fn placed_len(results: &[OcrResult]) -> usize {
+    results
+        .iter()
+        .filter(|r| r.no_geometry != Some(true))
+        .count()
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    /// Columns read right to left, as in Japanese.
+    VerticalRtl,
+    /// Columns read left to right (Mongolian-style layouts, text rotated counter-clockwise).
+    VerticalLtr,
+    Horizontal,
+}
+
+impl Orientation {
+    pub fn is_vertical(self) -> bool {
+        self != Self::Horizontal
+    }
+
+    /// The `forcedOrientation` value older clients understand.
+    pub fn legacy(self) -> &'static str {
+        match self {
+            Self::VerticalRtl | Self::VerticalLtr => "vertical",
+            Self::Horizontal => "horizontal",
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
@@ -283,16 +343,247 @@ pub struct BoundingBox {
     pub rotation: Option<f64>,
 }
 
-/// Helper to strip the scheme/host/query from the URL for caching purposes.
+/// Helper to strip the scheme/host/query from the URL for caching purposes. Query parameters
+/// are only kept for hosts configured in `MANGATAN_OCR_CACHE_KEY_QUERY` (see [`CacheKeyRules`]).
+///
+/// [`CacheKeyRules`]: crate::cache_key::CacheKeyRules
 pub fn get_cache_key(url: &str) -> String {
-    if let Ok(parsed) = reqwest::Url::parse(url) {
-        return parsed.path().to_string();
+    match data_url_bytes(url) {
+        // Content-addressed like uploads, so the same image shares one entry either way
+        Some(Ok(bytes)) => upload_cache_key(&bytes),
+        Some(Err(_)) => upload_cache_key(url.as_bytes()),
+        None => crate::cache_key::rules().key_for(url),
+    }
+}
+
+/// Cache key for an image that didn't come from a URL (uploads and `data:` URLs).
+pub fn upload_cache_key(image_bytes: &[u8]) -> String {
+    format!("/upload/{:016x}", crate::state::fingerprint(image_bytes))
+}
+
+/// The image embedded in a `data:` URL, or `None` for any other URL. Only base64 payloads are
+/// supported, which is what canvases and file readers produce.
+pub fn data_url_bytes(url: &str) -> Option<anyhow::Result<Vec<u8>>> {
+    let rest = url.strip_prefix("data:")?;
+    let decoded = rest
+        .split_once(',')
+        .filter(|(meta, _)| meta.ends_with(";base64"))
+        .ok_or_else(|| anyhow!("Failed decode data URL: expected a base64 payload"))
+        .and_then(|(_, payload)| {
+            // An unencoded `+` in a query string arrives as a space
+            let payload: String = payload
+                .chars()
+                .filter_map(|c| match c {
+                    ' ' => Some('+'),
+                    c if c.is_ascii_whitespace() => None,
+                    c => Some(c),
+                })
+                .collect();
+            BASE64_STANDARD
+                .decode(payload)
+                .map_err(|err| anyhow!("Failed decode data URL: {err}"))
+        });
+    Some(decoded)
+}
+
+/// `url` for log lines; `data:` URLs would otherwise dump the whole image.
+fn loggable_url(url: &str) -> std::borrow::Cow<'_, str> {
+    match url.starts_with("data:") {
+        true => format!("data: URL ({} bytes)", url.len()).into(),
+        false => url.into(),
     }
-    url.split('?').next().unwrap_or(url).to_string()
+}
+
+/// Removes zero-width characters (ZWSP, ZWNJ, ZWJ, word joiner, BOM), which some SRS importers
+/// and search boxes choke on.
+pub fn strip_zero_width(text: &str) -> String {
+    text.chars()
+        .filter(|c| {
+            !matches!(
+                c,
+                '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}'
+            )
+        })
+        .collect()
+}
+
+/// Joins merged results into plain text in reading order (see [`reading_order`]).
+pub fn results_to_plain_text(results: &[OcrResult]) -> String {
+    reading_order(results)
+        .iter()
+        .map(|r| r.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Sorts merged results into reading order: columns when the page is mostly vertical text
+/// (right to left, unless most of it was detected as left-to-right), top-to-bottom rows
+/// otherwise. Lines without geometry go last.
+pub fn reading_order(results: &[OcrResult]) -> Vec<&OcrResult> {
+    let (mut ordered, unplaced): (Vec<&OcrResult>, Vec<&OcrResult>) = results
+        .iter()
+        .partition(|r| r.no_geometry != Some(true));
+
+    let vertical_count = ordered
+        .iter()
+        .filter(|r| r.forced_orientation.as_deref() == Some("vertical"))
+        .count();
+    let is_vertical_page = vertical_count * 2 > ordered.len();
+    let ltr_count = ordered
+        .iter()
+        .filter(|r| r.orientation == Some(Orientation::VerticalLtr))
+        .count();
+    let is_ltr_page = is_vertical_page && ltr_count * 2 > vertical_count;
+
+    ordered.sort_by(|a, b| {
+        let (a_box, b_box) = (&a.tight_bounding_box, &b.tight_bounding_box);
+        if is_ltr_page {
+            a_box
+                .x
+                .total_cmp(&b_box.x)
+                .then(a_box.y.total_cmp(&b_box.y))
+        } else if is_vertical_page {
+            (b_box.x + b_box.width)
+                .total_cmp(&(a_box.x + a_box.width))
+                .then(a_box.y.total_cmp(&b_box.y))
+        } else {
+            a_box.y.total_cmp(&b_box.y).then(a_box.x.total_cmp(&b_box.x))
+        }
+    });
+
+    ordered.extend(unplaced);
+    ordered
 }
 
 lazy_static! {
     static ref CJK_REGEX: Regex = Regex::new(r"[\p{Han}\p{Hiragana}\p{Katakana}]").unwrap();
+    static ref LENS_PACING: (Duration, Duration) = lens_pacing_from_env();
+    static ref LAST_LENS_CALL: tokio::sync::Mutex<Option<Instant>> = tokio::sync::Mutex::new(None);
+    static ref MAX_LENS_DIMENSION: Option<u32> = max_dimension_from_env();
+    static ref TOTAL_TIMEOUT: Option<Duration> = total_timeout_from_env();
+}
+
+const DEFAULT_LENS_DELAY_MS: u64 = 300;
+const DEFAULT_LENS_JITTER_MS: u64 = 200;
+
+/// Reads `MANGATAN_OCR_LENS_DELAY_MS` / `MANGATAN_OCR_LENS_JITTER_MS`; set both to 0 to disable.
+fn lens_pacing_from_env() -> (Duration, Duration) {
+    let read_ms = |name: &str, default: u64| {
+        std::env::var(name)
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .unwrap_or(default)
+    };
+    (
+        Duration::from_millis(read_ms("MANGATAN_OCR_LENS_DELAY_MS", DEFAULT_LENS_DELAY_MS)),
+        Duration::from_millis(read_ms(
+            "MANGATAN_OCR_LENS_JITTER_MS",
+            DEFAULT_LENS_JITTER_MS,
+        )),
+    )
+}
+
+/// Reads `MANGATAN_OCR_MAX_DIMENSION` (pixels); unset or 0 sends chunks at full resolution.
+fn max_dimension_from_env() -> Option<u32> {
+    std::env::var("MANGATAN_OCR_MAX_DIMENSION")
+        .ok()
+        .and_then(|v| v.trim().parse::<u32>().ok())
+        .filter(|max| *max > 0)
+}
+
+const DEFAULT_TOTAL_TIMEOUT_SECS: u64 = 90;
+
+/// Reads `MANGATAN_OCR_TOTAL_TIMEOUT` (seconds); 0 lets a page take as long as it needs.
+fn total_timeout_from_env() -> Option<Duration> {
+    parse_total_timeout(std::env::var("MANGATAN_OCR_TOTAL_TIMEOUT").ok().as_deref())
+}
+
+/// The deadline for a whole [`fetch_and_process`] call, retries included, from the value of
+/// `MANGATAN_OCR_TOTAL_TIMEOUT`.
+pub fn parse_total_timeout(value: Option<&str>) -> Option<Duration> {
+    let secs = value
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_TOTAL_TIMEOUT_SECS);
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// Runs `fut` to completion, or until `deadline` passes, in which case it's `None`.
+async fn before_deadline<F: std::future::Future>(
+    deadline: Option<Instant>,
+    fut: F,
+) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) => {
+            tokio::time::timeout(deadline.saturating_duration_since(Instant::now()), fut)
+                .await
+                .ok()
+        }
+        None => Some(fut.await),
+    }
+}
+
+/// Shrinks a chunk whose longest side exceeds the configured maximum before it is sent to Lens.
+/// Lens geometry is relative to the image it was given, so boxes still scale onto the original
+/// page size.
+fn downscale_for_lens(chunk: RgbaImage) -> RgbaImage {
+    let Some(max_dimension) = *MAX_LENS_DIMENSION else {
+        return chunk;
+    };
+    let (width, height) = chunk.dimensions();
+    let longest = width.max(height);
+    if longest <= max_dimension {
+        return chunk;
+    }
+
+    let scale = f64::from(max_dimension) / f64::from(longest);
+    let scaled = |side: u32| ((f64::from(side) * scale).round() as u32).max(1);
+    tracing::debug!(
+        "Downscaling OCR chunk {width}x{height} to {}x{}",
+        scaled(width),
+        scaled(height)
+    );
+    image::imageops::resize(&chunk, scaled(width), scaled(height), FilterType::Triangle)
+}
+
+tokio::task_local! {
+    // Lens calls made within `count_lens_calls`; only those are paced
+    static LENS_CALLS: Arc<AtomicUsize>;
+}
+
+/// Runs `future` as background work (chapter and archive jobs): each Lens call it makes is
+/// paced and added to `counter`.
+pub async fn count_lens_calls<F: Future>(counter: Arc<AtomicUsize>, future: F) -> F::Output {
+    LENS_CALLS.scope(counter, future).await
+}
+
+/// Waits until at least the configured delay (plus random jitter) has passed since the previous
+/// paced Lens call. Shared by every job, so concurrent chapter jobs pace themselves too. Calls
+/// outside [`count_lens_calls`] are a reader waiting on a page and aren't held up.
+async fn pace_lens_call() {
+    if LENS_CALLS
+        .try_with(|calls| calls.fetch_add(1, Ordering::Relaxed))
+        .is_err()
+    {
+        return;
+    }
+    let (delay, jitter) = *LENS_PACING;
+    if delay.is_zero() && jitter.is_zero() {
+        return;
+    }
+
+    let mut last_call = LAST_LENS_CALL.lock().await;
+    if let Some(last) = *last_call {
+        let jitter_ms = match jitter.as_millis() as u64 {
+            0 => 0,
+            max => RandomState::new().build_hasher().finish() % (max + 1),
+        };
+        let wait = delay + Duration::from_millis(jitter_ms);
+        let elapsed = last.elapsed();
+        if elapsed < wait {
+            tokio::time::sleep(wait - elapsed).await;
+        }
+    }
+    *last_call = Some(Instant::now());
 }
 
 fn post_process_text(text: String) -> String {
@@ -361,16 +652,52 @@ fn decode_avif_custom(bytes: &[u8]) -> anyhow::Result<DynamicImage> {
     }
 }
 
+/// A page's OCR results, after [`cap_results`].
+#[derive(Clone, Debug)]
+pub struct PageResults {
+    pub results: Vec<OcrResult>,
+    /// How many blocks the page had when the cap dropped some.
+    pub truncated_from: Option<usize>,
+    /// `MANGATAN_OCR_TOTAL_TIMEOUT` ran out before every chunk was OCR'd, so `results` only
+    /// covers the top of the page.
+    pub incomplete: bool,
+    /// `results` is empty and the text prefilter thinks the page has no text, so that's expected.
+    pub looks_blank: bool,
+    /// How the merge turned out; `None` when merging is disabled or the page came from a cache
+    /// entry that predates the metrics.
+    pub merge_quality: Option<MergeQuality>,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn fetch_and_process(
     url: &str,
     user: Option<String>,
     pass: Option<String>,
+    fetch_headers: &HeaderMap,
     add_space_on_merge: Option<bool>,
-) -> anyhow::Result<Vec<OcrResult>> {
+    reading_direction: Option<ReadingDirection>,
+    image_cache: Option<&ImageCache>,
+    rate_limiter: &RateLimiter,
+) -> anyhow::Result<PageResults> {
+    // Inline images skip the fetch entirely, and a malformed one won't improve on retry
+    let inline_image = data_url_bytes(url).transpose()?;
+    let deadline = TOTAL_TIMEOUT.map(|timeout| Instant::now() + timeout);
     let mut last_error = anyhow!("Unknown error");
 
     for attempt_number in 1..=3 {
-        match fetch_and_process_internal(url, user.clone(), pass.clone(), add_space_on_merge).await
+        match fetch_and_process_internal(
+            url,
+            inline_image.as_deref(),
+            user.clone(),
+            pass.clone(),
+            fetch_headers,
+            add_space_on_merge,
+            reading_direction,
+            image_cache,
+            rate_limiter,
+            deadline,
+        )
+        .await
         {
             Ok(result) => return Ok(result),
             Err(error) => {
@@ -378,54 +705,28 @@ pub async fn fetch_and_process(
                 tracing::warn!(
                     "Attempt {} failed for {}: {:?}",
                     attempt_number,
-                    url,
+                    loggable_url(url),
                     last_error
                 );
-                tokio::time::sleep(Duration::from_secs(attempt_number)).await;
+                let backoff = Duration::from_secs(attempt_number);
+                if deadline.is_some_and(|deadline| Instant::now() + backoff >= deadline) {
+                    break;
+                }
+                tokio::time::sleep(backoff).await;
             }
         }
     }
     Err(last_error)
 }
 
-// --- Data Structure for Test Caching ---
-
-#[derive(Serialize, Deserialize, Clone)]
-pub struct RawChunk {
-    pub lines: Vec<OcrResult>,
-    pub width: u32,
-    pub height: u32,
-    pub global_y: u32,
-    pub full_width: u32,
-    pub full_height: u32,
-}
-
-// --- Public Helper for Testing ---
-pub async fn get_raw_ocr_data(
-    image_bytes: &[u8],
+/// Creates a `LensClient`, routed through Suwayomi's SOCKS proxy when one is configured, else
+/// through `MANGATAN_HTTP_PROXY`.
+async fn build_lens_client(
     user: Option<String>,
     pass: Option<String>,
-) -> anyhow::Result<Vec<RawChunk>> {
-    let reader = ImageReader::new(Cursor::new(image_bytes))
-        .with_guessed_format()
-        .map_err(|err| anyhow!("Failed with_guessed_format: {err:?}"))?;
-
-    let decoded_image = if reader.format() == Some(ImageFormat::Avif) {
-        decode_avif_custom(image_bytes)?
-    } else {
-        reader
-            .decode()
-            .map_err(|err| anyhow!("Failed decode: {err:?}"))?
-    };
-
-    let full_image_width = decoded_image.width();
-    let full_image_height = decoded_image.height();
-    let chunk_height_limit = 3000;
-
-    let mut raw_chunks = Vec::new();
-
+) -> anyhow::Result<LensClient> {
     // Fetch proxy settings
-    let proxy_settings = get_proxy_settings(user.clone(), pass.clone()).await.ok().flatten();
+    let proxy_settings = get_proxy_settings(user, pass).await.ok().flatten();
     
     // Create LensClient with optional proxy
     let lens_client = if let Some(ref proxy) = proxy_settings {
@@ -469,12 +770,134 @@ pub async fn get_raw_ocr_data(
             LensClient::new_with_proxy(None, Some(&proxy_url))
                 .map_err(|e| anyhow!("Failed to create LensClient with proxy: {}", e))?
         } else {
-            LensClient::new(None)
+            default_lens_client()?
         }
     } else {
-        LensClient::new(None)
+        default_lens_client()?
     };
 
+    Ok(lens_client)
+}
+
+fn default_lens_client() -> anyhow::Result<LensClient> {
+    match outbound_proxy() {
+        Some(proxy_url) => {
+            tracing::info!("Using MANGATAN_HTTP_PROXY for Google Lens");
+            LensClient::new_with_proxy(None, Some(&proxy_url))
+                .map_err(|e| anyhow!("Failed to create LensClient with proxy: {e}"))
+        }
+        None => Ok(LensClient::new(None)),
+    }
+}
+
+/// Hosts that never go through `MANGATAN_HTTP_PROXY` (Suwayomi and Mangatan itself).
+const LOCAL_HOSTS: &str = "localhost,127.0.0.1,::1";
+
+/// Proxy URL for outbound traffic from `MANGATAN_HTTP_PROXY`. The standard `HTTP_PROXY`,
+/// `HTTPS_PROXY` and `NO_PROXY` variables are honored by reqwest either way.
+pub fn outbound_proxy() -> Option<String> {
+    std::env::var("MANGATAN_HTTP_PROXY")
+        .ok()
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+}
+
+/// A reqwest client that sends everything except local traffic through `outbound_proxy`.
+pub fn http_client() -> reqwest::Client {
+    let mut builder = reqwest::Client::builder();
+    if let Some(url) = outbound_proxy() {
+        match reqwest::Proxy::all(&url) {
+            Ok(proxy) => {
+                builder = builder.proxy(proxy.no_proxy(reqwest::NoProxy::from_string(LOCAL_HOSTS)))
+            }
+            Err(e) => tracing::warn!("Ignoring invalid MANGATAN_HTTP_PROXY: {e}"),
+        }
+    }
+    builder.build().unwrap_or_default()
+}
+
+/// Runs a tiny blank image through Lens to confirm it is reachable and not rate-limiting us.
+/// Returns the round-trip latency.
+pub async fn check_lens_health(
+    user: Option<String>,
+    pass: Option<String>,
+) -> anyhow::Result<Duration> {
+    let lens_client = build_lens_client(user, pass).await?;
+
+    let probe_image = DynamicImage::new_rgb8(64, 64);
+    let mut image_buffer = Cursor::new(Vec::new());
+    probe_image
+        .write_to(&mut image_buffer, ImageFormat::Png)
+        .map_err(|err| anyhow!("Failed write_to: {err:?}"))?;
+    let probe_png_bytes = image_buffer.into_inner();
+
+    let started = Instant::now();
+    lens_client
+        .process_image_bytes(&probe_png_bytes, Some("jp"))
+        .await
+        .map_err(LensFailure::new)?;
+
+    Ok(started.elapsed())
+}
+
+// --- Data Structure for Test Caching ---
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RawChunk {
+    pub lines: Vec<OcrResult>,
+    /// Text of lines Lens returned without geometry, in response order.
+    #[serde(default)]
+    pub no_geometry_lines: Vec<String>,
+    pub width: u32,
+    pub height: u32,
+    pub global_y: u32,
+    pub full_width: u32,
+    pub full_height: u32,
+}
+
+/// Decodes a page image in any format the pipeline supports (AVIF through `avif-decode`).
+pub fn decode_image(image_bytes: &[u8]) -> anyhow::Result<DynamicImage> {
+    let reader = ImageReader::new(Cursor::new(image_bytes))
+        .with_guessed_format()
+        .map_err(|err| anyhow!("Failed with_guessed_format: {err:?}"))?;
+
+    if reader.format() == Some(ImageFormat::Avif) {
+        decode_avif_custom(image_bytes)
+    } else {
+        reader
+            .decode()
+            .map_err(|err| anyhow!("Failed decode: {err:?}"))
+    }
+}
+
+// --- Public Helper for Testing ---
+pub async fn get_raw_ocr_data(
+    image_bytes: &[u8],
+    user: Option<String>,
+    pass: Option<String>,
+) -> anyhow::Result<Vec<RawChunk>> {
+    let (raw_chunks, _) = get_raw_ocr_data_until(image_bytes, user, pass, None).await?;
+    Ok(raw_chunks)
+}
+
+/// [`get_raw_ocr_data`], stopping at `deadline` with the chunks done so far. The flag tells
+/// whether it stopped early.
+async fn get_raw_ocr_data_until(
+    image_bytes: &[u8],
+    user: Option<String>,
+    pass: Option<String>,
+    deadline: Option<Instant>,
+) -> anyhow::Result<(Vec<RawChunk>, bool)> {
+    let decoded_image = decode_image(image_bytes)?;
+
+    let full_image_width = decoded_image.width();
+    let full_image_height = decoded_image.height();
+    let chunk_height_limit = 3000;
+
+    let mut raw_chunks = Vec::new();
+
+    let lens_client = build_lens_client(user, pass).await?;
+
     let mut current_y_position = 0;
     while current_y_position < full_image_height {
         let current_chunk_height =
@@ -491,26 +914,48 @@ pub async fn get_raw_ocr_data(
                 current_chunk_height,
             )
             .to_image();
+        #[cfg(feature = "text-prefilter")]
+        if !crate::prefilter::should_ocr(&chunk_image) {
+            raw_chunks.push(RawChunk {
+                lines: Vec::new(),
+                no_geometry_lines: Vec::new(),
+                width: full_image_width,
+                height: current_chunk_height,
+                global_y: current_y_position,
+                full_width: full_image_width,
+                full_height: full_image_height,
+            });
+            current_y_position += chunk_height_limit;
+            continue;
+        }
+        let chunk_image = downscale_for_lens(chunk_image);
         let mut image_buffer = Cursor::new(Vec::new());
         chunk_image
             .write_to(&mut image_buffer, ImageFormat::Png)
             .map_err(|err| anyhow!("Failed write_to: {err:?}"))?;
         let chunk_png_bytes = image_buffer.into_inner();
 
-        let lens_response = lens_client
-            .process_image_bytes(&chunk_png_bytes, Some("jp"))
-            .await
-            .map_err(|err| anyhow!("Failed process_image_bytes: {err:?}"))?;
+        pace_lens_call().await;
+        let lens_call = lens_client.process_image_bytes(&chunk_png_bytes, Some("jp"));
+        let Some(lens_response) = before_deadline(deadline, lens_call).await else {
+            tracing::warn!(
+                "OCR deadline (MANGATAN_OCR_TOTAL_TIMEOUT) hit after {} chunks; returning those",
+                raw_chunks.len()
+            );
+            return Ok((raw_chunks, true));
+        };
+        let lens_response = lens_response.map_err(LensFailure::new)?;
 
         let mut flat_ocr_lines = Vec::new();
+        let mut no_geometry_lines = Vec::new();
         for paragraph in lens_response.paragraphs {
             for line in paragraph.lines {
-                if let Some(geometry) = line.geometry {
-                    let clean_text = post_process_text(line.text);
-                    if clean_text.trim().is_empty() {
-                        continue;
-                    }
+                let clean_text = post_process_text(line.text);
+                if clean_text.trim().is_empty() {
+                    continue;
+                }
 
+                if let Some(geometry) = line.geometry {
                     let rotation = geometry.rotation_z as f64;
                     let cx = (geometry.center_x * full_image_width as f32) as f64;
                     let cy = (geometry.center_y * current_chunk_height as f32) as f64;
@@ -555,6 +1000,7 @@ pub async fn get_raw_ocr_data(
                         } else {
                             "horizontal".into()
                         }),
+                        orientation: None,
                         tight_bounding_box: BoundingBox {
                             x: min_x,
                             y: min_y,
@@ -562,13 +1008,17 @@ pub async fn get_raw_ocr_data(
                             height: aabb_h,
                             rotation: None,
                         },
+                        no_geometry: None,
                     });
+                } else {
+                    no_geometry_lines.push(clean_text);
                 }
             }
         }
 
         raw_chunks.push(RawChunk {
             lines: flat_ocr_lines,
+            no_geometry_lines,
             width: full_image_width,
             height: current_chunk_height,
             global_y: current_y_position,
@@ -579,16 +1029,217 @@ pub async fn get_raw_ocr_data(
         current_y_position += chunk_height_limit;
     }
 
-    Ok(raw_chunks)
+    Ok((raw_chunks, false))
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn fetch_and_process_internal(
     url: &str,
+    inline_image: Option<&[u8]>,
     user: Option<String>,
     pass: Option<String>,
+    fetch_headers: &HeaderMap,
     add_space_on_merge: Option<bool>,
-) -> anyhow::Result<Vec<OcrResult>> {
-    // 0. Force URL to Localhost
+    reading_direction: Option<ReadingDirection>,
+    image_cache: Option<&ImageCache>,
+    rate_limiter: &RateLimiter,
+    deadline: Option<Instant>,
+) -> anyhow::Result<PageResults> {
+    // 1. Fetch
+    let fetch = load_page_image(
+        url,
+        inline_image,
+        user.clone(),
+        pass.clone(),
+        fetch_headers,
+        image_cache,
+        rate_limiter,
+    );
+    let image_bytes = before_deadline(deadline, fetch)
+        .await
+        .ok_or_else(|| anyhow!("Timed out fetching the page (MANGATAN_OCR_TOTAL_TIMEOUT)"))??;
+
+    // 2. Decode & OCR (Wrapped) - now passes user/pass for proxy settings. The fetch headers
+    // belong to the source image only and are never sent to Lens.
+    let (raw_chunks, incomplete) =
+        get_raw_ocr_data_until(&image_bytes, user, pass, deadline).await?;
+
+    // 3. Merge & Normalize
+    let mut merge_config = MergeConfig::from_env();
+    merge_config.add_space_on_merge = add_space_on_merge;
+    merge_config.reading_direction = reading_direction;
+
+    let (mut results, merge_quality) = merge_raw_chunks_measured(raw_chunks, &merge_config);
+    let truncated_from = merge_config
+        .max_results
+        .and_then(|max| cap_results(&mut results, max));
+    if let Some(total) = truncated_from {
+        tracing::warn!(
+            "Kept the {} largest of {total} blocks for {url} (MANGATAN_OCR_MAX_RESULTS_PER_PAGE)",
+            results.len()
+        );
+    }
+    let looks_blank = placed_len(&results) == 0 && page_looks_blank(&image_bytes);
+    Ok(PageResults {
+        results,
+        truncated_from,
+        incomplete,
+        looks_blank,
+        merge_quality,
+    })
+}
+
+/// Whether the text prefilter would skip the whole page. Without the `text-prefilter` feature
+/// there's no telling, so no page looks blank.
+fn page_looks_blank(image_bytes: &[u8]) -> bool {
+    #[cfg(feature = "text-prefilter")]
+    {
+        decode_image(image_bytes)
+            .is_ok_and(|image| crate::prefilter::looks_blank(&image.to_rgba8()))
+    }
+    #[cfg(not(feature = "text-prefilter"))]
+    {
+        let _ = image_bytes;
+        false
+    }
+}
+
+/// Keeps the `max` blocks with the largest boxes, in their original order, so a page full of
+/// tiny sound effects can't bloat the cache and the overlay. Lines without geometry go first.
+/// Returns how many blocks there were when some were dropped.
+pub fn cap_results(results: &mut Vec<OcrResult>, max: usize) -> Option<usize> {
+    let total = results.len();
+    if total <= max {
+        return None;
+    }
+    let area = |r: &OcrResult| r.tight_bounding_box.width * r.tight_bounding_box.height;
+    let mut by_area: Vec<usize> = (0..total).collect();
+    by_area.sort_by(|&a, &b| {
+        area(&results[b])
+            .total_cmp(&area(&results[a]))
+            .then(a.cmp(&b))
+    });
+    let mut keep = vec![false; total];
+    for &index in &by_area[..max] {
+        keep[index] = true;
+    }
+    let mut index = 0;
+    results.retain(|_| {
+        index += 1;
+        keep[index - 1]
+    });
+    Some(total)
+}
+
+/// The page's image: the inline one, else from the image cache when a previous attempt already
+/// downloaded it, else fetched (and cached).
+async fn load_page_image(
+    url: &str,
+    inline_image: Option<&[u8]>,
+    user: Option<String>,
+    pass: Option<String>,
+    fetch_headers: &HeaderMap,
+    image_cache: Option<&ImageCache>,
+    rate_limiter: &RateLimiter,
+) -> anyhow::Result<Vec<u8>> {
+    if let Some(bytes) = inline_image {
+        return Ok(bytes.to_vec());
+    }
+    let cache_key = get_cache_key(url);
+    if let Some(bytes) = image_cache.and_then(|cache| cache.get(&cache_key)) {
+        return Ok(bytes);
+    }
+
+    rate_limiter.acquire(url).await;
+    let bytes = fetch_image_bytes(url, user, pass, fetch_headers).await?;
+    if let Some(cache) = image_cache {
+        cache.put(&cache_key, &bytes);
+    }
+    Ok(bytes)
+}
+
+/// What Lens returned for a page, per chunk and before `auto_merge`; the same data as the
+/// `.raw.json` regression fixtures. Fetched like [`fetch_and_process`] but tried only once, since
+/// it's for inspecting a single response.
+pub async fn fetch_raw_chunks(
+    url: &str,
+    user: Option<String>,
+    pass: Option<String>,
+    fetch_headers: &HeaderMap,
+    image_cache: Option<&ImageCache>,
+    rate_limiter: &RateLimiter,
+) -> anyhow::Result<Vec<RawChunk>> {
+    let inline_image = data_url_bytes(url).transpose()?;
+    let image_bytes = load_page_image(
+        url,
+        inline_image.as_deref(),
+        user.clone(),
+        pass.clone(),
+        fetch_headers,
+        image_cache,
+        rate_limiter,
+    )
+    .await?;
+    get_raw_ocr_data(&image_bytes, user, pass).await
+}
+
+/// Headers that describe the connection itself; the client sets these and a request can't
+/// override them.
+const RESERVED_FETCH_HEADERS: &[&str] =
+    &["host", "content-length", "transfer-encoding", "connection"];
+
+/// Validates the extra headers (`Referer`, `Cookie`, ...) a request wants sent with the page
+/// download, for sources with hotlink protection.
+pub fn image_fetch_headers(raw: &HashMap<String, String>) -> anyhow::Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in raw {
+        let name = HeaderName::from_bytes(name.trim().as_bytes())
+            .with_context(|| format!("Invalid header name: {name:?}"))?;
+        if RESERVED_FETCH_HEADERS.contains(&name.as_str()) {
+            return Err(anyhow!("Header can't be overridden: {name}"));
+        }
+        let value = HeaderValue::from_str(value.trim())
+            .with_context(|| format!("Invalid value for header {name}"))?;
+        headers.insert(name, value);
+    }
+    Ok(headers)
+}
+
+/// Adds the credentials Suwayomi wants for a page download, unless `headers` already has some:
+/// `token` as a bearer token, else the `Authorization` header the request came in with (the
+/// WebUI sends Suwayomi's along through the proxy). Basic auth from `user`/`pass` still wins.
+pub fn with_suwayomi_auth(
+    mut headers: HeaderMap,
+    token: Option<&str>,
+    incoming: &HeaderMap,
+) -> anyhow::Result<HeaderMap> {
+    if headers.contains_key(AUTHORIZATION) {
+        return Ok(headers);
+    }
+    let value = match token.map(str::trim).filter(|token| !token.is_empty()) {
+        Some(token) => {
+            let mut value = HeaderValue::from_str(&format!("Bearer {token}"))
+                .map_err(|_| anyhow!("Invalid token"))?;
+            value.set_sensitive(true);
+            value
+        }
+        None => match incoming.get(AUTHORIZATION) {
+            Some(value) => value.clone(),
+            None => return Ok(headers),
+        },
+    };
+    headers.insert(AUTHORIZATION, value);
+    Ok(headers)
+}
+
+/// Fetches a page image from the local Suwayomi server, sending `headers` along with it.
+pub async fn fetch_image_bytes(
+    url: &str,
+    user: Option<String>,
+    pass: Option<String>,
+    headers: &HeaderMap,
+) -> anyhow::Result<Vec<u8>> {
+    // Force URL to Localhost
     let target_url = match reqwest::Url::parse(url) {
         Ok(mut parsed) => {
             let _ = parsed.set_scheme("http");
@@ -599,9 +1250,8 @@ async fn fetch_and_process_internal(
         Err(_) => url.to_string(),
     };
 
-    // 1. Fetch
-    let client = reqwest::Client::new();
-    let mut request = client.get(&target_url);
+    let client = http_client();
+    let mut request = client.get(&target_url).headers(headers.clone());
     if let Some(username) = &user {
         request = request.basic_auth(username, pass.as_ref());
     }
@@ -609,19 +1259,30 @@ async fn fetch_and_process_internal(
         .send()
         .await?
         .error_for_status()
-        .map_err(|err| anyhow!("Failed error_for_status (URL: {target_url}): {err:?}"))?;
-    let image_bytes = response.bytes().await?.to_vec();
+        .with_context(|| format!("Failed error_for_status (URL: {target_url})"))?;
 
-    // 2. Decode & OCR (Wrapped) - now passes user/pass for proxy settings
-    let raw_chunks = get_raw_ocr_data(&image_bytes, user, pass).await?;
+    Ok(response.bytes().await?.to_vec())
+}
 
-    // 3. Merge & Normalize
+/// Merges the lines of each chunk and normalizes the boxes to the full image (0.0 - 1.0).
+pub fn merge_raw_chunks(raw_chunks: Vec<RawChunk>, merge_config: &MergeConfig) -> Vec<OcrResult> {
+    merge_raw_chunks_measured(raw_chunks, merge_config).0
+}
+
+/// [`merge_raw_chunks`], along with how the merge of the whole page turned out.
+pub fn merge_raw_chunks_measured(
+    raw_chunks: Vec<RawChunk>,
+    merge_config: &MergeConfig,
+) -> (Vec<OcrResult>, Option<MergeQuality>) {
     let mut final_results = Vec::new();
-    let mut merge_config = MergeConfig::default();
-    merge_config.add_space_on_merge = add_space_on_merge;
+    let mut chunk_quality = Vec::new();
 
     for chunk in raw_chunks {
-        let merged_lines = merge::auto_merge(chunk.lines, chunk.width, chunk.height, &merge_config);
+        let (merged_lines, quality) =
+            merge::auto_merge_measured(chunk.lines, chunk.width, chunk.height, merge_config);
+        if let Some(quality) = quality {
+            chunk_quality.push((quality, chunk.width as f64 * chunk.height as f64));
+        }
 
         for mut result in merged_lines {
             // Adjust Coordinates: Chunk Pixels -> Global Pixels -> Global Normalized
@@ -639,7 +1300,20 @@ async fn fetch_and_process_internal(
 
             final_results.push(result);
         }
+
+        // Kept so the text isn't lost; overlay clients filter these out by default
+        final_results.extend(chunk.no_geometry_lines.into_iter().map(|text| OcrResult {
+            text,
+            tight_bounding_box: BoundingBox::default(),
+            is_merged: Some(false),
+            forced_orientation: None,
+            orientation: None,
+            no_geometry: Some(true),
+        }));
     }
 
-    Ok(final_results)
+    let quality = merge_config
+        .enabled
+        .then(|| MergeQuality::combine(chunk_quality));
+    (final_results, quality)
 }
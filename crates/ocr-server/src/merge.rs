@@ -1,8 +1,13 @@
 use lazy_static::lazy_static;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use tracing::{debug, info};
 
-use crate::logic::{BoundingBox, OcrResult};
+use crate::{
+    logic::{self, BoundingBox, OcrResult, Orientation},
+    quality::{self, MergeQuality},
+};
 
 lazy_static! {
     static ref JAPANESE_REGEX: Regex = Regex::new(r"[\p{Hiragana}\p{Katakana}\p{Han}]").unwrap();
@@ -13,11 +18,98 @@ lazy_static! {
     static ref KATAKANA_REGEX: Regex = Regex::new(r"[\p{Katakana}]").unwrap();
 }
 
-#[derive(Clone)]
+/// What each line contributes to the orientation vote.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrientationWeight {
+    /// One vote per line.
+    #[default]
+    Count,
+    /// Votes weighted by box area, so a long line outweighs a stray character.
+    Area,
+}
+
+/// How a line's font size is estimated for the size comparisons that gate merging.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FontSizeMethod {
+    /// The box's cross-axis extent: its width for columns, its height for rows.
+    #[default]
+    Cross,
+    /// Side of the square each character would take up if it filled the box evenly. Outlines,
+    /// ruby and stretched lettering inflate the cross axis much more than this.
+    Area,
+}
+
+impl FontSizeMethod {
+    fn estimate(self, line: &OcrResult, is_vertical: bool) -> f64 {
+        let b = &line.tight_bounding_box;
+        match self {
+            Self::Cross if is_vertical => b.width,
+            Self::Cross => b.height,
+            Self::Area => {
+                let chars = line.text.chars().filter(|c| !c.is_whitespace()).count();
+                (b.width * b.height / chars.max(1) as f64).sqrt()
+            }
+        }
+    }
+}
+
+/// What happens to merged boxes that still overlap once merging is done, e.g. where columns of
+/// two bubbles interleave. Overlapping boxes make tap targets that steal each other's taps.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlapPolicy {
+    #[default]
+    Ignore,
+    /// Log each overlapping pair.
+    Log,
+    /// Cut the lower-priority box back to the edge of the other one, as long as that takes no
+    /// more than [`MAX_OVERLAP_SHRINK`] of it; pairs that would need more are logged instead.
+    Shrink,
+}
+
+/// Most of a box's area [`OverlapPolicy::Shrink`] may cut away.
+pub const MAX_OVERLAP_SHRINK: f64 = 0.3;
+
+/// Which way vertical columns are read, for content the detection gets wrong or can't tell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadingDirection {
+    /// Japanese manga and traditional Chinese.
+    Rtl,
+    Ltr,
+}
+
+/// Most blocks a page keeps unless `MANGATAN_OCR_MAX_RESULTS_PER_PAGE` says otherwise, several
+/// times what a dense page produces.
+pub const DEFAULT_MAX_RESULTS_PER_PAGE: usize = 500;
+
+#[derive(Clone, Debug, Serialize)]
 pub struct MergeConfig {
     pub enabled: bool,
     pub font_size_ratio: f64,
     pub add_space_on_merge: Option<bool>,
+    /// When set, touching lines are settled on one orientation before merging: the side
+    /// holding more than this share of the vote wins (`0.5` is a simple majority, `0.66`
+    /// requires two thirds). Without a clear winner each line keeps its own orientation.
+    pub orientation_threshold: Option<f64>,
+    pub orientation_weight: OrientationWeight,
+    pub font_size_method: FontSizeMethod,
+    /// Most blocks kept per page (see [`cap_results`](crate::logic::cap_results)); `None` keeps
+    /// them all.
+    pub max_results: Option<usize>,
+    /// Column order of every vertical group. `None` goes by the order Lens read the columns in,
+    /// reading right to left when that doesn't tell.
+    pub reading_direction: Option<ReadingDirection>,
+    /// Margin added on each side of every returned box, as a fraction of its width and height,
+    /// so overlays don't clip outlined text. Boxes stay inside the page.
+    pub box_padding: f64,
+    /// Size horizontal lines by the median of their neighbours' estimates rather than their own,
+    /// so ascenders and descenders don't make two lines of one bubble look like different fonts.
+    /// Lines far off the median keep their own size.
+    pub robust_horizontal_font: bool,
+    pub overlap_policy: OverlapPolicy,
 }
 
 impl Default for MergeConfig {
@@ -26,6 +118,73 @@ impl Default for MergeConfig {
             enabled: true,
             font_size_ratio: 3.0,
             add_space_on_merge: None,
+            orientation_threshold: None,
+            orientation_weight: OrientationWeight::Count,
+            font_size_method: FontSizeMethod::Cross,
+            max_results: Some(DEFAULT_MAX_RESULTS_PER_PAGE),
+            reading_direction: None,
+            box_padding: 0.0,
+            robust_horizontal_font: false,
+            overlap_policy: OverlapPolicy::Ignore,
+        }
+    }
+}
+
+impl MergeConfig {
+    /// Defaults, with the orientation vote taken from `MANGATAN_OCR_ORIENTATION_THRESHOLD`
+    /// and `MANGATAN_OCR_ORIENTATION_WEIGHT` (`count` or `area`), the font size estimate from
+    /// `MANGATAN_OCR_FONT_SIZE_METHOD` (`cross` or `area`), the per-page cap from
+    /// `MANGATAN_OCR_MAX_RESULTS_PER_PAGE` (`0` for none), the box padding from
+    /// `MANGATAN_OCR_BOX_PADDING` (up to `0.5`), the horizontal font smoothing from
+    /// `MANGATAN_OCR_ROBUST_HORIZONTAL_FONT`, and what happens to overlapping boxes from
+    /// `MANGATAN_OCR_OVERLAP_POLICY` (`ignore`, `log` or `shrink`).
+    pub fn from_env() -> Self {
+        let orientation_threshold = std::env::var("MANGATAN_OCR_ORIENTATION_THRESHOLD")
+            .ok()
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .filter(|t| (0.0..1.0).contains(t));
+        let orientation_weight = match std::env::var("MANGATAN_OCR_ORIENTATION_WEIGHT") {
+            Ok(v) if v.trim().eq_ignore_ascii_case("area") => OrientationWeight::Area,
+            _ => OrientationWeight::Count,
+        };
+        let font_size_method = match std::env::var("MANGATAN_OCR_FONT_SIZE_METHOD") {
+            Ok(v) if v.trim().eq_ignore_ascii_case("area") => FontSizeMethod::Area,
+            _ => FontSizeMethod::Cross,
+        };
+        let max_results = match std::env::var("MANGATAN_OCR_MAX_RESULTS_PER_PAGE")
+            .ok()
+            .and_then(|v| v.trim().parse::<usize>().ok())
+        {
+            Some(0) => None,
+            Some(max) => Some(max),
+            None => Some(DEFAULT_MAX_RESULTS_PER_PAGE),
+        };
+        let box_padding = std::env::var("MANGATAN_OCR_BOX_PADDING")
+            .ok()
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .filter(|p| (0.0..=0.5).contains(p))
+            .unwrap_or(0.0);
+        let robust_horizontal_font = std::env::var("MANGATAN_OCR_ROBUST_HORIZONTAL_FONT")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        let overlap_policy = match std::env::var("MANGATAN_OCR_OVERLAP_POLICY")
+            .map(|v| v.trim().to_ascii_lowercase())
+            .as_deref()
+        {
+            Ok("log") => OverlapPolicy::Log,
+            Ok("shrink") => OverlapPolicy::Shrink,
+            _ => OverlapPolicy::Ignore,
+        };
+
+        Self {
+            orientation_threshold,
+            orientation_weight,
+            font_size_method,
+            max_results,
+            box_padding,
+            robust_horizontal_font,
+            overlap_policy,
+            ..Self::default()
         }
     }
 }
@@ -94,8 +253,107 @@ fn calculate_aabb(points: &[Point]) -> (f64, f64, f64, f64, f64) {
     (center_x, center_y, width, height, 0.0)
 }
 
+// --- Post-Processing ---
+
+/// Applies `policy` to every pair of `results` whose boxes overlap. The box of a merged group
+/// outranks a single line's, and otherwise the larger box does.
+fn resolve_overlaps(results: &mut [OcrResult], policy: OverlapPolicy) {
+    if policy == OverlapPolicy::Ignore {
+        return;
+    }
+    let priority = |r: &OcrResult| {
+        let b = &r.tight_bounding_box;
+        (r.is_merged == Some(true), b.width * b.height)
+    };
+    for i in 0..results.len() {
+        for j in (i + 1)..results.len() {
+            if !overlaps(
+                &results[i].tight_bounding_box,
+                &results[j].tight_bounding_box,
+            ) {
+                continue;
+            }
+            let (keep, shrink) = match priority(&results[j]) > priority(&results[i]) {
+                true => (j, i),
+                false => (i, j),
+            };
+            let other = results[keep].tight_bounding_box.clone();
+            if policy == OverlapPolicy::Shrink
+                && shrink_away(&mut results[shrink].tight_bounding_box, &other)
+            {
+                debug!(
+                    "Shrank {:?} out of {:?}",
+                    results[shrink].text, results[keep].text
+                );
+                continue;
+            }
+            info!(
+                "Merged boxes overlap: {:?} and {:?}",
+                results[i].text, results[j].text
+            );
+        }
+    }
+}
+
+fn overlaps(a: &BoundingBox, b: &BoundingBox) -> bool {
+    (a.x + a.width).min(b.x + b.width) > a.x.max(b.x)
+        && (a.y + a.height).min(b.y + b.height) > a.y.max(b.y)
+}
+
+/// Cuts `bbox` back to whichever edge of `other` keeps the most of it. Returns whether it did;
+/// rotated boxes, and cuts over [`MAX_OVERLAP_SHRINK`], are left alone.
+fn shrink_away(bbox: &mut BoundingBox, other: &BoundingBox) -> bool {
+    if bbox.rotation.is_some_and(|r| r != 0.0) || other.rotation.is_some_and(|r| r != 0.0) {
+        return false;
+    }
+    let (left, top) = (bbox.x, bbox.y);
+    let (right, bottom) = (bbox.x + bbox.width, bbox.y + bbox.height);
+    let (other_right, other_bottom) = (other.x + other.width, other.y + other.height);
+    // (left, top, right, bottom) with one side moved to the other box's edge
+    let candidates = [
+        (other_right, top, right, bottom),
+        (left, top, other.x, bottom),
+        (left, other_bottom, right, bottom),
+        (left, top, right, other.y),
+    ];
+    let kept = |&(l, t, r, b): &(f64, f64, f64, f64)| (r - l) * (b - t);
+    let best = candidates
+        .into_iter()
+        .filter(|&(l, t, r, b)| l >= left && t >= top && r <= right && b <= bottom)
+        .filter(|candidate| kept(candidate) > 0.0)
+        .max_by(|x, y| kept(x).total_cmp(&kept(y)));
+    let Some((l, t, r, b)) = best else {
+        return false;
+    };
+    if kept(&(l, t, r, b)) < bbox.width * bbox.height * (1.0 - MAX_OVERLAP_SHRINK) {
+        return false;
+    }
+    bbox.x = l;
+    bbox.y = t;
+    bbox.width = r - l;
+    bbox.height = b - t;
+    true
+}
+
 // --- Pre-Processing Filters ---
 
+/// Grows `bbox` by `padding` of its size on each side, clamped to the `page_w` x `page_h` chunk
+/// so the box still lies within [0, 1] once normalized.
+fn pad_box(bbox: &mut BoundingBox, padding: f64, page_w: u32, page_h: u32) {
+    if padding <= 0.0 {
+        return;
+    }
+    let (page_w, page_h) = (page_w as f64, page_h as f64);
+    let left = (bbox.x - bbox.width * padding).max(0.0);
+    let top = (bbox.y - bbox.height * padding).max(0.0);
+    let right = (bbox.x + bbox.width * (1.0 + padding)).min(page_w);
+    let bottom = (bbox.y + bbox.height * (1.0 + padding)).min(page_h);
+    bbox.x = left;
+    bbox.y = top;
+    bbox.width = (right - left).max(0.0);
+    bbox.height = (bottom - top).max(0.0);
+}
+
 fn filter_bad_boxes(lines: Vec<OcrResult>, page_w: u32, page_h: u32) -> Vec<OcrResult> {
     let mut keep = vec![true; lines.len()];
     let n = lines.len();
@@ -286,6 +544,52 @@ impl UnionFind {
     }
 }
 
+/// How far off its group's median a horizontal line's size may be and still count as the same
+/// font; further off, it's likely a heading or a caption and keeps its own size.
+const ROBUST_FONT_TOLERANCE: f64 = 1.4;
+
+/// Replaces the font size of each horizontal line with the median of its provisional group:
+/// horizontal lines stacked at most one line height apart with overlapping extents. A line
+/// with descenders can measure a third taller than one without, enough to fail the similarity
+/// tiers of [`are_lines_mergeable`] between two lines of the same bubble.
+fn smooth_horizontal_fonts(processed: &mut [ProcessedLine]) {
+    let horizontal: Vec<usize> = (0..processed.len())
+        .filter(|&i| !processed[i].is_vertical)
+        .collect();
+    let mut uf = UnionFind::new(processed.len());
+    for (n, &i) in horizontal.iter().enumerate() {
+        for &j in &horizontal[n + 1..] {
+            let (a, b) = (&processed[i], &processed[j]);
+            let line_height = (a.max_cross - a.min_cross).max(b.max_cross - b.min_cross);
+            let gap_cross = (b.min_cross - a.max_cross).max(a.min_cross - b.max_cross);
+            let overlap_main = a.max_main.min(b.max_main) - a.min_main.max(b.min_main);
+            if gap_cross <= line_height && overlap_main > 0.0 {
+                uf.union(i, j);
+            }
+        }
+    }
+
+    let mut groups: std::collections::HashMap<usize, Vec<usize>> = std::collections::HashMap::new();
+    for &i in &horizontal {
+        groups.entry(uf.find(i)).or_default().push(i);
+    }
+    for members in groups.values().filter(|members| members.len() > 1) {
+        let mut sizes: Vec<f64> = members.iter().map(|&i| processed[i].font_size).collect();
+        sizes.sort_by(|a, b| a.total_cmp(b));
+        let mid = sizes.len() / 2;
+        let median = match sizes.len() % 2 {
+            0 => (sizes[mid - 1] + sizes[mid]) / 2.0,
+            _ => sizes[mid],
+        };
+        for &i in members {
+            let size = processed[i].font_size;
+            if size.max(median) / size.min(median) <= ROBUST_FONT_TOLERANCE {
+                processed[i].font_size = median;
+            }
+        }
+    }
+}
+
 fn are_lines_mergeable(a: &ProcessedLine, b: &ProcessedLine, config: &MergeConfig) -> bool {
     if a.is_vertical != b.is_vertical {
         return false;
@@ -376,32 +680,149 @@ fn are_lines_mergeable(a: &ProcessedLine, b: &ProcessedLine, config: &MergeConfi
     true
 }
 
+fn line_is_vertical(l: &OcrResult) -> bool {
+    let b = &l.tight_bounding_box;
+    let is_japanese = JAPANESE_REGEX.is_match(&l.text);
+    let char_count = l.text.chars().count();
+
+    if is_japanese {
+        if char_count == 1 {
+            b.height > b.width * 0.8
+        } else {
+            let lens_is_vertical = l.forced_orientation.as_deref() == Some("vertical");
+            let is_physically_vertical = b.height > b.width;
+            lens_is_vertical || is_physically_vertical
+        }
+    } else {
+        let lens_is_vertical = l.forced_orientation.as_deref() == Some("vertical");
+        lens_is_vertical && b.height > b.width * 1.1
+    }
+}
+
+/// Which way the columns of a vertical group run. Consecutive lines (in Lens' order) that sit
+/// side by side, i.e. overlap by at least half their height, vote by whether the next column
+/// lies to the left or the right. Ties and groups without such pairs read right to left.
+fn column_direction(lines: &[&OcrResult]) -> Orientation {
+    let mut votes = 0i32;
+    for pair in lines.windows(2) {
+        let (a, b) = (&pair[0].tight_bounding_box, &pair[1].tight_bounding_box);
+        let overlap_y = (a.y + a.height).min(b.y + b.height) - a.y.max(b.y);
+        if overlap_y < a.height.min(b.height) * 0.5 {
+            continue;
+        }
+        let (center_a, center_b) = (a.x + a.width / 2.0, b.x + b.width / 2.0);
+        match center_b.total_cmp(&center_a) {
+            Ordering::Less => votes += 1,
+            Ordering::Greater => votes -= 1,
+            Ordering::Equal => {}
+        }
+    }
+    match votes < 0 {
+        true => Orientation::VerticalLtr,
+        false => Orientation::VerticalRtl,
+    }
+}
+
+/// Lines whose boxes overlap or nearly touch, regardless of orientation.
+fn are_lines_touching(a: &BoundingBox, b: &BoundingBox) -> bool {
+    let min_side = a.width.min(a.height).min(b.width).min(b.height);
+    let gap_x = 0.0f64.max(b.x - (a.x + a.width)).max(a.x - (b.x + b.width));
+    let gap_y = 0.0f64
+        .max(b.y - (a.y + a.height))
+        .max(a.y - (b.y + b.height));
+    gap_x <= min_side * 0.2 && gap_y <= min_side * 0.2
+}
+
+/// Puts each cluster of touching lines to an orientation vote and applies a clear winner to
+/// every line in it. Mixed clusters otherwise never merge, since lines only merge with lines
+/// of the same orientation.
+fn settle_orientations(
+    lines: &[OcrResult],
+    orientations: &mut [bool],
+    threshold: f64,
+    weight: OrientationWeight,
+) {
+    let mut uf = UnionFind::new(lines.len());
+    for i in 0..lines.len() {
+        for j in (i + 1)..lines.len() {
+            if are_lines_touching(&lines[i].tight_bounding_box, &lines[j].tight_bounding_box) {
+                uf.union(i, j);
+            }
+        }
+    }
+
+    let mut clusters: std::collections::HashMap<usize, Vec<usize>> =
+        std::collections::HashMap::new();
+    for i in 0..lines.len() {
+        clusters.entry(uf.find(i)).or_default().push(i);
+    }
+
+    for indices in clusters.values().filter(|indices| indices.len() > 1) {
+        let (mut vertical, mut total) = (0.0, 0.0);
+        for &i in indices {
+            let b = &lines[i].tight_bounding_box;
+            let vote = match weight {
+                OrientationWeight::Count => 1.0,
+                OrientationWeight::Area => b.width * b.height,
+            };
+            total += vote;
+            if orientations[i] {
+                vertical += vote;
+            }
+        }
+        if total <= 0.0 {
+            continue;
+        }
+
+        let winner = if vertical / total > threshold {
+            true
+        } else if (total - vertical) / total > threshold {
+            false
+        } else {
+            continue;
+        };
+        for &i in indices {
+            orientations[i] = winner;
+        }
+    }
+}
+
 pub fn auto_merge(lines: Vec<OcrResult>, w: u32, h: u32, config: &MergeConfig) -> Vec<OcrResult> {
-    if !config.enabled || lines.is_empty() {
-        return lines;
+    auto_merge_measured(lines, w, h, config).0
+}
+
+/// [`auto_merge`], along with how the merge turned out. No quality when merging is disabled.
+pub fn auto_merge_measured(
+    lines: Vec<OcrResult>,
+    w: u32,
+    h: u32,
+    config: &MergeConfig,
+) -> (Vec<OcrResult>, Option<MergeQuality>) {
+    if !config.enabled {
+        return (lines, None);
+    }
+    if lines.is_empty() {
+        return (lines, Some(MergeQuality::default()));
     }
 
     let clean_lines = filter_bad_boxes(lines, w, h);
 
-    let processed: Vec<ProcessedLine> = clean_lines
+    let mut orientations: Vec<bool> = clean_lines.iter().map(line_is_vertical).collect();
+    let own_orientations = orientations.clone();
+    if let Some(threshold) = config.orientation_threshold {
+        settle_orientations(
+            &clean_lines,
+            &mut orientations,
+            threshold,
+            config.orientation_weight,
+        );
+    }
+
+    let mut processed: Vec<ProcessedLine> = clean_lines
         .iter()
-        .map(|l| {
+        .zip(&orientations)
+        .map(|(l, &is_v)| {
             let b = &l.tight_bounding_box;
-            let is_japanese = JAPANESE_REGEX.is_match(&l.text);
-            let char_count = l.text.chars().count();
-
-            let is_v = if is_japanese {
-                if char_count == 1 {
-                    b.height > b.width * 0.8
-                } else {
-                    let lens_is_vertical = l.forced_orientation.as_deref() == Some("vertical");
-                    let is_physically_vertical = b.height > b.width;
-                    lens_is_vertical || is_physically_vertical
-                }
-            } else {
-                let lens_is_vertical = l.forced_orientation.as_deref() == Some("vertical");
-                lens_is_vertical && b.height > b.width * 1.1
-            };
 
             let (min_main, max_main, min_cross, max_cross) = if is_v {
                 (b.y, b.y + b.height, b.x, b.x + b.width)
@@ -411,7 +832,7 @@ pub fn auto_merge(lines: Vec<OcrResult>, w: u32, h: u32, config: &MergeConfig) -
 
             ProcessedLine {
                 is_vertical: is_v,
-                font_size: if is_v { b.width } else { b.height },
+                font_size: config.font_size_method.estimate(l, is_v),
                 length_main: if is_v { b.height } else { b.width },
                 min_main,
                 max_main,
@@ -420,6 +841,9 @@ pub fn auto_merge(lines: Vec<OcrResult>, w: u32, h: u32, config: &MergeConfig) -
             }
         })
         .collect();
+    if config.robust_horizontal_font {
+        smooth_horizontal_fonts(&mut processed);
+    }
 
     let mut uf = UnionFind::new(processed.len());
     for i in 0..processed.len() {
@@ -430,36 +854,58 @@ pub fn auto_merge(lines: Vec<OcrResult>, w: u32, h: u32, config: &MergeConfig) -
         }
     }
 
-    let mut groups: std::collections::HashMap<usize, Vec<usize>> = std::collections::HashMap::new();
+    // Groups in order of their first line, so the same input always comes out the same
+    let mut group_of_root: std::collections::HashMap<usize, usize> =
+        std::collections::HashMap::new();
+    let mut groups: Vec<Vec<usize>> = Vec::new();
     for i in 0..processed.len() {
-        groups.entry(uf.find(i)).or_default().push(i);
+        let group = *group_of_root.entry(uf.find(i)).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(i);
     }
 
     let mut results = Vec::new();
-    for (_, indices) in groups {
-        if indices.is_empty() {
-            continue;
-        }
+    let mut measured = Vec::with_capacity(groups.len());
+    for indices in groups {
+        // Still in Lens' order, which `column_direction` relies on
+        let mut group_lines: Vec<&OcrResult> = indices.iter().map(|&i| &clean_lines[i]).collect();
+        let is_vertical = processed[indices[0]].is_vertical;
+        let orientation = match (is_vertical, config.reading_direction) {
+            (false, _) => Orientation::Horizontal,
+            (true, Some(ReadingDirection::Rtl)) => Orientation::VerticalRtl,
+            (true, Some(ReadingDirection::Ltr)) => Orientation::VerticalLtr,
+            (true, None) => column_direction(&group_lines),
+        };
+
+        let mut group = quality::Group {
+            bbox: BoundingBox::default(),
+            font_sizes: indices.iter().map(|&i| processed[i].font_size).collect(),
+            vertical: indices.iter().map(|&i| own_orientations[i]).collect(),
+        };
 
         if indices.len() == 1 {
             let mut line = clean_lines[indices[0]].clone();
-            let is_v = processed[indices[0]].is_vertical;
-            line.forced_orientation = Some(if is_v {
-                "vertical".into()
-            } else {
-                "horizontal".into()
-            });
+            group.bbox = line.tight_bounding_box.clone();
+            measured.push(group);
+            pad_box(&mut line.tight_bounding_box, config.box_padding, w, h);
+            line.forced_orientation = Some(orientation.legacy().into());
+            line.orientation = Some(orientation);
             results.push(line);
             continue;
         }
 
-        let mut group_lines: Vec<&OcrResult> = indices.iter().map(|&i| &clean_lines[i]).collect();
-        let is_vertical = processed[indices[0]].is_vertical;
-
         group_lines.sort_by(|a, b| {
             let ba = &a.tight_bounding_box;
             let bb = &b.tight_bounding_box;
-            if is_vertical {
+            if orientation == Orientation::VerticalLtr {
+                if (ba.x - bb.x).abs() > 5.0 {
+                    ba.x.partial_cmp(&bb.x).unwrap_or(Ordering::Equal)
+                } else {
+                    ba.y.partial_cmp(&bb.y).unwrap_or(Ordering::Equal)
+                }
+            } else if is_vertical {
                 let ra = ba.x + ba.width;
                 let rb = bb.x + bb.width;
                 if (ra - rb).abs() > 5.0 {
@@ -520,24 +966,35 @@ pub fn auto_merge(lines: Vec<OcrResult>, w: u32, h: u32, config: &MergeConfig) -
         for l in &group_lines {
             points.extend(get_bounding_box_corners(&l.tight_bounding_box));
         }
-        let (cx, cy, w, h, _rot) = calculate_aabb(&points);
+        let (cx, cy, box_w, box_h, _rot) = calculate_aabb(&points);
+        let mut bbox = BoundingBox {
+            x: cx - box_w / 2.0,
+            y: cy - box_h / 2.0,
+            width: box_w,
+            height: box_h,
+            rotation: None,
+        };
+        group.bbox = bbox.clone();
+        measured.push(group);
+        pad_box(&mut bbox, config.box_padding, w, h);
 
         results.push(OcrResult {
             text: text_content,
-            tight_bounding_box: BoundingBox {
-                x: cx - w / 2.0,
-                y: cy - h / 2.0,
-                width: w,
-                height: h,
-                rotation: None,
-            },
+            tight_bounding_box: bbox,
             is_merged: Some(true),
-            forced_orientation: Some(if is_vertical {
-                "vertical".into()
-            } else {
-                "horizontal".into()
-            }),
+            forced_orientation: Some(orientation.legacy().into()),
+            orientation: Some(orientation),
+            no_geometry: None,
         });
     }
-    results
+
+    resolve_overlaps(&mut results, config.overlap_policy);
+
+    // Reading position rather than the order the groups were found in
+    let results = logic::reading_order(&results)
+        .into_iter()
+        .cloned()
+        .collect();
+    let quality = MergeQuality::measure(&measured, w as f64, h as f64);
+    (results, Some(quality))
 }
@@ -0,0 +1,196 @@
+//! Cheap local check for whether a chunk has any text at all, so blank gutters and text-free art
+//! don't cost a Lens call. Built with the `text-prefilter` feature.
+//!
+//! The check binarizes the chunk and counts connected components shaped like glyphs: not too small
+//! or large, and made of strokes that are thin next to their extent. It is tuned to never skip
+//! text; a chunk is only skipped when it has (next to) nothing glyph-like.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use image::{GrayImage, RgbaImage, imageops::FilterType};
+use lazy_static::lazy_static;
+
+/// Glyph-like components a chunk needs to be sent to Lens.
+pub const DEFAULT_MIN_GLYPHS: usize = 1;
+// Chunks are analyzed at this size at most, plenty for furigana to survive
+const WORKING_DIMENSION: u32 = 1600;
+// Luma gap between ink and paper below which the chunk counts as flat (gradients, JPEG noise)
+const MIN_CONTRAST: f64 = 32.0;
+const MIN_GLYPH_SIDE: u32 = 3;
+// Stroke width over the longer bounding box side; solid blobs are around 0.5
+const MAX_STROKE_RATIO: f64 = 0.4;
+
+lazy_static! {
+    static ref MIN_GLYPHS: Option<usize> = min_glyphs_from_env();
+}
+
+static SKIPPED_CHUNKS: AtomicUsize = AtomicUsize::new(0);
+
+/// Reads `MANGATAN_OCR_PREFILTER_MIN_GLYPHS`; 0 sends every chunk to Lens.
+fn min_glyphs_from_env() -> Option<usize> {
+    match std::env::var("MANGATAN_OCR_PREFILTER_MIN_GLYPHS") {
+        Ok(v) => v.trim().parse::<usize>().ok().filter(|min| *min > 0),
+        Err(_) => Some(DEFAULT_MIN_GLYPHS),
+    }
+}
+
+/// Chunks skipped since startup.
+pub fn skipped_chunks() -> usize {
+    SKIPPED_CHUNKS.load(Ordering::Relaxed)
+}
+
+/// Whether the chunk should go to Lens under the configured threshold. Counts the ones that don't.
+pub fn should_ocr(chunk: &RgbaImage) -> bool {
+    let Some(min_glyphs) = *MIN_GLYPHS else {
+        return true;
+    };
+    let glyphs = count_glyphs(chunk, min_glyphs);
+    if glyphs >= min_glyphs {
+        return true;
+    }
+    SKIPPED_CHUNKS.fetch_add(1, Ordering::Relaxed);
+    tracing::debug!(
+        "Skipping OCR of {}x{} chunk: {glyphs} glyph-like components",
+        chunk.width(),
+        chunk.height()
+    );
+    false
+}
+
+/// Whether a whole page has fewer glyph-like components than the threshold, i.e. an empty OCR
+/// result is expected. Unlike [`should_ocr`], doesn't count as a skipped chunk.
+pub fn looks_blank(page: &RgbaImage) -> bool {
+    let min_glyphs = MIN_GLYPHS.unwrap_or(DEFAULT_MIN_GLYPHS);
+    count_glyphs(page, min_glyphs) < min_glyphs
+}
+
+/// Glyph-like components in `chunk`, counting dark-on-light and light-on-dark alike. Stops
+/// counting at `enough`.
+pub fn count_glyphs(chunk: &RgbaImage, enough: usize) -> usize {
+    let luma = working_luma(chunk);
+    let Some(threshold) = ink_threshold(&luma) else {
+        return 0;
+    };
+    let dark = count_glyph_components(&luma, enough, |value| value <= threshold);
+    if dark >= enough {
+        return dark;
+    }
+    dark.max(count_glyph_components(&luma, enough, |value| {
+        value > threshold
+    }))
+}
+
+fn working_luma(chunk: &RgbaImage) -> GrayImage {
+    let luma = image::DynamicImage::ImageRgba8(chunk.clone()).into_luma8();
+    let (width, height) = luma.dimensions();
+    let longest = width.max(height);
+    if longest <= WORKING_DIMENSION {
+        return luma;
+    }
+    let scale = f64::from(WORKING_DIMENSION) / f64::from(longest);
+    let scaled = |side: u32| ((f64::from(side) * scale).round() as u32).max(1);
+    image::imageops::resize(&luma, scaled(width), scaled(height), FilterType::Triangle)
+}
+
+/// Otsu's threshold, or `None` when the two classes are too close to hold any ink.
+fn ink_threshold(luma: &GrayImage) -> Option<u8> {
+    let mut histogram = [0u64; 256];
+    for pixel in luma.pixels() {
+        histogram[pixel.0[0] as usize] += 1;
+    }
+    let total = luma.pixels().len() as f64;
+    let sum: f64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(value, count)| value as f64 * *count as f64)
+        .sum();
+
+    let mut best = None;
+    let mut best_variance = 0.0;
+    let (mut below_count, mut below_sum) = (0.0, 0.0);
+    for (value, count) in histogram.iter().enumerate().take(255) {
+        below_count += *count as f64;
+        below_sum += value as f64 * *count as f64;
+        let above_count = total - below_count;
+        if below_count == 0.0 || above_count == 0.0 {
+            continue;
+        }
+        let below_mean = below_sum / below_count;
+        let above_mean = (sum - below_sum) / above_count;
+        let variance = below_count * above_count * (above_mean - below_mean).powi(2);
+        if variance > best_variance {
+            best_variance = variance;
+            best = Some((value as u8, above_mean - below_mean));
+        }
+    }
+    best.filter(|(_, contrast)| *contrast >= MIN_CONTRAST)
+        .map(|(threshold, _)| threshold)
+}
+
+/// Labels the 8-connected components of the pixels `is_ink` picks and counts the glyph-like ones.
+fn count_glyph_components(luma: &GrayImage, enough: usize, is_ink: impl Fn(u8) -> bool) -> usize {
+    let (width, height) = luma.dimensions();
+    let ink: Vec<bool> = luma.pixels().map(|pixel| is_ink(pixel.0[0])).collect();
+    let at = |x: u32, y: u32| (y * width + x) as usize;
+    // Glyphs are small next to the chunk; bigger components are frames, bubbles or art
+    let max_side = (width.min(height) / 2).max(MIN_GLYPH_SIDE);
+
+    let mut seen = vec![false; ink.len()];
+    let mut stack = Vec::new();
+    let mut glyphs = 0;
+    for start in 0..ink.len() {
+        if !ink[start] || seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        stack.push(start);
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+        let (mut area, mut edge) = (0usize, 0usize);
+        while let Some(index) = stack.pop() {
+            let (x, y) = (index as u32 % width, index as u32 / width);
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+            area += 1;
+
+            let mut on_edge = false;
+            for dy in -1i64..=1 {
+                for dx in -1i64..=1 {
+                    if dx == 0 && dy == 0 {
+                        continue;
+                    }
+                    let (nx, ny) = (i64::from(x) + dx, i64::from(y) + dy);
+                    if nx < 0 || ny < 0 || nx >= i64::from(width) || ny >= i64::from(height) {
+                        on_edge = true;
+                        continue;
+                    }
+                    let neighbor = at(nx as u32, ny as u32);
+                    if !ink[neighbor] {
+                        on_edge = true;
+                    } else if !seen[neighbor] {
+                        seen[neighbor] = true;
+                        stack.push(neighbor);
+                    }
+                }
+            }
+            if on_edge {
+                edge += 1;
+            }
+        }
+
+        let longest = (max_x - min_x + 1).max(max_y - min_y + 1);
+        if !(MIN_GLYPH_SIDE..=max_side).contains(&longest) {
+            continue;
+        }
+        // Edge pixels run along both sides of a stroke, so this is about its width
+        let stroke_width = 2.0 * area as f64 / edge.max(1) as f64;
+        if stroke_width <= MAX_STROKE_RATIO * f64::from(longest) {
+            glyphs += 1;
+            if glyphs >= enough {
+                break;
+            }
+        }
+    }
+    glyphs
+}
@@ -0,0 +1,209 @@
+//! Signals that a page's merge probably went wrong, so suspect pages can be found without
+//! reading every chapter. Measured during [`auto_merge`](crate::merge::auto_merge) and kept with
+//! the cache entry.
+
+use serde::{Deserialize, Serialize};
+
+use crate::logic::BoundingBox;
+
+/// Pages with fewer groups than this aren't judged by their share of single-line groups; a
+/// page with two captions is fine.
+pub const MIN_GROUPS_FOR_SHARE: usize = 4;
+
+/// How a page's merge turned out.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct MergeQuality {
+    pub single_line_groups: usize,
+    pub multi_line_groups: usize,
+    /// Largest coefficient of variation (standard deviation over mean) of the font sizes within
+    /// one group. Lines of one bubble share a font, so a high value means unrelated text was
+    /// merged.
+    pub font_size_variation: f64,
+    /// Share of the page covered by two or more group boxes.
+    pub overlap_fraction: f64,
+    /// Groups whose lines didn't agree on an orientation before the vote settled it.
+    pub mixed_orientation_groups: usize,
+}
+
+/// One merged group, as the metrics see it.
+#[derive(Clone, Debug)]
+pub struct Group {
+    /// The group's box before padding, in the same units as the page size.
+    pub bbox: BoundingBox,
+    pub font_sizes: Vec<f64>,
+    /// Whether each line looked vertical on its own.
+    pub vertical: Vec<bool>,
+}
+
+impl MergeQuality {
+    /// Measures the groups of a `width` by `height` page (or chunk of one).
+    pub fn measure(groups: &[Group], width: f64, height: f64) -> Self {
+        let single_line_groups = groups.iter().filter(|g| g.font_sizes.len() <= 1).count();
+        let font_size_variation = groups
+            .iter()
+            .map(|g| variation(&g.font_sizes))
+            .fold(0.0, f64::max);
+        let mixed_orientation_groups = groups
+            .iter()
+            .filter(|g| g.vertical.iter().any(|&v| v != g.vertical[0]))
+            .count();
+        let boxes: Vec<&BoundingBox> = groups.iter().map(|g| &g.bbox).collect();
+        let page_area = width * height;
+        let overlap_fraction = match page_area > 0.0 {
+            true => (overlap_area(&boxes) / page_area).min(1.0),
+            false => 0.0,
+        };
+
+        Self {
+            single_line_groups,
+            multi_line_groups: groups.len() - single_line_groups,
+            font_size_variation,
+            overlap_fraction,
+            mixed_orientation_groups,
+        }
+    }
+
+    /// One page's quality from its chunks', each with the chunk's area.
+    pub fn combine(chunks: impl IntoIterator<Item = (Self, f64)>) -> Self {
+        let mut page = Self::default();
+        let mut total_area = 0.0;
+        let mut covered = 0.0;
+        for (chunk, area) in chunks {
+            page.single_line_groups += chunk.single_line_groups;
+            page.multi_line_groups += chunk.multi_line_groups;
+            page.font_size_variation = page.font_size_variation.max(chunk.font_size_variation);
+            page.mixed_orientation_groups += chunk.mixed_orientation_groups;
+            covered += chunk.overlap_fraction * area;
+            total_area += area;
+        }
+        if total_area > 0.0 {
+            page.overlap_fraction = covered / total_area;
+        }
+        page
+    }
+
+    pub fn groups(&self) -> usize {
+        self.single_line_groups + self.multi_line_groups
+    }
+
+    /// Share of the groups that are a single line; 0 for pages with too few groups to tell.
+    pub fn single_line_share(&self) -> f64 {
+        match self.groups() {
+            groups if groups < MIN_GROUPS_FOR_SHARE => 0.0,
+            groups => self.single_line_groups as f64 / groups as f64,
+        }
+    }
+}
+
+/// Where a page starts counting as a probable bad merge.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct QualityThresholds {
+    /// Most lines of the page went unmerged.
+    pub single_line_share: f64,
+    pub font_size_variation: f64,
+    pub overlap_fraction: f64,
+    pub mixed_orientation_groups: usize,
+}
+
+impl Default for QualityThresholds {
+    fn default() -> Self {
+        Self {
+            single_line_share: 0.8,
+            font_size_variation: 0.35,
+            overlap_fraction: 0.02,
+            mixed_orientation_groups: 0,
+        }
+    }
+}
+
+impl QualityThresholds {
+    /// The metrics `quality` exceeds, each with how far past its threshold it is (the ratio of
+    /// value to threshold; for orientations, the count over the allowed count plus one).
+    pub fn exceeded(&self, quality: &MergeQuality) -> Vec<(&'static str, f64)> {
+        let ratios = [
+            (
+                "single_line_share",
+                quality.single_line_share(),
+                self.single_line_share,
+            ),
+            (
+                "font_size_variation",
+                quality.font_size_variation,
+                self.font_size_variation,
+            ),
+            (
+                "overlap_fraction",
+                quality.overlap_fraction,
+                self.overlap_fraction,
+            ),
+        ];
+        let mut exceeded: Vec<(&'static str, f64)> = ratios
+            .into_iter()
+            .filter(|(_, value, limit)| value > limit)
+            .map(|(name, value, limit)| (name, value / limit.max(f64::EPSILON)))
+            .collect();
+        if quality.mixed_orientation_groups > self.mixed_orientation_groups {
+            exceeded.push((
+                "mixed_orientation_groups",
+                quality.mixed_orientation_groups as f64
+                    / (self.mixed_orientation_groups + 1) as f64,
+            ));
+        }
+        exceeded
+    }
+}
+
+fn variation(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    if mean <= 0.0 {
+        return 0.0;
+    }
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    variance.sqrt() / mean
+}
+
+/// Area covered by two or more of `boxes`: the union of their pairwise intersections.
+fn overlap_area(boxes: &[&BoundingBox]) -> f64 {
+    let mut intersections = Vec::new();
+    for (i, a) in boxes.iter().enumerate() {
+        for b in &boxes[i + 1..] {
+            let x1 = a.x.max(b.x);
+            let y1 = a.y.max(b.y);
+            let x2 = (a.x + a.width).min(b.x + b.width);
+            let y2 = (a.y + a.height).min(b.y + b.height);
+            if x2 > x1 && y2 > y1 {
+                intersections.push((x1, y1, x2, y2));
+            }
+        }
+    }
+    if intersections.is_empty() {
+        return 0.0;
+    }
+
+    // Split the plane along every edge and add up the cells some intersection covers
+    let edges = |pick: fn(&(f64, f64, f64, f64)) -> [f64; 2]| {
+        let mut edges: Vec<f64> = intersections.iter().flat_map(pick).collect();
+        edges.sort_by(f64::total_cmp);
+        edges.dedup();
+        edges
+    };
+    let xs = edges(|r| [r.0, r.2]);
+    let ys = edges(|r| [r.1, r.3]);
+    let mut area = 0.0;
+    for x in xs.windows(2) {
+        let mid_x = (x[0] + x[1]) / 2.0;
+        for y in ys.windows(2) {
+            let mid_y = (y[0] + y[1]) / 2.0;
+            if intersections
+                .iter()
+                .any(|r| r.0 < mid_x && mid_x < r.2 && r.1 < mid_y && mid_y < r.3)
+            {
+                area += (x[1] - x[0]) * (y[1] - y[0]);
+            }
+        }
+    }
+    area
+}
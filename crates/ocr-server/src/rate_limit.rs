@@ -0,0 +1,161 @@
+use std::{collections::HashMap, net::IpAddr, sync::Mutex, time::Duration};
+
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+/// Limit for hosts no rule matches, unless they are local (those are never limited by default).
+pub const DEFAULT_REQUESTS_PER_SEC: f64 = 2.0;
+
+#[derive(Clone, Debug)]
+struct Rule {
+    pattern: String,
+    /// `None` means unlimited.
+    per_sec: Option<f64>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Goes negative while requests are queued behind the limit.
+    tokens: f64,
+    updated: Instant,
+    last_delay: Duration,
+}
+
+/// Per-host token buckets for page image fetches, shared by interactive requests and jobs so
+/// preprocessing can't hammer a source site. Waiting requests reserve their slot up front and
+/// sleep until it comes, so there is no polling.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rules: Vec<Rule>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    /// Rules are `(host pattern, requests per second)` pairs, checked in order; `None` means
+    /// unlimited. A pattern is a host name, `*.suffix`, `prefix*` or `*`.
+    pub fn new(rules: Vec<(String, Option<f64>)>) -> Self {
+        Self {
+            rules: rules
+                .into_iter()
+                .map(|(pattern, per_sec)| Rule {
+                    pattern: pattern.to_ascii_lowercase(),
+                    per_sec,
+                })
+                .collect(),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reads rules from `MANGATAN_OCR_RATE_LIMITS`, e.g. `*.example.org=1,cdn.example.com=0.5,*=4`.
+    /// A rate of `0` means unlimited.
+    pub fn from_env() -> Self {
+        let rules = std::env::var("MANGATAN_OCR_RATE_LIMITS")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|rule| !rule.trim().is_empty())
+            .filter_map(|rule| {
+                let parsed = rule.split_once('=').and_then(|(pattern, rate)| {
+                    let rate: f64 = rate.trim().parse().ok()?;
+                    (rate >= 0.0)
+                        .then(|| (pattern.trim().to_string(), (rate > 0.0).then_some(rate)))
+                });
+                if parsed.is_none() {
+                    warn!("Ignoring invalid MANGATAN_OCR_RATE_LIMITS rule: {rule}");
+                }
+                parsed
+            })
+            .collect();
+        Self::new(rules)
+    }
+
+    fn limit_for(&self, host: &str) -> Option<f64> {
+        match self
+            .rules
+            .iter()
+            .find(|rule| host_matches(&rule.pattern, host))
+        {
+            Some(rule) => rule.per_sec,
+            None if is_local(host) => None,
+            None => Some(DEFAULT_REQUESTS_PER_SEC),
+        }
+    }
+
+    /// Takes a slot for a request to `url`'s host and returns how long the caller must wait
+    /// before sending it. URLs without a host (e.g. `data:`) are never limited.
+    pub fn reserve(&self, url: &str) -> Duration {
+        let Some(host) = host_of(url) else {
+            return Duration::ZERO;
+        };
+        let Some(per_sec) = self.limit_for(&host) else {
+            return Duration::ZERO;
+        };
+
+        let now = Instant::now();
+        let capacity = per_sec.max(1.0);
+        let mut buckets = self.buckets.lock().expect("lock");
+        let bucket = buckets.entry(host).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+            last_delay: Duration::ZERO,
+        });
+
+        let refill = now.saturating_duration_since(bucket.updated).as_secs_f64() * per_sec;
+        bucket.tokens = (bucket.tokens + refill).min(capacity) - 1.0;
+        bucket.updated = now;
+        bucket.last_delay = match bucket.tokens < 0.0 {
+            true => Duration::from_secs_f64(-bucket.tokens / per_sec),
+            false => Duration::ZERO,
+        };
+        bucket.last_delay
+    }
+
+    /// Waits for a slot for a request to `url`'s host and returns how long that took.
+    pub async fn acquire(&self, url: &str) -> Duration {
+        let delay = self.reserve(url);
+        if !delay.is_zero() {
+            debug!("Rate limited {url}, waiting {delay:?}");
+            tokio::time::sleep(delay).await;
+        }
+        delay
+    }
+
+    /// The wait handed to the most recent request for `url`'s host.
+    pub fn current_delay(&self, url: &str) -> Duration {
+        host_of(url)
+            .and_then(|host| {
+                let buckets = self.buckets.lock().expect("lock");
+                buckets.get(&host).map(|bucket| bucket.last_delay)
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn host_of(url: &str) -> Option<String> {
+    let parsed = reqwest::Url::parse(url).ok()?;
+    parsed.host_str().map(str::to_ascii_lowercase)
+}
+
+pub(crate) fn host_matches(pattern: &str, host: &str) -> bool {
+    if pattern == "*" {
+        return true;
+    }
+    if let Some(suffix) = pattern.strip_prefix('*') {
+        return host.ends_with(suffix);
+    }
+    if let Some(prefix) = pattern.strip_suffix('*') {
+        return host.starts_with(prefix);
+    }
+    pattern == host
+}
+
+/// Loopback and private-network addresses, i.e. the user's own Suwayomi.
+fn is_local(host: &str) -> bool {
+    if host == "localhost" {
+        return true;
+    }
+    match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => ip.is_loopback() || ip.is_private() || ip.is_link_local(),
+        Ok(IpAddr::V6(ip)) => ip.is_loopback(),
+        Err(_) => false,
+    }
+}
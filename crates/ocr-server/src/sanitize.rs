@@ -0,0 +1,143 @@
+//! Checks cache entries that come from outside this build (imports, and cache files written by
+//! older or modified builds) before the merge and overlay code gets to see them.
+
+use std::{collections::HashMap, fs, ops::RangeInclusive, path::Path};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    logic::OcrResult,
+    state::{CacheEntry, write_atomic},
+};
+
+/// Where box edges may lie, in fractions of the page. Boxes of text at the very edge stick out
+/// a little; anything further out is clamped back.
+pub const COORD_RANGE: RangeInclusive<f64> = -0.5..=1.5;
+
+/// An entry that couldn't be used as is or repaired, kept as it was found.
+#[derive(Clone, Debug)]
+pub struct Rejected {
+    pub key: String,
+    pub reason: String,
+    pub entry: Value,
+}
+
+/// Parses entries one at a time, so a malformed one doesn't take the rest down with it.
+pub fn parse_entries(
+    raw: HashMap<String, Value>,
+    rejected: &mut Vec<Rejected>,
+) -> HashMap<String, CacheEntry> {
+    let mut entries = HashMap::with_capacity(raw.len());
+    for (key, value) in raw {
+        match CacheEntry::deserialize(&value) {
+            Ok(entry) => {
+                entries.insert(key, entry);
+            }
+            Err(e) => rejected.push(Rejected {
+                key,
+                reason: e.to_string(),
+                entry: value,
+            }),
+        }
+    }
+    entries
+}
+
+/// Repairs what it can in `entries` and moves the rest to `rejected`. Returns how many entries
+/// were repaired.
+pub fn check_entries(
+    entries: &mut HashMap<String, CacheEntry>,
+    rejected: &mut Vec<Rejected>,
+) -> usize {
+    let mut repaired = 0;
+    entries.retain(|key, entry| match check_entry(entry) {
+        Ok(changed) => {
+            repaired += usize::from(changed);
+            true
+        }
+        Err(reason) => {
+            rejected.push(Rejected {
+                key: key.clone(),
+                reason,
+                entry: serde_json::to_value(&*entry).unwrap_or_default(),
+            });
+            false
+        }
+    });
+    repaired
+}
+
+/// Lines without text are dropped (a page without any is cached with no lines at all), and
+/// boxes are brought into [`COORD_RANGE`]. Returns whether anything changed, or why the entry
+/// can't be used.
+pub fn check_entry(entry: &mut CacheEntry) -> Result<bool, String> {
+    let lines = entry.data.len();
+    entry.data.retain(|result| !result.text.trim().is_empty());
+    let mut changed = entry.data.len() != lines;
+    for result in &mut entry.data {
+        changed |= check_result(result)?;
+    }
+    Ok(changed)
+}
+
+fn check_result(result: &mut OcrResult) -> Result<bool, String> {
+    let bbox = &mut result.tight_bounding_box;
+    if [bbox.x, bbox.y, bbox.width, bbox.height]
+        .iter()
+        .any(|value| !value.is_finite())
+    {
+        return Err(format!("non-finite box for {:?}", result.text));
+    }
+    let bad_rotation = bbox.rotation.is_some_and(|rotation| !rotation.is_finite());
+    if bad_rotation {
+        bbox.rotation = None;
+    }
+    let mut changed = bad_rotation;
+    for (start, size) in [
+        (&mut bbox.x, &mut bbox.width),
+        (&mut bbox.y, &mut bbox.height),
+    ] {
+        changed |= clamp_span(start, size)
+            .ok_or_else(|| format!("box for {:?} is off the page", result.text))?;
+    }
+    Ok(changed)
+}
+
+/// Makes `size` non-negative (a negative one measures from the other edge) and clamps both
+/// edges into [`COORD_RANGE`]. Returns whether anything changed, or `None` when the span lies
+/// entirely outside the range.
+fn clamp_span(start: &mut f64, size: &mut f64) -> Option<bool> {
+    if *size >= 0.0 && COORD_RANGE.contains(start) && COORD_RANGE.contains(&(*start + *size)) {
+        return Some(false);
+    }
+    let (low, high) = (*COORD_RANGE.start(), *COORD_RANGE.end());
+    let (from, to) = if *size < 0.0 {
+        (*start + *size, *start)
+    } else {
+        (*start, *start + *size)
+    };
+    if to < low || from > high {
+        return None;
+    }
+    let from = from.clamp(low, high);
+    *start = from;
+    *size = to.clamp(low, high) - from;
+    Some(true)
+}
+
+/// Adds `rejected` to the quarantine file at `path`, next to what's already in it, keyed by
+/// cache key. Returns whether the file was written.
+pub fn quarantine(path: &Path, rejected: &[Rejected]) -> bool {
+    let mut quarantined: HashMap<String, Value> = fs::read(path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default();
+    for entry in rejected {
+        quarantined.insert(
+            entry.key.clone(),
+            serde_json::json!({ "reason": entry.reason, "entry": entry.entry }),
+        );
+    }
+    serde_json::to_vec_pretty(&quarantined).is_ok_and(|bytes| write_atomic(path, &bytes))
+}
@@ -1,36 +1,177 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs,
     io::Write,
-    path::PathBuf,
-    sync::{Arc, RwLock, atomic::AtomicUsize},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
+use mangatan_core::data_dir;
 use serde::{Deserialize, Serialize};
-use tracing::warn;
+use serde_json::Value;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
 
-use crate::logic::OcrResult;
+use crate::{
+    backend_watch::BackendWatch,
+    error::ApiError,
+    image_cache::{ImageCache, ImageCacheStats},
+    job_history::JobHistory,
+    logic::OcrResult,
+    quality::MergeQuality,
+    rate_limit::RateLimiter,
+    sanitize::{self, Rejected},
+    watchdog::{self, Heartbeat, WorkerHealth},
+};
 
-#[derive(Clone, Copy, Serialize, Debug)]
+#[derive(Clone, Serialize, Debug)]
 pub struct JobProgress {
     pub current: usize,
     pub total: usize,
+    /// Pages that were already cached when the job started, e.g. after an interrupted run.
+    pub resumed_from: usize,
+    /// Pages whose cache key is shared with another page of the job (see
+    /// [`CacheKeyRules::count_collisions`](crate::cache_key::CacheKeyRules::count_collisions)).
+    pub key_collisions: usize,
+    /// Pages that failed in the main pass.
+    pub failed: usize,
+    /// Pages still failing during and after the retry sweep at the end of the job; `None` until
+    /// the sweep starts.
+    pub still_failing: Option<usize>,
+    /// Most recent page failure, in the same shape the HTTP endpoints return.
+    pub last_error: Option<ApiError>,
+    /// Lens is suspect (see [`BackendWatch`]), so pages coming back empty aren't cached.
+    pub backend_suspect: bool,
+}
+
+/// How the OCR cache is laid out on disk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheLayout {
+    /// Everything lives in a single `ocr-cache.json` file.
+    Single,
+    /// One file per series (`context`) under `ocr-cache/`, so saving or corrupting one series
+    /// doesn't affect the others.
+    PerSeries,
+}
+
+// Switching layouts loads the other one's files on the first start; once everything is saved
+// in the new layout they're renamed to `<name>.migrated-<unix secs>`, so switching back later
+// migrates again instead of reviving stale entries.
+
+impl CacheLayout {
+    /// Reads the layout from `MANGATAN_OCR_CACHE_LAYOUT` (`single` or `per-series`).
+    pub fn from_env() -> Self {
+        match std::env::var("MANGATAN_OCR_CACHE_LAYOUT") {
+            Ok(value) if value.eq_ignore_ascii_case("per-series") => Self::PerSeries,
+            _ => Self::Single,
+        }
+    }
+}
+
+/// How often unsaved cache changes are written out when `MANGATAN_OCR_FLUSH_INTERVAL_SECS`
+/// isn't set.
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Reads `MANGATAN_OCR_FLUSH_INTERVAL_SECS`; `0` turns the periodic flush off and saves on every
+/// change instead.
+fn flush_interval_from_env() -> Option<Duration> {
+    match std::env::var("MANGATAN_OCR_FLUSH_INTERVAL_SECS") {
+        Ok(value) => match value.trim().parse::<u64>() {
+            Ok(0) => None,
+            Ok(secs) => Some(Duration::from_secs(secs)),
+            Err(_) => {
+                warn!("Ignoring invalid MANGATAN_OCR_FLUSH_INTERVAL_SECS={value:?}");
+                Some(DEFAULT_FLUSH_INTERVAL)
+            }
+        },
+        Err(_) => Some(DEFAULT_FLUSH_INTERVAL),
+    }
 }
 
 #[derive(Clone)]
 pub struct AppState {
     pub cache: Arc<RwLock<HashMap<String, CacheEntry>>>,
     pub cache_path: PathBuf,
+    pub cache_layout: CacheLayout,
     pub active_jobs: Arc<AtomicUsize>,
     pub requests_processed: Arc<AtomicUsize>,
+    // Session counters, only reported in the shutdown summary
+    pub cache_hits: Arc<AtomicUsize>,
+    pub cache_misses: Arc<AtomicUsize>,
+    pub jobs_completed: Arc<AtomicUsize>,
+    pub started_at: Instant,
     pub active_chapter_jobs: Arc<RwLock<HashMap<String, JobProgress>>>,
+    /// Summaries of finished chapter jobs.
+    pub job_history: Arc<JobHistory>,
     pub chapter_pages_map: Arc<RwLock<HashMap<String, usize>>>,
+    pub paused: Arc<AtomicBool>,
+    /// When set, `/ocr` cache misses are refused while paused instead of being processed.
+    pub pause_interactive: bool,
+    /// Default for stripping zero-width characters from returned text; requests can override it.
+    pub strip_zero_width: bool,
+    /// Downloaded page images kept for retries; `None` unless enabled via env.
+    pub image_cache: Option<ImageCache>,
+    /// Per-host limits on page image fetches.
+    pub rate_limiter: Arc<RateLimiter>,
+    /// Cache keys exempt from eviction, and from purges that ask to keep them.
+    pub pinned: Arc<RwLock<HashSet<String>>>,
+    /// How often [`cache_changed`](Self::cache_changed) changes get saved; `None` saves each one
+    /// right away.
+    pub flush_interval: Option<Duration>,
+    /// Finished save cycles, watched by [`watchdog::watch_saver`].
+    pub saver_heartbeat: Heartbeat,
+    /// How long changes may wait on the saver before it counts as stalled; `None` never does.
+    pub saver_stall_after: Option<Duration>,
+    /// Whether Lens has started returning nothing for pages with text.
+    pub backend_watch: BackendWatch,
+    /// Stops the background saver and watchdog once cancelled. Whoever cancels it still has to
+    /// flush the cache themselves.
+    pub shutdown: CancellationToken,
+    cache_dirty: Arc<AtomicBool>,
+    pinned_path: PathBuf,
+    quarantine_path: PathBuf,
+    pause_marker_path: PathBuf,
+    // Fingerprints of the series files as last written, so unchanged series aren't rewritten
+    series_fingerprints: Arc<Mutex<HashMap<PathBuf, u64>>>,
+    // The other layout's files this cache was loaded from, until the first complete save
+    migrated_from: Arc<Mutex<Option<PathBuf>>>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct CacheEntry {
     pub context: String,
     pub data: Vec<OcrResult>,
+    /// How many blocks the page had before the per-page cap dropped some.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncated_from: Option<usize>,
+    /// How the merge turned out, for `/quality-report`. Missing on entries cached before it was
+    /// measured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merge_quality: Option<MergeQuality>,
+}
+
+impl CacheEntry {
+    /// Blocks with a box on the page; a page with only `no_geometry` lines has nothing to show.
+    pub fn placed_len(&self) -> usize {
+        crate::logic::placed_len(&self.data)
+    }
+}
+
+/// What the OCR data takes up on disk, for a settings screen that offers to purge it.
+#[derive(Serialize, Debug)]
+pub struct StorageStats {
+    /// `ocr-cache.json`, or everything under `ocr-cache/` with the per-series layout.
+    pub cache_bytes: u64,
+    /// Entries in memory; the file catches up on the next flush when `unsaved_changes` is set.
+    pub cache_entries: usize,
+    pub unsaved_changes: bool,
+    pub image_cache: Option<ImageCacheStats>,
+    /// The cache plus the image cache.
+    pub total_bytes: u64,
 }
 
 // Struct for the persistent state (cache and metadata)
@@ -40,35 +181,295 @@ struct PersistentState {
     chapter_pages_map: HashMap<String, usize>,
 }
 
+/// [`PersistentState`] as found on disk, for when some entries don't parse.
+#[derive(Deserialize, Default)]
+struct RawPersistentState {
+    #[serde(default)]
+    cache: HashMap<String, Value>,
+    #[serde(default)]
+    chapter_pages_map: HashMap<String, usize>,
+}
+
+const SINGLE_FILE: &str = "ocr-cache.json";
+const SERIES_DIR: &str = "ocr-cache";
+const SERIES_META_FILE: &str = "chapter-pages.json";
+const PINNED_FILE: &str = "ocr-pinned.json";
+const QUARANTINE_FILE: &str = "ocr-quarantine.json";
+
 impl AppState {
     pub fn new(cache_dir: PathBuf) -> Self {
-        let cache_path = cache_dir.join("ocr-cache.json");
+        // Still starts, serving what's cached, but nothing new gets saved
+        if let Err(e) = data_dir::check_writable(&cache_dir) {
+            tracing::error!(
+                "OCR cache directory {} is not writable ({e}); new results won't be saved.",
+                cache_dir.display()
+            );
+        }
+        let cache_layout = CacheLayout::from_env();
+        let single_path = cache_dir.join(SINGLE_FILE);
+        let series_path = cache_dir.join(SERIES_DIR);
+        let cache_path = match cache_layout {
+            CacheLayout::Single => single_path.clone(),
+            CacheLayout::PerSeries => series_path.clone(),
+        };
 
-        let persistent_state = if cache_path.exists() {
-            if let Ok(file) = fs::File::open(&cache_path) {
-                serde_json::from_reader(file).unwrap_or_else(|e| {
-                    warn!("Failed to deserialize cache file: {e}. Starting fresh.");
-                    PersistentState::default()
-                })
-            } else {
-                warn!("Failed to open cache file. Starting fresh.");
-                PersistentState::default()
+        let mut series_fingerprints = HashMap::new();
+        let mut rejected = Vec::new();
+        let mut migrated_from = None;
+        let mut persistent_state = match cache_layout {
+            CacheLayout::Single if !single_path.exists() && series_path.is_dir() => {
+                migrated_from = Some(series_path.clone());
+                load_series_dir(&series_path, &mut series_fingerprints, &mut rejected)
+            }
+            CacheLayout::Single => load_single_file(&single_path, &mut rejected),
+            CacheLayout::PerSeries if series_path.is_dir() => {
+                load_series_dir(&series_path, &mut series_fingerprints, &mut rejected)
+            }
+            CacheLayout::PerSeries => {
+                // Migrate from the monolithic file on first start; the next save splits it up.
+                if single_path.exists() {
+                    migrated_from = Some(single_path.clone());
+                }
+                load_single_file(&single_path, &mut rejected)
             }
-        } else {
-            PersistentState::default()
         };
+        if let Some(old) = &migrated_from {
+            info!(
+                "OCR cache: migrating {} to the {cache_layout:?} layout.",
+                old.display()
+            );
+        }
+        // Entries written by older or modified builds get repaired, or set aside so the next
+        // save doesn't lose them for good
+        let repaired = sanitize::check_entries(&mut persistent_state.cache, &mut rejected);
+        let quarantine_path = cache_dir.join(QUARANTINE_FILE);
+        if repaired > 0 || !rejected.is_empty() {
+            warn!(
+                "OCR cache: repaired {repaired} entries, set aside {} that can't be used (see {}).",
+                rejected.len(),
+                quarantine_path.display()
+            );
+        }
+        if !rejected.is_empty() {
+            sanitize::quarantine(&quarantine_path, &rejected);
+        }
+
+        // The pause survives restarts so a reboot doesn't silently resume a large backlog
+        let pause_marker_path = cache_dir.join("ocr-paused");
+        let paused = pause_marker_path.exists();
+        if paused {
+            warn!("OCR background jobs are paused (resume via POST /resume).");
+        }
+        let pause_interactive =
+            std::env::var("MANGATAN_OCR_PAUSE_INTERACTIVE").is_ok_and(|v| v == "1" || v == "true");
+        let strip_zero_width =
+            std::env::var("MANGATAN_OCR_NO_ZWSP").is_ok_and(|v| v == "1" || v == "true");
+        let image_cache = ImageCache::from_env(&cache_dir);
+        let pinned_path = cache_dir.join(PINNED_FILE);
+        let pinned = load_pinned(&pinned_path);
+        let job_history = Arc::new(JobHistory::from_env(&cache_dir));
 
         Self {
             cache: Arc::new(RwLock::new(persistent_state.cache)),
             chapter_pages_map: Arc::new(RwLock::new(persistent_state.chapter_pages_map)),
             cache_path,
+            cache_layout,
             active_jobs: Arc::new(AtomicUsize::new(0)),
             requests_processed: Arc::new(AtomicUsize::new(0)),
+            cache_hits: Arc::new(AtomicUsize::new(0)),
+            cache_misses: Arc::new(AtomicUsize::new(0)),
+            jobs_completed: Arc::new(AtomicUsize::new(0)),
+            started_at: Instant::now(),
             active_chapter_jobs: Arc::new(RwLock::new(HashMap::new())),
+            job_history,
+            series_fingerprints: Arc::new(Mutex::new(series_fingerprints)),
+            migrated_from: Arc::new(Mutex::new(migrated_from)),
+            paused: Arc::new(AtomicBool::new(paused)),
+            pause_interactive,
+            strip_zero_width,
+            image_cache,
+            rate_limiter: Arc::new(RateLimiter::from_env()),
+            pinned: Arc::new(RwLock::new(pinned)),
+            flush_interval: flush_interval_from_env(),
+            saver_heartbeat: Heartbeat::new("started"),
+            saver_stall_after: watchdog::stall_after_from_env(),
+            backend_watch: BackendWatch::from_env(),
+            shutdown: CancellationToken::new(),
+            cache_dirty: Arc::new(AtomicBool::new(repaired > 0 || !rejected.is_empty())),
+            pinned_path,
+            quarantine_path,
+            pause_marker_path,
+        }
+    }
+
+    pub fn is_pinned(&self, cache_key: &str) -> bool {
+        self.pinned
+            .read()
+            .expect("pinned lock poisoned")
+            .contains(cache_key)
+    }
+
+    /// Pins or unpins a cache key and persists the set. Returns whether anything changed.
+    pub fn set_pinned(&self, cache_key: &str, pinned: bool) -> bool {
+        let changed = {
+            let mut set = self.pinned.write().expect("pinned lock poisoned");
+            if pinned {
+                set.insert(cache_key.to_string())
+            } else {
+                set.remove(cache_key)
+            }
+        };
+        if changed {
+            self.save_pinned();
         }
+        changed
+    }
+
+    pub fn clear_pinned(&self) {
+        self.pinned.write().expect("pinned lock poisoned").clear();
+        self.save_pinned();
+    }
+
+    /// Sets `rejected` cache entries aside in `ocr-quarantine.json` for inspection. Returns
+    /// whether the file was written.
+    pub fn quarantine(&self, rejected: &[Rejected]) -> bool {
+        sanitize::quarantine(&self.quarantine_path, rejected)
+    }
+
+    fn save_pinned(&self) {
+        let bytes = {
+            let set = self.pinned.read().expect("pinned lock poisoned");
+            let mut keys: Vec<&String> = set.iter().collect();
+            keys.sort();
+            serde_json::to_vec_pretty(&keys).unwrap_or_default()
+        };
+        write_atomic(&self.pinned_path, &bytes);
+    }
+
+    /// Logs what this session did; called once when the server shuts down.
+    pub fn log_session_summary(&self) {
+        let hits = self.cache_hits.load(Ordering::Relaxed);
+        let misses = self.cache_misses.load(Ordering::Relaxed);
+        let hit_rate = if hits + misses == 0 {
+            0.0
+        } else {
+            hits as f64 * 100.0 / (hits + misses) as f64
+        };
+        info!(
+            "📊 [OCR] Session summary: uptime={}s requests={} cache_hits={hits} cache_misses={misses} ({hit_rate:.1}% hits) jobs_completed={} jobs_active={} cached_pages={}",
+            self.started_at.elapsed().as_secs(),
+            self.requests_processed.load(Ordering::Relaxed),
+            self.jobs_completed.load(Ordering::Relaxed),
+            self.active_jobs.load(Ordering::Relaxed),
+            self.cache.read().expect("cache lock poisoned").len(),
+        );
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+
+        let result = if paused {
+            fs::write(&self.pause_marker_path, b"")
+        } else if self.pause_marker_path.exists() {
+            fs::remove_file(&self.pause_marker_path)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = result {
+            warn!("Failed to persist pause state: {e}");
+        }
+    }
+
+    /// Records a change to the cache. Saved by the next periodic flush, or right away when
+    /// there's no flush interval.
+    pub fn cache_changed(&self) {
+        match self.flush_interval {
+            Some(_) => self.cache_dirty.store(true, Ordering::Release),
+            None => self.save_cache(),
+        }
+    }
+
+    /// Whether there are changes that haven't been saved yet.
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.cache_dirty.load(Ordering::Acquire)
+    }
+
+    /// Saves the cache if it changed since the last save. Returns whether it did.
+    pub fn flush_cache(&self) -> bool {
+        if !self.has_unsaved_changes() {
+            return false;
+        }
+        self.save_cache();
+        true
     }
 
     pub fn save_cache(&self) {
+        self.saver_heartbeat.start("saving cache");
+        // Cleared before the cache is read, so a change made during the save is kept for the
+        // next one
+        self.cache_dirty.store(false, Ordering::Release);
+        let saved = match self.cache_layout {
+            CacheLayout::Single => self.save_single_file(),
+            CacheLayout::PerSeries => self.save_series_dir(),
+        };
+        if saved {
+            self.retire_migrated_files();
+        }
+        self.saver_heartbeat.finish("saved cache");
+    }
+
+    /// Renames the files a layout switch migrated from, now that everything in them is saved
+    /// in the current layout.
+    fn retire_migrated_files(&self) {
+        let mut migrated_from = self.migrated_from.lock().expect("migration lock poisoned");
+        let Some(old) = migrated_from.take() else {
+            return;
+        };
+        let retired = set_aside_path(&old, "migrated");
+        match fs::rename(&old, &retired) {
+            Ok(()) => info!(
+                "OCR cache migrated to the {:?} layout; the old copy is kept at {}.",
+                self.cache_layout,
+                retired.display()
+            ),
+            Err(e) => {
+                warn!("Failed to set aside {} after migrating: {e}", old.display());
+                *migrated_from = Some(old);
+            }
+        }
+    }
+
+    /// Stalled when changes (or a save) have been pending for longer than the threshold
+    /// without the saver finishing a cycle.
+    pub fn saver_health(&self) -> WorkerHealth {
+        let (busy, since, activity) = self.saver_heartbeat.read();
+        let pending = busy || self.has_unsaved_changes();
+        WorkerHealth {
+            last_heartbeat_secs: since.as_secs(),
+            last_activity: activity,
+            stalled: pending && self.saver_stall_after.is_some_and(|after| since >= after),
+        }
+    }
+
+    /// Reads the sizes off disk, so a large image cache takes a moment.
+    pub fn storage_stats(&self) -> StorageStats {
+        let cache_bytes = disk_usage(&self.cache_path);
+        let image_cache = self.image_cache.as_ref().map(ImageCache::stats);
+        StorageStats {
+            cache_bytes,
+            cache_entries: self.cache.read().expect("cache lock poisoned").len(),
+            unsaved_changes: self.has_unsaved_changes(),
+            total_bytes: cache_bytes + image_cache.as_ref().map_or(0, |stats| stats.bytes),
+            image_cache,
+        }
+    }
+
+    /// Returns whether the file was written.
+    fn save_single_file(&self) -> bool {
         let state_to_save = {
             let cache = self.cache.read().expect("cache lock poisoned");
             let pages_map = self
@@ -83,15 +484,259 @@ impl AppState {
             serde_json::to_vec_pretty(&state).unwrap_or_default()
         };
 
-        let tmp_path = self.cache_path.with_extension("tmp");
+        write_atomic(&self.cache_path, &state_to_save)
+    }
 
-        if let Ok(mut file) = fs::File::create(&tmp_path) {
-            if file.write_all(&state_to_save).is_ok() {
-                let _ = file.sync_all();
-                let _ = fs::rename(&tmp_path, &self.cache_path);
+    /// Returns whether every series file is on disk.
+    fn save_series_dir(&self) -> bool {
+        let files = {
+            let cache = self.cache.read().expect("cache lock poisoned");
+            let pages_map = self
+                .chapter_pages_map
+                .read()
+                .expect("pages map lock poisoned");
+
+            let mut by_series: HashMap<&str, HashMap<&String, &CacheEntry>> = HashMap::new();
+            for (key, entry) in cache.iter() {
+                by_series
+                    .entry(entry.context.as_str())
+                    .or_default()
+                    .insert(key, entry);
             }
+
+            let mut files: Vec<(PathBuf, Vec<u8>)> = by_series
+                .into_iter()
+                .map(|(context, entries)| {
+                    (
+                        self.cache_path.join(series_file_name(context)),
+                        serde_json::to_vec_pretty(&entries).unwrap_or_default(),
+                    )
+                })
+                .collect();
+            files.push((
+                self.cache_path.join(SERIES_META_FILE),
+                serde_json::to_vec_pretty(&*pages_map).unwrap_or_default(),
+            ));
+            files
+        };
+
+        if let Err(e) = fs::create_dir_all(&self.cache_path) {
+            tracing::error!("Failed to create cache directory: {e}");
+            return false;
+        }
+
+        let mut fingerprints = self
+            .series_fingerprints
+            .lock()
+            .expect("fingerprints lock poisoned");
+        let mut written = HashMap::with_capacity(files.len());
+        let mut all_saved = true;
+
+        for (path, bytes) in files {
+            let fingerprint = fingerprint(&bytes);
+            let unchanged = fingerprints.get(&path) == Some(&fingerprint);
+            // A failed write is remembered with a bogus fingerprint so it's retried next time
+            let saved = unchanged || write_atomic(&path, &bytes);
+            all_saved &= saved;
+            written.insert(path, if saved { fingerprint } else { 0 });
+        }
+
+        // Series that no longer have any entries (e.g. after a purge) get their files removed
+        for stale in fingerprints
+            .keys()
+            .filter(|path| !written.contains_key(*path))
+        {
+            let _ = fs::remove_file(stale);
+        }
+
+        *fingerprints = written;
+        all_saved
+    }
+}
+
+/// Saves unsaved cache changes every `interval`, until [`AppState::shutdown`] is cancelled.
+pub async fn flush_periodically(state: AppState, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = state.shutdown.cancelled() => return,
+        }
+        if !state.has_unsaved_changes() {
+            state.saver_heartbeat.idle();
+            continue;
+        }
+        let state = state.clone();
+        let _ = tokio::task::spawn_blocking(move || state.flush_cache()).await;
+    }
+}
+
+fn load_single_file(path: &Path, rejected: &mut Vec<Rejected>) -> PersistentState {
+    if !path.exists() {
+        return PersistentState::default();
+    }
+
+    let Ok(bytes) = fs::read(path) else {
+        warn!("Failed to open cache file. Starting fresh.");
+        return PersistentState::default();
+    };
+    match serde_json::from_slice(&bytes) {
+        Ok(state) => state,
+        Err(e) => match serde_json::from_slice::<RawPersistentState>(&bytes) {
+            Ok(raw) => {
+                warn!("Some cache entries don't parse ({e}); loading the others.");
+                PersistentState {
+                    cache: sanitize::parse_entries(raw.cache, rejected),
+                    chapter_pages_map: raw.chapter_pages_map,
+                }
+            }
+            Err(_) => {
+                warn!("Failed to deserialize cache file: {e}. Starting fresh.");
+                PersistentState::default()
+            }
+        },
+    }
+}
+
+fn load_pinned(path: &Path) -> HashSet<String> {
+    let Ok(bytes) = fs::read(path) else {
+        return HashSet::new();
+    };
+    serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+        warn!("Failed to deserialize pinned entries: {e}. Starting without pins.");
+        HashSet::new()
+    })
+}
+
+fn load_series_dir(
+    dir: &Path,
+    fingerprints: &mut HashMap<PathBuf, u64>,
+    rejected: &mut Vec<Rejected>,
+) -> PersistentState {
+    let mut state = PersistentState::default();
+
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        warn!("Failed to read cache directory. Starting fresh.");
+        return state;
+    };
+
+    for path in read_dir.flatten().map(|entry| entry.path()) {
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let Ok(bytes) = fs::read(&path) else {
+            warn!("Failed to open cache file {}. Skipping.", path.display());
+            continue;
+        };
+
+        let is_meta = path
+            .file_name()
+            .is_some_and(|name| name == SERIES_META_FILE);
+        let loaded = if is_meta {
+            serde_json::from_slice::<HashMap<String, usize>>(&bytes)
+                .map(|pages_map| state.chapter_pages_map.extend(pages_map))
         } else {
-            tracing::error!("Failed to create temp file for saving cache");
+            serde_json::from_slice::<HashMap<String, CacheEntry>>(&bytes)
+                .or_else(|e| {
+                    // Keep the entries that do parse; the rest go to quarantine
+                    let raw =
+                        serde_json::from_slice::<HashMap<String, Value>>(&bytes).map_err(|_| e)?;
+                    warn!(
+                        "Some entries of cache file {} don't parse; loading the others.",
+                        path.display()
+                    );
+                    Ok(sanitize::parse_entries(raw, rejected))
+                })
+                .map(|entries| state.cache.extend(entries))
+        };
+
+        match loaded {
+            Ok(()) => {
+                fingerprints.insert(path, fingerprint(&bytes));
+            }
+            // Only this series is lost. The file is moved out of the way, so saving the series
+            // again can't overwrite it, and kept for inspection.
+            Err(e) => {
+                let aside = set_aside_path(&path, "malformed");
+                match fs::rename(&path, &aside) {
+                    Ok(()) => warn!(
+                        "Failed to deserialize cache file {}: {e}. Skipping; moved to {}.",
+                        path.display(),
+                        aside.display()
+                    ),
+                    Err(rename_err) => warn!(
+                        "Failed to deserialize cache file {}: {e}. Skipping; couldn't move it \
+                         aside: {rename_err}",
+                        path.display()
+                    ),
+                }
+            }
+        }
+    }
+
+    state
+}
+
+/// Builds a filesystem-safe, stable file name for a series.
+fn series_file_name(context: &str) -> String {
+    let slug: String = context
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_alphanumeric() => Some(c),
+            ' ' | '-' | '_' => Some('_'),
+            _ => None,
+        })
+        .take(48)
+        .collect();
+
+    // The hash keeps distinct series with the same slug apart
+    format!("{slug}-{:016x}.json", fingerprint(context.as_bytes()))
+}
+
+/// `<path>.<label>-<unix secs>`, where a file or directory that's no longer used is kept.
+fn set_aside_path(path: &Path, label: &str) -> PathBuf {
+    let suffix = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let mut aside = path.as_os_str().to_owned();
+    aside.push(format!(".{label}-{suffix}"));
+    PathBuf::from(aside)
+}
+
+/// Size of a file, or of everything under a directory; 0 if it doesn't exist.
+fn disk_usage(path: &Path) -> u64 {
+    let Ok(meta) = fs::metadata(path) else {
+        return 0;
+    };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    fs::read_dir(path).map_or(0, |entries| {
+        entries
+            .flatten()
+            .map(|entry| disk_usage(&entry.path()))
+            .sum()
+    })
+}
+
+/// FNV-1a, used instead of `DefaultHasher` because file names must be stable across builds.
+pub(crate) fn fingerprint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+pub(crate) fn write_atomic(path: &Path, bytes: &[u8]) -> bool {
+    let tmp_path = path.with_extension("tmp");
+
+    if let Ok(mut file) = fs::File::create(&tmp_path) {
+        if file.write_all(bytes).is_ok() {
+            let _ = file.sync_all();
+            return fs::rename(&tmp_path, path).is_ok();
         }
+    } else {
+        tracing::error!("Failed to create temp file for saving cache");
     }
+    false
 }
@@ -0,0 +1,131 @@
+//! Stall detection for the cache saver. A save stuck on a lock otherwise goes unnoticed until
+//! the changes are lost at shutdown.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use tracing::{error, info};
+
+use crate::state::AppState;
+
+/// How long the saver may go without finishing a cycle while there's something to save.
+pub const DEFAULT_STALL_AFTER: Duration = Duration::from_secs(5 * 60);
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Reads `MANGATAN_OCR_SAVER_STALL_SECS`; `0` turns stall detection off.
+pub fn stall_after_from_env() -> Option<Duration> {
+    match std::env::var("MANGATAN_OCR_SAVER_STALL_SECS") {
+        Ok(v) => v
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs),
+        Err(_) => Some(DEFAULT_STALL_AFTER),
+    }
+}
+
+struct Beat {
+    at: Instant,
+    activity: String,
+    busy: bool,
+}
+
+/// When a background worker last finished a cycle, and what it's doing now.
+#[derive(Clone)]
+pub struct Heartbeat {
+    beat: Arc<Mutex<Beat>>,
+}
+
+impl Heartbeat {
+    pub fn new(activity: &str) -> Self {
+        Self {
+            beat: Arc::new(Mutex::new(Beat {
+                at: Instant::now(),
+                activity: activity.to_string(),
+                busy: false,
+            })),
+        }
+    }
+
+    /// Notes that a cycle started; it isn't finished until [`finish`](Self::finish).
+    pub fn start(&self, activity: &str) {
+        let mut beat = self.beat.lock().expect("heartbeat lock poisoned");
+        beat.activity = activity.to_string();
+        beat.busy = true;
+    }
+
+    /// Records a cycle that found nothing to do, unless one is still running elsewhere.
+    pub fn idle(&self) {
+        let mut beat = self.beat.lock().expect("heartbeat lock poisoned");
+        if !beat.busy {
+            beat.at = Instant::now();
+            beat.activity = "idle".to_string();
+        }
+    }
+
+    /// Records a finished cycle.
+    pub fn finish(&self, activity: &str) {
+        let mut beat = self.beat.lock().expect("heartbeat lock poisoned");
+        beat.at = Instant::now();
+        beat.activity = activity.to_string();
+        beat.busy = false;
+    }
+
+    /// Whether a cycle is running, how long ago the last one finished, and the latest activity.
+    pub fn read(&self) -> (bool, Duration, String) {
+        let beat = self.beat.lock().expect("heartbeat lock poisoned");
+        (beat.busy, beat.at.elapsed(), beat.activity.clone())
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct WorkerHealth {
+    pub last_heartbeat_secs: u64,
+    pub last_activity: String,
+    /// Work has been waiting longer than the stall threshold.
+    pub stalled: bool,
+}
+
+/// Checks the saver every few seconds and logs when it stalls and when it recovers.
+pub async fn watch_saver(state: AppState) {
+    if state.saver_stall_after.is_none() {
+        return;
+    }
+    let mut stalled = false;
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(CHECK_INTERVAL) => {}
+            _ = state.shutdown.cancelled() => return,
+        }
+        let health = state.saver_health();
+        match (stalled, health.stalled) {
+            (false, true) => error!(
+                "🚨 OCR cache saver stalled: no save finished in {}s while changes are pending \
+                 (last activity: {}). POST /maintenance/force-save to try a save.",
+                health.last_heartbeat_secs, health.last_activity
+            ),
+            (true, false) => info!("✅ OCR cache saver recovered"),
+            _ => {}
+        }
+        stalled = health.stalled;
+    }
+}
+
+/// Saves the cache from a fresh blocking task, for when the regular saver is stuck. Gives up
+/// waiting after `timeout`, though the attempt itself can't be cancelled.
+pub async fn force_save(state: AppState, timeout: Duration) -> Result<Duration, String> {
+    let started = Instant::now();
+    let task = tokio::task::spawn_blocking(move || state.save_cache());
+    match tokio::time::timeout(timeout, task).await {
+        Ok(Ok(())) => Ok(started.elapsed()),
+        Ok(Err(e)) => Err(format!("Save task failed: {e}")),
+        Err(_) => Err(format!(
+            "Save didn't finish within {}s; the cache is probably locked",
+            timeout.as_secs()
+        )),
+    }
+}
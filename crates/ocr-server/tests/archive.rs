@@ -0,0 +1,140 @@
+mod common;
+
+use std::{
+    cmp::Ordering,
+    fs,
+    io::{Cursor, Write},
+};
+
+use common::ScratchDir;
+use mangatan_ocr_server::{
+    archive::{self, ExtractedArchive},
+    error::ErrorCode,
+};
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+/// A zip of `(name, contents)` entries; names ending in `/` are directories.
+fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+    for (name, contents) in entries {
+        match name.ends_with('/') {
+            true => zip.add_directory(*name, options).expect("add dir"),
+            false => {
+                zip.start_file(*name, options).expect("start file");
+                zip.write_all(contents).expect("write file");
+            }
+        }
+    }
+    zip.finish().expect("finish zip").into_inner()
+}
+
+#[test]
+fn names_sort_by_their_numbers() {
+    let mut names = vec![
+        "p10.jpg",
+        "p2.jpg",
+        "P1.jpg",
+        "p02b.jpg",
+        "cover.jpg",
+        "p002.jpg",
+    ];
+    names.sort_by(|a, b| archive::natural_cmp(a, b));
+    assert_eq!(
+        names,
+        [
+            "cover.jpg",
+            "P1.jpg",
+            "p002.jpg",
+            "p2.jpg",
+            "p02b.jpg",
+            "p10.jpg"
+        ]
+    );
+    assert_eq!(
+        archive::natural_cmp("vol2/p1.png", "vol10/p1.png"),
+        Ordering::Less
+    );
+}
+
+#[test]
+fn only_images_are_unpacked_in_page_order() {
+    let parent = ScratchDir::new("archive-extract");
+    let bytes = zip(&[
+        ("ch1/", b""),
+        ("ch1/page10.jpg", b"ten"),
+        ("ch1/page2.png", b"two"),
+        ("ch1/page1.JPG", b"one"),
+        ("ch1/info.txt", b"not a page"),
+        ("ch1/.hidden.jpg", b"hidden"),
+        ("__MACOSX/ch1/._page1.jpg", b"resource fork"),
+    ]);
+
+    let archive = ExtractedArchive::extract(&bytes, parent.path()).expect("extract");
+    assert_eq!(archive.hash, archive::archive_hash(&bytes));
+    assert!(archive.dir.starts_with(parent.path()));
+    let contents: Vec<Vec<u8>> = archive
+        .pages
+        .iter()
+        .map(|page| fs::read(page).expect("read page"))
+        .collect();
+    assert_eq!(
+        contents,
+        [b"one".to_vec(), b"two".to_vec(), b"ten".to_vec()]
+    );
+
+    let dir = archive.dir.clone();
+    drop(archive);
+    assert!(!dir.exists(), "temp dir is removed on drop");
+}
+
+#[test]
+fn bad_archives_are_refused_without_leaving_files() {
+    let parent = ScratchDir::new("archive-refused");
+    let not_zip = ExtractedArchive::extract(b"not a zip", parent.path()).expect_err("not a zip");
+    assert_eq!(not_zip.code, ErrorCode::BadRequest);
+    let no_images = ExtractedArchive::extract(&zip(&[("readme.txt", b"hi")]), parent.path())
+        .expect_err("no images");
+    assert_eq!(no_images.code, ErrorCode::BadRequest);
+    assert_eq!(fs::read_dir(parent.path()).expect("read dir").count(), 0);
+}
+
+#[tokio::test]
+async fn a_job_over_cached_pages_finishes_and_cleans_up() {
+    let parent = ScratchDir::new("archive-job");
+    let state = common::state("archive-job-cache");
+    let archive = ExtractedArchive::extract(
+        &zip(&[("1.png", b"first"), ("2.png", b"second")]),
+        parent.path(),
+    )
+    .expect("extract");
+    let hash = archive.hash.clone();
+    let dir = archive.dir.clone();
+    {
+        // Already OCR'd by an earlier upload, so Lens is never called
+        let mut cache = state.cache.write().expect("lock");
+        for index in 0..2 {
+            cache.insert(
+                archive::page_cache_key(&hash, index),
+                common::entry("Local", Vec::new()),
+            );
+        }
+    }
+
+    archive::run_archive_job(state.clone(), archive, "Local".to_string()).await;
+
+    let job_id = archive::job_id(&hash);
+    assert_eq!(job_id, format!("/local/{hash}"));
+    let summary = state.job_history.latest_for(&job_id).expect("recorded");
+    assert_eq!(
+        (summary.total, summary.skipped, summary.processed),
+        (2, 2, 0)
+    );
+    assert_eq!(summary.lens_calls, 0);
+    assert!(state.active_chapter_jobs.read().expect("lock").is_empty());
+    assert_eq!(
+        state.chapter_pages_map.read().expect("lock").get(&job_id),
+        Some(&2)
+    );
+    assert!(!dir.exists(), "temp dir is removed after the job");
+}
@@ -0,0 +1,51 @@
+use std::{fmt, time::Duration};
+
+use anyhow::anyhow;
+use axum::{Json, http::StatusCode};
+use mangatan_ocr_server::{
+    error::{ErrorCode, LensFailure},
+    handlers,
+};
+
+/// Stands in for the error type of the Lens client.
+#[derive(Debug)]
+struct LensError(&'static str);
+
+impl fmt::Display for LensError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for LensError {}
+
+#[test]
+fn a_healthy_probe_reports_its_latency() {
+    let Json(body) =
+        handlers::backend_health_response(Ok(Duration::from_millis(240))).expect("healthy");
+    assert_eq!(body["status"], "ok");
+    assert_eq!(body["latency_ms"], 240);
+}
+
+#[test]
+fn a_failed_probe_is_a_backend_failure() {
+    for failure in [
+        anyhow::Error::new(LensFailure::new(LensError("connection reset"))),
+        anyhow!("Failed to create LensClient with proxy: bad URL"),
+    ] {
+        let err = handlers::backend_health_response(Err(failure)).expect_err("unhealthy");
+        assert_eq!(err.code, ErrorCode::OcrBackendFailed, "{err}");
+        assert_eq!(err.code.status(), StatusCode::BAD_GATEWAY);
+        assert!(err.retryable);
+    }
+}
+
+#[test]
+fn a_rate_limited_probe_says_so() {
+    let failure = LensFailure::new(LensError("RESOURCE_EXHAUSTED: quota exceeded"));
+    let err = handlers::backend_health_response(Err(failure.into())).expect_err("rate limited");
+    assert_eq!(err.code, ErrorCode::RateLimited, "{err}");
+    assert_eq!(err.code.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(err.retryable);
+    assert!(err.message.contains("RESOURCE_EXHAUSTED"), "{err}");
+}
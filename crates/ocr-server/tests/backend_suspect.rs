@@ -0,0 +1,165 @@
+mod common;
+
+use axum::{Json, extract::State};
+use common::{block, unplaced};
+use mangatan_ocr_server::{
+    backend_watch::{BackendWatch, CanaryPage, DEFAULT_EMPTY_STREAK, Verdict},
+    handlers,
+    logic::{OcrResult, PageResults},
+};
+use reqwest::header::HeaderMap;
+
+fn page(results: Vec<OcrResult>, looks_blank: bool) -> PageResults {
+    PageResults {
+        results,
+        truncated_from: None,
+        incomplete: false,
+        looks_blank,
+        merge_quality: None,
+    }
+}
+
+fn canary() -> CanaryPage {
+    CanaryPage {
+        url: "http://example.com/page/3".to_string(),
+        user: None,
+        pass: None,
+        fetch_headers: HeaderMap::new(),
+    }
+}
+
+#[test]
+fn trips_after_a_streak_of_empty_pages() {
+    let watch = BackendWatch::new(Some(3));
+    let empty = page(Vec::new(), false);
+
+    assert_eq!(watch.record("p1", &empty, canary), Verdict::Cache);
+    assert_eq!(watch.record("p2", &empty, canary), Verdict::Cache);
+    assert!(!watch.is_suspect());
+    assert!(watch.canary().is_none());
+
+    assert_eq!(watch.record("p3", &empty, canary), Verdict::Tripped);
+    assert!(watch.is_suspect());
+    assert_eq!(watch.trips(), 1);
+    assert_eq!(
+        watch.canary().map(|page| page.url),
+        Some(canary().url),
+        "the tripping page becomes the canary"
+    );
+    // Until Lens finds text again, empty results stay out of the cache
+    assert_eq!(watch.record("p4", &empty, canary), Verdict::Skip);
+
+    let mut cached = watch.take_suspect_keys().into_iter().collect::<Vec<_>>();
+    cached.sort();
+    assert_eq!(cached, ["p1", "p2"]);
+}
+
+#[test]
+fn lines_without_geometry_dont_end_the_streak() {
+    let watch = BackendWatch::new(Some(2));
+    let unplaced_only = page(vec![unplaced("こんにちは")], false);
+
+    assert_eq!(watch.record("p1", &unplaced_only, canary), Verdict::Cache);
+    assert_eq!(watch.record("p2", &unplaced_only, canary), Verdict::Tripped);
+    assert_eq!(
+        watch.record(
+            "p3",
+            &page(vec![block("こんにちは"), unplaced("さようなら")], false),
+            canary
+        ),
+        Verdict::Cache
+    );
+    assert!(!watch.is_suspect());
+}
+
+#[test]
+fn pages_with_text_end_the_streak() {
+    let watch = BackendWatch::new(Some(2));
+    let empty = page(Vec::new(), false);
+
+    assert_eq!(watch.record("p1", &empty, canary), Verdict::Cache);
+    assert_eq!(
+        watch.record("p2", &page(vec![block("こんにちは")], false), canary),
+        Verdict::Cache
+    );
+    assert_eq!(watch.record("p3", &empty, canary), Verdict::Cache);
+    assert!(!watch.is_suspect());
+
+    assert_eq!(watch.record("p4", &empty, canary), Verdict::Tripped);
+    assert_eq!(
+        watch.record("p5", &page(vec![block("こんにちは")], false), canary),
+        Verdict::Cache
+    );
+    assert!(!watch.is_suspect());
+    assert!(watch.canary().is_none());
+    assert_eq!(watch.record("p6", &empty, canary), Verdict::Cache);
+}
+
+#[test]
+fn blank_pages_do_not_count() {
+    let watch = BackendWatch::new(Some(2));
+    for key in ["p1", "p2", "p3"] {
+        assert_eq!(
+            watch.record(key, &page(Vec::new(), true), canary),
+            Verdict::Cache
+        );
+    }
+    assert!(!watch.is_suspect());
+    assert!(watch.take_suspect_keys().is_empty());
+}
+
+#[test]
+fn disabled_watch_caches_everything() {
+    let watch = BackendWatch::new(None);
+    for key in ["p1", "p2", "p3", "p4", "p5", "p6"] {
+        assert_eq!(
+            watch.record(key, &page(Vec::new(), false), canary),
+            Verdict::Cache
+        );
+    }
+    assert!(!watch.is_suspect());
+}
+
+#[tokio::test]
+async fn purge_drops_the_empty_results_cached_during_the_streak() {
+    let state = common::state("backend-suspect");
+
+    let entry = |data| common::entry("Series", data);
+    let empty = page(Vec::new(), false);
+    let streak: Vec<String> = (1..DEFAULT_EMPTY_STREAK).map(|n| format!("p{n}")).collect();
+    {
+        let mut cache = state.cache.write().expect("lock");
+        cache.insert("before".to_string(), entry(Vec::new()));
+        for key in &streak {
+            assert_eq!(
+                state.backend_watch.record(key, &empty, canary),
+                Verdict::Cache
+            );
+            cache.insert(key.clone(), entry(Vec::new()));
+        }
+    }
+    assert_eq!(
+        state.backend_watch.record("last", &empty, canary),
+        Verdict::Tripped
+    );
+    // p1 was OCR'd again after Lens recovered
+    state
+        .cache
+        .write()
+        .expect("lock")
+        .insert("p1".to_string(), entry(vec![block("こんにちは")]));
+
+    let Json(status) = handlers::status_handler(State(state.clone())).await;
+    assert_eq!(status["backend_suspect"], true);
+
+    let Json(purged) = handlers::purge_suspect_empty_handler(State(state.clone())).await;
+    assert_eq!(purged["removed"], streak.len() - 1);
+
+    let cache = state.cache.read().expect("lock");
+    assert!(!cache.contains_key("p2"));
+    assert!(cache.contains_key("p1"), "re-OCR'd entries are kept");
+    assert!(
+        cache.contains_key("before"),
+        "empty results from before the streak are kept"
+    );
+}
@@ -0,0 +1,97 @@
+use mangatan_ocr_server::{
+    logic::{self, BoundingBox, OcrResult, RawChunk},
+    merge::{self, MergeConfig},
+};
+
+fn line(text: &str, x: f64, y: f64, width: f64, height: f64) -> OcrResult {
+    OcrResult {
+        text: text.to_string(),
+        tight_bounding_box: BoundingBox {
+            x,
+            y,
+            width,
+            height,
+            rotation: None,
+        },
+        is_merged: None,
+        forced_orientation: None,
+        orientation: None,
+        no_geometry: None,
+    }
+}
+
+/// Two touching columns covering x 100..180 and y 100..300.
+fn bubble(x: f64) -> Vec<OcrResult> {
+    vec![
+        line("右の列です", x + 40.0, 100.0, 40.0, 200.0),
+        line("左の列です", x, 100.0, 40.0, 200.0),
+    ]
+}
+
+fn padded(padding: f64) -> MergeConfig {
+    MergeConfig {
+        box_padding: padding,
+        ..MergeConfig::default()
+    }
+}
+
+fn merged_box(lines: Vec<OcrResult>, config: &MergeConfig) -> BoundingBox {
+    let results = merge::auto_merge(lines, 1000, 1000, config);
+    assert_eq!(results.len(), 1, "{results:#?}");
+    results[0].tight_bounding_box.clone()
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+}
+
+#[test]
+fn no_padding_by_default() {
+    let bbox = merged_box(bubble(100.0), &MergeConfig::default());
+    assert_close(bbox.x, 100.0);
+    assert_close(bbox.y, 100.0);
+    assert_close(bbox.width, 80.0);
+    assert_close(bbox.height, 200.0);
+}
+
+#[test]
+fn padding_grows_each_side_by_a_share_of_the_box() {
+    let bbox = merged_box(bubble(100.0), &padded(0.1));
+    assert_close(bbox.x, 92.0);
+    assert_close(bbox.y, 80.0);
+    assert_close(bbox.width, 96.0);
+    assert_close(bbox.height, 240.0);
+}
+
+#[test]
+fn single_lines_are_padded_too() {
+    let results = merge::auto_merge(
+        vec![line("一行だけです", 500.0, 100.0, 40.0, 240.0)],
+        1000,
+        1000,
+        &padded(0.25),
+    );
+    let bbox = &results[0].tight_bounding_box;
+    assert_close(bbox.x, 490.0);
+    assert_close(bbox.height, 360.0);
+}
+
+#[test]
+fn padded_boxes_stay_on_the_page() {
+    let chunk = RawChunk {
+        lines: bubble(2.0),
+        no_geometry_lines: Vec::new(),
+        width: 1000,
+        height: 1000,
+        global_y: 0,
+        full_width: 1000,
+        full_height: 1000,
+    };
+    let results = logic::merge_raw_chunks(vec![chunk], &padded(0.5));
+    let bbox = &results[0].tight_bounding_box;
+    assert_close(bbox.x, 0.0);
+    assert!(bbox.x + bbox.width <= 1.0);
+    assert!(bbox.y >= 0.0 && bbox.y + bbox.height <= 1.0);
+    // Only the side against the edge is cut short
+    assert_close(bbox.x + bbox.width, 0.122);
+}
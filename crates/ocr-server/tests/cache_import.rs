@@ -0,0 +1,122 @@
+mod common;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use common::ScratchDir;
+use mangatan_ocr_server::{
+    handlers::{self, ImportCacheRequest},
+    state::AppState,
+};
+use serde_json::{Value, json};
+
+fn line(text: &str, x: Value, y: f64, width: f64, height: f64) -> Value {
+    json!({
+        "text": text,
+        "tightBoundingBox": { "x": x, "y": y, "width": width, "height": height },
+    })
+}
+
+fn entry(lines: Vec<Value>) -> Value {
+    json!({ "context": "Series", "data": lines })
+}
+
+/// What an export from an older or hand-edited build can contain.
+fn mixed_export() -> Value {
+    json!({
+        "good": entry(vec![line("一行目", json!(0.1), 0.1, 0.05, 0.3)]),
+        "blank-page": entry(Vec::new()),
+        // Measured from the right edge, sticking out past the page, plus an empty line
+        "repairable": entry(vec![
+            line("二行目", json!(0.9), 0.1, -0.1, 1.6),
+            line("", json!(0.2), 0.2, 0.1, 0.1),
+        ]),
+        "no-context": { "data": [] },
+        // NaN coordinates end up as null in JSON
+        "nan": entry(vec![line("三行目", Value::Null, 0.1, 0.1, 0.1)]),
+        "off-page": entry(vec![line("四行目", json!(3.0), 0.1, 0.1, 0.1)]),
+    })
+}
+
+fn quarantined_keys(cache_dir: &std::path::Path) -> Vec<String> {
+    let bytes = std::fs::read(cache_dir.join("ocr-quarantine.json")).expect("quarantine file");
+    let quarantined: serde_json::Map<String, Value> =
+        serde_json::from_slice(&bytes).expect("quarantine json");
+    for (key, entry) in &quarantined {
+        assert!(entry["reason"].is_string(), "{key}: {entry}");
+        assert!(entry["entry"].is_object(), "{key}: {entry}");
+    }
+    let mut keys: Vec<String> = quarantined.keys().cloned().collect();
+    keys.sort();
+    keys
+}
+
+#[tokio::test]
+async fn import_repairs_what_it_can_and_counts_the_rest() {
+    let state = common::state("cache-import");
+    let data = serde_json::from_value(mixed_export()).expect("map");
+
+    let Json(body) = handlers::import_cache_handler(
+        State(state.clone()),
+        Query(ImportCacheRequest { quarantine: true }),
+        Json(data),
+    )
+    .await;
+    assert_eq!(body["added"], 3, "{body}");
+    assert_eq!(body["repaired"], 1, "{body}");
+    assert_eq!(body["rejected"], 3, "{body}");
+    assert_eq!(body["quarantined"], true, "{body}");
+
+    let cache = state.cache.read().expect("lock");
+    let repaired = &cache["repairable"].data;
+    assert_eq!(repaired.len(), 1, "the empty line is dropped");
+    let bbox = &repaired[0].tight_bounding_box;
+    assert!((bbox.x - 0.8).abs() < 1e-9, "{bbox:?}");
+    assert!((bbox.width - 0.1).abs() < 1e-9, "{bbox:?}");
+    assert!((bbox.y + bbox.height - 1.5).abs() < 1e-9, "{bbox:?}");
+    assert!(cache["blank-page"].data.is_empty());
+    assert!(!cache.contains_key("nan"));
+
+    assert_eq!(
+        quarantined_keys(state.dir.path()),
+        ["nan", "no-context", "off-page"]
+    );
+}
+
+#[tokio::test]
+async fn import_only_quarantines_when_asked() {
+    let state = common::state("cache-import-no-quarantine");
+    let data = serde_json::from_value(mixed_export()).expect("map");
+
+    let Json(body) = handlers::import_cache_handler(
+        State(state.clone()),
+        Query(ImportCacheRequest { quarantine: false }),
+        Json(data),
+    )
+    .await;
+    assert_eq!(body["rejected"], 3, "{body}");
+    assert_eq!(body["quarantined"], false, "{body}");
+    assert!(!state.dir.path().join("ocr-quarantine.json").exists());
+}
+
+#[test]
+fn loading_an_old_cache_file_keeps_the_usable_entries() {
+    let cache_dir = ScratchDir::new("cache-load-sanitize");
+    let file = json!({ "cache": mixed_export(), "chapter_pages_map": { "chapter": 3 } });
+    std::fs::write(cache_dir.path().join("ocr-cache.json"), file.to_string()).expect("write cache");
+
+    let state = AppState::new(cache_dir.path().to_path_buf());
+    let mut keys: Vec<String> = state.cache.read().expect("lock").keys().cloned().collect();
+    keys.sort();
+    assert_eq!(keys, ["blank-page", "good", "repairable"]);
+    assert_eq!(state.chapter_pages_map.read().expect("lock")["chapter"], 3);
+    assert!(
+        state.has_unsaved_changes(),
+        "the repaired cache gets written back"
+    );
+    assert_eq!(
+        quarantined_keys(cache_dir.path()),
+        ["nan", "no-context", "off-page"]
+    );
+}
@@ -0,0 +1,78 @@
+use mangatan_ocr_server::{cache_key::CacheKeyRules, logic};
+
+const QUERY_PAGED: &str = "https://reader.example.org/chapter/12/view";
+
+fn pages(base: &str) -> Vec<String> {
+    (0..3).map(|i| format!("{base}?page={i}&t=9")).collect()
+}
+
+#[test]
+fn default_keys_are_the_path() {
+    let rules = CacheKeyRules::default();
+    assert_eq!(
+        rules.key_for("http://localhost:4568/api/v1/manga/1/chapter/2/page/3?updated=1"),
+        "/api/v1/manga/1/chapter/2/page/3"
+    );
+    // Matches what older caches were written with
+    assert_eq!(
+        logic::get_cache_key("http://127.0.0.1:4567/api/v1/manga/1/chapter/2/page/3"),
+        "/api/v1/manga/1/chapter/2/page/3"
+    );
+}
+
+#[test]
+fn listed_params_are_kept_for_matching_hosts() {
+    let rules = CacheKeyRules::parse("*.example.org=page,chapter; other.net=p");
+
+    assert_eq!(
+        rules.key_for(&format!("{QUERY_PAGED}?t=9&page=3&chapter=12")),
+        "/chapter/12/view?chapter=12&page=3"
+    );
+    // Hosts without a rule, and URLs without the params, keep the path-only key
+    assert_eq!(
+        rules.key_for("https://cdn.elsewhere.com/chapter/12/view?page=3"),
+        "/chapter/12/view"
+    );
+    assert_eq!(rules.key_for(QUERY_PAGED), "/chapter/12/view");
+}
+
+#[test]
+fn collisions_are_counted_per_shared_key() {
+    let urls = pages(QUERY_PAGED);
+    assert_eq!(CacheKeyRules::default().count_collisions(&urls), 3);
+    assert_eq!(
+        CacheKeyRules::parse("reader.example.org=page").count_collisions(&urls),
+        0
+    );
+}
+
+#[test]
+fn data_urls_are_keyed_by_their_content() {
+    let png = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+    let url = "data:image/png;base64,iVBORw0KGgo=";
+
+    assert_eq!(
+        logic::data_url_bytes(url)
+            .expect("data URL")
+            .expect("decodes"),
+        png
+    );
+    // Shared with an upload of the same image
+    assert_eq!(logic::get_cache_key(url), logic::upload_cache_key(&png));
+    // `+` turned into a space by query decoding still decodes
+    assert_eq!(
+        logic::data_url_bytes("data:image/png;base64,+/8=")
+            .expect("data URL")
+            .expect("decodes"),
+        logic::data_url_bytes("data:image/png;base64, /8=")
+            .expect("data URL")
+            .expect("decodes")
+    );
+
+    assert!(
+        logic::data_url_bytes("data:text/plain,hello")
+            .expect("data URL")
+            .is_err()
+    );
+    assert!(logic::data_url_bytes(QUERY_PAGED).is_none());
+}
@@ -0,0 +1,97 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+mod common;
+
+use axum::{Json, Router, extract::State, routing::get};
+use common::{block, unplaced};
+use mangatan_ocr_server::{handlers, logic};
+
+/// Serves page images and counts how often anything asks for one.
+async fn counting_image_server() -> (String, Arc<AtomicUsize>) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let app = Router::new().route(
+        "/page/{n}",
+        get(move || async move {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Vec::<u8>::new()
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind should succeed");
+    let addr = listener
+        .local_addr()
+        .expect("listener should have an address");
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    (format!("http://{addr}/page"), hits)
+}
+
+#[tokio::test]
+async fn reports_cache_entries_without_running_ocr() {
+    let state = common::state("cached-status");
+    let (base, hits) = counting_image_server().await;
+
+    let urls: Vec<String> = (0..2000).map(|n| format!("{base}/{n}")).collect();
+    let cached_key = logic::get_cache_key(&urls[1]);
+    state.cache.write().expect("lock").insert(
+        cached_key.clone(),
+        common::entry("test", vec![block("一"), block("二")]),
+    );
+    state.set_pinned(&cached_key, true);
+
+    let Json(statuses) = handlers::cached_status_handler(State(state.clone()), Json(urls.clone()))
+        .await
+        .unwrap_or_else(|_| panic!("status request should succeed"));
+
+    assert_eq!(statuses.len(), urls.len());
+    assert!(statuses.iter().zip(&urls).all(|(s, url)| &s.url == url));
+    assert!(statuses[1].cached && statuses[1].pinned);
+    assert_eq!(statuses[1].block_count, Some(2));
+    assert_eq!(statuses.iter().filter(|s| s.cached).count(), 1);
+
+    // Nothing was fetched, so nothing reached Lens, and no entries appeared
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+    assert_eq!(state.cache.read().expect("lock").len(), 1);
+    assert_eq!(state.requests_processed.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn block_count_leaves_out_lines_without_geometry() {
+    let state = common::state("cached-status-unplaced");
+    let urls = vec![
+        "http://localhost/page/0".to_string(),
+        "http://localhost/page/1".to_string(),
+    ];
+    {
+        let mut cache = state.cache.write().expect("lock");
+        cache.insert(
+            logic::get_cache_key(&urls[0]),
+            common::entry("test", vec![block("一"), unplaced("二")]),
+        );
+        cache.insert(
+            logic::get_cache_key(&urls[1]),
+            common::entry("test", vec![unplaced("三")]),
+        );
+    }
+
+    let Json(statuses) = handlers::cached_status_handler(State(state.clone()), Json(urls))
+        .await
+        .unwrap_or_else(|_| panic!("status request should succeed"));
+    assert_eq!(statuses[0].block_count, Some(1));
+    assert_eq!(statuses[1].block_count, Some(0));
+}
+
+#[tokio::test]
+async fn rejects_oversized_batches() {
+    let state = common::state("cached-status-big");
+    let urls = vec!["http://localhost/page".to_string(); handlers::MAX_CACHED_STATUS_URLS + 1];
+    assert!(
+        handlers::cached_status_handler(State(state.clone()), Json(urls))
+            .await
+            .is_err()
+    );
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use mangatan_ocr_server::{
+    export::{self, ExportFormat},
+    logic::{BoundingBox, OcrResult},
+    state::CacheEntry,
+};
+use serde_json::Value;
+
+const CHAPTER: &str = "/api/v1/manga/1/chapter/2/page/";
+
+fn block(text: &str, x: f64, y: f64, vertical: bool) -> OcrResult {
+    let (width, height) = if vertical { (0.05, 0.3) } else { (0.3, 0.05) };
+    OcrResult {
+        text: text.to_string(),
+        tight_bounding_box: BoundingBox {
+            x,
+            y,
+            width,
+            height,
+            rotation: None,
+        },
+        is_merged: Some(true),
+        forced_orientation: Some(if vertical { "vertical" } else { "horizontal" }.to_string()),
+        orientation: None,
+        no_geometry: None,
+    }
+}
+
+fn entry(data: Vec<OcrResult>) -> CacheEntry {
+    CacheEntry {
+        context: "Test".to_string(),
+        data,
+        truncated_from: None,
+        merge_quality: None,
+    }
+}
+
+/// Two pages inserted out of order, plus a page of another chapter and a line Lens couldn't
+/// place, both of which must be left out.
+fn cache() -> HashMap<String, CacheEntry> {
+    let unplaced = OcrResult {
+        no_geometry: Some(true),
+        ..block("どこか", 0.0, 0.0, false)
+    };
+    HashMap::from([
+        (
+            format!("{CHAPTER}10"),
+            entry(vec![block("最後", 0.5, 0.5, true), unplaced]),
+        ),
+        (
+            format!("{CHAPTER}2"),
+            // Stored left column first; vertical pages read right to left
+            entry(vec![
+                block("二番目", 0.2, 0.1, true),
+                block("一番目", 0.7, 0.1, true),
+            ]),
+        ),
+        (
+            "/api/v1/manga/1/chapter/3/page/0".to_string(),
+            entry(vec![block("別の章", 0.1, 0.1, false)]),
+        ),
+    ])
+}
+
+#[test]
+fn cues_follow_page_then_reading_order() {
+    let cues = export::chapter_cues(&cache(), CHAPTER);
+    let order: Vec<_> = cues
+        .iter()
+        .map(|c| (c.page_index, c.reading_index, c.text.as_str()))
+        .collect();
+    assert_eq!(order, [(2, 0, "一番目"), (2, 1, "二番目"), (10, 0, "最後")]);
+    assert!(cues.iter().all(|c| c.orientation == "vertical"));
+}
+
+#[test]
+fn jsonl_has_one_object_per_block() {
+    let cues = export::chapter_cues(&cache(), CHAPTER);
+    let output = export::render(&cues, ExportFormat::Jsonl);
+    let lines: Vec<Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("each line should be JSON"))
+        .collect();
+
+    assert_eq!(lines.len(), 3);
+    for key in ["page_index", "reading_index", "text", "box", "orientation"] {
+        assert!(lines[0].get(key).is_some(), "missing {key}: {}", lines[0]);
+    }
+    assert_eq!(lines[0]["text"], "一番目");
+    assert_eq!(lines[0]["box"]["x"], 0.7);
+}
+
+#[test]
+fn subtitle_formats_number_blocks_sequentially() {
+    let cues = export::chapter_cues(&cache(), CHAPTER);
+
+    let srt = export::render(&cues, ExportFormat::Srt);
+    assert!(
+        srt.starts_with("1\n00:00:00,000 --> 00:00:03,000\n一番目\n\n2\n"),
+        "{srt}"
+    );
+    assert!(
+        srt.contains("3\n00:00:06,000 --> 00:00:09,000\n最後\n"),
+        "{srt}"
+    );
+
+    let ass = export::render(&cues, ExportFormat::Ass);
+    let dialogue: Vec<_> = ass
+        .lines()
+        .filter(|line| line.starts_with("Dialogue:"))
+        .collect();
+    assert_eq!(dialogue.len(), 3);
+    assert_eq!(
+        dialogue[2],
+        "Dialogue: 0,0:00:06.00,0:00:09.00,Default,p10#0,最後"
+    );
+}
@@ -0,0 +1,119 @@
+//! Scratch directories, states and cache entries shared by the integration tests.
+//!
+//! Everything a test writes lives under a [`ScratchDir`], which is removed again when the test
+//! drops it, so repeated runs don't pile up directories in the system temp dir.
+
+// Each test crate uses a different part of the kit
+#![allow(dead_code)]
+
+use std::{
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+};
+
+use mangatan_ocr_server::{
+    logic::{BoundingBox, OcrResult},
+    state::{AppState, CacheEntry},
+};
+
+/// An empty directory under the system temp dir, removed on drop.
+pub struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    /// `name` has to be unique across the test crates, which run side by side.
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("mangatan-{name}-{}", std::process::id()));
+        // Left over from a run that was killed before it could clean up
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("create scratch dir");
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// An [`AppState`] caching into its own [`ScratchDir`]; derefs to the state.
+pub struct TestState {
+    // Dropped before the dir it points into
+    state: AppState,
+    pub dir: ScratchDir,
+}
+
+impl TestState {
+    /// A second state over the same dir, as after a restart.
+    pub fn reload(&self) -> AppState {
+        AppState::new(self.dir.path().to_path_buf())
+    }
+}
+
+impl Deref for TestState {
+    type Target = AppState;
+
+    fn deref(&self) -> &AppState {
+        &self.state
+    }
+}
+
+impl DerefMut for TestState {
+    fn deref_mut(&mut self) -> &mut AppState {
+        &mut self.state
+    }
+}
+
+pub fn state(name: &str) -> TestState {
+    let dir = ScratchDir::new(name);
+    TestState {
+        state: AppState::new(dir.path().to_path_buf()),
+        dir,
+    }
+}
+
+/// A horizontal line of `text` in the top left of the page.
+pub fn block(text: &str) -> OcrResult {
+    OcrResult {
+        text: text.to_string(),
+        tight_bounding_box: BoundingBox {
+            x: 0.1,
+            y: 0.1,
+            width: 0.2,
+            height: 0.05,
+            rotation: None,
+        },
+        is_merged: None,
+        forced_orientation: None,
+        orientation: None,
+        no_geometry: None,
+    }
+}
+
+/// A line Lens recognized but couldn't locate, as the pipeline keeps it.
+pub fn unplaced(text: &str) -> OcrResult {
+    OcrResult {
+        tight_bounding_box: BoundingBox {
+            x: 0.0,
+            y: 0.0,
+            width: 0.0,
+            height: 0.0,
+            rotation: None,
+        },
+        no_geometry: Some(true),
+        ..block(text)
+    }
+}
+
+pub fn entry(context: &str, data: Vec<OcrResult>) -> CacheEntry {
+    CacheEntry {
+        context: context.to_string(),
+        data,
+        truncated_from: None,
+        merge_quality: None,
+    }
+}
@@ -0,0 +1,51 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use mangatan_ocr_server::{
+    convert::{self, ConvertFormat},
+    error::{ApiError, ErrorCode},
+};
+
+fn png_page() -> Vec<u8> {
+    let image = RgbaImage::from_fn(32, 48, |x, y| {
+        image::Rgba([(x * 8) as u8, (y * 5) as u8, 200, 255])
+    });
+    let mut out = Cursor::new(Vec::new());
+    DynamicImage::ImageRgba8(image)
+        .write_to(&mut out, ImageFormat::Png)
+        .expect("png encode");
+    out.into_inner()
+}
+
+#[test]
+fn pages_are_reencoded_to_the_requested_format() {
+    let png = png_page();
+
+    for format in [ConvertFormat::Webp, ConvertFormat::Jpeg] {
+        let converted = convert::convert_image(&png, format, 80).expect("convert");
+        assert!(convert::is_already(&converted, format), "{format:?}");
+
+        let decoded = image::load_from_memory(&converted).expect("decodes");
+        assert_eq!((decoded.width(), decoded.height()), (32, 48));
+    }
+    assert!(!convert::is_already(&png, ConvertFormat::Jpeg));
+}
+
+#[test]
+fn cache_keys_differ_per_format_and_quality() {
+    let keys = [
+        convert::cache_key("/page/1", ConvertFormat::Webp, 85),
+        convert::cache_key("/page/1", ConvertFormat::Jpeg, 85),
+        convert::cache_key("/page/1", ConvertFormat::Jpeg, 60),
+    ];
+    assert!(keys.iter().all(|key| key != "/page/1"));
+    assert_ne!(keys[0], keys[1]);
+    assert_ne!(keys[1], keys[2]);
+}
+
+#[test]
+fn undecodable_input_is_a_decode_failure() {
+    let err = convert::convert_image(b"not an image", ConvertFormat::Jpeg, 85)
+        .expect_err("garbage shouldn't decode");
+    assert_eq!(ApiError::classify(&err), ErrorCode::DecodeFailed);
+}
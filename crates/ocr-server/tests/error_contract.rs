@@ -0,0 +1,247 @@
+use std::fmt;
+
+use anyhow::anyhow;
+use axum::{
+    Router,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use mangatan_ocr_server::error::{ApiError, ErrorCode, LensFailure};
+use serde_json::Value;
+
+/// Splits a response into its status and parsed JSON body.
+async fn read_response(response: Response) -> (StatusCode, Value) {
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body should be readable");
+    let body = serde_json::from_slice(&bytes).expect("body should be JSON");
+    (status, body)
+}
+
+fn assert_envelope(body: &Value, code: &str, retryable: bool) {
+    let object = body.as_object().expect("error body should be an object");
+    let mut keys: Vec<_> = object.keys().map(String::as_str).collect();
+    keys.sort_unstable();
+    assert_eq!(keys, ["code", "message", "retryable"], "body: {body}");
+    assert_eq!(body["code"], code, "body: {body}");
+    assert_eq!(body["retryable"], retryable, "body: {body}");
+    assert!(body["message"].is_string(), "body: {body}");
+}
+
+/// Performs a GET against a throwaway server that always answers with `status`, and returns
+/// the failure the same way `logic::fetch_image_bytes` reports it.
+async fn upstream_failure(status: StatusCode) -> anyhow::Error {
+    let (url, err) = status_error(status).await;
+    anyhow::Error::new(err).context(format!("Failed error_for_status (URL: {url})"))
+}
+
+/// The `reqwest::Error` a GET answered with `status` fails with, and the URL it went to.
+async fn status_error(status: StatusCode) -> (String, reqwest::Error) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind should succeed");
+    let addr = listener
+        .local_addr()
+        .expect("listener should have an address");
+    let app = Router::new().route("/page", get(move || async move { status }));
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let url = format!("http://{addr}/page");
+    let err = reqwest::get(&url)
+        .await
+        .expect("request should reach the test server")
+        .error_for_status()
+        .expect_err("status should be an error");
+    (url, err)
+}
+
+/// Stands in for the error type of the Lens client.
+#[derive(Debug)]
+struct LensError(&'static str);
+
+impl fmt::Display for LensError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for LensError {}
+
+#[tokio::test]
+async fn envelope_shape_and_status_per_code() {
+    let cases = [
+        (
+            ErrorCode::UpstreamNotFound,
+            StatusCode::NOT_FOUND,
+            "upstream_not_found",
+            false,
+        ),
+        (
+            ErrorCode::UpstreamUnauthorized,
+            StatusCode::UNAUTHORIZED,
+            "upstream_unauthorized",
+            false,
+        ),
+        (
+            ErrorCode::DecodeFailed,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "decode_failed",
+            false,
+        ),
+        (
+            ErrorCode::OcrBackendFailed,
+            StatusCode::BAD_GATEWAY,
+            "ocr_backend_failed",
+            true,
+        ),
+        (
+            ErrorCode::RateLimited,
+            StatusCode::TOO_MANY_REQUESTS,
+            "rate_limited",
+            true,
+        ),
+        (
+            ErrorCode::BadRequest,
+            StatusCode::BAD_REQUEST,
+            "bad_request",
+            false,
+        ),
+        (
+            ErrorCode::NotFound,
+            StatusCode::NOT_FOUND,
+            "not_found",
+            false,
+        ),
+        (
+            ErrorCode::Unavailable,
+            StatusCode::SERVICE_UNAVAILABLE,
+            "unavailable",
+            true,
+        ),
+        (
+            ErrorCode::Internal,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal",
+            false,
+        ),
+    ];
+
+    for (code, expected_status, expected_code, retryable) in cases {
+        let (status, body) = read_response(ApiError::new(code, "boom").into_response()).await;
+        assert_eq!(status, expected_status, "code: {code:?}");
+        assert_envelope(&body, expected_code, retryable);
+        assert_eq!(body["message"], "boom");
+    }
+}
+
+#[tokio::test]
+async fn upstream_http_failures_are_classified() {
+    let cases = [
+        (StatusCode::NOT_FOUND, "upstream_not_found"),
+        (StatusCode::UNAUTHORIZED, "upstream_unauthorized"),
+        (StatusCode::FORBIDDEN, "upstream_unauthorized"),
+        (StatusCode::TOO_MANY_REQUESTS, "rate_limited"),
+        (StatusCode::INTERNAL_SERVER_ERROR, "internal"),
+    ];
+
+    for (upstream_status, expected_code) in cases {
+        let err = ApiError::from(upstream_failure(upstream_status).await);
+        let (_, body) = read_response(err.into_response()).await;
+        assert_envelope(&body, expected_code, expected_code == "rate_limited");
+    }
+}
+
+#[tokio::test]
+async fn pipeline_failures_are_classified() {
+    let cases = [
+        (
+            anyhow!("Failed decode: Format error"),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "decode_failed",
+        ),
+        (
+            anyhow!("avif-decode failed to parse: Unsupported"),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "decode_failed",
+        ),
+        (
+            anyhow!("Failed process_image_bytes: timed out"),
+            StatusCode::BAD_GATEWAY,
+            "ocr_backend_failed",
+        ),
+        // A page id in the URL isn't a status code
+        (
+            anyhow!(
+                "Failed to fetch http://host/api/v1/manga/429/chapter/1/page/0: connection reset"
+            ),
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal",
+        ),
+        (
+            anyhow!("Failed write_to: out of memory"),
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal",
+        ),
+    ];
+
+    for (err, expected_status, expected_code) in cases {
+        let retryable = matches!(expected_code, "ocr_backend_failed" | "rate_limited");
+        let (status, body) = read_response(ApiError::from(err).into_response()).await;
+        assert_eq!(status, expected_status, "body: {body}");
+        assert_envelope(&body, expected_code, retryable);
+    }
+}
+
+#[tokio::test]
+async fn lens_failures_are_classified() {
+    let cases = [
+        (
+            LensFailure::new(LensError("RESOURCE_EXHAUSTED: quota exceeded")),
+            "rate_limited",
+        ),
+        (
+            LensFailure::new(LensError("timed out")),
+            "ocr_backend_failed",
+        ),
+        (
+            LensFailure::new(status_error(StatusCode::TOO_MANY_REQUESTS).await.1),
+            "rate_limited",
+        ),
+        // Lens being down isn't the page missing, or an internal error
+        (
+            LensFailure::new(status_error(StatusCode::NOT_FOUND).await.1),
+            "ocr_backend_failed",
+        ),
+        (
+            LensFailure::new(status_error(StatusCode::INTERNAL_SERVER_ERROR).await.1),
+            "ocr_backend_failed",
+        ),
+    ];
+
+    for (failure, expected_code) in cases {
+        // As the pipeline reports it, under the page it was working on
+        let err = anyhow::Error::new(failure)
+            .context("Failed to OCR http://host/api/v1/manga/429/chapter/1/page/0");
+        let err = ApiError::from(err);
+        assert!(
+            err.message.contains("Failed process_image_bytes: "),
+            "{}",
+            err.message
+        );
+        let (_, body) = read_response(err.into_response()).await;
+        assert_envelope(&body, expected_code, true);
+    }
+}
+
+#[tokio::test]
+async fn context_is_kept_in_message() {
+    let err = ApiError::from(upstream_failure(StatusCode::NOT_FOUND).await);
+    assert!(
+        err.message.starts_with("Failed error_for_status"),
+        "{}",
+        err.message
+    );
+    assert!(err.message.contains("404"), "{}", err.message);
+}
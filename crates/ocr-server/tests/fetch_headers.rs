@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::Query,
+    http::{HeaderMap, HeaderValue, Uri, header},
+};
+use mangatan_ocr_server::{handlers::OcrRequest, logic};
+
+#[test]
+fn query_headers_are_validated_for_the_image_fetch() {
+    let uri: Uri =
+        "/ocr?url=/page/1&headers=%7B%22Referer%22%3A%22https%3A%2F%2Fexample.org%2F%22%7D"
+            .parse()
+            .expect("uri");
+    let Query(params) = Query::<OcrRequest>::try_from_uri(&uri).expect("query");
+    let headers = logic::image_fetch_headers(&params.headers).expect("valid headers");
+    assert_eq!(
+        headers.get("referer").and_then(|v| v.to_str().ok()),
+        Some("https://example.org/")
+    );
+
+    // Without the param there's nothing extra to send
+    let uri: Uri = "/ocr?url=/page/1".parse().expect("uri");
+    let Query(params) = Query::<OcrRequest>::try_from_uri(&uri).expect("query");
+    assert!(params.headers.is_empty());
+
+    for (name, value) in [
+        ("Host", "example.org"),
+        ("bad name", "x"),
+        ("Cookie", "a\nb"),
+    ] {
+        let raw = HashMap::from([(name.to_string(), value.to_string())]);
+        assert!(logic::image_fetch_headers(&raw).is_err(), "{name}");
+    }
+}
+
+#[test]
+fn suwayomi_credentials_come_from_the_token_or_the_incoming_request() {
+    let incoming = HeaderMap::from_iter([(
+        header::AUTHORIZATION,
+        HeaderValue::from_static("Bearer from-webui"),
+    )]);
+    let auth = |headers: &HeaderMap| {
+        headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+
+    let headers =
+        logic::with_suwayomi_auth(HeaderMap::new(), Some("abc"), &incoming).expect("valid token");
+    assert_eq!(auth(&headers).as_deref(), Some("Bearer abc"));
+
+    let headers =
+        logic::with_suwayomi_auth(HeaderMap::new(), None, &incoming).expect("forwarded header");
+    assert_eq!(auth(&headers).as_deref(), Some("Bearer from-webui"));
+
+    // An explicit header from `headers=` is left alone
+    let raw = HashMap::from([("Authorization".to_string(), "Basic eDp5".to_string())]);
+    let explicit = logic::image_fetch_headers(&raw).expect("valid headers");
+    let headers =
+        logic::with_suwayomi_auth(explicit, Some("abc"), &incoming).expect("explicit header");
+    assert_eq!(auth(&headers).as_deref(), Some("Basic eDp5"));
+
+    let headers = logic::with_suwayomi_auth(HeaderMap::new(), None, &HeaderMap::new())
+        .expect("no credentials");
+    assert!(headers.is_empty());
+    assert!(logic::with_suwayomi_auth(HeaderMap::new(), Some("a\nb"), &incoming).is_err());
+}
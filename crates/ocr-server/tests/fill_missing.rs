@@ -0,0 +1,79 @@
+mod common;
+
+use axum::{Json, extract::State, http::HeaderMap};
+use common::{block, unplaced};
+use mangatan_ocr_server::{
+    handlers::{self, JobRequest},
+    jobs,
+    logic::{self, OcrResult},
+    state::AppState,
+};
+
+fn cache(state: &AppState, url: &str, data: Vec<OcrResult>) {
+    state
+        .cache
+        .write()
+        .expect("lock")
+        .insert(logic::get_cache_key(url), common::entry("Series", data));
+}
+
+fn page(n: usize) -> String {
+    format!("http://suwayomi.invalid/api/v1/manga/1/chapter/1/page/{n}")
+}
+
+#[test]
+fn only_uncached_and_empty_pages_are_taken() {
+    let state = common::state("fill-missing-take");
+    cache(&state, &page(0), vec![block("一")]);
+    cache(&state, &page(1), Vec::new());
+    cache(&state, &page(2), Vec::new());
+    state.set_pinned(&logic::get_cache_key(&page(2)), true);
+
+    // Page 3 is listed twice; the job needs it once
+    let pages = vec![page(0), page(1), page(2), page(3), page(3), page(4)];
+    let missing = jobs::take_missing_pages(&state, pages);
+    assert_eq!(missing.pages, [page(1), page(3), page(4)]);
+    assert_eq!((missing.uncached, missing.empty), (2, 1));
+
+    let cache = state.cache.read().expect("lock");
+    assert!(
+        !cache.contains_key(&logic::get_cache_key(&page(1))),
+        "the empty result is dropped so the job OCRs the page again"
+    );
+    assert!(cache.contains_key(&logic::get_cache_key(&page(0))));
+    assert!(cache.contains_key(&logic::get_cache_key(&page(2))));
+}
+
+#[test]
+fn pages_with_only_lines_without_geometry_are_taken() {
+    let state = common::state("fill-missing-unplaced");
+    cache(&state, &page(0), vec![unplaced("一")]);
+    cache(&state, &page(1), vec![block("二"), unplaced("三")]);
+
+    let missing = jobs::take_missing_pages(&state, vec![page(0), page(1)]);
+    assert_eq!(missing.pages, [page(0)]);
+    assert_eq!((missing.uncached, missing.empty), (0, 1));
+}
+
+#[tokio::test]
+async fn a_complete_series_starts_no_job() {
+    let state = common::state("fill-missing-complete");
+    let pages: Vec<String> = (0..3).map(page).collect();
+    for url in &pages {
+        cache(&state, url, vec![block("字")]);
+    }
+    let request: JobRequest = serde_json::from_value(serde_json::json!({
+        "base_url": "http://suwayomi.invalid/api/v1/manga/1",
+        "context": "Series",
+        "pages": pages,
+    }))
+    .expect("request");
+
+    let Json(body) =
+        handlers::fill_missing_handler(State(state.clone()), HeaderMap::new(), Json(request))
+            .await
+            .expect("fill missing");
+    assert_eq!(body["status"], "complete");
+    assert_eq!(body["total"], 3);
+    assert!(state.active_chapter_jobs.read().expect("lock").is_empty());
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use std::time::Duration;
+
+use mangatan_ocr_server::state::AppState;
+
+fn add_entry(state: &AppState, key: &str) {
+    state
+        .cache
+        .write()
+        .expect("lock")
+        .insert(key.to_string(), common::entry("test", Vec::new()));
+    state.cache_changed();
+}
+
+#[test]
+fn changes_are_saved_on_flush_not_on_write() {
+    let mut state = common::state("flush-deferred");
+    state.flush_interval = Some(Duration::from_secs(3600));
+
+    add_entry(&state, "page-1");
+    add_entry(&state, "page-2");
+    assert!(state.has_unsaved_changes());
+    assert!(
+        !state.cache_path.exists(),
+        "nothing is written before a flush"
+    );
+
+    assert!(state.flush_cache());
+    assert!(!state.has_unsaved_changes());
+    assert!(!state.flush_cache(), "a clean cache isn't rewritten");
+
+    let reloaded = state.reload();
+    assert_eq!(reloaded.cache.read().expect("lock").len(), 2);
+}
+
+#[test]
+fn without_an_interval_every_change_is_saved() {
+    let mut state = common::state("flush-immediate");
+    state.flush_interval = None;
+
+    add_entry(&state, "page-1");
+    assert!(!state.has_unsaved_changes());
+
+    let reloaded = state.reload();
+    assert_eq!(reloaded.cache.read().expect("lock").len(), 1);
+}
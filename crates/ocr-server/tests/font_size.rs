@@ -0,0 +1,72 @@
+use mangatan_ocr_server::{
+    logic::{BoundingBox, OcrResult},
+    merge::{self, FontSizeMethod, MergeConfig},
+};
+
+fn line(text: &str, x: f64, y: f64, width: f64, height: f64) -> OcrResult {
+    OcrResult {
+        text: text.to_string(),
+        tight_bounding_box: BoundingBox {
+            x,
+            y,
+            width,
+            height,
+            rotation: None,
+        },
+        is_merged: None,
+        forced_orientation: None,
+        orientation: None,
+        no_geometry: None,
+    }
+}
+
+fn texts(config: &MergeConfig, lines: Vec<OcrResult>) -> Vec<String> {
+    let mut texts: Vec<String> = merge::auto_merge(lines, 1000, 1000, config)
+        .into_iter()
+        .map(|r| r.text)
+        .collect();
+    texts.sort();
+    texts
+}
+
+/// Two columns of the same lettering, the second one outlined, which pads its box sideways.
+fn outlined_bubble() -> Vec<OcrResult> {
+    vec![
+        line("一列目です", 600.0, 100.0, 40.0, 200.0),
+        line("二列目です", 502.0, 100.0, 62.0, 200.0),
+    ]
+}
+
+/// Two plain columns a line apart.
+fn plain_bubble() -> Vec<OcrResult> {
+    vec![
+        line("一列目です", 600.0, 100.0, 40.0, 200.0),
+        line("二列目です", 550.0, 100.0, 40.0, 200.0),
+    ]
+}
+
+#[test]
+fn cross_axis_estimate_splits_outlined_columns() {
+    let config = MergeConfig::default();
+    assert_eq!(config.font_size_method, FontSizeMethod::Cross);
+
+    assert_eq!(
+        texts(&config, outlined_bubble()),
+        ["一列目です", "二列目です"]
+    );
+    assert_eq!(texts(&config, plain_bubble()), ["一列目です\n二列目です"]);
+}
+
+#[test]
+fn area_estimate_groups_outlined_columns() {
+    let config = MergeConfig {
+        font_size_method: FontSizeMethod::Area,
+        ..MergeConfig::default()
+    };
+
+    assert_eq!(
+        texts(&config, outlined_bubble()),
+        ["一列目です\n二列目です"]
+    );
+    assert_eq!(texts(&config, plain_bubble()), ["一列目です\n二列目です"]);
+}
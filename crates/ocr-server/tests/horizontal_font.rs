@@ -0,0 +1,95 @@
+use mangatan_ocr_server::{
+    logic::{BoundingBox, OcrResult},
+    merge::{self, MergeConfig},
+};
+
+fn line(text: &str, x: f64, y: f64, width: f64, height: f64) -> OcrResult {
+    OcrResult {
+        text: text.to_string(),
+        tight_bounding_box: BoundingBox {
+            x,
+            y,
+            width,
+            height,
+            rotation: None,
+        },
+        is_merged: None,
+        forced_orientation: None,
+        orientation: None,
+        no_geometry: None,
+    }
+}
+
+fn texts(config: &MergeConfig, lines: Vec<OcrResult>) -> Vec<String> {
+    let mut texts: Vec<String> = merge::auto_merge(lines, 1000, 1000, config)
+        .into_iter()
+        .map(|r| r.text)
+        .collect();
+    texts.sort();
+    texts
+}
+
+fn robust() -> MergeConfig {
+    MergeConfig {
+        robust_horizontal_font: true,
+        ..MergeConfig::default()
+    }
+}
+
+/// A two-line bubble from a horizontally lettered page: the first line has ascenders and
+/// descenders, the second has neither, so its box is a third shorter.
+fn two_line_bubble() -> Vec<OcrResult> {
+    vec![
+        line("Where did you go?", 100.0, 100.0, 300.0, 30.0),
+        line("once more.", 120.0, 150.0, 240.0, 22.0),
+    ]
+}
+
+/// A chapter heading over the first line of narration, well over twice its size.
+fn heading_and_body() -> Vec<OcrResult> {
+    vec![
+        line("CHAPTER 3", 100.0, 600.0, 400.0, 60.0),
+        line("it was raining", 120.0, 680.0, 360.0, 24.0),
+    ]
+}
+
+#[test]
+fn raw_heights_split_a_two_line_bubble() {
+    assert_eq!(
+        texts(&MergeConfig::default(), two_line_bubble()),
+        ["Where did you go?", "once more."]
+    );
+}
+
+#[test]
+fn trimmed_heights_merge_a_two_line_bubble() {
+    assert_eq!(
+        texts(&robust(), two_line_bubble()),
+        ["Where did you go?\nonce more."]
+    );
+}
+
+#[test]
+fn headings_keep_their_own_size() {
+    let expected = ["CHAPTER 3", "it was raining"];
+    assert_eq!(texts(&MergeConfig::default(), heading_and_body()), expected);
+    assert_eq!(texts(&robust(), heading_and_body()), expected);
+}
+
+#[test]
+fn whole_page_groups_the_same_with_vertical_lines() {
+    let mut page = two_line_bubble();
+    page.extend(heading_and_body());
+    // A vertical sound effect between them is never smoothed with the horizontal lines
+    page.push(line("ドン", 700.0, 200.0, 40.0, 200.0));
+
+    assert_eq!(
+        texts(&robust(), page),
+        [
+            "CHAPTER 3",
+            "Where did you go?\nonce more.",
+            "it was raining",
+            "ドン"
+        ]
+    );
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use common::ScratchDir;
+use mangatan_ocr_server::job_history::{HISTORY_LIMIT, JobHistory, JobSummary, PageFailure};
+
+fn summary(base_url: &str, finished_at: u64, failed: usize) -> JobSummary {
+    JobSummary {
+        base_url: base_url.to_string(),
+        context: "Test Manga / Chapter 1".to_string(),
+        started_at: finished_at - 1000,
+        finished_at,
+        total: 10,
+        skipped: 2,
+        processed: 8 - failed,
+        failed: (0..failed)
+            .map(|page| PageFailure {
+                url: format!("{base_url}/page/{page}"),
+                error: "Lens failed".to_string(),
+            })
+            .collect(),
+        lens_calls: 12,
+        backend_suspect: false,
+    }
+}
+
+#[test]
+fn keeps_the_most_recent_jobs_newest_first() {
+    let history = JobHistory::new(None);
+    for i in 0..HISTORY_LIMIT as u64 + 5 {
+        history.record(summary(&format!("chapter-{i}"), 10_000 + i, 0));
+    }
+
+    let recent = history.recent();
+    assert_eq!(recent.len(), HISTORY_LIMIT);
+    assert_eq!(recent[0].base_url, format!("chapter-{}", HISTORY_LIMIT + 4));
+    assert_eq!(recent[HISTORY_LIMIT - 1].base_url, "chapter-5");
+}
+
+#[test]
+fn latest_for_returns_the_last_run_of_a_chapter() {
+    let history = JobHistory::new(None);
+    history.record(summary("chapter-a", 10_000, 3));
+    history.record(summary("chapter-b", 11_000, 0));
+    history.record(summary("chapter-a", 12_000, 1));
+
+    let latest = history.latest_for("chapter-a").expect("chapter-a ran");
+    assert_eq!(latest.finished_at, 12_000);
+    assert_eq!(latest.failed.len(), 1);
+    assert!(history.latest_for("chapter-c").is_none());
+}
+
+#[test]
+fn persisted_history_survives_a_restart() {
+    let dir = ScratchDir::new("job-history");
+    let path = dir.path().join("history.json");
+
+    JobHistory::new(Some(path.clone())).record(summary("chapter-a", 10_000, 2));
+
+    let reloaded = JobHistory::new(Some(path));
+    assert_eq!(reloaded.recent(), vec![summary("chapter-a", 10_000, 2)]);
+}
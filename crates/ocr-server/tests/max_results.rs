@@ -0,0 +1,47 @@
+use mangatan_ocr_server::logic::{self, BoundingBox, OcrResult};
+
+fn block(text: &str, side: f64) -> OcrResult {
+    OcrResult {
+        text: text.to_string(),
+        tight_bounding_box: BoundingBox {
+            x: 0.1,
+            y: 0.1,
+            width: side,
+            height: side,
+            rotation: None,
+        },
+        is_merged: None,
+        forced_orientation: None,
+        orientation: None,
+        no_geometry: None,
+    }
+}
+
+fn texts(results: &[OcrResult]) -> Vec<&str> {
+    results.iter().map(|r| r.text.as_str()).collect()
+}
+
+#[test]
+fn the_largest_blocks_are_kept_in_reading_order() {
+    let mut results = vec![
+        block("sfx", 0.01),
+        block("bubble", 0.2),
+        block("lost", 0.0),
+        block("caption", 0.1),
+        block("tiny", 0.02),
+    ];
+    assert_eq!(logic::cap_results(&mut results, 3), Some(5));
+    assert_eq!(texts(&results), ["bubble", "caption", "tiny"]);
+}
+
+#[test]
+fn pages_under_the_cap_are_left_alone() {
+    let mut results = vec![block("a", 0.1), block("b", 0.1)];
+    assert_eq!(logic::cap_results(&mut results, 2), None);
+    assert_eq!(texts(&results), ["a", "b"]);
+
+    // Equal boxes keep the earlier ones
+    let mut results = vec![block("a", 0.1), block("b", 0.1), block("c", 0.1)];
+    assert_eq!(logic::cap_results(&mut results, 2), Some(3));
+    assert_eq!(texts(&results), ["a", "b"]);
+}
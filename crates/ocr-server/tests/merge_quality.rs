@@ -0,0 +1,233 @@
+mod common;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use mangatan_ocr_server::{
+    handlers::{self, QualityReportRequest},
+    logic::{BoundingBox, OcrResult},
+    merge::{self, MergeConfig},
+    quality::{Group, MergeQuality, QualityThresholds},
+    state::CacheEntry,
+};
+
+fn bbox(x: f64, y: f64, width: f64, height: f64) -> BoundingBox {
+    BoundingBox {
+        x,
+        y,
+        width,
+        height,
+        rotation: None,
+    }
+}
+
+fn group(bbox: BoundingBox, font_sizes: &[f64], vertical: &[bool]) -> Group {
+    Group {
+        bbox,
+        font_sizes: font_sizes.to_vec(),
+        vertical: vertical.to_vec(),
+    }
+}
+
+fn line(text: &str, x: f64, y: f64, width: f64, height: f64) -> OcrResult {
+    OcrResult {
+        text: text.to_string(),
+        tight_bounding_box: bbox(x, y, width, height),
+        is_merged: None,
+        forced_orientation: None,
+        orientation: None,
+        no_geometry: None,
+    }
+}
+
+/// Two bubbles of evenly sized columns, well apart.
+fn good_page() -> Vec<Group> {
+    vec![
+        group(
+            bbox(10.0, 10.0, 60.0, 80.0),
+            &[20.0, 20.0, 21.0],
+            &[true; 3],
+        ),
+        group(bbox(120.0, 100.0, 40.0, 80.0), &[18.0, 18.0], &[true; 2]),
+    ]
+}
+
+#[test]
+fn a_clean_merge_exceeds_nothing() {
+    let quality = MergeQuality::measure(&good_page(), 200.0, 200.0);
+    assert_eq!(quality.single_line_groups, 0);
+    assert_eq!(quality.multi_line_groups, 2);
+    assert!(quality.font_size_variation < 0.05, "{quality:?}");
+    assert_eq!(quality.overlap_fraction, 0.0);
+    assert_eq!(quality.mixed_orientation_groups, 0);
+    assert!(QualityThresholds::default().exceeded(&quality).is_empty());
+}
+
+#[test]
+fn mismatched_fonts_and_orientations_are_flagged() {
+    let groups = vec![
+        // A caption swallowed by the bubble next to it
+        group(bbox(10.0, 10.0, 60.0, 80.0), &[10.0, 30.0], &[true, true]),
+        group(
+            bbox(120.0, 100.0, 40.0, 80.0),
+            &[18.0, 18.0],
+            &[true, false],
+        ),
+    ];
+    let quality = MergeQuality::measure(&groups, 200.0, 200.0);
+    assert!(
+        (quality.font_size_variation - 0.5).abs() < 1e-9,
+        "{quality:?}"
+    );
+    assert_eq!(quality.mixed_orientation_groups, 1);
+
+    let exceeded: Vec<&str> = QualityThresholds::default()
+        .exceeded(&quality)
+        .into_iter()
+        .map(|(metric, _)| metric)
+        .collect();
+    assert_eq!(
+        exceeded,
+        ["font_size_variation", "mixed_orientation_groups"]
+    );
+}
+
+#[test]
+fn overlap_counts_each_spot_once() {
+    let groups = vec![
+        group(bbox(0.0, 0.0, 100.0, 100.0), &[20.0], &[true]),
+        group(bbox(50.0, 50.0, 100.0, 100.0), &[20.0], &[true]),
+        // Inside the overlap of the other two
+        group(bbox(60.0, 60.0, 20.0, 20.0), &[20.0], &[true]),
+    ];
+    let quality = MergeQuality::measure(&groups, 200.0, 200.0);
+    assert!(
+        (quality.overlap_fraction - 2500.0 / 40000.0).abs() < 1e-9,
+        "{quality:?}"
+    );
+    assert_eq!(quality.single_line_share(), 0.0, "too few groups to judge");
+}
+
+#[test]
+fn an_unmerged_page_is_flagged() {
+    let groups: Vec<Group> = (0..5)
+        .map(|i| group(bbox(i as f64 * 30.0, 10.0, 20.0, 80.0), &[20.0], &[true]))
+        .collect();
+    let quality = MergeQuality::measure(&groups, 200.0, 200.0);
+    assert_eq!(quality.single_line_share(), 1.0);
+    let exceeded = QualityThresholds::default().exceeded(&quality);
+    assert_eq!(exceeded.len(), 1);
+    assert_eq!(exceeded[0].0, "single_line_share");
+}
+
+#[test]
+fn chunks_combine_weighted_by_area() {
+    let covered = MergeQuality {
+        single_line_groups: 1,
+        multi_line_groups: 2,
+        font_size_variation: 0.1,
+        overlap_fraction: 0.3,
+        mixed_orientation_groups: 1,
+    };
+    let clean = MergeQuality {
+        multi_line_groups: 1,
+        font_size_variation: 0.4,
+        ..MergeQuality::default()
+    };
+    let page = MergeQuality::combine([(covered, 100.0), (clean, 300.0)]);
+    assert_eq!(page.groups(), 4);
+    assert_eq!(page.font_size_variation, 0.4);
+    assert!((page.overlap_fraction - 0.075).abs() < 1e-9, "{page:?}");
+    assert_eq!(page.mixed_orientation_groups, 1);
+}
+
+#[test]
+fn auto_merge_measures_what_it_merged() {
+    let lines = vec![
+        line("一列目です", 600.0, 100.0, 40.0, 200.0),
+        line("二列目です", 550.0, 100.0, 40.0, 200.0),
+        line("別の吹き出し", 100.0, 500.0, 40.0, 240.0),
+    ];
+    let (merged, quality) = merge::auto_merge_measured(lines, 1000, 1000, &MergeConfig::default());
+    let quality = quality.expect("measured");
+    assert_eq!(merged.len(), 2);
+    assert_eq!(quality.multi_line_groups, 1);
+    assert_eq!(quality.single_line_groups, 1);
+    assert_eq!(quality.font_size_variation, 0.0);
+
+    let disabled = MergeConfig {
+        enabled: false,
+        ..MergeConfig::default()
+    };
+    let (_, quality) = merge::auto_merge_measured(Vec::new(), 1000, 1000, &disabled);
+    assert_eq!(quality, None);
+}
+
+#[tokio::test]
+async fn the_report_lists_suspect_pages_worst_first() {
+    let state = common::state("quality-report");
+
+    let mildly_off = MergeQuality {
+        multi_line_groups: 2,
+        font_size_variation: 0.4,
+        ..MergeQuality::default()
+    };
+    let badly_off = MergeQuality {
+        multi_line_groups: 2,
+        font_size_variation: 0.7,
+        mixed_orientation_groups: 1,
+        ..MergeQuality::default()
+    };
+    let pages = [
+        ("fine", "Series", Some(MergeQuality::default())),
+        ("mild", "Series", Some(mildly_off)),
+        ("bad", "Series", Some(badly_off.clone())),
+        ("old", "Series", None),
+        ("elsewhere", "Other", Some(badly_off)),
+    ];
+    {
+        let mut cache = state.cache.write().expect("lock");
+        for (key, context, merge_quality) in pages {
+            cache.insert(
+                key.to_string(),
+                CacheEntry {
+                    merge_quality,
+                    ..common::entry(context, Vec::new())
+                },
+            );
+        }
+    }
+
+    let report = |params: QualityReportRequest| {
+        let state = state.clone();
+        async move {
+            let Json(body) = handlers::quality_report_handler(State(state), Query(params)).await;
+            body
+        }
+    };
+    let body = report(QualityReportRequest {
+        context: Some("Series".to_string()),
+        ..QualityReportRequest::default()
+    })
+    .await;
+    assert_eq!(body["pages_checked"], 3, "{body}");
+    assert_eq!(body["unmeasured"], 1, "{body}");
+    let keys: Vec<&str> = body["pages"]
+        .as_array()
+        .expect("pages")
+        .iter()
+        .map(|page| page["cache_key"].as_str().expect("key"))
+        .collect();
+    assert_eq!(keys, ["bad", "mild"]);
+
+    // A looser threshold lets the mild page through
+    let body = report(QualityReportRequest {
+        context: Some("Series".to_string()),
+        font_size_variation: Some(0.5),
+        ..QualityReportRequest::default()
+    })
+    .await;
+    assert_eq!(body["pages"].as_array().expect("pages").len(), 1, "{body}");
+    assert_eq!(body["thresholds"]["font_size_variation"], 0.5);
+}
@@ -1,5 +1,5 @@
-use mangatan_ocr_server::logic::{self, RawChunk};
-use mangatan_ocr_server::merge::{self, MergeConfig};
+use mangatan_ocr_server::logic::{self, BoundingBox, OcrResult, RawChunk};
+use mangatan_ocr_server::merge::{self, MergeConfig, OrientationWeight};
 use pretty_assertions::StrComparison;
 use serde_json::Value;
 use std::fs;
@@ -108,7 +108,7 @@ async fn run_merge_regression_tests() {
                 } else {
                     println!("  [OCR] Running Lens OCR for {}...", test_name);
                     let image_bytes = fs::read(path).expect("Read image");
-                    let chunks = logic::get_raw_ocr_data(&image_bytes)
+                    let chunks = logic::get_raw_ocr_data(&image_bytes, None, None)
                         .await
                         .expect("Lens OCR failed");
 
@@ -210,3 +210,116 @@ async fn run_merge_regression_tests() {
         }
     }
 }
+
+fn fixture_line(text: &str, x: f64, y: f64, width: f64, height: f64) -> OcrResult {
+    OcrResult {
+        text: text.to_string(),
+        tight_bounding_box: BoundingBox {
+            x,
+            y,
+            width,
+            height,
+            rotation: None,
+        },
+        is_merged: None,
+        forced_orientation: None,
+        orientation: None,
+        no_geometry: None,
+    }
+}
+
+/// Two short vertical columns touching one long horizontal line. Each side wins a different
+/// kind of vote: the columns have the line count, the horizontal line has the area.
+fn mixed_orientation_fixture() -> Vec<OcrResult> {
+    vec![
+        fixture_line("これは", 500.0, 100.0, 40.0, 120.0),
+        fixture_line("テスト", 455.0, 100.0, 40.0, 120.0),
+        fixture_line("横書きの長い一行です", 300.0, 225.0, 400.0, 40.0),
+    ]
+}
+
+fn orientation_of(results: &[OcrResult], needle: &str) -> String {
+    results
+        .iter()
+        .find(|r| r.text.contains(needle))
+        .and_then(|r| r.forced_orientation.clone())
+        .unwrap_or_else(|| panic!("no result containing {needle}: {results:#?}"))
+}
+
+#[test]
+fn mixed_orientation_threshold() {
+    let orientations = |config: &MergeConfig| {
+        let results = merge::auto_merge(mixed_orientation_fixture(), 1000, 1000, config);
+        (
+            orientation_of(&results, "これは"),
+            orientation_of(&results, "横書き"),
+        )
+    };
+    let pair = |a: &str, b: &str| (a.to_string(), b.to_string());
+
+    // Default: no vote, each line keeps its own orientation
+    assert_eq!(
+        orientations(&MergeConfig::default()),
+        pair("vertical", "horizontal")
+    );
+
+    // Simple majority by count: the two columns outvote the horizontal line
+    let majority = MergeConfig {
+        orientation_threshold: Some(0.5),
+        ..MergeConfig::default()
+    };
+    assert_eq!(orientations(&majority), pair("vertical", "vertical"));
+
+    // 2 of 3 falls short of a 70% requirement, so nothing is overridden
+    let strict = MergeConfig {
+        orientation_threshold: Some(0.7),
+        ..MergeConfig::default()
+    };
+    assert_eq!(orientations(&strict), pair("vertical", "horizontal"));
+
+    // Weighted by area the long horizontal line wins instead
+    let by_area = MergeConfig {
+        orientation_threshold: Some(0.5),
+        orientation_weight: OrientationWeight::Area,
+        ..MergeConfig::default()
+    };
+    assert_eq!(orientations(&by_area), pair("horizontal", "horizontal"));
+}
+
+/// Separate bubbles spread over the page: two right-to-left columns, a horizontal caption and a
+/// lone column on the left.
+fn scattered_page_fixture() -> Vec<OcrResult> {
+    vec![
+        fixture_line("右の列", 900.0, 50.0, 40.0, 120.0),
+        fixture_line("二列目", 855.0, 50.0, 40.0, 120.0),
+        fixture_line("横書きの説明文です", 300.0, 700.0, 360.0, 40.0),
+        fixture_line("左の列", 100.0, 60.0, 40.0, 120.0),
+        fixture_line("中央", 500.0, 300.0, 40.0, 80.0),
+    ]
+}
+
+#[test]
+fn merge_output_is_deterministic() {
+    let mut shuffled = scattered_page_fixture();
+    // Any fixed order other than Lens' will do
+    shuffled.rotate_left(2);
+    shuffled.swap(0, 3);
+
+    let run = |lines: Vec<OcrResult>| {
+        let results = merge::auto_merge(lines, 1000, 1000, &MergeConfig::default());
+        serde_json::to_string(&results).expect("serialize")
+    };
+    let first = run(shuffled.clone());
+    for _ in 0..10 {
+        assert_eq!(run(shuffled.clone()), first);
+    }
+
+    // Reading position decides the order, not which group was found first: mostly columns, so
+    // right to left by right edge
+    let results = merge::auto_merge(shuffled, 1000, 1000, &MergeConfig::default());
+    let texts: Vec<&str> = results.iter().map(|r| r.text.as_str()).collect();
+    assert_eq!(
+        texts,
+        ["右の列\n二列目", "横書きの説明文です", "中央", "左の列"]
+    );
+}
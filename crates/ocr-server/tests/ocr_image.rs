@@ -0,0 +1,126 @@
+mod common;
+
+use std::io::{Cursor, Read};
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, Uri, header},
+    response::IntoResponse,
+};
+use base64::Engine;
+use image::{DynamicImage, ImageFormat, RgbImage};
+use mangatan_ocr_server::{
+    embed::{self, EmbedFormat},
+    handlers::{self, EmbedRequest, OcrRequest},
+    logic::{self, BoundingBox, OcrResult},
+};
+
+fn page(format: ImageFormat) -> Vec<u8> {
+    let image = RgbImage::from_fn(24, 36, |x, y| {
+        image::Rgb([(x * 10) as u8, (y * 7) as u8, 90])
+    });
+    let mut out = Cursor::new(Vec::new());
+    DynamicImage::ImageRgb8(image)
+        .write_to(&mut out, format)
+        .expect("encode");
+    out.into_inner()
+}
+
+fn results() -> Vec<OcrResult> {
+    vec![OcrResult {
+        text: "吹き出しの\n中身".to_string(),
+        tight_bounding_box: BoundingBox {
+            x: 0.1,
+            y: 0.2,
+            width: 0.3,
+            height: 0.4,
+            rotation: None,
+        },
+        is_merged: Some(true),
+        forced_orientation: None,
+        orientation: None,
+        no_geometry: None,
+    }]
+}
+
+fn texts(results: &[OcrResult]) -> Vec<&str> {
+    results.iter().map(|r| r.text.as_str()).collect()
+}
+
+#[test]
+fn pngs_keep_their_pixels_and_gain_the_results() {
+    let png = page(ImageFormat::Png);
+    let embedded = embed::embed(&png, &results(), EmbedFormat::Png).expect("embed");
+
+    let decoded = image::load_from_memory(&embedded).expect("still a valid png");
+    let original = image::load_from_memory(&png).expect("png");
+    assert_eq!(decoded.to_rgb8(), original.to_rgb8());
+    let read = embed::read_png(&embedded).expect("results embedded");
+    assert_eq!(texts(&read), ["吹き出しの\n中身"]);
+    assert!(embed::read_png(&png).is_none());
+}
+
+#[test]
+fn other_formats_become_png() {
+    let jpeg = page(ImageFormat::Jpeg);
+    let embedded = embed::embed(&jpeg, &results(), EmbedFormat::Png).expect("embed");
+    assert_eq!(image::guess_format(&embedded).ok(), Some(ImageFormat::Png));
+    let decoded = image::load_from_memory(&embedded).expect("valid png");
+    assert_eq!((decoded.width(), decoded.height()), (24, 36));
+    assert!(embed::read_png(&embedded).is_some());
+}
+
+#[test]
+fn zips_keep_the_page_as_served() {
+    let jpeg = page(ImageFormat::Jpeg);
+    let packed = embed::embed(&jpeg, &results(), EmbedFormat::Zip).expect("embed");
+    let mut archive = zip::ZipArchive::new(Cursor::new(packed)).expect("zip");
+
+    let mut image = Vec::new();
+    archive
+        .by_name("page.jpg")
+        .expect("image")
+        .read_to_end(&mut image)
+        .expect("read image");
+    assert_eq!(image, jpeg);
+    let sidecar = archive.by_name(embed::SIDECAR_NAME).expect("sidecar");
+    let read: Vec<OcrResult> = serde_json::from_reader(sidecar).expect("json");
+    assert_eq!(texts(&read), ["吹き出しの\n中身"]);
+}
+
+#[tokio::test]
+async fn the_endpoint_embeds_cached_results() {
+    let state = common::state("ocr-image");
+
+    let encoded = base64::engine::general_purpose::STANDARD.encode(page(ImageFormat::Png));
+    let url = format!("data:image/png;base64,{encoded}");
+    state.cache.write().expect("lock").insert(
+        logic::get_cache_key(&url),
+        common::entry("Archive", results()),
+    );
+
+    let query = url
+        .replace('+', "%2B")
+        .replace('/', "%2F")
+        .replace('=', "%3D");
+    let uri: Uri = format!("/ocr-image?url={query}").parse().expect("uri");
+    let Query(params) = Query::<OcrRequest>::try_from_uri(&uri).expect("query");
+    let Query(request) = Query::<EmbedRequest>::try_from_uri(&uri).expect("query");
+    assert_eq!(request.embed, EmbedFormat::Png);
+
+    let response = handlers::ocr_image_handler(
+        State(state.clone()),
+        HeaderMap::new(),
+        Query(params),
+        Query(request),
+    )
+    .await
+    .expect("embedded")
+    .into_response();
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    let read = embed::read_png(&body).expect("results embedded");
+    assert_eq!(texts(&read), ["吹き出しの\n中身"]);
+}
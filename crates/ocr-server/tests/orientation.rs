@@ -0,0 +1,154 @@
+use mangatan_ocr_server::{
+    logic::{self, BoundingBox, OcrResult, Orientation},
+    merge::{self, MergeConfig, ReadingDirection},
+};
+
+fn line(text: &str, x: f64, y: f64, width: f64, height: f64) -> OcrResult {
+    OcrResult {
+        text: text.to_string(),
+        tight_bounding_box: BoundingBox {
+            x,
+            y,
+            width,
+            height,
+            rotation: None,
+        },
+        is_merged: None,
+        forced_orientation: None,
+        orientation: None,
+        no_geometry: None,
+    }
+}
+
+/// Three touching columns in the order Lens read them, stepping `step` pixels per column.
+fn columns(start_x: f64, step: f64) -> Vec<OcrResult> {
+    ["一列目です", "二列目です", "三列目"]
+        .iter()
+        .enumerate()
+        .map(|(i, text)| {
+            let height = 40.0 * text.chars().count() as f64;
+            line(text, start_x + step * i as f64, 100.0, 40.0, height)
+        })
+        .collect()
+}
+
+fn merge_one(lines: Vec<OcrResult>) -> OcrResult {
+    merge_one_with(lines, &MergeConfig::default())
+}
+
+fn merge_one_with(lines: Vec<OcrResult>, config: &MergeConfig) -> OcrResult {
+    let mut results = merge::auto_merge(lines, 1000, 1000, config);
+    assert_eq!(results.len(), 1, "{results:#?}");
+    results.remove(0)
+}
+
+/// Three touching columns Lens read middle, left, right: their order says nothing about the
+/// direction, like the jumbled reads of sparse manhwa bubbles.
+fn ambiguous_columns() -> Vec<OcrResult> {
+    [("中の列", 145.0), ("左の列", 100.0), ("右の列", 190.0)]
+        .into_iter()
+        .map(|(text, x)| line(text, x, 100.0, 40.0, 120.0))
+        .collect()
+}
+
+#[test]
+fn right_to_left_columns() {
+    let merged = merge_one(columns(600.0, -45.0));
+    assert_eq!(merged.orientation, Some(Orientation::VerticalRtl));
+    assert_eq!(merged.forced_orientation.as_deref(), Some("vertical"));
+    assert_eq!(merged.text, "一列目です\n二列目です\n三列目");
+}
+
+#[test]
+fn left_to_right_columns() {
+    let merged = merge_one(columns(100.0, 45.0));
+    assert_eq!(merged.orientation, Some(Orientation::VerticalLtr));
+    // Old clients still see plain "vertical"
+    assert_eq!(merged.forced_orientation.as_deref(), Some("vertical"));
+    assert_eq!(merged.text, "一列目です\n二列目です\n三列目");
+
+    let json = serde_json::to_value(&merged).expect("serialize");
+    assert_eq!(json["orientation"], "vertical_ltr");
+    assert_eq!(json["forcedOrientation"], "vertical");
+}
+
+#[test]
+fn horizontal_rows() {
+    let merged = merge_one(vec![
+        line("横書きの一行目です", 100.0, 100.0, 360.0, 40.0),
+        line("二行目です", 100.0, 145.0, 200.0, 40.0),
+    ]);
+    assert_eq!(merged.orientation, Some(Orientation::Horizontal));
+    assert_eq!(merged.forced_orientation.as_deref(), Some("horizontal"));
+    assert_eq!(merged.text, "横書きの一行目です\n二行目です");
+}
+
+#[test]
+fn reading_order_follows_column_direction() {
+    let block = |text: &str, x: f64, orientation: Orientation| OcrResult {
+        forced_orientation: Some(orientation.legacy().to_string()),
+        orientation: Some(orientation),
+        ..line(text, x, 0.1, 0.05, 0.3)
+    };
+    let texts = |results: &[OcrResult]| -> Vec<String> {
+        logic::reading_order(results)
+            .iter()
+            .map(|r| r.text.clone())
+            .collect()
+    };
+
+    let rtl = [
+        block("left", 0.1, Orientation::VerticalRtl),
+        block("right", 0.8, Orientation::VerticalRtl),
+    ];
+    assert_eq!(texts(&rtl), ["right", "left"]);
+
+    let ltr = [
+        block("right", 0.8, Orientation::VerticalLtr),
+        block("left", 0.1, Orientation::VerticalLtr),
+    ];
+    assert_eq!(texts(&ltr), ["left", "right"]);
+}
+
+#[test]
+fn ambiguous_columns_read_right_to_left_by_default() {
+    let merged = merge_one(ambiguous_columns());
+    assert_eq!(merged.orientation, Some(Orientation::VerticalRtl));
+    assert_eq!(merged.text, "右の列\n中の列\n左の列");
+}
+
+#[test]
+fn requested_left_to_right_orders_the_columns() {
+    let config = MergeConfig {
+        reading_direction: Some(ReadingDirection::Ltr),
+        ..MergeConfig::default()
+    };
+    let merged = merge_one_with(ambiguous_columns(), &config);
+    assert_eq!(merged.orientation, Some(Orientation::VerticalLtr));
+    assert_eq!(merged.text, "左の列\n中の列\n右の列");
+
+    // Separate bubbles follow along in the page order
+    let bubbles = vec![
+        line("右の吹き出し", 800.0, 100.0, 40.0, 240.0),
+        line("左の吹き出し", 100.0, 100.0, 40.0, 240.0),
+    ];
+    let texts: Vec<String> = merge::auto_merge(bubbles, 1000, 1000, &config)
+        .into_iter()
+        .map(|r| r.text)
+        .collect();
+    assert_eq!(texts, ["左の吹き出し", "右の吹き出し"]);
+}
+
+#[test]
+fn requested_right_to_left_overrides_the_detection() {
+    let config = MergeConfig {
+        reading_direction: Some(ReadingDirection::Rtl),
+        ..MergeConfig::default()
+    };
+    let merged = merge_one_with(columns(100.0, 45.0), &config);
+    assert_eq!(merged.orientation, Some(Orientation::VerticalRtl));
+    assert_eq!(merged.text, "三列目\n二列目です\n一列目です");
+
+    let direction: ReadingDirection = serde_json::from_str("\"ltr\"").expect("deserialize");
+    assert_eq!(direction, ReadingDirection::Ltr);
+}
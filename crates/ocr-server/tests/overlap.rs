@@ -0,0 +1,94 @@
+use mangatan_ocr_server::{
+    logic::{BoundingBox, OcrResult},
+    merge::{self, MergeConfig, OverlapPolicy},
+};
+
+fn line(text: &str, x: f64, y: f64, width: f64, height: f64) -> OcrResult {
+    OcrResult {
+        text: text.to_string(),
+        tight_bounding_box: BoundingBox {
+            x,
+            y,
+            width,
+            height,
+            rotation: None,
+        },
+        is_merged: None,
+        forced_orientation: None,
+        orientation: None,
+        no_geometry: None,
+    }
+}
+
+/// A bubble of two wide columns at x 500..580, y 100..300, and a small-print one whose columns
+/// start at `top` and poke into its lower left corner.
+fn interleaved(top: f64) -> Vec<OcrResult> {
+    vec![
+        line("右の列です", 540.0, 100.0, 40.0, 200.0),
+        line("左の列です", 500.0, 100.0, 40.0, 200.0),
+        line("小さい字の右", 582.0, top, 10.0, 420.0 - top),
+        line("小さい字の左", 570.0, top, 10.0, 420.0 - top),
+    ]
+}
+
+fn merged(lines: Vec<OcrResult>, policy: OverlapPolicy) -> (BoundingBox, BoundingBox) {
+    let config = MergeConfig {
+        overlap_policy: policy,
+        ..MergeConfig::default()
+    };
+    let results = merge::auto_merge(lines, 1000, 1000, &config);
+    assert_eq!(results.len(), 2, "{results:#?}");
+    let big = results
+        .iter()
+        .find(|r| r.text.starts_with("右の列"))
+        .expect("big bubble");
+    let small = results
+        .iter()
+        .find(|r| r.text.starts_with("小さい字"))
+        .expect("small bubble");
+    (
+        big.tight_bounding_box.clone(),
+        small.tight_bounding_box.clone(),
+    )
+}
+
+fn overlap(a: &BoundingBox, b: &BoundingBox) -> f64 {
+    let width = (a.x + a.width).min(b.x + b.width) - a.x.max(b.x);
+    let height = (a.y + a.height).min(b.y + b.height) - a.y.max(b.y);
+    width.max(0.0) * height.max(0.0)
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+}
+
+#[test]
+fn overlaps_are_kept_by_default() {
+    let (big, small) = merged(interleaved(270.0), OverlapPolicy::default());
+    assert_close(overlap(&big, &small), 10.0 * 30.0);
+    // Logging doesn't move anything
+    let (_, logged) = merged(interleaved(270.0), OverlapPolicy::Log);
+    assert_close(logged.y, small.y);
+    assert_close(logged.height, small.height);
+}
+
+#[test]
+fn the_smaller_box_is_cut_back_to_the_bigger_ones_edge() {
+    let (big, small) = merged(interleaved(270.0), OverlapPolicy::Shrink);
+    assert_close(big.x, 500.0);
+    assert_close(big.height, 200.0);
+    // Cutting the top keeps more of it than cutting the left side would
+    assert_close(small.x, 570.0);
+    assert_close(small.y, 300.0);
+    assert_close(small.width, 22.0);
+    assert_close(small.height, 120.0);
+    assert_eq!(overlap(&big, &small), 0.0);
+}
+
+#[test]
+fn deep_overlaps_are_left_alone() {
+    // Either cut would take 45% of the small bubble
+    let (big, small) = merged(interleaved(200.0), OverlapPolicy::Shrink);
+    assert_close(small.y, 200.0);
+    assert_close(overlap(&big, &small), 10.0 * 100.0);
+}
@@ -0,0 +1,52 @@
+use mangatan_ocr_server::jobs::{self, ReadingOrder};
+
+fn chapter() -> Vec<String> {
+    (0..6)
+        .map(|i| format!("/api/v1/manga/1/chapter/1/page/{i}"))
+        .collect()
+}
+
+fn indices(pages: &[String]) -> Vec<usize> {
+    pages
+        .iter()
+        .map(|url| {
+            url.rsplit('/')
+                .next()
+                .and_then(|i| i.parse().ok())
+                .expect("page index")
+        })
+        .collect()
+}
+
+#[test]
+fn forward_keeps_the_request_order() {
+    let pages = jobs::order_pages(chapter(), ReadingOrder::Forward, None);
+    assert_eq!(pages, chapter());
+}
+
+#[test]
+fn reverse_starts_from_the_far_end() {
+    let pages = jobs::order_pages(chapter(), ReadingOrder::Reverse, None);
+    assert_eq!(indices(&pages), [5, 4, 3, 2, 1, 0]);
+}
+
+#[test]
+fn visible_page_comes_first_then_the_pages_behind_it() {
+    let forward = jobs::order_pages(chapter(), ReadingOrder::Forward, Some(2));
+    assert_eq!(indices(&forward), [2, 3, 4, 5, 0, 1]);
+
+    let reverse = jobs::order_pages(chapter(), ReadingOrder::Reverse, Some(2));
+    assert_eq!(indices(&reverse), [2, 1, 0, 5, 4, 3]);
+}
+
+#[test]
+fn out_of_range_start_is_ignored() {
+    let pages = jobs::order_pages(chapter(), ReadingOrder::Reverse, Some(6));
+    assert_eq!(indices(&pages), [5, 4, 3, 2, 1, 0]);
+}
+
+#[test]
+fn reading_order_is_snake_case() {
+    let order: ReadingOrder = serde_json::from_str("\"reverse\"").expect("parse");
+    assert_eq!(order, ReadingOrder::Reverse);
+}
@@ -0,0 +1,142 @@
+#![cfg(feature = "text-prefilter")]
+
+use image::{Rgba, RgbaImage};
+use mangatan_ocr_server::prefilter::{DEFAULT_MIN_GLYPHS, count_glyphs};
+
+const INK: Rgba<u8> = Rgba([20, 20, 20, 255]);
+const PAPER: Rgba<u8> = Rgba([245, 245, 245, 255]);
+
+// 8x8 stand-ins for 日, 十, 口, 人 and の
+const GLYPHS: [[&str; 8]; 5] = [
+    [
+        ".######.", ".#....#.", ".#....#.", ".######.", ".#....#.", ".#....#.", ".######.",
+        "........",
+    ],
+    [
+        "...#....", "...#....", "...#....", "########", "...#....", "...#....", "...#....",
+        "...#....",
+    ],
+    [
+        "........", "#######.", "#.....#.", "#.....#.", "#.....#.", "#.....#.", "#######.",
+        "........",
+    ],
+    [
+        "...#....", "...#....", "...#....", "..#.#...", "..#.#...", ".#...#..", ".#...#..",
+        "#.....#.",
+    ],
+    [
+        "..####..", ".#..#.#.", "#...#..#", "#...#..#", "#..#...#", "#..#...#", ".##...#.",
+        "........",
+    ],
+];
+
+/// Draws `count` glyphs in a vertical column at `x`, `scale` pixels per bitmap cell.
+fn draw_column(image: &mut RgbaImage, x: u32, y: u32, count: usize, scale: u32, ink: Rgba<u8>) {
+    for i in 0..count {
+        let glyph = &GLYPHS[i % GLYPHS.len()];
+        let top = y + i as u32 * 10 * scale;
+        for (row, line) in glyph.iter().enumerate() {
+            for (col, cell) in line.chars().enumerate() {
+                if cell != '#' {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let (px, py) = (x + col as u32 * scale + dx, top + row as u32 * scale + dy);
+                        if px < image.width() && py < image.height() {
+                            image.put_pixel(px, py, ink);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn page(width: u32, height: u32, paper: Rgba<u8>) -> RgbaImage {
+    RgbaImage::from_pixel(width, height, paper)
+}
+
+fn has_text(image: &RgbaImage) -> bool {
+    count_glyphs(image, DEFAULT_MIN_GLYPHS) >= DEFAULT_MIN_GLYPHS
+}
+
+#[test]
+fn dialogue_columns_are_never_skipped() {
+    for scale in [2, 3, 5, 8] {
+        let mut image = page(800, 1200, PAPER);
+        draw_column(&mut image, 400, 100, 6, scale, INK);
+        assert!(has_text(&image), "scale {scale}");
+    }
+}
+
+#[test]
+fn a_single_small_glyph_is_enough() {
+    for glyph in 0..GLYPHS.len() {
+        let mut image = page(600, 600, PAPER);
+        let mut single = page(16, 16, PAPER);
+        draw_column(&mut single, 0, 0, glyph + 1, 2, INK);
+        // Only the last glyph of the column lands inside the 16x16 tile
+        image::imageops::overlay(&mut image, &single, 300, 300);
+        assert!(has_text(&image), "glyph {glyph}");
+    }
+}
+
+#[test]
+fn light_text_on_dark_panels_is_found() {
+    let mut image = page(700, 900, Rgba([15, 15, 15, 255]));
+    draw_column(&mut image, 200, 80, 5, 4, Rgba([250, 250, 250, 255]));
+    assert!(has_text(&image));
+}
+
+#[test]
+fn text_in_tall_chunks_survives_the_working_downscale() {
+    let mut image = page(1000, 3000, PAPER);
+    draw_column(&mut image, 500, 2500, 4, 3, INK);
+    assert!(has_text(&image));
+}
+
+#[test]
+fn text_next_to_screentone_art_is_found() {
+    let mut image = RgbaImage::from_fn(800, 800, |x, y| {
+        if x < 400 && (x / 4 + y / 4) % 2 == 0 {
+            Rgba([120, 120, 120, 255])
+        } else {
+            PAPER
+        }
+    });
+    draw_column(&mut image, 600, 100, 4, 4, INK);
+    assert!(has_text(&image));
+}
+
+#[test]
+fn blank_and_flat_chunks_are_skipped() {
+    assert!(!has_text(&page(800, 3000, PAPER)));
+    assert!(!has_text(&page(800, 3000, Rgba([0, 0, 0, 255]))));
+
+    let gradient = RgbaImage::from_fn(800, 2000, |_, y| {
+        let value = (y * 255 / 2000) as u8;
+        Rgba([value, value, value, 255])
+    });
+    assert!(!has_text(&gradient));
+
+    // Scan noise a few levels either way
+    let noise = RgbaImage::from_fn(600, 600, |x, y| {
+        let value = 240 + ((x * 7 + y * 13) % 5) as u8;
+        Rgba([value, value, value, 255])
+    });
+    assert!(!has_text(&noise));
+}
+
+#[test]
+fn solid_shapes_are_not_glyphs() {
+    let mut image = page(800, 800, PAPER);
+    for (x, y) in [(100, 100), (400, 300), (200, 600)] {
+        for dy in 0..60 {
+            for dx in 0..60 {
+                image.put_pixel(x + dx, y + dy, INK);
+            }
+        }
+    }
+    assert_eq!(count_glyphs(&image, 10), 0);
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use mangatan_ocr_server::rate_limit::RateLimiter;
+
+const PAGE: &str = "https://cdn.example.org/manga/1/page/";
+
+fn millis(delay: Duration) -> u128 {
+    delay.as_millis()
+}
+
+#[test]
+fn remote_hosts_default_to_two_per_second() {
+    let limiter = RateLimiter::new(Vec::new());
+    let delays: Vec<_> = (0..4)
+        .map(|i| millis(limiter.reserve(&format!("{PAGE}{i}"))))
+        .collect();
+
+    // A one-second burst, then queued half a second apart
+    assert_eq!(delays[..2], [0, 0]);
+    assert!((450..=500).contains(&delays[2]), "{delays:?}");
+    assert!((950..=1000).contains(&delays[3]), "{delays:?}");
+    assert_eq!(millis(limiter.current_delay(PAGE)), delays[3]);
+}
+
+#[test]
+fn local_and_data_urls_are_not_limited() {
+    let limiter = RateLimiter::new(Vec::new());
+    for url in [
+        "http://127.0.0.1:4567/api/v1/manga/1/chapter/1/page/0",
+        "http://localhost:4568/api/v1/manga/1/chapter/1/page/0",
+        "http://192.168.1.20:4568/api/v1/manga/1/chapter/1/page/0",
+        "data:image/png;base64,AAAA",
+    ] {
+        for _ in 0..10 {
+            assert_eq!(limiter.reserve(url), Duration::ZERO, "{url}");
+        }
+    }
+}
+
+#[test]
+fn first_matching_rule_wins() {
+    let limiter = RateLimiter::new(vec![
+        ("*.example.org".to_string(), None),
+        ("*".to_string(), Some(1.0)),
+    ]);
+    for i in 0..10 {
+        assert_eq!(limiter.reserve(&format!("{PAGE}{i}")), Duration::ZERO);
+    }
+
+    let other = "https://images.other.net/1.jpg";
+    assert_eq!(limiter.reserve(other), Duration::ZERO);
+    assert!(limiter.reserve(other) > Duration::from_millis(900));
+}
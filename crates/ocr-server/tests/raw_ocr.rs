@@ -0,0 +1,32 @@
+use axum::http::HeaderMap;
+use mangatan_ocr_server::{
+    error::{ApiError, ErrorCode},
+    logic,
+    rate_limit::RateLimiter,
+};
+
+async fn raw_ocr_error(url: &str) -> ErrorCode {
+    let result = logic::fetch_raw_chunks(
+        url,
+        None,
+        None,
+        &HeaderMap::new(),
+        None,
+        &RateLimiter::new(Vec::new()),
+    )
+    .await;
+    let Err(err) = result else {
+        panic!("{url} shouldn't reach Lens");
+    };
+    ApiError::classify(&err)
+}
+
+#[tokio::test]
+async fn inline_pages_are_decoded_before_lens_is_asked() {
+    // "not an image", base64
+    let garbage = "data:image/png;base64,bm90IGFuIGltYWdl";
+    assert_eq!(raw_ocr_error(garbage).await, ErrorCode::DecodeFailed);
+
+    let malformed = "data:image/png;base64,@@@";
+    assert_eq!(raw_ocr_error(malformed).await, ErrorCode::DecodeFailed);
+}
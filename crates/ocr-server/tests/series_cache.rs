@@ -0,0 +1,160 @@
+mod common;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+use common::TestState;
+use mangatan_ocr_server::state::AppState;
+use serde_json::json;
+
+// The layout comes from the environment, so the tests here take turns
+static LAYOUT: Mutex<()> = Mutex::new(());
+
+fn use_layout(layout: &str) -> MutexGuard<'static, ()> {
+    let guard = LAYOUT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    set_layout(layout);
+    guard
+}
+
+fn set_layout(layout: &str) {
+    // SAFETY: only called while holding LAYOUT, which every test here holds for as long as it
+    // reads the environment
+    unsafe { std::env::set_var("MANGATAN_OCR_CACHE_LAYOUT", layout) };
+}
+
+fn add_entry(state: &AppState, series: &str, key: &str) {
+    state.cache.write().expect("lock").insert(
+        key.to_string(),
+        common::entry(series, vec![common::block(key)]),
+    );
+}
+
+fn series_dir(state: &TestState) -> PathBuf {
+    state.dir.path().join("ocr-cache")
+}
+
+/// The series file holding `key`.
+fn file_with(state: &TestState, key: &str) -> PathBuf {
+    fs::read_dir(series_dir(state))
+        .expect("series dir")
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| {
+            fs::read_to_string(path).is_ok_and(|text| text.contains(&format!("\"{key}\"")))
+        })
+        .unwrap_or_else(|| panic!("no series file holds {key}"))
+}
+
+/// Names in `dir` that contain `part`.
+fn names_with(dir: &Path, part: &str) -> Vec<String> {
+    fs::read_dir(dir)
+        .expect("read dir")
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.contains(part))
+        .collect()
+}
+
+fn keys(state: &AppState) -> Vec<String> {
+    let mut keys: Vec<String> = state.cache.read().expect("lock").keys().cloned().collect();
+    keys.sort();
+    keys
+}
+
+#[test]
+fn saving_rewrites_only_the_changed_series() {
+    let _layout = use_layout("per-series");
+    let state = common::state("series-fingerprint");
+    add_entry(&state, "Series A", "a1");
+    add_entry(&state, "Series B", "b1");
+    state.save_cache();
+
+    // Still valid, but no longer what a save of series B would write
+    let untouched = file_with(&state, "b1");
+    let mut marked = fs::read(&untouched).expect("read");
+    marked.push(b'\n');
+    fs::write(&untouched, &marked).expect("write");
+
+    add_entry(&state, "Series A", "a2");
+    state.save_cache();
+    assert_eq!(file_with(&state, "a2"), file_with(&state, "a1"));
+    assert_eq!(fs::read(&untouched).expect("read"), marked);
+    assert_eq!(keys(&state.reload()), ["a1", "a2", "b1"]);
+}
+
+#[test]
+fn a_corrupt_series_file_only_loses_that_series() {
+    let _layout = use_layout("per-series");
+    let state = common::state("series-corrupt");
+    add_entry(&state, "Series A", "a1");
+    add_entry(&state, "Series B", "b1");
+    state.save_cache();
+    fs::write(file_with(&state, "a1"), b"{ not json").expect("corrupt");
+
+    let reloaded = state.reload();
+    assert_eq!(keys(&reloaded), ["b1"]);
+
+    // Neither saving series A again nor sweeping the file of the emptied series B touches it
+    reloaded.cache.write().expect("lock").clear();
+    add_entry(&reloaded, "Series A", "a2");
+    reloaded.save_cache();
+    let malformed = names_with(&series_dir(&state), ".malformed-");
+    assert_eq!(malformed.len(), 1, "{malformed:?}");
+    assert_eq!(
+        fs::read(series_dir(&state).join(&malformed[0])).expect("read"),
+        b"{ not json"
+    );
+    assert_eq!(keys(&state.reload()), ["a2"]);
+}
+
+#[test]
+fn the_first_start_migrates_the_single_file() {
+    let _layout = use_layout("per-series");
+    let dir = common::ScratchDir::new("series-migrate");
+    let single = dir.path().join("ocr-cache.json");
+    let entry = |key: &str| json!({ "context": "Series", "data": [common::block(key)] });
+    let file = json!({
+        "cache": { "p1": entry("p1"), "p2": entry("p2") },
+        "chapter_pages_map": { "chapter": 2 },
+    });
+    fs::write(&single, file.to_string()).expect("write cache");
+
+    let state = AppState::new(dir.path().to_path_buf());
+    assert_eq!(keys(&state), ["p1", "p2"]);
+    assert!(single.exists(), "kept until the series files are written");
+
+    state.save_cache();
+    assert!(!single.exists(), "a switch back mustn't find stale entries");
+    assert_eq!(names_with(dir.path(), "ocr-cache.json.migrated-").len(), 1);
+    let reloaded = AppState::new(dir.path().to_path_buf());
+    assert_eq!(keys(&reloaded), ["p1", "p2"]);
+    assert_eq!(
+        reloaded.chapter_pages_map.read().expect("lock")["chapter"],
+        2
+    );
+}
+
+#[test]
+fn switching_back_migrates_the_series_files() {
+    let _layout = use_layout("per-series");
+    let state = common::state("series-switch-back");
+    add_entry(&state, "Series A", "a1");
+    state.save_cache();
+
+    set_layout("single");
+    let single = state.reload();
+    assert_eq!(keys(&single), ["a1"]);
+    single.save_cache();
+    assert!(!series_dir(&state).exists());
+    assert_eq!(names_with(state.dir.path(), "ocr-cache.migrated-").len(), 1);
+
+    set_layout("per-series");
+    add_entry(&single, "Series A", "a2");
+    single.save_cache();
+    assert_eq!(keys(&state.reload()), ["a1", "a2"]);
+}
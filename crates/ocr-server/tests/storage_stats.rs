@@ -0,0 +1,39 @@
+mod common;
+
+use axum::{Json, extract::State};
+use mangatan_ocr_server::handlers;
+
+#[tokio::test]
+async fn reports_cache_file_size_and_entries() {
+    let state = common::state("storage-stats");
+
+    let Json(empty) = handlers::storage_stats_handler(State(state.clone()))
+        .await
+        .expect("stats");
+    assert_eq!((empty.cache_bytes, empty.cache_entries), (0, 0));
+
+    for page in ["page-1", "page-2"] {
+        state.cache.write().expect("lock").insert(
+            page.to_string(),
+            common::entry("Series", vec![common::block("こんにちは")]),
+        );
+    }
+    state.cache_changed();
+    state.flush_cache();
+
+    let Json(stats) = handlers::storage_stats_handler(State(state.clone()))
+        .await
+        .expect("stats");
+    assert_eq!(stats.cache_entries, 2);
+    assert!(!stats.unsaved_changes);
+    assert_eq!(
+        stats.cache_bytes,
+        std::fs::metadata(&state.cache_path)
+            .expect("cache file")
+            .len()
+    );
+    assert_eq!(
+        stats.total_bytes,
+        stats.cache_bytes + stats.image_cache.map_or(0, |images| images.bytes)
+    );
+}
@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+use mangatan_ocr_server::{
+    error::{ApiError, ErrorCode},
+    logic,
+};
+
+#[test]
+fn total_timeout_defaults_to_ninety_seconds() {
+    assert_eq!(
+        logic::parse_total_timeout(None),
+        Some(Duration::from_secs(90))
+    );
+    assert_eq!(
+        logic::parse_total_timeout(Some("soon")),
+        Some(Duration::from_secs(90))
+    );
+}
+
+#[test]
+fn total_timeout_can_be_changed_or_disabled() {
+    assert_eq!(
+        logic::parse_total_timeout(Some(" 30 ")),
+        Some(Duration::from_secs(30))
+    );
+    assert_eq!(logic::parse_total_timeout(Some("0")), None);
+}
+
+#[test]
+fn deadline_errors_are_retryable() {
+    let err = anyhow::anyhow!("Timed out fetching the page (MANGATAN_OCR_TOTAL_TIMEOUT)");
+    let code = ApiError::classify(&err);
+    assert_eq!(code, ErrorCode::Unavailable);
+    assert!(code.retryable());
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use std::time::Duration;
+
+use common::TestState;
+use mangatan_ocr_server::{
+    state::{self, AppState},
+    watchdog,
+};
+
+fn fresh_state(name: &str) -> TestState {
+    let mut state = common::state(name);
+    state.flush_interval = Some(Duration::from_secs(3600));
+    state
+}
+
+fn add_entry(state: &AppState, key: &str) {
+    state
+        .cache
+        .write()
+        .expect("lock")
+        .insert(key.to_string(), common::entry("test", Vec::new()));
+    state.cache_changed();
+}
+
+#[test]
+fn only_pending_changes_count_as_a_stall() {
+    let mut state = fresh_state("watchdog-pending");
+    state.saver_stall_after = Some(Duration::ZERO);
+    assert!(!state.saver_health().stalled, "nothing to save");
+
+    add_entry(&state, "page-1");
+    let health = state.saver_health();
+    assert!(health.stalled);
+    assert_eq!(health.last_activity, "started");
+
+    state.save_cache();
+    let health = state.saver_health();
+    assert!(!health.stalled);
+    assert_eq!(health.last_activity, "saved cache");
+}
+
+#[test]
+fn a_save_that_never_finishes_is_a_stall() {
+    let mut state = fresh_state("watchdog-busy");
+    state.saver_stall_after = Some(Duration::ZERO);
+    state.saver_heartbeat.start("saving cache");
+    // An idle tick from the flusher must not hide the stuck save
+    state.saver_heartbeat.idle();
+
+    let health = state.saver_health();
+    assert!(health.stalled);
+    assert_eq!(health.last_activity, "saving cache");
+}
+
+#[test]
+fn detection_can_be_switched_off() {
+    let mut state = fresh_state("watchdog-off");
+    state.saver_stall_after = None;
+    add_entry(&state, "page-1");
+    assert!(!state.saver_health().stalled);
+}
+
+#[tokio::test]
+async fn force_save_writes_pending_changes() {
+    let state = fresh_state("watchdog-force");
+    add_entry(&state, "page-1");
+    assert!(!state.cache_path.exists());
+
+    watchdog::force_save(state.clone(), Duration::from_secs(10))
+        .await
+        .expect("forced save");
+    assert!(!state.has_unsaved_changes());
+    assert!(state.cache_path.exists());
+}
+
+#[tokio::test]
+async fn background_tasks_stop_on_shutdown() {
+    let mut state = fresh_state("watchdog-shutdown");
+    state.saver_stall_after = Some(Duration::from_secs(60));
+    let saver = tokio::spawn(state::flush_periodically(
+        state.clone(),
+        Duration::from_secs(3600),
+    ));
+    let watcher = tokio::spawn(watchdog::watch_saver(state.clone()));
+
+    state.shutdown.cancel();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        saver.await.expect("saver");
+        watcher.await.expect("watcher");
+    })
+    .await
+    .expect("both stop");
+}
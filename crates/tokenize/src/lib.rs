@@ -0,0 +1,167 @@
+use std::{
+    fmt,
+    ops::Range,
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use lindera::{
+    dictionary::{DictionaryKind, load_dictionary_from_kind},
+    mode::Mode,
+    segmenter::Segmenter,
+    tokenizer::Tokenizer as LinderaTokenizer,
+};
+use serde::Serialize;
+use tracing::{info, warn};
+
+// UniDic feature columns
+const POS_FIELD: usize = 0;
+const LEMMA_FIELD: usize = 7;
+const PRONUNCIATION_FIELD: usize = 9;
+/// At most one warning per this long about Lindera failing, however often it does.
+const FAILURE_LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+/// One word of segmented text.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub text: String,
+    /// Character (not byte) offsets within the tokenized text.
+    pub start: usize,
+    pub end: usize,
+    #[serde(skip)]
+    pub byte_range: Range<usize>,
+    /// Dictionary form, e.g. 食べる for 食べ.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lemma: Option<String>,
+    /// Pronunciation in katakana.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reading: Option<String>,
+    /// Top-level part of speech (名詞, 動詞, ...).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub part_of_speech: Option<String>,
+}
+
+/// Lindera couldn't segment some text.
+#[derive(Debug, Clone)]
+pub struct TokenizeError(String);
+
+impl fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tokenizing failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for TokenizeError {}
+
+/// Lindera with UniDic, shared by the dictionary lookup and the OCR server.
+pub struct Tokenizer {
+    inner: LinderaTokenizer,
+    failures: AtomicU64,
+    // When failures were last logged, and the count at that point
+    last_report: Mutex<Option<(Instant, u64)>>,
+}
+
+impl Tokenizer {
+    /// Loads UniDic. That takes a moment and a fair amount of memory, so prefer [`shared`].
+    ///
+    /// [`shared`]: Self::shared
+    pub fn new() -> Self {
+        info!("⏳ [Tokenize] Initializing Lindera (UniDic)...");
+        let dictionary = load_dictionary_from_kind(DictionaryKind::UniDic)
+            .expect("Failed to load UniDic dictionary");
+        let segmenter = Segmenter::new(Mode::Normal, dictionary, None);
+        info!("✅ [Tokenize] Lindera Initialized.");
+        Self {
+            inner: LinderaTokenizer::new(segmenter),
+            failures: AtomicU64::new(0),
+            last_report: Mutex::new(None),
+        }
+    }
+
+    /// The process-wide tokenizer, loaded on first use.
+    pub fn shared() -> Arc<Self> {
+        static SHARED: OnceLock<Arc<Tokenizer>> = OnceLock::new();
+        SHARED.get_or_init(|| Arc::new(Self::new())).clone()
+    }
+
+    /// Segments `text` into words. Empty if Lindera fails on it.
+    pub fn tokenize(&self, text: &str) -> Vec<Token> {
+        self.try_tokenize(text).unwrap_or_default()
+    }
+
+    /// Times Lindera failed since startup.
+    pub fn failures(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    /// Like [`tokenize`](Self::tokenize), for callers that can do something better than an
+    /// empty result. Failures are logged either way, at most every [`FAILURE_LOG_INTERVAL`].
+    pub fn try_tokenize(&self, text: &str) -> Result<Vec<Token>, TokenizeError> {
+        let mut tokens = match self.inner.tokenize(text) {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                let error = TokenizeError(e.to_string());
+                self.report_failure(text, &error);
+                return Err(error);
+            }
+        };
+
+        let mut chars_before = 0;
+        let mut counted_to = 0;
+        Ok(tokens
+            .iter_mut()
+            .map(|token| {
+                let byte_range = token.byte_start..token.byte_end;
+                chars_before += text
+                    .get(counted_to..byte_range.start)
+                    .map_or(0, |gap| gap.chars().count());
+                counted_to = byte_range.start;
+                let surface = token.text.to_string();
+                let start = chars_before;
+                let end = start + surface.chars().count();
+
+                let details = token.details();
+                let field = |index: usize| {
+                    details
+                        .get(index)
+                        .filter(|value| **value != "*")
+                        .map(|value| value.to_string())
+                };
+                Token {
+                    text: surface,
+                    start,
+                    end,
+                    byte_range,
+                    lemma: field(LEMMA_FIELD),
+                    reading: field(PRONUNCIATION_FIELD),
+                    part_of_speech: field(POS_FIELD),
+                }
+            })
+            .collect())
+    }
+
+    fn report_failure(&self, text: &str, error: &TokenizeError) {
+        let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut last_report = self.last_report.lock().expect("lock shouldn't panic");
+        let since = match *last_report {
+            Some((at, _)) if at.elapsed() < FAILURE_LOG_INTERVAL => return,
+            Some((_, reported)) => failures - reported,
+            None => 1,
+        };
+        *last_report = Some((Instant::now(), failures));
+        warn!(
+            "⚠️ [Tokenize] {error} ({} chars of input); {since} failures since the last report, \
+             {failures} in total",
+            text.chars().count()
+        );
+    }
+}
+
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,22 @@
+use mangatan_tokenize::Tokenizer;
+
+#[test]
+fn tokens_cover_the_text_in_order() {
+    let text = "猫が好きです。";
+    let tokens = Tokenizer::shared().tokenize(text);
+    assert!(!tokens.is_empty());
+
+    let mut next = 0;
+    for token in &tokens {
+        assert_eq!(token.start, next, "{tokens:?}");
+        assert_eq!(&text[token.byte_range.clone()], token.text);
+        assert_eq!(token.end - token.start, token.text.chars().count());
+        next = token.end;
+    }
+    assert_eq!(next, text.chars().count());
+
+    // Byte offsets are for the servers; clients get character offsets only
+    let json = serde_json::to_value(&tokens[0]).expect("serializes");
+    assert!(json.get("byte_range").is_none());
+    assert_eq!(json["start"], 0);
+}
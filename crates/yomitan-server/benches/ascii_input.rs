@@ -0,0 +1,53 @@
+//! Lookup latency for text without any Japanese in it (pasted English, base64 selected by
+//! accident), with and without the script fast path, against the bundled JMdict.
+//!
+//! Run with `cargo bench -p mangatan-yomitan-server --bench ascii_input`. Set
+//! `MANGATAN_BENCH_DATA_DIR` to reuse an already imported database between runs.
+
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use mangatan_yomitan_server::{PREBAKED_DICT, import, lookup::LookupService, state::AppState};
+
+const QUERIES: &[&str] = &[
+    "The quick brown fox jumps over the lazy dog, again and again.",
+    "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==",
+    "https://example.com/manga/1234/chapter/56?page=7&token=abcdef",
+];
+const ROUNDS: usize = 50;
+
+fn main() {
+    let data_dir = std::env::var_os("MANGATAN_BENCH_DATA_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("mangatan-preload-bench"));
+    let state = AppState::new(data_dir).expect("state");
+    if state.dictionaries.read().expect("lock").is_empty() {
+        println!("Importing bundled dictionary...");
+        import::import_zip(&state, PREBAKED_DICT).expect("import should succeed");
+    }
+
+    // Uncached, so every round does the full lookup
+    let lookup = LookupService::new().with_lookup_cache_size(0);
+    let fast = measure(&lookup, &state);
+    let lookup = lookup.with_script_fast_path(false);
+    let full = measure(&lookup, &state);
+
+    println!("ascii lookup, fast path: {fast:?}/lookup");
+    println!("ascii lookup, full scan: {full:?}/lookup");
+}
+
+fn measure(lookup: &LookupService, state: &AppState) -> Duration {
+    for query in QUERIES {
+        lookup.search(state, query, 0);
+    }
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for query in QUERIES {
+            std::hint::black_box(lookup.search(state, query, 0));
+        }
+    }
+    start.elapsed() / (ROUNDS * QUERIES.len()) as u32
+}
@@ -0,0 +1,59 @@
+//! Single-kana lookup latency with and without the term preload, against the bundled JMdict.
+//!
+//! Run with `cargo bench -p mangatan-yomitan-server --bench preload`. Set
+//! `MANGATAN_BENCH_DATA_DIR` to reuse an already imported database between runs.
+
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use mangatan_yomitan_server::{PREBAKED_DICT, import, lookup::LookupService, state::AppState};
+
+const QUERIES: &[&str] = &["の", "は", "が", "を", "に", "で", "と", "も", "か", "へ"];
+const ROUNDS: usize = 200;
+
+fn main() {
+    let data_dir = std::env::var_os("MANGATAN_BENCH_DATA_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("mangatan-preload-bench"));
+    let state = AppState::new(data_dir).expect("state");
+    if state.dictionaries.read().expect("lock").is_empty() {
+        println!("Importing bundled dictionary...");
+        import::import_zip(&state, PREBAKED_DICT).expect("import should succeed");
+    }
+    // Uncached, so repeated queries measure the preload rather than the lookup cache
+    let lookup = LookupService::new().with_lookup_cache_size(0);
+
+    while state.preload.stats().building {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    let stats = state.preload.stats();
+    println!(
+        "Preloaded {} terms ({} KiB)",
+        stats.terms,
+        stats.bytes / 1024
+    );
+
+    let preloaded = measure(&lookup, &state);
+    state.preload.clear();
+    let sqlite = measure(&lookup, &state);
+
+    println!("single-kana lookup, preload: {preloaded:?}/lookup");
+    println!("single-kana lookup, sqlite:  {sqlite:?}/lookup");
+}
+
+fn measure(lookup: &LookupService, state: &AppState) -> Duration {
+    // Warm the page cache so both runs measure lookups, not first reads
+    for query in QUERIES {
+        lookup.search(state, query, 0);
+    }
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for query in QUERIES {
+            std::hint::black_box(lookup.search(state, query, 0));
+        }
+    }
+    start.elapsed() / (ROUNDS * QUERIES.len()) as u32
+}
@@ -0,0 +1,439 @@
+use axum::http::StatusCode;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+const DEFAULT_ANKI_URL: &str = "http://127.0.0.1:8765";
+const DEFAULT_QUERY_TEMPLATE: &str = "Word:{term}";
+const DEFAULT_TTL_SECS: u64 = 300;
+// Lookups must stay snappy, so an unresponsive Anki is given up on quickly and left alone
+// for a while before the next attempt.
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(800);
+const UNREACHABLE_BACKOFF: Duration = Duration::from_secs(30);
+/// Hosts that never go through `MANGATAN_HTTP_PROXY`.
+const LOCAL_HOSTS: &str = "localhost,127.0.0.1,::1";
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AnkiStatus {
+    Yes,
+    No,
+    Unknown,
+}
+
+/// What an Anki endpoint answered, for the WebUI to key translations and actions off.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnkiCode {
+    // Errors
+    AnkiDisabled,
+    AnkiUnreachable,
+    InvalidResponse,
+    /// AnkiConnect ran the action and returned an error.
+    AnkiRejected,
+    // Duplicate check
+    DuplicateFound,
+    NoDuplicate,
+    // Field validation
+    FieldsValid,
+    ModelUnknown,
+    FieldUnknown,
+}
+
+/// Body of every `/anki/*` response: a stable `code`, the numbers and names a translated message
+/// needs in `details`, and a default English `message`.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct AnkiEnvelope {
+    pub code: AnkiCode,
+    pub message: String,
+    /// Always an object, empty when there's nothing to add.
+    pub details: Value,
+}
+
+impl AnkiEnvelope {
+    pub fn new(code: AnkiCode, message: impl Into<String>, details: Value) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Clone, Debug, PartialEq, Eq)]
+pub enum AnkiError {
+    #[error("Anki integration is disabled")]
+    Disabled,
+    #[error("AnkiConnect unreachable: {0}")]
+    Unreachable(String),
+    #[error("Invalid AnkiConnect response: {0}")]
+    InvalidResponse(String),
+    #[error("AnkiConnect error: {0}")]
+    Rejected(String),
+}
+
+impl AnkiError {
+    pub fn code(&self) -> AnkiCode {
+        match self {
+            Self::Disabled => AnkiCode::AnkiDisabled,
+            Self::Unreachable(_) => AnkiCode::AnkiUnreachable,
+            Self::InvalidResponse(_) => AnkiCode::InvalidResponse,
+            Self::Rejected(_) => AnkiCode::AnkiRejected,
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::Disabled => StatusCode::NOT_FOUND,
+            _ => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    /// `details.reason` carries the underlying error, untranslated.
+    pub fn envelope(&self) -> AnkiEnvelope {
+        let details = match self {
+            Self::Disabled => json!({}),
+            Self::Unreachable(reason) | Self::InvalidResponse(reason) | Self::Rejected(reason) => {
+                json!({ "reason": reason })
+            }
+        };
+        AnkiEnvelope::new(self.code(), self.to_string(), details)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AnkiConfig {
+    pub url: String,
+    /// AnkiConnect search query; `{term}` is replaced with the headword.
+    pub query_template: String,
+    /// Query deciding what counts as a duplicate before adding a card, e.g.
+    /// `deck:Mining Word:{term}`. Falls back to `query_template`.
+    pub duplicate_query: String,
+    pub ttl: Duration,
+}
+
+impl AnkiConfig {
+    /// Enabled by `MANGATAN_YOMITAN_ANKI_CHECK`. The URL comes from `MANGATAN_ANKI_URL`, the
+    /// query from `MANGATAN_YOMITAN_ANKI_QUERY`, the duplicate scope from
+    /// `MANGATAN_YOMITAN_ANKI_DUPLICATE_QUERY` and the cache TTL from
+    /// `MANGATAN_YOMITAN_ANKI_TTL_SECS`.
+    pub fn from_env() -> Option<Self> {
+        let enabled = std::env::var("MANGATAN_YOMITAN_ANKI_CHECK")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        if !enabled {
+            return None;
+        }
+
+        let ttl_secs = std::env::var("MANGATAN_YOMITAN_ANKI_TTL_SECS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(DEFAULT_TTL_SECS);
+
+        let query_template = std::env::var("MANGATAN_YOMITAN_ANKI_QUERY")
+            .unwrap_or_else(|_| DEFAULT_QUERY_TEMPLATE.into());
+
+        Some(Self {
+            url: std::env::var("MANGATAN_ANKI_URL").unwrap_or_else(|_| DEFAULT_ANKI_URL.into()),
+            duplicate_query: std::env::var("MANGATAN_YOMITAN_ANKI_DUPLICATE_QUERY")
+                .unwrap_or_else(|_| query_template.clone()),
+            query_template,
+            ttl: Duration::from_secs(ttl_secs),
+        })
+    }
+}
+
+/// Answers "is this word already in my deck?" for lookup results via AnkiConnect `findNotes`.
+pub struct AnkiChecker {
+    config: Option<AnkiConfig>,
+    client: reqwest::Client,
+    cache: Mutex<HashMap<String, (bool, Instant)>>,
+    unreachable_until: Mutex<Option<Instant>>,
+}
+
+impl AnkiChecker {
+    pub fn new(config: Option<AnkiConfig>) -> Self {
+        if let Some(config) = &config {
+            info!(
+                "🃏 [Yomitan] Anki duplicate check enabled ({}, query: {})",
+                config.url, config.query_template
+            );
+        }
+
+        let mut client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT);
+        // A remote AnkiConnect is reached through the outbound proxy like other internet traffic
+        if let Some(url) = std::env::var("MANGATAN_HTTP_PROXY")
+            .ok()
+            .filter(|url| !url.trim().is_empty())
+        {
+            match reqwest::Proxy::all(url.trim()) {
+                Ok(proxy) => {
+                    client =
+                        client.proxy(proxy.no_proxy(reqwest::NoProxy::from_string(LOCAL_HOSTS)))
+                }
+                Err(e) => warn!("Ignoring invalid MANGATAN_HTTP_PROXY: {e}"),
+            }
+        }
+
+        Self {
+            config,
+            client: client.build().unwrap_or_default(),
+            cache: Mutex::new(HashMap::new()),
+            unreachable_until: Mutex::new(None),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.is_some()
+    }
+
+    /// Looks up every term, answering from the cache where possible and sending the rest to
+    /// Anki in a single `multi` request. Terms are `unknown` when Anki can't be reached.
+    pub async fn statuses(&self, terms: &[String]) -> HashMap<String, AnkiStatus> {
+        let Some(config) = &self.config else {
+            return HashMap::new();
+        };
+
+        let mut statuses = HashMap::new();
+        let mut missing = Vec::new();
+        {
+            let cache = self.cache.lock().expect("lock");
+            for term in terms {
+                match cache.get(term) {
+                    Some((found, at)) if at.elapsed() < config.ttl => {
+                        statuses.insert(term.clone(), to_status(*found));
+                    }
+                    _ => missing.push(term.clone()),
+                }
+            }
+        }
+        missing.sort();
+        missing.dedup();
+
+        if missing.is_empty() {
+            return statuses;
+        }
+
+        let backing_off = self
+            .unreachable_until
+            .lock()
+            .expect("lock")
+            .is_some_and(|until| Instant::now() < until);
+
+        let found = if backing_off {
+            None
+        } else {
+            self.find_notes(config, &missing).await
+        };
+
+        match found {
+            Some(found) => {
+                let now = Instant::now();
+                let mut cache = self.cache.lock().expect("lock");
+                cache.retain(|_, (_, at)| at.elapsed() < config.ttl);
+                for (term, in_anki) in missing.into_iter().zip(found) {
+                    cache.insert(term.clone(), (in_anki, now));
+                    statuses.insert(term, to_status(in_anki));
+                }
+            }
+            None => {
+                for term in missing {
+                    statuses.insert(term, AnkiStatus::Unknown);
+                }
+            }
+        }
+
+        statuses
+    }
+
+    /// Returns the id of an existing note matching the duplicate-scope query, ignoring case and
+    /// full/half-width differences in the term. Meant to be called right before adding a card;
+    /// unlike `statuses` it bypasses the cache so a note added a moment ago is still caught.
+    pub async fn find_duplicate(
+        &self,
+        term: &str,
+        reading: &str,
+    ) -> Result<Option<i64>, AnkiError> {
+        let Some(config) = &self.config else {
+            return Err(AnkiError::Disabled);
+        };
+
+        let mut variants = vec![term.to_string(), fold_width_and_case(term)];
+        variants.dedup();
+        let query = variants
+            .iter()
+            .map(|variant| {
+                let scoped = config
+                    .duplicate_query
+                    .replace("{term}", &escape_query(variant))
+                    .replace("{reading}", &escape_query(reading));
+                format!("({scoped})")
+            })
+            .collect::<Vec<_>>()
+            .join(" OR ");
+
+        let result = self
+            .invoke(config, "findNotes", json!({ "query": query }))
+            .await?;
+        Ok(result
+            .as_array()
+            .and_then(|ids| ids.iter().filter_map(Value::as_i64).min()))
+    }
+
+    /// AnkiConnect's API version, to check that it answers at all.
+    pub async fn version(&self) -> Result<i64, AnkiError> {
+        let Some(config) = &self.config else {
+            return Err(AnkiError::Disabled);
+        };
+
+        let version = self.invoke(config, "version", json!({})).await?;
+        version
+            .as_i64()
+            .ok_or_else(|| AnkiError::InvalidResponse(format!("version {version}")))
+    }
+
+    /// Fields of the note type `model`, in Anki's order. `None` when the note type doesn't exist.
+    pub async fn model_field_names(&self, model: &str) -> Result<Option<Vec<String>>, AnkiError> {
+        let Some(config) = &self.config else {
+            return Err(AnkiError::Disabled);
+        };
+
+        let models = self.invoke(config, "modelNames", json!({})).await?;
+        let exists = models
+            .as_array()
+            .is_some_and(|names| names.iter().any(|name| name.as_str() == Some(model)));
+        if !exists {
+            return Ok(None);
+        }
+
+        let fields = self
+            .invoke(config, "modelFieldNames", json!({ "modelName": model }))
+            .await?;
+        Ok(Some(
+            fields
+                .as_array()
+                .map(|names| {
+                    names
+                        .iter()
+                        .filter_map(|name| name.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        ))
+    }
+
+    /// Sends one AnkiConnect action and returns its `result`, or its `error` as `Err`.
+    async fn invoke(
+        &self,
+        config: &AnkiConfig,
+        action: &str,
+        params: Value,
+    ) -> Result<Value, AnkiError> {
+        let body = json!({
+            "action": action,
+            "version": 6,
+            "params": params,
+        });
+        let mut response: Value = self
+            .client
+            .post(&config.url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| AnkiError::Unreachable(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| AnkiError::InvalidResponse(e.to_string()))?;
+
+        if let Some(error) = response.get("error").and_then(Value::as_str) {
+            return Err(AnkiError::Rejected(error.to_string()));
+        }
+        Ok(response
+            .get_mut("result")
+            .map(Value::take)
+            .unwrap_or_default())
+    }
+
+    async fn find_notes(&self, config: &AnkiConfig, terms: &[String]) -> Option<Vec<bool>> {
+        let actions: Vec<Value> = terms
+            .iter()
+            .map(|term| {
+                let query = config.query_template.replace("{term}", &escape_query(term));
+                json!({ "action": "findNotes", "params": { "query": query } })
+            })
+            .collect();
+
+        let body = json!({
+            "action": "multi",
+            "version": 6,
+            "params": { "actions": actions },
+        });
+
+        let response = self.client.post(&config.url).json(&body).send().await;
+        let parsed = match response {
+            Ok(resp) => resp.json::<Value>().await.ok(),
+            Err(e) => {
+                warn!("⚠️ [Yomitan] AnkiConnect unreachable: {}", e);
+                None
+            }
+        };
+
+        let results = parsed.as_ref().and_then(|v| v.get("result")?.as_array());
+        let Some(results) = results.filter(|r| r.len() == terms.len()) else {
+            *self.unreachable_until.lock().expect("lock") =
+                Some(Instant::now() + UNREACHABLE_BACKOFF);
+            return None;
+        };
+
+        // `multi` wraps each result as `{ result, error }` on version 6, bare arrays before that
+        Some(
+            results
+                .iter()
+                .map(|r| {
+                    r.get("result")
+                        .unwrap_or(r)
+                        .as_array()
+                        .is_some_and(|ids| !ids.is_empty())
+                })
+                .collect(),
+        )
+    }
+}
+
+fn to_status(found: bool) -> AnkiStatus {
+    if found {
+        AnkiStatus::Yes
+    } else {
+        AnkiStatus::No
+    }
+}
+
+/// Maps full-width ASCII (and the ideographic space) to half-width and lowercases, so `ＡＢＣ`,
+/// `abc` and `ABC` land on the same search term.
+fn fold_width_and_case(term: &str) -> String {
+    term.chars()
+        .map(|c| match c {
+            '\u{3000}' => ' ',
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            _ => c,
+        })
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Escapes characters that Anki's search syntax would otherwise treat as operators.
+fn escape_query(term: &str) -> String {
+    let mut escaped = String::with_capacity(term.len());
+    for c in term.chars() {
+        if matches!(c, '\\' | '"' | '*' | '_' | ':' | '(' | ')') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
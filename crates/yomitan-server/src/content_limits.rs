@@ -0,0 +1,109 @@
+//! Guards against broken community dictionaries: structured content nested deep enough to
+//! overflow the stack when it's walked, and single glossaries of several megabytes that bloat
+//! every lookup response they appear in.
+
+use serde_json::Value;
+
+/// Deepest structured-content nesting kept on import; real dictionaries stay far below this.
+pub const DEFAULT_MAX_DEPTH: usize = 48;
+/// Largest single glossary item kept on import, in bytes of JSON.
+pub const DEFAULT_MAX_GLOSSARY_BYTES: usize = 1024 * 1024;
+/// Largest record content sent in a lookup response before it's replaced by [`TRUNCATED_MARKER`].
+pub const DEFAULT_MAX_RECORD_BYTES: usize = 64 * 1024;
+
+/// Stands in for content cut off on import or left out of a lookup response.
+pub const TRUNCATED_MARKER: &str = "[Content truncated, view full entry]";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ContentLimits {
+    pub max_depth: usize,
+    pub max_glossary_bytes: usize,
+    pub max_record_bytes: usize,
+}
+
+impl Default for ContentLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_glossary_bytes: DEFAULT_MAX_GLOSSARY_BYTES,
+            max_record_bytes: DEFAULT_MAX_RECORD_BYTES,
+        }
+    }
+}
+
+impl ContentLimits {
+    /// Defaults, overridden by `MANGATAN_YOMITAN_MAX_CONTENT_DEPTH`,
+    /// `MANGATAN_YOMITAN_MAX_GLOSSARY_BYTES` and `MANGATAN_YOMITAN_MAX_RECORD_BYTES`. `0` lifts a
+    /// size limit; the depth limit can only be raised.
+    pub fn from_env() -> Self {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse::<usize>().ok())
+        };
+        let size = |name: &str, default: usize| match var(name) {
+            Some(0) => usize::MAX,
+            Some(bytes) => bytes,
+            None => default,
+        };
+        Self {
+            max_depth: var("MANGATAN_YOMITAN_MAX_CONTENT_DEPTH")
+                .filter(|depth| *depth > 0)
+                .unwrap_or(DEFAULT_MAX_DEPTH),
+            max_glossary_bytes: size(
+                "MANGATAN_YOMITAN_MAX_GLOSSARY_BYTES",
+                DEFAULT_MAX_GLOSSARY_BYTES,
+            ),
+            max_record_bytes: size(
+                "MANGATAN_YOMITAN_MAX_RECORD_BYTES",
+                DEFAULT_MAX_RECORD_BYTES,
+            ),
+        }
+    }
+
+    /// Applies the import limits to one structured-content glossary item and returns it as the
+    /// JSON string that gets stored, plus whether anything was cut.
+    pub fn limit_structured(&self, mut content: Value) -> (String, bool) {
+        let cut = truncate_depth(&mut content, self.max_depth);
+        let json = content.to_string();
+        if json.len() > self.max_glossary_bytes {
+            return (TRUNCATED_MARKER.to_string(), true);
+        }
+        (json, cut)
+    }
+
+    /// Applies the size limit to one plain-text glossary item.
+    pub fn limit_text(&self, text: &str) -> (String, bool) {
+        if text.len() <= self.max_glossary_bytes {
+            return (text.to_string(), false);
+        }
+        let mut end = self.max_glossary_bytes;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        (format!("{}{TRUNCATED_MARKER}", &text[..end]), true)
+    }
+}
+
+/// Replaces whatever is nested more than `max_depth` arrays or objects deep with
+/// [`TRUNCATED_MARKER`]. Returns whether anything was replaced. Never recurses deeper than
+/// `max_depth`, however deep the input.
+pub fn truncate_depth(value: &mut Value, max_depth: usize) -> bool {
+    if !(value.is_array() || value.is_object()) {
+        return false;
+    }
+    if max_depth == 0 {
+        *value = Value::String(TRUNCATED_MARKER.to_string());
+        return true;
+    }
+    let children: Vec<&mut Value> = match value {
+        Value::Array(items) => items.iter_mut().collect(),
+        Value::Object(fields) => fields.values_mut().collect(),
+        _ => Vec::new(),
+    };
+    let mut cut = false;
+    for child in children {
+        cut |= truncate_depth(child, max_depth - 1);
+    }
+    cut
+}
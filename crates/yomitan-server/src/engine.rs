@@ -0,0 +1,112 @@
+//! The lookup service behind `/lookup` and `/tap`. Building it loads Lindera's UniDic, which on
+//! phones takes long enough to hold up the whole server, so it can load in the background
+//! instead while lookups answer 503.
+
+use serde::Serialize;
+use std::{
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
+};
+use tracing::{error, info};
+
+use crate::lookup::LookupService;
+
+/// Reads `MANGATAN_YOMITAN_LAZY_ENGINE`: whether the server starts before the engine has loaded.
+pub fn lazy_from_env() -> bool {
+    std::env::var("MANGATAN_YOMITAN_LAZY_ENGINE").is_ok_and(|v| v == "1" || v == "true")
+}
+
+/// Where loading stands, as reported by `/engine-status`.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum EngineStatus {
+    Loading {
+        elapsed_ms: u64,
+    },
+    /// `load_ms` is 0 when the engine was built before the server started.
+    Ready {
+        load_ms: u64,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+/// A [`LookupService`] that is either ready or still being built.
+#[derive(Clone)]
+pub struct LookupEngine {
+    ready: Arc<OnceLock<(Arc<LookupService>, Duration)>>,
+    failure: Arc<OnceLock<String>>,
+    created_at: Instant,
+}
+
+impl LookupEngine {
+    /// An engine that has yet to be [`load`](Self::load)ed.
+    pub fn pending() -> Self {
+        Self {
+            ready: Arc::new(OnceLock::new()),
+            failure: Arc::new(OnceLock::new()),
+            created_at: Instant::now(),
+        }
+    }
+
+    pub fn ready(service: LookupService) -> Self {
+        let engine = Self::pending();
+        let _ = engine.ready.set((Arc::new(service), Duration::ZERO));
+        engine
+    }
+
+    /// Builds the service on a blocking thread, unless it's already there. A panic while
+    /// building (UniDic failing to load) is reported through [`status`](Self::status).
+    pub async fn load(self, build: impl FnOnce() -> LookupService + Send + 'static) {
+        if self.is_ready() {
+            return;
+        }
+        info!("⏳ [Yomitan] Loading the dictionary engine in the background...");
+        match tokio::task::spawn_blocking(build).await {
+            Ok(service) => {
+                let took = self.created_at.elapsed();
+                let _ = self.ready.set((Arc::new(service), took));
+                info!(
+                    "✅ [Yomitan] Dictionary engine ready after {:.1}s",
+                    took.as_secs_f32()
+                );
+            }
+            Err(e) => {
+                error!("❌ [Yomitan] Dictionary engine failed to load: {e}");
+                let _ = self.failure.set(e.to_string());
+            }
+        }
+    }
+
+    /// The service, once it has loaded.
+    pub fn get(&self) -> Option<Arc<LookupService>> {
+        self.ready.get().map(|(service, _)| service.clone())
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.get().is_some()
+    }
+
+    pub fn status(&self) -> EngineStatus {
+        if let Some((_, took)) = self.ready.get() {
+            return EngineStatus::Ready {
+                load_ms: took.as_millis() as u64,
+            };
+        }
+        match self.failure.get() {
+            Some(error) => EngineStatus::Failed {
+                error: error.clone(),
+            },
+            None => EngineStatus::Loading {
+                elapsed_ms: self.created_at.elapsed().as_millis() as u64,
+            },
+        }
+    }
+}
+
+impl From<LookupService> for LookupEngine {
+    fn from(service: LookupService) -> Self {
+        Self::ready(service)
+    }
+}
@@ -0,0 +1,323 @@
+use crate::state::{AppState, StoredRecord, terms_by_term_sql};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::{HashMap, HashSet};
+use tracing::error;
+use wordbase_api::{DictionaryId, Record};
+
+pub const DEFAULT_EXAMPLE_LIMIT: usize = 10;
+pub const MAX_EXAMPLE_LIMIT: usize = 50;
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Example {
+    pub text: String,
+    pub dictionary_name: String,
+}
+
+/// Collects example sentences for `term` from every enabled dictionary (or the enabled ones in
+/// `subset`), highest priority first. When `reading` is given, entries stored under a different
+/// reading are skipped.
+pub fn find_examples(
+    state: &AppState,
+    term: &str,
+    reading: Option<&str>,
+    limit: usize,
+    subset: Option<&HashSet<DictionaryId>>,
+) -> Vec<Example> {
+    let dict_configs: HashMap<DictionaryId, (bool, i64, String)> = {
+        let dicts = state.dictionaries.read().expect("lock");
+        dicts
+            .iter()
+            .map(|(id, d)| (*id, (d.enabled, d.priority, d.name.clone())))
+            .collect()
+    };
+
+    let conn = match state.pool.get() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("❌ Failed to get DB connection: {}", e);
+            return vec![];
+        }
+    };
+    let mut stmt = match conn.prepare(&terms_by_term_sql(subset)) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("❌ DB Prepare Error: {}", e);
+            return vec![];
+        }
+    };
+    let rows: Vec<(i64, Vec<u8>)> =
+        match stmt.query_map([term], |row| Ok((row.get(0)?, row.get(1)?))) {
+            Ok(rows) => rows.flatten().collect(),
+            Err(e) => {
+                error!("❌ DB Query Error: {}", e);
+                return vec![];
+            }
+        };
+
+    let mut decoder = snap::raw::Decoder::new();
+    let mut records: Vec<(i64, String, StoredRecord)> = rows
+        .into_iter()
+        .filter_map(|(dict_id, compressed)| {
+            let (enabled, priority, name) = dict_configs.get(&DictionaryId(dict_id))?;
+            if !enabled {
+                return None;
+            }
+            let decompressed = decoder.decompress_vec(&compressed).ok()?;
+            let stored = serde_json::from_slice::<StoredRecord>(&decompressed).ok()?;
+            Some((*priority, name.clone(), stored))
+        })
+        .collect();
+    records.sort_by_key(|(priority, _, _)| *priority);
+
+    let mut seen = HashSet::new();
+    let mut examples = Vec::new();
+    for (_, dictionary_name, stored) in records {
+        // Headword rows store their reading; reading rows are keyed by it
+        let stored_reading = stored.reading.as_deref().unwrap_or(term);
+        if reading.is_some_and(|r| !r.is_empty() && r != stored_reading && r != term) {
+            continue;
+        }
+        let Record::YomitanGlossary(gloss) = &stored.record else {
+            continue;
+        };
+
+        let needles: Vec<&str> = [term, stored_reading]
+            .into_iter()
+            .filter(|s| !s.is_empty())
+            .collect();
+        for text in extract_examples(&json!(gloss.content), &needles) {
+            if seen.insert(text.clone()) {
+                examples.push(Example {
+                    text,
+                    dictionary_name: dictionary_name.clone(),
+                });
+                if examples.len() >= limit {
+                    return examples;
+                }
+            }
+        }
+    }
+    examples
+}
+
+/// Pulls example sentences out of a glossary: nodes whose `data` attributes mark them as
+/// examples (Yomitan's `example-sentence` convention), and list items that use one of the
+/// `needles` (headword or reading). Structured content stored as a JSON string is parsed first.
+pub fn extract_examples(content: &Value, needles: &[&str]) -> Vec<String> {
+    let mut out = Vec::new();
+    walk(content, needles, &mut out);
+    out
+}
+
+fn walk(node: &Value, needles: &[&str], out: &mut Vec<String>) {
+    match node {
+        Value::String(s) => {
+            let trimmed = s.trim_start();
+            if (trimmed.starts_with('{') || trimmed.starts_with('['))
+                && let Ok(parsed) = serde_json::from_str::<Value>(s)
+            {
+                walk(&parsed, needles, out);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                walk(item, needles, out);
+            }
+        }
+        Value::Object(map) => {
+            if is_example_node(node) {
+                push_text(example_sentence(node), out);
+                return;
+            }
+            if map.get("tag").and_then(Value::as_str) == Some("li") {
+                let text = plain_text(node);
+                if needles.iter().any(|n| text.contains(n)) && !has_nested_list(node) {
+                    push_text(text, out);
+                    return;
+                }
+            }
+            for value in map.values() {
+                walk(value, needles, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn push_text(text: String, out: &mut Vec<String>) {
+    let text = text.trim();
+    if !text.is_empty() {
+        out.push(text.to_string());
+    }
+}
+
+fn data_values(node: &Value) -> impl Iterator<Item = &str> {
+    node.get("data")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flat_map(|data| data.values())
+        .filter_map(Value::as_str)
+}
+
+fn is_example_node(node: &Value) -> bool {
+    data_values(node).any(|v| {
+        let v = v.to_ascii_lowercase();
+        (v == "example" || v == "examples" || v.starts_with("example-sentence"))
+            // The translation half of an example is picked up with its parent
+            && !v.ends_with("-b")
+    })
+}
+
+/// For Yomitan's `example-sentence` containers only the `-a` (original language) half is wanted;
+/// other example nodes are used whole.
+fn example_sentence(node: &Value) -> String {
+    fn find_first_half(node: &Value) -> Option<&Value> {
+        if data_values(node).any(|v| v.eq_ignore_ascii_case("example-sentence-a")) {
+            return Some(node);
+        }
+        match node {
+            Value::Array(items) => items.iter().find_map(find_first_half),
+            Value::Object(map) => map.get("content").and_then(find_first_half),
+            _ => None,
+        }
+    }
+
+    plain_text(find_first_half(node).unwrap_or(node))
+}
+
+fn has_nested_list(node: &Value) -> bool {
+    match node {
+        Value::Array(items) => items.iter().any(has_nested_list),
+        Value::Object(map) => map.get("content").is_some_and(|content| {
+            let is_list = |v: &Value| {
+                matches!(
+                    v.get("tag").and_then(Value::as_str),
+                    Some("ul" | "ol" | "li")
+                )
+            };
+            match content {
+                Value::Array(items) => items.iter().any(|i| is_list(i) || has_nested_list(i)),
+                other => is_list(other) || has_nested_list(other),
+            }
+        }),
+        _ => false,
+    }
+}
+
+/// Flattens structured content to text, dropping furigana (`rt`/`rp`) and images.
+fn plain_text(node: &Value) -> String {
+    fn collect(node: &Value, out: &mut String) {
+        match node {
+            Value::String(s) => out.push_str(s),
+            Value::Array(items) => items.iter().for_each(|i| collect(i, out)),
+            Value::Object(map) => {
+                let tag = map.get("tag").and_then(Value::as_str);
+                if matches!(tag, Some("rt" | "rp" | "img")) {
+                    return;
+                }
+                if tag == Some("br") {
+                    out.push('\n');
+                }
+                if let Some(content) = map.get("content") {
+                    collect(content, out);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut out = String::new();
+    collect(node, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Modelled on Yomitan's JMdict export: a sense with a tagged example sentence pair
+    fn jmdict_style() -> Value {
+        json!([{
+            "type": "structured-content",
+            "content": [
+                { "tag": "ul", "content": [{ "tag": "li", "content": "to eat" }] },
+                {
+                    "tag": "div",
+                    "data": { "content": "example-sentence" },
+                    "content": [
+                        {
+                            "tag": "div",
+                            "data": { "content": "example-sentence-a" },
+                            "content": [
+                                { "tag": "ruby", "content": ["朝", { "tag": "rt", "content": "あさ" }] },
+                                "ご飯を",
+                                { "tag": "span", "content": "食べる" },
+                                "。"
+                            ]
+                        },
+                        {
+                            "tag": "div",
+                            "data": { "content": "example-sentence-b" },
+                            "content": "I eat breakfast."
+                        }
+                    ]
+                }
+            ]
+        }])
+    }
+
+    // Monolingual dictionaries often list usages as plain list items
+    fn monolingual_style() -> Value {
+        json!({
+            "type": "structured-content",
+            "content": {
+                "tag": "ol",
+                "content": [
+                    { "tag": "li", "content": "食物を口に入れ、かんで飲みこむ。" },
+                    { "tag": "li", "content": "「毎朝パンを食べる」" },
+                    { "tag": "li", "content": ["「", { "tag": "ruby", "content": ["生活", { "tag": "rt", "content": "せいかつ" }] }, "をたべる」"] }
+                ]
+            }
+        })
+    }
+
+    #[test]
+    fn extracts_tagged_example_sentences() {
+        assert_eq!(
+            extract_examples(&jmdict_style(), &["食べる", "たべる"]),
+            vec!["朝ご飯を食べる。"]
+        );
+    }
+
+    #[test]
+    fn extracts_list_items_using_the_headword_or_reading() {
+        assert_eq!(
+            extract_examples(&monolingual_style(), &["食べる", "たべる"]),
+            vec!["「毎朝パンを食べる」", "「生活をたべる」"]
+        );
+    }
+
+    #[test]
+    fn parses_structured_content_stored_as_strings() {
+        // The importer keeps structured glossaries as JSON strings
+        let stored = json!([
+            "plain gloss",
+            serde_json::to_string(&monolingual_style()).expect("serialize")
+        ]);
+        assert_eq!(extract_examples(&stored, &["食べる"]).len(), 1);
+    }
+
+    #[test]
+    fn skips_lists_wrapping_other_lists() {
+        let nested = json!({
+            "tag": "li",
+            "content": [
+                "食べる",
+                { "tag": "ul", "content": [{ "tag": "li", "content": "パンを食べる" }] }
+            ]
+        });
+        assert_eq!(extract_examples(&nested, &["食べる"]), vec!["パンを食べる"]);
+    }
+}
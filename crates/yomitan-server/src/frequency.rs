@@ -0,0 +1,244 @@
+use crate::{
+    import::{record_entry_counts, register_dictionary},
+    lookup::normalize_ideographs,
+    state::{AppState, DictionaryInfo, StoredRecord},
+};
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::collections::HashMap;
+use tracing::info;
+use wordbase_api::{Record, dict::yomitan::Glossary};
+
+/// Where the fields of a frequency list row are, and how rows are split.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    /// Zero-based column of the word.
+    pub term_column: usize,
+    /// Zero-based column of the reading, for lists that tell homographs apart.
+    pub reading_column: Option<usize>,
+    /// Zero-based column of the rank (1 is the most common word).
+    pub rank_column: usize,
+    /// Field separator. `None` guesses tab or comma from the first line.
+    pub delimiter: Option<char>,
+    /// Whether the first line is column names rather than a row.
+    pub has_header: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            term_column: 0,
+            reading_column: None,
+            rank_column: 1,
+            delimiter: None,
+            has_header: false,
+        }
+    }
+}
+
+/// A frequency list row, after duplicates have been resolved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrequencyRow {
+    pub term: String,
+    pub reading: Option<String>,
+    pub rank: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParsedList {
+    /// Distinct terms in order of first appearance.
+    pub rows: Vec<FrequencyRow>,
+    /// Rows without the configured columns or with a rank that isn't a positive integer.
+    pub skipped: usize,
+    /// Rows for a term that came up before; the lowest rank was kept.
+    pub duplicates: usize,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FrequencyImport {
+    pub dictionary_id: i64,
+    pub name: String,
+    pub terms: usize,
+    pub skipped: usize,
+    pub duplicates: usize,
+}
+
+/// Splits a line at `delimiter`, honoring double-quoted fields (with `""` for a quote).
+fn split_fields(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.trim().is_empty() => {
+                quoted = true;
+                field.clear();
+            }
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Reads a CSV or TSV frequency list. Never fails: rows it can't make sense of are counted in
+/// [`ParsedList::skipped`].
+pub fn parse_frequency_csv(text: &str, options: &CsvOptions) -> ParsedList {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let delimiter = options.delimiter.unwrap_or_else(|| {
+        match text.lines().next().is_some_and(|line| line.contains('\t')) {
+            true => '\t',
+            false => ',',
+        }
+    });
+    if options.has_header {
+        lines.next();
+    }
+
+    let mut list = ParsedList::default();
+    let mut index: HashMap<(String, Option<String>), usize> = HashMap::new();
+    for line in lines {
+        let fields = split_fields(line, delimiter);
+        let field = |column: usize| {
+            fields
+                .get(column)
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+        };
+        let (Some(term), Some(rank)) = (
+            field(options.term_column),
+            field(options.rank_column).and_then(|rank| rank.parse::<i64>().ok()),
+        ) else {
+            list.skipped += 1;
+            continue;
+        };
+        // Stored like term bank headwords, so variant glyphs count as the same term
+        let normalized = normalize_ideographs(term);
+        let term = normalized.as_deref().unwrap_or(term);
+        // 0 is what entries without frequency data store
+        if rank <= 0 {
+            list.skipped += 1;
+            continue;
+        }
+        let reading = options
+            .reading_column
+            .and_then(field)
+            .filter(|reading| *reading != term)
+            .map(str::to_string);
+
+        let key = (term.to_string(), reading.clone());
+        match index.get(&key) {
+            Some(&i) => {
+                list.duplicates += 1;
+                list.rows[i].rank = list.rows[i].rank.min(rank);
+            }
+            None => {
+                index.insert(key, list.rows.len());
+                list.rows.push(FrequencyRow {
+                    term: term.to_string(),
+                    reading,
+                    rank,
+                });
+            }
+        }
+    }
+    list
+}
+
+/// Rank carried by a row of a frequency list. These are stored like term bank entries, with the
+/// rank as popularity and no definitions.
+pub fn frequency_rank(record: &Record) -> Option<i64> {
+    match record {
+        Record::YomitanGlossary(g) if g.content.is_empty() && g.tags.is_empty() => {
+            Some(g.popularity)
+        }
+        _ => None,
+    }
+}
+
+/// Imports a frequency list as a new dictionary named `name`. Its ranks then sort lookup results
+/// and show up as their frequency like those of any other dictionary.
+pub fn import_frequency_csv(
+    state: &AppState,
+    name: &str,
+    language: Option<String>,
+    data: &[u8],
+    options: &CsvOptions,
+) -> Result<FrequencyImport> {
+    info!(
+        "📦 [Import] Starting frequency list import '{}' ({} bytes)...",
+        name,
+        data.len()
+    );
+    let text = std::str::from_utf8(data).map_err(|_| anyhow!("Frequency list isn't UTF-8"))?;
+    let list = parse_frequency_csv(text, options);
+    if list.rows.is_empty() {
+        return Err(anyhow!(
+            "No usable rows in the frequency list ({} skipped)",
+            list.skipped
+        ));
+    }
+
+    let _guard = state.lock_writes("importing frequency list");
+    let mut conn = state.pool.get()?;
+    let tx = conn.transaction()?;
+    let dict_id = register_dictionary(
+        state,
+        &tx,
+        name,
+        language,
+        None,
+        DictionaryInfo::imported_now(),
+    )?;
+
+    let mut encoder = snap::raw::Encoder::new();
+    {
+        let mut stmt =
+            tx.prepare("INSERT INTO terms (term, dictionary_id, json) VALUES (?, ?, ?)")?;
+        for row in &list.rows {
+            let stored = StoredRecord {
+                dictionary_id: dict_id,
+                record: Record::YomitanGlossary(Glossary {
+                    popularity: row.rank,
+                    tags: Vec::new(),
+                    content: Vec::new(),
+                }),
+                reading: row.reading.clone(),
+            };
+            let compressed = encoder.compress_vec(&serde_json::to_vec(&stored)?)?;
+            stmt.execute(rusqlite::params![row.term, dict_id.0, compressed])?;
+            if let Some(reading) = &row.reading {
+                stmt.execute(rusqlite::params![reading, dict_id.0, compressed])?;
+            }
+        }
+    }
+
+    // A frequency list is term metadata, like the frequency banks of Yomitan dictionaries
+    record_entry_counts(state, &tx, dict_id, 0, 0, list.rows.len())?;
+    tx.commit()?;
+    info!(
+        "💾 [Import] Frequency list committed. Terms: {}, skipped rows: {}, duplicates: {}",
+        list.rows.len(),
+        list.skipped,
+        list.duplicates
+    );
+    state.preload.refresh(state.pool.clone());
+    state.dictionaries_changed();
+
+    Ok(FrequencyImport {
+        dictionary_id: dict_id.0,
+        name: name.to_string(),
+        terms: list.rows.len(),
+        skipped: list.skipped,
+        duplicates: list.duplicates,
+    })
+}
@@ -1,18 +1,53 @@
-use crate::{PREBAKED_DICT, ServerState, import};
+use crate::{
+    PREBAKED_DICT, ServerState,
+    anki::{AnkiCode, AnkiEnvelope, AnkiError, AnkiStatus},
+    content_limits::TRUNCATED_MARKER,
+    engine::EngineStatus,
+    examples,
+    frequency::{self, CsvOptions},
+    import,
+    lookup::{LookupService, ScanMode},
+    maintenance,
+    state::{AppState, DictionaryData, DictionaryInfo, StoredRecord, normalize_language},
+    user_dict::{self, USER_DICTIONARY_ID, UserTerm, UserTermError, UserTermPatch},
+    vocab::{self, VocabEntry},
+};
 use axum::{
     Json,
-    extract::{Multipart, Query, State},
-    http::StatusCode,
+    extract::{Multipart, Path, Query, Request, State},
+    http::{HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
 };
+use futures::{StreamExt, stream};
+use mangatan_tokenize::Tokenizer;
+use rusqlite::OptionalExtension;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, Value as JsonValue, json};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 use tracing::{error, info};
-use wordbase_api::{DictionaryId, Record, Term};
+use wordbase_api::{DictionaryId, FrequencyValue, Record, Term};
 
 #[derive(Deserialize)]
 pub struct LookupParams {
     pub text: String,
     pub index: Option<usize>,
+    /// Comma-separated dictionary ids to limit this lookup to.
+    pub dictionaries: Option<String>,
+    /// Comma-separated dictionary ids to search instead of the enabled ones, whether they're
+    /// enabled or not. Takes precedence over `dictionaries`.
+    pub dictionary_ids: Option<String>,
+    /// `forward` (the default) or `around`; see [`ScanMode`].
+    pub scan_mode: Option<ScanMode>,
+}
+
+/// `dictionaries=<id,id,...>` for endpoints whose other input comes in the body.
+#[derive(Deserialize)]
+pub struct DictionarySubsetParams {
+    pub dictionaries: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -28,6 +63,43 @@ pub struct ApiDefinition {
     pub dictionary_name: String,
     pub tags: Vec<String>,
     pub content: JsonValue,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency: Option<ApiFrequency>,
+    /// Set when `content` was too large to send and holds only the truncation marker;
+    /// `GET /records/{id}` returns the full record.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub truncated_record_id: Option<i64>,
+    /// Set on entries of the user dictionary; `PATCH` or `DELETE /user-terms/{id}` edits them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_term_id: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiFrequency {
+    pub value: i64,
+    /// How to render `value`, e.g. `①` for ranks and `1234 occurrences` for counts.
+    pub display: FrequencyDisplay,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FrequencyDisplay {
+    /// Position in a frequency list; lower is more common.
+    Rank,
+    /// Times the term was seen in a corpus; higher is more common.
+    Occurrence,
+}
+
+impl ApiFrequency {
+    /// `None` for a zero value, which dictionaries without frequency data store.
+    fn from_value(value: &FrequencyValue) -> Option<Self> {
+        let (value, display) = match *value {
+            FrequencyValue::Rank(v) => (v, FrequencyDisplay::Rank),
+            FrequencyValue::Occurrence(v) => (v, FrequencyDisplay::Occurrence),
+        };
+        (value != 0).then_some(Self { value, display })
+    }
 }
 
 #[derive(Serialize)]
@@ -40,6 +112,12 @@ pub struct ApiGroupedResult {
     pub forms: Vec<ApiForm>,
     // ADDED: Return the length of the match so the frontend can highlight it
     pub match_len: usize,
+    // Only present for `scan_mode=around`, where the match needn't start at the cursor
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<TokenSpan>,
+    // Only present when the Anki duplicate check is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_anki: Option<AnkiStatus>,
 }
 
 #[derive(Deserialize)]
@@ -50,6 +128,81 @@ pub enum DictionaryAction {
     Reorder { order: Vec<i64> },
 }
 
+#[derive(Deserialize)]
+pub struct MergeDictionariesRequest {
+    pub source_ids: Vec<i64>,
+    pub target_name: String,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateDictionaryRequest {
+    /// New source language; `null` or an empty string clears it (treated as Japanese).
+    pub language: Option<String>,
+}
+
+/// Selects dictionaries by id, by name or both; at least one must be given.
+#[derive(Deserialize)]
+pub struct BulkToggleRequest {
+    #[serde(default)]
+    pub ids: Option<Vec<i64>>,
+    /// Case-insensitive substring of the dictionary name, e.g. "freq".
+    #[serde(default)]
+    pub name_contains: Option<String>,
+    pub enabled: bool,
+}
+
+/// Dictionary setup without the term data, for backups and moving to another device.
+#[derive(Serialize, Deserialize)]
+pub struct ConfigSnapshot {
+    pub dictionaries: Vec<DictionaryConfig>,
+    /// Environment-controlled settings, exported for reference; an import leaves them alone.
+    #[serde(default)]
+    pub settings: JsonValue,
+    /// Entries of the user dictionary, which exist nowhere but here and in yomitan.db.
+    #[serde(default)]
+    pub user_terms: Vec<UserTerm>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DictionaryConfig {
+    pub name: String,
+    #[serde(default)]
+    pub revision: Option<String>,
+    pub priority: i64,
+    pub enabled: bool,
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+/// Rejects mutating requests with 403 when the server runs in read-only mode.
+pub async fn read_only_guard(
+    State(state): State<ServerState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if state.app.read_only {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "status": "error",
+                "message": "Dictionary management is disabled (read-only mode)."
+            })),
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+/// Records request activity so background maintenance only runs while the server is idle.
+pub async fn track_activity(
+    State(state): State<ServerState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    state.app.touch();
+    next.run(request).await
+}
+
 pub async fn manage_dictionaries_handler(
     State(state): State<ServerState>,
     Json(action): Json<DictionaryAction>,
@@ -57,6 +210,7 @@ pub async fn manage_dictionaries_handler(
     let app_state = state.app.clone();
 
     let res = tokio::task::spawn_blocking(move || -> Result<(), String> {
+        let _guard = app_state.lock_writes("managing dictionaries");
         let mut conn = app_state.pool.get().map_err(|e| e.to_string())?;
         let mut should_vacuum = false;
 
@@ -65,6 +219,9 @@ pub async fn manage_dictionaries_handler(
 
             match action {
                 DictionaryAction::Toggle { id, enabled } => {
+                    if id == USER_DICTIONARY_ID.0 && !enabled {
+                        return Err("The user dictionary is always enabled".to_string());
+                    }
                     tx.execute(
                         "UPDATE dictionaries SET enabled = ? WHERE id = ?",
                         rusqlite::params![enabled, id],
@@ -77,6 +234,9 @@ pub async fn manage_dictionaries_handler(
                     }
                 }
                 DictionaryAction::Delete { id } => {
+                    if id == USER_DICTIONARY_ID.0 {
+                        return Err("The user dictionary can't be deleted".to_string());
+                    }
                     info!("🗑️ [Yomitan] Deleting dictionary {}...", id);
                     tx.execute(
                         "DELETE FROM terms WHERE dictionary_id = ?",
@@ -92,6 +252,8 @@ pub async fn manage_dictionaries_handler(
                     let mut dicts = app_state.dictionaries.write().expect("lock");
                     dicts.remove(&DictionaryId(id));
                     should_vacuum = true;
+                    // The deleted dictionary's rows must not be served from memory
+                    app_state.preload.clear();
                 }
                 DictionaryAction::Reorder { order } => {
                     let mut stmt = tx
@@ -113,11 +275,14 @@ pub async fn manage_dictionaries_handler(
 
             tx.commit().map_err(|e| e.to_string())?;
         }
+        app_state.dictionaries_changed();
 
         if should_vacuum {
             info!("🧹 [Yomitan] Vacuuming database to reclaim disk space...");
             conn.execute("VACUUM", []).map_err(|e| e.to_string())?;
             info!("✨ [Yomitan] Vacuum complete.");
+            // Rebuilt only now so the background scan doesn't hold up the vacuum
+            app_state.preload.refresh(app_state.pool.clone());
         }
 
         Ok(())
@@ -131,12 +296,261 @@ pub async fn manage_dictionaries_handler(
     }
 }
 
+/// Moves every term of the source dictionaries into one new dictionary and drops the sources.
+pub async fn merge_dictionaries_handler(
+    State(state): State<ServerState>,
+    Json(req): Json<MergeDictionariesRequest>,
+) -> (StatusCode, Json<Value>) {
+    let app_state = state.app.clone();
+
+    let res = tokio::task::spawn_blocking(move || {
+        // Taken before the sources are read, so an import or compaction can't change them
+        // halfway through
+        let _guard = app_state.try_lock_writes("merging dictionaries")?;
+        Some(merge_dictionaries(&app_state, req))
+    })
+    .await
+    .unwrap_or_else(|e| Some(Err(e.to_string())));
+
+    match res {
+        None => (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "status": "error",
+                "message": "An import or database maintenance is running; try again shortly."
+            })),
+        ),
+        Some(Ok(msg)) => (
+            StatusCode::OK,
+            Json(json!({ "status": "ok", "message": msg })),
+        ),
+        Some(Err(e)) => {
+            error!("❌ [Merge Dictionaries] Failed: {}", e);
+            (
+                StatusCode::OK,
+                Json(json!({ "status": "error", "message": e })),
+            )
+        }
+    }
+}
+
+/// The merge itself, with the write lock held by the caller.
+fn merge_dictionaries(
+    app_state: &AppState,
+    req: MergeDictionariesRequest,
+) -> Result<String, String> {
+    let mut source_ids = req.source_ids;
+    source_ids.sort_unstable();
+    source_ids.dedup();
+
+    let target_name = req.target_name.trim().to_string();
+    if target_name.is_empty() {
+        return Err("Target name must not be empty".to_string());
+    }
+    if source_ids.contains(&USER_DICTIONARY_ID.0) {
+        return Err("The user dictionary can't be merged".to_string());
+    }
+
+    let sources: Vec<DictionaryData> = {
+        let dicts = app_state.dictionaries.read().expect("lock");
+        source_ids
+            .iter()
+            .map(|id| {
+                dicts
+                    .get(&DictionaryId(*id))
+                    .cloned()
+                    .ok_or_else(|| format!("Dictionary {id} not found"))
+            })
+            .collect::<Result<_, _>>()?
+    };
+    if sources.len() < 2 {
+        return Err("Select at least two dictionaries to merge".to_string());
+    }
+
+    let priority = sources.iter().map(|d| d.priority).min().unwrap_or(0);
+    let enabled = sources.iter().any(|d| d.enabled);
+    // Only kept when every source agrees; a mixed merge falls back to the Japanese pipeline
+    let language = sources[0].language.clone().filter(|language| {
+        sources
+            .iter()
+            .all(|d| d.language.as_ref() == Some(language))
+    });
+
+    let mut conn = app_state.pool.get().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    // Only taken once the merge has committed, so a failed one doesn't use up an id. Nothing
+    // else hands out ids while the caller holds the write lock.
+    let target_id = DictionaryId(*app_state.next_dict_id.read().expect("lock"));
+
+    let info = DictionaryInfo::imported_now();
+    tx.execute(
+        "INSERT INTO dictionaries (id, name, priority, enabled, language, imported_at)
+         VALUES (?, ?, ?, ?, ?, ?)",
+        rusqlite::params![
+            target_id.0,
+            target_name,
+            priority,
+            enabled,
+            language,
+            info.imported_at
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    // The dictionary id is also stored inside each compressed record, so rows are rewritten
+    // rather than just relabelled
+    let mut moved = 0;
+    {
+        let mut select = tx
+            .prepare("SELECT rowid, json FROM terms WHERE dictionary_id = ?")
+            .map_err(|e| e.to_string())?;
+        let mut update = tx
+            .prepare("UPDATE terms SET dictionary_id = ?, json = ? WHERE rowid = ?")
+            .map_err(|e| e.to_string())?;
+        let mut decoder = snap::raw::Decoder::new();
+        let mut encoder = snap::raw::Encoder::new();
+
+        for source in &sources {
+            // Streamed rather than collected, since a source can have millions of rows. A moved
+            // row leaves the source's range of the dictionary_id index, so the cursor doesn't
+            // come across it again.
+            let mut rows = select
+                .query(rusqlite::params![source.id.0])
+                .map_err(|e| e.to_string())?;
+            while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+                let rowid: i64 = row.get(0).map_err(|e| e.to_string())?;
+                let blob: Vec<u8> = row.get(1).map_err(|e| e.to_string())?;
+                let json_bytes = decoder.decompress_vec(&blob).map_err(|e| e.to_string())?;
+                let mut stored: StoredRecord =
+                    serde_json::from_slice(&json_bytes).map_err(|e| e.to_string())?;
+                stored.dictionary_id = target_id;
+
+                let json_bytes = serde_json::to_vec(&stored).map_err(|e| e.to_string())?;
+                let compressed = encoder
+                    .compress_vec(&json_bytes)
+                    .map_err(|e| e.to_string())?;
+                update
+                    .execute(rusqlite::params![target_id.0, compressed, rowid])
+                    .map_err(|e| e.to_string())?;
+                moved += 1;
+            }
+
+            tx.execute(
+                "DELETE FROM dictionaries WHERE id = ?",
+                rusqlite::params![source.id.0],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    }
+
+    tx.commit().map_err(|e| e.to_string())?;
+    {
+        let mut next_id = app_state.next_dict_id.write().expect("lock");
+        *next_id = (*next_id).max(target_id.0 + 1);
+    }
+    app_state.preload.refresh(app_state.pool.clone());
+
+    {
+        let mut dicts = app_state.dictionaries.write().expect("lock");
+        for source in &sources {
+            dicts.remove(&source.id);
+        }
+        dicts.insert(
+            target_id,
+            DictionaryData {
+                id: target_id,
+                name: target_name.clone(),
+                priority,
+                enabled,
+                language,
+                // A merge is a new dictionary of the user's making
+                revision: None,
+                info,
+            },
+        );
+    }
+    app_state.dictionaries_changed();
+
+    info!(
+        "🔗 [Yomitan] Merged {} dictionaries into '{}' ({} rows)",
+        sources.len(),
+        target_name,
+        moved
+    );
+    Ok(format!(
+        "Merged {} dictionaries into '{}'",
+        sources.len(),
+        target_name
+    ))
+}
+
+/// Updates a dictionary's settings; currently only its source language, which decides which
+/// lookup pipeline its entries are matched with.
+pub async fn update_dictionary_handler(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+    Json(req): Json<UpdateDictionaryRequest>,
+) -> (StatusCode, Json<Value>) {
+    let language = req.language.as_deref().and_then(normalize_language);
+
+    // Never block the runtime behind a long import or compaction
+    let Some(_guard) = state.app.try_lock_writes("updating dictionary") else {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "status": "error",
+                "message": "An import or database maintenance is running; try again shortly."
+            })),
+        );
+    };
+    let updated = state
+        .app
+        .pool
+        .get()
+        .map_err(|e| e.to_string())
+        .and_then(|conn| {
+            conn.execute(
+                "UPDATE dictionaries SET language = ? WHERE id = ?",
+                rusqlite::params![language, id],
+            )
+            .map_err(|e| e.to_string())
+        });
+
+    let mut dicts = state.app.dictionaries.write().expect("lock");
+    match (updated, dicts.get_mut(&DictionaryId(id))) {
+        (Ok(1), Some(dict)) => {
+            dict.language = language;
+            state.app.dictionaries_changed();
+            info!(
+                "🌐 [Yomitan] Dictionary '{}' language set to {:?}",
+                dict.name, dict.language
+            );
+            (
+                StatusCode::OK,
+                Json(json!({ "status": "ok", "dictionary": dict.clone() })),
+            )
+        }
+        (Ok(_), _) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "status": "error", "message": format!("Dictionary {id} not found") })),
+        ),
+        (Err(e), _) => {
+            error!("❌ [Update Dictionary] Failed: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "status": "error", "message": e })),
+            )
+        }
+    }
+}
+
 pub async fn install_defaults_handler(State(state): State<ServerState>) -> Json<Value> {
     let app_state = state.app.clone();
 
     {
         let dicts = app_state.dictionaries.read().expect("lock");
-        if !dicts.is_empty() {
+        if !dicts.keys().all(|id| *id == USER_DICTIONARY_ID) {
             return Json(json!({ "status": "ok", "message": "Dictionaries already exist." }));
         }
     }
@@ -170,21 +584,33 @@ pub async fn reset_db_handler(State(state): State<ServerState>) -> Json<Value> {
 
     let res = tokio::task::spawn_blocking(move || {
         {
+            // The user's own entries can't be reinstalled, so a reset keeps them
             let mut dicts = app_state.dictionaries.write().expect("lock");
-            dicts.clear();
+            dicts.retain(|id, _| *id == USER_DICTIONARY_ID);
             let mut next_id = app_state.next_dict_id.write().expect("lock");
             *next_id = 1;
         }
 
-        if let Ok(mut conn) = app_state.pool.get() {
-            if let Ok(tx) = conn.transaction() {
-                let _ = tx.execute("DELETE FROM terms", []);
-                let _ = tx.execute("DELETE FROM dictionaries", []);
-                let _ = tx.execute("DELETE FROM metadata", []);
-                let _ = tx.commit();
+        // Released before the import below, which takes it itself
+        {
+            let _guard = app_state.lock_writes("resetting database");
+            if let Ok(mut conn) = app_state.pool.get() {
+                if let Ok(tx) = conn.transaction() {
+                    let _ = tx.execute(
+                        "DELETE FROM terms WHERE dictionary_id != ?",
+                        [USER_DICTIONARY_ID.0],
+                    );
+                    let _ = tx.execute(
+                        "DELETE FROM dictionaries WHERE id != ?",
+                        [USER_DICTIONARY_ID.0],
+                    );
+                    let _ = tx.execute("DELETE FROM metadata", []);
+                    let _ = tx.commit();
+                }
+                app_state.dictionaries_changed();
+                info!("🧹 [Yomitan] Vacuuming after reset...");
+                let _ = conn.execute("VACUUM", []);
             }
-            info!("🧹 [Yomitan] Vacuuming after reset...");
-            let _ = conn.execute("VACUUM", []);
         }
 
         import::import_zip(&app_state, crate::PREBAKED_DICT)
@@ -203,10 +629,46 @@ pub async fn reset_db_handler(State(state): State<ServerState>) -> Json<Value> {
     }
 }
 
+/// Set on `/lookup` responses when part of the `dictionaries` or `dictionary_ids` selection
+/// was ignored.
+pub const WARNING_HEADER: &str = "x-mangatan-warning";
+
+/// Parses a `dictionaries=<id,id,...>` selection. Ids that aren't installed dictionaries are
+/// left out and named in the returned warning; disabled dictionaries stay in the set but never
+/// match, since lookups still honour the enabled flags (except for `dictionary_ids`).
+fn dictionary_subset(
+    state: &ServerState,
+    raw: Option<&str>,
+) -> (Option<HashSet<DictionaryId>>, Option<String>) {
+    let Some(raw) = raw.filter(|raw| !raw.trim().is_empty()) else {
+        return (None, None);
+    };
+
+    let dicts = state.app.dictionaries.read().expect("lock");
+    let mut subset = HashSet::new();
+    let mut invalid = Vec::new();
+    for part in raw
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+    {
+        match part.parse().map(DictionaryId) {
+            Ok(id) if dicts.contains_key(&id) => {
+                subset.insert(id);
+            }
+            _ => invalid.push(part),
+        }
+    }
+
+    let warning = (!invalid.is_empty())
+        .then(|| format!("Ignored unknown dictionary ids: {}", invalid.join(", ")));
+    (Some(subset), warning)
+}
+
 pub async fn lookup_handler(
     State(state): State<ServerState>,
     Query(params): Query<LookupParams>,
-) -> Result<Json<Vec<ApiGroupedResult>>, (StatusCode, Json<Value>)> {
+) -> Result<Response, (StatusCode, Json<Value>)> {
     let cursor_idx = params.index.unwrap_or(0);
 
     if state.app.is_loading() {
@@ -215,8 +677,141 @@ pub async fn lookup_handler(
             Json(json!({ "error": "loading", "message": "Dictionaries are importing..." })),
         ));
     }
+    let lookup = loaded_engine(&state)?;
 
-    let raw_results = state.lookup.search(&state.app, &params.text, cursor_idx);
+    let (only, only_warning) = dictionary_subset(&state, params.dictionary_ids.as_deref());
+    let include_disabled = only.is_some();
+    let (subset, warning) = match only {
+        Some(ids) => (Some(ids), only_warning),
+        None => dictionary_subset(&state, params.dictionaries.as_deref()),
+    };
+    let results = grouped_lookup(
+        &state,
+        &lookup,
+        &params.text,
+        cursor_idx,
+        params.scan_mode.unwrap_or_default(),
+        subset.as_ref(),
+        include_disabled,
+    )
+    .await;
+    // The body is a bare array clients already parse, so the warning goes in a header
+    let mut response = Json(results).into_response();
+    if let Some(warning) = warning.and_then(|w| HeaderValue::from_str(&w).ok()) {
+        response.headers_mut().insert(WARNING_HEADER, warning);
+    }
+    Ok(response)
+}
+
+#[derive(Deserialize)]
+pub struct TapRequest {
+    pub text: String,
+    /// Character (not byte) offset of the tap within `text`.
+    pub offset: usize,
+}
+
+/// Character range within the request's text: the token that was tapped, or a match.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TokenSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Serialize)]
+pub struct TapResponse {
+    pub token_span: TokenSpan,
+    pub entries: Vec<ApiGroupedResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+/// Segments `text`, finds the token under the tap and looks up from that token's start, so the
+/// highlighted span always matches a tokenizer boundary.
+pub async fn tap_handler(
+    State(state): State<ServerState>,
+    Query(params): Query<DictionarySubsetParams>,
+    Json(req): Json<TapRequest>,
+) -> Result<Json<TapResponse>, (StatusCode, Json<Value>)> {
+    if state.app.is_loading() {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": "loading", "message": "Dictionaries are importing..." })),
+        ));
+    }
+
+    let lookup = loaded_engine(&state)?;
+
+    let Some(token) = lookup.token_at(&req.text, req.offset) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "bad_offset", "message": "Offset is past the end of the text" })),
+        ));
+    };
+
+    let start = req.text[..token.start].chars().count();
+    let token_span = TokenSpan {
+        start,
+        end: start + req.text[token.clone()].chars().count(),
+    };
+    let (subset, warning) = dictionary_subset(&state, params.dictionaries.as_deref());
+    let entries = grouped_lookup(
+        &state,
+        &lookup,
+        &req.text,
+        token.start,
+        ScanMode::Forward,
+        subset.as_ref(),
+        false,
+    )
+    .await;
+
+    Ok(Json(TapResponse {
+        token_span,
+        entries,
+        warning,
+    }))
+}
+
+/// The lookup engine, or the 503 to answer with while it's still loading (or failed to).
+fn loaded_engine(state: &ServerState) -> Result<Arc<LookupService>, (StatusCode, Json<Value>)> {
+    state.lookup.get().ok_or_else(|| {
+        let body = match state.lookup.status() {
+            EngineStatus::Failed { error } => json!({
+                "error": "engine_failed",
+                "message": format!("Dictionary engine failed to load: {error}"),
+            }),
+            _ => json!({ "error": "loading", "message": "Dictionary engine is still loading..." }),
+        };
+        (StatusCode::SERVICE_UNAVAILABLE, Json(body))
+    })
+}
+
+/// Whether lookups can be served yet; see [`ServerState::lookup`].
+pub async fn engine_status_handler(State(state): State<ServerState>) -> Json<Value> {
+    Json(json!({
+        "ready": state.lookup.is_ready(),
+        "status": state.lookup.status(),
+    }))
+}
+
+/// Runs a lookup at byte offset `cursor_idx` and groups the entries by headword and reading.
+/// With `include_disabled`, `subset` is searched whether its dictionaries are enabled or not.
+async fn grouped_lookup(
+    state: &ServerState,
+    lookup: &LookupService,
+    text: &str,
+    cursor_idx: usize,
+    scan_mode: ScanMode,
+    subset: Option<&HashSet<DictionaryId>>,
+    include_disabled: bool,
+) -> Vec<ApiGroupedResult> {
+    let raw_results = match (scan_mode, subset) {
+        (ScanMode::Around, _) => {
+            lookup.search_around(&state.app, text, cursor_idx, subset, include_disabled)
+        }
+        (_, Some(ids)) if include_disabled => lookup.search_only(&state.app, text, cursor_idx, ids),
+        _ => lookup.search_in(&state.app, text, cursor_idx, subset),
+    };
 
     let dict_meta: std::collections::HashMap<DictionaryId, String> = {
         let dicts = state.app.dictionaries.read().expect("lock");
@@ -230,6 +825,7 @@ pub async fn lookup_handler(
         definitions: Vec<ApiDefinition>,
         forms_set: Vec<(String, String)>,
         match_len: usize, // Added to aggregator
+        span: Option<TokenSpan>,
     }
 
     let mut map: Vec<Aggregator> = Vec::new();
@@ -245,7 +841,12 @@ pub async fn lookup_handler(
             continue;
         }
 
-        let match_len = entry.span_chars.end as usize;
+        let match_len = (entry.span_chars.end - entry.span_chars.start) as usize;
+        // Forward spans start at the cursor, which the client already knows
+        let span = (scan_mode == ScanMode::Around).then_some(TokenSpan {
+            start: entry.span_chars.start as usize,
+            end: entry.span_chars.end as usize,
+        });
 
         let (content_val, tags) = if let Record::YomitanGlossary(gloss) = &entry.record {
             let t = gloss
@@ -268,6 +869,12 @@ pub async fn lookup_handler(
         } else {
             (json!(entry.record), vec![])
         };
+        let oversized = content_val.to_string().len() > state.app.content_limits.max_record_bytes;
+        let (content_val, truncated_record_id) = if oversized {
+            (json!([TRUNCATED_MARKER]), Some(entry.record_id.0))
+        } else {
+            (content_val, None)
+        };
 
         let dict_name = dict_meta
             .get(&entry.source)
@@ -278,6 +885,19 @@ pub async fn lookup_handler(
             dictionary_name: dict_name,
             tags,
             content: content_val,
+            // A frequency list's rank over the dictionary's own popularity
+            frequency: entry
+                .profile_sorting_frequency
+                .as_ref()
+                .and_then(ApiFrequency::from_value)
+                .or_else(|| {
+                    entry
+                        .source_sorting_frequency
+                        .as_ref()
+                        .and_then(ApiFrequency::from_value)
+                }),
+            truncated_record_id,
+            user_term_id: (entry.source == USER_DICTIONARY_ID).then_some(entry.record_id.0),
         };
 
         if let Some(existing) = map
@@ -300,11 +920,12 @@ pub async fn lookup_handler(
                 definitions: vec![def_obj],
                 forms_set: vec![(headword.clone(), reading.clone())],
                 match_len, // Capture match length
+                span,
             });
         }
     }
 
-    let final_results: Vec<ApiGroupedResult> = map
+    let mut final_results: Vec<ApiGroupedResult> = map
         .into_iter()
         .map(|agg| {
             let mut forms_vec = Vec::new();
@@ -322,11 +943,26 @@ pub async fn lookup_handler(
                 definitions: agg.definitions,
                 forms: forms_vec,
                 match_len: agg.match_len, // Expose match length
+                span: agg.span,
+                in_anki: None,
             }
         })
         .collect();
 
-    Ok(Json(final_results))
+    if state.anki.is_enabled() {
+        let headwords: Vec<String> = final_results.iter().map(|r| r.headword.clone()).collect();
+        let statuses = state.anki.statuses(&headwords).await;
+        for result in &mut final_results {
+            result.in_anki = Some(
+                statuses
+                    .get(&result.headword)
+                    .copied()
+                    .unwrap_or(AnkiStatus::Unknown),
+            );
+        }
+    }
+
+    final_results
 }
 
 fn calculate_furigana(headword: &str, reading: &str) -> Vec<(String, String)> {
@@ -364,15 +1000,799 @@ fn calculate_furigana(headword: &str, reading: &str) -> Vec<(String, String)> {
     parts
 }
 
+#[derive(Deserialize)]
+pub struct ExamplesParams {
+    pub term: String,
+    pub reading: Option<String>,
+    pub limit: Option<usize>,
+    pub dictionaries: Option<String>,
+}
+
+/// Example sentences for a looked-up entry, pulled from its glossaries in dictionary order.
+pub async fn examples_handler(
+    State(state): State<ServerState>,
+    Query(params): Query<ExamplesParams>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    if state.app.is_loading() {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": "loading", "message": "Dictionaries are importing..." })),
+        ));
+    }
+
+    let limit = params
+        .limit
+        .unwrap_or(examples::DEFAULT_EXAMPLE_LIMIT)
+        .clamp(1, examples::MAX_EXAMPLE_LIMIT);
+    let (subset, warning) = dictionary_subset(&state, params.dictionaries.as_deref());
+    let app_state = state.app.clone();
+    let found = tokio::task::spawn_blocking(move || {
+        examples::find_examples(
+            &app_state,
+            &params.term,
+            params.reading.as_deref(),
+            limit,
+            subset.as_ref(),
+        )
+    })
+    .await
+    .unwrap_or_default();
+
+    let mut body = json!({ "examples": found });
+    if let Some(warning) = warning {
+        body["warning"] = json!(warning);
+    }
+    Ok(Json(body))
+}
+
+/// Body of `/vocab-report`: the chapter as sentences (e.g. the OCR text export's lines) or as
+/// one text, which is split into sentences here.
+#[derive(Deserialize)]
+pub struct VocabReportRequest {
+    #[serde(default)]
+    pub sentences: Vec<String>,
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VocabReportResponse {
+    pub entries: Vec<VocabEntry>,
+    /// Words counted, not counting punctuation.
+    pub total_words: usize,
+    pub unique_lemmas: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+/// Tokenizes a chapter and looks up each distinct lemma once, most frequent in the input first.
+/// The lookups run in chunks, a few at a time, so other requests keep getting connections.
+pub async fn vocab_report_handler(
+    State(state): State<ServerState>,
+    Query(params): Query<DictionarySubsetParams>,
+    Json(req): Json<VocabReportRequest>,
+) -> Result<Json<VocabReportResponse>, (StatusCode, Json<Value>)> {
+    if state.app.is_loading() {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": "loading", "message": "Dictionaries are importing..." })),
+        ));
+    }
+    // Tokenizing needs UniDic, which is what the engine is busy loading
+    loaded_engine(&state)?;
+
+    let chars: usize = req
+        .sentences
+        .iter()
+        .chain(req.text.as_ref())
+        .map(|s| s.chars().count())
+        .sum();
+    if chars > vocab::MAX_REPORT_CHARS {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(json!({
+                "error": "too_long",
+                "message": format!("Input is limited to {} characters", vocab::MAX_REPORT_CHARS),
+            })),
+        ));
+    }
+
+    let occurrences = tokio::task::spawn_blocking(move || {
+        let tokenizer = Tokenizer::shared();
+        let mut sentences = req.sentences;
+        if let Some(text) = &req.text {
+            sentences.extend(vocab::split_sentences(text).into_iter().map(str::to_string));
+        }
+        vocab::count_lemmas(
+            sentences
+                .iter()
+                .flat_map(|sentence| tokenizer.tokenize(sentence)),
+        )
+    })
+    .await
+    .unwrap_or_default();
+
+    let (subset, warning) = dictionary_subset(&state, params.dictionaries.as_deref());
+    let lemmas: Vec<String> = occurrences.iter().map(|o| o.lemma.clone()).collect();
+    let chunks: Vec<Vec<String>> = lemmas
+        .chunks(vocab::LOOKUP_CHUNK)
+        .map(<[String]>::to_vec)
+        .collect();
+    let coverage: HashMap<_, _> = stream::iter(chunks)
+        .map(|chunk| {
+            let app_state = state.app.clone();
+            let subset = subset.clone();
+            tokio::task::spawn_blocking(move || {
+                vocab::lookup_lemmas(&app_state, &chunk, subset.as_ref())
+            })
+        })
+        .buffer_unordered(vocab::LOOKUP_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .flatten()
+        .flatten()
+        .collect();
+
+    let total_words = occurrences.iter().map(|o| o.count).sum();
+    let mut entries: Vec<VocabEntry> = occurrences
+        .into_iter()
+        .map(|o| {
+            let found = coverage.get(&o.lemma).copied().unwrap_or_default();
+            VocabEntry::new(o, found)
+        })
+        .collect();
+    // Stable, so ties stay in order of first appearance
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.occurrences));
+
+    Ok(Json(VocabReportResponse {
+        unique_lemmas: entries.len(),
+        total_words,
+        entries,
+        warning,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct AnkiDuplicateParams {
+    pub term: String,
+    #[serde(default)]
+    pub reading: String,
+}
+
+/// Checks whether a card for `term` already exists, so the add-card path can skip or flag it.
+pub async fn anki_duplicate_handler(
+    State(state): State<ServerState>,
+    Query(params): Query<AnkiDuplicateParams>,
+) -> (StatusCode, Json<AnkiEnvelope>) {
+    let term = &params.term;
+    let envelope = match state.anki.find_duplicate(term, &params.reading).await {
+        Ok(Some(note_id)) => AnkiEnvelope::new(
+            AnkiCode::DuplicateFound,
+            format!("\"{term}\" is already in Anki (note {note_id})"),
+            json!({ "term": term, "duplicate": true, "noteId": note_id }),
+        ),
+        Ok(None) => AnkiEnvelope::new(
+            AnkiCode::NoDuplicate,
+            format!("\"{term}\" isn't in Anki yet"),
+            json!({ "term": term, "duplicate": false, "noteId": null }),
+        ),
+        Err(e) => return anki_failure("Duplicate check", e),
+    };
+    (StatusCode::OK, Json(envelope))
+}
+
+#[derive(Deserialize)]
+pub struct AnkiValidateRequest {
+    pub model: String,
+    pub sentence_field: Option<String>,
+    pub image_field: Option<String>,
+}
+
+/// Checks a note type and field mapping against Anki before any card is mined with it, so a
+/// typo in a field name shows up in the settings instead of as silently missing sentences.
+pub async fn anki_validate_handler(
+    State(state): State<ServerState>,
+    Json(req): Json<AnkiValidateRequest>,
+) -> (StatusCode, Json<AnkiEnvelope>) {
+    let available = match state.anki.model_field_names(&req.model).await {
+        Ok(fields) => fields,
+        Err(e) => return anki_failure("Field validation", e),
+    };
+
+    let model_exists = available.is_some();
+    let available = available.unwrap_or_default();
+    let mut fields = serde_json::Map::new();
+    let mut unknown_fields = Vec::new();
+    for (key, name) in [
+        ("sentence_field", &req.sentence_field),
+        ("image_field", &req.image_field),
+    ] {
+        if let Some(name) = name {
+            let valid = available.contains(name);
+            if !valid {
+                unknown_fields.push(name.clone());
+            }
+            fields.insert(key.to_string(), json!({ "name": name, "valid": valid }));
+        }
+    }
+
+    let model = &req.model;
+    let (code, message) = if !model_exists {
+        (
+            AnkiCode::ModelUnknown,
+            format!("Anki has no note type named \"{model}\""),
+        )
+    } else if !unknown_fields.is_empty() {
+        let names: Vec<String> = unknown_fields.iter().map(|f| format!("\"{f}\"")).collect();
+        (
+            AnkiCode::FieldUnknown,
+            format!("\"{model}\" has no field named {}", names.join(" or ")),
+        )
+    } else {
+        (
+            AnkiCode::FieldsValid,
+            format!("\"{model}\" has all the mapped fields"),
+        )
+    };
+    let details = json!({
+        "valid": code == AnkiCode::FieldsValid,
+        "model": model,
+        "model_exists": model_exists,
+        "fields": fields,
+        "unknown_fields": unknown_fields,
+        "available_fields": available,
+    });
+    (
+        StatusCode::OK,
+        Json(AnkiEnvelope::new(code, message, details)),
+    )
+}
+
+/// The error response of an Anki endpoint; only failures to talk to Anki get logged.
+fn anki_failure(action: &str, error: AnkiError) -> (StatusCode, Json<AnkiEnvelope>) {
+    if error != AnkiError::Disabled {
+        error!("❌ [Anki] {action} failed: {error}");
+    }
+    (error.status(), Json(error.envelope()))
+}
+
+/// One dictionary with everything known about it, for an about page.
+pub async fn get_dictionary_handler(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+) -> (StatusCode, Json<Value>) {
+    let dicts = state.app.dictionaries.read().expect("lock");
+    match dicts.get(&DictionaryId(id)) {
+        Some(dict) => (
+            StatusCode::OK,
+            Json(json!({ "status": "ok", "dictionary": dict })),
+        ),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "status": "error", "message": format!("Dictionary {id} not found") })),
+        ),
+    }
+}
+
+/// One stored record in full, for entries a lookup response cut short.
+pub async fn get_record_handler(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+) -> (StatusCode, Json<Value>) {
+    let app_state = state.app.clone();
+    let res = tokio::task::spawn_blocking(move || -> Result<Option<StoredRecord>, String> {
+        let conn = app_state.pool.get().map_err(|e| e.to_string())?;
+        let compressed: Option<Vec<u8>> = conn
+            .query_row("SELECT json FROM terms WHERE rowid = ?", [id], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let Some(compressed) = compressed else {
+            return Ok(None);
+        };
+        let json = snap::raw::Decoder::new()
+            .decompress_vec(&compressed)
+            .map_err(|e| e.to_string())?;
+        serde_json::from_slice(&json)
+            .map(Some)
+            .map_err(|e| e.to_string())
+    })
+    .await;
+
+    match res {
+        Ok(Ok(Some(stored))) => {
+            let dictionary_name = state
+                .app
+                .dictionaries
+                .read()
+                .expect("lock")
+                .get(&stored.dictionary_id)
+                .map(|d| d.name.clone());
+            (
+                StatusCode::OK,
+                Json(json!({
+                    "status": "ok",
+                    "id": id,
+                    "dictionaryName": dictionary_name,
+                    "reading": stored.reading,
+                    "record": stored.record,
+                })),
+            )
+        }
+        Ok(Ok(None)) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "status": "error", "message": format!("Record {id} not found") })),
+        ),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "status": "error", "message": e })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "status": "error", "message": e.to_string() })),
+        ),
+    }
+}
+
 pub async fn list_dictionaries_handler(State(state): State<ServerState>) -> Json<Value> {
     let dicts = state.app.dictionaries.read().expect("lock");
     let mut list: Vec<_> = dicts.values().cloned().collect();
     list.sort_by_key(|d| d.priority);
-    Json(
-        json!({ "dictionaries": list, "status": if state.app.is_loading() { "loading" } else { "ready" } }),
+    Json(json!({
+        "dictionaries": list,
+        "status": if state.app.is_loading() { "loading" } else { "ready" },
+        "read_only": state.app.read_only,
+        "preload": state.app.preload.stats(),
+        "writer": state.app.writer_health(),
+        "build": mangatan_core::build_info::current(),
+        "storage": maintenance::db_stats(&state.app).ok().map(|stats| json!({
+            "file_bytes": stats.file_bytes,
+            "page_size": stats.page_size,
+            "page_count": stats.page_count,
+            "freelist_count": stats.freelist_count,
+            "free_fraction": stats.free_fraction(),
+        })),
+    }))
+}
+
+pub async fn config_export_handler(
+    State(state): State<ServerState>,
+) -> Result<Json<ConfigSnapshot>, (StatusCode, Json<Value>)> {
+    // A backup that silently lacks the user's own entries is worse than none
+    let user_terms = user_dict::list(&state.app).map_err(|e| {
+        error!(
+            "❌ [Config Export] Failed to read the user dictionary: {}",
+            e
+        );
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "status": "error", "message": e.to_string() })),
+        )
+    })?;
+    let mut dictionaries: Vec<DictionaryConfig> = {
+        let dicts = state.app.dictionaries.read().expect("lock");
+        dicts
+            .values()
+            .map(|d| DictionaryConfig {
+                name: d.name.clone(),
+                revision: d.revision.clone(),
+                priority: d.priority,
+                enabled: d.enabled,
+                language: d.language.clone(),
+            })
+            .collect()
+    };
+    dictionaries.sort_by_key(|d| d.priority);
+
+    Ok(Json(ConfigSnapshot {
+        dictionaries,
+        settings: json!({
+            "read_only": state.app.read_only,
+            "preload_terms": state.app.preload.stats().limit,
+            "anki_check": state.anki.is_enabled(),
+        }),
+        user_terms: user_terms.into_iter().map(|entry| entry.term).collect(),
+    }))
+}
+
+/// Sets the enabled flag on every dictionary matching the request in one transaction, e.g. to
+/// switch off all frequency dictionaries at once.
+pub async fn bulk_toggle_handler(
+    State(state): State<ServerState>,
+    Json(req): Json<BulkToggleRequest>,
+) -> (StatusCode, Json<Value>) {
+    let name_contains = req
+        .name_contains
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_lowercase);
+    if req.ids.is_none() && name_contains.is_none() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "status": "error",
+                "message": "Give ids, name_contains or both to select dictionaries."
+            })),
+        );
+    }
+
+    let Some(_guard) = state.app.try_lock_writes("toggling dictionaries") else {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "status": "error",
+                "message": "An import or database maintenance is running; try again shortly."
+            })),
+        );
+    };
+
+    let mut affected: Vec<DictionaryId> = {
+        let dicts = state.app.dictionaries.read().expect("lock");
+        dicts
+            .values()
+            // Only ever enabled; see `manage_dictionaries_handler`
+            .filter(|d| d.id != USER_DICTIONARY_ID || req.enabled)
+            .filter(|d| req.ids.as_ref().is_none_or(|ids| ids.contains(&d.id.0)))
+            .filter(|d| {
+                name_contains
+                    .as_ref()
+                    .is_none_or(|needle| d.name.to_lowercase().contains(needle))
+            })
+            .map(|d| d.id)
+            .collect()
+    };
+    affected.sort_by_key(|id| id.0);
+
+    let applied = state
+        .app
+        .pool
+        .get()
+        .map_err(|e| e.to_string())
+        .and_then(|mut conn| {
+            let tx = conn.transaction().map_err(|e| e.to_string())?;
+            for id in &affected {
+                tx.execute(
+                    "UPDATE dictionaries SET enabled = ? WHERE id = ?",
+                    rusqlite::params![req.enabled, id.0],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+            tx.commit().map_err(|e| e.to_string())
+        });
+    if let Err(e) = applied {
+        error!("❌ [Bulk Toggle] Failed: {}", e);
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "status": "error", "message": e })),
+        );
+    }
+
+    {
+        let mut dicts = state.app.dictionaries.write().expect("lock");
+        for id in &affected {
+            if let Some(dict) = dicts.get_mut(id) {
+                dict.enabled = req.enabled;
+            }
+        }
+    }
+    state.app.dictionaries_changed();
+    info!(
+        "🔀 [Yomitan] {} {} dictionaries",
+        if req.enabled { "Enabled" } else { "Disabled" },
+        affected.len()
+    );
+
+    let ids: Vec<i64> = affected.iter().map(|id| id.0).collect();
+    (StatusCode::OK, Json(json!({ "status": "ok", "ids": ids })))
+}
+
+/// Reapplies an exported setup (priority, enabled flag, language) to the installed
+/// dictionaries with the same name and revision, and adds the user dictionary entries that
+/// are missing. Dictionaries the snapshot doesn't mention are left as they are.
+pub async fn config_import_handler(
+    State(state): State<ServerState>,
+    Json(snapshot): Json<ConfigSnapshot>,
+) -> (StatusCode, Json<Value>) {
+    let Some(_guard) = state.app.try_lock_writes("importing config") else {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "status": "error",
+                "message": "An import or database maintenance is running; try again shortly."
+            })),
+        );
+    };
+
+    // First, so the user dictionary exists for its settings to be matched below
+    let mut user_terms_added = 0;
+    for term in snapshot.user_terms.iter().cloned() {
+        match user_dict::add(&state.app, term) {
+            Ok(_) => user_terms_added += 1,
+            Err(UserTermError::Duplicate | UserTermError::Invalid(_)) => {}
+            Err(e) => {
+                error!("❌ [Config Import] Failed to add a user term: {}", e);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({ "status": "error", "message": e.to_string() })),
+                );
+            }
+        }
+    }
+
+    let mut updates = Vec::new();
+    let mut unmatched = Vec::new();
+    {
+        let dicts = state.app.dictionaries.read().expect("lock");
+        for config in &snapshot.dictionaries {
+            let matches: Vec<DictionaryId> = dicts
+                .values()
+                .filter(|d| d.name == config.name && d.revision == config.revision)
+                .map(|d| d.id)
+                .collect();
+            if matches.is_empty() {
+                unmatched.push(config.name.clone());
+            }
+            updates.extend(matches.into_iter().map(|id| (id, config)));
+        }
+    }
+
+    let applied = state
+        .app
+        .pool
+        .get()
+        .map_err(|e| e.to_string())
+        .and_then(|mut conn| {
+            let tx = conn.transaction().map_err(|e| e.to_string())?;
+            for (id, config) in &updates {
+                tx.execute(
+                    "UPDATE dictionaries SET priority = ?, enabled = ?, language = ? WHERE id = ?",
+                    rusqlite::params![
+                        config.priority,
+                        config.enabled,
+                        config.language.as_deref().and_then(normalize_language),
+                        id.0
+                    ],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+            tx.commit().map_err(|e| e.to_string())
+        });
+    if let Err(e) = applied {
+        error!("❌ [Config Import] Failed: {}", e);
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "status": "error", "message": e })),
+        );
+    }
+
+    {
+        let mut dicts = state.app.dictionaries.write().expect("lock");
+        for (id, config) in &updates {
+            if let Some(dict) = dicts.get_mut(id) {
+                dict.priority = config.priority;
+                dict.enabled = config.enabled;
+                dict.language = config.language.as_deref().and_then(normalize_language);
+            }
+        }
+    }
+    state.app.dictionaries_changed();
+    info!(
+        "📥 [Yomitan] Config import applied to {} dictionaries ({} unmatched), added {} user terms",
+        updates.len(),
+        unmatched.len(),
+        user_terms_added
+    );
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "status": "ok",
+            "applied": updates.len(),
+            "unmatched": unmatched,
+            "user_terms_added": user_terms_added,
+        })),
     )
 }
 
+fn user_term_response(result: Result<Value, UserTermError>) -> (StatusCode, Json<Value>) {
+    match result {
+        Ok(body) => (StatusCode::OK, Json(body)),
+        Err(e) => {
+            let status = match e {
+                UserTermError::Invalid(_) => StatusCode::BAD_REQUEST,
+                UserTermError::NotFound(_) => StatusCode::NOT_FOUND,
+                UserTermError::Duplicate => StatusCode::CONFLICT,
+                UserTermError::Db(_) => {
+                    error!("❌ [User Terms] Failed: {}", e);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }
+            };
+            (
+                status,
+                Json(json!({ "status": "error", "message": e.to_string() })),
+            )
+        }
+    }
+}
+
+/// Adds an entry to the user dictionary, creating the dictionary on first use.
+pub async fn add_user_term_handler(
+    State(state): State<ServerState>,
+    Json(term): Json<UserTerm>,
+) -> (StatusCode, Json<Value>) {
+    let Some(_guard) = state.app.try_lock_writes("adding user term") else {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "status": "error",
+                "message": "An import or database maintenance is running; try again shortly."
+            })),
+        );
+    };
+    user_term_response(user_dict::add(&state.app, term).map(|entry| {
+        info!("✏️ [Yomitan] Added user term '{}'", entry.term.headword);
+        json!({ "status": "ok", "term": entry })
+    }))
+}
+
+/// Changes the given fields of a user dictionary entry; `id` is its `userTermId`.
+pub async fn update_user_term_handler(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+    Json(patch): Json<UserTermPatch>,
+) -> (StatusCode, Json<Value>) {
+    let Some(_guard) = state.app.try_lock_writes("updating user term") else {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "status": "error",
+                "message": "An import or database maintenance is running; try again shortly."
+            })),
+        );
+    };
+    user_term_response(
+        user_dict::update(&state.app, id, patch)
+            .map(|entry| json!({ "status": "ok", "term": entry })),
+    )
+}
+
+pub async fn delete_user_term_handler(
+    State(state): State<ServerState>,
+    Path(id): Path<i64>,
+) -> (StatusCode, Json<Value>) {
+    let Some(_guard) = state.app.try_lock_writes("deleting user term") else {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "status": "error",
+                "message": "An import or database maintenance is running; try again shortly."
+            })),
+        );
+    };
+    user_term_response(user_dict::delete(&state.app, id).map(|()| json!({ "status": "ok" })))
+}
+
+/// Shrinks yomitan.db back to its live data; SQLite keeps the file at its high-water mark after
+/// deletes otherwise.
+pub async fn compact_handler(State(state): State<ServerState>) -> (StatusCode, Json<Value>) {
+    info!("🗜️ [Yomitan] Compaction requested...");
+    let app_state = state.app.clone();
+    let res = tokio::task::spawn_blocking(move || maintenance::compact(&app_state))
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+
+    match res {
+        Ok(report) => (
+            StatusCode::OK,
+            Json(json!({
+                "status": "ok",
+                "before_bytes": report.before_bytes,
+                "after_bytes": report.after_bytes,
+                "reclaimed_bytes": report.before_bytes.saturating_sub(report.after_bytes),
+                "duration_ms": report.duration_ms,
+            })),
+        ),
+        Err(e) => {
+            error!("❌ [Compact] Failed: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "status": "error", "message": e })),
+            )
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PrefixIndexParams {
+    /// Index only terms up to this many characters.
+    pub max_term_length: Option<usize>,
+}
+
+/// Builds the opt-in prefix index (see [`maintenance::build_prefix_index`]). It's never built at
+/// startup, since on a large database it takes a while.
+pub async fn build_prefix_index_handler(
+    State(state): State<ServerState>,
+    Query(params): Query<PrefixIndexParams>,
+) -> (StatusCode, Json<Value>) {
+    if params.max_term_length == Some(0) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "status": "error", "message": "max_term_length must be at least 1" })),
+        );
+    }
+    info!("🔎 [Yomitan] Prefix index build requested...");
+    let app_state = state.app.clone();
+    let res = tokio::task::spawn_blocking(move || {
+        maintenance::build_prefix_index(&app_state, params.max_term_length)
+    })
+    .await
+    .unwrap_or_else(|e| Err(e.to_string()));
+
+    match res {
+        Ok(report) => (
+            StatusCode::OK,
+            Json(json!({
+                "status": "ok",
+                "max_term_length": report.index.max_term_length,
+                "duration_ms": report.duration_ms,
+            })),
+        ),
+        Err(e) => {
+            error!("❌ [Yomitan] {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "status": "error", "message": e })),
+            )
+        }
+    }
+}
+
+pub async fn drop_prefix_index_handler(
+    State(state): State<ServerState>,
+) -> (StatusCode, Json<Value>) {
+    let app_state = state.app.clone();
+    let res = tokio::task::spawn_blocking(move || maintenance::drop_prefix_index(&app_state))
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+
+    match res {
+        Ok(dropped) => (
+            StatusCode::OK,
+            Json(json!({ "status": "ok", "dropped": dropped })),
+        ),
+        Err(e) => {
+            error!("❌ [Yomitan] Failed to drop the prefix index: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "status": "error", "message": e })),
+            )
+        }
+    }
+}
+
+/// Disk space taken by the dictionary database.
+pub async fn storage_stats_handler(State(state): State<ServerState>) -> (StatusCode, Json<Value>) {
+    let app_state = state.app.clone();
+    let res = tokio::task::spawn_blocking(move || maintenance::storage_stats(&app_state))
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+
+    match res {
+        Ok(stats) => (StatusCode::OK, Json(json!(stats))),
+        Err(e) => {
+            error!("❌ [Yomitan] Failed to read storage stats: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "status": "error", "message": e })),
+            )
+        }
+    }
+}
+
 pub async fn import_handler(
     State(state): State<ServerState>,
     mut multipart: Multipart,
@@ -423,3 +1843,114 @@ pub async fn import_handler(
     }
     Json(json!({ "status": "error", "message": "No file field found" }))
 }
+
+/// Imports a (word, rank) list as a frequency dictionary. Multipart fields: `file` (CSV or TSV),
+/// `name`, and optionally `language`, `term_column`, `reading_column`, `rank_column` (zero-based),
+/// `delimiter` (`tab` or a single character) and `has_header` (`true`/`false`).
+pub async fn import_frequency_csv_handler(
+    State(state): State<ServerState>,
+    mut multipart: Multipart,
+) -> Json<Value> {
+    let mut data = None;
+    let mut name = None;
+    let mut language = None;
+    let mut options = CsvOptions::default();
+
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => {
+                error!("❌ [Frequency Import] Multipart error: {}", e);
+                return Json(
+                    json!({ "status": "error", "message": format!("Multipart Error: {}", e) }),
+                );
+            }
+        };
+        let field_name = field.name().unwrap_or_default().to_string();
+        if field_name == "file" {
+            match field.bytes().await {
+                Ok(bytes) => data = Some(bytes),
+                Err(e) => {
+                    error!("❌ [Frequency Import] Failed to read field bytes: {}", e);
+                    return Json(
+                        json!({ "status": "error", "message": format!("Upload Failed: {}", e) }),
+                    );
+                }
+            }
+            continue;
+        }
+
+        let value = field.text().await.unwrap_or_default();
+        let value = value.trim();
+        let column = || value.parse::<usize>().ok();
+        let parsed = match field_name.as_str() {
+            "name" => {
+                name = Some(value.to_string()).filter(|n| !n.is_empty());
+                true
+            }
+            "language" => {
+                language = normalize_language(value);
+                true
+            }
+            "term_column" => column().map(|c| options.term_column = c).is_some(),
+            "rank_column" => column().map(|c| options.rank_column = c).is_some(),
+            "reading_column" => {
+                options.reading_column = column();
+                value.is_empty() || options.reading_column.is_some()
+            }
+            "delimiter" => {
+                options.delimiter = match value {
+                    "tab" | "\\t" => Some('\t'),
+                    "" => None,
+                    _ => value.chars().next().filter(|_| value.chars().count() == 1),
+                };
+                value.is_empty() || options.delimiter.is_some()
+            }
+            "has_header" => value.parse().map(|h| options.has_header = h).is_ok(),
+            _ => true,
+        };
+        if !parsed {
+            return Json(json!({
+                "status": "error",
+                "message": format!("Invalid value for {field_name}: {value:?}"),
+            }));
+        }
+    }
+
+    let Some(data) = data else {
+        return Json(json!({ "status": "error", "message": "No file field found" }));
+    };
+    let Some(name) = name else {
+        return Json(json!({ "status": "error", "message": "A dictionary name is required" }));
+    };
+    info!(
+        "📥 [Frequency Import] Received '{}' ({} bytes)",
+        name,
+        data.len()
+    );
+
+    let app_state = state.app.clone();
+    let res = tokio::task::spawn_blocking(move || {
+        frequency::import_frequency_csv(&app_state, &name, language, &data, &options)
+    })
+    .await
+    .unwrap_or_else(|e| Err(anyhow::anyhow!(e.to_string())));
+    match res {
+        Ok(imported) => {
+            info!(
+                "✅ Imported frequency list '{}' ({} terms)",
+                imported.name, imported.terms
+            );
+            Json(json!({
+                "status": "ok",
+                "message": format!("Imported '{}'", imported.name),
+                "dictionary": imported,
+            }))
+        }
+        Err(e) => {
+            error!("❌ {}", e);
+            Json(json!({ "status": "error", "message": e.to_string() }))
+        }
+    }
+}
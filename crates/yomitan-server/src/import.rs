@@ -1,8 +1,17 @@
-use crate::state::{AppState, DictionaryData, StoredRecord};
+use crate::content_limits::{ContentLimits, TRUNCATED_MARKER};
+use crate::lookup::normalize_ideographs;
+use crate::state::{AppState, DictionaryData, DictionaryInfo, StoredRecord, normalize_language};
 use anyhow::Result;
-use serde_json::{Value, json};
-use std::io::Read;
-use tracing::info;
+use serde_json::{Value, json, value::RawValue};
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+};
+use tracing::{info, warn};
 use wordbase_api::{
     DictionaryId, DictionaryKind, DictionaryMeta, Record,
     dict::yomitan::{Glossary, structured},
@@ -15,6 +24,7 @@ pub fn import_zip(state: &AppState, data: &[u8]) -> Result<String> {
         data.len()
     );
 
+    let guard = state.lock_writes("importing dictionary");
     let mut zip = ZipArchive::new(std::io::Cursor::new(data))?;
 
     // 1. Find index.json
@@ -31,7 +41,7 @@ pub fn import_zip(state: &AppState, data: &[u8]) -> Result<String> {
     let index_file_name =
         index_file_name.ok_or_else(|| anyhow::anyhow!("No index.json found in zip"))?;
 
-    let meta = {
+    let (meta, language, info) = {
         let mut file = zip.by_name(&index_file_name)?;
         let mut s = String::new();
         file.read_to_string(&mut s)?;
@@ -41,8 +51,13 @@ pub fn import_zip(state: &AppState, data: &[u8]) -> Result<String> {
         let mut dm = DictionaryMeta::new(DictionaryKind::Yomitan, name);
         dm.version = json["revision"].as_str().map(|s| s.to_string());
         dm.description = json["description"].as_str().map(|s| s.to_string());
-        dm
+        (
+            dm,
+            json["sourceLanguage"].as_str().and_then(normalize_language),
+            DictionaryInfo::from_index(&json),
+        )
     };
+    let revision = meta.version.clone();
 
     let dict_name = meta.name.clone();
 
@@ -51,30 +66,7 @@ pub fn import_zip(state: &AppState, data: &[u8]) -> Result<String> {
     let tx = conn.transaction()?;
 
     // 3. Register Dictionary in DB and Memory
-    let dict_id;
-    {
-        let mut next_id = state.next_dict_id.write().expect("lock");
-        dict_id = DictionaryId(*next_id);
-        *next_id += 1;
-
-        // Insert into DB
-        tx.execute(
-            "INSERT INTO dictionaries (id, name, priority, enabled) VALUES (?, ?, ?, ?)",
-            rusqlite::params![dict_id.0, dict_name, 0, true],
-        )?;
-
-        // Update Memory
-        let mut dicts = state.dictionaries.write().expect("lock");
-        dicts.insert(
-            dict_id,
-            DictionaryData {
-                id: dict_id,
-                name: dict_name.clone(),
-                priority: 0,
-                enabled: true,
-            },
-        );
-    }
+    let dict_id = register_dictionary(state, &tx, &dict_name, language, revision, info)?;
 
     // 4. Scan for term banks and Insert
     let file_names: Vec<String> = (0..zip.len())
@@ -82,95 +74,372 @@ pub fn import_zip(state: &AppState, data: &[u8]) -> Result<String> {
         .collect();
 
     let mut terms_found = 0;
-
-    // Create reusable encoder
-    let mut encoder = snap::raw::Encoder::new();
+    let limits = state.content_limits;
+    // Headwords of entries cut down by the content limits
+    let mut truncated = Vec::new();
+    // Only counted for the about page; kanji and meta banks aren't imported
+    let (mut kanji_found, mut meta_found) = (0, 0);
 
     for name in file_names {
-        if name.contains("term_bank") && name.ends_with(".json") {
+        if !name.ends_with(".json") {
+            continue;
+        }
+        if name.contains("kanji_bank") || name.contains("meta_bank") {
+            let mut file = zip.by_name(&name)?;
+            let mut s = String::new();
+            file.read_to_string(&mut s)?;
+            let entries = serde_json::from_str::<Vec<Value>>(&s).map_or(0, |bank| bank.len());
+            if name.contains("meta_bank") {
+                meta_found += entries;
+            } else {
+                kanji_found += entries;
+            }
+        } else if name.contains("term_bank") {
             info!("   -> Processing {}", name);
+            guard.beat(&format!("importing {name}"));
             let mut file = zip.by_name(&name)?;
             let mut s = String::new();
             file.read_to_string(&mut s)?;
 
-            let bank: Vec<Value> = serde_json::from_str(&s).unwrap_or_default();
+            // Entries are parsed one at a time so a single entry nested past serde_json's
+            // recursion limit doesn't take the whole bank down with it
+            let bank: Vec<&RawValue> = serde_json::from_str(&s).unwrap_or_default();
 
             // Note: Added dictionary_id column to INSERT
             let mut stmt =
                 tx.prepare("INSERT INTO terms (term, dictionary_id, json) VALUES (?, ?, ?)")?;
 
-            for entry in bank {
-                if let Some(arr) = entry.as_array() {
-                    let headword = arr.get(0).and_then(|v| v.as_str()).unwrap_or("");
-                    let reading = arr.get(1).and_then(|v| v.as_str()).unwrap_or("");
-
-                    let definition_arr = arr.get(5).and_then(|v| v.as_array());
-                    let mut content_list = Vec::new();
-                    if let Some(defs) = definition_arr {
-                        for d in defs {
-                            if let Some(str_def) = d.as_str() {
-                                content_list.push(structured::Content::String(str_def.to_string()));
-                            } else if let Some(obj_def) = d.as_object() {
-                                let json_str = serde_json::to_string(&obj_def).unwrap_or_default();
-                                content_list.push(structured::Content::String(json_str));
-                            }
-                        }
-                    }
+            encode_bank(&bank, dict_id, limits, state.import_threads, |entry| {
+                if entry.truncated {
+                    truncated.push(entry.headword.clone());
+                }
+                // Insert Headword mapping
+                stmt.execute(rusqlite::params![
+                    entry.headword,
+                    dict_id.0,
+                    entry.compressed
+                ])?;
+                terms_found += 1;
 
-                    if headword.is_empty() {
-                        continue;
-                    }
+                // Insert Reading mapping
+                if let Some(r) = entry.reading {
+                    stmt.execute(rusqlite::params![r, dict_id.0, entry.compressed])?;
+                }
+                Ok(())
+            })?;
+        }
+    }
 
-                    let tags_raw = arr.get(2).and_then(|v| v.as_str()).unwrap_or("");
-                    let mut tags_vec = Vec::new();
-                    if !tags_raw.is_empty() {
-                        for t_str in tags_raw.split_whitespace() {
-                            if let Ok(tag) = serde_json::from_value(json!(t_str)) {
-                                tags_vec.push(tag);
-                            }
-                        }
-                    }
+    record_entry_counts(state, &tx, dict_id, terms_found, kanji_found, meta_found)?;
+    tx.commit()?;
+    info!(
+        "💾 [Import] Database transaction committed. Total Terms: {}",
+        terms_found
+    );
+    state.preload.refresh(state.pool.clone());
+    state.dictionaries_changed();
 
-                    let record = Record::YomitanGlossary(Glossary {
-                        popularity: arr.get(4).and_then(|v| v.as_i64()).unwrap_or(0),
-                        tags: tags_vec,
-                        content: content_list,
-                    });
+    if truncated.is_empty() {
+        return Ok(format!("Imported '{}'", dict_name));
+    }
+    warn!(
+        "✂️ [Import] Truncated {} entries of '{dict_name}' that exceed the content limits: {}{}",
+        truncated.len(),
+        truncated[..truncated.len().min(10)].join(", "),
+        if truncated.len() > 10 { ", ..." } else { "" }
+    );
+    Ok(format!(
+        "Imported '{dict_name}' ({} oversized entries truncated)",
+        truncated.len()
+    ))
+}
 
-                    let stored_reading = if !reading.is_empty() && reading != headword {
-                        Some(reading.to_string())
-                    } else {
-                        None
-                    };
+/// Parses one term bank entry. Definitions too deeply nested to parse at all are replaced with
+/// [`TRUNCATED_MARKER`], in which case the entry comes back flagged as cut.
+fn parse_entry(raw: &RawValue) -> (Value, bool) {
+    if let Ok(entry) = serde_json::from_str(raw.get()) {
+        return (entry, false);
+    }
+    let Ok(fields) = serde_json::from_str::<Vec<&RawValue>>(raw.get()) else {
+        return (Value::Null, false);
+    };
+    let mut cut = false;
+    let entry = fields
+        .into_iter()
+        .enumerate()
+        .map(|(i, field)| {
+            if i != 5 {
+                return serde_json::from_str(field.get()).unwrap_or(Value::Null);
+            }
+            let definitions: Vec<&RawValue> = serde_json::from_str(field.get()).unwrap_or_default();
+            let definitions = definitions.into_iter().map(|def| {
+                serde_json::from_str(def.get()).unwrap_or_else(|_| {
+                    cut = true;
+                    json!({ "type": "structured-content", "content": TRUNCATED_MARKER })
+                })
+            });
+            Value::Array(definitions.collect())
+        })
+        .collect();
+    (Value::Array(entry), cut)
+}
 
-                    let stored = StoredRecord {
-                        dictionary_id: dict_id,
-                        record,
-                        reading: stored_reading.clone(),
-                    };
+/// Term bank entries compressed per unit of work handed to an import thread.
+const IMPORT_CHUNK: usize = 512;
 
-                    // CHANGED: Serialize to bytes -> Compress -> Insert
-                    let json_bytes = serde_json::to_vec(&stored)?;
-                    let compressed = encoder.compress_vec(&json_bytes)?;
+/// Threads compressing records during an import unless `MANGATAN_YOMITAN_IMPORT_THREADS` says
+/// otherwise: every core, but at most two on Android to keep the phone responsive.
+pub fn default_import_threads() -> usize {
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    match cfg!(target_os = "android") {
+        true => cores.min(2),
+        false => cores,
+    }
+}
 
-                    // Insert Headword mapping
-                    stmt.execute(rusqlite::params![headword, dict_id.0, compressed])?;
-                    terms_found += 1;
+/// Reads `MANGATAN_YOMITAN_IMPORT_THREADS`, defaulting to [`default_import_threads`]; `1`
+/// compresses on the importing thread alone.
+pub fn import_threads_from_env() -> usize {
+    std::env::var("MANGATAN_YOMITAN_IMPORT_THREADS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&threads| threads > 0)
+        .unwrap_or_else(default_import_threads)
+}
+
+/// One term bank entry, ready to insert.
+struct EncodedEntry {
+    headword: String,
+    /// Also inserted as a term, when it differs from the headword.
+    reading: Option<String>,
+    compressed: Vec<u8>,
+    /// Cut down by the content limits.
+    truncated: bool,
+}
 
-                    // Insert Reading mapping
-                    if let Some(r) = stored_reading {
-                        stmt.execute(rusqlite::params![r, dict_id.0, compressed])?;
+/// Builds and compresses the record of one term bank entry; `None` for entries without a
+/// headword.
+fn encode_entry(
+    raw: &RawValue,
+    dict_id: DictionaryId,
+    limits: ContentLimits,
+    encoder: &mut snap::raw::Encoder,
+) -> Result<Option<EncodedEntry>> {
+    let (entry, mut cut) = parse_entry(raw);
+    let Some(arr) = entry.as_array() else {
+        return Ok(None);
+    };
+    let headword = arr.get(0).and_then(|v| v.as_str()).unwrap_or("");
+    // Lookups search for plain ideographs, without variation selectors
+    let normalized = normalize_ideographs(headword);
+    let headword = normalized.as_deref().unwrap_or(headword);
+    let reading = arr.get(1).and_then(|v| v.as_str()).unwrap_or("");
+
+    let definition_arr = arr.get(5).and_then(|v| v.as_array());
+    if headword.is_empty() {
+        return Ok(None);
+    }
+
+    let mut content_list = Vec::new();
+    if let Some(defs) = definition_arr {
+        for d in defs {
+            let (content, was_cut) = if let Some(str_def) = d.as_str() {
+                limits.limit_text(str_def)
+            } else if d.is_object() {
+                limits.limit_structured(d.clone())
+            } else {
+                continue;
+            };
+            cut |= was_cut;
+            content_list.push(structured::Content::String(content));
+        }
+    }
+
+    let tags_raw = arr.get(2).and_then(|v| v.as_str()).unwrap_or("");
+    let mut tags_vec = Vec::new();
+    if !tags_raw.is_empty() {
+        for t_str in tags_raw.split_whitespace() {
+            if let Ok(tag) = serde_json::from_value(json!(t_str)) {
+                tags_vec.push(tag);
+            }
+        }
+    }
+
+    let record = Record::YomitanGlossary(Glossary {
+        popularity: arr.get(4).and_then(|v| v.as_i64()).unwrap_or(0),
+        tags: tags_vec,
+        content: content_list,
+    });
+
+    let stored_reading = if !reading.is_empty() && reading != headword {
+        Some(reading.to_string())
+    } else {
+        None
+    };
+
+    let stored = StoredRecord {
+        dictionary_id: dict_id,
+        record,
+        reading: stored_reading.clone(),
+    };
+
+    // CHANGED: Serialize to bytes -> Compress -> Insert
+    let json_bytes = serde_json::to_vec(&stored)?;
+    Ok(Some(EncodedEntry {
+        headword: headword.to_string(),
+        reading: stored_reading,
+        compressed: encoder.compress_vec(&json_bytes)?,
+        truncated: cut,
+    }))
+}
+
+/// Encodes the entries of `bank` on up to `threads` threads and hands each to `insert`, in
+/// bank order, on the calling thread. Compressing is what takes the CPU time; the inserts stay
+/// on the one thread that holds the transaction, so a failure still rolls everything back.
+fn encode_bank(
+    bank: &[&RawValue],
+    dict_id: DictionaryId,
+    limits: ContentLimits,
+    threads: usize,
+    mut insert: impl FnMut(EncodedEntry) -> Result<()>,
+) -> Result<()> {
+    let chunks: Vec<&[&RawValue]> = bank.chunks(IMPORT_CHUNK).collect();
+    let threads = threads.min(chunks.len());
+    if threads <= 1 {
+        let mut encoder = snap::raw::Encoder::new();
+        for raw in bank {
+            if let Some(entry) = encode_entry(raw, dict_id, limits, &mut encoder)? {
+                insert(entry)?;
+            }
+        }
+        return Ok(());
+    }
+
+    let next_chunk = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        let (sender, receiver) = mpsc::sync_channel(threads * 2);
+        for _ in 0..threads {
+            let (sender, chunks, next_chunk) = (sender.clone(), &chunks, &next_chunk);
+            scope.spawn(move || {
+                let mut encoder = snap::raw::Encoder::new();
+                loop {
+                    let index = next_chunk.fetch_add(1, Ordering::Relaxed);
+                    let Some(chunk) = chunks.get(index) else {
+                        return;
+                    };
+                    let encoded: Result<Vec<EncodedEntry>> = chunk
+                        .iter()
+                        .filter_map(|raw| {
+                            encode_entry(raw, dict_id, limits, &mut encoder).transpose()
+                        })
+                        .collect();
+                    // The inserter has failed and stopped listening
+                    if sender.send((index, encoded)).is_err() {
+                        return;
                     }
                 }
+            });
+        }
+        drop(sender);
+
+        // Chunks finish out of order; they're inserted in order so rowids match the bank
+        let mut finished = BTreeMap::new();
+        let mut next_insert = 0;
+        for (index, encoded) in receiver {
+            finished.insert(index, encoded);
+            while let Some(encoded) = finished.remove(&next_insert) {
+                encoded?.into_iter().try_for_each(&mut insert)?;
+                next_insert += 1;
             }
         }
-    }
+        Ok(())
+    })
+}
 
-    tx.commit()?;
-    info!(
-        "💾 [Import] Database transaction committed. Total Terms: {}",
-        terms_found
+/// Adds a dictionary row within `tx` and to the in-memory list, enabled at priority 0.
+pub(crate) fn register_dictionary(
+    state: &AppState,
+    tx: &rusqlite::Transaction,
+    name: &str,
+    language: Option<String>,
+    revision: Option<String>,
+    info: DictionaryInfo,
+) -> Result<DictionaryId> {
+    let dict_id = {
+        let mut next_id = state.next_dict_id.write().expect("lock");
+        *next_id += 1;
+        DictionaryId(*next_id - 1)
+    };
+    insert_dictionary(state, tx, dict_id, name, 0, language, revision, info)?;
+    Ok(dict_id)
+}
+
+/// Adds the dictionary row `dict_id` within `tx` and to the in-memory list, enabled.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn insert_dictionary(
+    state: &AppState,
+    tx: &rusqlite::Transaction,
+    dict_id: DictionaryId,
+    name: &str,
+    priority: i64,
+    language: Option<String>,
+    revision: Option<String>,
+    info: DictionaryInfo,
+) -> Result<()> {
+    // Insert into DB
+    tx.execute(
+        "INSERT INTO dictionaries (id, name, priority, enabled, language, revision, description,
+         author, attribution, url, imported_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        rusqlite::params![
+            dict_id.0,
+            name,
+            priority,
+            true,
+            language,
+            revision,
+            info.description,
+            info.author,
+            info.attribution,
+            info.url,
+            info.imported_at
+        ],
+    )?;
+
+    // Update Memory
+    let mut dicts = state.dictionaries.write().expect("lock");
+    dicts.insert(
+        dict_id,
+        DictionaryData {
+            id: dict_id,
+            name: name.to_string(),
+            priority,
+            enabled: true,
+            language,
+            revision,
+            info,
+        },
     );
+    Ok(())
+}
 
-    Ok(format!("Imported '{}'", dict_name))
+/// Stores how many entries of each bank a dictionary has, within `tx` and in memory.
+pub(crate) fn record_entry_counts(
+    state: &AppState,
+    tx: &rusqlite::Transaction,
+    dict_id: DictionaryId,
+    terms: usize,
+    kanji: usize,
+    meta: usize,
+) -> Result<()> {
+    let (terms, kanji, meta) = (terms as i64, kanji as i64, meta as i64);
+    tx.execute(
+        "UPDATE dictionaries SET term_count = ?, kanji_count = ?, meta_count = ? WHERE id = ?",
+        rusqlite::params![terms, kanji, meta, dict_id.0],
+    )?;
+    if let Some(dict) = state.dictionaries.write().expect("lock").get_mut(&dict_id) {
+        dict.info.term_count = Some(terms);
+        dict.info.kanji_count = Some(kanji);
+        dict.info.meta_count = Some(meta);
+    }
+    Ok(())
 }
@@ -1,46 +1,116 @@
 use axum::{
     Router,
     extract::DefaultBodyLimit,
-    routing::{get, post},
+    middleware,
+    routing::{get, patch, post},
 };
+use mangatan_core::data_dir::ReadOnlyDataDir;
 use std::{path::PathBuf, sync::Arc};
 use tower_http::{cors::CorsLayer, limit::RequestBodyLimitLayer};
 use tracing::{error, info};
 
+pub mod anki;
+pub mod content_limits;
+pub mod engine;
+pub mod examples;
+pub mod frequency;
 pub mod handlers;
 pub mod import;
 pub mod lookup;
+pub mod lookup_cache;
+pub mod maintenance;
+pub mod preload;
 pub mod state;
+pub mod user_dict;
+pub mod vocab;
+pub mod watchdog;
 
+use anki::{AnkiChecker, AnkiConfig};
+use engine::LookupEngine;
 use handlers::{
-    import_handler, install_defaults_handler, list_dictionaries_handler, lookup_handler,
-    manage_dictionaries_handler, reset_db_handler,
+    add_user_term_handler, anki_duplicate_handler, anki_validate_handler,
+    build_prefix_index_handler, bulk_toggle_handler, compact_handler, config_export_handler,
+    config_import_handler, delete_user_term_handler, drop_prefix_index_handler,
+    engine_status_handler, examples_handler, get_dictionary_handler, get_record_handler,
+    import_frequency_csv_handler, import_handler, install_defaults_handler,
+    list_dictionaries_handler, lookup_handler, manage_dictionaries_handler,
+    merge_dictionaries_handler, read_only_guard, reset_db_handler, storage_stats_handler,
+    tap_handler, track_activity, update_dictionary_handler, update_user_term_handler,
+    vocab_report_handler,
 };
 use lookup::LookupService;
-use state::AppState;
+use state::{AppState, StateConfig};
+use user_dict::USER_DICTIONARY_ID;
 
 pub static PREBAKED_DICT: &[u8] = include_bytes!("../assets/JMdict_english.zip");
 
 #[derive(Clone)]
 pub struct ServerState {
     pub app: AppState,
-    pub lookup: Arc<LookupService>,
+    /// Loaded in the background when `MANGATAN_YOMITAN_LAZY_ENGINE` is set.
+    pub lookup: LookupEngine,
+    pub anki: Arc<AnkiChecker>,
 }
 
-pub fn create_router(data_dir: PathBuf, auto_install: bool) -> Router {
-    let state = ServerState {
-        app: AppState::new(data_dir),
-        lookup: Arc::new(LookupService::new()),
-    };
+impl ServerState {
+    /// Fails when `data_dir` can't be written; see [`AppState::new`].
+    pub fn new(data_dir: PathBuf) -> Result<Self, ReadOnlyDataDir> {
+        let app = AppState::new(data_dir)?;
+        let lookup = match engine::lazy_from_env() {
+            true => LookupEngine::pending(),
+            false => LookupEngine::ready(LookupService::new()),
+        };
+        Ok(Self {
+            app,
+            lookup,
+            anki: Arc::new(AnkiChecker::new(AnkiConfig::from_env())),
+        })
+    }
+
+    /// A server that reads nothing from the environment: `config` for the state, default lookup
+    /// settings and no Anki check. For tests and embedders that configure it themselves.
+    pub fn with_config(data_dir: PathBuf, config: StateConfig) -> Result<Self, ReadOnlyDataDir> {
+        Ok(Self {
+            app: AppState::with_config(data_dir, config)?,
+            lookup: LookupService::default().into(),
+            anki: Arc::new(AnkiChecker::new(None)),
+        })
+    }
+
+    /// Logs what's loaded at the end of the session; called once when the server shuts down.
+    pub fn log_session_summary(&self) {
+        let (total, enabled) = {
+            let dicts = self.app.dictionaries.read().expect("lock");
+            (dicts.len(), dicts.values().filter(|d| d.enabled).count())
+        };
+        info!(
+            "📊 [Yomitan] Session summary: dictionaries_loaded={total} dictionaries_enabled={enabled}"
+        );
+    }
+}
+
+pub fn create_router(data_dir: PathBuf, auto_install: bool) -> Result<Router, ReadOnlyDataDir> {
+    Ok(create_router_with_state(
+        ServerState::new(data_dir)?,
+        auto_install,
+    ))
+}
 
+/// Same as [`create_router`], around a state the caller keeps a handle to.
+pub fn create_router_with_state(state: ServerState, auto_install: bool) -> Router {
     let app_state_clone = state.app.clone();
 
+    if !state.lookup.is_ready() {
+        tokio::spawn(state.lookup.clone().load(LookupService::new));
+    }
+
     tokio::spawn(async move {
         tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
 
         let needs_import = {
+            // The user's own entries don't stand in for a real dictionary
             let dicts = app_state_clone.dictionaries.read().expect("lock");
-            dicts.is_empty()
+            dicts.keys().all(|id| *id == USER_DICTIONARY_ID)
         };
 
         if needs_import {
@@ -62,15 +132,57 @@ pub fn create_router(data_dir: PathBuf, auto_install: bool) -> Router {
         }
     });
 
+    tokio::spawn(maintenance::auto_compact(
+        state.app.clone(),
+        maintenance::compact_threshold_from_env(),
+    ));
+    tokio::spawn(watchdog::watch_writer(state.app.clone()));
+
     let limit = 1024 * 1024 * 1024;
 
-    Router::new()
-        .route("/lookup", get(lookup_handler))
-        .route("/dictionaries", get(list_dictionaries_handler))
+    let mutating_routes = Router::new()
         .route("/import", post(import_handler))
+        .route("/import-frequency-csv", post(import_frequency_csv_handler))
         .route("/reset", post(reset_db_handler))
         .route("/manage", post(manage_dictionaries_handler))
+        .route("/dictionaries/merge", post(merge_dictionaries_handler))
+        .route("/dictionaries/bulk-toggle", post(bulk_toggle_handler))
+        .route("/dictionaries/{id}", patch(update_dictionary_handler))
         .route("/install-defaults", post(install_defaults_handler))
+        .route("/maintenance/compact", post(compact_handler))
+        .route(
+            "/maintenance/prefix-index",
+            post(build_prefix_index_handler).delete(drop_prefix_index_handler),
+        )
+        .route("/config/import", post(config_import_handler))
+        .route("/user-terms", post(add_user_term_handler))
+        .route(
+            "/user-terms/{id}",
+            patch(update_user_term_handler).delete(delete_user_term_handler),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            read_only_guard,
+        ));
+
+    Router::new()
+        .route("/lookup", get(lookup_handler))
+        .route("/engine-status", get(engine_status_handler))
+        .route("/tap", post(tap_handler))
+        .route("/dictionaries", get(list_dictionaries_handler))
+        .route("/dictionaries/{id}", get(get_dictionary_handler))
+        .route("/records/{id}", get(get_record_handler))
+        .route("/config/export", get(config_export_handler))
+        .route("/storage-stats", get(storage_stats_handler))
+        .route("/examples", get(examples_handler))
+        .route("/vocab-report", post(vocab_report_handler))
+        .route("/anki/duplicate", get(anki_duplicate_handler))
+        .route("/anki/validate", post(anki_validate_handler))
+        .merge(mutating_routes)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            track_activity,
+        ))
         .layer(CorsLayer::permissive())
         .layer(DefaultBodyLimit::max(limit))
         .layer(RequestBodyLimitLayer::new(limit))
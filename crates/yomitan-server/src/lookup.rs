@@ -1,17 +1,52 @@
-use crate::state::{AppState, StoredRecord};
-use lindera::{
-    dictionary::{DictionaryKind, load_dictionary_from_kind},
-    mode::Mode,
-    segmenter::Segmenter,
-    tokenizer::Tokenizer,
+use crate::{
+    frequency::frequency_rank,
+    lookup_cache::{DEFAULT_LOOKUP_CACHE_SIZE, LookupCache},
+    preload::PreloadedRows,
+    state::{AppState, StoredRecord, terms_by_term_sql},
 };
+use icu_normalizer::ComposingNormalizerBorrowed;
+use mangatan_tokenize::Tokenizer;
+use serde::Deserialize;
+use std::cmp::Reverse;
 use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
-use tracing::{error, info};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use tracing::error;
 use wordbase_api::{DictionaryId, FrequencyValue, Record, RecordEntry, RecordId, Span, Term};
 
+/// Default for [`LookupService::max_deinflection_depth`].
+pub const DEFAULT_MAX_DEINFLECTION_DEPTH: usize = 6;
+/// Default for [`LookupService::scan_window`].
+pub const DEFAULT_SCAN_WINDOW: usize = 24;
+pub const MAX_SCAN_WINDOW: usize = 48;
+/// How many characters before the cursor a [`ScanMode::Around`] lookup starts scans from.
+pub const MAX_SCAN_BACK: usize = 10;
+/// How many characters longer than the scanned text a deinflected candidate can get (stems like
+/// 食べ -> 食べる, 갔 -> 가다). Substrings longer than the longest stored term plus this can't
+/// match anything.
+const MAX_DEINFLECTION_GROWTH: usize = 4;
+
 pub struct LookupService {
     tokenizer: Arc<Tokenizer>,
+    max_deinflection_depth: usize,
+    scan_window: usize,
+    script_fast_path: bool,
+    tokenize_fallback: bool,
+    cache: LookupCache,
+    // Longest term in the database (in characters), for the dictionaries generation it was read at
+    longest_term: Mutex<Option<(u64, usize)>>,
+}
+
+/// Where a lookup looks for words relative to the cursor.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanMode {
+    /// Words starting at the cursor.
+    #[default]
+    Forward,
+    /// Words covering the character at the cursor, wherever they start, for clients that put
+    /// the cursor on the end of a selection or on whichever character was tapped.
+    Around,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -19,6 +54,10 @@ struct Candidate {
     pub word: String,
     pub source_len: usize,
     pub _reason: String,
+    // Number of deinflection steps applied to reach `word` (0 = surface form)
+    pub depth: usize,
+    // Language whose rules produced `word`; `None` for rewrites that apply to any language
+    pub language: Option<&'static str>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -30,22 +69,208 @@ enum Script {
     Other,
 }
 
+/// The default settings, whatever the environment says.
+impl Default for LookupService {
+    fn default() -> Self {
+        Self {
+            tokenizer: Tokenizer::shared(),
+            max_deinflection_depth: DEFAULT_MAX_DEINFLECTION_DEPTH,
+            scan_window: DEFAULT_SCAN_WINDOW,
+            script_fast_path: true,
+            tokenize_fallback: true,
+            cache: LookupCache::new(DEFAULT_LOOKUP_CACHE_SIZE),
+            longest_term: Mutex::new(None),
+        }
+    }
+}
+
 impl LookupService {
+    /// Defaults overridden by `MANGATAN_YOMITAN_MAX_DEINFLECTION_DEPTH`,
+    /// `MANGATAN_YOMITAN_SCAN_WINDOW`, `MANGATAN_YOMITAN_LOOKUP_CACHE` and
+    /// `MANGATAN_YOMITAN_TOKENIZE_FALLBACK`.
     pub fn new() -> Self {
-        info!("⏳ [Lookup] Initializing Lindera (UniDic)...");
-        let dictionary = load_dictionary_from_kind(DictionaryKind::UniDic)
-            .expect("Failed to load UniDic dictionary");
-
-        let segmenter = Segmenter::new(Mode::Normal, dictionary, None);
-        let tokenizer = Tokenizer::new(segmenter);
-        info!("✅ [Lookup] Lindera Initialized.");
+        let max_deinflection_depth = std::env::var("MANGATAN_YOMITAN_MAX_DEINFLECTION_DEPTH")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_DEINFLECTION_DEPTH);
+        let scan_window = std::env::var("MANGATAN_YOMITAN_SCAN_WINDOW")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_SCAN_WINDOW);
+        let tokenize_fallback =
+            std::env::var("MANGATAN_YOMITAN_TOKENIZE_FALLBACK").map_or(true, |v| v.trim() != "0");
 
         Self {
-            tokenizer: Arc::new(tokenizer),
+            cache: LookupCache::from_env(),
+            ..Self::default()
         }
+        .with_max_deinflection_depth(max_deinflection_depth)
+        .with_scan_window(scan_window)
+        .with_tokenize_fallback(tokenize_fallback)
+    }
+
+    /// Caps how many deinflection steps a candidate may be away from the scanned text. Every
+    /// candidate costs a `WHERE term = ?` query per scanned substring, so slower devices can
+    /// trade recall for speed by lowering this (0 disables deinflection entirely).
+    pub fn with_max_deinflection_depth(mut self, depth: usize) -> Self {
+        self.max_deinflection_depth = depth;
+        self
+    }
+
+    pub fn max_deinflection_depth(&self) -> usize {
+        self.max_deinflection_depth
+    }
+
+    /// How many characters from the cursor a lookup considers (clamped to 1..=48). Each one is
+    /// a substring to generate candidates for, so longer windows cost more per lookup.
+    pub fn with_scan_window(mut self, chars: usize) -> Self {
+        self.scan_window = chars.clamp(1, MAX_SCAN_WINDOW);
+        self
+    }
+
+    pub fn scan_window(&self) -> usize {
+        self.scan_window
+    }
+
+    /// Turns off the early return for text without kana or kanji; for benchmarks and debugging.
+    pub fn with_script_fast_path(mut self, enabled: bool) -> Self {
+        self.script_fast_path = enabled;
+        self
+    }
+
+    /// Whether Japanese text Lindera fails on still gets a dictionary form guessed from its
+    /// okurigana (see [`fallback_lemmas`]). Off, such lookups only match the surface form.
+    pub fn with_tokenize_fallback(mut self, enabled: bool) -> Self {
+        self.tokenize_fallback = enabled;
+        self
+    }
+
+    /// Replaces the lookup result cache with one of `size` entries (0 disables it).
+    pub fn with_lookup_cache_size(mut self, size: usize) -> Self {
+        self.cache = LookupCache::new(size);
+        self
     }
 
     pub fn search(&self, state: &AppState, text: &str, cursor_offset: usize) -> Vec<RecordEntry> {
+        self.search_in(state, text, cursor_offset, None)
+    }
+
+    /// Like [`search`](Self::search), but only enabled dictionaries in `subset` (when given)
+    /// contribute results.
+    pub fn search_in(
+        &self,
+        state: &AppState,
+        text: &str,
+        cursor_offset: usize,
+        subset: Option<&HashSet<DictionaryId>>,
+    ) -> Vec<RecordEntry> {
+        self.search_with(state, text, cursor_offset, subset, false)
+    }
+
+    /// Like [`search`](Self::search), but searches exactly the dictionaries in `ids`, enabled
+    /// or not, for a one-off "search in" without touching the enabled flags.
+    pub fn search_only(
+        &self,
+        state: &AppState,
+        text: &str,
+        cursor_offset: usize,
+        ids: &HashSet<DictionaryId>,
+    ) -> Vec<RecordEntry> {
+        self.search_with(state, text, cursor_offset, Some(ids), true)
+    }
+
+    /// A [`ScanMode::Around`] lookup: entries for every word covering the character at
+    /// `cursor_offset` (the last character when it's at the end of `text`) that starts no more
+    /// than [`MAX_SCAN_BACK`] characters before it. Unlike the forward searches, spans are
+    /// relative to the start of `text`. Longest matches come first, and among those the ones
+    /// starting nearest the cursor. With `include_disabled`, `subset` is searched as in
+    /// [`search_only`](Self::search_only).
+    pub fn search_around(
+        &self,
+        state: &AppState,
+        text: &str,
+        cursor_offset: usize,
+        subset: Option<&HashSet<DictionaryId>>,
+        include_disabled: bool,
+    ) -> Vec<RecordEntry> {
+        let Some((anchor, starts)) = scan_starts(text, cursor_offset, MAX_SCAN_BACK) else {
+            return vec![];
+        };
+
+        let mut results = Vec::new();
+        let mut seen = HashSet::new();
+        for start in starts {
+            let start_chars = text[..start].chars().count() as u64;
+            for mut entry in self.search_with(state, text, start, subset, include_disabled) {
+                let end = start + entry.span_bytes.end as usize;
+                if end <= anchor || !seen.insert(entry.record_id.0) {
+                    continue;
+                }
+                entry.span_bytes = Span {
+                    start: start as u64,
+                    end: end as u64,
+                };
+                entry.span_chars = Span {
+                    start: start_chars,
+                    end: start_chars + entry.span_chars.end,
+                };
+                results.push(entry);
+            }
+        }
+        // Stable, so each start's own ranking survives among matches of one length
+        results.sort_by_key(|entry| Reverse(entry.span_chars.end - entry.span_chars.start));
+        results
+    }
+
+    fn search_with(
+        &self,
+        state: &AppState,
+        text: &str,
+        cursor_offset: usize,
+        subset: Option<&HashSet<DictionaryId>>,
+        include_disabled: bool,
+    ) -> Vec<RecordEntry> {
+        let start_index = self.snap_to_char_boundary(text, cursor_offset);
+        if start_index >= text.len() {
+            return vec![];
+        }
+
+        let search_text = &text[start_index..];
+        let written: Vec<char> = search_text.chars().take(self.scan_window).collect();
+        // Only the window from the cursor matters, so overlapping hovers share entries
+        let window: String = written.iter().collect();
+        let generation = state.dictionaries_generation();
+        if let Some(cached) = self
+            .cache
+            .get(generation, &window, subset, include_disabled)
+        {
+            return cached;
+        }
+        // Terms are stored with ideographs normalized, so the window is matched the same way
+        // and spans are mapped back onto the text as written
+        let (chars, written_ends) = normalize_window(&written);
+
+        let dict_configs: HashMap<DictionaryId, (bool, i64, Option<String>)> = {
+            let dicts = state.dictionaries.read().expect("lock");
+            dicts
+                .iter()
+                .map(|(id, d)| {
+                    let enabled = (d.enabled || include_disabled)
+                        && subset.is_none_or(|ids| ids.contains(id));
+                    (*id, (enabled, d.priority, d.language.clone()))
+                })
+                .collect()
+        };
+
+        // A pasted paragraph of English or a base64 blob can't match a Japanese dictionary, so
+        // skip the queries, unless a dictionary for another language is in use
+        let japanese_only = dict_configs.values().all(|(enabled, _, language)| {
+            !*enabled || matches!(language.as_deref(), None | Some("ja"))
+        });
+        if self.script_fast_path && japanese_only && !chars.iter().any(|c| is_japanese_char(*c)) {
+            return vec![];
+        }
+
         let mut results = Vec::new();
         let mut processed_candidates = HashSet::new();
 
@@ -56,34 +281,31 @@ impl LookupService {
                 return vec![];
             }
         };
-
-        let dict_configs: HashMap<DictionaryId, (bool, i64)> = {
-            let dicts = state.dictionaries.read().expect("lock");
-            dicts
-                .iter()
-                .map(|(id, d)| (*id, (d.enabled, d.priority)))
-                .collect()
+        let longest_term = self.longest_term(&conn, generation);
+        // Language-specific candidates are only generated when an enabled dictionary takes them
+        let wants_language = |language: &str| {
+            dict_configs.values().any(|(enabled, _, dict_language)| {
+                *enabled && accepts_language(dict_language.as_deref(), Some(language))
+            })
         };
 
-        let mut stmt = match conn.prepare("SELECT dictionary_id, json FROM terms WHERE term = ?") {
+        let mut stmt = match conn.prepare(&terms_by_term_sql(subset)) {
             Ok(s) => s,
             Err(e) => {
                 error!("❌ DB Prepare Error: {}", e);
                 return vec![];
             }
         };
+        let preloaded = state.preload.snapshot();
 
-        let start_index = self.snap_to_char_boundary(text, cursor_offset);
-        if start_index >= text.len() {
-            return vec![];
-        }
-
-        let search_text = &text[start_index..];
-        let chars: Vec<char> = search_text.chars().take(24).collect();
         let script = self.detect_script(&chars);
         let mut decoder = snap::raw::Decoder::new();
 
-        for len in (1..=chars.len()).rev() {
+        // Longest first; substrings too long for any stored term are skipped outright
+        let longest_source = chars
+            .len()
+            .min(longest_term.saturating_add(MAX_DEINFLECTION_GROWTH));
+        for len in (1..=longest_source).rev() {
             let substring: String = chars[0..len].iter().collect();
 
             // Skip single character Latin/Symbol lookups unless explicitly desired
@@ -95,92 +317,121 @@ impl LookupService {
                 continue;
             }
 
-            let candidates = self.generate_candidates(&substring, &script);
+            let candidates = self.generate_candidates(&substring, &script, &wants_language);
 
             for candidate in candidates {
                 if !self.is_valid_candidate(&substring, &candidate.word, &script) {
                     continue;
                 }
 
-                if processed_candidates.contains(&candidate.word) {
+                if processed_candidates.contains(&candidate.word)
+                    || candidate.word.chars().count() > longest_term
+                {
                     continue;
                 }
                 processed_candidates.insert(candidate.word.clone());
 
-                let rows = stmt.query_map(rusqlite::params![candidate.word], |row| {
-                    let dict_id: i64 = row.get(0)?;
-                    let compressed: Vec<u8> = row.get(1)?;
-                    Ok((dict_id, compressed))
-                });
-
-                if let Ok(mapped_rows) = rows {
-                    for row_result in mapped_rows {
-                        if let Ok((dict_id_raw, compressed_data)) = row_result {
-                            let dict_id = DictionaryId(dict_id_raw);
+                let queried: PreloadedRows;
+                let rows = match preloaded.get(&candidate.word) {
+                    Some(rows) => rows,
+                    None => {
+                        queried = stmt
+                            .query_map(rusqlite::params![candidate.word], |row| {
+                                let dict_id: i64 = row.get(0)?;
+                                let compressed: Vec<u8> = row.get(1)?;
+                                let row_id: i64 = row.get(2)?;
+                                Ok((dict_id, compressed, row_id))
+                            })
+                            .map(|rows| rows.flatten().collect())
+                            .unwrap_or_default();
+                        &queried
+                    }
+                };
 
-                            if let Some((enabled, _)) = dict_configs.get(&dict_id) {
-                                if !*enabled {
-                                    continue;
-                                }
-                            }
+                for (dict_id_raw, compressed_data, row_id) in rows {
+                    let dict_id = DictionaryId(*dict_id_raw);
 
-                            if let Ok(decompressed) = decoder.decompress_vec(&compressed_data) {
-                                if let Ok(stored) =
-                                    serde_json::from_slice::<StoredRecord>(&decompressed)
-                                {
-                                    let match_len = candidate.source_len;
-
-                                    let term_obj = Term::from_parts(
-                                        Some(candidate.word.as_str()),
-                                        stored.reading.as_deref(),
-                                    )
-                                    .unwrap_or_else(|| {
-                                        Term::from_headword(candidate.word.clone()).unwrap()
-                                    });
-
-                                    let mut freq = 0;
-                                    if let Record::YomitanGlossary(g) = &stored.record {
-                                        freq = g.popularity;
-                                    }
+                    if let Some((enabled, _, language)) = dict_configs.get(&dict_id) {
+                        if !*enabled || !accepts_language(language.as_deref(), candidate.language) {
+                            continue;
+                        }
+                    }
 
-                                    results.push(RecordEntry {
-                                        span_bytes: Span {
-                                            start: 0,
-                                            end: candidate.word.len() as u64,
-                                        },
-                                        span_chars: Span {
-                                            start: 0,
-                                            end: match_len as u64,
-                                        },
-                                        source: stored.dictionary_id,
-                                        term: term_obj,
-                                        record_id: RecordId(0),
-                                        record: stored.record.clone(),
-                                        profile_sorting_frequency: None,
-                                        source_sorting_frequency: Some(FrequencyValue::Rank(freq)),
-                                    });
-                                }
+                    if let Ok(decompressed) = decoder.decompress_vec(compressed_data) {
+                        if let Ok(stored) = serde_json::from_slice::<StoredRecord>(&decompressed) {
+                            let match_len = written_ends[candidate.source_len];
+
+                            let term_obj = Term::from_parts(
+                                Some(candidate.word.as_str()),
+                                stored.reading.as_deref(),
+                            )
+                            .unwrap_or_else(|| {
+                                Term::from_headword(candidate.word.clone()).unwrap()
+                            });
+
+                            let mut freq = 0;
+                            if let Record::YomitanGlossary(g) = &stored.record {
+                                freq = g.popularity;
                             }
+
+                            results.push(RecordEntry {
+                                span_bytes: Span {
+                                    start: 0,
+                                    end: written[..match_len]
+                                        .iter()
+                                        .map(|c| c.len_utf8())
+                                        .sum::<usize>()
+                                        as u64,
+                                },
+                                span_chars: Span {
+                                    start: 0,
+                                    end: match_len as u64,
+                                },
+                                source: stored.dictionary_id,
+                                term: term_obj,
+                                record_id: RecordId(*row_id),
+                                record: stored.record.clone(),
+                                profile_sorting_frequency: None,
+                                source_sorting_frequency: Some(FrequencyValue::Rank(freq)),
+                            });
                         }
                     }
                 }
             }
         }
 
+        apply_frequency_lists(&mut results, &dict_configs);
+
         results.sort_by(|a, b| {
             let len_cmp = b.span_chars.end.cmp(&a.span_chars.end);
             if len_cmp != std::cmp::Ordering::Equal {
                 return len_cmp;
             }
 
-            let prio_a = dict_configs.get(&a.source).map(|(_, p)| *p).unwrap_or(999);
-            let prio_b = dict_configs.get(&b.source).map(|(_, p)| *p).unwrap_or(999);
+            let prio_a = dict_configs
+                .get(&a.source)
+                .map(|(_, p, _)| *p)
+                .unwrap_or(999);
+            let prio_b = dict_configs
+                .get(&b.source)
+                .map(|(_, p, _)| *p)
+                .unwrap_or(999);
 
             let prio_cmp = prio_a.cmp(&prio_b);
             if prio_cmp != std::cmp::Ordering::Equal {
                 return prio_cmp;
             }
 
+            // Frequency list ranks, lowest first, then entries no list has
+            let rank_of = |entry: &RecordEntry| match entry.profile_sorting_frequency {
+                Some(FrequencyValue::Rank(rank)) => rank,
+                _ => i64::MAX,
+            };
+            let rank_cmp = rank_of(a).cmp(&rank_of(b));
+            if rank_cmp != std::cmp::Ordering::Equal {
+                return rank_cmp;
+            }
+
             let get_val = |f: Option<&FrequencyValue>| -> i64 {
                 match f {
                     Some(FrequencyValue::Rank(v)) => *v,
@@ -192,9 +443,53 @@ impl LookupService {
                 .cmp(&get_val(a.source_sorting_frequency.as_ref()))
         });
 
+        self.cache
+            .insert(generation, &window, subset, include_disabled, &results);
         results
     }
 
+    /// Length in characters of the longest stored term, re-read after dictionaries change.
+    /// `usize::MAX` (no bound) when it can't be read.
+    fn longest_term(&self, conn: &rusqlite::Connection, generation: u64) -> usize {
+        let mut longest = self.longest_term.lock().expect("lock");
+        if let Some((read_at, chars)) = *longest
+            && read_at == generation
+        {
+            return chars;
+        }
+        match conn.query_row(
+            "SELECT COALESCE(MAX(length(term)), 0) FROM terms",
+            [],
+            |row| row.get::<_, i64>(0),
+        ) {
+            Ok(chars) => {
+                let chars = chars as usize;
+                *longest = Some((generation, chars));
+                chars
+            }
+            Err(e) => {
+                error!("❌ Failed to read the longest term length: {}", e);
+                usize::MAX
+            }
+        }
+    }
+
+    /// Byte range of the token covering the `char_offset`-th character, as segmented by the
+    /// tokenizer. Falls back to just that character when no token covers it (e.g. the tokenizer
+    /// failed). `None` when the offset is past the end of `text`.
+    pub fn token_at(&self, text: &str, char_offset: usize) -> Option<Range<usize>> {
+        let (byte_offset, c) = text.char_indices().nth(char_offset)?;
+        let fallback = byte_offset..byte_offset + c.len_utf8();
+
+        Some(
+            self.tokenizer
+                .tokenize(text)
+                .into_iter()
+                .find(|t| t.byte_range.contains(&byte_offset))
+                .map_or(fallback, |t| t.byte_range),
+        )
+    }
+
     fn snap_to_char_boundary(&self, text: &str, index: usize) -> usize {
         if index >= text.len() {
             return text.len();
@@ -219,7 +514,7 @@ impl LookupService {
             if c.is_ascii_alphabetic() || (c >= '\u{00C0}' && c <= '\u{00FF}') {
                 return Script::Latin;
             }
-            if c >= '\u{4E00}' && c <= '\u{9FFF}' {
+            if is_ideograph(c) {
                 return Script::Chinese;
             }
         }
@@ -230,14 +525,17 @@ impl LookupService {
         if source == candidate {
             return true;
         }
+        // Iteration mark rewrites change the kanji set by construction (時々 -> 時時)
+        if expand_iteration_marks(source).as_deref() == Some(candidate)
+            || compress_iteration_marks(source).as_deref() == Some(candidate)
+        {
+            return true;
+        }
         match script {
             Script::Japanese | Script::Chinese => {
-                let source_kanji: Vec<char> =
-                    source.chars().filter(|c| self.is_ideograph(*c)).collect();
-                let cand_kanji: Vec<char> = candidate
-                    .chars()
-                    .filter(|c| self.is_ideograph(*c))
-                    .collect();
+                let source_kanji: Vec<char> = source.chars().filter(|c| is_ideograph(*c)).collect();
+                let cand_kanji: Vec<char> =
+                    candidate.chars().filter(|c| is_ideograph(*c)).collect();
                 if !cand_kanji.is_empty() {
                     for k in cand_kanji {
                         if source_kanji.contains(&k) {
@@ -252,42 +550,74 @@ impl LookupService {
         }
     }
 
-    fn is_ideograph(&self, c: char) -> bool {
-        c >= '\u{4E00}' && c <= '\u{9FFF}'
-    }
-
-    fn generate_candidates(&self, text: &str, script: &Script) -> Vec<Candidate> {
+    fn generate_candidates(
+        &self,
+        text: &str,
+        script: &Script,
+        wants_language: &dyn Fn(&str) -> bool,
+    ) -> Vec<Candidate> {
         let mut candidates = Vec::new();
 
         candidates.push(Candidate {
             word: text.to_string(),
             source_len: text.chars().count(),
             _reason: "Original".to_string(),
+            depth: 0,
+            language: None,
         });
 
+        if matches!(script, Script::Japanese | Script::Chinese) && wants_language("ja") {
+            let rewrites = [expand_iteration_marks(text), compress_iteration_marks(text)];
+            for word in rewrites.into_iter().flatten() {
+                candidates.push(Candidate {
+                    word,
+                    source_len: text.chars().count(),
+                    _reason: "IterationMark".to_string(),
+                    depth: 0,
+                    language: Some("ja"),
+                });
+            }
+        }
+
+        // Each pass below only produces deinflections, so a limit of 0 skips them all, tokenizer
+        // included; the English one still lowercases
+        let max_depth = self.max_deinflection_depth;
         match script {
-            Script::Japanese => {
-                if let Ok(mut tokens) = self.tokenizer.tokenize(text) {
-                    if let Some(first_token) = tokens.first_mut() {
-                        let details = first_token.details();
-                        if details.len() >= 8 {
-                            let lemma = &details[7];
-                            if *lemma != "*" && *lemma != text {
-                                candidates.push(Candidate {
-                                    word: lemma.to_string(),
-                                    source_len: first_token.text.chars().count(),
-                                    _reason: "Lindera".to_string(),
-                                });
-                            }
+            Script::Japanese if max_depth >= 1 && wants_language("ja") => {
+                match self.tokenizer.try_tokenize(text) {
+                    Ok(tokens) => {
+                        if let Some(first_token) = tokens.into_iter().next()
+                            && let Some(lemma) = first_token.lemma
+                            && lemma != text
+                        {
+                            candidates.push(Candidate {
+                                word: lemma,
+                                source_len: first_token.end - first_token.start,
+                                _reason: "Lindera".to_string(),
+                                depth: 1,
+                                language: Some("ja"),
+                            });
                         }
                     }
+                    // The tokenizer logs the failure; guess instead of losing deinflection entirely
+                    Err(_) if self.tokenize_fallback => {
+                        let (source_len, lemmas) = fallback_lemmas(text);
+                        candidates.extend(lemmas.into_iter().map(|word| Candidate {
+                            word,
+                            source_len,
+                            _reason: "Fallback".to_string(),
+                            depth: 1,
+                            language: Some("ja"),
+                        }));
+                    }
+                    Err(_) => {}
                 }
             }
-            Script::Korean => {
+            Script::Korean if max_depth >= 1 && wants_language("ko") => {
                 candidates.extend(self.generate_korean_candidates(text));
             }
             Script::Latin => {
-                candidates.extend(self.generate_english_candidates(text));
+                candidates.extend(self.generate_english_candidates(text, max_depth));
             }
             _ => {}
         }
@@ -351,6 +681,8 @@ impl LookupService {
                         word: recomposed,
                         source_len: src_len,
                         _reason: "Ko-Deinflect".to_string(),
+                        depth: 1,
+                        language: Some("ko"),
                     });
                 }
             }
@@ -501,7 +833,9 @@ impl LookupService {
 
     // --- ENGLISH PROCESSING ---
 
-    fn generate_english_candidates(&self, text: &str) -> Vec<Candidate> {
+    /// Lowercasing, then prefix and suffix rules (depth 1) and doubled consonants (depth 2), as
+    /// far as `max_depth` allows.
+    fn generate_english_candidates(&self, text: &str, max_depth: usize) -> Vec<Candidate> {
         let mut results = Vec::new();
         let src_len = text.chars().count();
         let lower = text.to_lowercase();
@@ -511,8 +845,13 @@ impl LookupService {
                 word: lower.clone(),
                 source_len: src_len,
                 _reason: "Lowercase".to_string(),
+                depth: 0,
+                language: None,
             });
         }
+        if max_depth == 0 {
+            return results;
+        }
 
         // 1. Prefixes
         let prefixes = vec![("un", ""), ("re", "")];
@@ -524,6 +863,8 @@ impl LookupService {
                     word: format!("{}{}", repl, stem),
                     source_len: src_len,
                     _reason: "En-Prefix".to_string(),
+                    depth: 1,
+                    language: Some("en"),
                 });
             }
         }
@@ -560,10 +901,12 @@ impl LookupService {
                     word: format!("{}{}", stem, repl),
                     source_len: src_len,
                     _reason: "En-Suffix".to_string(),
+                    depth: 1,
+                    language: Some("en"),
                 });
 
                 // Double consonant check (e.g. running -> runn -> run)
-                if check_double && stem.len() >= 2 {
+                if check_double && max_depth >= 2 && stem.len() >= 2 {
                     let last_char = stem.chars().last().unwrap();
                     let second_last = stem.chars().nth(stem.len() - 2).unwrap();
 
@@ -573,6 +916,8 @@ impl LookupService {
                             word: format!("{}{}", reduced_stem, repl),
                             source_len: src_len,
                             _reason: "En-Double".to_string(),
+                            depth: 2,
+                            language: Some("en"),
                         });
                     }
                 }
@@ -582,3 +927,433 @@ impl LookupService {
         results
     }
 }
+
+/// Takes the rows of frequency lists (see [`frequency`](crate::frequency)) out of `results` and
+/// sets their rank as the sorting frequency of the entries for the same term, from the highest
+/// priority list. A list without readings covers every reading of its terms.
+fn apply_frequency_lists(
+    results: &mut Vec<RecordEntry>,
+    dict_configs: &HashMap<DictionaryId, (bool, i64, Option<String>)>,
+) {
+    let term_key = |term: &Term| match term {
+        Term::Full(h, r) => (h.to_string(), r.to_string()),
+        Term::Headword(h) => (h.to_string(), String::new()),
+        Term::Reading(r) => (r.to_string(), String::new()),
+    };
+
+    // (headword, reading) -> (priority, rank)
+    let mut ranks: HashMap<(String, String), (i64, i64)> = HashMap::new();
+    results.retain(|entry| {
+        let Some(rank) = frequency_rank(&entry.record) else {
+            return true;
+        };
+        let priority = dict_configs.get(&entry.source).map_or(999, |(_, p, _)| *p);
+        ranks
+            .entry(term_key(&entry.term))
+            .and_modify(|best| {
+                if priority < best.0 {
+                    *best = (priority, rank);
+                }
+            })
+            .or_insert((priority, rank));
+        false
+    });
+    if ranks.is_empty() {
+        return;
+    }
+
+    for entry in results.iter_mut() {
+        let (headword, reading) = term_key(&entry.term);
+        let rank = ranks
+            .get(&(headword.clone(), reading))
+            .or_else(|| ranks.get(&(headword, String::new())));
+        if let Some(&(_, rank)) = rank {
+            entry.profile_sorting_frequency = Some(FrequencyValue::Rank(rank));
+        }
+    }
+}
+
+/// Whether a dictionary in `dict_language` takes a candidate produced by `candidate_language`'s
+/// rules. Japanese dictionaries, and those with no language set, keep the full pipeline; any
+/// other language only takes surface forms and its own rules (e.g. Korean deinflection), so a
+/// Chinese dictionary is matched by plain longest prefix.
+fn accepts_language(dict_language: Option<&str>, candidate_language: Option<&str>) -> bool {
+    match (dict_language, candidate_language) {
+        (_, None) | (None | Some("ja"), _) => true,
+        (Some(dict), Some(candidate)) => dict == candidate,
+    }
+}
+
+// --- ITERATION MARKS ---
+
+const UNVOICED_KANA: &str =
+    "かきくけこさしすせそたちつてとはひふへほうカキクケコサシスセソタチツテトハヒフヘホウ";
+const VOICED_KANA: &str =
+    "がぎぐげござじずぜぞだぢづでどばびぶべぼゔガギグゲゴザジズゼゾダヂヅデドバビブベボヴ";
+
+/// Kana, kanji and the marks that only appear in Japanese text (々, 〆, ー, half-width kana).
+fn is_japanese_char(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3005}'..='\u{3007}'
+            | '\u{3040}'..='\u{30FF}'
+            | '\u{3400}'..='\u{4DBF}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FF66}'..='\u{FF9F}'
+            | '\u{20000}'..='\u{3FFFF}'
+    )
+}
+
+/// CJK ideographs, including the extensions outside the BMP (𠮟, 𩸽) and the compatibility
+/// block.
+fn is_ideograph(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3400}'..='\u{4DBF}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{20000}'..='\u{3FFFF}'
+    )
+}
+
+fn is_variation_selector(c: char) -> bool {
+    matches!(c, '\u{FE00}'..='\u{FE0F}' | '\u{E0100}'..='\u{E01EF}')
+}
+
+/// How `c` is stored: compatibility ideographs become the unified ideograph they stand for
+/// (U+F900 豈 -> U+8C48 豈) and variation selectors are dropped (`None`), so a glyph variant
+/// finds the same entries as the plain character.
+fn stored_form(c: char) -> Option<char> {
+    if is_variation_selector(c) {
+        return None;
+    }
+    if !matches!(c, '\u{F900}'..='\u{FAFF}' | '\u{2F800}'..='\u{2FA1F}') {
+        return Some(c);
+    }
+    let mut buf = [0; 4];
+    let nfc = ComposingNormalizerBorrowed::new_nfc().normalize(c.encode_utf8(&mut buf));
+    // Compatibility ideographs decompose to exactly one character
+    Some(nfc.chars().next().unwrap_or(c))
+}
+
+/// `text` with its ideographs in stored form; `None` when it already is.
+pub fn normalize_ideographs(text: &str) -> Option<String> {
+    let normalized: String = text.chars().filter_map(stored_form).collect();
+    (normalized != text).then_some(normalized)
+}
+
+/// The window in stored form, and for each length of a prefix of it, how many characters of
+/// `written` that prefix covers. A variation selector goes with the character before it.
+fn normalize_window(written: &[char]) -> (Vec<char>, Vec<usize>) {
+    let mut chars = Vec::with_capacity(written.len());
+    let mut ends = Vec::with_capacity(written.len() + 1);
+    ends.push(0);
+    for (i, &c) in written.iter().enumerate() {
+        match stored_form(c) {
+            Some(c) => {
+                chars.push(c);
+                ends.push(i + 1);
+            }
+            None if !chars.is_empty() => {
+                if let Some(end) = ends.last_mut() {
+                    *end = i + 1;
+                }
+            }
+            None => {}
+        }
+    }
+    (chars, ends)
+}
+
+fn voiced_kana(c: char) -> char {
+    UNVOICED_KANA
+        .chars()
+        .position(|x| x == c)
+        .and_then(|i| VOICED_KANA.chars().nth(i))
+        .unwrap_or(c)
+}
+
+fn unvoiced_kana(c: char) -> char {
+    VOICED_KANA
+        .chars()
+        .position(|x| x == c)
+        .and_then(|i| UNVOICED_KANA.chars().nth(i))
+        .unwrap_or(c)
+}
+
+const HIRAGANA_ROWS: [&str; 5] = [
+    "あかがさざただなはばぱまやらわ",
+    "いきぎしじちぢにひびぴみ り ",
+    "うくぐすずつづぬふぶぷむゆるう",
+    "えけげせぜてでねへべぺめ れ ",
+    "おこごそぞとどのほぼぽもよろを",
+];
+
+/// Moves a hiragana to the same column of another vowel row (き -> く for row 2).
+fn shift_row(c: char, row: usize) -> Option<char> {
+    let column = HIRAGANA_ROWS
+        .iter()
+        .find_map(|r| r.chars().position(|x| x == c))?;
+    HIRAGANA_ROWS[row]
+        .chars()
+        .nth(column)
+        .filter(|shifted| *shifted != ' ')
+}
+
+/// Stand-in for Lindera's lemma when it can't tokenize `text`: the leading kanji with the
+/// hiragana after them as one word, turned back into possible dictionary forms by the common
+/// verb and adjective patterns (食べました -> 食べる, 書いて -> 書く, 高かった -> 高い).
+/// Returns the word's length in characters with the guesses; the wrong ones just don't match.
+fn fallback_lemmas(text: &str) -> (usize, Vec<String>) {
+    let chars: Vec<char> = text.chars().collect();
+    let stem_len = chars.iter().take_while(|c| is_ideograph(**c)).count();
+    let okurigana_len = chars[stem_len..]
+        .iter()
+        .take_while(|c| ('\u{3041}'..='\u{3096}').contains(*c))
+        .count();
+    let Some(&first) = chars
+        .get(stem_len)
+        .filter(|_| stem_len > 0 && okurigana_len > 0)
+    else {
+        return (0, Vec::new());
+    };
+    let stem: String = chars[..stem_len].iter().collect();
+    let with = |ending: &str| format!("{stem}{ending}");
+
+    let mut lemmas = Vec::new();
+    match first {
+        // Onbin of godan verbs: 書いて, 泳いだ, 読んで, 行って
+        'い' => lemmas.extend([with("く"), with("ぐ"), with("いる"), with("う")]),
+        'ん' => lemmas.extend([with("む"), with("ぶ"), with("ぬ")]),
+        'っ' => lemmas.extend([with("う"), with("つ"), with("る")]),
+        // Adjectives: 高かった, 高くない, 高ければ, 高さ
+        'か' | 'く' | 'け' | 'さ' => lemmas.push(with("い")),
+        _ => {}
+    }
+    // Godan stems (書きます, 書かない, 書けば) and ichidan ones (食べる, 起きる)
+    if let Some(dictionary) = shift_row(first, 2) {
+        lemmas.push(format!("{stem}{dictionary}"));
+    }
+    if shift_row(first, 1) == Some(first) || shift_row(first, 3) == Some(first) {
+        lemmas.push(format!("{stem}{first}る"));
+    }
+    let word: String = chars[..stem_len + okurigana_len].iter().collect();
+    lemmas.retain(|lemma| *lemma != word);
+    lemmas.sort();
+    lemmas.dedup();
+    (stem_len + okurigana_len, lemmas)
+}
+
+/// Replaces iteration marks with the character they repeat: 時々 -> 時時, くゞる -> くぐる.
+/// Returns `None` when the text has no expandable marks.
+fn expand_iteration_marks(text: &str) -> Option<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut prev: Option<char> = None;
+    let mut changed = false;
+
+    for c in text.chars() {
+        let expanded = match (c, prev) {
+            ('々', Some(p)) => Some(p),
+            ('ゝ' | 'ヽ', Some(p)) => Some(unvoiced_kana(p)),
+            ('ゞ' | 'ヾ', Some(p)) => Some(voiced_kana(p)),
+            _ => None,
+        };
+
+        let out = expanded.unwrap_or(c);
+        changed |= expanded.is_some();
+        result.push(out);
+        prev = Some(out);
+    }
+
+    changed.then_some(result)
+}
+
+/// Replaces a repeated kanji with 々, the form dictionaries use: 人人 -> 人々.
+/// Returns `None` when nothing repeats.
+fn compress_iteration_marks(text: &str) -> Option<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut prev: Option<char> = None;
+    let mut changed = false;
+
+    for c in text.chars() {
+        let is_kanji = ('\u{4E00}'..='\u{9FFF}').contains(&c);
+        if is_kanji && prev == Some(c) {
+            result.push('々');
+            changed = true;
+        } else {
+            result.push(c);
+        }
+        prev = Some(c);
+    }
+
+    changed.then_some(result)
+}
+
+/// Byte offsets a [`ScanMode::Around`] lookup scans from, nearest first: the character at
+/// `cursor` (snapped back to a character boundary, or the last character when `cursor` is at or
+/// past the end) and up to `back` characters before it. Also returns that first offset, which
+/// matches have to reach past. `None` for empty text.
+fn scan_starts(text: &str, cursor: usize, back: usize) -> Option<(usize, Vec<usize>)> {
+    let mut index = cursor.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    let anchor = match index < text.len() {
+        true => index,
+        false => text.char_indices().next_back()?.0,
+    };
+    let before = text[..anchor].char_indices().rev().take(back);
+    let starts = std::iter::once(anchor)
+        .chain(before.map(|(i, _)| i))
+        .collect();
+    Some((anchor, starts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_kanji_iteration_mark() {
+        assert_eq!(expand_iteration_marks("時々").as_deref(), Some("時時"));
+        assert_eq!(expand_iteration_marks("人々の").as_deref(), Some("人人の"));
+    }
+
+    #[test]
+    fn expands_kana_iteration_marks() {
+        assert_eq!(expand_iteration_marks("こゝろ").as_deref(), Some("こころ"));
+        assert_eq!(expand_iteration_marks("くゞる").as_deref(), Some("くぐる"));
+        assert_eq!(expand_iteration_marks("いすゞ").as_deref(), Some("いすず"));
+        assert_eq!(expand_iteration_marks("ぶゝ").as_deref(), Some("ぶふ"));
+        assert_eq!(expand_iteration_marks("バヽ").as_deref(), Some("バハ"));
+        assert_eq!(expand_iteration_marks("ハヾ").as_deref(), Some("ハバ"));
+    }
+
+    #[test]
+    fn leaves_text_without_marks_alone() {
+        assert_eq!(expand_iteration_marks("時間"), None);
+        // A leading mark has nothing to repeat
+        assert_eq!(expand_iteration_marks("々"), None);
+    }
+
+    #[test]
+    fn recognizes_japanese_script() {
+        for text in ["ひらがな", "カタカナ", "漢字", "々", "ｶﾀｶﾅ", "ー"] {
+            assert!(text.chars().all(is_japanese_char), "{text}");
+        }
+        let garbage = "iVBORw0KGgoAAAANSUhEUgAA+/= Hello, world! 한국어 ①";
+        assert!(!garbage.chars().any(is_japanese_char));
+    }
+
+    #[test]
+    fn language_specific_candidates_only_reach_their_dictionaries() {
+        // Unset and Japanese dictionaries keep every rewrite
+        assert!(accepts_language(None, Some("ja")));
+        assert!(accepts_language(Some("ja"), Some("en")));
+        // Others only take surface forms and their own rules
+        assert!(accepts_language(Some("zh"), None));
+        assert!(!accepts_language(Some("zh"), Some("ja")));
+        assert!(accepts_language(Some("ko"), Some("ko")));
+        assert!(!accepts_language(Some("ko"), Some("en")));
+    }
+
+    #[test]
+    fn normalizes_variant_ideographs() {
+        assert_eq!(
+            normalize_ideographs("\u{845B}\u{E0100}城").as_deref(),
+            Some("葛城")
+        );
+        assert_eq!(
+            normalize_ideographs("\u{F900}").as_deref(),
+            Some("\u{8C48}")
+        );
+        assert_eq!(
+            normalize_ideographs("\u{2F800}").as_deref(),
+            Some("\u{4E3D}")
+        );
+        assert_eq!(normalize_ideographs("𠮟る"), None);
+        assert_eq!(normalize_ideographs("食べる"), None);
+    }
+
+    #[test]
+    fn maps_normalized_prefixes_back_to_written_characters() {
+        let written: Vec<char> = "\u{FE00}葛\u{E0100}城\u{F900}".chars().collect();
+        let (chars, ends) = normalize_window(&written);
+        assert_eq!(chars, ['葛', '城', '\u{8C48}']);
+        // The leading selector has nothing to attach to and goes with 葛, the one after it too
+        assert_eq!(ends, [0, 3, 4, 5]);
+    }
+
+    #[test]
+    fn recognizes_ideographs_outside_the_bmp() {
+        assert!(is_japanese_char('𠮟'));
+        assert!(is_ideograph('𩸽'));
+    }
+
+    #[test]
+    fn candidates_stop_at_the_depth_limit() {
+        let words = |depth| -> Vec<String> {
+            LookupService::default()
+                .with_max_deinflection_depth(depth)
+                .generate_candidates("Running", &Script::Latin, &|_| true)
+                .into_iter()
+                .map(|c| c.word)
+                .collect()
+        };
+        assert_eq!(words(0), ["Running", "running"]);
+        let shallow = words(1);
+        assert!(shallow.contains(&"runn".to_string()), "{shallow:?}");
+        assert!(!shallow.contains(&"run".to_string()), "{shallow:?}");
+        assert!(words(2).contains(&"run".to_string()));
+        // Korean rules are all one step deep
+        let korean = LookupService::default()
+            .with_max_deinflection_depth(0)
+            .generate_candidates("했어요", &Script::Korean, &|_| true);
+        assert_eq!(korean.len(), 1);
+    }
+
+    #[test]
+    fn guesses_dictionary_forms_without_lindera() {
+        let guesses = |text: &str| fallback_lemmas(text).1;
+        assert!(guesses("食べました").contains(&"食べる".to_string()));
+        assert!(guesses("書きます").contains(&"書く".to_string()));
+        assert!(guesses("書いて").contains(&"書く".to_string()));
+        assert!(guesses("読んだ").contains(&"読む".to_string()));
+        assert!(guesses("高かった").contains(&"高い".to_string()));
+        assert_eq!(fallback_lemmas("食べましたか。").0, 6);
+        // Nothing to go on without both kanji and okurigana
+        assert_eq!(fallback_lemmas("たべる"), (0, Vec::new()));
+        assert_eq!(fallback_lemmas("漢字"), (0, Vec::new()));
+    }
+
+    #[test]
+    fn compresses_repeated_kanji() {
+        assert_eq!(compress_iteration_marks("人人").as_deref(), Some("人々"));
+        assert_eq!(compress_iteration_marks("時時").as_deref(), Some("時々"));
+        assert_eq!(compress_iteration_marks("時間"), None);
+        // Only kanji are compressed; repeated kana stay as written
+        assert_eq!(compress_iteration_marks("ここ"), None);
+    }
+
+    #[test]
+    fn around_scans_start_at_and_before_the_cursor() {
+        // 食 べ 物 を, three bytes each
+        let text = "食べ物を";
+        assert_eq!(scan_starts(text, 6, 10), Some((6, vec![6, 3, 0])));
+        assert_eq!(scan_starts(text, 9, 1), Some((9, vec![9, 6])));
+        // Nothing before the first character
+        assert_eq!(scan_starts(text, 0, 10), Some((0, vec![0])));
+    }
+
+    #[test]
+    fn around_scans_handle_awkward_cursors() {
+        let text = "食べ物";
+        // Inside べ counts as on it
+        assert_eq!(scan_starts(text, 4, 10), Some((3, vec![3, 0])));
+        // At or past the end is the end of a selection of the last character
+        assert_eq!(scan_starts(text, 9, 10), Some((6, vec![6, 3, 0])));
+        assert_eq!(scan_starts(text, 100, 10), Some((6, vec![6, 3, 0])));
+        assert_eq!(scan_starts("", 0, 10), None);
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+use wordbase_api::{DictionaryId, RecordEntry};
+
+pub const DEFAULT_LOOKUP_CACHE_SIZE: usize = 256;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    window: String,
+    // Sorted, so the same selection in any order shares an entry
+    subset: Option<Vec<i64>>,
+    include_disabled: bool,
+}
+
+/// Small LRU of recent lookup results, keyed on the scanned window of text (and the request's
+/// dictionary selection). Hovering back and forth over one bubble repeats the same windows, which
+/// then skip the candidate queries entirely.
+///
+/// Entries belong to a dictionaries generation (see `AppState::dictionaries_changed`); the first
+/// access under a newer generation drops them all.
+pub struct LookupCache {
+    capacity: usize,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    generation: u64,
+    tick: u64,
+    entries: HashMap<CacheKey, (u64, Vec<RecordEntry>)>,
+}
+
+impl Inner {
+    /// Drops everything cached under an older generation. Returns false when `generation` is
+    /// itself outdated, i.e. the caller raced a dictionary change.
+    fn sync_generation(&mut self, generation: u64) -> bool {
+        if generation > self.generation {
+            self.generation = generation;
+            self.entries.clear();
+        }
+        generation == self.generation
+    }
+}
+
+impl LookupCache {
+    /// `capacity` 0 disables caching.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Size from `MANGATAN_YOMITAN_LOOKUP_CACHE` (entries); `0` disables the cache.
+    pub fn from_env() -> Self {
+        let capacity = std::env::var("MANGATAN_YOMITAN_LOOKUP_CACHE")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(DEFAULT_LOOKUP_CACHE_SIZE);
+        Self::new(capacity)
+    }
+
+    pub fn get(
+        &self,
+        generation: u64,
+        window: &str,
+        subset: Option<&HashSet<DictionaryId>>,
+        include_disabled: bool,
+    ) -> Option<Vec<RecordEntry>> {
+        if self.capacity == 0 {
+            return None;
+        }
+        let mut inner = self.inner.lock().expect("lock");
+        if !inner.sync_generation(generation) {
+            return None;
+        }
+        inner.tick += 1;
+        let tick = inner.tick;
+        let (used, results) =
+            inner
+                .entries
+                .get_mut(&cache_key(window, subset, include_disabled))?;
+        *used = tick;
+        Some(results.clone())
+    }
+
+    pub fn insert(
+        &self,
+        generation: u64,
+        window: &str,
+        subset: Option<&HashSet<DictionaryId>>,
+        include_disabled: bool,
+        results: &[RecordEntry],
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().expect("lock");
+        if !inner.sync_generation(generation) {
+            return;
+        }
+        let key = cache_key(window, subset, include_disabled);
+        if inner.entries.len() >= self.capacity && !inner.entries.contains_key(&key) {
+            let oldest = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, (used, _))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                inner.entries.remove(&oldest);
+            }
+        }
+        inner.tick += 1;
+        let tick = inner.tick;
+        inner.entries.insert(key, (tick, results.to_vec()));
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().expect("lock").entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn cache_key(
+    window: &str,
+    subset: Option<&HashSet<DictionaryId>>,
+    include_disabled: bool,
+) -> CacheKey {
+    CacheKey {
+        window: window.to_string(),
+        subset: subset.map(|ids| {
+            let mut ids: Vec<i64> = ids.iter().map(|id| id.0).collect();
+            ids.sort_unstable();
+            ids
+        }),
+        include_disabled,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_the_least_recently_used_window() {
+        let cache = LookupCache::new(2);
+        cache.insert(0, "一", None, false, &[]);
+        cache.insert(0, "二", None, false, &[]);
+        // Touching 一 leaves 二 as the oldest
+        assert!(cache.get(0, "一", None, false).is_some());
+        cache.insert(0, "三", None, false, &[]);
+
+        assert!(cache.get(0, "一", None, false).is_some());
+        assert!(cache.get(0, "二", None, false).is_none());
+        assert!(cache.get(0, "三", None, false).is_some());
+    }
+
+    #[test]
+    fn keys_include_the_subset_and_generation() {
+        let cache = LookupCache::new(8);
+        let subset = HashSet::from([DictionaryId(2), DictionaryId(1)]);
+        cache.insert(0, "一", Some(&subset), false, &[]);
+
+        assert!(cache.get(0, "一", None, false).is_none());
+        let reordered = HashSet::from([DictionaryId(1), DictionaryId(2)]);
+        assert!(cache.get(0, "一", Some(&reordered), false).is_some());
+        // Searching disabled dictionaries too gives different results
+        assert!(cache.get(0, "一", Some(&subset), true).is_none());
+
+        // A dictionary change empties the cache, and late results from before it are dropped
+        assert!(cache.get(1, "一", Some(&subset), false).is_none());
+        cache.insert(0, "一", None, false, &[]);
+        assert!(cache.is_empty());
+    }
+}
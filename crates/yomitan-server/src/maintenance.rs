@@ -0,0 +1,272 @@
+use rusqlite::{
+    Connection, OptionalExtension,
+    backup::{Backup, StepResult},
+};
+use serde::Serialize;
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+use crate::state::AppState;
+
+/// Free-page fraction at which idle databases are compacted automatically.
+pub const DEFAULT_COMPACT_THRESHOLD: f64 = 0.25;
+
+const COMPACT_CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// How long the server must go without a request before an automatic compaction.
+const COMPACT_IDLE_AFTER: Duration = Duration::from_secs(5 * 60);
+/// Don't bother rewriting the file to win back less than this.
+const COMPACT_MIN_FREE_BYTES: u64 = 16 * 1024 * 1024;
+
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct DbStats {
+    pub file_bytes: u64,
+    pub page_size: u64,
+    pub page_count: u64,
+    /// Pages left empty by deletes; SQLite reuses them but never gives them back to the OS.
+    pub freelist_count: u64,
+}
+
+impl DbStats {
+    pub fn free_bytes(&self) -> u64 {
+        self.freelist_count * self.page_size
+    }
+
+    pub fn free_fraction(&self) -> f64 {
+        match self.page_count {
+            0 => 0.0,
+            pages => self.freelist_count as f64 / pages as f64,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct CompactReport {
+    pub before_bytes: u64,
+    pub after_bytes: u64,
+    pub duration_ms: u64,
+}
+
+pub fn db_stats(state: &AppState) -> Result<DbStats, String> {
+    let conn = state.pool.get().map_err(|e| e.to_string())?;
+    let pragma = |name: &str| -> Result<u64, String> {
+        conn.query_row(&format!("PRAGMA {name}"), [], |row| row.get::<_, i64>(0))
+            .map(|value| value as u64)
+            .map_err(|e| e.to_string())
+    };
+    Ok(DbStats {
+        file_bytes: fs::metadata(state.db_path()).map_or(0, |m| m.len()),
+        page_size: pragma("page_size")?,
+        page_count: pragma("page_count")?,
+        freelist_count: pragma("freelist_count")?,
+    })
+}
+
+/// What the dictionaries take up on disk, for a settings screen that offers to purge them.
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct StorageStats {
+    /// Size of `yomitan.db`, free pages included.
+    pub db_bytes: u64,
+    /// What a compaction would give back.
+    pub free_bytes: u64,
+    /// Rows in `terms`; an entry with a reading has one for each.
+    pub term_count: u64,
+    pub dictionary_count: usize,
+    /// See [`build_prefix_index`]; `None` until it's built.
+    pub prefix_index: Option<PrefixIndex>,
+}
+
+/// Counts every term row, which takes a moment on a large database.
+pub fn storage_stats(state: &AppState) -> Result<StorageStats, String> {
+    let db = db_stats(state)?;
+    let conn = state.pool.get().map_err(|e| e.to_string())?;
+    let term_count = conn
+        .query_row("SELECT count(*) FROM terms", [], |row| row.get::<_, i64>(0))
+        .map_err(|e| e.to_string())?;
+    Ok(StorageStats {
+        db_bytes: db.file_bytes,
+        free_bytes: db.free_bytes(),
+        term_count: term_count as u64,
+        dictionary_count: state.dictionaries.read().expect("lock").len(),
+        prefix_index: prefix_index(state)?,
+    })
+}
+
+/// Writes a compacted copy of the database with `VACUUM INTO`, then copies it back over the live
+/// database with SQLite's backup API. Imports and dictionary edits wait on the import lock for
+/// the whole run; lookups keep reading during the copy and only pause for the final swap.
+///
+/// The swap goes through SQLite rather than a file rename so pooled connections (and Windows,
+/// which can't replace an open file) see the compacted pages.
+pub fn compact(state: &AppState) -> Result<CompactReport, String> {
+    let _guard = state.lock_writes("compacting database");
+    let started = Instant::now();
+    let db_path = state.db_path();
+    let before_bytes = fs::metadata(&db_path).map_or(0, |m| m.len());
+
+    let tmp_path = db_path.with_extension("db.compact");
+    let _ = fs::remove_file(&tmp_path);
+
+    let result = (|| {
+        let mut conn = state.pool.get().map_err(|e| e.to_string())?;
+        conn.execute("VACUUM INTO ?", [tmp_path.to_string_lossy()])
+            .map_err(|e| format!("VACUUM INTO failed: {e}"))?;
+
+        let compacted = Connection::open(&tmp_path).map_err(|e| e.to_string())?;
+        let backup = Backup::new(&compacted, &mut conn).map_err(|e| e.to_string())?;
+        // One step copies everything inside a single write transaction
+        for _ in 0..20 {
+            match backup.step(-1).map_err(|e| e.to_string())? {
+                StepResult::Done => return Ok(()),
+                _ => std::thread::sleep(Duration::from_millis(250)),
+            }
+        }
+        Err("Database stayed busy; compaction skipped".to_string())
+    })();
+    let _ = fs::remove_file(&tmp_path);
+    result?;
+
+    let report = CompactReport {
+        before_bytes,
+        after_bytes: fs::metadata(&db_path).map_or(0, |m| m.len()),
+        duration_ms: started.elapsed().as_millis() as u64,
+    };
+    info!(
+        "🗜️ [Yomitan] Compacted database: {} -> {} bytes in {}ms",
+        report.before_bytes, report.after_bytes, report.duration_ms
+    );
+    Ok(report)
+}
+
+const PREFIX_INDEX: &str = "idx_term_nocase";
+
+/// The opt-in index that lets `term LIKE 'x%'` and case-insensitive matches seek instead of
+/// scanning `terms`: SQLite only uses an index for `LIKE` when it's `COLLATE NOCASE`.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrefixIndex {
+    /// Only terms up to this many characters are indexed, which keeps sentence-length entries
+    /// out of it; queries must include `length(term) <= N` to use it. `None` indexes them all.
+    pub max_term_length: Option<usize>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct PrefixIndexReport {
+    pub index: PrefixIndex,
+    pub duration_ms: u64,
+}
+
+/// The prefix index, if it has been built.
+pub fn prefix_index(state: &AppState) -> Result<Option<PrefixIndex>, String> {
+    let conn = state.pool.get().map_err(|e| e.to_string())?;
+    let sql: Option<String> = conn
+        .query_row(
+            "SELECT sql FROM sqlite_master WHERE type = 'index' AND name = ?",
+            [PREFIX_INDEX],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    // The limit lives in the index's own definition, so it can't drift from what's indexed
+    Ok(sql.map(|sql| PrefixIndex {
+        max_term_length: sql
+            .rsplit_once("length(term) <= ")
+            .and_then(|(_, max)| max.trim().parse().ok()),
+    }))
+}
+
+/// (Re)builds the prefix index. This reads the whole `terms` table, so it can take minutes on a
+/// large database; imports and dictionary edits wait for it. Later imports keep it up to date,
+/// at some cost to their speed.
+pub fn build_prefix_index(
+    state: &AppState,
+    max_term_length: Option<usize>,
+) -> Result<PrefixIndexReport, String> {
+    let _guard = state.lock_writes("building prefix index");
+    let started = Instant::now();
+    let partial = max_term_length
+        .map(|max| format!(" WHERE length(term) <= {max}"))
+        .unwrap_or_default();
+    let conn = state.pool.get().map_err(|e| e.to_string())?;
+    conn.execute_batch(&format!(
+        "BEGIN;
+         DROP INDEX IF EXISTS {PREFIX_INDEX};
+         CREATE INDEX {PREFIX_INDEX} ON terms(term COLLATE NOCASE){partial};
+         COMMIT;"
+    ))
+    .map_err(|e| {
+        let _ = conn.execute_batch("ROLLBACK");
+        format!("Failed to build the prefix index: {e}")
+    })?;
+
+    let report = PrefixIndexReport {
+        index: PrefixIndex { max_term_length },
+        duration_ms: started.elapsed().as_millis() as u64,
+    };
+    info!(
+        "🔎 [Yomitan] Built the prefix index (max term length: {}) in {}ms",
+        max_term_length.map_or("none".to_string(), |max| max.to_string()),
+        report.duration_ms
+    );
+    Ok(report)
+}
+
+/// Drops the prefix index; its pages become free space until the next compaction. Returns
+/// whether there was one.
+pub fn drop_prefix_index(state: &AppState) -> Result<bool, String> {
+    let _guard = state.lock_writes("dropping prefix index");
+    let existed = prefix_index(state)?.is_some();
+    let conn = state.pool.get().map_err(|e| e.to_string())?;
+    conn.execute_batch(&format!("DROP INDEX IF EXISTS {PREFIX_INDEX}"))
+        .map_err(|e| e.to_string())?;
+    if existed {
+        info!("🔎 [Yomitan] Dropped the prefix index");
+    }
+    Ok(existed)
+}
+
+/// Threshold from `MANGATAN_YOMITAN_COMPACT_THRESHOLD` (free-page fraction); `0` disables
+/// automatic compaction.
+pub fn compact_threshold_from_env() -> f64 {
+    std::env::var("MANGATAN_YOMITAN_COMPACT_THRESHOLD")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_COMPACT_THRESHOLD)
+}
+
+/// Periodically compacts the database once enough of it is free pages and nobody is using it.
+pub async fn auto_compact(state: AppState, threshold: f64) {
+    if threshold <= 0.0 || state.read_only {
+        return;
+    }
+
+    loop {
+        tokio::time::sleep(COMPACT_CHECK_INTERVAL).await;
+        if state.is_loading() || state.idle_for() < COMPACT_IDLE_AFTER {
+            continue;
+        }
+
+        let check_state = state.clone();
+        let stats = match tokio::task::spawn_blocking(move || db_stats(&check_state)).await {
+            Ok(Ok(stats)) => stats,
+            Ok(Err(e)) => {
+                warn!("⚠️ [Yomitan] Could not read database stats: {e}");
+                continue;
+            }
+            Err(_) => continue,
+        };
+        if stats.free_fraction() < threshold || stats.free_bytes() < COMPACT_MIN_FREE_BYTES {
+            continue;
+        }
+
+        info!(
+            "🗜️ [Yomitan] {:.0}% of the database is free pages, compacting while idle...",
+            stats.free_fraction() * 100.0
+        );
+        let compact_state = state.clone();
+        if let Ok(Err(e)) = tokio::task::spawn_blocking(move || compact(&compact_state)).await {
+            warn!("⚠️ [Yomitan] Automatic compaction failed: {e}");
+        }
+    }
+}
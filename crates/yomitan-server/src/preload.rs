@@ -0,0 +1,215 @@
+use crate::{
+    frequency::frequency_rank,
+    state::{DbPool, StoredRecord},
+};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    },
+    time::Instant,
+};
+use tracing::{info, warn};
+use wordbase_api::Record;
+
+pub const DEFAULT_PRELOAD_TERMS: usize = 20_000;
+
+/// Longest term (in characters) preloaded when no dictionary carries popularity scores.
+const SHORT_TERM_CHARS: usize = 2;
+
+/// The `(dictionary_id, json, rowid)` rows of one term, exactly as stored in `terms`.
+pub type PreloadedRows = Vec<(i64, Vec<u8>, i64)>;
+
+/// Reads `MANGATAN_YOMITAN_PRELOAD_TERMS`, defaulting to [`DEFAULT_PRELOAD_TERMS`].
+pub fn limit_from_env() -> usize {
+    std::env::var("MANGATAN_YOMITAN_PRELOAD_TERMS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_PRELOAD_TERMS)
+}
+
+/// In-memory copy of the most common terms' rows, so hot lookups (particles, single kana) skip
+/// SQLite. A term is either fully present with all its rows or absent, in which case lookups
+/// query the database as usual.
+pub struct TermPreload {
+    limit: usize,
+    terms: RwLock<Arc<HashMap<String, PreloadedRows>>>,
+    bytes: AtomicUsize,
+    building: AtomicBool,
+    // Bumped on every refresh so a slow build can't overwrite the result of a newer one
+    generation: AtomicU64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PreloadStats {
+    pub limit: usize,
+    pub terms: usize,
+    /// Approximate heap usage of the map.
+    pub bytes: usize,
+    pub building: bool,
+}
+
+impl TermPreload {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            terms: RwLock::new(Arc::new(HashMap::new())),
+            bytes: AtomicUsize::new(0),
+            building: AtomicBool::new(false),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Size from `MANGATAN_YOMITAN_PRELOAD_TERMS`; `0` disables preloading.
+    pub fn from_env() -> Self {
+        Self::new(limit_from_env())
+    }
+
+    /// The current map; cheap to take once per lookup.
+    pub fn snapshot(&self) -> Arc<HashMap<String, PreloadedRows>> {
+        self.terms.read().expect("lock").clone()
+    }
+
+    pub fn stats(&self) -> PreloadStats {
+        PreloadStats {
+            limit: self.limit,
+            terms: self.terms.read().expect("lock").len(),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            building: self.building.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Empties the map and discards any build still running.
+    pub fn clear(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.building.store(false, Ordering::Relaxed);
+        *self.terms.write().expect("lock") = Arc::new(HashMap::new());
+        self.bytes.store(0, Ordering::Relaxed);
+    }
+
+    /// Drops the current map right away (it may hold rows of a deleted dictionary, or miss
+    /// rows of a new one) and rebuilds it on a background thread.
+    pub fn refresh(self: &Arc<Self>, pool: DbPool) {
+        self.clear();
+        if self.limit == 0 {
+            return;
+        }
+
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        self.building.store(true, Ordering::Relaxed);
+        let preload = self.clone();
+
+        std::thread::spawn(move || {
+            let start = Instant::now();
+            let result = build(&pool, preload.limit);
+
+            if preload.generation.load(Ordering::SeqCst) != generation {
+                return;
+            }
+            match result {
+                Ok(terms) => {
+                    let bytes = estimate_bytes(&terms);
+                    info!(
+                        "⚡ [Preload] Cached {} terms ({} KiB) in {:?}",
+                        terms.len(),
+                        bytes / 1024,
+                        start.elapsed()
+                    );
+                    *preload.terms.write().expect("lock") = Arc::new(terms);
+                    preload.bytes.store(bytes, Ordering::Relaxed);
+                }
+                Err(e) => warn!("⚠️ [Preload] Failed to build term cache: {e}"),
+            }
+            preload.building.store(false, Ordering::Relaxed);
+        });
+    }
+}
+
+fn build(pool: &DbPool, limit: usize) -> anyhow::Result<HashMap<String, PreloadedRows>> {
+    let conn = pool.get()?;
+
+    let mut terms = most_popular_terms(&conn, limit)?;
+    if terms.is_empty() {
+        terms = short_terms(&conn, limit)?;
+    }
+
+    let mut stmt = conn.prepare("SELECT dictionary_id, json, rowid FROM terms WHERE term = ?")?;
+    let mut map = HashMap::with_capacity(terms.len());
+    for term in terms {
+        let rows = stmt
+            .query_map([&term], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<Result<PreloadedRows, _>>()?;
+        map.insert(term, rows);
+    }
+    Ok(map)
+}
+
+/// Terms ranked by their best glossary popularity score. Empty when no dictionary sets one.
+fn most_popular_terms(conn: &rusqlite::Connection, limit: usize) -> anyhow::Result<Vec<String>> {
+    let mut best: HashMap<String, i64> = HashMap::new();
+    let mut decoder = snap::raw::Decoder::new();
+
+    let mut stmt = conn.prepare("SELECT term, json FROM terms")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let compressed: Vec<u8> = row.get(1)?;
+        let Some(stored) = decoder
+            .decompress_vec(&compressed)
+            .ok()
+            .and_then(|json| serde_json::from_slice::<StoredRecord>(&json).ok())
+        else {
+            continue;
+        };
+        let Record::YomitanGlossary(gloss) = &stored.record else {
+            continue;
+        };
+        // Frequency list ranks count the other way
+        if frequency_rank(&stored.record).is_some() {
+            continue;
+        }
+        if gloss.popularity > 0 {
+            let score = best.entry(row.get(0)?).or_insert(0);
+            *score = (*score).max(gloss.popularity);
+        }
+    }
+
+    let mut ranked: Vec<(String, i64)> = best.into_iter().collect();
+    ranked.sort_by(|(a_term, a), (b_term, b)| b.cmp(a).then_with(|| a_term.cmp(b_term)));
+    Ok(ranked
+        .into_iter()
+        .take(limit)
+        .map(|(term, _)| term)
+        .collect())
+}
+
+/// Fallback without popularity data: the shortest terms, which are mostly particles and kana.
+fn short_terms(conn: &rusqlite::Connection, limit: usize) -> anyhow::Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT term FROM terms WHERE length(term) <= ? ORDER BY length(term), term LIMIT ?",
+    )?;
+    let terms = stmt
+        .query_map(
+            rusqlite::params![SHORT_TERM_CHARS as i64, limit as i64],
+            |row| row.get(0),
+        )?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(terms)
+}
+
+fn estimate_bytes(terms: &HashMap<String, PreloadedRows>) -> usize {
+    let entry_overhead = size_of::<String>() + size_of::<PreloadedRows>() + size_of::<u64>();
+    let row_overhead = size_of::<(i64, Vec<u8>, i64)>();
+    terms
+        .iter()
+        .map(|(term, rows)| {
+            entry_overhead
+                + term.len()
+                + rows
+                    .iter()
+                    .map(|(_, json, _)| row_overhead + json.len())
+                    .sum::<usize>()
+        })
+        .sum()
+}
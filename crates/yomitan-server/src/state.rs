@@ -1,25 +1,220 @@
+use mangatan_core::data_dir::{self, ReadOnlyDataDir};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::ErrorCode;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
-    path::PathBuf,
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
     sync::{
-        Arc, RwLock,
-        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
     },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
-use tracing::info;
+use tracing::{error, info, warn};
 use wordbase_api::{DictionaryId, Record};
 
+use crate::{
+    content_limits::ContentLimits,
+    import,
+    preload::{self, DEFAULT_PRELOAD_TERMS, TermPreload},
+    watchdog::{self, DEFAULT_STALL_AFTER, Heartbeat, WriteGuard, WriterHealth},
+};
+
 pub type DbPool = Pool<SqliteConnectionManager>;
 
+const DB_FILE: &str = "yomitan.db";
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct DictionaryData {
     pub id: DictionaryId,
     pub name: String,
     pub priority: i64,
     pub enabled: bool,
+    /// Primary language subtag of the headwords (`ja`, `zh`, ...), from `sourceLanguage` in
+    /// index.json or set by the user. `None` is treated like Japanese.
+    #[serde(default)]
+    pub language: Option<String>,
+    /// `revision` from index.json, so a config export can be matched to a re-imported copy.
+    #[serde(default)]
+    pub revision: Option<String>,
+    #[serde(flatten)]
+    pub info: DictionaryInfo,
+}
+
+/// What index.json and the import say about a dictionary, for an about page. Several dictionary
+/// licenses require the attribution to be shown. Empty for dictionaries imported before it was
+/// kept.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct DictionaryInfo {
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub attribution: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Unix time of the import.
+    #[serde(default)]
+    pub imported_at: Option<i64>,
+    /// Entries of the term, kanji and meta (frequency, pitch, ...) banks.
+    #[serde(default)]
+    pub term_count: Option<i64>,
+    #[serde(default)]
+    pub kanji_count: Option<i64>,
+    #[serde(default)]
+    pub meta_count: Option<i64>,
+}
+
+impl DictionaryInfo {
+    /// Reads the descriptive fields of index.json and stamps the current time.
+    pub fn from_index(index: &serde_json::Value) -> Self {
+        let field = |name: &str| {
+            index[name]
+                .as_str()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        Self {
+            description: field("description"),
+            author: field("author"),
+            attribution: field("attribution"),
+            url: field("url"),
+            ..Self::imported_now()
+        }
+    }
+
+    /// Nothing but the import time.
+    pub fn imported_now() -> Self {
+        Self {
+            imported_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|since| since.as_secs() as i64),
+            ..Self::default()
+        }
+    }
+}
+
+/// Reduces a language tag to its lowercase primary subtag (`zh-Hans` -> `zh`); `None` if empty.
+pub fn normalize_language(tag: &str) -> Option<String> {
+    let primary = tag.trim().split(['-', '_']).next().unwrap_or_default();
+    (!primary.is_empty()).then(|| primary.to_ascii_lowercase())
+}
+
+/// Query for the `(dictionary_id, json, rowid)` rows of one term, restricted to the `subset` dictionaries when a request names
+/// them. Filtering in SQL keeps a narrow lookup from decompressing rows it would throw away.
+pub fn terms_by_term_sql(subset: Option<&HashSet<DictionaryId>>) -> String {
+    let base = "SELECT dictionary_id, json, rowid FROM terms WHERE term = ?";
+    match subset {
+        None => base.to_string(),
+        Some(ids) => {
+            // Ids are integers, so inlining them can't inject anything
+            let ids: Vec<String> = ids.iter().map(|id| id.0.to_string()).collect();
+            format!("{base} AND dictionary_id IN ({})", ids.join(","))
+        }
+    }
+}
+
+fn journal_path(db_path: &Path) -> PathBuf {
+    let mut path = db_path.as_os_str().to_owned();
+    path.push("-journal");
+    PathBuf::from(path)
+}
+
+/// A `-journal` next to the database means a write was cut off, usually by the OS killing the
+/// app mid-import. SQLite rolls it back on the first read; that read happens here so recovery
+/// gets logged, followed by a quick check. A database that still doesn't pass is moved aside
+/// (the dictionaries then need a re-import) instead of crashing every launch.
+fn recover_interrupted_write(db_path: &Path) {
+    let journal = journal_path(db_path);
+    if !journal.exists() {
+        return;
+    }
+    warn!("⚠️ [Yomitan] Found a journal from an interrupted write, recovering...");
+
+    let check = rusqlite::Connection::open(db_path).and_then(|conn| {
+        conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))?;
+        conn.query_row("PRAGMA quick_check", [], |row| row.get::<_, String>(0))
+    });
+    let reason = match check {
+        Ok(result) if result == "ok" => {
+            // What's left after the rollback isn't hot, so nothing needs it
+            let _ = std::fs::remove_file(&journal);
+            info!("✅ [Yomitan] Rolled back the interrupted write; database is intact.");
+            return;
+        }
+        Ok(result) => result,
+        Err(rusqlite::Error::SqliteFailure(e, _))
+            if matches!(e.code, ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase) =>
+        {
+            e.to_string()
+        }
+        Err(e) => {
+            // Locked or unreadable rather than damaged; opening the pool reports it
+            error!("❌ [Yomitan] Couldn't check the database after an interrupted write: {e}");
+            return;
+        }
+    };
+
+    let suffix = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let mut moved = db_path.as_os_str().to_owned();
+    moved.push(format!(".malformed-{suffix}"));
+    let moved = PathBuf::from(moved);
+    if let Err(e) = std::fs::rename(db_path, &moved) {
+        error!("❌ [Yomitan] Failed to move the damaged database aside: {e}");
+        return;
+    }
+    let _ = std::fs::remove_file(&journal);
+    error!(
+        "❌ [Yomitan] Database damaged by the interrupted write ({reason}); moved to {} and starting fresh.",
+        moved.display()
+    );
+}
+
+/// Settings of an [`AppState`] that come from the environment in production.
+#[derive(Clone, Debug)]
+pub struct StateConfig {
+    /// `MANGATAN_YOMITAN_READ_ONLY`
+    pub read_only: bool,
+    /// `MANGATAN_YOMITAN_PRELOAD_TERMS`; `0` disables preloading.
+    pub preload_terms: usize,
+    pub content_limits: ContentLimits,
+    /// `MANGATAN_YOMITAN_WRITER_STALL_SECS`
+    pub writer_stall_after: Option<Duration>,
+    /// `MANGATAN_YOMITAN_IMPORT_THREADS`
+    pub import_threads: usize,
+}
+
+impl Default for StateConfig {
+    fn default() -> Self {
+        Self {
+            read_only: false,
+            preload_terms: DEFAULT_PRELOAD_TERMS,
+            content_limits: ContentLimits::default(),
+            writer_stall_after: Some(DEFAULT_STALL_AFTER),
+            import_threads: import::default_import_threads(),
+        }
+    }
+}
+
+impl StateConfig {
+    pub fn from_env() -> Self {
+        Self {
+            read_only: std::env::var("MANGATAN_YOMITAN_READ_ONLY")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            preload_terms: preload::limit_from_env(),
+            content_limits: ContentLimits::from_env(),
+            writer_stall_after: watchdog::stall_after_from_env(),
+            import_threads: import::import_threads_from_env(),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -29,6 +224,23 @@ pub struct AppState {
     pub pool: DbPool,
     pub data_dir: PathBuf,
     pub loading: Arc<AtomicBool>,
+    // Shared deployments can lock dictionary management; lookups keep working
+    pub read_only: bool,
+    pub preload: Arc<TermPreload>,
+    /// Depth and size limits on glossary content, applied on import and in lookup responses.
+    pub content_limits: ContentLimits,
+    /// Held by imports, dictionary edits and compaction so their writes never interleave. Taken
+    /// through [`lock_writes`](Self::lock_writes) so the writer watchdog sees each write.
+    pub import_lock: Arc<Mutex<()>>,
+    /// Progress of whichever write holds the import lock, watched by [`watchdog::watch_writer`].
+    pub writer: Heartbeat,
+    /// How long a write may go without progress before it counts as stalled; `None` never does.
+    pub writer_stall_after: Option<Duration>,
+    /// Threads compressing term records during imports; the inserts stay on one thread.
+    pub import_threads: usize,
+    last_activity: Arc<Mutex<Instant>>,
+    // Bumped whenever dictionaries or their settings change, to invalidate cached lookups
+    dictionaries_generation: Arc<AtomicU64>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -39,11 +251,23 @@ pub struct StoredRecord {
 }
 
 impl AppState {
-    pub fn new(data_dir: PathBuf) -> Self {
-        if !data_dir.exists() {
-            let _ = std::fs::create_dir_all(&data_dir);
+    /// Opens (or creates) the database in `data_dir`, configured from the environment. Fails
+    /// when `data_dir` can't be written, which SQLite would otherwise report much later and less
+    /// clearly; each binary decides how to tell the user.
+    pub fn new(data_dir: PathBuf) -> Result<Self, ReadOnlyDataDir> {
+        Self::with_config(data_dir, StateConfig::from_env())
+    }
+
+    /// Like [`new`](Self::new), with the settings given instead of read from the environment.
+    pub fn with_config(data_dir: PathBuf, config: StateConfig) -> Result<Self, ReadOnlyDataDir> {
+        if let Err(source) = data_dir::check_writable(&data_dir) {
+            return Err(ReadOnlyDataDir {
+                path: data_dir,
+                source,
+            });
         }
-        let db_path = data_dir.join("yomitan.db");
+        let db_path = data_dir.join(DB_FILE);
+        recover_interrupted_write(&db_path);
         let manager = SqliteConnectionManager::file(&db_path);
 
         let pool = Pool::new(manager).expect("Failed to create DB pool");
@@ -60,7 +284,17 @@ impl AppState {
                 id INTEGER PRIMARY KEY,
                 name TEXT NOT NULL,
                 priority INTEGER DEFAULT 0,
-                enabled BOOLEAN DEFAULT 1
+                enabled BOOLEAN DEFAULT 1,
+                language TEXT,
+                revision TEXT,
+                description TEXT,
+                author TEXT,
+                attribution TEXT,
+                url TEXT,
+                imported_at INTEGER,
+                term_count INTEGER,
+                kanji_count INTEGER,
+                meta_count INTEGER
              );
 
              CREATE TABLE IF NOT EXISTS terms (
@@ -79,13 +313,43 @@ impl AppState {
         )
         .expect("Failed to initialize database tables");
 
+        // Databases created by older versions lack the newer columns
+        for (column, kind) in [
+            ("language", "TEXT"),
+            ("revision", "TEXT"),
+            ("description", "TEXT"),
+            ("author", "TEXT"),
+            ("attribution", "TEXT"),
+            ("url", "TEXT"),
+            ("imported_at", "INTEGER"),
+            ("term_count", "INTEGER"),
+            ("kanji_count", "INTEGER"),
+            ("meta_count", "INTEGER"),
+        ] {
+            let exists = conn
+                .prepare("SELECT 1 FROM pragma_table_info('dictionaries') WHERE name = ?")
+                .and_then(|mut stmt| stmt.exists([column]))
+                .unwrap_or(false);
+            if !exists {
+                conn.execute(
+                    &format!("ALTER TABLE dictionaries ADD COLUMN {column} {kind}"),
+                    [],
+                )
+                .unwrap_or_else(|e| panic!("Failed to add dictionaries.{column} column: {e}"));
+            }
+        }
+
         // 2. Load Dictionaries from DB
         let mut dicts = HashMap::new();
         let mut max_id = 0;
 
         {
             let mut stmt = conn
-                .prepare("SELECT id, name, priority, enabled FROM dictionaries")
+                .prepare(
+                    "SELECT id, name, priority, enabled, language, revision, description, author,
+                     attribution, url, imported_at, term_count, kanji_count, meta_count
+                     FROM dictionaries",
+                )
                 .unwrap();
             let rows = stmt
                 .query_map([], |row| {
@@ -94,6 +358,18 @@ impl AppState {
                         name: row.get(1)?,
                         priority: row.get(2)?,
                         enabled: row.get(3)?,
+                        language: row.get(4)?,
+                        revision: row.get(5)?,
+                        info: DictionaryInfo {
+                            description: row.get(6)?,
+                            author: row.get(7)?,
+                            attribution: row.get(8)?,
+                            url: row.get(9)?,
+                            imported_at: row.get(10)?,
+                            term_count: row.get(11)?,
+                            kanji_count: row.get(12)?,
+                            meta_count: row.get(13)?,
+                        },
                     })
                 })
                 .unwrap();
@@ -113,15 +389,97 @@ impl AppState {
             dicts.len()
         );
 
-        Self {
+        let read_only = config.read_only;
+        if read_only {
+            info!("🔒 [Yomitan] Read-only mode: dictionary management is disabled.");
+        }
+
+        let preload = Arc::new(TermPreload::new(config.preload_terms));
+        preload.refresh(pool.clone());
+
+        Ok(Self {
             dictionaries: Arc::new(RwLock::new(dicts)),
             next_dict_id: Arc::new(RwLock::new(max_id + 1)),
             pool,
             data_dir,
             loading: Arc::new(AtomicBool::new(false)),
+            read_only,
+            preload,
+            content_limits: config.content_limits,
+            import_lock: Arc::new(Mutex::new(())),
+            writer: Heartbeat::new(),
+            writer_stall_after: config.writer_stall_after,
+            import_threads: config.import_threads,
+            last_activity: Arc::new(Mutex::new(Instant::now())),
+            dictionaries_generation: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    pub fn db_path(&self) -> PathBuf {
+        self.data_dir.join(DB_FILE)
+    }
+
+    /// Takes the import lock for `activity`, waiting for any running write.
+    pub fn lock_writes(&self, activity: &str) -> WriteGuard<'_> {
+        let lock = self.import_lock.lock().expect("lock");
+        WriteGuard::new(lock, &self.writer, activity)
+    }
+
+    /// Like [`lock_writes`](Self::lock_writes), but `None` while another write is running.
+    pub fn try_lock_writes(&self, activity: &str) -> Option<WriteGuard<'_>> {
+        let lock = self.import_lock.try_lock().ok()?;
+        Some(WriteGuard::new(lock, &self.writer, activity))
+    }
+
+    pub fn writer_health(&self) -> WriterHealth {
+        let (busy, since, activity) = self.writer.read();
+        WriterHealth {
+            last_heartbeat_secs: since.as_secs(),
+            last_activity: activity,
+            stalled: busy && self.writer_stall_after.is_some_and(|after| since >= after),
         }
     }
 
+    /// Waits up to `timeout` for a running import or dictionary edit to commit, so the process
+    /// can be killed without leaving a journal behind. Returns false if a write was still
+    /// running; it's then rolled back on the next start (see `recover_interrupted_write`).
+    pub fn flush_for_shutdown(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let _guard = loop {
+            match self.import_lock.try_lock() {
+                Ok(guard) => break guard,
+                Err(_) if Instant::now() >= deadline => {
+                    warn!(
+                        "⚠️ [Yomitan] Shutting down during a dictionary write; it will be rolled back on the next start."
+                    );
+                    return false;
+                }
+                Err(_) => std::thread::sleep(Duration::from_millis(50)),
+            }
+        };
+        let clean = !journal_path(&self.db_path()).exists();
+        info!("💾 [Yomitan] Database flushed for shutdown (clean={clean}).");
+        clean
+    }
+
+    /// Marks the server as in use, which holds off automatic maintenance.
+    pub fn touch(&self) {
+        *self.last_activity.lock().expect("lock") = Instant::now();
+    }
+
+    pub fn idle_for(&self) -> Duration {
+        self.last_activity.lock().expect("lock").elapsed()
+    }
+
+    /// Marks installed dictionaries, their terms or their settings as changed.
+    pub fn dictionaries_changed(&self) {
+        self.dictionaries_generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn dictionaries_generation(&self) -> u64 {
+        self.dictionaries_generation.load(Ordering::SeqCst)
+    }
+
     pub fn set_loading(&self, val: bool) {
         self.loading.store(val, Ordering::SeqCst);
     }
@@ -0,0 +1,378 @@
+//! The built-in "User Dictionary" of the user's own glosses, for slang and names no published
+//! dictionary has. Its entries are stored like imported ones, so lookups find them without
+//! special-casing; an entry's id is the `rowid` of either of its rows (headword or reading),
+//! which lookups return as `userTermId`.
+
+use std::fmt;
+
+use rusqlite::{OptionalExtension, Transaction};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use wordbase_api::{
+    DictionaryId, Record,
+    dict::yomitan::{Glossary, structured},
+};
+
+use crate::{
+    import::insert_dictionary,
+    lookup::normalize_ideographs,
+    state::{AppState, DictionaryInfo},
+};
+
+/// Imported dictionaries are numbered from 1, so this never clashes with one.
+pub const USER_DICTIONARY_ID: DictionaryId = DictionaryId(0);
+pub const USER_DICTIONARY_NAME: &str = "User Dictionary";
+/// Ahead of imported dictionaries, which start at 0, until the user reorders them.
+const USER_DICTIONARY_PRIORITY: i64 = -1;
+
+/// One entry, as the user writes it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UserTerm {
+    pub headword: String,
+    /// Empty when the headword is its own reading.
+    #[serde(default)]
+    pub reading: String,
+    /// One definition per line.
+    pub glossary: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Fields of a [`UserTerm`] to change; the others are kept.
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct UserTermPatch {
+    pub headword: Option<String>,
+    pub reading: Option<String>,
+    pub glossary: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+/// A stored [`UserTerm`] with the id to edit or delete it by.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct UserTermEntry {
+    pub id: i64,
+    #[serde(flatten)]
+    pub term: UserTerm,
+}
+
+#[derive(Debug)]
+pub enum UserTermError {
+    /// Missing headword or glossary.
+    Invalid(&'static str),
+    NotFound(i64),
+    /// The same headword, reading and glossary are already in the dictionary.
+    Duplicate,
+    Db(String),
+}
+
+impl fmt::Display for UserTermError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(message) => f.write_str(message),
+            Self::NotFound(id) => write!(f, "User term {id} not found"),
+            Self::Duplicate => f.write_str("The user dictionary already has this entry"),
+            Self::Db(message) => f.write_str(message),
+        }
+    }
+}
+
+impl<E: Into<anyhow::Error>> From<E> for UserTermError {
+    fn from(err: E) -> Self {
+        Self::Db(err.into().to_string())
+    }
+}
+
+/// A [`StoredRecord`](crate::state::StoredRecord) with the headword added, so every entry's blob is its own even when
+/// another shares its reading and glossary. Lookups read it as a plain `StoredRecord`.
+#[derive(Serialize, Deserialize)]
+struct UserRecord {
+    dictionary_id: DictionaryId,
+    record: Record,
+    reading: Option<String>,
+    headword: String,
+}
+
+impl UserTerm {
+    /// Trimmed, with the ideographs normalized like imported headwords, and checked.
+    fn normalized(mut self) -> Result<Self, UserTermError> {
+        let headword = self.headword.trim();
+        self.headword = normalize_ideographs(headword).unwrap_or_else(|| headword.to_string());
+        self.reading = self.reading.trim().to_string();
+        if self.reading == self.headword {
+            self.reading.clear();
+        }
+        self.glossary = self.glossary.trim().to_string();
+        self.tags = self
+            .tags
+            .iter()
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        match (self.headword.is_empty(), self.glossary.is_empty()) {
+            (true, _) => Err(UserTermError::Invalid("A headword is required")),
+            (_, true) => Err(UserTermError::Invalid("A glossary is required")),
+            _ => Ok(self),
+        }
+    }
+
+    /// The compressed record both rows of the entry hold.
+    fn encode(&self) -> Result<Vec<u8>, UserTermError> {
+        let record = UserRecord {
+            dictionary_id: USER_DICTIONARY_ID,
+            record: Record::YomitanGlossary(Glossary {
+                popularity: 0,
+                tags: self
+                    .tags
+                    .iter()
+                    .filter_map(|tag| {
+                        // A tag-bank style tag, with nothing but a name to show
+                        let full =
+                            json!({ "name": tag, "category": "", "description": "", "order": 0 });
+                        serde_json::from_value(full)
+                            .or_else(|_| serde_json::from_value(json!(tag)))
+                            .ok()
+                    })
+                    .collect(),
+                content: self
+                    .glossary
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(|line| structured::Content::String(line.to_string()))
+                    .collect(),
+            }),
+            reading: (!self.reading.is_empty()).then(|| self.reading.clone()),
+            headword: self.headword.clone(),
+        };
+        Ok(snap::raw::Encoder::new().compress_vec(&serde_json::to_vec(&record)?)?)
+    }
+
+    fn decode(blob: &[u8]) -> Result<Self, UserTermError> {
+        let record: UserRecord =
+            serde_json::from_slice(&snap::raw::Decoder::new().decompress_vec(blob)?)?;
+        let Record::YomitanGlossary(glossary) = record.record else {
+            return Err(UserTermError::Db("Not a glossary record".to_string()));
+        };
+        let lines: Vec<String> = glossary
+            .content
+            .iter()
+            .map(|content| match content {
+                structured::Content::String(text) => text.clone(),
+                other => json!(other).to_string(),
+            })
+            .collect();
+        Ok(Self {
+            headword: record.headword,
+            reading: record.reading.unwrap_or_default(),
+            glossary: lines.join("\n"),
+            tags: glossary
+                .tags
+                .iter()
+                .filter_map(|tag| match json!(tag) {
+                    serde_json::Value::String(name) => Some(name),
+                    value => value["name"].as_str().map(str::to_string),
+                })
+                .collect(),
+        })
+    }
+}
+
+/// The entry `id` belongs to, with the ids of its headword row and of its reading row if it
+/// has one.
+fn find_entry(tx: &Transaction, id: i64) -> Result<(i64, UserTerm, Option<i64>), UserTermError> {
+    let blob: Vec<u8> = tx
+        .query_row(
+            "SELECT json FROM terms WHERE rowid = ? AND dictionary_id = ?",
+            rusqlite::params![id, USER_DICTIONARY_ID.0],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or(UserTermError::NotFound(id))?;
+    let term = UserTerm::decode(&blob)?;
+    let rows: Vec<(i64, String)> = tx
+        .prepare("SELECT rowid, term FROM terms WHERE dictionary_id = ? AND json = ?")?
+        .query_map(rusqlite::params![USER_DICTIONARY_ID.0, blob], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect::<Result<_, _>>()?;
+    let (headword_rows, reading_rows): (Vec<_>, Vec<_>) = rows
+        .into_iter()
+        .partition(|(_, row_term)| *row_term == term.headword);
+    let headword_row = headword_rows
+        .first()
+        .map(|(rowid, _)| *rowid)
+        .ok_or(UserTermError::NotFound(id))?;
+    let reading_row = reading_rows.first().map(|(rowid, _)| *rowid);
+    Ok((headword_row, term, reading_row))
+}
+
+fn exists(tx: &Transaction, blob: &[u8]) -> Result<bool, UserTermError> {
+    Ok(tx
+        .query_row(
+            "SELECT 1 FROM terms WHERE dictionary_id = ? AND json = ?",
+            rusqlite::params![USER_DICTIONARY_ID.0, blob],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some())
+}
+
+/// Creates the user dictionary, enabled and first in line, unless it's there already.
+fn ensure_dictionary(state: &AppState, tx: &Transaction) -> Result<(), UserTermError> {
+    if state
+        .dictionaries
+        .read()
+        .expect("lock")
+        .contains_key(&USER_DICTIONARY_ID)
+    {
+        return Ok(());
+    }
+    insert_dictionary(
+        state,
+        tx,
+        USER_DICTIONARY_ID,
+        USER_DICTIONARY_NAME,
+        USER_DICTIONARY_PRIORITY,
+        None,
+        None,
+        DictionaryInfo {
+            description: Some("Entries added in Mangatan.".to_string()),
+            ..DictionaryInfo::imported_now()
+        },
+    )?;
+    Ok(())
+}
+
+/// Keeps the dictionary's entry count and the lookup caches in step with a change.
+fn finish(state: &AppState, tx: Transaction) -> Result<(), UserTermError> {
+    let count: i64 = tx.query_row(
+        "SELECT COUNT(DISTINCT json) FROM terms WHERE dictionary_id = ?",
+        [USER_DICTIONARY_ID.0],
+        |row| row.get(0),
+    )?;
+    tx.execute(
+        "UPDATE dictionaries SET term_count = ? WHERE id = ?",
+        rusqlite::params![count, USER_DICTIONARY_ID.0],
+    )?;
+    tx.commit()?;
+    if let Some(dict) = state
+        .dictionaries
+        .write()
+        .expect("lock")
+        .get_mut(&USER_DICTIONARY_ID)
+    {
+        dict.info.term_count = Some(count);
+    }
+    state.preload.refresh(state.pool.clone());
+    state.dictionaries_changed();
+    Ok(())
+}
+
+/// Adds `term`, creating the user dictionary on first use. Returns the new entry.
+pub fn add(state: &AppState, term: UserTerm) -> Result<UserTermEntry, UserTermError> {
+    let term = term.normalized()?;
+    let blob = term.encode()?;
+    let mut conn = state.pool.get()?;
+    let tx = conn.transaction()?;
+    if exists(&tx, &blob)? {
+        return Err(UserTermError::Duplicate);
+    }
+    ensure_dictionary(state, &tx)?;
+
+    let insert = "INSERT INTO terms (term, dictionary_id, json) VALUES (?, ?, ?)";
+    tx.execute(
+        insert,
+        rusqlite::params![term.headword, USER_DICTIONARY_ID.0, blob],
+    )?;
+    let id = tx.last_insert_rowid();
+    if !term.reading.is_empty() {
+        tx.execute(
+            insert,
+            rusqlite::params![term.reading, USER_DICTIONARY_ID.0, blob],
+        )?;
+    }
+    finish(state, tx)?;
+    Ok(UserTermEntry { id, term })
+}
+
+/// Applies `patch` to the entry `id` belongs to. Its headword row keeps its id.
+pub fn update(
+    state: &AppState,
+    id: i64,
+    patch: UserTermPatch,
+) -> Result<UserTermEntry, UserTermError> {
+    let mut conn = state.pool.get()?;
+    let tx = conn.transaction()?;
+    let (headword_row, current, reading_row) = find_entry(&tx, id)?;
+    let term = UserTerm {
+        headword: patch.headword.unwrap_or_else(|| current.headword.clone()),
+        reading: patch.reading.unwrap_or_else(|| current.reading.clone()),
+        glossary: patch.glossary.unwrap_or_else(|| current.glossary.clone()),
+        tags: patch.tags.unwrap_or_else(|| current.tags.clone()),
+    }
+    .normalized()?;
+    if term == current {
+        return Ok(UserTermEntry {
+            id: headword_row,
+            term,
+        });
+    }
+    let blob = term.encode()?;
+    if exists(&tx, &blob)? {
+        return Err(UserTermError::Duplicate);
+    }
+
+    tx.execute(
+        "UPDATE terms SET term = ?, json = ? WHERE rowid = ?",
+        rusqlite::params![term.headword, blob, headword_row],
+    )?;
+    match (reading_row, term.reading.is_empty()) {
+        (Some(rowid), false) => tx.execute(
+            "UPDATE terms SET term = ?, json = ? WHERE rowid = ?",
+            rusqlite::params![term.reading, blob, rowid],
+        )?,
+        (Some(rowid), true) => tx.execute("DELETE FROM terms WHERE rowid = ?", [rowid])?,
+        (None, false) => tx.execute(
+            "INSERT INTO terms (term, dictionary_id, json) VALUES (?, ?, ?)",
+            rusqlite::params![term.reading, USER_DICTIONARY_ID.0, blob],
+        )?,
+        (None, true) => 0,
+    };
+    finish(state, tx)?;
+    Ok(UserTermEntry {
+        id: headword_row,
+        term,
+    })
+}
+
+/// Removes the entry `id` belongs to.
+pub fn delete(state: &AppState, id: i64) -> Result<(), UserTermError> {
+    let mut conn = state.pool.get()?;
+    let tx = conn.transaction()?;
+    let (headword_row, _, reading_row) = find_entry(&tx, id)?;
+    for rowid in std::iter::once(headword_row).chain(reading_row) {
+        tx.execute("DELETE FROM terms WHERE rowid = ?", [rowid])?;
+    }
+    finish(state, tx)
+}
+
+/// Every entry, oldest first, for backups.
+pub fn list(state: &AppState) -> Result<Vec<UserTermEntry>, UserTermError> {
+    let conn = state.pool.get()?;
+    let rows: Vec<(i64, String, Vec<u8>)> = conn
+        .prepare("SELECT rowid, term, json FROM terms WHERE dictionary_id = ? ORDER BY rowid")?
+        .query_map([USER_DICTIONARY_ID.0], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?
+        .collect::<Result<_, _>>()?;
+
+    let mut entries = Vec::new();
+    for (id, row_term, blob) in rows {
+        let term = UserTerm::decode(&blob)?;
+        // Reading rows repeat an entry listed under its headword row
+        if row_term == term.headword {
+            entries.push(UserTermEntry { id, term });
+        }
+    }
+    Ok(entries)
+}
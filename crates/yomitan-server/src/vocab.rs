@@ -0,0 +1,196 @@
+use crate::{
+    frequency::frequency_rank,
+    state::{AppState, StoredRecord, terms_by_term_sql},
+};
+use mangatan_tokenize::Token;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use tracing::error;
+use wordbase_api::{DictionaryId, Record};
+
+/// Longest input `/vocab-report` takes, a few times a long chapter.
+pub const MAX_REPORT_CHARS: usize = 200_000;
+/// Lemmas looked up per blocking task.
+pub const LOOKUP_CHUNK: usize = 200;
+/// Lookup chunks running at once, so a report doesn't hog the connection pool.
+pub const LOOKUP_CONCURRENCY: usize = 2;
+
+// UniDic parts of speech that aren't vocabulary
+const SKIPPED_POS: &[&str] = &["補助記号", "記号", "空白"];
+
+/// A distinct word of the input, before it's been looked up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Occurrence {
+    pub lemma: String,
+    /// Form as written the first time it came up.
+    pub surface: String,
+    pub reading: Option<String>,
+    pub part_of_speech: Option<String>,
+    pub count: usize,
+}
+
+/// What the enabled dictionaries know about a lemma.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Coverage {
+    pub in_dictionary: bool,
+    /// Frequency value of the highest priority dictionary that has one.
+    pub frequency: Option<i64>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct VocabEntry {
+    pub lemma: String,
+    pub surface: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reading: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub part_of_speech: Option<String>,
+    pub occurrences: usize,
+    pub in_dictionary: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency: Option<i64>,
+}
+
+impl VocabEntry {
+    pub fn new(occurrence: Occurrence, coverage: Coverage) -> Self {
+        Self {
+            lemma: occurrence.lemma,
+            surface: occurrence.surface,
+            reading: occurrence.reading,
+            part_of_speech: occurrence.part_of_speech,
+            occurrences: occurrence.count,
+            in_dictionary: coverage.in_dictionary,
+            frequency: coverage.frequency,
+        }
+    }
+}
+
+/// Splits a chapter's text after sentence-ending punctuation and line breaks, so it can be
+/// tokenized a sentence at a time.
+pub fn split_sentences(text: &str) -> Vec<&str> {
+    text.split_inclusive(['。', '！', '？', '!', '?', '\n'])
+        .map(str::trim)
+        .filter(|sentence| !sentence.is_empty())
+        .collect()
+}
+
+/// Dictionary form to look a token up under. UniDic writes loanwords as `パン-pão`, which no
+/// dictionary has, so the origin is cut off. `None` for punctuation and whitespace.
+fn lemma_of(token: &Token) -> Option<String> {
+    if token
+        .part_of_speech
+        .as_deref()
+        .is_some_and(|pos| SKIPPED_POS.contains(&pos))
+        || !token.text.chars().any(char::is_alphanumeric)
+    {
+        return None;
+    }
+    let lemma = token
+        .lemma
+        .as_deref()
+        .and_then(|lemma| lemma.split('-').next())
+        .filter(|lemma| !lemma.is_empty())
+        .unwrap_or(&token.text);
+    Some(lemma.to_string())
+}
+
+/// Counts the distinct lemmas of `tokens`, in order of first appearance.
+pub fn count_lemmas(tokens: impl IntoIterator<Item = Token>) -> Vec<Occurrence> {
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut occurrences: Vec<Occurrence> = Vec::new();
+    for token in tokens {
+        let Some(lemma) = lemma_of(&token) else {
+            continue;
+        };
+        match index.get(&lemma) {
+            Some(&i) => occurrences[i].count += 1,
+            None => {
+                index.insert(lemma.clone(), occurrences.len());
+                occurrences.push(Occurrence {
+                    lemma,
+                    surface: token.text,
+                    reading: token.reading,
+                    part_of_speech: token.part_of_speech,
+                    count: 1,
+                });
+            }
+        }
+    }
+    occurrences
+}
+
+/// Looks each of `lemmas` up in the enabled dictionaries (or the enabled ones in `subset`).
+/// Lemmas the database couldn't be asked about are left out.
+pub fn lookup_lemmas(
+    state: &AppState,
+    lemmas: &[String],
+    subset: Option<&HashSet<DictionaryId>>,
+) -> HashMap<String, Coverage> {
+    let priorities: HashMap<DictionaryId, i64> = {
+        let dicts = state.dictionaries.read().expect("lock");
+        dicts
+            .iter()
+            .filter(|(_, d)| d.enabled)
+            .map(|(id, d)| (*id, d.priority))
+            .collect()
+    };
+
+    let conn = match state.pool.get() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("❌ Failed to get DB connection: {}", e);
+            return HashMap::new();
+        }
+    };
+    let mut stmt = match conn.prepare(&terms_by_term_sql(subset)) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("❌ DB Prepare Error: {}", e);
+            return HashMap::new();
+        }
+    };
+
+    let mut decoder = snap::raw::Decoder::new();
+    let mut found = HashMap::new();
+    for lemma in lemmas {
+        let rows: Vec<(i64, Vec<u8>)> =
+            match stmt.query_map([lemma], |row| Ok((row.get(0)?, row.get(1)?))) {
+                Ok(rows) => rows.flatten().collect(),
+                Err(e) => {
+                    error!("❌ DB Query Error: {}", e);
+                    continue;
+                }
+            };
+
+        let mut coverage = Coverage::default();
+        let mut best_priority = i64::MAX;
+        for (dict_id, compressed) in rows {
+            let Some(&priority) = priorities.get(&DictionaryId(dict_id)) else {
+                continue;
+            };
+            let Some(stored) = decoder
+                .decompress_vec(&compressed)
+                .ok()
+                .and_then(|data| serde_json::from_slice::<StoredRecord>(&data).ok())
+            else {
+                coverage.in_dictionary = true;
+                continue;
+            };
+            // Frequency lists rank words without defining them
+            if frequency_rank(&stored.record).is_none() {
+                coverage.in_dictionary = true;
+            }
+            // Dictionaries without frequency data store 0
+            if let Record::YomitanGlossary(g) = &stored.record
+                && g.popularity != 0
+                && priority < best_priority
+            {
+                best_priority = priority;
+                coverage.frequency = Some(g.popularity);
+            }
+        }
+        found.insert(lemma.clone(), coverage);
+    }
+    found
+}
@@ -0,0 +1,130 @@
+//! Stall detection for dictionary writes. Imports, edits and compaction all hold the import lock;
+//! one that hangs blocks every later write without anything in the log saying why.
+
+use serde::Serialize;
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+use tracing::{error, info};
+
+use crate::state::AppState;
+
+/// How long a write may go without reporting progress before it counts as stalled. Imports
+/// report once per bank file, so this only needs to cover the largest bank.
+pub const DEFAULT_STALL_AFTER: Duration = Duration::from_secs(10 * 60);
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Reads `MANGATAN_YOMITAN_WRITER_STALL_SECS`; `0` turns stall detection off.
+pub fn stall_after_from_env() -> Option<Duration> {
+    match std::env::var("MANGATAN_YOMITAN_WRITER_STALL_SECS") {
+        Ok(v) => v
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs),
+        Err(_) => Some(DEFAULT_STALL_AFTER),
+    }
+}
+
+struct Beat {
+    at: Instant,
+    activity: String,
+    busy: bool,
+}
+
+/// When the writer last made progress, and on what.
+#[derive(Clone)]
+pub struct Heartbeat {
+    beat: Arc<Mutex<Beat>>,
+}
+
+impl Heartbeat {
+    pub fn new() -> Self {
+        Self {
+            beat: Arc::new(Mutex::new(Beat {
+                at: Instant::now(),
+                activity: "idle".to_string(),
+                busy: false,
+            })),
+        }
+    }
+
+    fn set(&self, activity: &str, busy: bool) {
+        let mut beat = self.beat.lock().expect("lock");
+        beat.at = Instant::now();
+        beat.activity = activity.to_string();
+        beat.busy = busy;
+    }
+
+    /// Whether a write is running, how long ago the writer last made progress, and on what.
+    pub fn read(&self) -> (bool, Duration, String) {
+        let beat = self.beat.lock().expect("lock");
+        (beat.busy, beat.at.elapsed(), beat.activity.clone())
+    }
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The import lock, held for one write. Progress is reported through [`beat`](Self::beat) and
+/// the writer goes back to idle when the guard drops.
+pub struct WriteGuard<'a> {
+    _lock: MutexGuard<'a, ()>,
+    heartbeat: &'a Heartbeat,
+}
+
+impl<'a> WriteGuard<'a> {
+    pub(crate) fn new(lock: MutexGuard<'a, ()>, heartbeat: &'a Heartbeat, activity: &str) -> Self {
+        heartbeat.set(activity, true);
+        Self {
+            _lock: lock,
+            heartbeat,
+        }
+    }
+
+    /// Reports progress on a long write.
+    pub fn beat(&self, activity: &str) {
+        self.heartbeat.set(activity, true);
+    }
+}
+
+impl Drop for WriteGuard<'_> {
+    fn drop(&mut self) {
+        self.heartbeat.set("idle", false);
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct WriterHealth {
+    pub last_heartbeat_secs: u64,
+    pub last_activity: String,
+    /// A write has gone longer than the stall threshold without progress.
+    pub stalled: bool,
+}
+
+/// Checks the writer periodically and logs when it stalls and when it recovers.
+pub async fn watch_writer(state: AppState) {
+    if state.writer_stall_after.is_none() {
+        return;
+    }
+    let mut stalled = false;
+    loop {
+        tokio::time::sleep(CHECK_INTERVAL).await;
+        let health = state.writer_health();
+        match (stalled, health.stalled) {
+            (false, true) => error!(
+                "🚨 [Yomitan] Dictionary writer stalled: no progress in {}s (last activity: {}). \
+                 Imports and dictionary edits will wait until it finishes.",
+                health.last_heartbeat_secs, health.last_activity
+            ),
+            (true, false) => info!("✅ [Yomitan] Dictionary writer recovered"),
+            _ => {}
+        }
+        stalled = health.stalled;
+    }
+}
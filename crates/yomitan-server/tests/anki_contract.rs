@@ -0,0 +1,215 @@
+mod fixtures;
+
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    http::StatusCode,
+    routing::post,
+};
+use fixtures::Scratch;
+use mangatan_yomitan_server::{
+    ServerState,
+    anki::{AnkiChecker, AnkiCode, AnkiConfig, AnkiEnvelope, AnkiError},
+    handlers::{self, AnkiDuplicateParams, AnkiValidateRequest},
+    state::StateConfig,
+};
+use serde_json::{Value, json};
+
+/// An AnkiConnect stand-in with a `Mining` note type and one note, for 既出.
+async fn fake_anki_connect() -> String {
+    async fn answer(Json(request): Json<Value>) -> Json<Value> {
+        let params = &request["params"];
+        let result = match request["action"].as_str() {
+            Some("findNotes") => {
+                let query = params["query"].as_str().unwrap_or_default();
+                if query.contains("壊れ") {
+                    return Json(json!({ "result": null, "error": "collection is not available" }));
+                }
+                json!(if query.contains("既出") {
+                    vec![42]
+                } else {
+                    vec![]
+                })
+            }
+            Some("modelNames") => json!(["Basic", "Mining"]),
+            Some("modelFieldNames") => json!(["Word", "Sentence", "Picture"]),
+            _ => return Json(json!({ "result": null, "error": "unsupported action" })),
+        };
+        Json(json!({ "result": result, "error": null }))
+    }
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind should succeed");
+    let addr = listener
+        .local_addr()
+        .expect("listener should have an address");
+    tokio::spawn(
+        async move { axum::serve(listener, Router::new().route("/", post(answer))).await },
+    );
+    format!("http://{addr}")
+}
+
+fn server(name: &str, anki_url: Option<String>) -> Scratch<ServerState> {
+    let config = anki_url.map(|url| AnkiConfig {
+        url,
+        query_template: "Word:{term}".to_string(),
+        duplicate_query: "Word:{term}".to_string(),
+        ttl: Duration::from_secs(60),
+    });
+    fixtures::scratch(name, |dir| ServerState {
+        anki: Arc::new(AnkiChecker::new(config)),
+        ..ServerState::with_config(dir, StateConfig::default()).expect("state")
+    })
+}
+
+/// Serializes the envelope and checks the keys the WebUI relies on.
+fn body(envelope: &AnkiEnvelope) -> Value {
+    let body = serde_json::to_value(envelope).expect("serializes");
+    let mut keys: Vec<_> = body
+        .as_object()
+        .expect("object")
+        .keys()
+        .map(String::as_str)
+        .collect();
+    keys.sort_unstable();
+    assert_eq!(keys, ["code", "details", "message"], "body: {body}");
+    assert!(body["message"].is_string(), "body: {body}");
+    assert!(body["details"].is_object(), "body: {body}");
+    body
+}
+
+async fn duplicate(server: &ServerState, term: &str) -> (StatusCode, Value) {
+    let params = AnkiDuplicateParams {
+        term: term.to_string(),
+        reading: String::new(),
+    };
+    let (status, Json(envelope)) =
+        handlers::anki_duplicate_handler(State(server.clone()), Query(params)).await;
+    (status, body(&envelope))
+}
+
+async fn validate(server: &ServerState, model: &str, sentence: &str) -> (StatusCode, Value) {
+    let request = AnkiValidateRequest {
+        model: model.to_string(),
+        sentence_field: Some(sentence.to_string()),
+        image_field: Some("Picture".to_string()),
+    };
+    let (status, Json(envelope)) =
+        handlers::anki_validate_handler(State(server.clone()), Json(request)).await;
+    (status, body(&envelope))
+}
+
+#[test]
+fn codes_serialize_as_snake_case() {
+    let codes = [
+        (AnkiCode::AnkiDisabled, "anki_disabled"),
+        (AnkiCode::AnkiUnreachable, "anki_unreachable"),
+        (AnkiCode::InvalidResponse, "invalid_response"),
+        (AnkiCode::AnkiRejected, "anki_rejected"),
+        (AnkiCode::DuplicateFound, "duplicate_found"),
+        (AnkiCode::NoDuplicate, "no_duplicate"),
+        (AnkiCode::FieldsValid, "fields_valid"),
+        (AnkiCode::ModelUnknown, "model_unknown"),
+        (AnkiCode::FieldUnknown, "field_unknown"),
+    ];
+    for (code, name) in codes {
+        assert_eq!(serde_json::to_value(code).expect("serializes"), name);
+    }
+}
+
+#[test]
+fn errors_carry_their_reason() {
+    let disabled = body(&AnkiError::Disabled.envelope());
+    assert_eq!(disabled["code"], "anki_disabled");
+    assert_eq!(disabled["details"], json!({}));
+    assert_eq!(AnkiError::Disabled.status(), StatusCode::NOT_FOUND);
+
+    let rejected = AnkiError::Rejected("deck was not found".to_string());
+    assert_eq!(rejected.status(), StatusCode::BAD_GATEWAY);
+    let rejected = body(&rejected.envelope());
+    assert_eq!(rejected["code"], "anki_rejected");
+    assert_eq!(
+        rejected["details"],
+        json!({ "reason": "deck was not found" })
+    );
+}
+
+#[tokio::test]
+async fn disabled_anki_answers_not_found() {
+    let server = server("anki-disabled", None);
+    let (status, body) = duplicate(&server, "猫").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "anki_disabled");
+
+    let (status, body) = validate(&server, "Mining", "Sentence").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "anki_disabled");
+}
+
+#[tokio::test]
+async fn duplicate_check_results() {
+    let server = server("anki-duplicate", Some(fake_anki_connect().await));
+
+    let (status, found) = duplicate(&server, "既出").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(found["code"], "duplicate_found");
+    assert_eq!(
+        found["details"],
+        json!({ "term": "既出", "duplicate": true, "noteId": 42 })
+    );
+
+    let (status, missing) = duplicate(&server, "新語").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(missing["code"], "no_duplicate");
+    assert_eq!(
+        missing["details"],
+        json!({ "term": "新語", "duplicate": false, "noteId": null })
+    );
+
+    let (status, rejected) = duplicate(&server, "壊れ").await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(rejected["code"], "anki_rejected");
+    assert_eq!(rejected["details"]["reason"], "collection is not available");
+}
+
+#[tokio::test]
+async fn validation_results() {
+    let server = server("anki-validate", Some(fake_anki_connect().await));
+
+    let (status, valid) = validate(&server, "Mining", "Sentence").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(valid["code"], "fields_valid");
+    assert_eq!(valid["details"]["valid"], true);
+
+    let (_, unknown_field) = validate(&server, "Mining", "Sentense").await;
+    assert_eq!(unknown_field["code"], "field_unknown");
+    assert_eq!(
+        unknown_field["details"]["unknown_fields"],
+        json!(["Sentense"])
+    );
+    assert_eq!(
+        unknown_field["details"]["available_fields"],
+        json!(["Word", "Sentence", "Picture"])
+    );
+
+    let (_, unknown_model) = validate(&server, "Mineing", "Sentence").await;
+    assert_eq!(unknown_model["code"], "model_unknown");
+    assert_eq!(unknown_model["details"]["model_exists"], false);
+}
+
+#[tokio::test]
+async fn unreachable_anki_is_a_bad_gateway() {
+    // Nothing listens on a port that was just released
+    let addr = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("free port");
+    let server = server("anki-unreachable", Some(format!("http://{addr}")));
+
+    let (status, body) = duplicate(&server, "猫").await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(body["code"], "anki_unreachable");
+    assert!(body["details"]["reason"].is_string());
+}
@@ -0,0 +1,105 @@
+mod fixtures;
+
+use axum::{Json, extract::State};
+use fixtures::Scratch;
+use mangatan_yomitan_server::{
+    ServerState,
+    handlers::{self, BulkToggleRequest},
+    import,
+};
+use serde_json::{Value, json};
+
+fn server(name: &str) -> Scratch<ServerState> {
+    let server = fixtures::scratch(&format!("bulk-toggle-{name}"), |dir| {
+        ServerState::new(dir).expect("state")
+    });
+    for title in ["JMdict", "JPDB Freq", "Innocent Corpus FREQ"] {
+        let terms = json!([["語", "ご", "", "", 0, ["word"], 1, ""]]);
+        let zip = fixtures::dictionary_zip(fixtures::bare_index(title), terms);
+        import::import_zip(&server.app, &zip).expect("import");
+    }
+    server
+}
+
+fn id_of(server: &ServerState, title: &str) -> i64 {
+    let dicts = server.app.dictionaries.read().expect("lock");
+    dicts
+        .values()
+        .find(|d| d.name == title)
+        .map(|d| d.id.0)
+        .expect("imported dictionary")
+}
+
+fn enabled_in_db(server: &ServerState, id: i64) -> bool {
+    let conn = server.app.pool.get().expect("conn");
+    conn.query_row(
+        "SELECT enabled FROM dictionaries WHERE id = ?",
+        [id],
+        |row| row.get(0),
+    )
+    .expect("dictionary row")
+}
+
+async fn toggle(server: &ServerState, request: Value) -> (u16, Value) {
+    let request: BulkToggleRequest = serde_json::from_value(request).expect("request");
+    let (status, Json(body)) =
+        handlers::bulk_toggle_handler(State(server.clone()), Json(request)).await;
+    (status.as_u16(), body)
+}
+
+#[tokio::test]
+async fn disables_dictionaries_by_name() {
+    let server = server("name");
+    let (status, body) = toggle(
+        &server,
+        json!({ "name_contains": "freq", "enabled": false }),
+    )
+    .await;
+    assert_eq!(status, 200);
+
+    let mut expected = vec![
+        id_of(&server, "JPDB Freq"),
+        id_of(&server, "Innocent Corpus FREQ"),
+    ];
+    expected.sort_unstable();
+    assert_eq!(body["ids"], json!(expected));
+
+    let jmdict = id_of(&server, "JMdict");
+    let dicts = server.app.dictionaries.read().expect("lock");
+    for dict in dicts.values() {
+        assert_eq!(dict.enabled, dict.id.0 == jmdict, "{}", dict.name);
+        assert_eq!(
+            enabled_in_db(&server, dict.id.0),
+            dict.enabled,
+            "{}",
+            dict.name
+        );
+    }
+}
+
+#[tokio::test]
+async fn ids_and_name_narrow_each_other() {
+    let server = server("both");
+    let jpdb = id_of(&server, "JPDB Freq");
+    let jmdict = id_of(&server, "JMdict");
+    let (_, body) = toggle(
+        &server,
+        json!({ "ids": [jpdb, jmdict], "name_contains": "Freq", "enabled": false }),
+    )
+    .await;
+    assert_eq!(body["ids"], json!([jpdb]));
+    assert!(enabled_in_db(&server, jmdict));
+
+    let (_, body) = toggle(&server, json!({ "ids": [jpdb], "enabled": true })).await;
+    assert_eq!(body["ids"], json!([jpdb]));
+    assert!(enabled_in_db(&server, jpdb));
+}
+
+#[tokio::test]
+async fn requires_a_selection() {
+    let server = server("empty");
+    let (status, _) = toggle(&server, json!({ "enabled": false })).await;
+    assert_eq!(status, 400);
+    let dicts = server.app.dictionaries.read().expect("lock");
+    assert!(dicts.values().all(|d| d.enabled));
+}
@@ -0,0 +1,52 @@
+mod fixtures;
+
+use mangatan_yomitan_server::{import, lookup::LookupService, maintenance, state::AppState};
+use serde_json::{Value, json};
+
+/// Enough rows to leave a few MB of free pages behind once deleted.
+fn bulky_dictionary() -> Vec<u8> {
+    let terms: Vec<Value> = (0..4000)
+        .map(|i| {
+            let gloss = format!("filler definition {i} ").repeat(40);
+            json!([format!("語{i}"), "ご", "", "", 0, [gloss], i, ""])
+        })
+        .collect();
+    fixtures::dictionary_zip(fixtures::bare_index("Bulky"), json!(terms))
+}
+
+#[test]
+fn compaction_shrinks_the_file_and_keeps_lookups_working() {
+    let state = fixtures::scratch("compact", |dir| AppState::new(dir).expect("state"));
+
+    import::import_zip(&state, &bulky_dictionary()).expect("bulky import");
+    import::import_zip(
+        &state,
+        &fixtures::dictionary_zip(
+            fixtures::bare_index("Mini JMdict"),
+            json!([["人人", "ひとびと", "", "", 0, ["people"], 1, ""]]),
+        ),
+    )
+    .expect("small import");
+
+    // Like a merge, which drops rows without vacuuming
+    state
+        .pool
+        .get()
+        .expect("conn")
+        .execute("DELETE FROM terms WHERE term LIKE '語%'", [])
+        .expect("delete");
+    let before = maintenance::db_stats(&state).expect("stats");
+    assert!(before.freelist_count > 0, "{before:?}");
+
+    let report = maintenance::compact(&state).expect("compact");
+    assert_eq!(report.before_bytes, before.file_bytes);
+    assert!(report.after_bytes < report.before_bytes, "{report:?}");
+
+    let after = maintenance::db_stats(&state).expect("stats");
+    assert_eq!(after.freelist_count, 0);
+    assert_eq!(after.file_bytes, report.after_bytes);
+
+    // Pooled connections opened before the swap read the compacted database
+    let results = LookupService::new().search(&state, "人人", 0);
+    assert!(!results.is_empty());
+}
@@ -0,0 +1,136 @@
+mod fixtures;
+
+use axum::extract::{Path, State};
+use mangatan_yomitan_server::{
+    ServerState,
+    content_limits::{DEFAULT_MAX_DEPTH, TRUNCATED_MARKER, truncate_depth},
+    handlers, import,
+    lookup::LookupService,
+    state::AppState,
+};
+use serde_json::{Value, json};
+use wordbase_api::Record;
+
+fn dictionary_zip(terms: Value) -> Vec<u8> {
+    fixtures::dictionary_zip(fixtures::bare_index("Broken"), terms)
+}
+
+/// Structured content with `depth` nested `div`s around a bit of text.
+fn nested_content(depth: usize) -> Value {
+    let mut content = json!("深い");
+    for _ in 0..depth {
+        content = json!({ "tag": "div", "content": [content] });
+    }
+    json!({ "type": "structured-content", "content": content })
+}
+
+fn depth_of(value: &Value) -> usize {
+    match value {
+        Value::Array(items) => 1 + items.iter().map(depth_of).max().unwrap_or(0),
+        Value::Object(fields) => 1 + fields.values().map(depth_of).max().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// The stored glossary items of the first entry found for `term`.
+fn glossary(state: &AppState, term: &str) -> Vec<String> {
+    let results = LookupService::new().search(state, term, 0);
+    let entry = results.first().expect("entry found");
+    let Record::YomitanGlossary(glossary) = &entry.record else {
+        panic!("not a glossary: {:?}", entry.record);
+    };
+    serde_json::from_value(json!(glossary.content)).expect("string content")
+}
+
+#[test]
+fn deep_nesting_is_cut_at_the_limit() {
+    let mut value = nested_content(10);
+    assert!(truncate_depth(&mut value, 4));
+    assert_eq!(depth_of(&value), 4);
+    assert!(value.to_string().contains(TRUNCATED_MARKER));
+
+    let mut shallow = nested_content(1);
+    assert!(!truncate_depth(&mut shallow, 4));
+    assert_eq!(shallow, nested_content(1));
+}
+
+#[test]
+fn import_truncates_deeply_nested_entries_and_reports_them() {
+    let state = fixtures::scratch("limits-deep", |dir| AppState::new(dir).expect("state"));
+    let message = import::import_zip(
+        &state,
+        &dictionary_zip(json!([
+            ["深淵", "しんえん", "", "", 0, [nested_content(100)], 1, ""],
+            ["浅瀬", "あさせ", "", "", 0, [nested_content(3)], 2, ""],
+        ])),
+    )
+    .expect("import");
+    assert!(
+        message.contains("1 oversized entries truncated"),
+        "{message}"
+    );
+
+    let deep: Value = serde_json::from_str(&glossary(&state, "深淵")[0]).expect("json");
+    assert!(depth_of(&deep) <= DEFAULT_MAX_DEPTH);
+    assert!(deep.to_string().contains(TRUNCATED_MARKER));
+
+    let shallow: Value = serde_json::from_str(&glossary(&state, "浅瀬")[0]).expect("json");
+    assert_eq!(shallow, nested_content(3));
+}
+
+#[test]
+fn import_truncates_oversized_glossaries() {
+    let mut state = fixtures::scratch("limits-huge", |dir| AppState::new(dir).expect("state"));
+    state.content_limits.max_glossary_bytes = 1000;
+    let huge = "長".repeat(2000);
+    import::import_zip(
+        &state,
+        &dictionary_zip(json!([[
+            "長文",
+            "ちょうぶん",
+            "",
+            "",
+            0,
+            [huge, { "type": "structured-content", "content": "短い" }],
+            1,
+            ""
+        ]])),
+    )
+    .expect("import");
+
+    let items = glossary(&state, "長文");
+    assert!(items[0].len() <= 1000 + TRUNCATED_MARKER.len());
+    assert!(items[0].ends_with(TRUNCATED_MARKER));
+    assert!(items[1].contains("短い"));
+}
+
+#[tokio::test]
+async fn lookups_point_to_the_full_record() {
+    let mut server =
+        fixtures::scratch("limits-record", |dir| ServerState::new(dir).expect("state"));
+    server.app.content_limits.max_glossary_bytes = usize::MAX;
+    let long = "説明".repeat(5000);
+    import::import_zip(
+        &server.app,
+        &dictionary_zip(json!([["辞書", "じしょ", "", "", 0, [long], 1, ""]])),
+    )
+    .expect("import");
+
+    let results = LookupService::new().search(&server.app, "辞書", 0);
+    let record_id = results[0].record_id.0;
+
+    let (status, body) = handlers::get_record_handler(State(server.clone()), Path(record_id)).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["dictionaryName"], "Broken");
+    assert_eq!(body["reading"], "じしょ");
+    assert!(body["record"].to_string().contains(&"説明".repeat(5000)));
+}
+
+#[tokio::test]
+async fn unknown_records_are_not_found() {
+    let server = fixtures::scratch("limits-missing", |dir| {
+        ServerState::new(dir).expect("state")
+    });
+    let (status, _) = handlers::get_record_handler(State(server.clone()), Path(42)).await;
+    assert_eq!(status, 404);
+}
@@ -0,0 +1,101 @@
+mod fixtures;
+
+use fixtures::ScratchDir;
+use mangatan_yomitan_server::{import, state::AppState};
+use serde_json::json;
+
+fn attributed_dictionary() -> Vec<u8> {
+    let index = json!({
+        "title": "Mini JMdict",
+        "revision": "2024-01-01",
+        "format": 3,
+        "description": "A tiny JMdict",
+        "author": "EDRDG",
+        "attribution": "CC BY-SA 4.0, Electronic Dictionary Research and Development Group",
+        "url": "https://www.edrdg.org/",
+    });
+    fixtures::dictionary_zip_with(
+        index,
+        vec![
+            (
+                "term_bank_1.json",
+                json!([
+                    ["猫", "ねこ", "", "", 0, ["cat"], 1, ""],
+                    ["犬", "いぬ", "", "", 0, ["dog"], 2, ""],
+                ]),
+            ),
+            (
+                "kanji_bank_1.json",
+                json!([["猫", "ビョウ", "ねこ", "", ["cat"], {}]]),
+            ),
+            (
+                "term_meta_bank_1.json",
+                json!([
+                    ["猫", "freq", 1200],
+                    ["犬", "freq", 900],
+                    ["犬", "pitch", {}]
+                ]),
+            ),
+        ],
+    )
+}
+
+#[test]
+fn import_keeps_index_metadata_and_bank_counts_across_restarts() {
+    let dir = ScratchDir::new("info-import");
+    {
+        let state = AppState::new(dir.path().to_path_buf()).expect("state");
+        import::import_zip(&state, &attributed_dictionary()).expect("import");
+    }
+
+    let state = AppState::new(dir.path().to_path_buf()).expect("state");
+    let dicts = state.dictionaries.read().expect("lock");
+    let dict = dicts.values().next().expect("imported dictionary");
+    assert_eq!(dict.info.description.as_deref(), Some("A tiny JMdict"));
+    assert_eq!(dict.info.author.as_deref(), Some("EDRDG"));
+    assert!(
+        dict.info
+            .attribution
+            .as_deref()
+            .is_some_and(|a| a.starts_with("CC BY-SA"))
+    );
+    assert_eq!(dict.info.url.as_deref(), Some("https://www.edrdg.org/"));
+    assert!(dict.info.imported_at.is_some_and(|at| at > 0));
+    assert_eq!(dict.info.term_count, Some(2));
+    assert_eq!(dict.info.kanji_count, Some(1));
+    assert_eq!(dict.info.meta_count, Some(3));
+
+    let listed = serde_json::to_value(dict).expect("serialize");
+    assert_eq!(listed["author"], "EDRDG");
+    assert_eq!(listed["term_count"], 2);
+}
+
+#[test]
+fn databases_from_older_versions_gain_the_columns() {
+    let dir = ScratchDir::new("info-migrate");
+    {
+        let conn = rusqlite::Connection::open(dir.path().join("yomitan.db")).expect("open");
+        conn.execute_batch(
+            "CREATE TABLE dictionaries (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                priority INTEGER DEFAULT 0,
+                enabled BOOLEAN DEFAULT 1
+             );
+             INSERT INTO dictionaries (id, name, priority, enabled) VALUES (1, 'Old', 0, 1);",
+        )
+        .expect("old schema");
+    }
+
+    let state = AppState::new(dir.path().to_path_buf()).expect("state");
+    {
+        let dicts = state.dictionaries.read().expect("lock");
+        let old = dicts.values().next().expect("old dictionary");
+        assert_eq!(old.name, "Old");
+        assert_eq!(old.info.imported_at, None);
+        assert_eq!(old.info.term_count, None);
+    }
+
+    import::import_zip(&state, &attributed_dictionary()).expect("import after migration");
+    assert_eq!(state.dictionaries.read().expect("lock").len(), 2);
+}
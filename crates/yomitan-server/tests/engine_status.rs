@@ -0,0 +1,81 @@
+mod fixtures;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+};
+use fixtures::Scratch;
+use mangatan_yomitan_server::{
+    ServerState,
+    engine::{EngineStatus, LookupEngine},
+    handlers::{self, LookupParams},
+    import,
+    lookup::LookupService,
+    state::StateConfig,
+};
+
+fn pending_server(name: &str) -> Scratch<ServerState> {
+    let server = fixtures::scratch(name, |dir| ServerState {
+        lookup: LookupEngine::pending(),
+        ..ServerState::with_config(dir, StateConfig::default()).expect("state")
+    });
+    import::import_zip(&server.app, &fixtures::bilingual()).expect("import");
+    server
+}
+
+async fn lookup_status(server: &ServerState) -> (StatusCode, serde_json::Value) {
+    let params = LookupParams {
+        text: "食べ物".to_string(),
+        index: Some(0),
+        dictionaries: None,
+        dictionary_ids: None,
+        scan_mode: None,
+    };
+    match handlers::lookup_handler(State(server.clone()), Query(params)).await {
+        Ok(response) => (response.status(), serde_json::Value::Null),
+        Err((status, body)) => (status, body.0),
+    }
+}
+
+#[tokio::test]
+async fn lookups_wait_for_the_engine() {
+    let server = pending_server("engine-pending");
+    let status = handlers::engine_status_handler(State(server.clone())).await;
+    assert_eq!(status["ready"], false);
+    assert_eq!(status["status"]["state"], "loading");
+
+    let (code, body) = lookup_status(&server).await;
+    assert_eq!(code, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"], "loading");
+
+    server.lookup.clone().load(LookupService::default).await;
+    let status = handlers::engine_status_handler(State(server.clone())).await;
+    assert_eq!(status["ready"], true);
+    assert_eq!(status["status"]["state"], "ready");
+    assert_eq!(lookup_status(&server).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn a_failed_load_is_reported() {
+    let server = pending_server("engine-failed");
+    server
+        .lookup
+        .clone()
+        .load(|| panic!("UniDic is missing"))
+        .await;
+
+    assert!(matches!(
+        server.lookup.status(),
+        EngineStatus::Failed { error } if error.contains("UniDic is missing")
+    ));
+    let (code, body) = lookup_status(&server).await;
+    assert_eq!(code, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"], "engine_failed");
+}
+
+#[test]
+fn engines_built_up_front_are_ready() {
+    let engine = LookupEngine::from(LookupService::default());
+    assert!(engine.get().is_some());
+    assert_eq!(engine.status(), EngineStatus::Ready { load_ms: 0 });
+}
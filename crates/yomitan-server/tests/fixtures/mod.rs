@@ -0,0 +1,279 @@
+//! Tiny handcrafted Yomitan dictionaries, built as zips at test time, the golden files lookups
+//! over them are compared against, and the scratch dirs tests keep their databases in.
+//!
+//! After an intended change in lookup output, rerun the tests with `MANGATAN_UPDATE_GOLDEN=1`
+//! to rewrite `golden/*.json`, and review the diff like any other change.
+
+// Each test crate uses a different part of the kit
+#![allow(dead_code)]
+
+use std::{
+    io::{Cursor, Write},
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Value, json};
+
+/// An empty directory under the system temp dir, removed on drop.
+pub struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    /// `name` has to be unique across the test crates, which run side by side.
+    pub fn new(name: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("mangatan-yomitan-{name}-{}", std::process::id()));
+        // Left over from a run that was killed before it could clean up
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("data dir");
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A state opened in its own [`ScratchDir`], which outlives it; derefs to the state.
+pub struct Scratch<T> {
+    // Dropped before the dir it points into
+    value: T,
+    pub dir: ScratchDir,
+}
+
+impl<T> Deref for Scratch<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Scratch<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+/// Opens a state, or anything else that keeps files, in a fresh [`ScratchDir`].
+pub fn scratch<T>(name: &str, open: impl FnOnce(PathBuf) -> T) -> Scratch<T> {
+    let dir = ScratchDir::new(name);
+    Scratch {
+        value: open(dir.path().to_path_buf()),
+        dir,
+    }
+}
+
+/// A dictionary with a single term bank.
+pub fn dictionary_zip(index: Value, terms: Value) -> Vec<u8> {
+    dictionary_zip_with(index, vec![("term_bank_1.json", terms)])
+}
+
+/// A dictionary with any mix of banks, each a `(file name, contents)` pair.
+pub fn dictionary_zip_with(index: Value, banks: Vec<(&str, Value)>) -> Vec<u8> {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default();
+    for (name, content) in std::iter::once(("index.json", index)).chain(banks) {
+        zip.start_file(name, options).expect("zip entry");
+        zip.write_all(content.to_string().as_bytes())
+            .expect("zip write");
+    }
+    zip.finish().expect("zip finish").into_inner()
+}
+
+/// An index with only the required fields, like most dictionaries in the wild.
+pub fn bare_index(title: &str) -> Value {
+    json!({ "title": title, "revision": "1", "format": 3 })
+}
+
+fn index(title: &str) -> Value {
+    json!({
+        "title": title,
+        "revision": "fixture-1",
+        "format": 3,
+        "sourceLanguage": "ja",
+        "author": "Mangatan tests",
+    })
+}
+
+/// Japanese-English glossary: verbs to deinflect, homographs, iteration marks, a kana-only word.
+pub fn bilingual() -> Vec<u8> {
+    dictionary_zip_with(
+        index("Fixture Bilingual"),
+        vec![
+            (
+                "term_bank_1.json",
+                json!([
+                    ["食べる", "たべる", "v1 vt", "v1", 100, ["to eat"], 1, "P"],
+                    ["食べ物", "たべもの", "n", "", 80, ["food"], 2, "P"],
+                    [
+                        "見る",
+                        "みる",
+                        "v1 vt",
+                        "v1",
+                        90,
+                        ["to see", "to look"],
+                        3,
+                        "P"
+                    ],
+                    ["行く", "いく", "v5k-s vi", "v5", 95, ["to go"], 4, "P"],
+                    ["生", "なま", "n adj-no", "", 10, ["raw"], 5, ""],
+                    ["生", "せい", "n", "", 30, ["life"], 6, ""],
+                    ["心", "こころ", "n", "", 70, ["heart", "mind"], 7, "P"],
+                    ["時時", "ときどき", "adv", "", 60, ["sometimes"], 8, ""],
+                    ["する", "", "vs-i", "vs", 99, ["to do"], 9, "P"],
+                ]),
+            ),
+            (
+                "kanji_bank_1.json",
+                json!([["食", "ショク", "た.べる", "", ["eat"], {}]]),
+            ),
+        ],
+    )
+}
+
+/// Frequency list in the meta bank format, with and without readings.
+pub fn frequency() -> Vec<u8> {
+    dictionary_zip_with(
+        index("Fixture Frequency"),
+        vec![(
+            "term_meta_bank_1.json",
+            json!([
+                ["食べる", "freq", 120],
+                ["見る", "freq", { "value": 80, "displayValue": "80㋕" }],
+                ["生", "freq", { "reading": "せい", "frequency": 900 }],
+                ["生", "freq", { "reading": "なま", "frequency": 2400 }],
+            ]),
+        )],
+    )
+}
+
+/// Pitch accents; nothing but meta bank entries.
+pub fn pitch() -> Vec<u8> {
+    dictionary_zip_with(
+        index("Fixture Pitch"),
+        vec![(
+            "term_meta_bank_1.json",
+            json!([
+                ["食べる", "pitch", { "reading": "たべる", "pitches": [{ "position": 2 }] }],
+                [
+                    "心",
+                    "pitch",
+                    { "reading": "こころ", "pitches": [{ "position": 2 }, { "position": 3 }] }
+                ],
+            ]),
+        )],
+    )
+}
+
+/// Structured-content definitions, sharing 食べる with [`bilingual`] for priority tests.
+pub fn structured() -> Vec<u8> {
+    let definition = |gloss: &str| {
+        json!({
+            "type": "structured-content",
+            "content": [
+                { "tag": "span", "data": { "content": "part-of-speech" }, "content": "動詞" },
+                { "tag": "ul", "content": [{ "tag": "li", "content": gloss }] },
+            ],
+        })
+    };
+    dictionary_zip_with(
+        index("Fixture Structured"),
+        vec![(
+            "term_bank_1.json",
+            json!([
+                [
+                    "食べる",
+                    "たべる",
+                    "",
+                    "v1",
+                    5,
+                    [definition("食物を口に入れる")],
+                    1,
+                    ""
+                ],
+                [
+                    "見物",
+                    "けんぶつ",
+                    "n vs",
+                    "",
+                    5,
+                    [definition("見て楽しむこと")],
+                    2,
+                    ""
+                ],
+            ]),
+        )],
+    )
+}
+
+/// Headwords written with a variation selector (葛󠄀城), a compatibility ideograph (神 as
+/// U+FA19) and an ideograph outside the BMP (𠮟る). Not part of [`all`].
+pub fn variants() -> Vec<u8> {
+    dictionary_zip_with(
+        index("Fixture Variants"),
+        vec![(
+            "term_bank_1.json",
+            json!([
+                [
+                    "葛\u{E0100}城",
+                    "かつらぎ",
+                    "n",
+                    "",
+                    10,
+                    ["Katsuragi"],
+                    1,
+                    ""
+                ],
+                ["\u{FA19}社", "じんじゃ", "n", "", 20, ["shrine"], 2, ""],
+                ["𠮟る", "しかる", "v5r vt", "v5", 30, ["to scold"], 3, ""],
+            ]),
+        )],
+    )
+}
+
+/// All four, in import order.
+pub fn all() -> Vec<(&'static str, Vec<u8>)> {
+    vec![
+        ("bilingual", bilingual()),
+        ("frequency", frequency()),
+        ("pitch", pitch()),
+        ("structured", structured()),
+    ]
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/golden")
+        .join(format!("{name}.json"))
+}
+
+/// Compares `actual` with `golden/<name>.json`, or rewrites the file when
+/// `MANGATAN_UPDATE_GOLDEN` is set.
+pub fn assert_golden(name: &str, actual: &Value) {
+    let path = golden_path(name);
+    let pretty = serde_json::to_string_pretty(actual).expect("serialize") + "\n";
+    if std::env::var_os("MANGATAN_UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().expect("golden dir")).expect("create golden dir");
+        std::fs::write(&path, pretty).expect("write golden");
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!(
+            "{}: {e}; run with MANGATAN_UPDATE_GOLDEN=1 to create it",
+            path.display()
+        )
+    });
+    let expected: Value = serde_json::from_str(&expected).expect("golden json");
+    assert!(
+        &expected == actual,
+        "lookup output differs from {}; run with MANGATAN_UPDATE_GOLDEN=1 to update it\nactual:\n{pretty}",
+        path.display()
+    );
+}
@@ -0,0 +1,133 @@
+mod fixtures;
+
+use mangatan_yomitan_server::{
+    frequency::{self, CsvOptions},
+    import,
+    lookup::LookupService,
+    state::AppState,
+};
+use serde_json::json;
+use wordbase_api::{FrequencyValue, Record, Term};
+
+#[test]
+fn malformed_rows_are_skipped_and_duplicates_keep_the_best_rank() {
+    let csv = "\u{feff}word,rank\n\
+               猫,120\n\
+               \"犬\",45\n\
+               猫,80\n\
+               鳥\n\
+               魚,often\n\
+               \n\
+               \"a \"\"quoted\"\" word, with comma\",7\n";
+    let list = frequency::parse_frequency_csv(
+        csv,
+        &CsvOptions {
+            has_header: true,
+            ..CsvOptions::default()
+        },
+    );
+
+    let rows: Vec<(&str, i64)> = list
+        .rows
+        .iter()
+        .map(|row| (row.term.as_str(), row.rank))
+        .collect();
+    assert_eq!(
+        rows,
+        [("猫", 80), ("犬", 45), ("a \"quoted\" word, with comma", 7)]
+    );
+    assert_eq!(list.skipped, 2);
+    assert_eq!(list.duplicates, 1);
+}
+
+#[test]
+fn tsv_columns_are_configurable() {
+    let tsv = "1\tする\t\n2\t言う\tいう\n3\t生\tせい\n3\t生\tなま\n";
+    let list = frequency::parse_frequency_csv(
+        tsv,
+        &CsvOptions {
+            term_column: 1,
+            reading_column: Some(2),
+            rank_column: 0,
+            ..CsvOptions::default()
+        },
+    );
+
+    let rows: Vec<(&str, Option<&str>, i64)> = list
+        .rows
+        .iter()
+        .map(|row| (row.term.as_str(), row.reading.as_deref(), row.rank))
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            ("する", None, 1),
+            ("言う", Some("いう"), 2),
+            ("生", Some("せい"), 3),
+            ("生", Some("なま"), 3),
+        ]
+    );
+    assert_eq!((list.skipped, list.duplicates), (0, 0));
+}
+
+#[test]
+fn imported_ranks_sort_and_annotate_lookups() {
+    let state = fixtures::scratch("freq-csv", |dir| AppState::new(dir).expect("state"));
+    import::import_zip(
+        &state,
+        &fixtures::dictionary_zip(
+            fixtures::bare_index("Mini JMdict"),
+            json!([
+                ["生", "なま", "", "", 0, ["raw"], 1, ""],
+                ["生", "せい", "", "", 0, ["life"], 2, ""],
+            ]),
+        ),
+    )
+    .expect("import dictionary");
+
+    let imported = frequency::import_frequency_csv(
+        &state,
+        "My corpus",
+        None,
+        "生,なま,900\n生,せい,300\n".as_bytes(),
+        &CsvOptions {
+            reading_column: Some(1),
+            rank_column: 2,
+            ..CsvOptions::default()
+        },
+    )
+    .expect("import frequency list");
+    assert_eq!(imported.terms, 2);
+    assert!(
+        state
+            .dictionaries
+            .read()
+            .expect("lock")
+            .values()
+            .any(|d| d.name == "My corpus" && d.enabled)
+    );
+
+    let results = LookupService::new().search(&state, "生", 0);
+    // The list's rows rank the definitions instead of showing up as definitions of their own
+    assert!(results.iter().all(|entry| match &entry.record {
+        Record::YomitanGlossary(g) => !g.content.is_empty(),
+        _ => true,
+    }));
+    let ranked: Vec<(String, Option<FrequencyValue>)> = results
+        .iter()
+        .map(|entry| {
+            let reading = match &entry.term {
+                Term::Full(_, r) | Term::Reading(r) => r.to_string(),
+                Term::Headword(h) => h.to_string(),
+            };
+            (reading, entry.profile_sorting_frequency.clone())
+        })
+        .collect();
+    assert_eq!(
+        ranked,
+        [
+            ("せい".to_string(), Some(FrequencyValue::Rank(300))),
+            ("なま".to_string(), Some(FrequencyValue::Rank(900))),
+        ]
+    );
+}
@@ -0,0 +1,252 @@
+mod fixtures;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use fixtures::Scratch;
+use mangatan_yomitan_server::{
+    ServerState,
+    handlers::{self, DictionaryAction, LookupParams},
+    import,
+    lookup::LookupService,
+    state::StateConfig,
+};
+use serde_json::{Value, json};
+
+/// A server with every fixture imported, configured without the environment. Deinflection
+/// is off, so the goldens don't depend on the UniDic build Lindera was compiled with.
+fn server(name: &str) -> Scratch<ServerState> {
+    let mut server = deinflecting_server(name);
+    server.lookup = LookupService::default()
+        .with_max_deinflection_depth(0)
+        .into();
+    server
+}
+
+fn deinflecting_server(name: &str) -> Scratch<ServerState> {
+    let server = fixtures::scratch(name, |dir| {
+        ServerState::with_config(dir, StateConfig::default()).expect("state")
+    });
+    for (fixture, zip) in fixtures::all() {
+        import::import_zip(&server.app, &zip).unwrap_or_else(|e| panic!("import {fixture}: {e}"));
+    }
+    server
+}
+
+async fn lookup(server: &ServerState, text: &str) -> Value {
+    let params = LookupParams {
+        text: text.to_string(),
+        index: Some(0),
+        dictionaries: None,
+        dictionary_ids: None,
+        scan_mode: None,
+    };
+    let response = handlers::lookup_handler(State(server.clone()), Query(params))
+        .await
+        .expect("lookup");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    serde_json::from_slice(&body).expect("json")
+}
+
+/// `{ text: results }` for each of `texts`.
+async fn battery(server: &ServerState, texts: &[&str]) -> Value {
+    let mut results = serde_json::Map::new();
+    for text in texts {
+        results.insert(text.to_string(), lookup(server, text).await);
+    }
+    Value::Object(results)
+}
+
+/// Names of the dictionaries with definitions for `text`, and the warning header if any.
+async fn sources(
+    server: &ServerState,
+    text: &str,
+    dictionaries: Option<String>,
+    dictionary_ids: Option<String>,
+) -> (Vec<String>, Option<String>) {
+    let params = LookupParams {
+        text: text.to_string(),
+        index: Some(0),
+        dictionaries,
+        dictionary_ids,
+        scan_mode: None,
+    };
+    let response = handlers::lookup_handler(State(server.clone()), Query(params))
+        .await
+        .expect("lookup");
+    let warning = response
+        .headers()
+        .get(handlers::WARNING_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    let results: Value = serde_json::from_slice(&body).expect("json");
+    let mut names: Vec<String> = results
+        .as_array()
+        .expect("results")
+        .iter()
+        .flat_map(|group| group["definitions"].as_array().cloned().unwrap_or_default())
+        .filter_map(|definition| definition["dictionaryName"].as_str().map(str::to_string))
+        .collect();
+    names.sort();
+    names.dedup();
+    (names, warning)
+}
+
+fn dictionary_id(server: &ServerState, name: &str) -> i64 {
+    let dicts = server.app.dictionaries.read().expect("lock");
+    dicts
+        .values()
+        .find(|d| d.name == name)
+        .unwrap_or_else(|| panic!("{name} not imported"))
+        .id
+        .0
+}
+
+#[test]
+fn fixtures_import_with_their_bank_counts() {
+    let server = server("import");
+    let mut dicts: Vec<Value> = server
+        .app
+        .dictionaries
+        .read()
+        .expect("lock")
+        .values()
+        .map(|d| {
+            json!({
+                "id": d.id.0,
+                "name": d.name,
+                "language": d.language,
+                "revision": d.revision,
+                "priority": d.priority,
+                "enabled": d.enabled,
+                "termCount": d.info.term_count,
+                "kanjiCount": d.info.kanji_count,
+                "metaCount": d.info.meta_count,
+            })
+        })
+        .collect();
+    dicts.sort_by_key(|d| d["id"].as_i64());
+    fixtures::assert_golden("dictionaries", &json!(dicts));
+}
+
+#[tokio::test]
+async fn conjugated_forms_find_their_dictionary_form() {
+    let deinflecting = deinflecting_server("conjugation");
+    // Spans and extra matches vary with the UniDic build, so only the top entry is compared
+    for (text, dictionary_form) in [
+        ("食べた", "食べる"),
+        ("食べられなかった", "食べる"),
+        ("見ました", "見る"),
+        ("行って", "行く"),
+    ] {
+        let results = lookup(&deinflecting, text).await;
+        assert_eq!(results[0]["headword"], dictionary_form, "{text}: {results}");
+    }
+
+    // Without deinflection only the surface forms match
+    let surface = lookup(&server("surface"), "食べた").await;
+    assert_eq!(surface, json!([]));
+}
+
+#[tokio::test]
+async fn iteration_marks_and_kana_are_normalized() {
+    let server = server("normalization");
+    let results = battery(&server, &["時々", "こゝろ", "心", "こころ", "タベル"]).await;
+    fixtures::assert_golden("normalization", &results);
+}
+
+#[tokio::test]
+async fn dictionary_priority_orders_definitions() {
+    let server = server("priority");
+    let before = lookup(&server, "食べる").await;
+
+    let order = vec![
+        dictionary_id(&server, "Fixture Structured"),
+        dictionary_id(&server, "Fixture Bilingual"),
+        dictionary_id(&server, "Fixture Frequency"),
+        dictionary_id(&server, "Fixture Pitch"),
+    ];
+    let Json(response) = handlers::manage_dictionaries_handler(
+        State(server.clone()),
+        Json(DictionaryAction::Reorder { order }),
+    )
+    .await;
+    assert_eq!(response["status"], "ok", "{response}");
+    let after = lookup(&server, "食べる").await;
+
+    fixtures::assert_golden(
+        "priorities",
+        &json!({ "imported": before, "structuredFirst": after }),
+    );
+}
+
+#[tokio::test]
+async fn homographs_and_readings_are_grouped_once() {
+    let server = server("dedupe");
+    let results = battery(&server, &["生", "なま", "たべる", "食べ物", "する"]).await;
+    fixtures::assert_golden("dedupe", &results);
+
+    // Rows found through the headword and the reading of an entry end up in a single group
+    for (text, groups) in results.as_object().expect("battery") {
+        let mut keys: Vec<(&Value, &Value)> = groups
+            .as_array()
+            .expect("results")
+            .iter()
+            .map(|group| (&group["headword"], &group["reading"]))
+            .collect();
+        let found = keys.len();
+        keys.sort_by_key(|(h, r)| (h.to_string(), r.to_string()));
+        keys.dedup();
+        assert_eq!(keys.len(), found, "{text}: {groups}");
+    }
+}
+
+#[tokio::test]
+async fn dictionary_ids_search_disabled_dictionaries_too() {
+    let server = server("dictionary-ids");
+    let structured = dictionary_id(&server, "Fixture Structured");
+    let bilingual = dictionary_id(&server, "Fixture Bilingual");
+    let Json(response) = handlers::manage_dictionaries_handler(
+        State(server.clone()),
+        Json(DictionaryAction::Toggle {
+            id: structured,
+            enabled: false,
+        }),
+    )
+    .await;
+    assert_eq!(response["status"], "ok", "{response}");
+
+    let (all, _) = sources(&server, "食べる", None, None).await;
+    assert!(!all.contains(&"Fixture Structured".to_string()), "{all:?}");
+    // `dictionaries` only narrows the enabled set
+    let (narrowed, _) = sources(&server, "食べる", Some(structured.to_string()), None).await;
+    assert!(narrowed.is_empty(), "{narrowed:?}");
+
+    let (only, warning) = sources(&server, "食べる", None, Some(structured.to_string())).await;
+    assert_eq!(only, ["Fixture Structured"]);
+    assert_eq!(warning, None);
+
+    // It takes precedence over `dictionaries`, and unknown ids are reported the same way
+    let (only, warning) = sources(
+        &server,
+        "食べる",
+        Some(bilingual.to_string()),
+        Some(format!("{structured},999")),
+    )
+    .await;
+    assert_eq!(only, ["Fixture Structured"]);
+    assert_eq!(
+        warning.as_deref(),
+        Some("Ignored unknown dictionary ids: 999")
+    );
+
+    // The global flags are left alone
+    let (after, _) = sources(&server, "食べる", None, None).await;
+    assert_eq!(after, all);
+}
@@ -0,0 +1,70 @@
+mod fixtures;
+
+use std::io::{Cursor, Write};
+
+use mangatan_yomitan_server::{
+    import,
+    state::{AppState, StateConfig},
+};
+use serde_json::{Value, json};
+
+/// A dictionary big enough to be split across several import threads.
+fn large_dictionary() -> Vec<u8> {
+    let index = json!({ "title": "Large", "revision": "1", "format": 3 });
+    let terms: Vec<Value> = (0..3000)
+        .map(|n| match n % 3 {
+            0 => json!([
+                format!("語{n}"),
+                format!("ご{n}"),
+                "n",
+                "",
+                n,
+                [format!("word {n}")],
+                n,
+                ""
+            ]),
+            1 => json!([format!("語{n}"), "", "", "", 0, [{ "type": "text", "text": "x" }], n, ""]),
+            // Skipped without a headword
+            _ => json!(["", format!("ご{n}"), "", "", 0, ["nothing"], n, ""]),
+        })
+        .collect();
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default();
+    for (name, content) in [("index.json", index), ("term_bank_1.json", json!(terms))] {
+        zip.start_file(name, options).expect("zip entry");
+        zip.write_all(content.to_string().as_bytes())
+            .expect("zip write");
+    }
+    zip.finish().expect("zip finish").into_inner()
+}
+
+/// Every term row in insertion order.
+fn term_rows(threads: usize) -> Vec<(String, Vec<u8>)> {
+    let config = StateConfig {
+        import_threads: threads,
+        ..StateConfig::default()
+    };
+    let state = fixtures::scratch(&format!("threads-{threads}"), |dir| {
+        AppState::with_config(dir, config).expect("state")
+    });
+    import::import_zip(&state, &large_dictionary()).expect("import");
+
+    let conn = state.pool.get().expect("connection");
+    let mut stmt = conn
+        .prepare("SELECT term, json FROM terms ORDER BY rowid")
+        .expect("prepare");
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .expect("query")
+        .collect::<Result<_, _>>()
+        .expect("rows")
+}
+
+#[test]
+fn threaded_imports_store_the_same_rows_in_the_same_order() {
+    let single = term_rows(1);
+    // 2000 headwords, plus a reading row for each of the 1000 that have one
+    assert_eq!(single.len(), 3000);
+    assert_eq!(single[0].0, "語0");
+    assert_eq!(single[1].0, "ご0");
+    assert_eq!(term_rows(4), single);
+}
@@ -0,0 +1,111 @@
+mod fixtures;
+
+use std::collections::HashSet;
+
+use fixtures::Scratch;
+use mangatan_yomitan_server::{import, lookup::LookupService, state::AppState};
+use serde_json::json;
+use wordbase_api::DictionaryId;
+
+/// A CC-CEDICT style dictionary: pinyin readings, `sourceLanguage: zh`.
+fn chinese_dictionary() -> Vec<u8> {
+    fixtures::dictionary_zip(
+        json!({ "title": "Mini CEDICT", "revision": "1", "format": 3, "sourceLanguage": "zh" }),
+        json!([
+            ["中国", "zhong1 guo2", "", "", 0, ["China"], 1, ""],
+            [
+                "中国人",
+                "zhong1 guo2 ren2",
+                "",
+                "",
+                0,
+                ["Chinese person"],
+                2,
+                ""
+            ],
+            ["人人", "ren2 ren2", "", "", 0, ["everyone"], 3, ""],
+        ]),
+    )
+}
+
+/// A Japanese dictionary without `sourceLanguage`, like most existing ones.
+fn japanese_dictionary() -> Vec<u8> {
+    fixtures::dictionary_zip(
+        json!({ "title": "Mini JMdict", "revision": "1", "format": 3 }),
+        json!([["人人", "ひとびと", "", "", 0, ["people"], 1, ""]]),
+    )
+}
+
+fn state_with_both(name: &str) -> (Scratch<AppState>, DictionaryId, DictionaryId) {
+    let state = fixtures::scratch(&format!("language-{name}"), |dir| {
+        AppState::new(dir).expect("state")
+    });
+
+    import::import_zip(&state, &chinese_dictionary()).expect("zh import");
+    import::import_zip(&state, &japanese_dictionary()).expect("ja import");
+
+    let id_of = |title: &str| {
+        let dicts = state.dictionaries.read().expect("lock");
+        dicts
+            .values()
+            .find(|d| d.name == title)
+            .map(|d| d.id)
+            .expect("imported dictionary")
+    };
+    let (zh, ja) = (id_of("Mini CEDICT"), id_of("Mini JMdict"));
+    (state, zh, ja)
+}
+
+#[test]
+fn import_reads_source_language() {
+    let (state, zh, ja) = state_with_both("import");
+    let dicts = state.dictionaries.read().expect("lock");
+    assert_eq!(dicts[&zh].language.as_deref(), Some("zh"));
+    assert_eq!(dicts[&ja].language, None);
+}
+
+#[test]
+fn chinese_dictionary_uses_longest_prefix() {
+    let (state, zh, _) = state_with_both("prefix");
+    let results = LookupService::new().search(&state, "中国人很多", 0);
+
+    let matches: Vec<_> = results
+        .iter()
+        .map(|r| (r.source, r.span_chars.end))
+        .collect();
+    assert_eq!(matches, [(zh, 3), (zh, 2)]);
+}
+
+#[test]
+fn japanese_rewrites_do_not_reach_chinese_dictionaries() {
+    let (state, zh, ja) = state_with_both("merge");
+    // 人々 only matches 人人 through the (Japanese) iteration mark rewrite
+    let results = LookupService::new().search(&state, "人々", 0);
+
+    let sources: Vec<_> = results.iter().map(|r| r.source).collect();
+    assert!(sources.contains(&ja), "{sources:?}");
+    assert!(!sources.contains(&zh), "{sources:?}");
+}
+
+#[test]
+fn dictionary_subset_limits_sources() {
+    let (state, zh, ja) = state_with_both("subset");
+    let lookup = LookupService::new();
+
+    for id in [zh, ja] {
+        let subset = HashSet::from([id]);
+        let sources: Vec<_> = lookup
+            .search_in(&state, "人人", 0, Some(&subset))
+            .iter()
+            .map(|r| r.source)
+            .collect();
+        assert!(!sources.is_empty());
+        assert!(sources.iter().all(|source| *source == id), "{sources:?}");
+    }
+    // An empty selection matches nothing rather than everything
+    assert!(
+        lookup
+            .search_in(&state, "人人", 0, Some(&HashSet::new()))
+            .is_empty()
+    );
+}
@@ -0,0 +1,107 @@
+mod fixtures;
+
+use axum::{Json, extract::State, http::StatusCode};
+use fixtures::Scratch;
+use mangatan_yomitan_server::{
+    ServerState, handlers, import,
+    state::{AppState, StateConfig},
+};
+use serde_json::{Value, json};
+
+fn server(name: &str) -> Scratch<ServerState> {
+    let server = fixtures::scratch(name, |dir| {
+        ServerState::with_config(dir, StateConfig::default()).expect("state")
+    });
+    import::import_zip(&server.app, &fixtures::bilingual()).expect("import");
+    import::import_zip(&server.app, &fixtures::variants()).expect("import");
+    server
+}
+
+fn source_ids(state: &AppState) -> Vec<i64> {
+    let dicts = state.dictionaries.read().expect("lock");
+    let mut ids: Vec<i64> = dicts.keys().map(|id| id.0).collect();
+    ids.sort_unstable();
+    ids
+}
+
+/// `(rowid, dictionary_id)` of every term row.
+fn rows(state: &AppState) -> Vec<(i64, i64)> {
+    let conn = state.pool.get().expect("connection");
+    let mut stmt = conn
+        .prepare("SELECT rowid, dictionary_id FROM terms ORDER BY rowid")
+        .expect("prepare");
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .expect("query")
+        .collect::<Result<_, _>>()
+        .expect("rows")
+}
+
+async fn merge(server: &ServerState, ids: Vec<i64>) -> (StatusCode, Value) {
+    let req = serde_json::from_value(json!({ "source_ids": ids, "target_name": "Merged" }))
+        .expect("request");
+    let (status, Json(body)) =
+        handlers::merge_dictionaries_handler(State(server.clone()), Json(req)).await;
+    (status, body)
+}
+
+#[tokio::test]
+async fn merged_rows_keep_their_ids() {
+    let server = server("merge");
+    let ids = source_ids(&server.app);
+    let before = rows(&server.app);
+    let next_id = *server.app.next_dict_id.read().expect("lock");
+
+    let (status, body) = merge(&server, ids).await;
+    assert_eq!(
+        (status, body["status"].as_str()),
+        (StatusCode::OK, Some("ok"))
+    );
+
+    let after = rows(&server.app);
+    assert_eq!(after.len(), before.len());
+    assert!(after.iter().all(|(_, dict)| *dict == next_id), "{after:?}");
+    let rowids = |rows: &[(i64, i64)]| rows.iter().map(|(rowid, _)| *rowid).collect::<Vec<_>>();
+    assert_eq!(rowids(&after), rowids(&before));
+    assert_eq!(source_ids(&server.app), [next_id]);
+    assert_eq!(*server.app.next_dict_id.read().expect("lock"), next_id + 1);
+}
+
+#[tokio::test]
+async fn a_failed_merge_changes_nothing() {
+    let server = server("merge-failed");
+    let ids = source_ids(&server.app);
+    let next_id = *server.app.next_dict_id.read().expect("lock");
+    {
+        // A record that can't be decompressed stops the merge partway through
+        let conn = server.app.pool.get().expect("connection");
+        conn.execute(
+            "UPDATE terms SET json = x'ff' WHERE rowid = (SELECT max(rowid) FROM terms)",
+            [],
+        )
+        .expect("damage a row");
+    }
+    let before = rows(&server.app);
+
+    let (status, body) = merge(&server, ids.clone()).await;
+    assert_eq!(
+        (status, body["status"].as_str()),
+        (StatusCode::OK, Some("error"))
+    );
+    assert_eq!(rows(&server.app), before);
+    assert_eq!(source_ids(&server.app), ids);
+    assert_eq!(*server.app.next_dict_id.read().expect("lock"), next_id);
+}
+
+#[test]
+fn merging_waits_for_no_running_write() {
+    let server = server("merge-busy");
+    let ids = source_ids(&server.app);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("runtime");
+    let _import = server.app.import_lock.lock().expect("lock");
+
+    let (status, _) = runtime.block_on(merge(&server, ids.clone()));
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(source_ids(&server.app), ids);
+}
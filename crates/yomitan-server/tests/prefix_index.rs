@@ -0,0 +1,138 @@
+mod fixtures;
+
+use axum::extract::{Query, State};
+use fixtures::Scratch;
+use mangatan_yomitan_server::{
+    ServerState,
+    handlers::{self, PrefixIndexParams},
+    import,
+    maintenance::{self, PrefixIndex},
+    state::StateConfig,
+};
+
+fn server(name: &str) -> Scratch<ServerState> {
+    let server = fixtures::scratch(name, |dir| {
+        ServerState::with_config(dir, StateConfig::default()).expect("state")
+    });
+    import::import_zip(&server.app, &fixtures::bilingual()).expect("import");
+    server
+}
+
+/// The `EXPLAIN QUERY PLAN` details for `sql`.
+fn plan(server: &ServerState, sql: &str) -> String {
+    let conn = server.app.pool.get().expect("connection");
+    let mut stmt = conn
+        .prepare(&format!("EXPLAIN QUERY PLAN {sql}"))
+        .expect("prepare");
+    let details: Vec<String> = stmt
+        .query_map([], |row| row.get::<_, String>(3))
+        .expect("query")
+        .collect::<Result<_, _>>()
+        .expect("rows");
+    details.join("; ")
+}
+
+fn prefix_matches(server: &ServerState, sql: &str) -> Vec<String> {
+    let conn = server.app.pool.get().expect("connection");
+    let mut stmt = conn.prepare(sql).expect("prepare");
+    let mut terms: Vec<String> = stmt
+        .query_map([], |row| row.get(0))
+        .expect("query")
+        .collect::<Result<_, _>>()
+        .expect("rows");
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+async fn build(server: &ServerState, max_term_length: Option<usize>) -> serde_json::Value {
+    let (status, body) = handlers::build_prefix_index_handler(
+        State(server.clone()),
+        Query(PrefixIndexParams { max_term_length }),
+    )
+    .await;
+    assert_eq!(status, 200, "{body:?}");
+    body.0
+}
+
+const PREFIX_QUERY: &str = "SELECT term FROM terms WHERE term LIKE '食べ%'";
+
+#[tokio::test]
+async fn prefix_searches_use_the_index_once_built() {
+    let server = server("prefix-index");
+    assert_eq!(maintenance::prefix_index(&server.app), Ok(None));
+    assert!(
+        !plan(&server, PREFIX_QUERY).contains("idx_term_nocase"),
+        "not built at startup"
+    );
+
+    let body = build(&server, None).await;
+    assert_eq!(body["max_term_length"], serde_json::Value::Null);
+    assert_eq!(
+        maintenance::prefix_index(&server.app),
+        Ok(Some(PrefixIndex {
+            max_term_length: None
+        }))
+    );
+    assert!(
+        plan(&server, PREFIX_QUERY).contains("INDEX idx_term_nocase (term>"),
+        "{}",
+        plan(&server, PREFIX_QUERY)
+    );
+    assert_eq!(prefix_matches(&server, PREFIX_QUERY), ["食べる", "食べ物"]);
+
+    let (_, stats) = handlers::storage_stats_handler(State(server.clone())).await;
+    assert_eq!(
+        stats["prefix_index"]["max_term_length"],
+        serde_json::Value::Null
+    );
+}
+
+#[tokio::test]
+async fn a_length_limit_makes_a_partial_index() {
+    let server = server("prefix-index-partial");
+    build(&server, Some(3)).await;
+    assert_eq!(
+        maintenance::prefix_index(&server.app),
+        Ok(Some(PrefixIndex {
+            max_term_length: Some(3)
+        }))
+    );
+
+    let limited = format!("{PREFIX_QUERY} AND length(term) <= 3");
+    assert!(
+        plan(&server, &limited).contains("INDEX idx_term_nocase (term>"),
+        "{}",
+        plan(&server, &limited)
+    );
+    assert_eq!(prefix_matches(&server, &limited), ["食べる", "食べ物"]);
+
+    // Rebuilding replaces the limit
+    build(&server, Some(2)).await;
+    assert_eq!(
+        maintenance::prefix_index(&server.app),
+        Ok(Some(PrefixIndex {
+            max_term_length: Some(2)
+        }))
+    );
+}
+
+#[tokio::test]
+async fn dropping_and_rejecting_a_zero_limit() {
+    let server = server("prefix-index-drop");
+    let (status, _) = handlers::build_prefix_index_handler(
+        State(server.clone()),
+        Query(PrefixIndexParams {
+            max_term_length: Some(0),
+        }),
+    )
+    .await;
+    assert_eq!(status, 400);
+
+    build(&server, None).await;
+    let (_, dropped) = handlers::drop_prefix_index_handler(State(server.clone())).await;
+    assert_eq!(dropped["dropped"], true);
+    assert_eq!(maintenance::prefix_index(&server.app), Ok(None));
+    let (_, again) = handlers::drop_prefix_index_handler(State(server.clone())).await;
+    assert_eq!(again["dropped"], false);
+}
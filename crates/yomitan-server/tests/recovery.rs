@@ -0,0 +1,49 @@
+mod fixtures;
+
+use std::time::Duration;
+
+use fixtures::ScratchDir;
+use mangatan_yomitan_server::state::AppState;
+
+#[test]
+fn damaged_database_with_a_leftover_journal_is_moved_aside() {
+    let scratch = ScratchDir::new("recovery");
+    let dir = scratch.path();
+    std::fs::write(dir.join("yomitan.db"), b"not a database, cut off mid-write").expect("db");
+    std::fs::write(dir.join("yomitan.db-journal"), b"").expect("journal");
+
+    let state = AppState::new(dir.to_path_buf()).expect("state");
+    assert!(state.dictionaries.read().expect("lock").is_empty());
+    assert!(!dir.join("yomitan.db-journal").exists());
+
+    let kept: Vec<String> = std::fs::read_dir(dir)
+        .expect("read dir")
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with("yomitan.db.malformed-"))
+        .collect();
+    assert_eq!(kept.len(), 1, "{kept:?}");
+}
+
+#[test]
+fn flush_waits_for_a_running_write() {
+    let state = fixtures::scratch("recovery-flush", |dir| AppState::new(dir).expect("state"));
+    assert!(state.flush_for_shutdown(Duration::ZERO));
+
+    let _import = state.import_lock.lock().expect("lock");
+    assert!(!state.flush_for_shutdown(Duration::from_millis(100)));
+}
+
+#[test]
+fn an_unwritable_data_dir_is_an_error_not_a_crash() {
+    // Can't be created, even by root: its parent is a file
+    let scratch = ScratchDir::new("recovery-unwritable");
+    let file = scratch.path().join("not-a-dir");
+    std::fs::write(&file, b"").expect("file");
+    let dir = file.join("data");
+
+    let Err(err) = AppState::new(dir.clone()) else {
+        panic!("opened a state in an unwritable dir");
+    };
+    assert_eq!(err.path, dir);
+}
@@ -0,0 +1,110 @@
+mod fixtures;
+
+use axum::extract::{Query, State};
+use fixtures::Scratch;
+use mangatan_yomitan_server::{
+    ServerState,
+    handlers::{self, LookupParams},
+    import,
+    lookup::{LookupService, ScanMode},
+    state::StateConfig,
+};
+use serde_json::Value;
+
+fn server(name: &str) -> Scratch<ServerState> {
+    let server = fixtures::scratch(name, |dir| ServerState {
+        lookup: LookupService::default()
+            .with_max_deinflection_depth(0)
+            .into(),
+        ..ServerState::with_config(dir, StateConfig::default()).expect("state")
+    });
+    import::import_zip(&server.app, &fixtures::bilingual()).expect("import");
+    server
+}
+
+async fn lookup(server: &ServerState, text: &str, index: usize, scan_mode: ScanMode) -> Vec<Value> {
+    let params = LookupParams {
+        text: text.to_string(),
+        index: Some(index),
+        dictionaries: None,
+        dictionary_ids: None,
+        scan_mode: Some(scan_mode),
+    };
+    let response = handlers::lookup_handler(State(server.clone()), Query(params))
+        .await
+        .expect("lookup");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    serde_json::from_slice(&body).expect("json")
+}
+
+fn headwords(results: &[Value]) -> Vec<&str> {
+    results
+        .iter()
+        .map(|result| result["headword"].as_str().expect("headword"))
+        .collect()
+}
+
+#[tokio::test]
+async fn tapping_the_end_of_a_word_finds_all_of_it() {
+    let server = server("scan-mode-end");
+    // The cursor on 物, three bytes per character
+    assert!(
+        lookup(&server, "食べ物を", 6, ScanMode::Forward)
+            .await
+            .is_empty()
+    );
+
+    let results = lookup(&server, "食べ物を", 6, ScanMode::Around).await;
+    assert_eq!(headwords(&results), ["食べ物"]);
+    assert_eq!(results[0]["matchLen"], 3);
+    assert_eq!(results[0]["span"]["start"], 0);
+    assert_eq!(results[0]["span"]["end"], 3);
+}
+
+#[tokio::test]
+async fn matches_have_to_cover_the_cursor() {
+    let server = server("scan-mode-cover");
+    // On を, which no word before it reaches
+    assert!(
+        lookup(&server, "食べ物を", 9, ScanMode::Around)
+            .await
+            .is_empty()
+    );
+
+    // On 心: 食べ物 ends just before it
+    let results = lookup(&server, "食べ物心", 9, ScanMode::Around).await;
+    assert_eq!(headwords(&results), ["心"]);
+    assert_eq!(results[0]["span"]["start"], 3);
+    assert_eq!(results[0]["span"]["end"], 4);
+}
+
+#[tokio::test]
+async fn awkward_cursors_are_settled_sensibly() {
+    let server = server("scan-mode-cursor");
+    // At the start there's nothing before to scan
+    let results = lookup(&server, "心を", 0, ScanMode::Around).await;
+    assert_eq!(headwords(&results), ["心"]);
+    // At the end, the selection ended on the last character
+    let results = lookup(&server, "食べ物", 9, ScanMode::Around).await;
+    assert_eq!(headwords(&results), ["食べ物"]);
+    // Inside べ's bytes counts as on べ
+    let results = lookup(&server, "食べ物", 4, ScanMode::Around).await;
+    assert_eq!(headwords(&results), ["食べ物"]);
+    assert!(lookup(&server, "", 0, ScanMode::Around).await.is_empty());
+}
+
+#[test]
+fn around_spans_are_relative_to_the_text() {
+    let server = server("scan-mode-spans");
+    let lookup = server.lookup.get().expect("ready");
+    let results = lookup.search_around(&server.app, "を食べ物", 9, None, false);
+    let first = results.first().expect("a match");
+    assert_eq!((first.span_chars.start, first.span_chars.end), (1, 4));
+    assert_eq!((first.span_bytes.start, first.span_bytes.end), (3, 12));
+    // Forward spans stay relative to the cursor
+    let results = lookup.search(&server.app, "を食べ物", 3);
+    let first = results.first().expect("a match");
+    assert_eq!((first.span_chars.start, first.span_chars.end), (0, 3));
+}
@@ -0,0 +1,26 @@
+mod fixtures;
+
+use axum::extract::State;
+use mangatan_yomitan_server::{ServerState, handlers, import, state::StateConfig};
+
+#[tokio::test]
+async fn reports_database_size_and_terms() {
+    let server = fixtures::scratch("storage", |dir| {
+        ServerState::with_config(dir, StateConfig::default()).expect("state")
+    });
+    let (status, empty) = handlers::storage_stats_handler(State(server.clone())).await;
+    assert_eq!(status, 200);
+    assert_eq!(empty["term_count"], 0);
+    assert_eq!(empty["dictionary_count"], 0);
+
+    import::import_zip(&server.app, &fixtures::bilingual()).expect("import");
+    let (status, stats) = handlers::storage_stats_handler(State(server.clone())).await;
+    assert_eq!(status, 200);
+    // Eight entries have a reading of their own, so they're stored twice
+    assert_eq!(stats["term_count"], 17);
+    assert_eq!(stats["dictionary_count"], 1);
+    assert_eq!(
+        stats["db_bytes"],
+        std::fs::metadata(server.app.db_path()).expect("db").len()
+    );
+}
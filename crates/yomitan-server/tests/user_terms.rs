@@ -0,0 +1,240 @@
+mod fixtures;
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use fixtures::Scratch;
+use mangatan_yomitan_server::{
+    ServerState,
+    handlers::{self, LookupParams},
+    import,
+    lookup::LookupService,
+    state::StateConfig,
+    user_dict::{USER_DICTIONARY_ID, USER_DICTIONARY_NAME},
+};
+use serde_json::{Value, json};
+
+fn server(name: &str) -> Scratch<ServerState> {
+    let server = fixtures::scratch(name, |dir| ServerState {
+        lookup: LookupService::default()
+            .with_max_deinflection_depth(0)
+            .into(),
+        ..ServerState::with_config(dir, StateConfig::default()).expect("state")
+    });
+    import::import_zip(&server.app, &fixtures::bilingual()).expect("import");
+    server
+}
+
+async fn add(server: &ServerState, term: Value) -> (StatusCode, Value) {
+    let term = serde_json::from_value(term).expect("term");
+    let (status, Json(body)) =
+        handlers::add_user_term_handler(State(server.clone()), Json(term)).await;
+    (status, body)
+}
+
+async fn update(server: &ServerState, id: i64, patch: Value) -> (StatusCode, Value) {
+    let patch = serde_json::from_value(patch).expect("patch");
+    let (status, Json(body)) =
+        handlers::update_user_term_handler(State(server.clone()), Path(id), Json(patch)).await;
+    (status, body)
+}
+
+/// The user dictionary's definitions in the lookup results for `text`.
+async fn user_definitions(server: &ServerState, text: &str) -> Vec<Value> {
+    let params = LookupParams {
+        text: text.to_string(),
+        index: Some(0),
+        dictionaries: None,
+        dictionary_ids: None,
+        scan_mode: None,
+    };
+    let response = handlers::lookup_handler(State(server.clone()), Query(params))
+        .await
+        .expect("lookup");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    let results: Vec<Value> = serde_json::from_slice(&body).expect("json");
+    results
+        .iter()
+        .flat_map(|result| {
+            result["definitions"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default()
+        })
+        .filter(|definition| definition["dictionaryName"] == USER_DICTIONARY_NAME)
+        .collect()
+}
+
+#[tokio::test]
+async fn user_terms_are_found_edited_and_deleted_by_their_id() {
+    let server = server("user-terms-crud");
+    let (status, body) = add(
+        &server,
+        json!({
+            "headword": "推し",
+            "reading": "おし",
+            "glossary": "favourite idol\none's fave",
+            "tags": ["slang"]
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let id = body["term"]["id"].as_i64().expect("id");
+    {
+        let dicts = server.app.dictionaries.read().expect("lock");
+        let dict = &dicts[&USER_DICTIONARY_ID];
+        assert!(dict.enabled);
+        assert!(dicts.values().all(|other| other.priority >= dict.priority));
+    }
+
+    let definitions = user_definitions(&server, "推し").await;
+    assert_eq!(definitions.len(), 1);
+    assert_eq!(definitions[0]["userTermId"], id);
+    assert_eq!(
+        definitions[0]["content"],
+        json!(["favourite idol", "one's fave"])
+    );
+    assert_eq!(definitions[0]["tags"], json!(["slang"]));
+    // Found by its reading too, under the reading row's id, which edits the same entry
+    let by_reading = user_definitions(&server, "おし").await;
+    let reading_id = by_reading[0]["userTermId"].as_i64().expect("id");
+
+    let (status, body) = update(&server, reading_id, json!({ "glossary": "bias" })).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["term"]["id"], id);
+    assert_eq!(body["term"]["headword"], "推し");
+    assert_eq!(
+        user_definitions(&server, "推し").await[0]["content"],
+        json!(["bias"])
+    );
+
+    let (status, _) = handlers::delete_user_term_handler(State(server.clone()), Path(id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(user_definitions(&server, "推し").await.is_empty());
+    assert!(user_definitions(&server, "おし").await.is_empty());
+    assert_eq!(
+        update(&server, id, json!({ "glossary": "gone" })).await.0,
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn imported_definitions_have_no_user_term_id() {
+    let server = server("user-terms-imported");
+    add(
+        &server,
+        json!({ "headword": "食べ物", "reading": "たべもの", "glossary": "my own note" }),
+    )
+    .await;
+    let params = LookupParams {
+        text: "食べ物".to_string(),
+        index: Some(0),
+        dictionaries: None,
+        dictionary_ids: None,
+        scan_mode: None,
+    };
+    let response = handlers::lookup_handler(State(server.clone()), Query(params))
+        .await
+        .expect("lookup");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    let results: Vec<Value> = serde_json::from_slice(&body).expect("json");
+    let definitions = results[0]["definitions"].as_array().expect("definitions");
+    // Ahead of the imported dictionary, with only its entry editable
+    assert_eq!(definitions[0]["dictionaryName"], USER_DICTIONARY_NAME);
+    assert!(definitions[0]["userTermId"].is_i64());
+    assert!(
+        definitions[1..]
+            .iter()
+            .all(|definition| definition.get("userTermId").is_none())
+    );
+}
+
+#[tokio::test]
+async fn bad_and_repeated_entries_are_refused() {
+    let server = server("user-terms-refused");
+    let term = json!({ "headword": "草", "reading": "くさ", "glossary": "lol" });
+    assert_eq!(add(&server, term.clone()).await.0, StatusCode::OK);
+    assert_eq!(add(&server, term).await.0, StatusCode::CONFLICT);
+    assert_eq!(
+        add(&server, json!({ "headword": " ", "glossary": "nothing" }))
+            .await
+            .0,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        add(&server, json!({ "headword": "草", "glossary": "" }))
+            .await
+            .0,
+        StatusCode::BAD_REQUEST
+    );
+    // Another headword with the same reading and glossary is its own entry
+    assert_eq!(
+        add(
+            &server,
+            json!({ "headword": "艸", "reading": "くさ", "glossary": "lol" })
+        )
+        .await
+        .0,
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn the_user_dictionary_cannot_be_deleted_or_disabled() {
+    let server = server("user-terms-protected");
+    add(&server, json!({ "headword": "草", "glossary": "lol" })).await;
+
+    for action in [
+        json!({ "action": "Delete", "payload": { "id": USER_DICTIONARY_ID.0 } }),
+        json!({ "action": "Toggle", "payload": { "id": USER_DICTIONARY_ID.0, "enabled": false } }),
+    ] {
+        let action = serde_json::from_value(action).expect("action");
+        let Json(body) =
+            handlers::manage_dictionaries_handler(State(server.clone()), Json(action)).await;
+        assert_eq!(body["status"], "error");
+    }
+    let dicts = server.app.dictionaries.read().expect("lock");
+    assert!(dicts[&USER_DICTIONARY_ID].enabled);
+}
+
+#[tokio::test]
+async fn exports_carry_user_terms_to_another_install() {
+    let source = server("user-terms-export");
+    add(
+        &source,
+        json!({ "headword": "推し", "reading": "おし", "glossary": "fave", "tags": ["slang"] }),
+    )
+    .await;
+    add(&source, json!({ "headword": "草", "glossary": "lol" })).await;
+
+    let Json(snapshot) = handlers::config_export_handler(State(source.clone()))
+        .await
+        .expect("export");
+    let snapshot = serde_json::to_value(&snapshot).expect("snapshot");
+    assert_eq!(
+        snapshot["user_terms"],
+        json!([
+            { "headword": "推し", "reading": "おし", "glossary": "fave", "tags": ["slang"] },
+            { "headword": "草", "reading": "", "glossary": "lol", "tags": [] }
+        ])
+    );
+
+    let target = server("user-terms-import");
+    for expected in [2, 0] {
+        let snapshot = serde_json::from_value(snapshot.clone()).expect("snapshot");
+        let (status, Json(body)) =
+            handlers::config_import_handler(State(target.clone()), Json(snapshot)).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["user_terms_added"], expected);
+    }
+    assert_eq!(
+        user_definitions(&target, "草").await[0]["content"],
+        json!(["lol"])
+    );
+}
@@ -0,0 +1,88 @@
+mod fixtures;
+
+use axum::extract::{Query, State};
+use fixtures::Scratch;
+use mangatan_yomitan_server::{
+    ServerState,
+    handlers::{self, LookupParams},
+    import,
+    lookup::LookupService,
+    state::StateConfig,
+};
+use serde_json::Value;
+
+fn server(name: &str) -> Scratch<ServerState> {
+    let server = fixtures::scratch(name, |dir| {
+        ServerState::with_config(dir, StateConfig::default()).expect("state")
+    });
+    import::import_zip(&server.app, &fixtures::variants()).expect("import");
+    server
+}
+
+/// `(headword, matchLen)` of each result for `text`.
+async fn lookup(server: &ServerState, text: &str) -> Vec<(String, u64)> {
+    let params = LookupParams {
+        text: text.to_string(),
+        index: Some(0),
+        dictionaries: None,
+        dictionary_ids: None,
+        scan_mode: None,
+    };
+    let response = handlers::lookup_handler(State(server.clone()), Query(params))
+        .await
+        .expect("lookup");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    let results: Vec<Value> = serde_json::from_slice(&body).expect("json");
+    results
+        .iter()
+        .map(|result| {
+            (
+                result["headword"].as_str().expect("headword").to_string(),
+                result["matchLen"].as_u64().expect("matchLen"),
+            )
+        })
+        .collect()
+}
+
+/// `(span_chars.end, span_bytes.end)` of the longest match for `text`.
+fn spans(server: &ServerState, text: &str) -> (u64, u64) {
+    let results = LookupService::new().search(&server.app, text, 0);
+    let first = results.first().expect("a match");
+    (first.span_chars.end, first.span_bytes.end)
+}
+
+#[tokio::test]
+async fn variation_selectors_are_ignored_on_both_sides() {
+    let server = server("variants-selector");
+    // Stored without the selector, so the plain spelling finds it
+    assert_eq!(lookup(&server, "葛城に").await, [("葛城".to_string(), 2)]);
+    // The selector in the text goes with 葛 and counts towards the match
+    assert_eq!(
+        lookup(&server, "葛\u{E0100}城に").await,
+        [("葛城".to_string(), 3)]
+    );
+    assert_eq!(spans(&server, "葛\u{E0100}城に"), (3, 10));
+}
+
+#[tokio::test]
+async fn compatibility_ideographs_match_their_unified_form() {
+    let server = server("variants-compat");
+    assert_eq!(lookup(&server, "神社へ").await, [("神社".to_string(), 2)]);
+    assert_eq!(
+        lookup(&server, "\u{FA19}社へ").await,
+        [("神社".to_string(), 2)]
+    );
+    assert_eq!(spans(&server, "\u{FA19}社へ"), (2, 6));
+}
+
+#[tokio::test]
+async fn spans_count_characters_outside_the_bmp_once() {
+    let server = server("variants-astral");
+    // Like `Array.from(text).length` on the WebUI's side, not UTF-16 units
+    assert_eq!(lookup(&server, "𠮟る").await, [("𠮟る".to_string(), 2)]);
+    assert_eq!(spans(&server, "𠮟る"), (2, 7));
+    // Text made only of such ideographs isn't skipped as non-Japanese
+    assert_eq!(spans(&server, "𠮟るな"), (2, 7));
+}
@@ -0,0 +1,75 @@
+mod fixtures;
+
+use mangatan_tokenize::Token;
+use mangatan_yomitan_server::{import, state::AppState, vocab};
+use serde_json::json;
+
+fn token(text: &str, lemma: Option<&str>, pos: &str) -> Token {
+    Token {
+        text: text.to_string(),
+        start: 0,
+        end: text.chars().count(),
+        byte_range: 0..text.len(),
+        lemma: lemma.map(str::to_string),
+        reading: None,
+        part_of_speech: Some(pos.to_string()),
+    }
+}
+
+#[test]
+fn lemmas_are_counted_once_per_occurrence_without_punctuation() {
+    let occurrences = vocab::count_lemmas([
+        token("食べ", Some("食べる"), "動詞"),
+        token("た", Some("た"), "助動詞"),
+        token("。", Some("。"), "補助記号"),
+        token("食べる", Some("食べる"), "動詞"),
+        token("パン", Some("パン-pão"), "名詞"),
+    ]);
+
+    let counts: Vec<(&str, &str, usize)> = occurrences
+        .iter()
+        .map(|o| (o.lemma.as_str(), o.surface.as_str(), o.count))
+        .collect();
+    assert_eq!(
+        counts,
+        [("食べる", "食べ", 2), ("た", "た", 1), ("パン", "パン", 1)]
+    );
+}
+
+#[test]
+fn chapters_split_after_sentence_endings() {
+    assert_eq!(
+        vocab::split_sentences("猫だ。本当？\n  \nはい！"),
+        ["猫だ。", "本当？", "はい！"]
+    );
+}
+
+#[test]
+fn lookups_report_coverage_and_frequency_of_enabled_dictionaries() {
+    let state = fixtures::scratch("vocab", |dir| AppState::new(dir).expect("state"));
+    import::import_zip(
+        &state,
+        &fixtures::dictionary_zip(
+            fixtures::bare_index("Mini JMdict"),
+            json!([
+                ["食べる", "たべる", "", "v1", 120, ["to eat"], 1, ""],
+                ["猫", "ねこ", "", "", 0, ["cat"], 2, ""],
+            ]),
+        ),
+    )
+    .expect("import");
+
+    let lemmas = ["食べる", "猫", "犬"].map(str::to_string);
+    let found = vocab::lookup_lemmas(&state, &lemmas, None);
+    assert!(found["食べる"].in_dictionary);
+    assert_eq!(found["食べる"].frequency, Some(120));
+    assert!(found["猫"].in_dictionary);
+    assert_eq!(found["猫"].frequency, None);
+    assert!(!found["犬"].in_dictionary);
+
+    for dict in state.dictionaries.write().expect("lock").values_mut() {
+        dict.enabled = false;
+    }
+    let found = vocab::lookup_lemmas(&state, &lemmas, None);
+    assert!(found.values().all(|coverage| !coverage.in_dictionary));
+}